@@ -0,0 +1,137 @@
+//! Benchmarks of the consolidation path
+//!
+//! Run with `cargo bench --bench consolidation`, or through
+//! `cargo run --bin test_runner -- --bench-profile <baseline>` to compare a
+//! change against a saved baseline.
+
+use std::io::{Cursor, Read};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use parquet_consolidator::test_utils::{create_random_parquet_file, create_test_parquet_file};
+use parquet_consolidator::{consolidate_readers, consolidate_with_options, ConsolidateOptions, InputFormat, ReaderInput};
+use tempfile::TempDir;
+
+/// Latency added to every read of a simulated remote input
+const REMOTE_READ_LATENCY: Duration = Duration::from_millis(1);
+
+/// Largest read a simulated remote input answers at once, like one chunk
+/// of an HTTP response body
+const REMOTE_CHUNK_BYTES: usize = 64 * 1024;
+
+/// A reader that answers in small chunks after a delay, standing in for an
+/// object store or HTTP download
+struct RemoteReader {
+    inner: Cursor<Vec<u8>>,
+}
+
+impl Read for RemoteReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        thread::sleep(REMOTE_READ_LATENCY);
+        let len = buf.len().min(REMOTE_CHUNK_BYTES);
+        self.inner.read(&mut buf[..len])
+    }
+}
+
+fn wide_schema(columns: usize) -> SchemaRef {
+    let fields: Vec<Field> = (0..columns)
+        .map(|i| {
+            let data_type = [DataType::Int64, DataType::Float64, DataType::Utf8][i % 3].clone();
+            Field::new(format!("c{}", i), data_type, true)
+        })
+        .collect();
+    Arc::new(Schema::new(fields))
+}
+
+fn total_bytes(files: &[PathBuf]) -> u64 {
+    files.iter().map(|file| std::fs::metadata(file).unwrap().len()).sum()
+}
+
+fn bench_files(c: &mut Criterion, name: &str, dir: &Path, files: &[PathBuf], options: &ConsolidateOptions) {
+    let output = dir.join(format!("{}.out.parquet", name));
+    let mut group = c.benchmark_group(name);
+    group.sample_size(10).throughput(Throughput::Bytes(total_bytes(files)));
+    group.bench_function("consolidate", |b| {
+        b.iter(|| consolidate_with_options(files, &output, options).unwrap())
+    });
+    group.finish();
+}
+
+fn small_many_files(c: &mut Criterion) {
+    let temp_dir = TempDir::new().unwrap();
+    let files: Vec<PathBuf> = (0..500)
+        .map(|i| {
+            let file = temp_dir.path().join(format!("part-{:05}.parquet", i));
+            create_test_parquet_file(&file, i * 100, i * 100 + 100).unwrap();
+            file
+        })
+        .collect();
+    bench_files(c, "small_many_files", temp_dir.path(), &files, &ConsolidateOptions::default());
+}
+
+fn few_huge_files(c: &mut Criterion) {
+    let temp_dir = TempDir::new().unwrap();
+    let files: Vec<PathBuf> = (0..3)
+        .map(|i| {
+            let file = temp_dir.path().join(format!("huge-{}.parquet", i));
+            create_test_parquet_file(&file, i * 1_000_000, i * 1_000_000 + 1_000_000).unwrap();
+            file
+        })
+        .collect();
+    bench_files(c, "few_huge_files", temp_dir.path(), &files, &ConsolidateOptions::default());
+
+    let options = ConsolidateOptions { preserve_row_groups: true, ..Default::default() };
+    bench_files(c, "few_huge_files_preserve_row_groups", temp_dir.path(), &files, &options);
+}
+
+fn wide_table(c: &mut Criterion) {
+    let temp_dir = TempDir::new().unwrap();
+    let schema = wide_schema(300);
+    let files: Vec<PathBuf> = (0..4)
+        .map(|i| {
+            let file = temp_dir.path().join(format!("wide-{}.parquet", i));
+            create_random_parquet_file(&file, &schema, 5_000, 0.1, i).unwrap();
+            file
+        })
+        .collect();
+    bench_files(c, "wide_table", temp_dir.path(), &files, &ConsolidateOptions::default());
+}
+
+fn remote_simulated(c: &mut Criterion) {
+    let temp_dir = TempDir::new().unwrap();
+    let schema = wide_schema(12);
+    let inputs: Vec<Vec<u8>> = (0..8)
+        .map(|i| {
+            let file = temp_dir.path().join(format!("remote-{}.parquet", i));
+            create_random_parquet_file(&file, &schema, 20_000, 0.05, i).unwrap();
+            std::fs::read(&file).unwrap()
+        })
+        .collect();
+    let output = temp_dir.path().join("remote.out.parquet");
+
+    let mut group = c.benchmark_group("remote_simulated");
+    group.sample_size(10).throughput(Throughput::Bytes(inputs.iter().map(|input| input.len() as u64).sum()));
+    group.bench_function("consolidate_readers", |b| {
+        b.iter_batched(
+            || {
+                inputs
+                    .iter()
+                    .enumerate()
+                    .map(|(i, input)| {
+                        let reader = RemoteReader { inner: Cursor::new(input.clone()) };
+                        ReaderInput::new(format!("remote-{}", i), InputFormat::Parquet, reader)
+                    })
+                    .collect::<Vec<_>>()
+            },
+            |readers| consolidate_readers(readers, &output, &ConsolidateOptions::default()).unwrap(),
+            BatchSize::PerIteration,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(benches, small_many_files, few_huge_files, wide_table, remote_simulated);
+criterion_main!(benches);
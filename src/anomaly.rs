@@ -0,0 +1,277 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use anyhow::Result;
+use parquet::file::reader::{FileReader, SerializedFileReader};
+use parquet::file::statistics::Statistics;
+use crate::formats::InputFormat;
+
+/// Fewest files with statistics for a column before its distribution is
+/// trusted to single out outliers
+const MIN_FILES: usize = 3;
+
+/// Smallest gap between a file's null rate and the rest of the dataset's
+/// that is reported
+const NULL_RATE_GAP: f64 = 0.5;
+
+/// How many typical ranges a file's values may lie beyond the typical
+/// range before they are reported
+const RANGE_FACTOR: f64 = 10.0;
+
+/// Footer statistics of one top-level column in one input file
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileColumnStats {
+    pub rows: u64,
+    /// Missing when a row group has no null count
+    pub nulls: Option<u64>,
+    /// Missing for non-numeric columns and when a row group has no bounds
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+}
+
+/// Read the per-column statistics of a parquet file from its footer
+///
+/// Only top-level columns are covered; no data pages are read.
+pub fn file_column_stats(path: &Path) -> Result<BTreeMap<String, FileColumnStats>> {
+    let reader = SerializedFileReader::new(File::open(path)?)?;
+    let metadata = reader.metadata();
+    let rows = metadata.file_metadata().num_rows().max(0) as u64;
+
+    let mut columns = BTreeMap::new();
+    for (index, column) in metadata.file_metadata().schema_descr().columns().iter().enumerate() {
+        if column.path().parts().len() != 1 {
+            continue;
+        }
+
+        let mut stats = FileColumnStats { rows, nulls: Some(0), min: None, max: None };
+        let mut bounds_complete = true;
+        for row_group in metadata.row_groups() {
+            let statistics = row_group.column(index).statistics();
+            stats.nulls = stats.nulls.zip(statistics.and_then(Statistics::null_count_opt)).map(|(a, b)| a + b);
+            match statistics.and_then(numeric_bounds) {
+                Some((min, max)) => {
+                    stats.min = Some(stats.min.map_or(min, |current| current.min(min)));
+                    stats.max = Some(stats.max.map_or(max, |current| current.max(max)));
+                }
+                // A row group of nulls only has no bounds but cannot widen them
+                None if row_group.num_rows() > 0
+                    && statistics.and_then(Statistics::null_count_opt) != Some(row_group.num_rows() as u64) =>
+                {
+                    bounds_complete = false
+                }
+                None => {}
+            }
+        }
+        if !bounds_complete {
+            stats.min = None;
+            stats.max = None;
+        }
+        columns.insert(column.name().to_string(), stats);
+    }
+    Ok(columns)
+}
+
+fn numeric_bounds(statistics: &Statistics) -> Option<(f64, f64)> {
+    match statistics {
+        Statistics::Int32(s) => Some((*s.min_opt()? as f64, *s.max_opt()? as f64)),
+        Statistics::Int64(s) => Some((*s.min_opt()? as f64, *s.max_opt()? as f64)),
+        Statistics::Float(s) => Some((*s.min_opt()? as f64, *s.max_opt()? as f64)),
+        Statistics::Double(s) => Some((*s.min_opt()?, *s.max_opt()?)),
+        _ => None,
+    }
+}
+
+/// How a file stands out from the rest of the dataset
+#[derive(Debug, Clone, PartialEq)]
+pub enum AnomalyKind {
+    /// The file's share of nulls differs sharply from the other files'
+    NullRate { file_rate: f64, others_rate: f64 },
+    /// The file's values lie far outside the range most files share
+    Range { min: f64, max: f64, typical_min: f64, typical_max: f64 },
+}
+
+/// An input file whose statistics for a column are a strong outlier
+#[derive(Debug, Clone, PartialEq)]
+pub struct Anomaly {
+    pub file: PathBuf,
+    pub column: String,
+    pub kind: AnomalyKind,
+}
+
+impl fmt::Display for Anomaly {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            AnomalyKind::NullRate { file_rate, others_rate } => write!(
+                f,
+                "{:?}: column {} is {:.1}% null, against {:.1}% in the other files",
+                self.file,
+                self.column,
+                file_rate * 100.0,
+                others_rate * 100.0
+            ),
+            AnomalyKind::Range { min, max, typical_min, typical_max } => write!(
+                f,
+                "{:?}: column {} ranges from {} to {}, far outside the typical {} to {}",
+                self.file, self.column, min, max, typical_min, typical_max
+            ),
+        }
+    }
+}
+
+/// Find input files whose column statistics are strong outliers
+///
+/// Statistics are read from parquet footers only; other formats and
+/// unreadable footers are skipped. A file is reported when a column's null
+/// rate is at least 50 percentage points away from that of all other files
+/// combined, or when its values reach more than ten typical ranges beyond
+/// the median minimum and maximum of all files. Columns need statistics in
+/// at least three files to be judged.
+pub fn detect_anomalies(input_files: &[PathBuf]) -> Vec<Anomaly> {
+    let files: Vec<(&PathBuf, BTreeMap<String, FileColumnStats>)> = input_files
+        .iter()
+        .filter(|path| InputFormat::from_path(path) == Some(InputFormat::Parquet))
+        .filter_map(|path| Some((path, file_column_stats(path).ok()?)))
+        .collect();
+
+    let mut by_column: BTreeMap<&str, Vec<(&PathBuf, &FileColumnStats)>> = BTreeMap::new();
+    for (path, columns) in &files {
+        for (column, stats) in columns {
+            by_column.entry(column).or_default().push((path, stats));
+        }
+    }
+
+    let mut anomalies = Vec::new();
+    for (column, stats) in by_column {
+        anomalies.extend(null_rate_anomalies(column, &stats));
+        anomalies.extend(range_anomalies(column, &stats));
+    }
+    anomalies.sort_by(|a, b| (&a.file, &a.column).cmp(&(&b.file, &b.column)));
+    anomalies
+}
+
+fn null_rate_anomalies(column: &str, stats: &[(&PathBuf, &FileColumnStats)]) -> Vec<Anomaly> {
+    let counted: Vec<(&PathBuf, u64, u64)> = stats
+        .iter()
+        .filter(|(_, stats)| stats.rows > 0)
+        .filter_map(|(path, stats)| Some((*path, stats.nulls?, stats.rows)))
+        .collect();
+    if counted.len() < MIN_FILES {
+        return Vec::new();
+    }
+
+    let total_nulls: u64 = counted.iter().map(|(_, nulls, _)| nulls).sum();
+    let total_rows: u64 = counted.iter().map(|(_, _, rows)| rows).sum();
+    counted
+        .iter()
+        .filter_map(|(path, nulls, rows)| {
+            let file_rate = *nulls as f64 / *rows as f64;
+            let others_rate = (total_nulls - nulls) as f64 / (total_rows - rows) as f64;
+            ((file_rate - others_rate).abs() >= NULL_RATE_GAP).then(|| Anomaly {
+                file: (*path).clone(),
+                column: column.to_string(),
+                kind: AnomalyKind::NullRate { file_rate, others_rate },
+            })
+        })
+        .collect()
+}
+
+fn range_anomalies(column: &str, stats: &[(&PathBuf, &FileColumnStats)]) -> Vec<Anomaly> {
+    let bounded: Vec<(&PathBuf, f64, f64)> = stats
+        .iter()
+        .filter_map(|(path, stats)| Some((*path, stats.min?, stats.max?)))
+        .collect();
+    if bounded.len() < MIN_FILES {
+        return Vec::new();
+    }
+
+    let typical_min = median(bounded.iter().map(|(_, min, _)| *min).collect());
+    let typical_max = median(bounded.iter().map(|(_, _, max)| *max).collect());
+    // Constant columns have no spread, so fall back to a tenth of their magnitude
+    let spread = (typical_max - typical_min).max(typical_min.abs().max(typical_max.abs()) / 10.0);
+    if spread <= 0.0 || !spread.is_finite() {
+        return Vec::new();
+    }
+
+    bounded
+        .iter()
+        .filter(|(_, min, max)| {
+            *min < typical_min - RANGE_FACTOR * spread || *max > typical_max + RANGE_FACTOR * spread
+        })
+        .map(|(path, min, max)| Anomaly {
+            file: (*path).clone(),
+            column: column.to_string(),
+            kind: AnomalyKind::Range { min: *min, max: *max, typical_min, typical_max },
+        })
+        .collect()
+}
+
+fn median(mut values: Vec<f64>) -> f64 {
+    values.sort_by(f64::total_cmp);
+    let middle = values.len() / 2;
+    if values.len().is_multiple_of(2) {
+        (values[middle - 1] + values[middle]) / 2.0
+    } else {
+        values[middle]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use arrow::array::{Float64Array, Int64Array, RecordBatch};
+    use arrow::datatypes::{DataType, Field, Schema};
+    use parquet::arrow::ArrowWriter;
+
+    fn write_file(path: &Path, values: Vec<Option<f64>>) -> Result<()> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("value", DataType::Float64, true),
+        ]));
+        let ids = Int64Array::from_iter_values(0..values.len() as i64);
+        let batch = RecordBatch::try_new(schema.clone(), vec![Arc::new(ids), Arc::new(Float64Array::from(values))])?;
+        let mut writer = ArrowWriter::try_new(File::create(path)?, schema, None)?;
+        writer.write(&batch)?;
+        writer.close()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_detect_anomalies() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let files: Vec<PathBuf> = (0..5).map(|i| temp_dir.path().join(format!("{}.parquet", i))).collect();
+        for file in &files[..3] {
+            write_file(file, (0..10).map(|i| Some(i as f64)).collect())?;
+        }
+        write_file(&files[3], vec![None; 10])?;
+        write_file(&files[4], vec![Some(1.0), Some(5_000.0), None])?;
+
+        let stats = file_column_stats(&files[4])?;
+        assert_eq!(stats["value"], FileColumnStats { rows: 3, nulls: Some(1), min: Some(1.0), max: Some(5_000.0) });
+        assert_eq!(stats["id"].max, Some(2.0));
+
+        let anomalies = detect_anomalies(&files);
+        assert_eq!(
+            anomalies,
+            vec![
+                Anomaly {
+                    file: files[3].clone(),
+                    column: "value".to_string(),
+                    kind: AnomalyKind::NullRate { file_rate: 1.0, others_rate: 1.0 / 33.0 },
+                },
+                Anomaly {
+                    file: files[4].clone(),
+                    column: "value".to_string(),
+                    kind: AnomalyKind::Range { min: 1.0, max: 5_000.0, typical_min: 0.0, typical_max: 9.0 },
+                },
+            ]
+        );
+        assert!(anomalies[0].to_string().contains("is 100.0% null, against 3.0% in the other files"));
+
+        // Too few files to tell what is typical
+        assert!(detect_anomalies(&[files[0].clone(), files[3].clone()]).is_empty());
+
+        Ok(())
+    }
+}
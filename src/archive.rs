@@ -0,0 +1,267 @@
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+use anyhow::{Result, Context};
+use flate2::read::GzDecoder;
+
+/// Archive formats whose members can be read as input files
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    Zip,
+    Tar,
+    TarGz,
+}
+
+impl ArchiveFormat {
+    /// Detect the archive format of a file from its name
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use parquet_consolidator::ArchiveFormat;
+    /// use std::path::Path;
+    ///
+    /// assert_eq!(ArchiveFormat::from_path(Path::new("daily.ZIP")), Some(ArchiveFormat::Zip));
+    /// assert_eq!(ArchiveFormat::from_path(Path::new("daily.tar.gz")), Some(ArchiveFormat::TarGz));
+    /// assert_eq!(ArchiveFormat::from_path(Path::new("daily.parquet")), None);
+    /// ```
+    pub fn from_path(path: &Path) -> Option<ArchiveFormat> {
+        let name = path.file_name()?.to_str()?.to_lowercase();
+        if name.ends_with(".zip") {
+            Some(ArchiveFormat::Zip)
+        } else if name.ends_with(".tar") {
+            Some(ArchiveFormat::Tar)
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(ArchiveFormat::TarGz)
+        } else {
+            None
+        }
+    }
+}
+
+/// Paths of the files inside the archive at `archive`, in archive order
+///
+/// Each member is named by joining its path inside the archive onto the
+/// archive's path, e.g. `daily.zip/part-0.parquet`, which is how the rest
+/// of the pipeline refers to it. Directory entries are left out.
+pub fn archive_members(archive: &Path) -> Result<Vec<PathBuf>> {
+    let format = ArchiveFormat::from_path(archive)
+        .ok_or_else(|| anyhow::anyhow!("Not a zip or tar archive: {:?}", archive))?;
+    let file = File::open(archive).context(format!("Failed to open archive {:?}", archive))?;
+
+    let mut names = Vec::new();
+    match format {
+        ArchiveFormat::Zip => {
+            let zip = zip::ZipArchive::new(BufReader::new(file))
+                .context(format!("Failed to read zip archive {:?}", archive))?;
+            names.extend(zip.file_names().filter(|name| !name.ends_with('/')).map(str::to_string));
+        }
+        ArchiveFormat::Tar => for_each_tar_file(BufReader::new(file), |name, _| {
+            names.push(name);
+            Ok(false)
+        })
+        .context(format!("Failed to read tar archive {:?}", archive))?,
+        ArchiveFormat::TarGz => for_each_tar_file(GzDecoder::new(BufReader::new(file)), |name, _| {
+            names.push(name);
+            Ok(false)
+        })
+        .context(format!("Failed to read tar archive {:?}", archive))?,
+    }
+    Ok(names.into_iter().map(|name| archive.join(name)).collect())
+}
+
+/// Split a member path produced by [`archive_members`] into the archive and
+/// the member's name inside it
+///
+/// Returns `None` for paths that exist on disk or don't lie inside an
+/// archive file.
+pub fn split_archive_path(path: &Path) -> Option<(&Path, String)> {
+    if path.exists() {
+        return None;
+    }
+    let archive = path
+        .ancestors()
+        .skip(1)
+        .find(|ancestor| ArchiveFormat::from_path(ancestor).is_some() && ancestor.is_file())?;
+    let member = path.strip_prefix(archive).ok()?;
+    let parts: Vec<String> = member.iter().map(|part| part.to_string_lossy().into_owned()).collect();
+    Some((archive, parts.join("/")))
+}
+
+/// Read the whole member `name` of the archive at `archive` into memory
+pub fn read_archive_member(archive: &Path, name: &str) -> Result<Vec<u8>> {
+    let format = ArchiveFormat::from_path(archive)
+        .ok_or_else(|| anyhow::anyhow!("Not a zip or tar archive: {:?}", archive))?;
+    let file = File::open(archive).context(format!("Failed to open archive {:?}", archive))?;
+    let context = || format!("Failed to read {} from archive {:?}", name, archive);
+
+    let mut data = None;
+    let mut take_member = |member: String, reader: &mut dyn Read| -> Result<bool> {
+        if member != name {
+            return Ok(false);
+        }
+        let mut contents = Vec::new();
+        reader.read_to_end(&mut contents)?;
+        data = Some(contents);
+        Ok(true)
+    };
+    match format {
+        ArchiveFormat::Zip => {
+            let mut zip = zip::ZipArchive::new(BufReader::new(file)).with_context(context)?;
+            let mut member = zip.by_name(name).with_context(context)?;
+            take_member(name.to_string(), &mut member).with_context(context)?;
+        }
+        ArchiveFormat::Tar => for_each_tar_file(BufReader::new(file), take_member).with_context(context)?,
+        ArchiveFormat::TarGz => {
+            for_each_tar_file(GzDecoder::new(BufReader::new(file)), take_member).with_context(context)?
+        }
+    }
+    data.ok_or_else(|| anyhow::anyhow!("Archive {:?} has no member {}", archive, name))
+}
+
+const TAR_BLOCK: usize = 512;
+
+/// Visit the regular files of a ustar or GNU tar stream in order, stopping
+/// once `visit` returns true
+///
+/// GNU long names are followed; links, directories and pax headers are
+/// skipped. `visit` may read the member's contents from the reader it is
+/// given, and whatever it leaves unread is skipped.
+fn for_each_tar_file<R: Read>(
+    mut reader: R,
+    mut visit: impl FnMut(String, &mut dyn Read) -> Result<bool>,
+) -> Result<()> {
+    let mut header = [0u8; TAR_BLOCK];
+    let mut long_name: Option<String> = None;
+    loop {
+        if !read_block(&mut reader, &mut header)? || header.iter().all(|&byte| byte == 0) {
+            return Ok(());
+        }
+        let size = tar_octal(&header[124..136]).context("Invalid size in tar header")?;
+        let padded = size.div_ceil(TAR_BLOCK as u64) * TAR_BLOCK as u64;
+        let mut contents = (&mut reader).take(size);
+
+        let name = match long_name.take() {
+            Some(name) => name,
+            None => {
+                let prefix = tar_string(&header[345..500]);
+                let name = tar_string(&header[..100]);
+                if prefix.is_empty() { name } else { format!("{}/{}", prefix, name) }
+            }
+        };
+        let stop = match header[156] {
+            b'L' => {
+                let mut name = Vec::new();
+                contents.read_to_end(&mut name)?;
+                long_name = Some(tar_string(&name));
+                false
+            }
+            b'0' | 0 if !name.ends_with('/') => visit(name, &mut contents)?,
+            _ => false,
+        };
+        if stop {
+            return Ok(());
+        }
+        std::io::copy(&mut contents, &mut std::io::sink())?;
+        std::io::copy(&mut (&mut reader).take(padded - size), &mut std::io::sink())?;
+    }
+}
+
+/// Fill `block`, returning false at a clean end of the stream
+fn read_block<R: Read>(reader: &mut R, block: &mut [u8; TAR_BLOCK]) -> Result<bool> {
+    let mut filled = 0;
+    while filled < TAR_BLOCK {
+        match reader.read(&mut block[filled..])? {
+            0 if filled == 0 => return Ok(false),
+            0 => anyhow::bail!("Truncated tar header"),
+            read => filled += read,
+        }
+    }
+    Ok(true)
+}
+
+fn tar_string(field: &[u8]) -> String {
+    let end = field.iter().position(|&byte| byte == 0).unwrap_or(field.len());
+    String::from_utf8_lossy(&field[..end]).into_owned()
+}
+
+fn tar_octal(field: &[u8]) -> Result<u64> {
+    let digits = tar_string(field);
+    let digits = digits.trim_matches(|c: char| c == ' ' || c == '\0');
+    if digits.is_empty() {
+        return Ok(0);
+    }
+    Ok(u64::from_str_radix(digits, 8)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use flate2::write::GzEncoder;
+
+    /// A tar stream holding `files`, with a GNU long-name entry for names
+    /// that don't fit the header
+    fn tar_bytes(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut tar = Vec::new();
+        let mut entry = |name: &str, typeflag: u8, contents: &[u8]| {
+            let mut header = [0u8; TAR_BLOCK];
+            header[..name.len().min(100)].copy_from_slice(&name.as_bytes()[..name.len().min(100)]);
+            header[124..135].copy_from_slice(format!("{:011o}", contents.len()).as_bytes());
+            header[156] = typeflag;
+            header[257..263].copy_from_slice(b"ustar\0");
+            tar.extend_from_slice(&header);
+            tar.extend_from_slice(contents);
+            tar.resize(tar.len().div_ceil(TAR_BLOCK) * TAR_BLOCK, 0);
+        };
+        entry("nested/", b'5', b"");
+        for (name, contents) in files {
+            if name.len() > 100 {
+                entry("././@LongLink", b'L', name.as_bytes());
+            }
+            entry(name, b'0', contents);
+        }
+        tar.resize(tar.len() + 2 * TAR_BLOCK, 0);
+        tar
+    }
+
+    #[test]
+    fn test_archive_members() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let long_name = format!("nested/{}.parquet", "x".repeat(120));
+        let files: [(&str, &[u8]); 3] = [("a.parquet", b"first"), (&long_name, b"second"), ("b.csv", &[7; 600])];
+
+        let zip_path = temp_dir.path().join("daily.zip");
+        let mut zip = zip::ZipWriter::new(File::create(&zip_path)?);
+        zip.add_directory("nested/", zip::write::SimpleFileOptions::default())?;
+        for (name, contents) in files {
+            zip.start_file(name, zip::write::SimpleFileOptions::default())?;
+            zip.write_all(contents)?;
+        }
+        zip.finish()?;
+
+        let tar_path = temp_dir.path().join("daily.tar");
+        std::fs::write(&tar_path, tar_bytes(&files))?;
+        let tgz_path = temp_dir.path().join("daily.tgz");
+        let mut gz = GzEncoder::new(File::create(&tgz_path)?, flate2::Compression::default());
+        gz.write_all(&tar_bytes(&files))?;
+        gz.finish()?;
+
+        for archive in [&zip_path, &tar_path, &tgz_path] {
+            let members = archive_members(archive)?;
+            let names: Vec<PathBuf> = files.iter().map(|(name, _)| archive.join(name)).collect();
+            assert_eq!(members, names, "{:?}", archive);
+
+            for (member, (name, contents)) in members.iter().zip(files) {
+                assert_eq!(split_archive_path(member), Some((archive.as_path(), name.to_string())));
+                assert_eq!(read_archive_member(archive, name)?, contents);
+            }
+            assert!(read_archive_member(archive, "missing.parquet").is_err());
+        }
+
+        assert_eq!(split_archive_path(&zip_path), None);
+        assert_eq!(split_archive_path(&temp_dir.path().join("other/a.parquet")), None);
+
+        Ok(())
+    }
+}
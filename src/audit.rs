@@ -0,0 +1,232 @@
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use anyhow::{Result, Context};
+use chrono::{SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use xxhash_rust::xxh3::{xxh3_128, Xxh3};
+
+/// A file as it was when the run saw it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileRecord {
+    pub path: PathBuf,
+    pub bytes: u64,
+    /// XXH3-128 of the contents, as 32 hex digits
+    pub xxh3: String,
+}
+
+impl FileRecord {
+    /// Size and checksum of the file at `path`, reading it in full
+    pub fn of(path: &Path) -> Result<Self> {
+        let mut file = File::open(path).context(format!("Failed to open {:?} for checksumming", path))?;
+        let mut hasher = Xxh3::new();
+        let mut buffer = vec![0u8; 1 << 20];
+        let mut bytes = 0;
+        loop {
+            let read = file.read(&mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            hasher.update(&buffer[..read]);
+            bytes += read as u64;
+        }
+        Ok(FileRecord { path: path.to_path_buf(), bytes, xxh3: format!("{:032x}", hasher.digest128()) })
+    }
+}
+
+/// One run in the audit log
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditRecord {
+    /// When the run finished, in UTC
+    pub timestamp: String,
+    /// `consolidate` or the subcommand that ran
+    pub command: String,
+    pub version: String,
+    /// The command-line arguments the run was started with
+    pub arguments: Vec<String>,
+    pub inputs: Vec<FileRecord>,
+    pub outputs: Vec<FileRecord>,
+    /// Files that existed before the run and were overwritten
+    #[serde(default)]
+    pub replaced: Vec<FileRecord>,
+    /// Files the run deleted
+    #[serde(default)]
+    pub deleted: Vec<PathBuf>,
+    pub rows_written: u64,
+    /// Errors of the jobs that failed; empty when the run succeeded
+    #[serde(default)]
+    pub errors: Vec<String>,
+    /// Hash of the previous record, chaining the log together
+    pub previous_hash: Option<String>,
+    /// Hash of this record and the previous hash
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub hash: String,
+}
+
+impl AuditRecord {
+    /// A record of the running build, started now with this process's arguments
+    pub fn new(command: &str) -> Self {
+        AuditRecord {
+            timestamp: Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+            command: command.to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            arguments: std::env::args().skip(1).collect(),
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+            replaced: Vec::new(),
+            deleted: Vec::new(),
+            rows_written: 0,
+            errors: Vec::new(),
+            previous_hash: None,
+            hash: String::new(),
+        }
+    }
+
+    /// Checksum `paths` into records, skipping files that don't exist
+    pub fn file_records<'a>(paths: impl IntoIterator<Item = &'a PathBuf>) -> Result<Vec<FileRecord>> {
+        paths.into_iter().filter(|path| path.is_file()).map(|path| FileRecord::of(path)).collect()
+    }
+
+    /// The hash of this record chained to `previous_hash`
+    fn chained_hash(&self) -> Result<String> {
+        let mut value = serde_json::to_value(self)?;
+        if let Some(fields) = value.as_object_mut() {
+            fields.remove("hash");
+        }
+        Ok(format!("{:032x}", xxh3_128(serde_json::to_string(&value)?.as_bytes())))
+    }
+}
+
+/// Append `record` to the JSON-lines audit log at `path`, chaining it to
+/// the last record in the log
+///
+/// Each record stores the hash of its predecessor and a hash over its own
+/// contents, so editing, reordering or deleting earlier records breaks the
+/// chain that [`verify_audit_log`] checks. The hash is XXH3-128 rather than
+/// a cryptographic hash: it catches accidental edits and naive tampering,
+/// not a writer who recomputes every later hash, so keep the log on
+/// write-once storage where that matters.
+pub fn append_audit_record(path: &Path, mut record: AuditRecord) -> Result<AuditRecord> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .read(true)
+        .append(true)
+        .open(path)
+        .context(format!("Failed to open the audit log {:?}", path))?;
+
+    let mut last_line = None;
+    for line in BufReader::new(&mut file).lines() {
+        let line = line?;
+        if !line.trim().is_empty() {
+            last_line = Some(line);
+        }
+    }
+    record.previous_hash = match last_line {
+        Some(line) => {
+            let last: AuditRecord = serde_json::from_str(&line)
+                .context(format!("The last record of the audit log {:?} is not valid", path))?;
+            Some(last.hash)
+        }
+        None => None,
+    };
+    record.hash = record.chained_hash()?;
+
+    writeln!(file, "{}", serde_json::to_string(&record)?)?;
+    file.sync_all()?;
+    Ok(record)
+}
+
+/// The outcome of checking an audit log's hash chain
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuditVerification {
+    pub records: usize,
+    /// Line and reason of the first record that breaks the chain
+    pub broken: Option<(usize, String)>,
+}
+
+impl fmt::Display for AuditVerification {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.broken {
+            None => writeln!(f, "{} records, hash chain intact", self.records),
+            Some((line, reason)) => writeln!(f, "Hash chain broken at line {}: {}", line, reason),
+        }
+    }
+}
+
+/// Recompute the hash chain of an audit log
+pub fn verify_audit_log(path: &Path) -> Result<AuditVerification> {
+    let file = File::open(path).context(format!("Failed to open the audit log {:?}", path))?;
+    let mut previous_hash: Option<String> = None;
+    let mut records = 0;
+    for (index, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let broken = |reason: &str| Ok(AuditVerification { records, broken: Some((index + 1, reason.to_string())) });
+        let Ok(record) = serde_json::from_str::<AuditRecord>(&line) else {
+            return broken("not a valid audit record");
+        };
+        if record.previous_hash != previous_hash {
+            return broken("previous hash does not match the preceding record");
+        }
+        if record.hash != record.chained_hash()? {
+            return broken("record contents do not match its hash");
+        }
+        previous_hash = Some(record.hash);
+        records += 1;
+    }
+    Ok(AuditVerification { records, broken: None })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_audit_log_hash_chain() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let input = temp_dir.path().join("a.parquet");
+        fs::write(&input, b"some bytes")?;
+        let log = temp_dir.path().join("runs.jsonl");
+
+        let mut record = AuditRecord::new("consolidate");
+        record.inputs = AuditRecord::file_records([&input, &temp_dir.path().join("missing.parquet")])?;
+        record.rows_written = 10;
+        assert_eq!(record.inputs.len(), 1);
+        assert_eq!(record.inputs[0].bytes, 10);
+        assert_eq!(record.inputs[0].xxh3, format!("{:032x}", xxh3_128(b"some bytes")));
+
+        let first = append_audit_record(&log, record.clone())?;
+        assert_eq!(first.previous_hash, None);
+        let mut second = record;
+        second.command = "compact".to_string();
+        second.deleted = vec![input.clone()];
+        let second = append_audit_record(&log, second)?;
+        assert_eq!(second.previous_hash.as_ref(), Some(&first.hash));
+        assert_ne!(second.hash, first.hash);
+
+        let verification = verify_audit_log(&log)?;
+        assert_eq!(verification, AuditVerification { records: 2, broken: None });
+        assert_eq!(verification.to_string(), "2 records, hash chain intact\n");
+
+        // Editing a record breaks its hash
+        let contents = fs::read_to_string(&log)?;
+        fs::write(&log, contents.replacen("\"rows_written\":10", "\"rows_written\":11", 1))?;
+        assert_eq!(verify_audit_log(&log)?.broken.map(|(line, _)| line), Some(1));
+
+        // Dropping a record breaks the link of the next one
+        let second_line = contents.lines().nth(1).unwrap();
+        fs::write(&log, format!("{}\n", second_line))?;
+        let verification = verify_audit_log(&log)?;
+        assert_eq!(verification.records, 0);
+        assert_eq!(
+            verification.broken,
+            Some((1, "previous hash does not match the preceding record".to_string()))
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,185 @@
+use std::fmt;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use anyhow::{Result, Context};
+use parquet::file::reader::{FileReader, SerializedFileReader};
+use crate::consolidator::ConsolidateOptions;
+use crate::file_info::file_size;
+use crate::formats::{is_buffered_input, InputFormat};
+use crate::memory::format_bytes;
+use crate::row_groups::identical_input_schema;
+
+/// How many times the decoded size of its inputs a run held in memory is
+/// assumed to need: the decoded inputs, the stacked frame and the writer's
+/// buffers
+pub const IN_MEMORY_OVERHEAD: u64 = 3;
+
+/// How a run is executed, as picked by [`plan_execution`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Execution {
+    /// Decode every input at once and stack them in memory
+    Eager,
+    /// Copy row groups to the output one at a time without decoding them,
+    /// so memory use stays around the size of one row group
+    Streaming,
+    /// Decode the inputs this many at a time before stacking them, when
+    /// neither of the others fits
+    Batched(usize),
+}
+
+/// The execution picked for a run and what it was based on
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExecutionPlan {
+    /// Estimated size of the inputs once decoded
+    pub decoded_bytes: u64,
+    /// Memory the process could still allocate, where the platform reports it
+    pub available_bytes: Option<u64>,
+    pub execution: Execution,
+}
+
+impl ExecutionPlan {
+    /// The options to run with: `options` with the picked execution applied
+    pub fn apply(&self, options: &ConsolidateOptions) -> ConsolidateOptions {
+        let mut options = options.clone();
+        match self.execution {
+            Execution::Eager => {}
+            Execution::Streaming => options.preserve_row_groups = true,
+            Execution::Batched(batch) => {
+                options.max_open_files = Some(options.max_open_files.map_or(batch, |max| max.min(batch)));
+            }
+        }
+        options
+    }
+
+    /// Why the run may still run out of memory, when it might
+    pub fn warning(&self) -> Option<String> {
+        let available = self.available_bytes?;
+        match self.execution {
+            Execution::Batched(_) => Some(format!(
+                "The inputs decode to about {} and only {} of memory is available; they are read in batches, \
+                 but the stacked result is still held in memory and may not fit",
+                format_bytes(self.decoded_bytes),
+                format_bytes(available)
+            )),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ExecutionPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Inputs decode to about {}", format_bytes(self.decoded_bytes))?;
+        match self.available_bytes {
+            Some(available) => write!(f, " with {} of memory available", format_bytes(available))?,
+            None => write!(f, " and available memory is unknown")?,
+        }
+        match self.execution {
+            Execution::Eager => write!(f, "; reading them in memory in one pass"),
+            Execution::Streaming => write!(f, "; streaming their row groups to the output"),
+            Execution::Batched(batch) => write!(f, "; reading them in memory in batches of {}", batch),
+        }
+    }
+}
+
+/// Estimated size of an input once decoded
+///
+/// Parquet inputs report the uncompressed size of their row groups in the
+/// footer. Other inputs are assumed to decode to about their size on disk,
+/// which understates compressed CSV and NDJSON.
+pub fn decoded_size(path: &Path) -> Result<u64> {
+    let parquet = InputFormat::from_path(path).unwrap_or(InputFormat::Parquet) == InputFormat::Parquet;
+    if !parquet || is_buffered_input(path) {
+        return Ok(file_size(path));
+    }
+    let reader = SerializedFileReader::new(File::open(path)?)
+        .context(format!("Failed to read parquet footer of {:?}", path))?;
+    Ok(reader.metadata().row_groups().iter().map(|row_group| row_group.total_byte_size().max(0) as u64).sum())
+}
+
+/// Pick how to execute a run from the decoded size of its inputs and the
+/// memory available
+///
+/// Runs fitting in memory [`IN_MEMORY_OVERHEAD`] times over are executed
+/// eagerly, as are all runs when the available memory is unknown. Larger
+/// ones stream row groups when writing to a path with parquet inputs of one
+/// schema and no option that needs the rows in memory. Otherwise inputs are
+/// decoded in batches sized to fit, which bounds the memory used while
+/// decoding but not the stacked result.
+pub fn plan_execution(
+    input_files: &[PathBuf],
+    options: &ConsolidateOptions,
+    available_bytes: Option<u64>,
+    to_path: bool,
+) -> Result<ExecutionPlan> {
+    let decoded_bytes = input_files.iter().map(|path| decoded_size(path)).sum::<Result<u64>>()?;
+    let budget = available_bytes.map(|available| available / IN_MEMORY_OVERHEAD);
+    let execution = match budget {
+        None => Execution::Eager,
+        Some(budget) if decoded_bytes <= budget => Execution::Eager,
+        Some(_) if to_path && can_stream(input_files, options) => Execution::Streaming,
+        Some(budget) => {
+            let per_file = (decoded_bytes / input_files.len().max(1) as u64).max(1);
+            Execution::Batched((budget / per_file).max(1) as usize)
+        }
+    };
+    Ok(ExecutionPlan { decoded_bytes, available_bytes, execution })
+}
+
+fn can_stream(input_files: &[PathBuf], options: &ConsolidateOptions) -> bool {
+    options.row_level_feature().is_none()
+        && !options.continue_on_error
+        && options.file_timeout.is_none()
+        && options.deadline.is_none()
+        && options.read.columns.is_empty()
+        && options.read.row_groups.is_empty()
+        && identical_input_schema(input_files, &options.read).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::redact::ColumnRedaction;
+    use crate::test_utils::create_test_parquet_file;
+
+    #[test]
+    fn test_plan_execution() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let inputs: Vec<PathBuf> = (0..4).map(|i| temp_dir.path().join(format!("{}.parquet", i))).collect();
+        for (i, input) in inputs.iter().enumerate() {
+            create_test_parquet_file(input, 0, 100 * (i as i32 + 1))?;
+        }
+        let csv = temp_dir.path().join("extra.csv");
+        std::fs::write(&csv, "id,name,value\n1,a,1.0\n")?;
+
+        let decoded = inputs.iter().map(|path| decoded_size(path)).sum::<Result<u64>>()?;
+        assert!(decoded > 0);
+        assert_eq!(decoded_size(&csv)?, 22);
+        let options = ConsolidateOptions::default();
+
+        let plan = plan_execution(&inputs, &options, Some(decoded * IN_MEMORY_OVERHEAD), true)?;
+        assert_eq!(plan.execution, Execution::Eager);
+        assert_eq!(plan.warning(), None);
+        assert_eq!(plan_execution(&inputs, &options, None, true)?.execution, Execution::Eager);
+
+        let plan = plan_execution(&inputs, &options, Some(decoded), true)?;
+        assert_eq!(plan.execution, Execution::Streaming);
+        assert!(plan.apply(&options).preserve_row_groups);
+        assert!(plan.to_string().ends_with("streaming their row groups to the output"));
+
+        // Writing to a sink, with mixed inputs or with row-level options, the rows are decoded
+        let plan = plan_execution(&inputs, &options, Some(decoded), false)?;
+        assert_eq!(plan.execution, Execution::Batched(1));
+        assert!(plan.warning().unwrap().contains("may not fit"));
+        let mixed: Vec<PathBuf> = inputs.iter().cloned().chain([csv]).collect();
+        assert!(matches!(plan_execution(&mixed, &options, Some(decoded), true)?.execution, Execution::Batched(_)));
+        let redacting = ConsolidateOptions { redactions: vec!["name=/a/".parse::<ColumnRedaction>()?], ..Default::default() };
+        let plan = plan_execution(&inputs, &redacting, Some(decoded * 2), true)?;
+        assert_eq!(plan.execution, Execution::Batched(2));
+
+        let capped = ConsolidateOptions { max_open_files: Some(1), ..Default::default() };
+        assert_eq!(plan.apply(&capped).max_open_files, Some(1));
+        assert_eq!(plan.apply(&options).max_open_files, Some(2));
+
+        Ok(())
+    }
+}
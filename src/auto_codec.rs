@@ -0,0 +1,206 @@
+use std::fmt;
+use anyhow::Result;
+use parquet::basic::{Compression, Encoding};
+use polars::prelude::*;
+use crate::warnings::WarningLog;
+use crate::writer::{parse_codec, write_dataframe_logged, ColumnCompression, ColumnEncoding, WriterOptions};
+
+/// Rows of each column written with every candidate codec and encoding
+pub const AUTO_CODEC_SAMPLE_ROWS: usize = 10_000;
+
+/// Codecs tried for each column, from cheapest to decode to most thorough
+const AUTO_CODECS: &[&str] = &["uncompressed", "snappy", "zstd:3", "zstd:9"];
+
+/// How much smaller, in percent, a costlier candidate's sample must be to
+/// be chosen over a cheaper one
+const MIN_SAVING_PERCENT: usize = 5;
+
+/// The codec and encoding picked for one column by `--compression auto`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnCodecChoice {
+    pub column: String,
+    pub codec: Compression,
+    /// `None` for the writer's default, dictionary encoding with a plain
+    /// fallback
+    pub encoding: Option<Encoding>,
+    /// Size of the sample written with the choice
+    pub sample_bytes: usize,
+    /// Size of the sample written with the default codec and encoding
+    pub default_bytes: usize,
+}
+
+/// A codec as `parse_codec` accepts it, e.g. `zstd:3`
+fn codec_name(codec: Compression) -> String {
+    match codec {
+        Compression::GZIP(level) => format!("gzip:{}", level.compression_level()),
+        Compression::BROTLI(level) => format!("brotli:{}", level.compression_level()),
+        Compression::ZSTD(level) => format!("zstd:{}", level.compression_level()),
+        other => other.to_string().to_lowercase(),
+    }
+}
+
+impl fmt::Display for ColumnCodecChoice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.column, codec_name(self.codec))?;
+        if let Some(encoding) = self.encoding {
+            write!(f, " with {}", encoding.to_string().to_lowercase())?;
+        }
+        write!(f, " ({} bytes sampled, {} with the default)", self.sample_bytes, self.default_bytes)
+    }
+}
+
+/// Encodings worth trying for a column of `data_type` besides the default
+fn candidate_encodings(data_type: &DataType) -> Vec<Encoding> {
+    match data_type {
+        data_type if data_type.is_integer() => vec![Encoding::DELTA_BINARY_PACKED],
+        DataType::Date | DataType::Datetime(_, _) | DataType::Duration(_) | DataType::Time => {
+            vec![Encoding::DELTA_BINARY_PACKED]
+        }
+        DataType::Float32 | DataType::Float64 => vec![Encoding::BYTE_STREAM_SPLIT],
+        DataType::Utf8 | DataType::Binary => vec![Encoding::DELTA_BYTE_ARRAY],
+        _ => Vec::new(),
+    }
+}
+
+/// Size of `sample` written as parquet with `codec` and `encoding`
+fn sample_size(sample: &DataFrame, codec: Compression, encoding: Option<Encoding>, options: &WriterOptions) -> Result<usize> {
+    let column = sample.get_column_names()[0].to_string();
+    let options = WriterOptions {
+        compression: codec,
+        column_encodings: encoding.map(|encoding| ColumnEncoding { column, encoding }).into_iter().collect(),
+        compat: options.compat,
+        created_by: options.created_by.clone(),
+        ..Default::default()
+    };
+    // The real write raises any warnings about the schema
+    Ok(write_dataframe_logged(&mut sample.clone(), Vec::new(), &options, &WarningLog::default())?.len())
+}
+
+/// Pick the codec and encoding of each column by writing a sample of it
+/// with every candidate and measuring the result
+///
+/// The sample is the first [`AUTO_CODEC_SAMPLE_ROWS`] rows. Candidates
+/// are tried from cheapest to decode to most thorough, and a costlier one
+/// is only chosen when its sample is at least 5% smaller, so columns that
+/// don't compress stay uncompressed. Columns with an explicit codec or
+/// encoding, nested columns and columns with a dot in their name keep the
+/// configured settings, as do all columns of an empty frame. Under a
+/// compatibility mode, only codecs are chosen.
+///
+/// # Examples
+///
+/// ```
+/// use polars::prelude::*;
+/// use parquet::basic::Compression;
+/// use parquet_consolidator::{choose_column_codecs, WriterOptions};
+///
+/// let ids: Vec<i64> = (0..5_000).collect();
+/// let levels: Vec<&str> = (0..5_000).map(|i| if i % 7 == 0 { "WARN" } else { "INFO" }).collect();
+/// let df = df!("id" => ids, "level" => levels).unwrap();
+///
+/// let choices = choose_column_codecs(&df, &WriterOptions::default()).unwrap();
+/// assert_eq!(choices.len(), 2);
+/// assert!(choices.iter().all(|choice| choice.sample_bytes <= choice.default_bytes));
+/// ```
+pub fn choose_column_codecs(df: &DataFrame, options: &WriterOptions) -> Result<Vec<ColumnCodecChoice>> {
+    if df.height() == 0 {
+        return Ok(Vec::new());
+    }
+    let codecs: Vec<Compression> = AUTO_CODECS.iter().map(|codec| parse_codec(codec)).collect::<Result<_>>()?;
+    let sample = df.head(Some(AUTO_CODEC_SAMPLE_ROWS));
+
+    let mut choices = Vec::new();
+    for series in sample.get_columns() {
+        let column = series.name();
+        let configured = options.column_compression.iter().any(|override_| override_.column == column)
+            || options.column_encodings.iter().any(|override_| override_.column == column);
+        let nested = matches!(series.dtype(), DataType::List(_));
+        if configured || nested || column.contains('.') {
+            continue;
+        }
+
+        let column_sample = DataFrame::new(vec![series.clone()])?;
+        let mut encodings = vec![None];
+        if options.compat.is_none() {
+            encodings.extend(candidate_encodings(series.dtype()).into_iter().map(Some));
+        }
+        let mut best: Option<(Compression, Option<Encoding>, usize)> = None;
+        for &encoding in &encodings {
+            for &codec in &codecs {
+                let bytes = sample_size(&column_sample, codec, encoding, options)?;
+                let saves_enough = best.is_none_or(|(_, _, best_bytes)| bytes * 100 < best_bytes * (100 - MIN_SAVING_PERCENT));
+                if saves_enough {
+                    best = Some((codec, encoding, bytes));
+                }
+            }
+        }
+        let (codec, encoding, sample_bytes) = best.expect("every column has at least one candidate");
+        choices.push(ColumnCodecChoice {
+            column: column.to_string(),
+            codec,
+            encoding,
+            sample_bytes,
+            default_bytes: sample_size(&column_sample, options.compression, None, options)?,
+        });
+    }
+    Ok(choices)
+}
+
+/// `options` with the choices applied as per-column overrides, and no
+/// longer choosing codecs itself
+pub fn apply_codec_choices(options: &WriterOptions, choices: &[ColumnCodecChoice]) -> WriterOptions {
+    let mut options = WriterOptions { auto_compression: false, ..options.clone() };
+    for choice in choices {
+        options.column_compression.push(ColumnCompression { column: choice.column.clone(), codec: choice.codec });
+        if let Some(encoding) = choice.encoding {
+            options.column_encodings.push(ColumnEncoding { column: choice.column.clone(), encoding });
+        }
+    }
+    options
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+    use crate::consolidator::{consolidate_with_options, ConsolidateOptions};
+
+    #[test]
+    fn test_auto_compression() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let input = temp_dir.path().join("logs.parquet");
+        let rows = 20_000;
+        // Log-like text repeats a lot; hashes don't compress at all
+        let messages: Vec<String> = (0..rows).map(|i| format!("GET /api/orders/{} 200", i % 50)).collect();
+        let hashes: Vec<f64> = (0..rows).map(|i| (i as f64 * 12.9898).sin() * 43758.5453 % 1.0).collect();
+        let mut df = df!("message" => messages, "noise" => hashes, "ts" => (0..rows as i64).collect::<Vec<_>>())?;
+        ParquetWriter::new(File::create(&input)?).finish(&mut df)?;
+
+        let output = temp_dir.path().join("out.parquet");
+        let writer = WriterOptions {
+            auto_compression: true,
+            column_compression: vec!["ts=snappy".parse()?],
+            ..Default::default()
+        };
+        let options = ConsolidateOptions { writer, ..Default::default() };
+        let summary = consolidate_with_options(&[input], &output, &options)?;
+
+        let choices = &summary.codec_choices;
+        assert_eq!(choices.iter().map(|choice| choice.column.as_str()).collect::<Vec<_>>(), ["message", "noise"]);
+        assert_ne!(choices[0].codec, Compression::UNCOMPRESSED);
+        assert!(choices[0].sample_bytes < choices[0].default_bytes);
+        assert!(summary.to_string().contains("message: "));
+
+        // The footer records the chosen codecs, though not their levels;
+        // ts kept its explicit codec
+        let reader = SerializedFileReader::new(File::open(&output)?)?;
+        let row_group = reader.metadata().row_group(0);
+        let codec_kind = |codec: Compression| std::mem::discriminant(&codec);
+        assert_eq!(codec_kind(row_group.column(0).compression()), codec_kind(choices[0].codec));
+        assert_eq!(codec_kind(row_group.column(1).compression()), codec_kind(choices[1].codec));
+        assert_eq!(row_group.column(2).compression(), Compression::SNAPPY);
+
+        Ok(())
+    }
+}
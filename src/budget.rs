@@ -0,0 +1,135 @@
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+use anyhow::{Result, Context};
+
+/// Exit status of a run that stopped at its runtime budget and left inputs
+/// for the next run, having written everything it read
+pub const PARTIAL_SUCCESS_EXIT_CODE: i32 = 3;
+
+/// Name of the remaining inputs list kept in a directory output
+pub const REMAINING_INPUTS_NAME: &str = "_remaining_inputs.txt";
+
+/// How long a run may spend reading inputs, e.g. `30m`
+///
+/// Written as a whole number with an optional unit: `s` (the default),
+/// `m` or `h`.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use parquet_consolidator::MaxRuntime;
+///
+/// assert_eq!("30m".parse::<MaxRuntime>().unwrap(), MaxRuntime(Duration::from_secs(1800)));
+/// assert_eq!("90".parse::<MaxRuntime>().unwrap().to_string(), "90s");
+/// assert!("soon".parse::<MaxRuntime>().is_err());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MaxRuntime(pub Duration);
+
+impl fmt::Display for MaxRuntime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let secs = self.0.as_secs();
+        match secs {
+            0 => write!(f, "0s"),
+            _ if secs.is_multiple_of(3600) => write!(f, "{}h", secs / 3600),
+            _ if secs.is_multiple_of(60) => write!(f, "{}m", secs / 60),
+            _ => write!(f, "{}s", secs),
+        }
+    }
+}
+
+impl FromStr for MaxRuntime {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        let (number, unit) = match s.find(|c: char| !c.is_ascii_digit()) {
+            Some(index) => s.split_at(index),
+            None => (s, "s"),
+        };
+        let scale = match unit.to_lowercase().as_str() {
+            "s" => 1,
+            "m" => 60,
+            "h" => 3600,
+            _ => anyhow::bail!("Invalid runtime: {} (expected a number of s, m or h, e.g. 30m)", s),
+        };
+        let number: u64 = number
+            .parse()
+            .map_err(|_| anyhow::anyhow!("Invalid runtime: {} (expected a number of s, m or h, e.g. 30m)", s))?;
+        Ok(MaxRuntime(Duration::from_secs(number.saturating_mul(scale))))
+    }
+}
+
+/// Where the inputs a budgeted run left are listed
+///
+/// # Examples
+///
+/// ```
+/// use parquet_consolidator::remaining_inputs_path;
+/// use std::path::Path;
+///
+/// assert_eq!(remaining_inputs_path(Path::new("out/events.parquet"), false), Path::new("out/events.parquet.remaining"));
+/// assert_eq!(remaining_inputs_path(Path::new("warehouse/events"), true), Path::new("warehouse/events/_remaining_inputs.txt"));
+/// ```
+pub fn remaining_inputs_path(output: &Path, is_directory: bool) -> PathBuf {
+    if is_directory {
+        output.join(REMAINING_INPUTS_NAME)
+    } else {
+        let mut name = output.as_os_str().to_owned();
+        name.push(".remaining");
+        PathBuf::from(name)
+    }
+}
+
+/// Record the inputs a run left, one per line, so the next run picks them
+/// up with `--files-from`; with none left, a list from an earlier run is
+/// removed
+///
+/// The list is written next to its final name and renamed into place, so
+/// an interrupted write never leaves half a list.
+pub fn write_remaining_inputs(path: &Path, remaining: &[PathBuf]) -> Result<()> {
+    if remaining.is_empty() {
+        return match fs::remove_file(path) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => {
+                Err(err).context(format!("Failed to remove {:?}", path))
+            }
+            _ => Ok(()),
+        };
+    }
+    let mut list = String::new();
+    for input in remaining {
+        list.push_str(&input.to_string_lossy());
+        list.push('\n');
+    }
+    let mut staging = path.as_os_str().to_owned();
+    staging.push(".tmp");
+    fs::write(&staging, list).context(format!("Failed to write {:?}", staging))?;
+    fs::rename(&staging, path).context(format!("Failed to move {:?} into place", path))
+}
+
+#[cfg(all(test, feature = "discovery"))]
+mod tests {
+    use super::*;
+    use crate::discovery::read_file_list;
+
+    #[test]
+    fn test_write_remaining_inputs() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let list = remaining_inputs_path(&temp_dir.path().join("events.parquet"), false);
+        let remaining = vec![PathBuf::from("in/c.parquet"), PathBuf::from("in/d e.parquet")];
+
+        write_remaining_inputs(&list, &remaining)?;
+        assert_eq!(read_file_list(fs::read(&list)?.as_slice(), false)?, remaining);
+
+        // A run that finishes clears the list of the run before it
+        write_remaining_inputs(&list, &[])?;
+        assert!(!list.exists());
+        write_remaining_inputs(&list, &[])?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,99 @@
+use std::fmt;
+use serde::Serialize;
+use crate::formats::InputFormat;
+use crate::writer::SUPPORTED_CODECS;
+
+/// What this build of the tool can do, for bug reports and capability checks
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BuildInfo {
+    pub version: &'static str,
+    /// Optional cargo features compiled in
+    pub features: Vec<&'static str>,
+    pub polars_version: &'static str,
+    pub arrow_version: &'static str,
+    pub parquet_version: &'static str,
+    pub input_formats: Vec<String>,
+    pub output_formats: Vec<&'static str>,
+    pub compression_codecs: Vec<&'static str>,
+    /// Where inputs and outputs can live; only local paths are supported
+    pub storage_schemes: Vec<&'static str>,
+}
+
+impl BuildInfo {
+    /// Describe the running build
+    pub fn current() -> Self {
+        let mut features = Vec::new();
+        if cfg!(feature = "discovery") {
+            features.push("discovery");
+        }
+        if cfg!(feature = "xlsx") {
+            features.push("xlsx");
+        }
+        if cfg!(feature = "otel") {
+            features.push("otel");
+        }
+        if cfg!(feature = "fault-injection") {
+            features.push("fault-injection");
+        }
+        if cfg!(feature = "schema-registry") {
+            features.push("schema-registry");
+        }
+        if cfg!(feature = "test-utils") {
+            features.push("test-utils");
+        }
+
+        BuildInfo {
+            version: env!("CARGO_PKG_VERSION"),
+            features,
+            polars_version: polars::VERSION,
+            arrow_version: arrow::ARROW_VERSION,
+            parquet_version: parquet::file::properties::DEFAULT_CREATED_BY
+                .trim_start_matches("parquet-rs version "),
+            input_formats: InputFormat::all().iter().map(ToString::to_string).collect(),
+            output_formats: vec!["parquet"],
+            compression_codecs: SUPPORTED_CODECS.to_vec(),
+            storage_schemes: vec!["file"],
+        }
+    }
+}
+
+impl fmt::Display for BuildInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let list = |items: &[&str]| if items.is_empty() { "none".to_string() } else { items.join(", ") };
+        let input_formats: Vec<&str> = self.input_formats.iter().map(String::as_str).collect();
+
+        writeln!(f, "parquet_consolidator {}", self.version)?;
+        writeln!(f, "Features: {}", list(&self.features))?;
+        writeln!(f, "polars: {}", self.polars_version)?;
+        writeln!(f, "arrow: {}", self.arrow_version)?;
+        writeln!(f, "parquet: {}", self.parquet_version)?;
+        writeln!(f, "Input formats: {}", list(&input_formats))?;
+        writeln!(f, "Output formats: {}", list(&self.output_formats))?;
+        writeln!(f, "Compression codecs: {}", list(&self.compression_codecs))?;
+        writeln!(f, "Storage schemes: {}", list(&self.storage_schemes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_info() -> anyhow::Result<()> {
+        let info = BuildInfo::current();
+        assert_eq!(info.version, env!("CARGO_PKG_VERSION"));
+        assert!(info.input_formats.contains(&"parquet".to_string()));
+        assert_eq!(info.features.contains(&"xlsx"), cfg!(feature = "xlsx"));
+        assert!(!info.parquet_version.starts_with("parquet-rs"));
+
+        let text = info.to_string();
+        assert!(text.starts_with(&format!("parquet_consolidator {}\n", info.version)));
+        assert!(text.contains("Compression codecs: uncompressed, snappy"));
+
+        let json: serde_json::Value = serde_json::to_value(&info)?;
+        assert_eq!(json["polars_version"], polars::VERSION);
+        assert_eq!(json["storage_schemes"][0], "file");
+
+        Ok(())
+    }
+}
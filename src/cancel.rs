@@ -0,0 +1,114 @@
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use anyhow::Result;
+
+/// The error a consolidation stopped by its [`CancellationToken`] fails
+/// with, found with `err.is::<Cancelled>()`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "The consolidation was cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+/// A flag an embedding service sets to stop a consolidation, such as when
+/// the request that started it is cancelled
+///
+/// Clones share the flag. A run checks it before it starts, between the
+/// batches and row groups it reads, and before it writes an output held in
+/// memory; a decode or an encode already under way is finished first. A
+/// file output the run started writing is removed.
+///
+/// # Examples
+///
+/// ```
+/// use parquet_consolidator::{consolidate_with_options, CancellationToken, Cancelled, ConsolidateOptions};
+/// use parquet_consolidator::test_utils::create_test_parquet_file;
+/// use tempfile::TempDir;
+///
+/// let temp_dir = TempDir::new().unwrap();
+/// let input = temp_dir.path().join("a.parquet");
+/// create_test_parquet_file(&input, 0, 10).unwrap();
+///
+/// let token = CancellationToken::new();
+/// let options = ConsolidateOptions { cancel: Some(token.clone()), ..Default::default() };
+/// token.cancel();
+/// let output = temp_dir.path().join("out.parquet");
+/// let err = consolidate_with_options(&[input], &output, &options).unwrap_err();
+/// assert!(err.is::<Cancelled>());
+/// assert!(!output.exists());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    /// Fail with [`Cancelled`] once the token is cancelled
+    pub fn check(&self) -> Result<()> {
+        match self.is_cancelled() {
+            true => Err(Cancelled.into()),
+            false => Ok(()),
+        }
+    }
+}
+
+/// A token sharing a flag the service already keeps
+impl From<Arc<AtomicBool>> for CancellationToken {
+    fn from(flag: Arc<AtomicBool>) -> Self {
+        CancellationToken(flag)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consolidator::{consolidate_with_options, ConsolidateOptions};
+    use crate::pipeline::PipelineOptions;
+    use crate::test_utils::create_test_parquet_file;
+
+    #[test]
+    fn test_cancelled_runs() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let inputs = [temp_dir.path().join("a.parquet"), temp_dir.path().join("b.parquet")];
+        create_test_parquet_file(&inputs[0], 0, 10)?;
+        create_test_parquet_file(&inputs[1], 10, 20)?;
+        let output = temp_dir.path().join("out.parquet");
+
+        // The service's own flag, set by its request handler
+        let flag = Arc::new(AtomicBool::new(false));
+        let token = CancellationToken::from(flag.clone());
+        let options = ConsolidateOptions { cancel: Some(token.clone()), max_open_files: Some(1), ..Default::default() };
+        assert_eq!(consolidate_with_options(&inputs, &output, &options)?.rows_written, 20);
+
+        flag.store(true, Ordering::SeqCst);
+        assert!(token.is_cancelled());
+        std::fs::remove_file(&output)?;
+        for options in [
+            options.clone(),
+            ConsolidateOptions { preserve_row_groups: true, ..options.clone() },
+            ConsolidateOptions { pipeline: Some(PipelineOptions::default()), ..options.clone() },
+        ] {
+            let err = consolidate_with_options(&inputs, &output, &options).unwrap_err();
+            assert!(err.is::<Cancelled>(), "{:#}", err);
+            assert!(!output.exists());
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,116 @@
+use anyhow::{Result, Context};
+use polars::prelude::*;
+
+/// Default name of the column holding each change record's operation
+pub const DEFAULT_OP_COLUMN: &str = "_op";
+
+/// How to fold change data capture records into a snapshot
+#[derive(Debug, Clone, PartialEq)]
+pub struct CdcOptions {
+    /// Columns identifying the row a change applies to
+    pub keys: Vec<String>,
+    /// Column holding insert/update/delete operations
+    pub op_column: String,
+    /// Column ordering changes, such as a commit timestamp or log sequence
+    /// number. Without it, later input rows are newer
+    pub order_by: Option<String>,
+}
+
+/// The kind of change a record describes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operation {
+    Upsert,
+    Delete,
+}
+
+/// Parse an operation, accepting full names and the single-letter codes
+/// used by Debezium (`c`reate, `r`ead, `u`pdate, `d`elete)
+fn parse_operation(op: &str) -> Option<Operation> {
+    match op.to_lowercase().as_str() {
+        "i" | "c" | "r" | "u" | "insert" | "create" | "read" | "update" | "upsert" => Some(Operation::Upsert),
+        "d" | "delete" => Some(Operation::Delete),
+        _ => None,
+    }
+}
+
+/// Reduce change records to the latest state of every key
+///
+/// The newest record per key wins; keys whose newest record is a delete
+/// are removed. The operation column is dropped from the result.
+pub fn apply_changes(df: &DataFrame, options: &CdcOptions) -> Result<DataFrame> {
+    if options.keys.is_empty() {
+        anyhow::bail!("CDC merge requires at least one key column");
+    }
+
+    let ordered = match &options.order_by {
+        Some(column) => df
+            .sort([column.as_str()], false, true)
+            .context(format!("Failed to order changes by {}", column))?,
+        None => df.clone(),
+    };
+    let latest = ordered.unique_stable(Some(&options.keys), UniqueKeepStrategy::Last, None)?;
+
+    let ops = latest
+        .column(&options.op_column)
+        .context(format!("CDC operation column not found: {}", options.op_column))?
+        .cast(&DataType::Utf8)?;
+    let keep = ops
+        .utf8()?
+        .into_iter()
+        .map(|op| match op.and_then(parse_operation) {
+            Some(operation) => Ok(operation == Operation::Upsert),
+            None => anyhow::bail!("Unknown CDC operation {:?} in column {}", op, options.op_column),
+        })
+        .collect::<Result<BooleanChunked>>()?;
+
+    Ok(latest.filter(&keep)?.drop(&options.op_column)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(order_by: Option<&str>) -> CdcOptions {
+        CdcOptions {
+            keys: vec!["id".to_string()],
+            op_column: DEFAULT_OP_COLUMN.to_string(),
+            order_by: order_by.map(String::from),
+        }
+    }
+
+    #[test]
+    fn test_apply_changes() -> Result<()> {
+        let changes = df!(
+            "id" => &[1i32, 2, 3, 1, 2],
+            "name" => &["a", "b", "c", "a2", "b"],
+            "_op" => &["insert", "I", "c", "u", "D"]
+        )?;
+
+        let mut snapshot = apply_changes(&changes, &options(None))?;
+        snapshot.sort_in_place(["id"], false, false)?;
+        assert_eq!(snapshot.get_column_names(), vec!["id", "name"]);
+        let names: Vec<Option<&str>> = snapshot.column("name")?.utf8()?.into_iter().collect();
+        assert_eq!(names, vec![Some("a2"), Some("c")]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_apply_changes_ordered() -> Result<()> {
+        let changes = df!(
+            "id" => &[1i32, 1, 1],
+            "lsn" => &[3i64, 1, 2],
+            "name" => &["newest", "oldest", "middle"],
+            "_op" => &["u", "i", "d"]
+        )?;
+
+        let snapshot = apply_changes(&changes, &options(Some("lsn")))?;
+        assert_eq!(snapshot.column("name")?.utf8()?.get(0), Some("newest"));
+
+        let changes = df!("id" => &[1i32], "_op" => &["merge"])?;
+        let err = apply_changes(&changes, &options(None)).unwrap_err();
+        assert!(err.to_string().contains("Unknown CDC operation"));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,296 @@
+use std::fmt;
+use std::path::Path;
+use anyhow::{Result, Context};
+use polars::prelude::*;
+use serde::Deserialize;
+
+/// What happens to a run when a check finds violating rows
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckPolicy {
+    /// Report the violations and keep the rows
+    #[default]
+    Warn,
+    /// Abort the run before anything is written
+    Fail,
+    /// Move the violating rows out of the output, into the quarantine file
+    /// when one is configured
+    Quarantine,
+}
+
+/// A constraint on the consolidated data
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CheckRule {
+    /// The column contains no nulls
+    NotNull { column: String },
+    /// No two rows share the same values in these columns
+    Unique { columns: Vec<String> },
+    /// Non-null values of a numeric column lie within the inclusive bounds
+    Range { column: String, min: Option<f64>, max: Option<f64> },
+    /// Non-null values are one of the listed values
+    AcceptedValues { column: String, values: Vec<String> },
+}
+
+impl fmt::Display for CheckRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CheckRule::NotNull { column } => write!(f, "not_null({})", column),
+            CheckRule::Unique { columns } => write!(f, "unique({})", columns.join(", ")),
+            CheckRule::Range { column, .. } => write!(f, "range({})", column),
+            CheckRule::AcceptedValues { column, .. } => write!(f, "accepted_values({})", column),
+        }
+    }
+}
+
+/// A single entry of a checks file
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Check {
+    /// Label used in reports; defaults to a description of the rule
+    pub name: Option<String>,
+    #[serde(flatten)]
+    pub rule: CheckRule,
+    #[serde(default)]
+    pub policy: CheckPolicy,
+}
+
+impl Check {
+    pub fn label(&self) -> String {
+        self.name.clone().unwrap_or_else(|| self.rule.to_string())
+    }
+
+    /// Mark the rows of a frame that violate this check
+    fn violations(&self, df: &DataFrame) -> Result<BooleanChunked> {
+        if let CheckRule::Unique { columns } = &self.rule {
+            return Ok(df.select(columns)?.is_duplicated()?);
+        }
+
+        let expr = match &self.rule {
+            CheckRule::NotNull { column } => col(column).is_null(),
+            CheckRule::Range { column, min, max } => {
+                let value = col(column).cast(DataType::Float64);
+                let mut outside = lit(false);
+                if let Some(min) = min {
+                    outside = outside.or(value.clone().lt(lit(*min)));
+                }
+                if let Some(max) = max {
+                    outside = outside.or(value.gt(lit(*max)));
+                }
+                outside.fill_null(lit(false))
+            }
+            CheckRule::AcceptedValues { column, values } => {
+                let value = col(column).cast(DataType::Utf8);
+                values
+                    .iter()
+                    .fold(value.clone().is_not_null(), |unknown, accepted| {
+                        unknown.and(value.clone().neq(lit(accepted.as_str())))
+                    })
+            }
+            CheckRule::Unique { .. } => unreachable!(),
+        };
+
+        let mask = df.clone().lazy().select([expr.alias("violation")]).collect()?;
+        Ok(mask.column("violation")?.bool()?.clone())
+    }
+}
+
+/// Constraints evaluated on the consolidated frame before it is written
+///
+/// Loaded from a TOML file with one `[[check]]` table per constraint:
+///
+/// ```
+/// use parquet_consolidator::{CheckPolicy, QualityChecks};
+///
+/// let checks = QualityChecks::from_toml_str(r#"
+///     [[check]]
+///     type = "not_null"
+///     column = "id"
+///     policy = "fail"
+///
+///     [[check]]
+///     type = "range"
+///     column = "score"
+///     min = 0
+///     max = 100
+/// "#).unwrap();
+/// assert_eq!(checks.checks.len(), 2);
+/// assert_eq!(checks.checks[1].policy, CheckPolicy::Warn);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct QualityChecks {
+    #[serde(rename = "check", default)]
+    pub checks: Vec<Check>,
+}
+
+impl QualityChecks {
+    pub fn from_toml_str(s: &str) -> Result<Self> {
+        toml::from_str(s).context("Invalid checks file")
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path).context(format!("Failed to read checks file {:?}", path))?;
+        Self::from_toml_str(&contents)
+    }
+
+    /// Evaluate every check against a frame
+    ///
+    /// Rows violating quarantine checks are split off from the frame into
+    /// the result's `rejected` frame. Fails when any check with the fail
+    /// policy finds a violation.
+    pub fn run(&self, df: DataFrame) -> Result<CheckResults> {
+        let mut outcomes = Vec::new();
+        let mut reasons: Vec<Option<String>> = vec![None; df.height()];
+
+        for check in &self.checks {
+            let violations = check
+                .violations(&df)
+                .context(format!("Failed to evaluate check {}", check.label()))?;
+            let count = violations.sum().unwrap_or(0) as usize;
+            if check.policy == CheckPolicy::Quarantine && count > 0 {
+                for (reason, violated) in reasons.iter_mut().zip(&violations) {
+                    if violated == Some(true) {
+                        let label = check.label();
+                        *reason = Some(match reason.take() {
+                            Some(existing) => format!("{}; {}", existing, label),
+                            None => label,
+                        });
+                    }
+                }
+            }
+            outcomes.push(CheckOutcome { check: check.label(), policy: check.policy, violations: count });
+        }
+
+        let failed: Vec<String> = outcomes
+            .iter()
+            .filter(|outcome| outcome.policy == CheckPolicy::Fail && outcome.violations > 0)
+            .map(|outcome| outcome.to_string())
+            .collect();
+        if !failed.is_empty() {
+            anyhow::bail!("Data quality checks failed: {}", failed.join("; "));
+        }
+
+        let reasons = Utf8Chunked::from_iter_options(QUARANTINE_REASON_COLUMN, reasons.into_iter());
+        let quarantined = reasons.is_not_null();
+        let mut rejected = df.filter(&quarantined)?;
+        rejected.with_column(reasons.filter(&quarantined)?.into_series())?;
+        let passed = if quarantined.any() { df.filter(&!quarantined)? } else { df };
+
+        Ok(CheckResults { passed, rejected, outcomes })
+    }
+}
+
+/// Name of the column recording why a row was quarantined
+pub const QUARANTINE_REASON_COLUMN: &str = "_quarantine_reason";
+
+/// Frames and outcomes produced by running the checks
+#[derive(Debug, Clone)]
+pub struct CheckResults {
+    /// Rows that passed every quarantine check
+    pub passed: DataFrame,
+    /// Quarantined rows, with the labels of the checks they violated in
+    /// the quarantine reason column
+    pub rejected: DataFrame,
+    pub outcomes: Vec<CheckOutcome>,
+}
+
+/// Result of evaluating one check
+#[derive(Debug, Clone, PartialEq)]
+pub struct CheckOutcome {
+    pub check: String,
+    pub policy: CheckPolicy,
+    pub violations: usize,
+}
+
+impl fmt::Display for CheckOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "check {} found {} violating rows", self.check, self.violations)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> DataFrame {
+        df!(
+            "id" => &[Some(1i32), Some(2), Some(2), None],
+            "score" => &[Some(10.0), Some(150.0), None, Some(-1.0)],
+            "status" => &[Some("active"), Some("gone"), Some("inactive"), None]
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_checks_count_violations() -> Result<()> {
+        let checks = QualityChecks::from_toml_str(
+            r#"
+            [[check]]
+            type = "not_null"
+            column = "id"
+
+            [[check]]
+            type = "unique"
+            columns = ["id"]
+
+            [[check]]
+            name = "score in range"
+            type = "range"
+            column = "score"
+            min = 0
+            max = 100
+
+            [[check]]
+            type = "accepted_values"
+            column = "status"
+            values = ["active", "inactive"]
+            "#,
+        )?;
+
+        let results = checks.run(sample())?;
+        assert_eq!(results.passed.height(), 4);
+        assert_eq!(results.rejected.height(), 0);
+        let counts: Vec<usize> = results.outcomes.iter().map(|o| o.violations).collect();
+        assert_eq!(counts, vec![1, 2, 2, 1]);
+        assert_eq!(results.outcomes[2].check, "score in range");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_checks_policies() -> Result<()> {
+        let checks = QualityChecks::from_toml_str(
+            r#"
+            [[check]]
+            type = "range"
+            column = "score"
+            min = 0
+            policy = "quarantine"
+
+            [[check]]
+            type = "not_null"
+            column = "id"
+            policy = "quarantine"
+            "#,
+        )?;
+        let results = checks.run(sample())?;
+        assert_eq!(results.passed.height(), 3);
+        assert_eq!(results.rejected.height(), 1);
+        let reason = results.rejected.column(QUARANTINE_REASON_COLUMN)?.utf8()?.get(0);
+        assert_eq!(reason, Some("range(score); not_null(id)"));
+
+        let checks = QualityChecks::from_toml_str(
+            r#"
+            [[check]]
+            type = "not_null"
+            column = "id"
+            policy = "fail"
+            "#,
+        )?;
+        let err = checks.run(sample()).unwrap_err();
+        assert!(err.to_string().contains("not_null(id) found 1 violating rows"));
+
+        assert!(QualityChecks::from_toml_str("[[check]]\ntype = \"bogus\"\n").is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,87 @@
+use anyhow::{Result, Context};
+use polars::prelude::*;
+use regex::Regex;
+
+/// Which top-level columns of the inputs are read, by name pattern
+///
+/// Patterns match anywhere in the name unless anchored, so `^metric_`
+/// selects columns starting with `metric_`. A column is kept when it
+/// matches `include`, or when there is no `include`, and doesn't match
+/// `exclude`.
+#[derive(Debug, Clone, Default)]
+pub struct ColumnSelection {
+    pub include: Option<Regex>,
+    pub exclude: Option<Regex>,
+}
+
+impl ColumnSelection {
+    /// Whether every column is kept
+    pub fn is_empty(&self) -> bool {
+        self.include.is_none() && self.exclude.is_none()
+    }
+
+    /// Whether the column named `name` is kept
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use parquet_consolidator::ColumnSelection;
+    /// use regex::Regex;
+    ///
+    /// let selection = ColumnSelection {
+    ///     include: Some(Regex::new("^metric_").unwrap()),
+    ///     exclude: Some(Regex::new("_debug$").unwrap()),
+    /// };
+    /// assert!(selection.keeps("metric_latency"));
+    /// assert!(!selection.keeps("metric_latency_debug"));
+    /// assert!(!selection.keeps("host"));
+    /// ```
+    pub fn keeps(&self, name: &str) -> bool {
+        self.include.as_ref().is_none_or(|include| include.is_match(name))
+            && !self.exclude.as_ref().is_some_and(|exclude| exclude.is_match(name))
+    }
+
+    /// Project a scan onto the kept columns, in their original order
+    ///
+    /// The projection is pushed down into the scan, so the dropped columns
+    /// of parquet inputs are never decoded. Fails when no column is kept.
+    pub fn apply(&self, scan: LazyFrame) -> Result<LazyFrame> {
+        if self.is_empty() {
+            return Ok(scan);
+        }
+        let schema = scan.schema().context("Failed to resolve the schema")?;
+        let kept: Vec<Expr> = schema.iter_names().filter(|name| self.keeps(name)).map(|name| col(name)).collect();
+        if kept.is_empty() {
+            anyhow::bail!("None of the {} columns match the column selection", schema.len());
+        }
+        Ok(scan.select(kept))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_column_selection() -> Result<()> {
+        let df = df!(
+            "host" => ["a", "b"],
+            "metric_cpu" => [0.5, 0.7],
+            "metric_cpu_debug" => [1, 2],
+            "metric_mem" => [10, 20]
+        )?;
+
+        let selection = ColumnSelection { include: Some(Regex::new("^metric_")?), exclude: Some(Regex::new("_debug$")?) };
+        let projected = selection.apply(df.clone().lazy())?.collect()?;
+        assert_eq!(projected.get_column_names(), ["metric_cpu", "metric_mem"]);
+
+        let dropping = ColumnSelection { include: None, exclude: Some(Regex::new("debug")?) };
+        assert_eq!(dropping.apply(df.clone().lazy())?.collect()?.width(), 3);
+        assert_eq!(ColumnSelection::default().apply(df.clone().lazy())?.collect()?.width(), 4);
+
+        let nothing = ColumnSelection { include: Some(Regex::new("^latency")?), exclude: None };
+        assert!(nothing.apply(df.lazy()).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,670 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, SystemTime};
+use anyhow::Result;
+use crate::audit::{append_audit_record, AuditRecord};
+use crate::budget::{remaining_inputs_path, write_remaining_inputs};
+use crate::compaction::{
+    execute_compaction, plan_compaction, plan_recompaction, CompactionPlan, CompactionPolicy, CompactionSummary,
+};
+use crate::consolidator::{ConsolidateOptions, RunSummary};
+use crate::datasets::{run_datasets, DatasetConfig, DatasetOutcome};
+use crate::discovery::{discover_input_files, listed_input_files, DiscoveryOptions, OutputInInputPolicy};
+use crate::duplicate_inputs::{collapse_duplicate_inputs, DuplicateInputs};
+use crate::health::HealthState;
+use crate::jobs::{
+    mirrored_jobs, plan_partition_overwrite, run_jobs, subdirectory_jobs, ConsolidationJob, PartitionOverwrite,
+    PartitionReplacement, MIRRORED_FILE_NAME,
+};
+use crate::manifest::OutputManifest;
+use crate::naming::{rename_by_range, IfExists, OutputNamer};
+use crate::runs::{new_run_id, run_directory, update_latest};
+use crate::sortedness::check_sorted;
+use crate::split::split_by_rows;
+use crate::timings::{Phase, PhaseClock, PhaseTiming};
+use crate::versions::{record_version, version_log_path, DatasetVersion};
+use crate::warnings::Warning;
+
+/// How a consolidate command splits its inputs into outputs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum JobLayout {
+    /// Every input into the one output
+    #[default]
+    Single,
+    /// One output per immediate subdirectory of the input; see
+    /// [`subdirectory_jobs`]
+    PerDirectory,
+    /// One output per directory of input files, mirroring the input tree;
+    /// see [`mirrored_jobs`]
+    Mirror,
+}
+
+/// The command line's `consolidate` subcommand as a library entry point:
+/// where the inputs are found, where the outputs go and what is done with
+/// them once written, around the [`ConsolidateOptions`] of each output
+///
+/// [`plan`](Self::plan) finds the jobs and [`execute`](Self::execute) runs
+/// them; in between, the options can be built for the jobs found and
+/// existing outputs confirmed.
+///
+/// # Examples
+///
+/// ```
+/// use parquet_consolidator::{ConsolidateCommand, ConsolidateOptions, DefaultNamer};
+/// use parquet_consolidator::test_utils::create_test_parquet_file;
+/// use tempfile::TempDir;
+///
+/// let temp_dir = TempDir::new().unwrap();
+/// let input = temp_dir.path().join("input");
+/// std::fs::create_dir(&input).unwrap();
+/// create_test_parquet_file(&input.join("a.parquet"), 0, 10).unwrap();
+/// create_test_parquet_file(&input.join("b.parquet"), 10, 15).unwrap();
+///
+/// let command = ConsolidateCommand {
+///     input: Some(input),
+///     output: temp_dir.path().join("out.parquet"),
+///     ..Default::default()
+/// };
+/// let plan = command.plan(&DefaultNamer).unwrap();
+/// let outcome = command.execute(plan, &ConsolidateOptions::default(), 1, &DefaultNamer, &mut |_| Ok(())).unwrap();
+/// assert_eq!(outcome.completed[0].summary.rows_written, 15);
+/// assert_eq!(outcome.written(), [temp_dir.path().join("out.parquet")]);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ConsolidateCommand {
+    /// The input file or directory; ignored when `input_list` is set
+    pub input: Option<PathBuf>,
+    /// Input files given one by one, as by `--files-from`
+    pub input_list: Option<Vec<PathBuf>>,
+    /// The output file, or directory with a layout other than
+    /// [`JobLayout::Single`]
+    pub output: PathBuf,
+    pub layout: JobLayout,
+    pub discovery: DiscoveryOptions,
+    /// What to do when the output lies inside the input directory
+    pub output_in_input: OutputInInputPolicy,
+    /// Write the run into its own directory under `output`, pointing
+    /// `LATEST` at it once the run succeeds
+    pub run_id_dirs: bool,
+    /// Read only the first of inputs with identical contents
+    pub skip_duplicate_inputs: bool,
+    /// What to do with outputs that already exist; not applied with
+    /// `name_by_range` or `partition_overwrite`, which decide for themselves
+    pub if_exists: IfExists,
+    /// How mirrored outputs replace the data files already in `output`
+    pub partition_overwrite: Option<PartitionOverwrite>,
+    /// Split each output into files of at most this many rows
+    pub max_rows_per_file: Option<usize>,
+    /// Rename each output after the range of this column it holds
+    pub name_by_range: Option<String>,
+    /// Fail each job whose output isn't sorted by this column
+    pub assert_sorted_by: Option<String>,
+    /// Record a dataset version for the run's outputs
+    pub versioned: bool,
+    /// Record the outputs, with their sizes and digests, in this manifest
+    pub manifest: Option<PathBuf>,
+    /// Where the inputs left unread at the deadline are listed, instead
+    /// of next to the output
+    pub remaining_inputs: Option<PathBuf>,
+}
+
+/// The jobs a [`ConsolidateCommand`] runs, and what it found planning them
+#[derive(Debug)]
+pub struct CommandPlan {
+    pub jobs: Vec<ConsolidationJob>,
+    /// The run's directory under the output, with `run_id_dirs`
+    pub run_id: Option<String>,
+    /// Identical inputs of which only the first is read, with
+    /// `skip_duplicate_inputs`
+    pub duplicate_inputs: Vec<DuplicateInputs>,
+    /// Outputs of jobs left out as they already exist
+    pub existing_outputs: Vec<PathBuf>,
+    /// Data files the outputs supersede, with `partition_overwrite`
+    pub partition_replacement: Option<PartitionReplacement>,
+    /// Time spent finding the inputs
+    pub discovery_timing: PhaseTiming,
+}
+
+impl CommandPlan {
+    /// Existing files the run overwrites or supersedes
+    pub fn replaced_files(&self) -> Vec<PathBuf> {
+        let mut replaced: Vec<PathBuf> =
+            self.jobs.iter().map(|job| job.output_path.clone()).filter(|path| path.is_file()).collect();
+        if let Some(replacement) = &self.partition_replacement {
+            replaced.extend(replacement.files());
+        }
+        replaced
+    }
+}
+
+/// What a [`ConsolidateCommand`] wrote
+#[derive(Debug, Default)]
+pub struct CommandOutcome {
+    /// The jobs that wrote outputs, in job order
+    pub completed: Vec<CompletedJob>,
+    /// Jobs that failed, or whose output failed its sort check
+    pub failures: Vec<anyhow::Error>,
+    /// Planned outputs of jobs the deadline passed before they started
+    pub deferred: Vec<PathBuf>,
+    /// Outputs split by `max_rows_per_file`, with how many files each
+    /// became
+    pub split: Vec<(PathBuf, usize)>,
+    /// Outputs left as they were, as their range-named path already existed
+    pub existing_range_names: Vec<PathBuf>,
+    /// Inputs left unread as the deadline passed
+    pub remaining: Vec<PathBuf>,
+    /// Where `remaining` was listed for the next run, with a deadline
+    pub remaining_list: Option<PathBuf>,
+    /// The dataset version recorded, with `versioned`, and its log
+    pub version: Option<(DatasetVersion, PathBuf)>,
+    /// Whether the run became the latest, with `run_id_dirs`
+    pub latest_updated: bool,
+    /// Superseded data files deleted, with `partition_overwrite`
+    pub deleted: Vec<PathBuf>,
+}
+
+/// A job of a [`ConsolidateCommand`] that wrote its outputs
+#[derive(Debug)]
+pub struct CompletedJob {
+    /// The job's summary, whose `output_path` is its first output
+    pub summary: RunSummary,
+    /// Every file written, more than one when split
+    pub outputs: Vec<PathBuf>,
+}
+
+impl CommandOutcome {
+    /// Every file written, in job order
+    pub fn written(&self) -> Vec<PathBuf> {
+        self.completed.iter().flat_map(|job| job.outputs.iter().cloned()).collect()
+    }
+
+    /// Rows written by every job
+    pub fn rows_written(&self) -> u64 {
+        self.completed.iter().map(|job| job.summary.rows_written as u64).sum()
+    }
+}
+
+impl ConsolidateCommand {
+    fn is_directory(&self) -> bool {
+        self.layout != JobLayout::Single || self.run_id_dirs
+    }
+
+    /// Find the inputs and plan the jobs consolidating them, naming the
+    /// outputs of a directory layout with `namer`
+    pub fn plan(&self, namer: &dyn OutputNamer) -> Result<CommandPlan> {
+        let run_id = if self.run_id_dirs { Some(new_run_id()?) } else { None };
+        let output = match &run_id {
+            Some(run_id) if self.layout != JobLayout::Single => run_directory(&self.output, run_id),
+            Some(run_id) => run_directory(&self.output, run_id).join(MIRRORED_FILE_NAME),
+            None => self.output.clone(),
+        };
+
+        let mut discovery = self.discovery.clone();
+        if let Some(input) = &self.input {
+            discovery.check_output_location(input, &self.output, self.output_in_input)?;
+        }
+        let discovery_clock = PhaseClock::start();
+        let mut jobs = match (&self.input_list, &self.input) {
+            (Some(paths), _) => {
+                let input_files = listed_input_files(paths, &discovery)?;
+                if input_files.is_empty() {
+                    anyhow::bail!("No input files found in the file list");
+                }
+                vec![ConsolidationJob { name: String::new(), input_files, output_path: output.clone() }]
+            }
+            (None, None) => anyhow::bail!("An input path or list of input files is required"),
+            (None, Some(input)) => match self.layout {
+                JobLayout::PerDirectory => {
+                    let jobs = subdirectory_jobs(input, &output, &discovery, namer)?;
+                    if jobs.is_empty() {
+                        anyhow::bail!("No subdirectories with input files found in {:?}", input);
+                    }
+                    jobs
+                }
+                JobLayout::Mirror => {
+                    let jobs = mirrored_jobs(input, &output, &discovery, namer)?;
+                    if jobs.is_empty() {
+                        anyhow::bail!("No {} files found in {:?}", discovery.format_names(), input);
+                    }
+                    jobs
+                }
+                JobLayout::Single => {
+                    let input_files = discover_input_files(input, &discovery)?;
+                    if input_files.is_empty() {
+                        anyhow::bail!("No {} files found in the specified directory", discovery.format_names());
+                    }
+                    vec![ConsolidationJob { name: String::new(), input_files, output_path: output.clone() }]
+                }
+            },
+        };
+        let mut duplicate_inputs = Vec::new();
+        if self.skip_duplicate_inputs {
+            for job in &mut jobs {
+                let (input_files, collapsed) = collapse_duplicate_inputs(&job.input_files)?;
+                duplicate_inputs.extend(collapsed);
+                job.input_files = input_files;
+            }
+        }
+        let discovery_timing = discovery_clock.stop();
+
+        let partition_replacement = match self.partition_overwrite {
+            Some(mode) => Some(plan_partition_overwrite(&mut jobs, &output, mode, namer)?),
+            None => None,
+        };
+        // With name_by_range the final name is only known after writing, and
+        // partition_overwrite has already decided what happens to existing files
+        let mut existing_outputs = Vec::new();
+        if self.name_by_range.is_none() && partition_replacement.is_none() {
+            jobs.retain_mut(|job| match self.if_exists.resolve(&job.output_path) {
+                Some(output_path) => {
+                    job.output_path = output_path;
+                    true
+                }
+                None => {
+                    existing_outputs.push(job.output_path.clone());
+                    false
+                }
+            });
+        }
+
+        Ok(CommandPlan { jobs, run_id, duplicate_inputs, existing_outputs, partition_replacement, discovery_timing })
+    }
+
+    /// Run the planned jobs `parallelism` at a time, then split, rename and
+    /// check their outputs, record the run and clean up what it replaced
+    ///
+    /// `finish_output` is called on each output before it is recorded in
+    /// the manifest and version log, such as to stamp it. Failed jobs are
+    /// collected on the outcome; whatever fails around them, such as
+    /// writing the manifest, fails the command.
+    pub fn execute(
+        &self,
+        plan: CommandPlan,
+        options: &ConsolidateOptions,
+        parallelism: usize,
+        namer: &dyn OutputNamer,
+        finish_output: &mut dyn FnMut(&Path) -> Result<()>,
+    ) -> Result<CommandOutcome> {
+        if self.is_directory() {
+            for job in &plan.jobs {
+                if let Some(parent) = job.output_path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+            }
+        }
+
+        let mut outcome = CommandOutcome::default();
+        let job_count = plan.jobs.len();
+        // Discovery's warnings go with the first output
+        let mut discovery_warnings: Vec<Warning> = self.discovery.warnings.take();
+        let mut versioned_inputs: Vec<PathBuf> = match self.versioned {
+            true => plan.jobs.iter().flat_map(|job| job.input_files.clone()).collect(),
+            false => Vec::new(),
+        };
+        for (job, result) in plan.jobs.iter().zip(run_jobs(&plan.jobs, options, parallelism)) {
+            let mut summary = match result {
+                Ok(summary) => summary,
+                Err(err) => {
+                    outcome.failures.push(err);
+                    continue;
+                }
+            };
+            outcome.remaining.extend(summary.remaining_files.iter().cloned());
+            if summary.is_deferred() {
+                outcome.deferred.push(job.output_path.clone());
+                continue;
+            }
+            if let (1, Some(timings)) = (job_count, &mut summary.timings) {
+                timings.record(Phase::Discovery, plan.discovery_timing);
+            }
+            summary.warnings.splice(0..0, std::mem::take(&mut discovery_warnings));
+
+            let parts = match self.max_rows_per_file {
+                Some(max_rows) => {
+                    let parts = split_by_rows(&job.output_path, max_rows, &options.writer, namer)?;
+                    if parts.len() > 1 {
+                        outcome.split.push((job.output_path.clone(), parts.len()));
+                    }
+                    parts
+                }
+                None => vec![job.output_path.clone()],
+            };
+            let mut outputs = Vec::new();
+            for part in parts {
+                match &self.name_by_range {
+                    Some(column) => match rename_by_range(&part, column, self.if_exists, namer)? {
+                        Some(output_path) => outputs.push(output_path),
+                        None => outcome.existing_range_names.push(part),
+                    },
+                    None => outputs.push(part),
+                }
+            }
+            if outputs.is_empty() {
+                continue;
+            }
+            if let Some(column) = &self.assert_sorted_by {
+                let unsorted = outputs.iter().find_map(|output| match check_sorted(output, column) {
+                    Ok(violation) => violation.map(|violation| anyhow::anyhow!("{}", violation)),
+                    Err(err) => Some(err),
+                });
+                if let Some(err) = unsorted {
+                    outcome.failures.push(err);
+                    continue;
+                }
+            }
+            for output in &outputs {
+                finish_output(output)?;
+            }
+            summary.output_path = outputs.first().cloned();
+            outcome.completed.push(CompletedJob { summary, outputs });
+        }
+
+        let written = outcome.written();
+        let is_directory = self.is_directory();
+        if options.deadline.is_some() {
+            let list = self.remaining_inputs.clone().unwrap_or_else(|| remaining_inputs_path(&self.output, is_directory));
+            write_remaining_inputs(&list, &outcome.remaining)?;
+            outcome.remaining_list = Some(list);
+        }
+
+        if self.versioned && outcome.failures.is_empty() && !outcome.completed.is_empty() {
+            versioned_inputs.retain(|input| !outcome.remaining.contains(input));
+            let log = version_log_path(&self.output, is_directory);
+            let version = record_version(&log, &versioned_inputs, &written, outcome.rows_written())?;
+            outcome.version = Some((version, log));
+        }
+
+        if let Some(run_id) = &plan.run_id {
+            if outcome.failures.is_empty() && outcome.remaining.is_empty() {
+                update_latest(&self.output, run_id)?;
+                outcome.latest_updated = true;
+            }
+        }
+
+        if let Some(replacement) = &plan.partition_replacement {
+            outcome.deleted = replacement.remove(&written, outcome.failures.is_empty())?;
+        }
+
+        if let Some(manifest) = self.manifest.as_ref().filter(|_| !written.is_empty()) {
+            OutputManifest::of(&written)?.save(manifest)?;
+        }
+        Ok(outcome)
+    }
+}
+
+/// The command line's `compact` subcommand as a library entry point
+///
+/// [`plan`](Self::plan) finds the compactions and deletions the policy
+/// requires and [`execute`](Self::execute) carries them out, so a caller
+/// can show the plan and ask for confirmation in between.
+#[derive(Debug, Clone)]
+pub struct CompactCommand {
+    /// The dataset's root directory
+    pub input: PathBuf,
+    pub policy: CompactionPolicy,
+    /// Archives are never read, as the files inside them can't be
+    /// rewritten or deleted in place
+    pub discovery: DiscoveryOptions,
+    /// Merge the small outputs of earlier compactions into larger
+    /// generations instead of compacting small files
+    pub recompact: bool,
+    /// With `recompact`, manifests of consolidation outputs to merge as
+    /// generation 1
+    pub manifests: Vec<OutputManifest>,
+    /// Append a record of the compaction, including every deleted file, to
+    /// this JSON-lines file
+    pub audit_log: Option<PathBuf>,
+}
+
+impl CompactCommand {
+    /// Plan the compactions and deletions due at `now`
+    pub fn plan(&self, now: SystemTime) -> Result<CompactionPlan> {
+        let discovery = DiscoveryOptions { read_archives: false, ..self.discovery.clone() };
+        match self.recompact {
+            true => plan_recompaction(&self.input, &self.policy, &discovery, &self.manifests, now),
+            false => plan_compaction(&self.input, &self.policy, &discovery, now),
+        }
+    }
+
+    /// Run the compactions of `plan`, up to `parallelism` at once, and
+    /// delete the files it replaces or expires
+    ///
+    /// With an audit log, a record of the planned inputs, the outputs and
+    /// the files actually deleted is appended whether or not the
+    /// compaction succeeded.
+    pub fn execute(&self, plan: &CompactionPlan, options: &ConsolidateOptions, parallelism: usize) -> Result<CompactionSummary> {
+        let record = match &self.audit_log {
+            Some(_) => {
+                let mut record = AuditRecord::new("compact");
+                let planned = plan.tasks.iter().flat_map(|task| &task.input_files).chain(&plan.expired);
+                record.inputs = AuditRecord::file_records(planned)?;
+                Some(record)
+            }
+            None => None,
+        };
+
+        let result = execute_compaction(plan, options, parallelism);
+
+        if let (Some(mut record), Some(log)) = (record, &self.audit_log) {
+            record.outputs = AuditRecord::file_records(plan.tasks.iter().map(|task| &task.output_path))?;
+            record.deleted = record.inputs.iter().map(|input| input.path.clone()).filter(|path| !path.exists()).collect();
+            match &result {
+                Ok(summary) => record.rows_written = summary.rows_written as u64,
+                Err(err) => record.errors.push(format!("{:#}", err)),
+            }
+            append_audit_record(log, record)?;
+        }
+        result
+    }
+}
+
+/// The command line's `run` subcommand as a library entry point
+///
+/// [`run_once`](Self::run_once) runs the selected datasets that are due;
+/// [`watch`](Self::watch) keeps running them as a service, recording each
+/// pass on a [`HealthState`].
+#[derive(Debug, Clone)]
+pub struct RunCommand {
+    pub config: DatasetConfig,
+    /// The dataset to run, or [`ALL_DATASETS`](crate::datasets::ALL_DATASETS)
+    pub selection: String,
+    /// Run the selected datasets even when they aren't due; not applied
+    /// when watching
+    pub force: bool,
+    /// When watching, the longest wait between passes; datasets without a
+    /// schedule run this often
+    pub poll_interval: Duration,
+}
+
+impl RunCommand {
+    /// Run the selected datasets once, with up to `parallelism` outputs of
+    /// each written at once
+    pub fn run_once(&self, options: &ConsolidateOptions, parallelism: usize) -> Result<Vec<(String, DatasetOutcome)>> {
+        run_datasets(&self.config, &self.selection, options, parallelism, self.force, SystemTime::now())
+    }
+
+    /// Run one pass over the datasets due, handing the outcomes to `report`
+    /// and recording the pass on `health`, which it marks ready; returns
+    /// how long to wait before the next pass
+    pub fn watch_pass<F>(
+        &self,
+        options: &ConsolidateOptions,
+        parallelism: usize,
+        health: &HealthState,
+        report: &mut F,
+    ) -> Duration
+    where
+        F: FnMut(&Result<Vec<(String, DatasetOutcome)>>),
+    {
+        let poll_interval = self.poll_interval.max(Duration::from_secs(1));
+        let outcomes = run_datasets(&self.config, &self.selection, options, parallelism, false, SystemTime::now());
+        report(&outcomes);
+        let wait = match &outcomes {
+            Ok(outcomes) => {
+                health.record_pass(!outcomes.iter().any(|(_, outcome)| outcome.is_failed()));
+                outcomes
+                    .iter()
+                    .filter_map(|(_, outcome)| match outcome {
+                        DatasetOutcome::NotDue(due_in) => Some(*due_in),
+                        _ => None,
+                    })
+                    .fold(poll_interval, Duration::min)
+            }
+            Err(_) => {
+                health.record_pass(false);
+                poll_interval
+            }
+        };
+        health.mark_ready();
+        wait
+    }
+
+    /// Run a pass over the datasets whenever one is due, forever; see
+    /// [`watch_pass`](Self::watch_pass)
+    pub fn watch<F>(
+        &self,
+        options: &ConsolidateOptions,
+        parallelism: usize,
+        health: &HealthState,
+        report: &mut F,
+    ) -> !
+    where
+        F: FnMut(&Result<Vec<(String, DatasetOutcome)>>),
+    {
+        loop {
+            let wait = self.watch_pass(options, parallelism, health, report);
+            thread::sleep(wait);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::naming::DefaultNamer;
+    use crate::test_utils::create_test_parquet_file;
+
+    #[test]
+    fn test_consolidate_command() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let input = temp_dir.path().join("input");
+        for (day, start) in [("day=1", 0), ("day=2", 10)] {
+            fs::create_dir_all(input.join(day))?;
+            create_test_parquet_file(&input.join(day).join("a.parquet"), start, start + 10)?;
+            create_test_parquet_file(&input.join(day).join("b.parquet"), start + 100, start + 105)?;
+        }
+        let output = temp_dir.path().join("warehouse");
+        let command = ConsolidateCommand {
+            input: Some(input.clone()),
+            output: output.clone(),
+            layout: JobLayout::Mirror,
+            discovery: DiscoveryOptions { recursive: true, ..Default::default() },
+            versioned: true,
+            manifest: Some(temp_dir.path().join("manifest.json")),
+            ..Default::default()
+        };
+
+        let plan = command.plan(&DefaultNamer)?;
+        assert_eq!(plan.jobs.len(), 2);
+        assert!(plan.replaced_files().is_empty());
+        let mut finished = Vec::new();
+        let outcome = command.execute(plan, &ConsolidateOptions::default(), 2, &DefaultNamer, &mut |output| {
+            finished.push(output.to_path_buf());
+            Ok(())
+        })?;
+        assert!(outcome.failures.is_empty());
+        assert_eq!(outcome.rows_written(), 30);
+        assert_eq!(finished, outcome.written());
+        assert_eq!(outcome.written(), [output.join("day=1").join(MIRRORED_FILE_NAME), output.join("day=2").join(MIRRORED_FILE_NAME)]);
+        assert_eq!(outcome.version.as_ref().map(|(version, _)| version.version), Some(1));
+        assert!(temp_dir.path().join("manifest.json").is_file());
+
+        // A second run replaces the outputs that are now there, or skips them
+        let command = ConsolidateCommand { versioned: false, manifest: None, ..command };
+        assert_eq!(command.plan(&DefaultNamer)?.replaced_files(), outcome.written());
+        let command = ConsolidateCommand { if_exists: IfExists::Skip, ..command };
+        let plan = command.plan(&DefaultNamer)?;
+        assert!(plan.jobs.is_empty());
+        assert_eq!(plan.existing_outputs, outcome.written());
+
+        // Each partition's 15 rows are split into two files
+        let command = ConsolidateCommand { output: temp_dir.path().join("split"), max_rows_per_file: Some(10), ..command };
+        let plan = command.plan(&DefaultNamer)?;
+        let outcome = command.execute(plan, &ConsolidateOptions::default(), 1, &DefaultNamer, &mut |_| Ok(()))?;
+        assert_eq!(outcome.split.len(), 2);
+        assert_eq!(outcome.written().len(), 4);
+
+        let command = ConsolidateCommand { input: None, ..command };
+        assert!(command.plan(&DefaultNamer).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_compact_command() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let input = temp_dir.path().join("table");
+        fs::create_dir(&input)?;
+        for i in 0..3 {
+            create_test_parquet_file(&input.join(format!("part-{}.parquet", i)), i * 10, i * 10 + 10)?;
+        }
+        let audit_log = temp_dir.path().join("audit.jsonl");
+        let command = CompactCommand {
+            input: input.clone(),
+            policy: CompactionPolicy::from_toml_str("small_file_bytes = 1000000\ntarget_file_bytes = 100000000")?,
+            discovery: DiscoveryOptions::default(),
+            recompact: false,
+            manifests: Vec::new(),
+            audit_log: Some(audit_log.clone()),
+        };
+
+        let plan = command.plan(SystemTime::now())?;
+        assert_eq!(plan.tasks.len(), 1);
+        let summary = command.execute(&plan, &ConsolidateOptions::default(), 1)?;
+        assert_eq!(summary.rows_written, 30);
+        assert!(!input.join("part-0.parquet").exists());
+
+        // The audit record lists the deleted inputs
+        assert_eq!(crate::audit::verify_audit_log(&audit_log)?.records, 1);
+        assert!(fs::read_to_string(&audit_log)?.contains("part-2.parquet"));
+        assert!(command.plan(SystemTime::now())?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_run_command_watch_pass() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let input = temp_dir.path().join("landing");
+        fs::create_dir(&input)?;
+        create_test_parquet_file(&input.join("a.parquet"), 0, 10)?;
+        let config = DatasetConfig::from_toml_str(&format!(
+            "[datasets.orders]\ninput = {:?}\noutput = {:?}\nschedule = \"1h\"\n",
+            input,
+            temp_dir.path().join("orders.parquet")
+        ))?;
+        let command = RunCommand {
+            config,
+            selection: crate::datasets::ALL_DATASETS.to_string(),
+            force: false,
+            poll_interval: Duration::from_secs(60),
+        };
+        let health = HealthState::new(None);
+        let options = ConsolidateOptions::default();
+
+        let mut reported = Vec::new();
+        let wait = command.watch_pass(&options, 1, &health, &mut |outcomes| {
+            reported.extend(outcomes.as_ref().unwrap().iter().map(|(_, outcome)| outcome.to_string()));
+        });
+        assert_eq!(wait, Duration::from_secs(60));
+        assert!(health.is_ready());
+        assert!(temp_dir.path().join("orders.parquet").exists());
+
+        let outcomes = command.run_once(&options, 1)?;
+        assert!(!outcomes[0].1.is_failed());
+        assert_eq!(reported.len(), 1);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,584 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use anyhow::{Result, Context};
+use serde::Deserialize;
+use crate::consolidator::ConsolidateOptions;
+use crate::discovery::{walk_input_files, DiscoveryOptions};
+use crate::file_info::FileInfo;
+use crate::footer::{edit_key_value_metadata, read_key_value_metadata, MetadataEdit};
+use crate::jobs::{run_jobs, ConsolidationJob};
+use crate::manifest::OutputManifest;
+use crate::workspace::Workspace;
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Footer key recording the compaction generation of a file: 1 for files
+/// compacted from uncompacted ones, and one more than their inputs' for
+/// files merged from earlier compactions' outputs
+pub const COMPACTION_GENERATION_KEY: &str = "parquet_consolidator.compaction_generation";
+
+/// Rules deciding which files of a dataset get compacted or expired
+///
+/// # Examples
+///
+/// ```
+/// use parquet_consolidator::CompactionPolicy;
+///
+/// let policy = CompactionPolicy::from_toml_str(r#"
+///     small_file_bytes = 1048576
+///     target_file_bytes = 134217728
+///     retention_days = 90
+/// "#).unwrap();
+/// assert_eq!(policy.min_files, 2);
+/// assert!(policy.partition_aware);
+/// ```
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CompactionPolicy {
+    /// Files smaller than this are compacted
+    pub small_file_bytes: u64,
+    /// Small files are packed into outputs of up to this many input bytes
+    pub target_file_bytes: u64,
+    /// Groups with fewer small files than this are left alone
+    #[serde(default = "default_min_files")]
+    pub min_files: usize,
+    /// Compact each directory separately, keeping partition layouts such as
+    /// `year=2024/` intact; otherwise the whole tree is compacted into its root
+    #[serde(default = "default_partition_aware")]
+    pub partition_aware: bool,
+    /// Files last modified more than this many days ago are deleted rather
+    /// than compacted
+    pub retention_days: Option<u64>,
+    /// When recompacting, each generation's outputs hold up to this many
+    /// times the input bytes of the generation before
+    #[serde(default = "default_generation_growth")]
+    pub generation_growth: u64,
+}
+
+fn default_min_files() -> usize {
+    2
+}
+
+fn default_partition_aware() -> bool {
+    true
+}
+
+fn default_generation_growth() -> u64 {
+    4
+}
+
+impl CompactionPolicy {
+    pub fn from_toml_str(s: &str) -> Result<Self> {
+        let policy: CompactionPolicy = toml::from_str(s).context("Invalid compaction policy")?;
+        if policy.target_file_bytes < policy.small_file_bytes {
+            anyhow::bail!("Compaction policy target_file_bytes must be at least small_file_bytes");
+        }
+        if policy.generation_growth == 0 {
+            anyhow::bail!("Compaction policy generation_growth must be at least 1");
+        }
+        Ok(policy)
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path).context(format!("Failed to read compaction policy {:?}", path))?;
+        Self::from_toml_str(&contents)
+    }
+
+    fn is_expired(&self, file: &FileInfo, now: SystemTime) -> bool {
+        self.retention_days.is_some_and(|days| {
+            now.duration_since(file.modified)
+                .is_ok_and(|age| age > Duration::from_secs(days * SECONDS_PER_DAY))
+        })
+    }
+
+    /// Input bytes an output of `generation` may hold
+    fn generation_target_bytes(&self, generation: u32) -> u64 {
+        let growth = self.generation_growth.saturating_pow(generation.saturating_sub(1));
+        self.target_file_bytes.saturating_mul(growth)
+    }
+}
+
+/// One planned compaction: small files merged into a new file next to them
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompactionTask {
+    pub input_files: Vec<PathBuf>,
+    pub input_bytes: u64,
+    pub output_path: PathBuf,
+    /// The compaction generation recorded in the output's footer
+    pub generation: u32,
+}
+
+/// The work a policy requires for a dataset
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CompactionPlan {
+    pub tasks: Vec<CompactionTask>,
+    /// Files past the retention period, to be deleted
+    pub expired: Vec<PathBuf>,
+}
+
+impl CompactionPlan {
+    pub fn is_empty(&self) -> bool {
+        self.tasks.is_empty() && self.expired.is_empty()
+    }
+}
+
+impl fmt::Display for CompactionPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "Nothing to compact");
+        }
+        for task in &self.tasks {
+            writeln!(
+                f,
+                "Compact {} files ({} bytes) into {} (generation {})",
+                task.input_files.len(),
+                task.input_bytes,
+                task.output_path.display(),
+                task.generation
+            )?;
+        }
+        for path in &self.expired {
+            writeln!(f, "Delete expired {}", path.display())?;
+        }
+        Ok(())
+    }
+}
+
+/// The outcome of executing a compaction plan
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CompactionSummary {
+    pub files_compacted: usize,
+    pub files_written: usize,
+    pub rows_written: usize,
+    pub files_expired: usize,
+}
+
+impl fmt::Display for CompactionSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Files compacted: {}", self.files_compacted)?;
+        writeln!(f, "Files written: {}", self.files_written)?;
+        writeln!(f, "Rows written: {}", self.rows_written)?;
+        writeln!(f, "Expired files deleted: {}", self.files_expired)
+    }
+}
+
+/// Plan the compactions and deletions `policy` requires under `input_dir`
+///
+/// Discovered files are grouped by directory when the policy is
+/// partition-aware, or into a single group otherwise. Within a group, files
+/// past the retention period are expired, and the remaining small files are
+/// packed in name order into outputs of at most `target_file_bytes` input
+/// bytes. Groups with fewer than `min_files` small files, and outputs that
+/// would hold a single file, are skipped.
+pub fn plan_compaction(
+    input_dir: &Path,
+    policy: &CompactionPolicy,
+    discovery: &DiscoveryOptions,
+    now: SystemTime,
+) -> Result<CompactionPlan> {
+    let mut plan = CompactionPlan::default();
+    for (directory, files) in group_files(input_dir, policy, discovery)? {
+        let (expired, live): (Vec<FileInfo>, Vec<FileInfo>) =
+            files.into_iter().partition(|file| policy.is_expired(file, now));
+        plan.expired.extend(expired.into_iter().map(|file| file.path));
+
+        let small: Vec<(PathBuf, u64)> = live
+            .into_iter()
+            .filter(|file| file.size < policy.small_file_bytes)
+            .map(|file| (file.path, file.size))
+            .collect();
+        if small.len() < policy.min_files.max(2) {
+            continue;
+        }
+
+        let mut outputs = OutputNames::new(&directory);
+        for batch in pack(small, policy.target_file_bytes) {
+            if batch.len() < 2 {
+                continue;
+            }
+            let generation = batch.iter().map(|(path, _)| compaction_generation(path)).max().unwrap_or(0) + 1;
+            plan.tasks.push(compaction_task(batch, outputs.next(), generation));
+        }
+    }
+
+    Ok(plan)
+}
+
+/// Plan merging the outputs of earlier compactions under `input_dir` into
+/// larger generations, like the levels of an LSM tree
+///
+/// Only files whose footer records a [`COMPACTION_GENERATION_KEY`], and
+/// consolidated outputs listed in one of `manifests` (as written by the
+/// `--manifest` of a consolidation), which count as generation 1, are
+/// considered. The outputs of many small runs can so be merged without
+/// touching uncompacted files. They are grouped by directory as in
+/// [`plan_compaction`], then by generation. Files of generation N smaller
+/// than the target of generation N + 1, `target_file_bytes` times
+/// `generation_growth` to the power N, are packed in name order into
+/// outputs of generation N + 1 of up to that many input bytes. Generations
+/// with fewer than `min_files` such files, and outputs that would hold a
+/// single file, are skipped. Retention is left to [`plan_compaction`].
+///
+/// # Examples
+///
+/// ```
+/// use std::time::SystemTime;
+/// use parquet_consolidator::{plan_recompaction, CompactionPolicy, DiscoveryOptions, OutputManifest};
+/// use parquet_consolidator::test_utils::create_test_parquet_file;
+/// use tempfile::TempDir;
+///
+/// let temp_dir = TempDir::new().unwrap();
+/// create_test_parquet_file(&temp_dir.path().join("part-0.parquet"), 0, 10).unwrap();
+/// create_test_parquet_file(&temp_dir.path().join("part-1.parquet"), 10, 20).unwrap();
+///
+/// // Files no compaction wrote are left to `plan_compaction`
+/// let policy = CompactionPolicy::from_toml_str("small_file_bytes = 1000000\ntarget_file_bytes = 1000000").unwrap();
+/// let discovery = DiscoveryOptions::default();
+/// let plan = plan_recompaction(temp_dir.path(), &policy, &discovery, &[], SystemTime::now()).unwrap();
+/// assert!(plan.is_empty());
+///
+/// // unless a manifest lists them as a consolidation's outputs
+/// let manifest = OutputManifest::of(&[temp_dir.path().join("part-0.parquet"), temp_dir.path().join("part-1.parquet")]).unwrap();
+/// let plan = plan_recompaction(temp_dir.path(), &policy, &discovery, &[manifest], SystemTime::now()).unwrap();
+/// assert_eq!(plan.tasks[0].generation, 2);
+/// ```
+pub fn plan_recompaction(
+    input_dir: &Path,
+    policy: &CompactionPolicy,
+    discovery: &DiscoveryOptions,
+    manifests: &[OutputManifest],
+    now: SystemTime,
+) -> Result<CompactionPlan> {
+    // Manifests may record paths relative to wherever the run was started
+    let listed: HashSet<PathBuf> = manifests
+        .iter()
+        .flat_map(|manifest| &manifest.outputs)
+        .filter_map(|output| fs::canonicalize(&output.path).ok())
+        .collect();
+    let mut plan = CompactionPlan::default();
+    for (directory, files) in group_files(input_dir, policy, discovery)? {
+        let mut generations: BTreeMap<u32, Vec<(PathBuf, u64)>> = BTreeMap::new();
+        for file in files.into_iter().filter(|file| !policy.is_expired(file, now)) {
+            let generation = match compaction_generation(&file.path) {
+                0 if fs::canonicalize(&file.path).is_ok_and(|path| listed.contains(&path)) => 1,
+                generation => generation,
+            };
+            if generation > 0 && file.size < policy.generation_target_bytes(generation + 1) {
+                generations.entry(generation).or_default().push((file.path, file.size));
+            }
+        }
+
+        // One set of names per directory, shared by its generations
+        let mut outputs = OutputNames::new(&directory);
+        for (generation, files) in generations {
+            if files.len() < policy.min_files.max(2) {
+                continue;
+            }
+            for batch in pack(files, policy.generation_target_bytes(generation + 1)) {
+                if batch.len() > 1 {
+                    plan.tasks.push(compaction_task(batch, outputs.next(), generation + 1));
+                }
+            }
+        }
+    }
+
+    Ok(plan)
+}
+
+/// Discovered files under `input_dir`, grouped by the directory their
+/// compacted outputs go to and sorted by name
+fn group_files(
+    input_dir: &Path,
+    policy: &CompactionPolicy,
+    discovery: &DiscoveryOptions,
+) -> Result<BTreeMap<PathBuf, Vec<FileInfo>>> {
+    if !input_dir.is_dir() {
+        anyhow::bail!("Compaction requires an input directory: {:?}", input_dir);
+    }
+
+    let mut groups: BTreeMap<PathBuf, Vec<FileInfo>> = BTreeMap::new();
+    for path in walk_input_files(input_dir, discovery) {
+        let file = FileInfo::stat(path?);
+        let group = match file.path.parent() {
+            Some(parent) if policy.partition_aware => parent.to_path_buf(),
+            _ => input_dir.to_path_buf(),
+        };
+        groups.entry(group).or_default().push(file);
+    }
+    for files in groups.values_mut() {
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+    }
+    Ok(groups)
+}
+
+fn compaction_task(batch: Vec<(PathBuf, u64)>, output_path: PathBuf, generation: u32) -> CompactionTask {
+    CompactionTask {
+        input_bytes: batch.iter().map(|(_, size)| size).sum(),
+        input_files: batch.into_iter().map(|(path, _)| path).collect(),
+        output_path,
+        generation,
+    }
+}
+
+/// The compaction generation recorded in a file's footer, or 0 for files
+/// no compaction wrote, including ones that aren't parquet
+pub fn compaction_generation(path: &Path) -> u32 {
+    read_key_value_metadata(path)
+        .ok()
+        .and_then(|metadata| metadata.into_iter().find(|(key, _)| key == COMPACTION_GENERATION_KEY)?.1)
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(0)
+}
+
+/// Split files into consecutive batches of at most `target_bytes` in total
+fn pack(files: Vec<(PathBuf, u64)>, target_bytes: u64) -> Vec<Vec<(PathBuf, u64)>> {
+    let mut batches: Vec<Vec<(PathBuf, u64)>> = Vec::new();
+    let mut batch_bytes = 0;
+    for (path, size) in files {
+        match batches.last_mut() {
+            Some(batch) if batch_bytes + size <= target_bytes => batch.push((path, size)),
+            _ => {
+                batches.push(vec![(path, size)]);
+                batch_bytes = 0;
+            }
+        }
+        batch_bytes += size;
+    }
+    batches
+}
+
+/// Free `compacted-NNNNN.parquet` names in a directory
+struct OutputNames {
+    directory: PathBuf,
+    next_index: usize,
+}
+
+impl OutputNames {
+    fn new(directory: &Path) -> Self {
+        OutputNames { directory: directory.to_path_buf(), next_index: 0 }
+    }
+
+    fn next(&mut self) -> PathBuf {
+        loop {
+            let path = self.directory.join(format!("compacted-{:05}.parquet", self.next_index));
+            self.next_index += 1;
+            if !path.exists() {
+                return path;
+            }
+        }
+    }
+}
+
+/// Execute a compaction plan
+///
+/// Each output is staged in a [`Workspace`] in the directory the outputs
+/// share, stamped with its generation under [`COMPACTION_GENERATION_KEY`],
+/// and moved into place before its inputs are deleted, so readers never see
+/// a partial file and a failed task leaves its inputs untouched.
+/// Expired files are deleted once every task has run. Fails after all tasks
+/// have run if any of them failed.
+pub fn execute_compaction(
+    plan: &CompactionPlan,
+    options: &ConsolidateOptions,
+    parallelism: usize,
+) -> Result<CompactionSummary> {
+    let mut summary = CompactionSummary::default();
+    let mut failures = Vec::new();
+    let outputs: Vec<&Path> = plan.tasks.iter().map(|task| task.output_path.as_path()).collect();
+    if let Some(directory) = common_directory(&outputs) {
+        let workspace = Workspace::create(&directory)?;
+        let jobs: Vec<ConsolidationJob> = plan
+            .tasks
+            .iter()
+            .map(|task| ConsolidationJob {
+                name: task.output_path.display().to_string(),
+                input_files: task.input_files.clone(),
+                output_path: workspace.stage(&task.output_path),
+            })
+            .collect();
+
+        for ((task, job), result) in plan.tasks.iter().zip(&jobs).zip(run_jobs(&jobs, options, parallelism)) {
+            let finished = result.and_then(|run| {
+                let generation = MetadataEdit::Set { key: COMPACTION_GENERATION_KEY.to_string(), value: task.generation.to_string() };
+                edit_key_value_metadata(&job.output_path, std::slice::from_ref(&generation))?;
+                #[cfg(feature = "fault-injection")]
+                crate::fault_injection::inject_fault(crate::fault_injection::FaultPoint::Commit, &task.output_path)?;
+                workspace.commit(&job.output_path, &task.output_path)?;
+                Ok(run)
+            });
+            match finished {
+                Ok(run) => {
+                    for input in &task.input_files {
+                        fs::remove_file(input).context(format!("Failed to delete compacted input {:?}", input))?;
+                    }
+                    summary.files_compacted += task.input_files.len();
+                    summary.files_written += 1;
+                    summary.rows_written += run.rows_written;
+                }
+                Err(err) => failures.push(err),
+            }
+        }
+    }
+
+    for path in &plan.expired {
+        fs::remove_file(path).context(format!("Failed to delete expired file {:?}", path))?;
+        summary.files_expired += 1;
+    }
+
+    if !failures.is_empty() {
+        let errors: Vec<String> = failures.iter().map(|err| format!("{:#}", err)).collect();
+        anyhow::bail!("{} of {} compactions failed: {}", failures.len(), plan.tasks.len(), errors.join("; "));
+    }
+    Ok(summary)
+}
+
+/// Deepest directory holding every path, if any
+fn common_directory(paths: &[&Path]) -> Option<PathBuf> {
+    let mut common = paths.first()?.parent()?.to_path_buf();
+    for path in &paths[1..] {
+        while !path.starts_with(&common) {
+            common = common.parent()?.to_path_buf();
+        }
+    }
+    Some(common)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use crate::file_info::file_size;
+    use crate::test_utils::create_test_parquet_file;
+
+    #[test]
+    fn test_plan_and_execute_compaction() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let input_dir = temp_dir.path().join("table");
+        fs::create_dir_all(input_dir.join("day=1"))?;
+        fs::create_dir_all(input_dir.join("day=2"))?;
+        for i in 0..5 {
+            create_test_parquet_file(&input_dir.join(format!("day=1/part-{}.parquet", i)), i * 10, i * 10 + 10)?;
+        }
+        create_test_parquet_file(&input_dir.join("day=2/part-0.parquet"), 0, 10)?;
+        create_test_parquet_file(&input_dir.join("day=2/part-1.parquet"), 10, 20)?;
+        let old = input_dir.join("day=2/part-old.parquet");
+        create_test_parquet_file(&old, 20, 30)?;
+        File::options().write(true).open(&old)?.set_modified(SystemTime::now() - Duration::from_secs(40 * SECONDS_PER_DAY))?;
+
+        let file_bytes = (0..5)
+            .map(|i| file_size(&input_dir.join(format!("day=1/part-{}.parquet", i))))
+            .max()
+            .unwrap();
+        let policy = CompactionPolicy {
+            small_file_bytes: file_bytes * 2,
+            target_file_bytes: file_bytes * 3,
+            min_files: 3,
+            partition_aware: true,
+            retention_days: Some(30),
+            generation_growth: 4,
+        };
+        let discovery = DiscoveryOptions { recursive: true, ..Default::default() };
+        let plan = plan_compaction(&input_dir, &policy, &discovery, SystemTime::now())?;
+
+        // day=1 packs into 3 + 2 files; day=2 has too few small files once
+        // the expired one is set aside
+        let sizes: Vec<usize> = plan.tasks.iter().map(|task| task.input_files.len()).collect();
+        assert_eq!(sizes, vec![3, 2]);
+        assert_eq!(plan.tasks[0].output_path, input_dir.join("day=1/compacted-00000.parquet"));
+        assert_eq!(plan.tasks[1].output_path, input_dir.join("day=1/compacted-00001.parquet"));
+        assert_eq!(plan.tasks[0].generation, 1);
+        assert_eq!(plan.expired, vec![old.clone()]);
+        assert!(plan.to_string().contains("Delete expired"));
+
+        let summary = execute_compaction(&plan, &ConsolidateOptions::default(), 2)?;
+        assert_eq!(
+            summary,
+            CompactionSummary { files_compacted: 5, files_written: 2, rows_written: 50, files_expired: 1 }
+        );
+        let mut remaining: Vec<String> = fs::read_dir(input_dir.join("day=1"))?
+            .map(|entry| entry.map(|entry| entry.file_name().to_string_lossy().into_owned()))
+            .collect::<std::io::Result<_>>()?;
+        remaining.sort();
+        assert_eq!(remaining, vec!["compacted-00000.parquet", "compacted-00001.parquet"]);
+        assert_eq!(compaction_generation(&input_dir.join("day=1/compacted-00000.parquet")), 1);
+        assert!(!old.exists());
+
+        let policy = CompactionPolicy { partition_aware: false, min_files: 2, retention_days: None, target_file_bytes: u64::MAX, ..policy };
+        let plan = plan_compaction(&input_dir, &policy, &discovery, SystemTime::now())?;
+        assert_eq!(plan.tasks.len(), 1);
+        assert_eq!(plan.tasks[0].output_path, input_dir.join("compacted-00000.parquet"));
+
+        assert!(CompactionPolicy::from_toml_str("small_file_bytes = 10\ntarget_file_bytes = 5").is_err());
+        assert!(CompactionPolicy::from_toml_str("small_file_bytes = 1\ntarget_file_bytes = 5\nunknown = 1").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_recompaction_generations() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let input_dir = temp_dir.path().join("table");
+        fs::create_dir_all(&input_dir)?;
+
+        // The outputs of four earlier compaction runs, and a file none wrote
+        let stamp = MetadataEdit::Set { key: COMPACTION_GENERATION_KEY.to_string(), value: "1".to_string() };
+        for run in 0..4 {
+            let output = input_dir.join(format!("compacted-{:05}.parquet", run));
+            create_test_parquet_file(&output, run * 100, run * 100 + 100)?;
+            edit_key_value_metadata(&output, std::slice::from_ref(&stamp))?;
+        }
+        let raw = input_dir.join("part-0.parquet");
+        create_test_parquet_file(&raw, 400, 410)?;
+        let file_bytes = (0..4).map(|run| file_size(&input_dir.join(format!("compacted-{:05}.parquet", run)))).max().unwrap();
+        let policy = CompactionPolicy {
+            small_file_bytes: 1,
+            target_file_bytes: file_bytes,
+            min_files: 2,
+            partition_aware: true,
+            retention_days: None,
+            generation_growth: 2,
+        };
+        let discovery = DiscoveryOptions::default();
+
+        // Generation 1 files merge in pairs, all that fit generation 2's target of
+        // twice target_file_bytes; the uncompacted file is left alone
+        let plan = plan_recompaction(&input_dir, &policy, &discovery, &[], SystemTime::now())?;
+        let tasks: Vec<(usize, u32)> = plan.tasks.iter().map(|task| (task.input_files.len(), task.generation)).collect();
+        assert_eq!(tasks, vec![(2, 2), (2, 2)]);
+        assert!(plan.to_string().contains("(generation 2)"));
+        let summary = execute_compaction(&plan, &ConsolidateOptions::default(), 2)?;
+        assert_eq!(summary.rows_written, 400);
+
+        // Whose outputs merge once more into generation 3
+        let plan = plan_recompaction(&input_dir, &policy, &discovery, &[], SystemTime::now())?;
+        assert_eq!(plan.tasks.len(), 1);
+        assert_eq!(plan.tasks[0].generation, 3);
+        execute_compaction(&plan, &ConsolidateOptions::default(), 1)?;
+        let outputs: Vec<PathBuf> = walk_input_files(&input_dir, &discovery).collect::<Result<_>>()?;
+        assert_eq!(outputs.len(), 2);
+        assert_eq!(compaction_generation(&outputs[0]), 3);
+        assert_eq!(compaction_generation(&raw), 0);
+        assert!(plan_recompaction(&input_dir, &policy, &discovery, &[], SystemTime::now())?.is_empty());
+
+        // Consolidated outputs listed in a manifest count as generation 1,
+        // though no compaction stamped them
+        let listed = temp_dir.path().join("listed");
+        fs::create_dir_all(&listed)?;
+        let outputs: Vec<PathBuf> = (0..2).map(|run| listed.join(format!("run-{}.parquet", run))).collect();
+        for (run, output) in outputs.iter().enumerate() {
+            create_test_parquet_file(output, run as i32 * 100, run as i32 * 100 + 100)?;
+        }
+        create_test_parquet_file(&listed.join("raw.parquet"), 200, 210)?;
+        let manifest = OutputManifest::of(&outputs)?;
+        assert!(plan_recompaction(&listed, &policy, &discovery, &[], SystemTime::now())?.is_empty());
+        let plan = plan_recompaction(&listed, &policy, &discovery, &[manifest], SystemTime::now())?;
+        assert_eq!(plan.tasks.len(), 1);
+        assert_eq!(plan.tasks[0].input_files, outputs);
+        assert_eq!(plan.tasks[0].generation, 2);
+
+        Ok(())
+    }
+}
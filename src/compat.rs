@@ -0,0 +1,356 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use anyhow::Result;
+use arrow::datatypes::{DataType, Field, Schema, TimeUnit};
+use parquet::basic::Compression;
+use parquet::file::properties::{EnabledStatistics, WriterPropertiesBuilder, WriterVersion};
+use crate::writer::WriterOptions;
+
+/// Characters Spark refuses in parquet column names
+const SPARK_INVALID_NAME_CHARS: &[char] = &[' ', ',', ';', '{', '}', '(', ')', '\n', '\t', '='];
+
+/// Downstream engines whose readers the output can be constrained to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompatMode {
+    /// Spark 3.x and Athena
+    Spark,
+}
+
+impl CompatMode {
+    /// Rewrite a schema into the representation the target engine reads
+    ///
+    /// Nanosecond timestamps become microseconds, list elements are named
+    /// `element` and map entries `key_value` with `key`/`value` children,
+    /// matching the standard parquet LIST and MAP layouts.
+    pub fn normalize_schema(&self, schema: &Schema) -> Schema {
+        let fields: Vec<Field> = schema.fields().iter().map(|field| normalize_field(field)).collect();
+        Schema::new_with_metadata(fields, schema.metadata().clone())
+    }
+
+    /// Constrain writer properties to what the target engine reads
+    pub(crate) fn apply(&self, builder: WriterPropertiesBuilder) -> WriterPropertiesBuilder {
+        match self {
+            // Data page v2 and the page index are not read reliably, while
+            // chunk-level statistics are used for predicate pushdown
+            CompatMode::Spark => builder
+                .set_writer_version(WriterVersion::PARQUET_1_0)
+                .set_statistics_enabled(EnabledStatistics::Chunk),
+        }
+    }
+
+    /// Describe columns of the merged schema the target engine cannot read
+    /// faithfully
+    pub fn lint(&self, schema: &Schema) -> Vec<String> {
+        let mut warnings = Vec::new();
+        let mut lowercase_names: HashMap<String, &str> = HashMap::new();
+
+        for field in schema.fields() {
+            let name = field.name();
+            if name.contains(SPARK_INVALID_NAME_CHARS) {
+                warnings.push(format!("Column {:?} contains characters Spark does not allow in column names", name));
+            }
+            if let Some(other) = lowercase_names.insert(name.to_lowercase(), name) {
+                warnings.push(format!(
+                    "Columns {:?} and {:?} differ only by case, which Athena treats as the same column",
+                    other, name
+                ));
+            }
+            lint_type(name, field.data_type(), &mut warnings);
+        }
+
+        warnings
+    }
+}
+
+fn normalize_field(field: &Field) -> Field {
+    field.clone().with_data_type(normalize_type(field.data_type()))
+}
+
+fn normalize_type(data_type: &DataType) -> DataType {
+    match data_type {
+        DataType::Timestamp(TimeUnit::Nanosecond, tz) => DataType::Timestamp(TimeUnit::Microsecond, tz.clone()),
+        DataType::List(item) => DataType::List(Arc::new(rename(item, "element"))),
+        DataType::LargeList(item) => DataType::LargeList(Arc::new(rename(item, "element"))),
+        DataType::Struct(children) => {
+            DataType::Struct(children.iter().map(|child| normalize_field(child)).collect())
+        }
+        DataType::Map(entries, sorted) => {
+            let entries = match entries.data_type() {
+                DataType::Struct(children) if children.len() == 2 => {
+                    let key = rename(&children[0], "key");
+                    let value = rename(&children[1], "value");
+                    Field::new("key_value", DataType::Struct(vec![key, value].into()), false)
+                }
+                _ => normalize_field(entries),
+            };
+            DataType::Map(Arc::new(entries), *sorted)
+        }
+        other => other.clone(),
+    }
+}
+
+fn rename(field: &Field, name: &str) -> Field {
+    normalize_field(field).with_name(name)
+}
+
+fn lint_type(column: &str, data_type: &DataType, warnings: &mut Vec<String>) {
+    let problem = match data_type {
+        DataType::UInt64 => Some("unsigned 64-bit integers are read as decimals by Spark and rejected by Athena"),
+        DataType::UInt8 | DataType::UInt16 | DataType::UInt32 => {
+            Some("unsigned integers are not supported by Athena or Spark before 3.2")
+        }
+        DataType::Float16 => Some("half-precision floats are not supported"),
+        DataType::Time32(_) | DataType::Time64(_) => Some("there is no TIME type in Spark or Athena"),
+        DataType::Duration(_) | DataType::Interval(_) => Some("durations and intervals are not supported"),
+        DataType::Null => Some("columns of only nulls have no type Spark can read"),
+        DataType::Decimal256(precision, _) if *precision > 38 => {
+            Some("decimals wider than 38 digits are not supported")
+        }
+        DataType::Timestamp(TimeUnit::Nanosecond, _) => {
+            Some("nanosecond timestamps are written with microsecond precision")
+        }
+        _ => None,
+    };
+    if let Some(problem) = problem {
+        warnings.push(format!("Column {:?} has type {}: {}", column, data_type, problem));
+    }
+
+    match data_type {
+        DataType::List(item) | DataType::LargeList(item) | DataType::FixedSizeList(item, _) => {
+            lint_type(column, item.data_type(), warnings)
+        }
+        DataType::Struct(children) => {
+            for child in children {
+                lint_type(&format!("{}.{}", column, child.name()), child.data_type(), warnings);
+            }
+        }
+        DataType::Map(entries, _) => lint_type(column, entries.data_type(), warnings),
+        _ => {}
+    }
+}
+
+impl fmt::Display for CompatMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CompatMode::Spark => write!(f, "spark"),
+        }
+    }
+}
+
+impl FromStr for CompatMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "spark" | "athena" => Ok(CompatMode::Spark),
+            other => anyhow::bail!("Unknown compatibility mode: {} (expected spark)", other),
+        }
+    }
+}
+
+/// A reader the output is checked against before it is written, without
+/// changing what is written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TargetReader {
+    Spark33,
+    Athena,
+    DuckDb,
+}
+
+impl TargetReader {
+    /// Describe the columns and writer options the reader can't handle
+    /// faithfully, each with what to do about it
+    pub fn check(&self, schema: &Schema, options: &WriterOptions) -> Vec<String> {
+        let mut warnings = Vec::new();
+        let mut lowercase_names: HashMap<String, &str> = HashMap::new();
+        for field in schema.fields() {
+            let name = field.name();
+            if *self == TargetReader::Spark33 && name.contains(SPARK_INVALID_NAME_CHARS) {
+                warnings.push(format!(
+                    "{} rejects column {:?}, as it contains one of \" ,;{{}}()\\n\\t=\"; rename it with a transform",
+                    self, name
+                ));
+            }
+            if let Some(other) = lowercase_names.insert(name.to_lowercase(), name) {
+                if matches!(self, TargetReader::Athena | TargetReader::DuckDb) {
+                    warnings.push(format!(
+                        "{} treats columns {:?} and {:?} as the same column, as its names are case-insensitive; rename one of them",
+                        self, other, name
+                    ));
+                }
+            }
+            self.check_type(name, field.data_type(), &mut warnings);
+        }
+
+        let codecs = std::iter::once((None, options.compression))
+            .chain(options.column_compression.iter().map(|o| (Some(o.column.as_str()), o.codec)));
+        for (column, codec) in codecs {
+            let problem = match (self, codec) {
+                (TargetReader::Spark33, Compression::LZ4_RAW) => {
+                    Some("its parquet-mr 1.12.2 predates the LZ4_RAW codec; use zstd or snappy")
+                }
+                (TargetReader::Spark33, Compression::BROTLI(_)) => {
+                    Some("it needs the brotli codec added to its classpath; use zstd or snappy")
+                }
+                (TargetReader::Athena, Compression::BROTLI(_)) => Some("it doesn't read brotli; use zstd or snappy"),
+                _ => None,
+            };
+            if let Some(problem) = problem {
+                let target = column.map_or("the output".to_string(), |column| format!("column {:?}", column));
+                warnings.push(format!("{} can't decompress {} ({}): {}", self, target, codec, problem));
+            }
+        }
+
+        warnings
+    }
+
+    fn check_type(&self, column: &str, data_type: &DataType, warnings: &mut Vec<String>) {
+        use TargetReader::*;
+        let problem = match (self, data_type) {
+            (Spark33, DataType::UInt64) => {
+                Some("it reads unsigned 64-bit integers as DECIMAL(20,0); cast the column to Int64 if the values fit")
+            }
+            (Athena, DataType::UInt8 | DataType::UInt16 | DataType::UInt32 | DataType::UInt64) => {
+                Some("it doesn't support unsigned integers; cast the column to a wider signed type")
+            }
+            (Spark33, DataType::Timestamp(TimeUnit::Nanosecond, _)) => {
+                Some("it rejects nanosecond timestamps; --compat spark writes them with microsecond precision")
+            }
+            (Athena, DataType::Timestamp(TimeUnit::Microsecond | TimeUnit::Nanosecond, _)) => {
+                Some("its timestamps have millisecond precision, so the rest is dropped on read")
+            }
+            (Spark33 | Athena, DataType::Time32(_) | DataType::Time64(_)) => {
+                Some("it has no TIME type; cast the column to a string or a duration in an integer")
+            }
+            (Spark33 | Athena, DataType::Duration(_) | DataType::Interval(_)) => {
+                Some("it doesn't read durations or intervals; cast the column to an integer")
+            }
+            (DuckDb, DataType::Duration(_)) => Some("it reads durations as plain BIGINT values with no unit"),
+            (Spark33 | Athena, DataType::Float16) => Some("it doesn't read half-precision floats; cast the column to Float32"),
+            (Spark33 | Athena, DataType::Null) => {
+                Some("a column of only nulls has no type it can read; cast the column to the type it should have")
+            }
+            (_, DataType::Decimal256(precision, _)) if *precision > 38 => {
+                Some("its decimals hold at most 38 digits; cast the column to a narrower decimal or a string")
+            }
+            _ => None,
+        };
+        if let Some(problem) = problem {
+            warnings.push(format!("{} can't read column {:?} ({}) faithfully: {}", self, column, data_type, problem));
+        }
+
+        match data_type {
+            DataType::List(item) | DataType::LargeList(item) | DataType::FixedSizeList(item, _) => {
+                self.check_type(column, item.data_type(), warnings)
+            }
+            DataType::Struct(children) => {
+                for child in children {
+                    self.check_type(&format!("{}.{}", column, child.name()), child.data_type(), warnings);
+                }
+            }
+            DataType::Map(entries, _) => self.check_type(column, entries.data_type(), warnings),
+            _ => {}
+        }
+    }
+}
+
+impl fmt::Display for TargetReader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            TargetReader::Spark33 => "spark-3.3",
+            TargetReader::Athena => "athena",
+            TargetReader::DuckDb => "duckdb",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for TargetReader {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "spark-3.3" => Ok(TargetReader::Spark33),
+            "athena" => Ok(TargetReader::Athena),
+            "duckdb" => Ok(TargetReader::DuckDb),
+            other => anyhow::bail!("Unknown target reader: {} (expected spark-3.3, athena or duckdb)", other),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compat_mode_from_str() {
+        assert_eq!("spark".parse::<CompatMode>().unwrap(), CompatMode::Spark);
+        assert_eq!("Athena".parse::<CompatMode>().unwrap(), CompatMode::Spark);
+        assert!("hive".parse::<CompatMode>().is_err());
+    }
+
+    #[test]
+    fn test_normalize_schema() {
+        let item = Arc::new(Field::new("item", DataType::Timestamp(TimeUnit::Nanosecond, None), true));
+        let schema = Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("events", DataType::LargeList(item), true),
+        ]);
+
+        let normalized = CompatMode::Spark.normalize_schema(&schema);
+        assert_eq!(normalized.field(0), schema.field(0));
+        match normalized.field(1).data_type() {
+            DataType::LargeList(item) => {
+                assert_eq!(item.name(), "element");
+                assert_eq!(item.data_type(), &DataType::Timestamp(TimeUnit::Microsecond, None));
+            }
+            other => panic!("unexpected type {}", other),
+        }
+    }
+
+    #[test]
+    fn test_lint() {
+        let schema = Schema::new(vec![
+            Field::new("id", DataType::UInt64, false),
+            Field::new("Name", DataType::Utf8, true),
+            Field::new("name", DataType::Utf8, true),
+            Field::new("bad column", DataType::Int32, true),
+            Field::new("score", DataType::Float64, true),
+        ]);
+
+        let warnings = CompatMode::Spark.lint(&schema);
+        assert_eq!(warnings.len(), 3);
+        assert!(warnings[0].contains("\"id\" has type UInt64"));
+        assert!(warnings[1].contains("differ only by case"));
+        assert!(warnings[2].contains("\"bad column\""));
+    }
+
+    #[test]
+    fn test_target_reader_check() {
+        let schema = Schema::new(vec![
+            Field::new("id", DataType::UInt64, false),
+            Field::new("ts", DataType::Timestamp(TimeUnit::Nanosecond, None), true),
+            Field::new("Name", DataType::Utf8, true),
+            Field::new("name", DataType::LargeUtf8, true),
+        ]);
+        let options = WriterOptions { compression: Compression::LZ4_RAW, ..Default::default() };
+
+        let spark = TargetReader::Spark33.check(&schema, &options);
+        assert_eq!(spark.len(), 3);
+        assert!(spark[0].starts_with("spark-3.3 can't read column \"id\" (UInt64) faithfully: it reads unsigned 64-bit"));
+        assert!(spark[1].contains("--compat spark writes them with microsecond precision"));
+        assert!(spark[2].starts_with("spark-3.3 can't decompress the output (LZ4_RAW)"));
+
+        let athena = TargetReader::Athena.check(&schema, &options);
+        assert_eq!(athena.len(), 3);
+        assert!(athena[2].contains("columns \"Name\" and \"name\" as the same column"));
+
+        // DuckDB reads unsigned integers, nanosecond timestamps and LZ4_RAW
+        assert_eq!(TargetReader::DuckDb.check(&schema, &options).len(), 1);
+
+        assert_eq!("Spark-3.3".parse::<TargetReader>().unwrap(), TargetReader::Spark33);
+        assert!("spark".parse::<TargetReader>().is_err());
+    }
+}
@@ -0,0 +1,70 @@
+use std::fs::File;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use anyhow::Result;
+use parquet::file::reader::{FileReader, SerializedFileReader};
+
+/// Describe the existing files a run is about to replace
+///
+/// Row counts are read from parquet footers; files in other formats or
+/// with unreadable footers are listed without one.
+pub fn overwrite_summary(paths: &[PathBuf]) -> String {
+    let counts: Vec<Option<i64>> = paths.iter().map(|path| parquet_row_count(path)).collect();
+    let rows: i64 = counts.iter().flatten().sum();
+    let rows = if counts.iter().all(Option::is_some) {
+        format!("{} rows", rows)
+    } else {
+        format!("at least {} rows", rows)
+    };
+
+    let mut summary = format!("This will overwrite {} existing files ({}):\n", paths.len(), rows);
+    for path in paths {
+        summary.push_str(&format!("  {}\n", path.display()));
+    }
+    summary
+}
+
+/// Show `summary` and ask whether to go ahead; only "y" or "yes" agree
+pub fn confirm(summary: &str, mut input: impl BufRead, mut output: impl Write) -> Result<bool> {
+    write!(output, "{}Continue? [y/N] ", summary)?;
+    output.flush()?;
+
+    let mut answer = String::new();
+    input.read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+fn parquet_row_count(path: &Path) -> Option<i64> {
+    let reader = SerializedFileReader::new(File::open(path).ok()?).ok()?;
+    Some(reader.metadata().file_metadata().num_rows())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use crate::test_utils::create_test_parquet_file;
+
+    #[test]
+    fn test_overwrite_summary_and_confirm() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let parquet = temp_dir.path().join("a.parquet");
+        let csv = temp_dir.path().join("b.csv");
+        create_test_parquet_file(&parquet, 0, 10)?;
+        fs::write(&csv, "id\n1\n")?;
+
+        let summary = overwrite_summary(std::slice::from_ref(&parquet));
+        assert!(summary.starts_with("This will overwrite 1 existing files (10 rows):\n"));
+        assert!(summary.contains("a.parquet"));
+        assert!(overwrite_summary(&[parquet, csv]).contains("(at least 10 rows)"));
+
+        let mut prompt = Vec::new();
+        assert!(confirm("summary\n", "yes\n".as_bytes(), &mut prompt)?);
+        assert_eq!(String::from_utf8(prompt)?, "summary\nContinue? [y/N] ");
+        assert!(confirm("", " Y \n".as_bytes(), std::io::sink())?);
+        assert!(!confirm("", "\n".as_bytes(), std::io::sink())?);
+        assert!(!confirm("", "".as_bytes(), std::io::sink())?);
+
+        Ok(())
+    }
+}
@@ -1,8 +1,41 @@
-use std::path::PathBuf;
+use std::fmt;
+use std::str::FromStr;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
 use anyhow::{Result, Context};
-use walkdir::WalkDir;
+use parquet::file::reader::{FileReader, SerializedFileReader};
 use polars::prelude::*;
-use std::fs::File;
+use crate::anomaly::detect_anomalies;
+use crate::cancel::{CancellationToken, Cancelled};
+use crate::auto_codec::{apply_codec_choices, choose_column_codecs, ColumnCodecChoice};
+use crate::auto_batch::plan_execution;
+use crate::cdc::{apply_changes, CdcOptions};
+use crate::content_digest::content_digest;
+use crate::checks::{CheckPolicy, QualityChecks};
+use crate::redact::{redact, ColumnRedaction};
+#[cfg(feature = "discovery")]
+use crate::discovery::{discover_input_files, DiscoveryOptions};
+use crate::field_metadata::{merge_field_metadata, FieldMetadataPolicy};
+use crate::fill_missing::{missing_column_value, ColumnFill};
+use crate::file_info::{file_size, is_named_pipe};
+use crate::dedupe::{drop_duplicate_keys, duplicate_key_report, DuplicateKeyReport, SeenKeys};
+use crate::formats::{prefers_row_group_parallelism, InputFormat, ReadOptions, ReaderInput};
+use crate::memory::{available_memory_bytes, format_bytes, peak_rss_bytes};
+use crate::nullability::{nullability_conflicts, NullabilityConflict, NullabilityPolicy};
+use crate::pipeline::{consolidate_pipelined, PipelineOptions};
+use crate::stats_validation::validate_statistics;
+use crate::timings::{Phase, PhaseClock, PhaseTimings};
+use crate::trace::{Span, TraceParent};
+use crate::transform::{apply_transforms, RowTransform};
+use crate::row_groups::{consolidate_preserving_row_groups, identical_input_schema};
+use crate::schema_merge::{merge_schemas, MergeOptions, SchemaMergeError};
+use crate::snapshot::{ChangedInputPolicy, InputSnapshot};
+use crate::warnings::{Warning, WarningKind, WarningLog};
+use crate::writer::{write_dataframe_logged, WriterOptions};
 
 /// Find all parquet files in the given path
 /// 
@@ -21,30 +54,31 @@ use std::fs::File;
 /// let files = find_parquet_files(&test_file, false).unwrap();
 /// assert_eq!(files.len(), 1);
 /// ```
-pub fn find_parquet_files(input_path: &PathBuf, recursive: bool) -> Result<Vec<PathBuf>> {
-    let mut parquet_files = Vec::new();
-
-    if input_path.is_file() {
-        if is_parquet_file(input_path) {
-            parquet_files.push(input_path.clone());
-        } else {
-            anyhow::bail!("Input file is not a parquet file: {:?}", input_path);
-        }
-    } else if input_path.is_dir() {
-        let walker = if recursive {
-            WalkDir::new(input_path)
-        } else {
-            WalkDir::new(input_path).max_depth(1)
-        };
-
-        for entry in walker.into_iter().filter_map(|e| e.ok()) {
-            if entry.file_type().is_file() && is_parquet_file(entry.path()) {
-                parquet_files.push(entry.path().to_path_buf());
-            }
-        }
-    }
+#[cfg(feature = "discovery")]
+pub fn find_parquet_files(input_path: &Path, recursive: bool) -> Result<Vec<PathBuf>> {
+    find_input_files(input_path, recursive, &[InputFormat::Parquet])
+}
 
-    Ok(parquet_files)
+/// Find all files of the given input formats in the given path
+/// 
+/// # Examples
+/// 
+/// ```
+/// use parquet_consolidator::{find_input_files, InputFormat};
+/// use tempfile::TempDir;
+/// 
+/// let temp_dir = TempDir::new().unwrap();
+/// std::fs::write(temp_dir.path().join("a.csv"), "id\n1\n").unwrap();
+/// std::fs::write(temp_dir.path().join("b.ndjson"), "{\"id\": 2}\n").unwrap();
+/// 
+/// let dir = temp_dir.path().to_path_buf();
+/// let files = find_input_files(&dir, false, &[InputFormat::Csv, InputFormat::Ndjson]).unwrap();
+/// assert_eq!(files.len(), 2);
+/// ```
+#[cfg(feature = "discovery")]
+pub fn find_input_files(input_path: &Path, recursive: bool, formats: &[InputFormat]) -> Result<Vec<PathBuf>> {
+    let options = DiscoveryOptions { recursive, formats: formats.to_vec(), ..Default::default() };
+    discover_input_files(input_path, &options)
 }
 
 /// Check if a file has a parquet extension
@@ -66,8 +100,235 @@ pub fn is_parquet_file(path: &std::path::Path) -> bool {
         .unwrap_or(false)
 }
 
+/// Options controlling a consolidation run
+#[derive(Debug, Clone, Default)]
+pub struct ConsolidateOptions {
+    pub verbose: bool,
+    pub read: ReadOptions,
+    pub writer: WriterOptions,
+    /// Copy input row groups 1:1 instead of re-chunking; requires all
+    /// inputs to be parquet files with identical schemas
+    pub preserve_row_groups: bool,
+    /// Data quality checks evaluated on the consolidated frame
+    pub checks: Option<QualityChecks>,
+    /// Where rows rejected by quarantine checks are written; without it
+    /// they are dropped
+    pub quarantine_path: Option<PathBuf>,
+    /// Patterns scrubbed from string columns before checks run and
+    /// anything is written
+    pub redactions: Vec<ColumnRedaction>,
+    /// User-defined transforms applied in order after redaction
+    pub transforms: Vec<Arc<dyn RowTransform>>,
+    /// Columns identifying a row; later rows repeating a key are dropped
+    pub dedupe_keys: Vec<String>,
+    /// File remembering the keys emitted by previous runs, whose rows are
+    /// dropped from this one
+    pub dedupe_state: Option<PathBuf>,
+    /// Merge change data capture records into a snapshot
+    pub cdc: Option<CdcOptions>,
+    /// Key columns to count duplicates of, without removing any rows
+    pub report_duplicates: Vec<String>,
+    /// Warn about input files whose column statistics are strong outliers
+    /// against the rest of the inputs
+    pub anomaly_warnings: bool,
+    /// Cross-check the footer statistics of this many row groups per
+    /// parquet input against their data, warning about wrong ones
+    pub validate_statistics: Option<usize>,
+    /// Time each phase of the run and include the breakdown in the summary;
+    /// inputs are then decoded before being stacked, which holds both in
+    /// memory at once
+    pub timings: bool,
+    /// Record a span for each run and each input file under this parent
+    pub trace: Option<TraceParent>,
+    /// Skip inputs whose schema can't be read, with a warning, instead of
+    /// failing the run
+    pub continue_on_error: bool,
+    /// With `continue_on_error`, fail anyway as soon as more inputs than
+    /// this are skipped
+    pub abort_threshold: Option<AbortThreshold>,
+    /// Decode at most this many inputs at a time, stacking the batches
+    /// afterwards, to stay under the process's open file limit
+    pub max_open_files: Option<usize>,
+    /// Pick eager, streaming or batched execution from the inputs' decoded
+    /// size and the memory available, overriding the options above
+    pub auto_batch: bool,
+    /// What to do with columns required in some parquet inputs and holding
+    /// nulls in others
+    pub nullability: NullabilityPolicy,
+    /// What to do when parquet inputs give a column's field metadata key
+    /// different values; the merged metadata is written to the output
+    pub field_metadata: FieldMetadataPolicy,
+    /// Give up on an input that takes longer than this to read, so a file on
+    /// a hung mount fails, or is skipped with `continue_on_error`, instead of
+    /// stalling the run; inputs are then decoded before being stacked
+    pub file_timeout: Option<Duration>,
+    /// Decode, transform and encode parquet inputs sharing one schema in
+    /// overlapping stages instead of collecting every row first; only
+    /// row-count-preserving transforms can be combined with it
+    pub pipeline: Option<PipelineOptions>,
+    /// Start reading no batch of inputs after this instant (though always
+    /// the first); the output holds the batches read, and the inputs left
+    /// are reported in the summary. Inputs are read at most
+    /// [`DEADLINE_BATCH_FILES`] at a time so it is checked between batches.
+    /// Runs copying row groups or pipelined can't stop early and fail with
+    /// one
+    pub deadline: Option<Instant>,
+    /// Sizes and modification times of the inputs when the run started;
+    /// inputs that differ from it before or after they are read fail the
+    /// output or are left out of it, by the snapshot's policy
+    pub snapshot: Option<Arc<InputSnapshot>>,
+    /// Stop the run, failing with [`Cancelled`], once this is cancelled
+    pub cancel: Option<CancellationToken>,
+    /// Print each warning to stderr as it is raised, as the command line
+    /// does; they are collected on the summary either way
+    pub print_warnings: bool,
+    /// Digest the rows of a parquet file output into the summary's
+    /// `content_digest`; see [`content_digest`]
+    pub content_digest: bool,
+}
+
+/// How many unreadable inputs a run skipping them tolerates
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AbortThreshold {
+    /// Fail once more than this fraction of the inputs is skipped
+    Fraction(f64),
+    /// Fail once more than this many inputs are skipped
+    Count(usize),
+}
+
+impl AbortThreshold {
+    /// Whether skipping `skipped` of `total` inputs goes over the threshold
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use parquet_consolidator::AbortThreshold;
+    ///
+    /// let threshold: AbortThreshold = "10%".parse().unwrap();
+    /// assert!(!threshold.exceeded(1, 10));
+    /// assert!(threshold.exceeded(2, 10));
+    /// assert!("3".parse::<AbortThreshold>().unwrap().exceeded(4, 1000));
+    /// ```
+    pub fn exceeded(&self, skipped: usize, total: usize) -> bool {
+        match *self {
+            AbortThreshold::Fraction(fraction) => skipped as f64 > fraction * total as f64,
+            AbortThreshold::Count(count) => skipped > count,
+        }
+    }
+}
+
+impl fmt::Display for AbortThreshold {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AbortThreshold::Fraction(fraction) => write!(f, "{}%", fraction * 100.0),
+            AbortThreshold::Count(count) => write!(f, "{}", count),
+        }
+    }
+}
+
+impl FromStr for AbortThreshold {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().strip_suffix('%') {
+            Some(percent) => {
+                let percent: f64 = percent.trim().parse()
+                    .map_err(|_| anyhow::anyhow!("Invalid abort threshold: {} (expected a count or a percentage)", s))?;
+                if !(0.0..=100.0).contains(&percent) {
+                    anyhow::bail!("Abort threshold must be between 0% and 100%, got {}", s);
+                }
+                Ok(AbortThreshold::Fraction(percent / 100.0))
+            }
+            None => s.trim().parse().map(AbortThreshold::Count)
+                .map_err(|_| anyhow::anyhow!("Invalid abort threshold: {} (expected a count or a percentage)", s)),
+        }
+    }
+}
+
+/// Number of duplicate keys listed in the run summary
+const DUPLICATE_SAMPLES: usize = 5;
+
+/// How user-defined transforms are named among the row-level features
+const TRANSFORMS_FEATURE: &str = "User-defined transforms";
+
+impl ConsolidateOptions {
+    /// Fail with [`Cancelled`] once the run's token is cancelled
+    pub(crate) fn check_cancelled(&self) -> Result<()> {
+        self.cancel.as_ref().map_or(Ok(()), CancellationToken::check)
+    }
+
+    /// Configured features that need the rows in memory, in the order they
+    /// are applied
+    fn row_level_features(&self) -> Vec<&'static str> {
+        let mut features = Vec::new();
+        if !self.report_duplicates.is_empty() {
+            features.push("Duplicate key reporting");
+        }
+        if self.cdc.is_some() {
+            features.push("CDC merge");
+        }
+        if !self.redactions.is_empty() {
+            features.push("Redaction");
+        }
+        if !self.transforms.is_empty() {
+            features.push(TRANSFORMS_FEATURE);
+        }
+        if !self.dedupe_keys.is_empty() {
+            features.push("Deduplication");
+        }
+        if self.checks.is_some() || self.quarantine_path.is_some() {
+            features.push("Data quality checks and quarantine");
+        }
+        features
+    }
+
+    /// Name the first configured feature that needs the rows in memory
+    pub(crate) fn row_level_feature(&self) -> Option<&'static str> {
+        self.row_level_features().first().copied()
+    }
+
+    /// Fail unless the options can be applied while streaming row groups,
+    /// as preserving them or a pipelined run does
+    fn ensure_streamable(&self) -> Result<()> {
+        let mode = match self.pipeline {
+            Some(_) => "a pipelined run",
+            None => "preserving row groups",
+        };
+        // Pipelined runs apply transforms one row group at a time
+        let feature = match self.pipeline {
+            Some(_) => self.row_level_features().into_iter().find(|&feature| feature != TRANSFORMS_FEATURE),
+            None => self.row_level_feature(),
+        };
+        if let Some(feature) = feature {
+            anyhow::bail!("{} cannot be combined with {}", feature, mode);
+        }
+        if self.continue_on_error {
+            anyhow::bail!("Skipping unreadable inputs cannot be combined with {}", mode);
+        }
+        if !self.read.columns.is_empty() {
+            anyhow::bail!("Column selection cannot be combined with {}", mode);
+        }
+        if !self.read.row_groups.is_empty() {
+            anyhow::bail!("Row group selection cannot be combined with {}", mode);
+        }
+        if self.file_timeout.is_some() {
+            anyhow::bail!("A file timeout cannot be combined with {}", mode);
+        }
+        if self.deadline.is_some() {
+            anyhow::bail!("A deadline cannot be combined with {}", mode);
+        }
+        if self.writer.auto_compression {
+            anyhow::bail!("Choosing compression per column from a sample cannot be combined with {}", mode);
+        }
+        Ok(())
+    }
+}
+
 /// Consolidate multiple parquet files into a single file
 /// 
+/// CSV and NDJSON inputs are detected by extension and scanned with the
+/// matching reader; anything else is read as parquet.
+/// 
 /// # Examples
 /// 
 /// ```no_run
@@ -82,39 +343,1135 @@ pub fn is_parquet_file(path: &std::path::Path) -> bool {
 /// 
 /// consolidate_parquet_files(&input_files, &output_path, true).unwrap();
 /// ```
-pub fn consolidate_parquet_files(input_files: &[PathBuf], output_path: &PathBuf, verbose: bool) -> Result<()> {
+pub fn consolidate_parquet_files(input_files: &[PathBuf], output_path: &Path, verbose: bool) -> Result<()> {
+    let options = ConsolidateOptions { verbose, ..Default::default() };
+    consolidate_with_options(input_files, output_path, &options)?;
+    Ok(())
+}
+
+/// What a consolidation run produced
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RunSummary {
+    /// Where the output was written; `None` when it went to a caller's sink
+    pub output_path: Option<PathBuf>,
+    pub input_files: usize,
+    pub rows_written: usize,
+    /// Keys shared by several input rows, when a duplicate report was requested
+    pub duplicate_keys: Option<DuplicateKeyReport>,
+    /// Peak resident set size of the process when the run finished, where
+    /// the platform reports it
+    pub peak_memory_bytes: Option<u64>,
+    /// Wall and CPU time per phase, when timings were requested
+    pub timings: Option<PhaseTimings>,
+    /// Inputs skipped because they couldn't be read
+    pub skipped_files: Vec<PathBuf>,
+    /// Inputs left unread because the deadline passed, for a later run
+    pub remaining_files: Vec<PathBuf>,
+    /// Columns required in some inputs and holding nulls in others
+    pub nullability_conflicts: Vec<NullabilityConflict>,
+    /// Rows dropped for being null in one of those columns, under
+    /// [`NullabilityPolicy::DropNullRows`]
+    pub null_rows_dropped: usize,
+    /// The codec and encoding picked for each column, under
+    /// [`WriterOptions::auto_compression`]
+    pub codec_choices: Vec<ColumnCodecChoice>,
+    /// Warnings raised by the run, in the order they were raised
+    pub warnings: Vec<Warning>,
+    /// Order-insensitive digest of the rows written, when asked for
+    pub content_digest: Option<String>,
+}
+
+impl RunSummary {
+    /// Whether the deadline passed before the run read any of its inputs
+    pub fn is_deferred(&self) -> bool {
+        self.input_files == 0 && !self.remaining_files.is_empty()
+    }
+}
+
+impl fmt::Display for RunSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Consolidated {} files into {} rows", self.input_files, self.rows_written)?;
+        if !self.skipped_files.is_empty() {
+            writeln!(f, "Skipped {} unreadable files", self.skipped_files.len())?;
+        }
+        if !self.remaining_files.is_empty() {
+            writeln!(f, "Left {} files unread when the deadline passed", self.remaining_files.len())?;
+        }
+        if !self.nullability_conflicts.is_empty() {
+            writeln!(
+                f,
+                "{} columns were required in some inputs and nullable in others; dropped {} rows with nulls in them",
+                self.nullability_conflicts.len(),
+                self.null_rows_dropped
+            )?;
+        }
+        if let Some(report) = &self.duplicate_keys {
+            write!(f, "{}", report)?;
+        }
+        if !self.codec_choices.is_empty() {
+            writeln!(f, "Compression chosen per column:")?;
+            for choice in &self.codec_choices {
+                writeln!(f, "  {}", choice)?;
+            }
+        }
+        if !self.warnings.is_empty() {
+            writeln!(f, "{} warnings", self.warnings.len())?;
+        }
+        if let Some(digest) = &self.content_digest {
+            writeln!(f, "Content digest: {}", digest)?;
+        }
+        if let Some(bytes) = self.peak_memory_bytes {
+            writeln!(f, "Peak memory (RSS): {}", format_bytes(bytes))?;
+        }
+        if let Some(timings) = &self.timings {
+            write!(f, "{}", timings)?;
+        }
+        Ok(())
+    }
+}
+
+/// Consolidate multiple input files into a single file using the given options
+/// 
+/// # Examples
+/// 
+/// ```no_run
+/// use std::path::PathBuf;
+/// use parquet_consolidator::{consolidate_with_options, ConsolidateOptions};
+/// 
+/// let input_files = vec![PathBuf::from("file1.parquet")];
+/// let options = ConsolidateOptions { verbose: true, ..Default::default() };
+/// 
+/// let summary = consolidate_with_options(&input_files, &PathBuf::from("out.parquet"), &options).unwrap();
+/// println!("{}", summary);
+/// ```
+pub fn consolidate_with_options(input_files: &[PathBuf], output_path: &Path, options: &ConsolidateOptions) -> Result<RunSummary> {
+    let span = options.trace.as_ref().map(|parent| parent.start_span("consolidate"));
+    let trace = span.as_ref().map(Span::as_parent);
+    let result = match &options.snapshot {
+        Some(snapshot) => consolidate_snapshot(input_files, output_path, options, snapshot, trace),
+        None => consolidate_to_path(input_files, output_path, options, trace),
+    };
+    // What went into a named pipe can't be read back
+    let digestible = !is_named_pipe(output_path);
+    let result = match result {
+        Ok(mut summary) if options.content_digest && digestible && !summary.is_deferred() => {
+            content_digest(&[output_path.to_path_buf()]).map(|digest| {
+                summary.content_digest = Some(digest);
+                summary
+            })
+        }
+        result => result,
+    };
+    if let Some(span) = span {
+        end_run_span(span, input_files, &result.as_ref());
+    }
+    result
+}
+
+fn consolidate_to_path(
+    input_files: &[PathBuf],
+    output_path: &Path,
+    options: &ConsolidateOptions,
+    trace: Option<TraceParent>,
+) -> Result<RunSummary> {
     if input_files.is_empty() {
         anyhow::bail!("No input files provided");
     }
+    options.check_cancelled()?;
+
+    if is_named_pipe(output_path) {
+        // Nothing written to a pipe can be sought back to or read again, so
+        // it is written like any other sink; opening it waits for a reader
+        if options.preserve_row_groups && options.pipeline.is_none() {
+            anyhow::bail!("Row groups can only be preserved into a named pipe by a pipelined run");
+        }
+        let pipe = OpenOptions::new()
+            .write(true)
+            .open(output_path)
+            .context(format!("Failed to open named pipe {:?}", output_path))?;
+        let (mut summary, _) = consolidate_to_sink(input_files, pipe, options, trace)?;
+        summary.output_path = Some(output_path.to_path_buf());
+        return Ok(summary);
+    }
+
+    options.writer.validate_row_group_limits()?;
+    let mut timings = PhaseTimings::default();
+    let warnings = WarningLog::new(options.print_warnings);
+    timings.time(Phase::SchemaCheck, || warn_about_anomalies(input_files, options, &warnings));
+    timings.time(Phase::SchemaCheck, || warn_about_statistics(input_files, options, &warnings));
+    let tuned = timings.time(Phase::SchemaCheck, || auto_batched(input_files, options, true, &warnings))?;
+    let options = tuned.as_ref().unwrap_or(options);
+    let parallel = timings.time(Phase::SchemaCheck, || row_group_parallel(input_files, options))?;
+    let options = parallel.as_ref().unwrap_or(options);
 
-    let mut dfs = Vec::new();
+    if options.preserve_row_groups || options.pipeline.is_some() {
+        options.ensure_streamable()?;
+        timings.time(Phase::Write, || match options.pipeline {
+            Some(_) => {
+                let output = File::create(output_path)?;
+                #[cfg(feature = "fault-injection")]
+                crate::fault_injection::inject_fault(crate::fault_injection::FaultPoint::Write, output_path)?;
+                consolidate_pipelined(input_files, output, options, &warnings).map(drop)
+            }
+            None => consolidate_preserving_row_groups(input_files, output_path, options, &warnings),
+        })
+        .inspect_err(|err| {
+            // Nothing of a cancelled output is worth keeping
+            if err.is::<Cancelled>() {
+                let _ = fs::remove_file(output_path);
+            }
+        })?;
+        let mut summary = finish_summary(input_files.len(), output_path, None)?;
+        summary.timings = options.timings.then_some(timings);
+        summary.warnings = warnings.into_warnings();
+        return Ok(summary);
+    }
 
+    let (scans, skipped_files) = timings.time(Phase::SchemaCheck, || {
+        scan_tolerating_errors(input_files, options, trace.as_ref(), &warnings)
+    })?;
+    let conflicts = timings.time(Phase::SchemaCheck, || {
+        resolve_nullability(input_files, &skipped_files, options, &warnings)
+    })?;
+    let described = timings.time(Phase::SchemaCheck, || {
+        with_field_metadata(input_files, &skipped_files, options, &warnings)
+    })?;
+    let options = described.as_ref().unwrap_or(options);
+    let open_output = || {
+        let output = File::create(output_path)?;
+        #[cfg(feature = "fault-injection")]
+        crate::fault_injection::inject_fault(crate::fault_injection::FaultPoint::Write, output_path)?;
+        Ok(output)
+    };
+    let output = consolidate_in_memory(scans, open_output, &format!("{:?}", output_path), options, &conflicts, timings, warnings)?;
+    let remaining_files = unread_inputs(input_files, &skipped_files, output.unread_inputs);
+    let mut summary = output.summary(input_files.len() - skipped_files.len() - remaining_files.len(), Some(output_path));
+    summary.skipped_files = skipped_files;
+    summary.remaining_files = remaining_files;
+    summary.nullability_conflicts = conflicts;
+    Ok(summary)
+}
+
+/// Consolidate input files into parquet written to any sink, such as an
+/// HTTP upload or an encryption wrapper, returning the sink once the file
+/// is complete
+///
+/// Row groups cannot be preserved, as that copies them between files.
+///
+/// # Examples
+///
+/// ```
+/// use parquet_consolidator::{consolidate_to_writer, ConsolidateOptions};
+/// use parquet_consolidator::test_utils::create_test_parquet_file;
+/// use tempfile::TempDir;
+///
+/// let temp_dir = TempDir::new().unwrap();
+/// let input = temp_dir.path().join("a.parquet");
+/// create_test_parquet_file(&input, 0, 10).unwrap();
+///
+/// let (summary, buffer) = consolidate_to_writer(&[input], Vec::new(), &ConsolidateOptions::default()).unwrap();
+/// assert_eq!(summary.rows_written, 10);
+/// assert_eq!(&buffer[..4], b"PAR1");
+/// ```
+pub fn consolidate_to_writer<W: Write + Send>(
+    input_files: &[PathBuf],
+    sink: W,
+    options: &ConsolidateOptions,
+) -> Result<(RunSummary, W)> {
+    let span = options.trace.as_ref().map(|parent| parent.start_span("consolidate"));
+    let result = consolidate_to_sink(input_files, sink, options, span.as_ref().map(Span::as_parent));
+    if let Some(span) = span {
+        end_run_span(span, input_files, &result.as_ref().map(|(summary, _)| summary));
+    }
+    result
+}
+
+fn consolidate_to_sink<W: Write + Send>(
+    input_files: &[PathBuf],
+    sink: W,
+    options: &ConsolidateOptions,
+    trace: Option<TraceParent>,
+) -> Result<(RunSummary, W)> {
+    if input_files.is_empty() {
+        anyhow::bail!("No input files provided");
+    }
+    if options.preserve_row_groups && options.pipeline.is_none() {
+        anyhow::bail!("Row groups can only be preserved when writing to a file path");
+    }
+    options.check_cancelled()?;
+
+    options.writer.validate_row_group_limits()?;
+    let mut timings = PhaseTimings::default();
+    let warnings = WarningLog::new(options.print_warnings);
+    timings.time(Phase::SchemaCheck, || warn_about_anomalies(input_files, options, &warnings));
+    timings.time(Phase::SchemaCheck, || warn_about_statistics(input_files, options, &warnings));
+    let tuned = timings.time(Phase::SchemaCheck, || auto_batched(input_files, options, false, &warnings))?;
+    let options = tuned.as_ref().unwrap_or(options);
+    let parallel = timings.time(Phase::SchemaCheck, || row_group_parallel(input_files, options))?;
+    let options = parallel.as_ref().unwrap_or(options);
+
+    if options.pipeline.is_some() {
+        options.ensure_streamable()?;
+        let (rows_written, sink) = timings.time(Phase::Write, || consolidate_pipelined(input_files, sink, options, &warnings))?;
+        let summary = RunSummary {
+            input_files: input_files.len(),
+            rows_written,
+            peak_memory_bytes: peak_rss_bytes(),
+            timings: options.timings.then_some(timings),
+            warnings: warnings.into_warnings(),
+            ..Default::default()
+        };
+        return Ok((summary, sink));
+    }
+
+    let (scans, skipped_files) = timings.time(Phase::SchemaCheck, || {
+        scan_tolerating_errors(input_files, options, trace.as_ref(), &warnings)
+    })?;
+    let conflicts = timings.time(Phase::SchemaCheck, || {
+        resolve_nullability(input_files, &skipped_files, options, &warnings)
+    })?;
+    let described = timings.time(Phase::SchemaCheck, || {
+        with_field_metadata(input_files, &skipped_files, options, &warnings)
+    })?;
+    let options = described.as_ref().unwrap_or(options);
+    let output = consolidate_in_memory(scans, || Ok(sink), "the output writer", options, &conflicts, timings, warnings)?;
+    let remaining_files = unread_inputs(input_files, &skipped_files, output.unread_inputs);
+    let mut summary = output.summary(input_files.len() - skipped_files.len() - remaining_files.len(), None);
+    summary.skipped_files = skipped_files;
+    summary.remaining_files = remaining_files;
+    summary.nullability_conflicts = conflicts;
+    Ok((summary, output.sink))
+}
+
+/// Consolidate the inputs, comparing them with the run's snapshot before
+/// and after they are read
+///
+/// Under [`ChangedInputPolicy::Skip`] the changed inputs are left out and
+/// the output consolidated again, until no input changes while it is
+/// read; they are reported as skipped.
+fn consolidate_snapshot(
+    input_files: &[PathBuf],
+    output_path: &Path,
+    options: &ConsolidateOptions,
+    snapshot: &InputSnapshot,
+    trace: Option<TraceParent>,
+) -> Result<RunSummary> {
+    let mut inputs = input_files.to_vec();
+    let mut left_out = Vec::new();
+    let warnings = WarningLog::new(options.print_warnings);
+    loop {
+        let mut changed = snapshot.changed(&inputs);
+        if changed.is_empty() {
+            let mut summary = consolidate_to_path(&inputs, output_path, options, trace.clone())?;
+            changed = snapshot.changed(&inputs);
+            if changed.is_empty() {
+                summary.skipped_files.extend(left_out);
+                let mut all_warnings = warnings.into_warnings();
+                all_warnings.append(&mut summary.warnings);
+                summary.warnings = all_warnings;
+                return Ok(summary);
+            }
+            if is_named_pipe(output_path) {
+                anyhow::bail!(
+                    "Inputs changed while being written to the named pipe {:?}, whose reader can't be sent them again: {}",
+                    output_path,
+                    changed.iter().map(ToString::to_string).collect::<Vec<_>>().join("; ")
+                );
+            }
+            // The output may hold part of a changed input
+            fs::remove_file(output_path).context(format!("Failed to remove {:?}", output_path))?;
+        }
+        let list = changed.iter().map(ToString::to_string).collect::<Vec<_>>().join("; ");
+        if snapshot.policy == ChangedInputPolicy::Fail {
+            anyhow::bail!("Inputs of {:?} changed after the run's snapshot was taken: {}", output_path, list);
+        }
+        warnings.warn(
+            WarningKind::ChangedInput,
+            format!("Leaving inputs out of {:?} that changed after the run's snapshot was taken: {}", output_path, list),
+        );
+        inputs.retain(|path| !changed.iter().any(|change| &change.path == path));
+        left_out.extend(changed.into_iter().map(|change| change.path));
+    }
+}
+
+/// The last `unread` of the inputs that weren't skipped, which the run
+/// never got to
+fn unread_inputs(input_files: &[PathBuf], skipped_files: &[PathBuf], unread: usize) -> Vec<PathBuf> {
+    let read: Vec<PathBuf> = input_files.iter().filter(|path| !skipped_files.contains(path)).cloned().collect();
+    read[read.len() - unread..].to_vec()
+}
+
+/// The options with the row groups of each input decoded in parallel,
+/// when the inputs are a few large files; see
+/// [`prefers_row_group_parallelism`]
+fn row_group_parallel(input_files: &[PathBuf], options: &ConsolidateOptions) -> Result<Option<ConsolidateOptions>> {
+    if options.read.row_group_parallel || options.preserve_row_groups || options.pipeline.is_some() {
+        return Ok(None);
+    }
+    let threads = thread::available_parallelism().map_or(1, |cpus| cpus.get());
+    if !prefers_row_group_parallelism(input_files, threads)? {
+        return Ok(None);
+    }
+    if options.verbose {
+        println!("Decoding the row groups of each of the {} inputs in parallel", input_files.len());
+    }
+    let mut options = options.clone();
+    options.read.row_group_parallel = true;
+    Ok(Some(options))
+}
+
+/// With `auto_batch`, the options with the execution picked for the inputs
+/// and the memory available applied; `None` when they are used as given
+fn auto_batched(
+    input_files: &[PathBuf],
+    options: &ConsolidateOptions,
+    to_path: bool,
+    warnings: &WarningLog,
+) -> Result<Option<ConsolidateOptions>> {
+    if !options.auto_batch || options.preserve_row_groups || options.pipeline.is_some() {
+        return Ok(None);
+    }
+    let plan = plan_execution(input_files, options, available_memory_bytes(), to_path)?;
+    if options.verbose {
+        println!("{}", plan);
+    }
+    if let Some(warning) = plan.warning() {
+        warnings.warn(WarningKind::MemoryPlan, warning);
+    }
+    Ok(Some(plan.apply(options)))
+}
+
+/// The nullability conflicts among the columns read from the inputs that
+/// weren't skipped, after applying the configured policy: failing on them,
+/// or warning that they are coerced to nullable
+fn resolve_nullability(
+    input_files: &[PathBuf],
+    skipped_files: &[PathBuf],
+    options: &ConsolidateOptions,
+    warnings: &WarningLog,
+) -> Result<Vec<NullabilityConflict>> {
+    let read: Vec<PathBuf> = input_files.iter().filter(|path| !skipped_files.contains(path)).cloned().collect();
+    let mut conflicts = nullability_conflicts(&read)?;
+    conflicts.retain(|conflict| options.read.columns.keeps(&conflict.column));
+    match (options.nullability, conflicts.first()) {
+        (_, None) => {}
+        (NullabilityPolicy::Error, Some(conflict)) => anyhow::bail!(
+            "{} (use another nullability policy to coerce the column to nullable or drop its null rows)",
+            conflict
+        ),
+        (NullabilityPolicy::CoerceNullable, Some(_)) => {
+            for conflict in &conflicts {
+                warnings.warn(WarningKind::NullabilityCoerced, format!("{}; it is written as nullable", conflict));
+            }
+        }
+        (NullabilityPolicy::DropNullRows, Some(_)) => {}
+    }
+    Ok(conflicts)
+}
+
+/// The options with the field metadata of the inputs that weren't skipped
+/// added to the writer's, after applying the configured policy to keys
+/// given different values; `None` when the inputs carry none
+///
+/// Metadata set in the writer options wins over the inputs'.
+fn with_field_metadata(
+    input_files: &[PathBuf],
+    skipped_files: &[PathBuf],
+    options: &ConsolidateOptions,
+    warnings: &WarningLog,
+) -> Result<Option<ConsolidateOptions>> {
+    let read: Vec<PathBuf> = input_files.iter().filter(|path| !skipped_files.contains(path)).cloned().collect();
+    let (mut metadata, conflicts) = merge_field_metadata(&read, options.field_metadata)?;
+    for conflict in &conflicts {
+        let outcome = match options.field_metadata {
+            FieldMetadataPolicy::Error => anyhow::bail!(
+                "{} (use another field metadata policy to keep one of them or leave the key out)",
+                conflict
+            ),
+            FieldMetadataPolicy::First => "the first is written",
+            FieldMetadataPolicy::Last => "the last is written",
+            FieldMetadataPolicy::Drop => "the key is left out",
+        };
+        warnings.warn(WarningKind::FieldMetadataConflict, format!("{}; {}", conflict, outcome));
+    }
+    if metadata.is_empty() {
+        return Ok(None);
+    }
+    for (column, configured) in &options.writer.field_metadata {
+        metadata.entry(column.clone()).or_default().extend(configured.clone());
+    }
+    let mut options = options.clone();
+    options.writer.field_metadata = metadata;
+    Ok(Some(options))
+}
+
+fn end_run_span(mut span: Span, input_files: &[PathBuf], result: &Result<&RunSummary, &anyhow::Error>) {
+    span.set_attribute("input.files", input_files.len());
+    if let Ok(summary) = result {
+        if let Some(path) = &summary.output_path {
+            span.set_attribute("output.path", path.display().to_string());
+        }
+        span.set_attribute("rows.written", summary.rows_written);
+    }
+    span.end(result);
+}
+
+fn warn_about_anomalies(input_files: &[PathBuf], options: &ConsolidateOptions, warnings: &WarningLog) {
+    if options.anomaly_warnings {
+        for anomaly in detect_anomalies(input_files) {
+            warnings.warn(WarningKind::InputAnomaly, anomaly.to_string());
+        }
+    }
+}
+
+fn warn_about_statistics(input_files: &[PathBuf], options: &ConsolidateOptions, warnings: &WarningLog) {
+    if let Some(sample) = options.validate_statistics {
+        for mismatch in validate_statistics(input_files, sample) {
+            warnings.warn(WarningKind::StatisticsMismatch, mismatch.to_string());
+        }
+    }
+}
+
+/// Check that a run's inputs can be merged, from their schemas alone,
+/// before any rows are read
+///
+/// Parquet schemas come from the footers; CSV and NDJSON schemas are
+/// inferred from their first rows, and compressed or archived inputs are
+/// decompressed to find theirs. Each input is merged into the schema of
+/// the inputs before it the way the run would merge them, so the first one
+/// that doesn't fit is named. Runs preserving row groups or pipelined need
+/// identical schemas instead. Nullability and field metadata conflicts are
+/// checked too when their policy is to fail. With `continue_on_error`,
+/// inputs whose schema can't be read are left to the run to skip.
+///
+/// # Examples
+///
+/// ```
+/// use parquet_consolidator::{check_input_schemas, ConsolidateOptions};
+/// use parquet_consolidator::test_utils::create_test_parquet_file;
+/// use tempfile::TempDir;
+///
+/// let temp_dir = TempDir::new().unwrap();
+/// let input = temp_dir.path().join("a.parquet");
+/// let not_parquet = temp_dir.path().join("b.parquet");
+/// create_test_parquet_file(&input, 0, 10).unwrap();
+/// std::fs::write(&not_parquet, "id,name").unwrap();
+///
+/// let options = ConsolidateOptions::default();
+/// assert!(check_input_schemas(&[input.clone()], &options).is_ok());
+/// assert!(check_input_schemas(&[input, not_parquet], &options).is_err());
+/// ```
+pub fn check_input_schemas(input_files: &[PathBuf], options: &ConsolidateOptions) -> Result<()> {
+    if input_files.is_empty() {
+        anyhow::bail!("No input files provided");
+    }
+    if options.preserve_row_groups || options.pipeline.is_some() {
+        identical_input_schema(input_files, &options.read)?;
+        return Ok(());
+    }
+
+    let merge = MergeOptions { union_columns: options.read.union_columns, ..Default::default() };
+    let mut merged: Option<Schema> = None;
+    let mut skipped = Vec::new();
     for input_file in input_files {
-        if verbose {
-            println!("Reading file: {:?}", input_file);
+        let format = InputFormat::from_path(input_file).unwrap_or(InputFormat::Parquet);
+        let schema = format
+            .scan_with_options(input_file, &options.read)
+            .and_then(|scan| scan.schema().context(format!("Failed to resolve the schema of {:?}", input_file)));
+        let schema = match schema {
+            Ok(schema) => schema.as_ref().clone(),
+            Err(_) if options.continue_on_error => {
+                skipped.push(input_file.clone());
+                continue;
+            }
+            Err(err) => return Err(err),
+        };
+        merged = Some(match merged {
+            None => schema,
+            Some(before) => merge_schemas(&[before, schema], &merge).map_err(|err| match err {
+                SchemaMergeError::ColumnCount { expected, found, .. } => anyhow::anyhow!(
+                    "{:?} has {} columns, where the inputs before it have {}",
+                    input_file, found, expected
+                ),
+                SchemaMergeError::ColumnName { position, expected, found, .. } => anyhow::anyhow!(
+                    "{:?} has column {} at position {}, where the inputs before it have {}",
+                    input_file, found, position, expected
+                ),
+                SchemaMergeError::IncompatibleTypes { column, left, right } => anyhow::anyhow!(
+                    "Column {} of {:?} is {}, which doesn't merge with {} in the inputs before it",
+                    column, input_file, right, left
+                ),
+                SchemaMergeError::Empty => anyhow::anyhow!("{}", err),
+            })?,
+        });
+    }
+
+    // Under these policies conflicts fail rather than warn
+    if options.nullability == NullabilityPolicy::Error {
+        resolve_nullability(input_files, &skipped, options, &WarningLog::new(false))?;
+    }
+    if options.field_metadata == FieldMetadataPolicy::Error {
+        with_field_metadata(input_files, &skipped, options, &WarningLog::new(false))?;
+    }
+    Ok(())
+}
+
+/// Describe how a run would read its inputs, without reading any rows
+///
+/// Returns the optimized polars plan of the scans and concatenation, so
+/// projection and predicate pushdown can be checked before a large job,
+/// followed by the steps applied to the collected rows.
+pub fn explain_consolidation(input_files: &[PathBuf], options: &ConsolidateOptions) -> Result<String> {
+    if input_files.is_empty() {
+        anyhow::bail!("No input files provided");
+    }
+
+    if let Some(pipeline) = options.pipeline {
+        let readers = match pipeline.readers {
+            0 => "one per CPU".to_string(),
+            readers => readers.to_string(),
+        };
+        return Ok(format!(
+            "Decode the row groups of {} parquet files on {} readers, transform and encode them in overlapping stages; no query plan is executed\n",
+            input_files.len(),
+            readers
+        ));
+    }
+
+    if options.preserve_row_groups {
+        return Ok(format!(
+            "Copy the row groups of {} parquet files unchanged; no query plan is executed\n",
+            input_files.len()
+        ));
+    }
+
+    let plan = concat_scans(scan_input_files(input_files, &options.read, false, None)?, &options.read)?
+        .describe_optimized_plan()
+        .context("Failed to optimize the query plan")?;
+    let mut explanation = format!("{}\n", plan.trim_end());
+    let features = options.row_level_features();
+    if !features.is_empty() {
+        explanation.push_str(&format!("Then in memory: {}\n", features.join(", ")));
+    }
+    Ok(explanation)
+}
+
+/// Discover the inputs under a path and merge them into one lazy frame
+/// without collecting or writing anything
+///
+/// The frame has the schema consolidation would write, so applications
+/// can add their own transformations or queries and decide how to sink
+/// the result.
+///
+/// # Examples
+///
+/// ```
+/// use polars::prelude::*;
+/// use parquet_consolidator::{consolidate_to_lazyframe, DiscoveryOptions, ReadOptions};
+/// use parquet_consolidator::test_utils::create_test_parquet_file;
+/// use tempfile::TempDir;
+///
+/// let temp_dir = TempDir::new().unwrap();
+/// create_test_parquet_file(&temp_dir.path().join("a.parquet"), 0, 10).unwrap();
+/// create_test_parquet_file(&temp_dir.path().join("b.parquet"), 10, 20).unwrap();
+///
+/// let lf = consolidate_to_lazyframe(temp_dir.path(), &DiscoveryOptions::default(), &ReadOptions::default()).unwrap();
+/// let df = lf.filter(col("id").gt_eq(lit(15))).collect().unwrap();
+/// assert_eq!(df.height(), 5);
+/// ```
+#[cfg(feature = "discovery")]
+pub fn consolidate_to_lazyframe(input_path: &Path, discovery: &DiscoveryOptions, read: &ReadOptions) -> Result<LazyFrame> {
+    let input_files = discover_input_files(input_path, discovery)?;
+    if input_files.is_empty() {
+        anyhow::bail!("No {} files found in {:?}", discovery.format_names(), input_path);
+    }
+    concat_scans(scan_input_files(&input_files, read, false, None)?, read)
+}
+
+/// Open a lazy scan of every input, recording a span per file under `trace`
+///
+/// Opening a scan reads the file's schema; rows are decoded later, for all
+/// inputs at once.
+fn scan_input_files(
+    input_files: &[PathBuf],
+    read: &ReadOptions,
+    verbose: bool,
+    trace: Option<&TraceParent>,
+) -> Result<Vec<LazyFrame>> {
+    input_files.iter().map(|input_file| scan_input_file(input_file, read, verbose, trace)).collect()
+}
+
+/// Open a lazy scan of every input as the options ask, returning the scans
+/// and the inputs skipped as unreadable
+///
+/// With `continue_on_error`, inputs whose scan fails are skipped with a
+/// warning, and the run fails as soon as the skipped inputs exceed the
+/// abort threshold, rather than consolidating a small healthy subset. With
+/// a `file_timeout`, inputs are read in full up front and one that times out
+/// counts as failed.
+fn scan_tolerating_errors(
+    input_files: &[PathBuf],
+    options: &ConsolidateOptions,
+    trace: Option<&TraceParent>,
+    warnings: &WarningLog,
+) -> Result<(Vec<LazyFrame>, Vec<PathBuf>)> {
+    let results: Box<dyn Iterator<Item = Result<LazyFrame>>> = match options.file_timeout {
+        Some(timeout) => Box::new(read_with_timeout(input_files, options, timeout, trace).into_iter()),
+        None => Box::new(input_files.iter().map(|input_file| scan_input_file(input_file, &options.read, options.verbose, trace))),
+    };
+    if !options.continue_on_error {
+        return Ok((results.collect::<Result<_>>()?, Vec::new()));
+    }
+
+    let mut scans = Vec::new();
+    let mut skipped = Vec::new();
+    for (input_file, result) in input_files.iter().zip(results) {
+        match result {
+            Ok(scan) => scans.push(scan),
+            Err(err) => {
+                warnings.warn(WarningKind::SkippedInput, format!("Skipping input: {:#}", err));
+                skipped.push(input_file.clone());
+                if let Some(threshold) = options.abort_threshold {
+                    if threshold.exceeded(skipped.len(), input_files.len()) {
+                        anyhow::bail!(
+                            "Skipped {} of {} inputs, more than the abort threshold of {}",
+                            skipped.len(),
+                            input_files.len(),
+                            threshold
+                        );
+                    }
+                }
+            }
+        }
+    }
+    if scans.is_empty() {
+        anyhow::bail!("None of the {} inputs could be read", input_files.len());
+    }
+    Ok((scans, skipped))
+}
+
+/// Read every input into memory on its own thread, at most as many at a
+/// time as the open file budget or the CPUs allow, giving up on those not
+/// read within `timeout` of their start
+///
+/// A read that times out can't be cancelled; its thread is left to finish
+/// or hang in the background and its result is discarded.
+fn read_with_timeout(
+    input_files: &[PathBuf],
+    options: &ConsolidateOptions,
+    timeout: Duration,
+    trace: Option<&TraceParent>,
+) -> Vec<Result<LazyFrame>> {
+    let workers = options
+        .max_open_files
+        .unwrap_or_else(|| thread::available_parallelism().map_or(1, |cpus| cpus.get()))
+        .max(1);
+    let mut results = Vec::with_capacity(input_files.len());
+    for chunk in input_files.chunks(workers) {
+        let started = Instant::now();
+        let reads: Vec<mpsc::Receiver<Result<DataFrame>>> = chunk
+            .iter()
+            .map(|input_file| {
+                let (sender, receiver) = mpsc::channel();
+                let (input_file, read, verbose, trace) = (input_file.clone(), options.read.clone(), options.verbose, trace.cloned());
+                thread::spawn(move || {
+                    let frame = scan_input_file(&input_file, &read, verbose, trace.as_ref())
+                        .and_then(|scan| scan.collect().context(format!("Failed to read {:?}", input_file)));
+                    let _ = sender.send(frame);
+                });
+                receiver
+            })
+            .collect();
+        for (input_file, read) in chunk.iter().zip(reads) {
+            results.push(match read.recv_timeout(timeout.saturating_sub(started.elapsed())) {
+                Ok(frame) => frame.map(DataFrame::lazy),
+                Err(mpsc::RecvTimeoutError::Timeout) => Err(anyhow::anyhow!(
+                    "Gave up on {:?} after {}s: reading it didn't finish within the file timeout",
+                    input_file,
+                    timeout.as_secs_f64()
+                )),
+                Err(mpsc::RecvTimeoutError::Disconnected) => Err(anyhow::anyhow!("Reading {:?} panicked", input_file)),
+            });
         }
+    }
+    results
+}
 
-        let df = LazyFrame::scan_parquet(input_file.to_str().unwrap(), Default::default())?;
-        dfs.push(df);
+/// Open a lazy scan of one input, recording a span under `trace`
+fn scan_input_file(input_file: &Path, read: &ReadOptions, verbose: bool, trace: Option<&TraceParent>) -> Result<LazyFrame> {
+    if verbose {
+        println!("Reading file: {:?}", input_file);
+    }
+
+    let span = trace.map(|parent| parent.start_span("read file"));
+    let format = InputFormat::from_path(input_file).unwrap_or(InputFormat::Parquet);
+    let scan = format.scan_with_options(input_file, read)
+        .context(format!("Failed to read {:?}", input_file));
+    if let Some(mut span) = span {
+        span.set_attribute("file.path", input_file.display().to_string());
+        span.set_attribute("file.format", format.to_string());
+        span.set_attribute("file.size_bytes", file_size(input_file));
+        span.end(&scan);
     }
+    scan
+}
 
+/// Stack the inputs, widening column types to their common supertypes
+fn concat_scans(scans: Vec<LazyFrame>, read: &ReadOptions) -> Result<LazyFrame> {
+    let scans = if read.union_columns { project_to_union(scans, &read.fill_missing)? } else { scans };
     let union_args = UnionArgs { parallel: true, rechunk: true, to_supertypes: true };
-    let mut concat_df = concat(dfs, union_args)
-        .context("Failed to concatenate DataFrames")?
+    concat(scans, union_args).context("Failed to concatenate DataFrames")
+}
+
+/// Project every scan onto the union of the scans' columns
+///
+/// Each scan keeps selecting only the columns its input has, so projection
+/// pushdown still reads just those, and the columns it lacks are literals
+/// of the column's type, null unless `fills` gives a value, expanded only
+/// as the frames are stacked.
+fn project_to_union(scans: Vec<LazyFrame>, fills: &[ColumnFill]) -> Result<Vec<LazyFrame>> {
+    let schemas = scans
+        .iter()
+        .map(|scan| Ok(scan.schema()?.as_ref().clone()))
+        .collect::<Result<Vec<Schema>>>()
+        .context("Failed to resolve the input schemas")?;
+    let union = merge_schemas(&schemas, &MergeOptions { union_columns: true, ..Default::default() })
+        .context("Failed to merge input schemas")?;
+
+    scans
+        .into_iter()
+        .zip(&schemas)
+        .map(|(scan, schema)| {
+            if schema.iter_names().eq(union.iter_names()) {
+                return Ok(scan);
+            }
+            let columns = union
+                .iter()
+                .map(|(name, data_type)| match schema.contains(name) {
+                    true => Ok(col(name)),
+                    false => missing_column_value(name, data_type, fills),
+                })
+                .collect::<Result<Vec<Expr>>>()?;
+            Ok(scan.select(columns))
+        })
         .collect()
-        .context("Failed to execute lazy computation")?;
+}
+
+/// Consolidate inputs read from arbitrary byte sources into a single file
+///
+/// Each input is buffered in memory and read in full, so this suits
+/// embedders holding inputs in buffers, archives or databases rather than
+/// on the filesystem. Row groups cannot be preserved.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::Cursor;
+/// use parquet_consolidator::{consolidate_readers, ConsolidateOptions, InputFormat, ReaderInput};
+/// use tempfile::TempDir;
+///
+/// let temp_dir = TempDir::new().unwrap();
+/// let inputs = vec![
+///     ReaderInput::new("first", InputFormat::Csv, Cursor::new(b"id\n1\n2\n".to_vec())),
+///     ReaderInput::new("second", InputFormat::Csv, Cursor::new(b"id\n3\n".to_vec())),
+/// ];
+///
+/// let output = temp_dir.path().join("out.parquet");
+/// let summary = consolidate_readers(inputs, &output, &ConsolidateOptions::default()).unwrap();
+/// assert_eq!(summary.rows_written, 3);
+/// ```
+pub fn consolidate_readers(inputs: Vec<ReaderInput>, output_path: &Path, options: &ConsolidateOptions) -> Result<RunSummary> {
+    if inputs.is_empty() {
+        anyhow::bail!("No input files provided");
+    }
+    if options.preserve_row_groups {
+        anyhow::bail!("Row groups can only be preserved when consolidating parquet files by path");
+    }
+    if options.pipeline.is_some() {
+        anyhow::bail!("Only parquet files consolidated by path can be pipelined");
+    }
+    if options.deadline.is_some() {
+        anyhow::bail!("A deadline only applies to files consolidated by path");
+    }
+
+    options.writer.validate_row_group_limits()?;
+
+    let input_count = inputs.len();
+    let mut timings = PhaseTimings::default();
+    let mut frames = Vec::new();
+    for input in inputs {
+        if options.verbose {
+            println!("Reading input: {}", input.name);
+        }
+        frames.push(timings.time(Phase::Decode, || input.read(&options.read))?.lazy());
+    }
+
+    let open_output = || {
+        let output = File::create(output_path)?;
+        #[cfg(feature = "fault-injection")]
+        crate::fault_injection::inject_fault(crate::fault_injection::FaultPoint::Write, output_path)?;
+        Ok(output)
+    };
+    let warnings = WarningLog::new(options.print_warnings);
+    let output = consolidate_in_memory(frames, open_output, &format!("{:?}", output_path), options, &[], timings, warnings)?;
+    Ok(output.summary(input_count, Some(output_path)))
+}
+
+fn finish_summary(
+    input_count: usize,
+    output_path: &Path,
+    duplicate_keys: Option<DuplicateKeyReport>,
+) -> Result<RunSummary> {
+    let reader = SerializedFileReader::new(File::open(output_path)?)
+        .context(format!("Failed to read back {:?}", output_path))?;
+    Ok(RunSummary {
+        output_path: Some(output_path.to_path_buf()),
+        input_files: input_count,
+        rows_written: reader.metadata().file_metadata().num_rows() as usize,
+        duplicate_keys,
+        peak_memory_bytes: peak_rss_bytes(),
+        timings: None,
+        ..Default::default()
+    })
+}
+
+/// The outcome of an in-memory consolidation
+struct InMemoryOutput<W> {
+    sink: W,
+    rows_written: usize,
+    duplicate_keys: Option<DuplicateKeyReport>,
+    null_rows_dropped: usize,
+    timings: Option<PhaseTimings>,
+    /// Trailing inputs not read because the deadline passed
+    unread_inputs: usize,
+    codec_choices: Vec<ColumnCodecChoice>,
+    warnings: Vec<Warning>,
+}
 
+impl<W> InMemoryOutput<W> {
+    fn summary(&self, input_count: usize, output_path: Option<&Path>) -> RunSummary {
+        RunSummary {
+            output_path: output_path.map(Path::to_path_buf),
+            input_files: input_count,
+            rows_written: self.rows_written,
+            duplicate_keys: self.duplicate_keys.clone(),
+            peak_memory_bytes: peak_rss_bytes(),
+            timings: self.timings.clone(),
+            null_rows_dropped: self.null_rows_dropped,
+            codec_choices: self.codec_choices.clone(),
+            warnings: self.warnings.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Inputs read at a time by a run with a deadline, which is checked
+/// between batches
+pub const DEADLINE_BATCH_FILES: usize = 16;
+
+/// Whether the run's deadline has passed
+fn deadline_passed(options: &ConsolidateOptions) -> bool {
+    options.deadline.is_some_and(|deadline| Instant::now() >= deadline)
+}
+
+/// Decode every input separately, `batch` of them at a time, or all at once
+///
+/// No batch but the first is started after `deadline`. Returns the frames
+/// and how many trailing inputs were left unread.
+fn collect_in_batches(dfs: Vec<LazyFrame>, batch: Option<usize>, options: &ConsolidateOptions) -> Result<(Vec<DataFrame>, usize)> {
+    let batch = batch.unwrap_or(dfs.len()).max(1);
+    let mut frames = Vec::with_capacity(dfs.len());
+    let mut unread = dfs.len();
+    for chunk in dfs.chunks(batch) {
+        options.check_cancelled()?;
+        if !frames.is_empty() && deadline_passed(options) {
+            break;
+        }
+        frames.extend(collect_all(chunk.to_vec())?);
+        unread -= chunk.len();
+    }
+    Ok((frames, unread))
+}
+
+/// Stack the inputs `batch` at a time, so no more than `batch` of them are
+/// open while being decoded
+///
+/// No batch but the first is started after `deadline`. Returns the stacked
+/// batches and how many trailing inputs were left unread.
+fn stack_in_batches(
+    dfs: Vec<LazyFrame>,
+    batch: usize,
+    options: &ConsolidateOptions,
+) -> Result<(Vec<DataFrame>, usize)> {
+    let mut stacked = Vec::new();
+    let mut unread = dfs.len();
+    for chunk in dfs.chunks(batch.max(1)) {
+        options.check_cancelled()?;
+        if !stacked.is_empty() && deadline_passed(options) {
+            break;
+        }
+        stacked.push(concat_scans(chunk.to_vec(), &options.read)?.collect().context("Failed to execute lazy computation")?);
+        unread -= chunk.len();
+    }
+    Ok((stacked, unread))
+}
+
+/// Collect every input into memory, apply the row-level options and write
+/// the result to the sink, which is only opened once there is something to
+/// write
+///
+/// Under [`NullabilityPolicy::DropNullRows`], rows null in any of the
+/// `conflicts` columns are dropped first.
+fn consolidate_in_memory<W: Write + Send>(
+    dfs: Vec<LazyFrame>,
+    open_sink: impl FnOnce() -> Result<W>,
+    destination: &str,
+    options: &ConsolidateOptions,
+    conflicts: &[NullabilityConflict],
+    mut timings: PhaseTimings,
+    warnings: WarningLog,
+) -> Result<InMemoryOutput<W>> {
+    let verbose = options.verbose;
+    let mut unread_inputs = 0;
+    let over_budget = options.max_open_files.filter(|&max| dfs.len() > max);
+    let batch = match options.deadline {
+        Some(_) => Some(options.max_open_files.map_or(DEADLINE_BATCH_FILES, |max| max.min(DEADLINE_BATCH_FILES))),
+        None => over_budget,
+    };
+    if let Some(batch) = batch.filter(|&batch| verbose && dfs.len() > batch) {
+        let reason = match over_budget {
+            Some(_) => "to stay under the open file budget",
+            None => "to check the deadline between them",
+        };
+        println!("Reading {} inputs in batches of {} {}", dfs.len(), batch, reason);
+    }
+    let mut concat_df = if options.timings {
+        // Decoding separately from stacking lets each be timed on its own
+        let (frames, unread) = timings.time(Phase::Decode, || collect_in_batches(dfs, batch, options))
+            .context("Failed to read the inputs")?;
+        unread_inputs = unread;
+        let stacked = timings.time(Phase::Concat, || concat_scans(frames.into_iter().map(DataFrame::lazy).collect(), &options.read))?;
+        timings.time(Phase::Concat, || stacked.collect()).context("Failed to concatenate DataFrames")?
+    } else if let Some(batch) = batch {
+        let (batches, unread) = timings.time(Phase::Decode, || stack_in_batches(dfs, batch, options))?;
+        unread_inputs = unread;
+        let stacked = concat_scans(batches.into_iter().map(DataFrame::lazy).collect(), &options.read)?;
+        timings.time(Phase::Concat, || stacked.collect()).context("Failed to concatenate DataFrames")?
+    } else {
+        let stacked = concat_scans(dfs, &options.read)?;
+        timings.time(Phase::Decode, || stacked.collect()).context("Failed to execute lazy computation")?
+    };
+    if verbose && unread_inputs > 0 {
+        println!("The deadline passed with {} inputs left unread; writing the batches read so far", unread_inputs);
+    }
+    options.check_cancelled()?;
+    let row_options = PhaseClock::start();
+
+    let mut null_rows_dropped = 0;
+    if options.nullability == NullabilityPolicy::DropNullRows && !conflicts.is_empty() {
+        let columns: Vec<String> = conflicts.iter().map(|conflict| conflict.column.clone()).collect();
+        let rows = concat_df.height();
+        concat_df = concat_df.drop_nulls(Some(&columns))?;
+        null_rows_dropped = rows - concat_df.height();
+        if verbose {
+            println!("Dropped {} rows with nulls in columns required by other inputs", null_rows_dropped);
+        }
+    }
+
+    let duplicate_keys = if options.report_duplicates.is_empty() {
+        None
+    } else {
+        Some(duplicate_key_report(&concat_df, &options.report_duplicates, DUPLICATE_SAMPLES)?)
+    };
+
+    if let Some(cdc) = &options.cdc {
+        let changes = concat_df.height();
+        concat_df = apply_changes(&concat_df, cdc)?;
+        if verbose {
+            println!("Merged {} change records into {} rows", changes, concat_df.height());
+        }
+    }
+
+    redact(&mut concat_df, &options.redactions)?;
+    if !options.transforms.is_empty() {
+        concat_df = apply_transforms(concat_df, &options.transforms)?;
+    }
+
+    let mut seen_keys = match &options.dedupe_state {
+        Some(_) if options.dedupe_keys.is_empty() => anyhow::bail!("A dedupe state requires dedupe key columns"),
+        Some(path) => Some(SeenKeys::load(path)?),
+        None => None,
+    };
+    if !options.dedupe_keys.is_empty() {
+        let rows = concat_df.height();
+        concat_df = drop_duplicate_keys(&concat_df, &options.dedupe_keys)?;
+        if let Some(seen_keys) = &seen_keys {
+            concat_df = seen_keys.filter_unseen(&concat_df, &options.dedupe_keys)?;
+        }
+        if verbose {
+            println!("Dropped {} rows with duplicate keys", rows - concat_df.height());
+        }
+    }
+
+    if let Some(checks) = &options.checks {
+        let mut results = checks.run(concat_df)?;
+        concat_df = results.passed;
+        for outcome in results.outcomes.iter().filter(|outcome| outcome.violations > 0) {
+            let message = match (outcome.policy, &options.quarantine_path) {
+                (CheckPolicy::Quarantine, Some(path)) => format!("{}; they were quarantined to {:?}", outcome, path),
+                (CheckPolicy::Quarantine, None) => format!("{}; they were dropped", outcome),
+                _ => outcome.to_string(),
+            };
+            warnings.warn(WarningKind::QualityCheck, message);
+        }
+
+        if let Some(path) = &options.quarantine_path {
+            if verbose {
+                println!("Writing {} quarantined rows to {:?}", results.rejected.height(), path);
+            }
+            File::create(path)
+                .map_err(anyhow::Error::from)
+                .and_then(|file| write_dataframe_logged(&mut results.rejected, file, &options.writer, &warnings))
+                .context(format!("Failed to write quarantined rows to {:?}", path))?;
+        }
+    }
+
+    timings.record(Phase::RowOptions, row_options.stop());
+
+    options.check_cancelled()?;
+    if verbose {
+        println!("Writing consolidated parquet file to {}", destination);
+    }
+
+    // Chosen here rather than by the writer, so the run can report them
+    let codec_choices = match options.writer.auto_compression {
+        true => timings.time(Phase::Write, || choose_column_codecs(&concat_df, &options.writer))?,
+        false => Vec::new(),
+    };
     if verbose {
-        println!("Writing consolidated parquet file to {:?}", output_path);
+        for choice in &codec_choices {
+            println!("Compressing {}", choice);
+        }
     }
+    let writer = apply_codec_choices(&options.writer, &codec_choices);
+    let sink = timings.time(Phase::Write, || write_dataframe_logged(&mut concat_df, open_sink()?, &writer, &warnings))?;
 
-    let file = File::create(output_path)?;
-    ParquetWriter::new(file)
-        .with_compression(ParquetCompression::Snappy)
-        .finish(&mut concat_df)
-        .context("Failed to write consolidated parquet file")?;
+    // Keys are only remembered once their rows are safely written
+    if let Some(seen_keys) = &mut seen_keys {
+        seen_keys.record(&concat_df, &options.dedupe_keys)?;
+        seen_keys.save()?;
+    }
 
-    Ok(())
+    Ok(InMemoryOutput {
+        sink,
+        rows_written: concat_df.height(),
+        duplicate_keys,
+        null_rows_dropped,
+        timings: options.timings.then_some(timings),
+        unread_inputs,
+        codec_choices,
+        warnings: warnings.into_warnings(),
+    })
 }
 
 #[cfg(test)]
@@ -138,6 +1495,7 @@ mod tests {
         assert!(!is_parquet_file(std::path::Path::new("test.")));
     }
 
+    #[cfg(feature = "discovery")]
     #[test]
     fn test_find_parquet_files_single_file() -> Result<()> {
         let temp_dir = TempDir::new()?;
@@ -153,6 +1511,7 @@ mod tests {
         Ok(())
     }
 
+    #[cfg(feature = "discovery")]
     #[test]
     fn test_find_parquet_files_non_parquet_file() {
         let temp_dir = TempDir::new().unwrap();
@@ -166,6 +1525,7 @@ mod tests {
         assert!(result.unwrap_err().to_string().contains("not a parquet file"));
     }
 
+    #[cfg(feature = "discovery")]
     #[test]
     fn test_find_parquet_files_directory_non_recursive() -> Result<()> {
         let temp_dir = TempDir::new()?;
@@ -180,7 +1540,7 @@ mod tests {
         fs::create_dir(&sub_dir)?;
         create_test_parquet_file(&sub_dir.join("file4.parquet"), 10, 15)?;
         
-        let result = find_parquet_files(&temp_dir.path().to_path_buf(), false)?;
+        let result = find_parquet_files(temp_dir.path(), false)?;
         
         // Should find only the 2 parquet files in the root directory
         assert_eq!(result.len(), 2);
@@ -197,6 +1557,7 @@ mod tests {
         Ok(())
     }
 
+    #[cfg(feature = "discovery")]
     #[test]
     fn test_find_parquet_files_directory_recursive() -> Result<()> {
         let temp_dir = TempDir::new()?;
@@ -215,7 +1576,7 @@ mod tests {
         fs::create_dir(&nested_dir)?;
         create_test_parquet_file(&nested_dir.join("file4.parquet"), 15, 20)?;
         
-        let result = find_parquet_files(&temp_dir.path().to_path_buf(), true)?;
+        let result = find_parquet_files(temp_dir.path(), true)?;
         
         // Should find all 4 parquet files
         assert_eq!(result.len(), 4);
@@ -233,11 +1594,12 @@ mod tests {
         Ok(())
     }
 
+    #[cfg(feature = "discovery")]
     #[test]
     fn test_find_parquet_files_empty_directory() -> Result<()> {
         let temp_dir = TempDir::new()?;
         
-        let result = find_parquet_files(&temp_dir.path().to_path_buf(), false)?;
+        let result = find_parquet_files(temp_dir.path(), false)?;
         assert_eq!(result.len(), 0);
         
         Ok(())
@@ -270,6 +1632,210 @@ mod tests {
         Ok(())
     }
 
+    #[cfg(feature = "discovery")]
+    #[test]
+    fn test_consolidate_mixed_formats() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let output_file = temp_dir.path().join("output.parquet");
+        
+        create_test_parquet_file(&temp_dir.path().join("file1.parquet"), 0, 10)?;
+        fs::write(temp_dir.path().join("file2.csv"), "id,name,value\n10,name_10,15.0\n11,name_11,16.5\n")?;
+        fs::write(
+            temp_dir.path().join("file3.ndjson"),
+            "{\"id\": 12, \"name\": \"name_12\", \"value\": 18.0}\n",
+        )?;
+        
+        let formats = [InputFormat::Parquet, InputFormat::Csv, InputFormat::Ndjson];
+        let input_files = find_input_files(temp_dir.path(), false, &formats)?;
+        assert_eq!(input_files.len(), 3);
+        
+        consolidate_parquet_files(&input_files, &output_file, false)?;
+        
+        let df = LazyFrame::scan_parquet(&output_file, Default::default())?
+            .collect()?;
+        assert_eq!(df.height(), 13);
+        
+        Ok(())
+    }
+
+    #[test]
+    fn test_consolidate_readers() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let parquet_file = temp_dir.path().join("file1.parquet");
+        let output_file = temp_dir.path().join("output.parquet");
+        create_test_parquet_file(&parquet_file, 0, 10)?;
+        
+        let inputs = vec![
+            ReaderInput::new("buffer.parquet", InputFormat::Parquet, std::io::Cursor::new(fs::read(&parquet_file)?)),
+            ReaderInput::new(
+                "blob.ndjson",
+                InputFormat::Ndjson,
+                &b"{\"id\": 10, \"name\": \"name_10\", \"value\": 15.0}\n"[..],
+            ),
+        ];
+        let summary = consolidate_readers(inputs, &output_file, &ConsolidateOptions::default())?;
+        assert_eq!(summary.input_files, 2);
+        assert_eq!(summary.rows_written, 11);
+        
+        let corrupt = vec![ReaderInput::new("corrupt.parquet", InputFormat::Parquet, &b"not parquet"[..])];
+        let err = consolidate_readers(corrupt, &output_file, &ConsolidateOptions::default()).unwrap_err();
+        assert!(err.to_string().contains("corrupt.parquet"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_continue_on_error_abort_threshold() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let output_file = temp_dir.path().join("output.parquet");
+        let mut input_files = Vec::new();
+        for i in 0..4 {
+            let file = temp_dir.path().join(format!("file{}.parquet", i));
+            create_test_parquet_file(&file, i * 10, i * 10 + 10)?;
+            input_files.push(file);
+        }
+        let corrupt = temp_dir.path().join("corrupt.parquet");
+        fs::write(&corrupt, b"not parquet")?;
+        input_files.push(corrupt.clone());
+
+        assert!(consolidate_with_options(&input_files, &output_file, &ConsolidateOptions::default()).is_err());
+
+        let options = ConsolidateOptions { continue_on_error: true, ..Default::default() };
+        let summary = consolidate_with_options(&input_files, &output_file, &options)?;
+        assert_eq!(summary.input_files, 4);
+        assert_eq!(summary.rows_written, 40);
+        assert_eq!(summary.skipped_files, vec![corrupt]);
+
+        let options = ConsolidateOptions { abort_threshold: Some("20%".parse()?), ..options };
+        assert_eq!(consolidate_with_options(&input_files, &output_file, &options)?.rows_written, 40);
+        let options = ConsolidateOptions { abort_threshold: Some("10%".parse()?), ..options };
+        let err = consolidate_with_options(&input_files, &output_file, &options).unwrap_err();
+        assert_eq!(err.to_string(), "Skipped 1 of 5 inputs, more than the abort threshold of 10%");
+        let options = ConsolidateOptions { abort_threshold: Some(AbortThreshold::Count(0)), ..options };
+        assert!(consolidate_with_options(&input_files, &output_file, &options).is_err());
+
+        assert!("150%".parse::<AbortThreshold>().is_err());
+        assert!("many".parse::<AbortThreshold>().is_err());
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_file_timeout_skips_hung_inputs() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let output_file = temp_dir.path().join("output.parquet");
+        let healthy = temp_dir.path().join("healthy.parquet");
+        create_test_parquet_file(&healthy, 0, 10)?;
+        // Opening a FIFO for reading blocks until a writer shows up, like a
+        // file on a hung mount
+        let hung = temp_dir.path().join("hung.parquet");
+        assert!(std::process::Command::new("mkfifo").arg(&hung).status()?.success());
+        let input_files = vec![healthy, hung.clone()];
+
+        let options = ConsolidateOptions { file_timeout: Some(Duration::from_millis(500)), ..Default::default() };
+        let err = consolidate_with_options(&input_files, &output_file, &options).unwrap_err();
+        assert!(err.to_string().starts_with(&format!("Gave up on {:?} after 0.5s", hung)));
+
+        let options = ConsolidateOptions { continue_on_error: true, ..options };
+        let summary = consolidate_with_options(&input_files, &output_file, &options)?;
+        assert_eq!(summary.rows_written, 10);
+        assert_eq!(summary.skipped_files, vec![hung]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_consolidate_in_batches_under_open_file_budget() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let output_file = temp_dir.path().join("output.parquet");
+        let mut input_files = Vec::new();
+        for i in 0..7 {
+            let file = temp_dir.path().join(format!("file{}.parquet", i));
+            create_test_parquet_file(&file, i * 10, i * 10 + 10)?;
+            input_files.push(file);
+        }
+
+        for timings in [false, true] {
+            let options = ConsolidateOptions { max_open_files: Some(3), timings, ..Default::default() };
+            let summary = consolidate_with_options(&input_files, &output_file, &options)?;
+            assert_eq!(summary.rows_written, 70);
+
+            let df = LazyFrame::scan_parquet(&output_file, Default::default())?.collect()?;
+            let ids: Vec<i32> = df.column("id")?.i32()?.into_no_null_iter().collect();
+            assert_eq!(ids, (0..70).collect::<Vec<_>>());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_deadline_without_open_file_budget() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let output_file = temp_dir.path().join("output.parquet");
+        let mut input_files = Vec::new();
+        for i in 0..20 {
+            let file = temp_dir.path().join(format!("file{:02}.parquet", i));
+            create_test_parquet_file(&file, i * 10, i * 10 + 10)?;
+            input_files.push(file);
+        }
+
+        // Only the first batch is read once the deadline has passed, whether
+        // or not the phases are timed
+        for timings in [false, true] {
+            let options = ConsolidateOptions { deadline: Some(Instant::now()), timings, ..Default::default() };
+            let summary = consolidate_with_options(&input_files, &output_file, &options)?;
+            assert_eq!(summary.rows_written, DEADLINE_BATCH_FILES * 10);
+            assert_eq!(summary.remaining_files, input_files[DEADLINE_BATCH_FILES..]);
+        }
+
+        let options = ConsolidateOptions { deadline: Some(Instant::now()), preserve_row_groups: true, ..Default::default() };
+        let err = consolidate_with_options(&input_files, &output_file, &options).unwrap_err();
+        assert!(err.to_string().contains("A deadline cannot be combined with preserving row groups"), "{:#}", err);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_explain_consolidation() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let file1 = temp_dir.path().join("file1.parquet");
+        let file2 = temp_dir.path().join("file2.parquet");
+        create_test_parquet_file(&file1, 0, 10)?;
+        create_test_parquet_file(&file2, 10, 20)?;
+        
+        let options = ConsolidateOptions { dedupe_keys: vec!["id".to_string()], ..Default::default() };
+        let explanation = explain_consolidation(&[file1.clone(), file2], &options)?;
+        assert!(explanation.contains("UNION"));
+        assert!(explanation.contains("file1.parquet"));
+        assert!(explanation.ends_with("Then in memory: Deduplication\n"));
+        
+        let options = ConsolidateOptions { preserve_row_groups: true, ..Default::default() };
+        assert!(explain_consolidation(&[file1], &options)?.contains("row groups of 1 parquet files"));
+        
+        Ok(())
+    }
+
+    #[test]
+    fn test_consolidate_large_files() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let output_file = temp_dir.path().join("output.parquet");
+
+        // A few large files instead of many small ones
+        let mut input_files = Vec::new();
+        for i in 0..3 {
+            let file = temp_dir.path().join(format!("large_file_{}.parquet", i));
+            create_test_parquet_file(&file, i * 50_000, (i + 1) * 50_000)?;
+            input_files.push(file);
+        }
+        consolidate_parquet_files(&input_files, &output_file, false)?;
+
+        let df = LazyFrame::scan_parquet(&output_file, Default::default())?.collect()?;
+        assert_eq!(df.height(), 150_000);
+
+        Ok(())
+    }
+
     #[test]
     fn test_consolidate_parquet_files_empty_input() {
         let temp_dir = TempDir::new().unwrap();
@@ -301,4 +1867,32 @@ mod tests {
         
         Ok(())
     }
+
+    #[test]
+    fn test_consolidate_union_columns() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let file1 = temp_dir.path().join("file1.parquet");
+        let file2 = temp_dir.path().join("file2.parquet");
+        let output_file = temp_dir.path().join("output.parquet");
+        create_test_parquet_file_with_extra_column(&file1, 0, 10)?;
+        create_test_parquet_file(&file2, 10, 20)?;
+
+        let options = ConsolidateOptions {
+            read: ReadOptions { union_columns: true, ..Default::default() },
+            ..Default::default()
+        };
+        let input_files = vec![file1, file2];
+        let summary = consolidate_with_options(&input_files, &output_file, &options)?;
+        assert_eq!(summary.rows_written, 20);
+
+        let df = ParquetReader::new(File::open(&output_file)?).finish()?;
+        assert_eq!(df.get_column_names(), ["id", "name", "value", "extra"]);
+        assert_eq!(df.column("extra")?.null_count(), 10);
+
+        // Only the columns an input has are scanned from it
+        let plan = explain_consolidation(&input_files, &options)?;
+        assert!(plan.contains("PROJECT */3 COLUMNS"), "{}", plan);
+
+        Ok(())
+    }
 }
@@ -0,0 +1,93 @@
+use std::fs::File;
+use std::path::PathBuf;
+use anyhow::{Result, Context};
+use arrow::array::RecordBatchReader;
+use arrow::row::{RowConverter, SortField};
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use xxhash_rust::xxh3::xxh3_128;
+
+/// Digest of the rows of parquet files that doesn't depend on their order,
+/// as 32 hex digits
+///
+/// Each row is hashed with XXH3-128 over arrow's row encoding of its
+/// values, and the hashes are summed, so rows repeated keep counting. Two
+/// runs producing the same rows get the same digest however their row
+/// groups, compression or splitting into files differ, while checking it
+/// costs one decode of the output instead of a sort. Column names aren't
+/// part of it, but types are: the same values as `Int32` and as `Int64`
+/// digest differently. Digests are only comparable between builds using
+/// the same arrow release, whose row encoding they rely on.
+///
+/// # Examples
+///
+/// ```
+/// use parquet_consolidator::content_digest;
+/// use parquet_consolidator::test_utils::create_test_parquet_file;
+/// use tempfile::TempDir;
+///
+/// let temp_dir = TempDir::new().unwrap();
+/// let (whole, first, second) = (temp_dir.path().join("whole.parquet"), temp_dir.path().join("a.parquet"), temp_dir.path().join("b.parquet"));
+/// create_test_parquet_file(&whole, 0, 20).unwrap();
+/// create_test_parquet_file(&first, 10, 20).unwrap();
+/// create_test_parquet_file(&second, 0, 10).unwrap();
+///
+/// assert_eq!(content_digest(&[whole]).unwrap(), content_digest(&[first, second]).unwrap());
+/// ```
+pub fn content_digest(paths: &[PathBuf]) -> Result<String> {
+    let mut sum: u128 = 0;
+    for path in paths {
+        let file = File::open(path).context(format!("Failed to open {:?}", path))?;
+        let reader = ParquetRecordBatchReaderBuilder::try_new(file)
+            .context(format!("Failed to read parquet footer of {:?}", path))?
+            .build()?;
+        let fields = reader.schema().fields().iter().map(|field| SortField::new(field.data_type().clone())).collect();
+        let converter = RowConverter::new(fields).context(format!("Can't digest the columns of {:?}", path))?;
+        for batch in reader {
+            let batch = batch.context(format!("Failed to read {:?}", path))?;
+            let rows = converter.convert_columns(batch.columns())?;
+            for row in rows.iter() {
+                sum = sum.wrapping_add(xxh3_128(row.as_ref()));
+            }
+        }
+    }
+    Ok(format!("{:032x}", sum))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use polars::prelude::*;
+    use crate::consolidator::{consolidate_with_options, ConsolidateOptions};
+    use crate::writer::{parse_codec, WriterOptions};
+
+    #[test]
+    fn test_content_digest() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let inputs = [temp_dir.path().join("a.parquet"), temp_dir.path().join("b.parquet")];
+        let mut first = df!("id" => &[1i64, 2, 2], "name" => &[None, Some("b"), Some("b")])?;
+        let mut second = df!("id" => &[3i64], "name" => &[Some("c")])?;
+        ParquetWriter::new(File::create(&inputs[0])?).finish(&mut first)?;
+        ParquetWriter::new(File::create(&inputs[1])?).finish(&mut second)?;
+
+        let forward = temp_dir.path().join("forward.parquet");
+        let options = ConsolidateOptions { content_digest: true, ..Default::default() };
+        let summary = consolidate_with_options(&inputs, &forward, &options)?;
+        let digest = summary.content_digest.clone().expect("a digest was asked for");
+        assert!(summary.to_string().contains(&digest));
+
+        // Another order, row group size and codec give the same rows
+        let backward = temp_dir.path().join("backward.parquet");
+        let writer = WriterOptions { max_row_group_rows: Some(1), compression: parse_codec("zstd")?, ..Default::default() };
+        let options = ConsolidateOptions { writer, ..options };
+        let reversed = [inputs[1].clone(), inputs[0].clone()];
+        assert_eq!(consolidate_with_options(&reversed, &backward, &options)?.content_digest, Some(digest.clone()));
+
+        // Dropping the repeated row changes it
+        let mut deduped = df!("id" => &[1i64, 2, 3], "name" => &[None, Some("b"), Some("c")])?;
+        let other = temp_dir.path().join("other.parquet");
+        ParquetWriter::new(File::create(&other)?).finish(&mut deduped)?;
+        assert_ne!(content_digest(&[other])?, digest);
+
+        Ok(())
+    }
+}
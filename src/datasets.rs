@@ -0,0 +1,422 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use anyhow::{Result, Context};
+use serde::{Deserialize, Serialize};
+use crate::consolidator::{ConsolidateOptions, RunSummary};
+use crate::discovery::{discover_input_files, file_name_regex, DiscoveryOptions, OutputInInputPolicy};
+use crate::formats::InputFormat;
+use crate::jobs::{mirrored_jobs, run_jobs, subdirectory_jobs, ConsolidationJob};
+use crate::naming::DefaultNamer;
+use crate::versions::{record_version, version_log_path};
+use crate::warnings::WarningLog;
+
+/// Dataset name selecting every dataset of a config
+pub const ALL_DATASETS: &str = "all";
+
+/// How the input of a dataset is split into outputs
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Partitioning {
+    /// Every input file into the single output file
+    #[default]
+    None,
+    /// One output file per immediate subdirectory of the input, as with
+    /// `--per-directory`
+    PerDirectory,
+    /// One output per directory of the input tree, keeping its layout, as
+    /// with `--mirror-structure`
+    Mirror,
+}
+
+/// One named dataset of a [`DatasetConfig`]
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DatasetDefinition {
+    pub input: PathBuf,
+    /// Output file, or output directory when partitioned
+    pub output: PathBuf,
+    #[serde(default)]
+    pub partitioning: Partitioning,
+    #[serde(default)]
+    pub recursive: bool,
+    /// Input formats to include (parquet, csv, ndjson); parquet only by default
+    #[serde(default)]
+    pub formats: Vec<String>,
+    #[serde(default)]
+    pub exclude_dirs: Vec<String>,
+    pub name_regex: Option<String>,
+    /// Skip files modified within this many seconds
+    pub min_file_age_secs: Option<u64>,
+    /// Minimum time between two runs of the dataset, e.g. `15m`, `6h` or
+    /// `1d`; see [`parse_interval`]. Runs whenever asked when unset
+    pub schedule: Option<String>,
+    /// Stamp each successful run with the next dataset version; see
+    /// [`record_version`]
+    #[serde(default)]
+    pub versioned: bool,
+    /// What to do when the output lies inside the input directory
+    /// (`error` or `exclude`)
+    #[serde(default)]
+    pub output_in_input: OutputInInputPolicy,
+}
+
+impl DatasetDefinition {
+    /// Which input files of the dataset are consolidated
+    pub fn discovery(&self) -> Result<DiscoveryOptions> {
+        let formats = if self.formats.is_empty() {
+            vec![InputFormat::Parquet]
+        } else {
+            self.formats.iter().map(|format| format.parse()).collect::<Result<_>>()?
+        };
+        Ok(DiscoveryOptions {
+            recursive: self.recursive,
+            formats,
+            exclude_dirs: self.exclude_dirs.clone(),
+            name_regex: self.name_regex.as_deref().map(file_name_regex).transpose()?,
+            min_file_age: self.min_file_age_secs.map(Duration::from_secs),
+            ..Default::default()
+        })
+    }
+
+    /// Minimum time between two runs, when the dataset has a schedule
+    pub fn interval(&self) -> Result<Option<Duration>> {
+        self.schedule.as_deref().map(parse_interval).transpose()
+    }
+
+    /// The jobs consolidating the dataset's current input files; none when
+    /// there are no input files
+    pub fn jobs(&self, name: &str) -> Result<Vec<ConsolidationJob>> {
+        self.jobs_logged(name, WarningLog::new(true))
+    }
+
+    /// [`jobs`](Self::jobs), raising discovery's warnings on a run's log
+    fn jobs_logged(&self, name: &str, warnings: WarningLog) -> Result<Vec<ConsolidationJob>> {
+        let mut discovery = DiscoveryOptions { warnings, ..self.discovery()? };
+        discovery.check_output_location(&self.input, &self.output, self.output_in_input)?;
+        match self.partitioning {
+            Partitioning::PerDirectory => subdirectory_jobs(&self.input, &self.output, &discovery, &DefaultNamer),
+            Partitioning::Mirror => mirrored_jobs(&self.input, &self.output, &discovery, &DefaultNamer),
+            Partitioning::None => {
+                let input_files = discover_input_files(&self.input, &discovery)?;
+                if input_files.is_empty() {
+                    return Ok(Vec::new());
+                }
+                Ok(vec![ConsolidationJob { name: name.to_string(), input_files, output_path: self.output.clone() }])
+            }
+        }
+    }
+}
+
+/// Named datasets maintained together, each with its own input, output,
+/// partitioning and schedule
+///
+/// Relative paths in a config file are resolved against the directory of
+/// the file, so a config can be deployed next to the data it describes.
+///
+/// # Examples
+///
+/// ```
+/// use parquet_consolidator::{DatasetConfig, Partitioning};
+///
+/// let config = DatasetConfig::from_toml_str(r#"
+///     [datasets.orders]
+///     input = "landing/orders"
+///     output = "warehouse/orders"
+///     partitioning = "mirror"
+///     schedule = "1h"
+///
+///     [datasets.customers]
+///     input = "landing/customers"
+///     output = "warehouse/customers.parquet"
+/// "#).unwrap();
+/// assert_eq!(config.datasets["orders"].partitioning, Partitioning::Mirror);
+/// assert_eq!(config.select("all").unwrap().len(), 2);
+/// assert!(config.select("invoices").is_err());
+/// ```
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DatasetConfig {
+    /// File remembering when each dataset last ran, for schedules;
+    /// `<config stem>.state.json` next to the config by default
+    pub state_file: Option<PathBuf>,
+    pub datasets: BTreeMap<String, DatasetDefinition>,
+}
+
+impl DatasetConfig {
+    pub fn from_toml_str(s: &str) -> Result<Self> {
+        let config: DatasetConfig = toml::from_str(s).context("Invalid dataset config")?;
+        if config.datasets.is_empty() {
+            anyhow::bail!("Dataset config defines no datasets");
+        }
+        if config.datasets.contains_key(ALL_DATASETS) {
+            anyhow::bail!("Dataset name {:?} is reserved for selecting every dataset", ALL_DATASETS);
+        }
+        for (name, dataset) in &config.datasets {
+            dataset.interval().context(format!("Invalid schedule for dataset {}", name))?;
+            dataset.discovery().context(format!("Invalid discovery settings for dataset {}", name))?;
+        }
+        Ok(config)
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path).context(format!("Failed to read dataset config {:?}", path))?;
+        let mut config = Self::from_toml_str(&contents)?;
+        let base = path.parent().unwrap_or(Path::new(""));
+        for dataset in config.datasets.values_mut() {
+            dataset.input = base.join(&dataset.input);
+            dataset.output = base.join(&dataset.output);
+        }
+        config.state_file = Some(match &config.state_file {
+            Some(state_file) => base.join(state_file),
+            None => {
+                let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+                base.join(format!("{}.state.json", stem))
+            }
+        });
+        Ok(config)
+    }
+
+    /// The datasets named by `selection`, either one name or
+    /// [`ALL_DATASETS`], in name order
+    pub fn select(&self, selection: &str) -> Result<Vec<(&str, &DatasetDefinition)>> {
+        if selection == ALL_DATASETS {
+            return Ok(self.datasets.iter().map(|(name, dataset)| (name.as_str(), dataset)).collect());
+        }
+        match self.datasets.get_key_value(selection) {
+            Some((name, dataset)) => Ok(vec![(name.as_str(), dataset)]),
+            None => anyhow::bail!(
+                "Unknown dataset {:?} (defined: {})",
+                selection,
+                self.datasets.keys().cloned().collect::<Vec<_>>().join(", ")
+            ),
+        }
+    }
+}
+
+/// Parse an interval such as `90s`, `15m`, `6h` or `1d`; a bare number is
+/// seconds
+pub fn parse_interval(s: &str) -> Result<Duration> {
+    let s = s.trim();
+    let (number, unit_secs) = match s.char_indices().last() {
+        Some((i, 's')) => (&s[..i], 1),
+        Some((i, 'm')) => (&s[..i], 60),
+        Some((i, 'h')) => (&s[..i], 60 * 60),
+        Some((i, 'd')) => (&s[..i], 24 * 60 * 60),
+        _ => (s, 1),
+    };
+    let count: u64 = number
+        .trim()
+        .parse()
+        .map_err(|_| anyhow::anyhow!("Invalid interval {:?} (expected e.g. 90s, 15m, 6h or 1d)", s))?;
+    Ok(Duration::from_secs(count * unit_secs))
+}
+
+/// When each dataset last ran successfully, as seconds since the Unix epoch
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DatasetState {
+    pub last_runs: BTreeMap<String, u64>,
+}
+
+impl DatasetState {
+    /// Read a state file; a missing file is an empty state
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(DatasetState::default());
+        }
+        let json = fs::read_to_string(path).context(format!("Failed to read dataset state {:?}", path))?;
+        serde_json::from_str(&json).context(format!("Failed to parse dataset state {:?}", path))
+    }
+
+    /// Write the state under a temporary name and rename it into place, so
+    /// an interrupted write keeps the previous state
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let staging = path.with_extension("json.tmp");
+        fs::write(&staging, serde_json::to_string_pretty(self)?).context(format!("Failed to write {:?}", staging))?;
+        fs::rename(&staging, path).context(format!("Failed to update dataset state {:?}", path))
+    }
+
+    /// How long until the dataset is due again; `None` when it is due now
+    pub fn due_in(&self, name: &str, interval: Option<Duration>, now: SystemTime) -> Option<Duration> {
+        let interval = interval?;
+        let last_run = UNIX_EPOCH + Duration::from_secs(*self.last_runs.get(name)?);
+        let elapsed = now.duration_since(last_run).unwrap_or_default();
+        interval.checked_sub(elapsed).filter(|remaining| !remaining.is_zero())
+    }
+
+    fn record(&mut self, name: &str, now: SystemTime) {
+        let secs = now.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        self.last_runs.insert(name.to_string(), secs);
+    }
+}
+
+/// What running one dataset did
+#[derive(Debug)]
+pub enum DatasetOutcome {
+    /// Skipped, as it ran less than its schedule ago; due again after this long
+    NotDue(Duration),
+    /// Every output was written; empty when there were no input files
+    Completed(Vec<RunSummary>),
+    /// Planning the dataset failed, or some of its outputs did
+    Failed(Vec<anyhow::Error>),
+}
+
+impl DatasetOutcome {
+    pub fn is_failed(&self) -> bool {
+        matches!(self, DatasetOutcome::Failed(_))
+    }
+}
+
+impl fmt::Display for DatasetOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DatasetOutcome::NotDue(due_in) => write!(f, "not due for another {}s", due_in.as_secs()),
+            DatasetOutcome::Completed(summaries) if summaries.is_empty() => write!(f, "no input files"),
+            DatasetOutcome::Completed(summaries) => write!(
+                f,
+                "wrote {} outputs with {} rows",
+                summaries.len(),
+                summaries.iter().map(|summary| summary.rows_written).sum::<usize>()
+            ),
+            DatasetOutcome::Failed(errors) => write!(f, "failed with {} errors", errors.len()),
+        }
+    }
+}
+
+/// Run the datasets named by `selection`, one after the other, skipping
+/// those not yet due by their schedule unless `force` is set
+///
+/// The outputs of each dataset run up to `parallelism` at once. A dataset
+/// failing doesn't stop the others. The state file records the datasets
+/// that completed, after each of them, so an interrupted run only repeats
+/// the unfinished ones.
+pub fn run_datasets(
+    config: &DatasetConfig,
+    selection: &str,
+    options: &ConsolidateOptions,
+    parallelism: usize,
+    force: bool,
+    now: SystemTime,
+) -> Result<Vec<(String, DatasetOutcome)>> {
+    let selected = config.select(selection)?;
+    let mut state = match &config.state_file {
+        Some(path) => DatasetState::load(path)?,
+        None => DatasetState::default(),
+    };
+
+    let mut outcomes = Vec::new();
+    for (name, dataset) in selected {
+        let due_in = if force { None } else { state.due_in(name, dataset.interval()?, now) };
+        let outcome = match due_in {
+            Some(due_in) => DatasetOutcome::NotDue(due_in),
+            None => run_dataset(name, dataset, options, parallelism),
+        };
+        if let DatasetOutcome::Completed(_) = outcome {
+            state.record(name, now);
+            if let Some(path) = &config.state_file {
+                state.save(path)?;
+            }
+        }
+        outcomes.push((name.to_string(), outcome));
+    }
+    Ok(outcomes)
+}
+
+fn run_dataset(name: &str, dataset: &DatasetDefinition, options: &ConsolidateOptions, parallelism: usize) -> DatasetOutcome {
+    let discovery_warnings = WarningLog::new(options.print_warnings);
+    let jobs = match dataset.jobs_logged(name, discovery_warnings.clone()) {
+        Ok(jobs) => jobs,
+        Err(err) => return DatasetOutcome::Failed(vec![err.context(format!("Failed to plan dataset {}", name))]),
+    };
+    for job in &jobs {
+        if let Some(parent) = job.output_path.parent() {
+            if let Err(err) = fs::create_dir_all(parent) {
+                return DatasetOutcome::Failed(vec![anyhow::Error::new(err).context(format!("Failed to create {:?}", parent))]);
+            }
+        }
+    }
+    let (summaries, errors): (Vec<_>, Vec<_>) = run_jobs(&jobs, options, parallelism).into_iter().partition(Result::is_ok);
+    if !errors.is_empty() {
+        return DatasetOutcome::Failed(errors.into_iter().map(Result::unwrap_err).collect());
+    }
+    let mut summaries: Vec<RunSummary> = summaries.into_iter().map(Result::unwrap).collect();
+    // Discovery's warnings go with the dataset's first output
+    if let Some(summary) = summaries.first_mut() {
+        summary.warnings.splice(0..0, discovery_warnings.take());
+    }
+    if dataset.versioned && !summaries.is_empty() {
+        let log = version_log_path(&dataset.output, dataset.partitioning != Partitioning::None);
+        let inputs: Vec<PathBuf> = jobs.into_iter().flat_map(|job| job.input_files).collect();
+        let outputs: Vec<PathBuf> = summaries.iter().filter_map(|summary| summary.output_path.clone()).collect();
+        let rows = summaries.iter().map(|summary| summary.rows_written as u64).sum();
+        if let Err(err) = record_version(&log, &inputs, &outputs, rows) {
+            return DatasetOutcome::Failed(vec![err]);
+        }
+    }
+    DatasetOutcome::Completed(summaries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::create_test_parquet_file;
+
+    #[test]
+    fn test_run_datasets() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let root = temp_dir.path();
+        for dir in ["landing/orders/day=1", "landing/orders/day=2", "landing/customers"] {
+            fs::create_dir_all(root.join(dir))?;
+        }
+        create_test_parquet_file(&root.join("landing/orders/day=1/a.parquet"), 0, 10)?;
+        create_test_parquet_file(&root.join("landing/orders/day=2/b.parquet"), 10, 30)?;
+        create_test_parquet_file(&root.join("landing/customers/c.parquet"), 0, 5)?;
+        let config_path = root.join("datasets.toml");
+        fs::write(
+            &config_path,
+            r#"
+                [datasets.orders]
+                input = "landing/orders"
+                output = "warehouse/orders"
+                partitioning = "mirror"
+                recursive = true
+                schedule = "1h"
+                versioned = true
+
+                [datasets.customers]
+                input = "landing/customers"
+                output = "warehouse/customers.parquet"
+            "#,
+        )?;
+        let config = DatasetConfig::from_file(&config_path)?;
+        assert_eq!(config.state_file.as_deref(), Some(root.join("datasets.state.json").as_path()));
+
+        let options = ConsolidateOptions::default();
+        let now = SystemTime::now();
+        let outcomes = run_datasets(&config, ALL_DATASETS, &options, 2, false, now)?;
+        let names: Vec<&str> = outcomes.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["customers", "orders"]);
+        assert!(matches!(&outcomes[1].1, DatasetOutcome::Completed(summaries) if summaries.len() == 2));
+        assert!(root.join("warehouse/orders/day=2/part-00000.parquet").exists());
+        assert!(root.join("warehouse/customers.parquet").exists());
+
+        // Within its schedule the scheduled dataset is skipped, unless forced
+        let later = now + Duration::from_secs(600);
+        let outcomes = run_datasets(&config, "orders", &options, 1, false, later)?;
+        assert!(matches!(outcomes[0].1, DatasetOutcome::NotDue(due_in) if (2999..=3000).contains(&due_in.as_secs())));
+        let outcomes = run_datasets(&config, "orders", &options, 1, true, later)?;
+        assert!(matches!(outcomes[0].1, DatasetOutcome::Completed(_)));
+        let versions = crate::versions::read_versions(&root.join("warehouse/orders/_versions.jsonl"))?;
+        assert_eq!(versions.iter().map(|version| version.version).collect::<Vec<_>>(), [1, 2]);
+        assert!(matches!(run_datasets(&config, "customers", &options, 1, false, later)?[0].1, DatasetOutcome::Completed(_)));
+
+        assert_eq!(parse_interval("15m")?, Duration::from_secs(900));
+        assert_eq!(parse_interval("42")?, Duration::from_secs(42));
+        assert!(parse_interval("soon").is_err());
+        assert!(DatasetConfig::from_toml_str("[datasets.all]\ninput = \"a\"\noutput = \"b\"\n").is_err());
+
+        Ok(())
+    }
+}
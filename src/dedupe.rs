@@ -0,0 +1,252 @@
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use anyhow::{Result, Context};
+use polars::prelude::*;
+use roaring::RoaringTreemap;
+use xxhash_rust::xxh3::xxh3_64;
+
+/// Drop rows whose key repeats that of an earlier row, keeping the first
+pub fn drop_duplicate_keys(df: &DataFrame, keys: &[String]) -> Result<DataFrame> {
+    Ok(df.unique_stable(Some(keys), UniqueKeepStrategy::First, None)?)
+}
+
+/// Stable 64-bit hash of each row's key
+///
+/// Key values are hashed through their string representation, so the
+/// hashes do not change between runs, builds, or input formats that
+/// produce the same values.
+pub fn key_hashes(df: &DataFrame, keys: &[String]) -> Result<Vec<u64>> {
+    let columns = keys
+        .iter()
+        .map(|key| {
+            let column = df.column(key).context(format!("Key column not found: {}", key))?;
+            Ok(column.cast(&DataType::Utf8)?)
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let columns = columns.iter().map(|c| Ok(c.utf8()?)).collect::<Result<Vec<_>>>()?;
+
+    let mut buffer = Vec::new();
+    let hashes = (0..df.height())
+        .map(|row| {
+            buffer.clear();
+            for column in &columns {
+                // Tag each value so that nulls, empty strings and values
+                // containing the separator cannot collide
+                match column.get(row) {
+                    Some(value) => {
+                        buffer.push(1);
+                        buffer.extend_from_slice(&(value.len() as u64).to_le_bytes());
+                        buffer.extend_from_slice(value.as_bytes());
+                    }
+                    None => buffer.push(0),
+                }
+            }
+            xxh3_64(&buffer)
+        })
+        .collect();
+
+    Ok(hashes)
+}
+
+/// Hashes of the keys emitted by previous consolidation runs
+///
+/// Persisted as a serialized roaring bitmap of 64-bit key hashes, which
+/// stays compact for millions of keys while remaining exact up to hash
+/// collisions.
+#[derive(Debug, Clone)]
+pub struct SeenKeys {
+    path: PathBuf,
+    seen: RoaringTreemap,
+}
+
+impl SeenKeys {
+    /// Load the state file, starting empty if it does not exist yet
+    pub fn load(path: &Path) -> Result<Self> {
+        let seen = if path.exists() {
+            let file = File::open(path).context(format!("Failed to open key state {:?}", path))?;
+            RoaringTreemap::deserialize_from(BufReader::new(file))
+                .context(format!("Failed to read key state {:?}", path))?
+        } else {
+            RoaringTreemap::new()
+        };
+        Ok(SeenKeys { path: path.to_path_buf(), seen })
+    }
+
+    pub fn len(&self) -> u64 {
+        self.seen.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.seen.is_empty()
+    }
+
+    /// Keep only rows whose key has not been emitted before
+    pub fn filter_unseen(&self, df: &DataFrame, keys: &[String]) -> Result<DataFrame> {
+        if self.seen.is_empty() {
+            return Ok(df.clone());
+        }
+        let unseen: BooleanChunked = key_hashes(df, keys)?
+            .into_iter()
+            .map(|hash| !self.seen.contains(hash))
+            .collect();
+        Ok(df.filter(&unseen)?)
+    }
+
+    /// Remember the keys of rows that were emitted
+    pub fn record(&mut self, df: &DataFrame, keys: &[String]) -> Result<()> {
+        self.seen.extend(key_hashes(df, keys)?);
+        Ok(())
+    }
+
+    /// Write the state back, replacing the file atomically
+    pub fn save(&self) -> Result<()> {
+        let temp_path = self.path.with_extension("tmp");
+        let mut writer = BufWriter::new(File::create(&temp_path)?);
+        self.seen.serialize_into(&mut writer)?;
+        writer.into_inner()?.sync_all()?;
+        fs::rename(&temp_path, &self.path).context(format!("Failed to save key state {:?}", self.path))?;
+        Ok(())
+    }
+}
+
+/// Keys shared by more than one row, found without removing any rows
+#[derive(Debug, Clone, PartialEq)]
+pub struct DuplicateKeyReport {
+    pub keys: Vec<String>,
+    /// Number of distinct keys that occur more than once
+    pub duplicate_keys: usize,
+    /// Number of rows carrying one of those keys
+    pub duplicate_rows: usize,
+    /// Most repeated keys, rendered as comma-separated values, with their
+    /// row counts
+    pub samples: Vec<(String, usize)>,
+}
+
+/// Count rows sharing a key, keeping up to `sample_size` examples
+pub fn duplicate_key_report(df: &DataFrame, keys: &[String], sample_size: usize) -> Result<DuplicateKeyReport> {
+    let key_exprs: Vec<Expr> = keys.iter().map(|key| col(key)).collect();
+    let duplicates = df
+        .clone()
+        .lazy()
+        .groupby(key_exprs)
+        .agg([count().alias("_rows")])
+        .filter(col("_rows").gt(lit(1)))
+        .sort("_rows", SortOptions { descending: true, ..Default::default() })
+        .collect()
+        .context("Failed to count duplicate keys")?;
+
+    let rows = duplicates.column("_rows")?.cast(&DataType::UInt64)?;
+    let rows = rows.u64()?;
+    let duplicate_rows = rows.into_no_null_iter().sum::<u64>() as usize;
+
+    let key_columns = keys
+        .iter()
+        .map(|key| duplicates.column(key)?.cast(&DataType::Utf8))
+        .collect::<PolarsResult<Vec<_>>>()?;
+    let samples = (0..duplicates.height().min(sample_size))
+        .map(|row| {
+            let values = key_columns
+                .iter()
+                .map(|column| Ok(column.utf8()?.get(row).unwrap_or("null").to_string()))
+                .collect::<Result<Vec<_>>>()?;
+            Ok((values.join(", "), rows.get(row).unwrap_or(0) as usize))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(DuplicateKeyReport {
+        keys: keys.to_vec(),
+        duplicate_keys: duplicates.height(),
+        duplicate_rows,
+        samples,
+    })
+}
+
+impl fmt::Display for DuplicateKeyReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Duplicate keys ({}): {} keys shared by {} rows",
+            self.keys.join(", "),
+            self.duplicate_keys,
+            self.duplicate_rows
+        )?;
+        for (key, rows) in &self.samples {
+            writeln!(f, "  {} ({} rows)", key, rows)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_hashes() -> Result<()> {
+        let df = df!(
+            "a" => &[Some("x"), Some("x"), None, Some("")],
+            "b" => &[1i32, 1, 1, 1]
+        )?;
+        let keys = vec!["a".to_string(), "b".to_string()];
+        let hashes = key_hashes(&df, &keys)?;
+        assert_eq!(hashes[0], hashes[1]);
+        assert_ne!(hashes[0], hashes[2]);
+        assert_ne!(hashes[2], hashes[3]);
+
+        assert!(key_hashes(&df, &["missing".to_string()]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_seen_keys_round_trip() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let state_path = temp_dir.path().join("seen.keys");
+        let keys = vec!["id".to_string()];
+
+        let first = df!("id" => &[1i64, 2, 3])?;
+        let mut state = SeenKeys::load(&state_path)?;
+        assert!(state.is_empty());
+        state.record(&first, &keys)?;
+        state.save()?;
+
+        let second = df!("id" => &[3i64, 4])?;
+        let state = SeenKeys::load(&state_path)?;
+        assert_eq!(state.len(), 3);
+        let unseen = state.filter_unseen(&second, &keys)?;
+        assert_eq!(unseen.column("id")?.i64()?.get(0), Some(4));
+        assert_eq!(unseen.height(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_drop_duplicate_keys() -> Result<()> {
+        let df = df!("id" => &[1i32, 2, 1], "v" => &["a", "b", "c"])?;
+        let deduped = drop_duplicate_keys(&df, &["id".to_string()])?;
+        let values: Vec<Option<&str>> = deduped.column("v")?.utf8()?.into_iter().collect();
+        assert_eq!(values, vec![Some("a"), Some("b")]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_duplicate_key_report() -> Result<()> {
+        let df = df!(
+            "id" => &[1i32, 2, 1, 3, 1, 2],
+            "day" => &["a", "a", "a", "a", "a", "b"]
+        )?;
+
+        let report = duplicate_key_report(&df, &["id".to_string()], 1)?;
+        assert_eq!(report.duplicate_keys, 2);
+        assert_eq!(report.duplicate_rows, 5);
+        assert_eq!(report.samples, vec![("1".to_string(), 3)]);
+
+        let report = duplicate_key_report(&df, &["id".to_string(), "day".to_string()], 5)?;
+        assert_eq!(report.duplicate_keys, 1);
+        assert_eq!(report.samples, vec![("1, a".to_string(), 3)]);
+        assert!(report.to_string().contains("1 keys shared by 3 rows"));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,1106 @@
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsString;
+use std::fmt;
+use std::fs::{self, File, TryLockError};
+use std::io::BufRead;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{Duration, SystemTime};
+use anyhow::{Result, Context};
+use parquet::file::reader::{FileReader, SerializedFileReader};
+use regex::Regex;
+use serde::Deserialize;
+use walkdir::{DirEntry, WalkDir};
+use crate::archive::{archive_members, ArchiveFormat};
+use crate::file_dates::FileDateFilter;
+use crate::file_info::{modified_time, FileInfo};
+use crate::formats::InputFormat;
+use crate::warnings::{WarningKind, WarningLog};
+
+/// Order in which discovered files are consolidated
+///
+/// Ties are broken by path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputOrder {
+    /// Least recently modified first
+    OldestFirst,
+    /// Most recently modified first
+    NewestFirst,
+    LargestFirst,
+    SmallestFirst,
+}
+
+impl InputOrder {
+    /// Sort files into this order
+    pub fn sort(&self, files: &mut [PathBuf]) {
+        let mut infos: Vec<FileInfo> = files.iter().cloned().map(FileInfo::stat).collect();
+        self.sort_file_infos(&mut infos);
+        for (file, info) in files.iter_mut().zip(infos) {
+            *file = info.path;
+        }
+    }
+
+    /// Sort discovered files into this order, using the metadata they
+    /// were discovered with
+    pub fn sort_file_infos(&self, files: &mut [FileInfo]) {
+        match self {
+            InputOrder::OldestFirst => files.sort_by_cached_key(|file| (file.modified, file.path.clone())),
+            InputOrder::NewestFirst => files.sort_by_cached_key(|file| (Reverse(file.modified), file.path.clone())),
+            InputOrder::LargestFirst => files.sort_by_cached_key(|file| (Reverse(file.size), file.path.clone())),
+            InputOrder::SmallestFirst => files.sort_by_cached_key(|file| (file.size, file.path.clone())),
+        }
+    }
+}
+
+impl fmt::Display for InputOrder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            InputOrder::OldestFirst => "oldest-first",
+            InputOrder::NewestFirst => "newest-first",
+            InputOrder::LargestFirst => "largest-first",
+            InputOrder::SmallestFirst => "smallest-first",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for InputOrder {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "oldest-first" => Ok(InputOrder::OldestFirst),
+            "newest-first" => Ok(InputOrder::NewestFirst),
+            "largest-first" => Ok(InputOrder::LargestFirst),
+            "smallest-first" => Ok(InputOrder::SmallestFirst),
+            other => anyhow::bail!(
+                "Unknown input order: {} (expected oldest-first, newest-first, largest-first or smallest-first)",
+                other
+            ),
+        }
+    }
+}
+
+/// What discovery does with a path it can't read, such as a directory
+/// without read permission
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UnreadablePathPolicy {
+    /// Skip it, printing the path and the error as a warning
+    #[default]
+    Warn,
+    /// Fail discovery with the path and the error
+    Fail,
+}
+
+impl fmt::Display for UnreadablePathPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            UnreadablePathPolicy::Warn => "warn",
+            UnreadablePathPolicy::Fail => "fail",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for UnreadablePathPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "warn" => Ok(UnreadablePathPolicy::Warn),
+            "fail" => Ok(UnreadablePathPolicy::Fail),
+            other => anyhow::bail!("Unknown unreadable path policy: {} (expected warn or fail)", other),
+        }
+    }
+}
+
+/// What a run does when its output lies inside its input directory, where
+/// a later run would read the output back as an input
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OutputInInputPolicy {
+    /// Refuse to run
+    #[default]
+    Error,
+    /// Run, leaving the output out of discovery
+    Exclude,
+}
+
+impl fmt::Display for OutputInInputPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            OutputInInputPolicy::Error => "error",
+            OutputInInputPolicy::Exclude => "exclude",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for OutputInInputPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "error" => Ok(OutputInInputPolicy::Error),
+            "exclude" => Ok(OutputInInputPolicy::Exclude),
+            other => anyhow::bail!("Unknown output in input policy: {} (expected error or exclude)", other),
+        }
+    }
+}
+
+/// `path` made absolute, with symlinks and `..` resolved as far as it
+/// exists, so two spellings of a path compare equal
+fn resolve_path(path: &Path) -> PathBuf {
+    let absolute = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    let mut existing = absolute.as_path();
+    let mut missing = Vec::new();
+    loop {
+        if let Ok(resolved) = existing.canonicalize() {
+            return missing.iter().rev().fold(resolved, |resolved, name| resolved.join(name));
+        }
+        match (existing.parent(), existing.file_name()) {
+            (Some(parent), Some(name)) => {
+                missing.push(name.to_owned());
+                existing = parent;
+            }
+            _ => return absolute,
+        }
+    }
+}
+
+/// Where `path` lies relative to the directory `dir`, when it lies inside
+/// it; `path` need not exist yet
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// use parquet_consolidator::path_within;
+/// use tempfile::TempDir;
+///
+/// let temp_dir = TempDir::new().unwrap();
+/// let input = temp_dir.path().join("input");
+/// std::fs::create_dir_all(&input).unwrap();
+/// let output = temp_dir.path().join("input/../input/merged/out.parquet");
+/// assert_eq!(path_within(&output, &input).unwrap(), Path::new("merged/out.parquet"));
+/// assert_eq!(path_within(&temp_dir.path().join("out.parquet"), &input), None);
+/// ```
+pub fn path_within(path: &Path, dir: &Path) -> Option<PathBuf> {
+    resolve_path(path).strip_prefix(resolve_path(dir)).ok().map(Path::to_path_buf)
+}
+
+/// Which files under an input path are picked up for consolidation
+#[derive(Debug, Clone)]
+pub struct DiscoveryOptions {
+    /// Descend into subdirectories
+    pub recursive: bool,
+    pub formats: Vec<InputFormat>,
+    /// Directory names that are never descended into, wherever they occur
+    pub exclude_dirs: Vec<String>,
+    /// Pattern that file names found in directories must match; see
+    /// [`file_name_regex`]
+    pub name_regex: Option<Regex>,
+    /// Consolidate at most this many files, the first ones in `order`;
+    /// the rest are left for a later run
+    pub max_files: Option<usize>,
+    /// Order files are consolidated in; by path when unset, except that a
+    /// file cap then takes the oldest files
+    pub order: Option<InputOrder>,
+    /// Skip files modified more recently than this, or locked by another
+    /// process, as they may still be being written
+    pub min_file_age: Option<Duration>,
+    /// List the files of a directory from its `_metadata` summary file,
+    /// when it has one, instead of walking it
+    pub use_summary_metadata: bool,
+    /// Treat zip and tar archives, given as the input or found in a
+    /// directory, as holding input files; see [`archive_members`]
+    pub read_archives: bool,
+    /// What to do with paths under the input that can't be read
+    pub unreadable_paths: UnreadablePathPolicy,
+    /// Files and directories that are never read or descended into, such
+    /// as an output written inside the input directory
+    pub exclude_paths: Vec<PathBuf>,
+    /// Read symlinks to files found in a directory as the files they point
+    /// to; otherwise they are skipped. Links to directories are never
+    /// followed, and an input path that is a link is always read
+    pub follow_file_symlinks: bool,
+    /// Keep only files whose names carry a date in a range; applied with
+    /// the name pattern
+    pub file_dates: Option<FileDateFilter>,
+    /// Where unreadable paths left out under [`UnreadablePathPolicy::Warn`]
+    /// are reported; by default they are also printed to stderr
+    pub warnings: WarningLog,
+}
+
+impl Default for DiscoveryOptions {
+    fn default() -> Self {
+        DiscoveryOptions {
+            recursive: false,
+            formats: vec![InputFormat::Parquet],
+            exclude_dirs: Vec::new(),
+            name_regex: None,
+            max_files: None,
+            order: None,
+            min_file_age: None,
+            use_summary_metadata: false,
+            read_archives: false,
+            unreadable_paths: UnreadablePathPolicy::Warn,
+            exclude_paths: Vec::new(),
+            follow_file_symlinks: true,
+            file_dates: None,
+            warnings: WarningLog::new(true),
+        }
+    }
+}
+
+impl DiscoveryOptions {
+    /// The accepted formats as they read in a message, such as
+    /// `csv or ndjson`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use parquet_consolidator::{DiscoveryOptions, InputFormat};
+    ///
+    /// let discovery = DiscoveryOptions { formats: vec![InputFormat::Csv, InputFormat::Ndjson], ..Default::default() };
+    /// assert_eq!(discovery.format_names(), "csv or ndjson");
+    /// assert_eq!(DiscoveryOptions::default().format_names(), "parquet");
+    /// ```
+    pub fn format_names(&self) -> String {
+        let names: Vec<String> = self.formats.iter().map(|format| format.to_string()).collect();
+        match names.split_last() {
+            Some((last, rest)) if !rest.is_empty() => format!("{} or {}", rest.join(", "), last),
+            _ => names.concat(),
+        }
+    }
+
+    fn matches_format(&self, path: &Path) -> bool {
+        InputFormat::from_path(path).is_some_and(|format| self.formats.contains(&format))
+    }
+
+    fn matches_name(&self, path: &Path) -> bool {
+        if self.name_regex.is_none() && self.file_dates.is_none() {
+            return true;
+        }
+        let Some(name) = path.file_name().map(|name| name.to_string_lossy()) else {
+            return false;
+        };
+        self.name_regex.as_ref().is_none_or(|regex| regex.is_match(&name))
+            && self.file_dates.as_ref().is_none_or(|filter| filter.matches(&name))
+    }
+
+    fn is_archive(&self, path: &Path) -> bool {
+        self.read_archives && ArchiveFormat::from_path(path).is_some()
+    }
+
+    /// The members of an archive with an accepted format and name
+    fn archive_input_files(&self, archive: &Path) -> Box<dyn Iterator<Item = Result<PathBuf>> + '_> {
+        match archive_members(archive) {
+            Ok(members) => Box::new(
+                members
+                    .into_iter()
+                    .filter(move |member| self.matches_format(member) && self.matches_name(member))
+                    .map(Ok),
+            ),
+            Err(err) => Box::new(std::iter::once(Err(err))),
+        }
+    }
+
+    /// Whether a walked entry is a file: a regular one, or a link resolving
+    /// to one when file symlinks are followed
+    fn is_walked_file(&self, entry: &DirEntry) -> bool {
+        entry.file_type().is_file() || (self.follow_file_symlinks && entry.path_is_symlink() && entry.path().is_file())
+    }
+
+    /// Whether a directory below the input path is skipped entirely
+    pub fn is_excluded_dir(&self, entry: &DirEntry) -> bool {
+        entry.depth() > 0
+            && entry.file_type().is_dir()
+            && self.exclude_dirs.iter().any(|name| entry.file_name() == name.as_str())
+    }
+
+    /// Apply `policy` to an `output` inside the input directory `input`:
+    /// fail, or add the output to the excluded paths
+    ///
+    /// Nothing is checked for an input file, which is never the output.
+    pub fn check_output_location(&mut self, input: &Path, output: &Path, policy: OutputInInputPolicy) -> Result<()> {
+        if !input.is_dir() || path_within(output, input).is_none() {
+            return Ok(());
+        }
+        match policy {
+            OutputInInputPolicy::Error => anyhow::bail!(
+                "The output {:?} is inside the input directory {:?}, where a later run would read it back as input; \
+                 write it elsewhere, or leave it out of discovery with --output-in-input exclude",
+                output,
+                input
+            ),
+            OutputInInputPolicy::Exclude => {
+                self.exclude_paths.push(output.to_path_buf());
+                Ok(())
+            }
+        }
+    }
+
+    /// The excluded paths inside `input_dir`, spelled as walking it spells
+    /// them
+    fn excluded_paths_in(&self, input_dir: &Path) -> Vec<PathBuf> {
+        self.exclude_paths
+            .iter()
+            .filter_map(|path| path_within(path, input_dir))
+            .map(|relative| input_dir.join(relative))
+            .collect()
+    }
+
+    /// Whether a path relative to the input directory lies in an excluded
+    /// directory, or below one when recursing is off
+    fn is_excluded_relative(&self, relative: &Path) -> bool {
+        let Some(parent) = relative.parent() else {
+            return false;
+        };
+        (!self.recursive && !parent.as_os_str().is_empty())
+            || parent.iter().any(|name| self.exclude_dirs.iter().any(|excluded| name == excluded.as_str()))
+    }
+
+    /// Walk `input_dir`, never entering excluded directories or yielding
+    /// excluded paths
+    ///
+    /// Entries that can't be read are errors under
+    /// [`UnreadablePathPolicy::Fail`]; otherwise they are left out with a
+    /// warning naming the path.
+    pub(crate) fn walk(&self, input_dir: &Path, recursive: bool) -> impl Iterator<Item = Result<DirEntry>> + '_ {
+        let walker = WalkDir::new(input_dir).sort_by_file_name();
+        let walker = if recursive { walker } else { walker.max_depth(1) };
+        let root = input_dir.to_path_buf();
+        let excluded_paths = self.excluded_paths_in(input_dir);
+        walker
+            .into_iter()
+            .filter_entry(move |entry| {
+                !self.is_excluded_dir(entry) && !excluded_paths.iter().any(|excluded| entry.path() == excluded)
+            })
+            .filter_map(move |entry| {
+                let err = match entry {
+                    Ok(entry) => return Some(Ok(entry)),
+                    Err(err) => err,
+                };
+                let path = err.path().unwrap_or(&root).to_path_buf();
+                let reason = err.io_error().map_or_else(|| err.to_string(), ToString::to_string);
+                match self.unreadable_paths {
+                    UnreadablePathPolicy::Fail => Some(Err(anyhow::anyhow!("Failed to read {:?}: {}", path, reason))),
+                    UnreadablePathPolicy::Warn => {
+                        let message = format!("Skipping {:?}, which can't be read: {}", path, reason);
+                        self.warnings.warn(WarningKind::UnreadablePath, message);
+                        None
+                    }
+                }
+            })
+    }
+}
+
+/// Compile a file name pattern that must match the whole name
+///
+/// # Examples
+///
+/// ```
+/// use parquet_consolidator::file_name_regex;
+///
+/// let regex = file_name_regex(r"part-\d+-.*\.parquet").unwrap();
+/// assert!(regex.is_match("part-00001-abc.parquet"));
+/// assert!(!regex.is_match("part-00001-abc.parquet.crc"));
+/// ```
+pub fn file_name_regex(pattern: &str) -> Result<Regex> {
+    Regex::new(&format!("^(?:{})$", pattern)).context(format!("Invalid file name pattern: {}", pattern))
+}
+
+/// Summary file written by Spark and Dask that lists the row groups of
+/// every data file in a dataset
+pub const SUMMARY_METADATA_FILE: &str = "_metadata";
+
+/// Summary file holding only the schema of a dataset
+pub const COMMON_METADATA_FILE: &str = "_common_metadata";
+
+/// The summary file of `input_dir` that carries the dataset schema,
+/// preferring `_common_metadata` as it holds no row groups
+pub fn summary_schema_file(input_dir: &Path) -> Option<PathBuf> {
+    [COMMON_METADATA_FILE, SUMMARY_METADATA_FILE]
+        .iter()
+        .map(|name| input_dir.join(name))
+        .find(|path| path.is_file())
+}
+
+/// Data files referenced by a `_metadata` summary file, in row group order
+///
+/// Paths are resolved against the summary file's directory. Only the
+/// summary's footer is read, so no data file is opened.
+pub fn summary_data_files(summary: &Path) -> Result<Vec<PathBuf>> {
+    let reader = SerializedFileReader::new(File::open(summary).context(format!("Failed to open {:?}", summary))?)
+        .context(format!("Failed to read summary metadata {:?}", summary))?;
+    let directory = summary.parent().unwrap_or(Path::new(""));
+
+    let mut seen = HashSet::new();
+    let mut files = Vec::new();
+    for row_group in reader.metadata().row_groups() {
+        for column in row_group.columns() {
+            let Some(file_path) = column.file_path() else {
+                anyhow::bail!("Summary metadata {:?} has a row group without a file path", summary);
+            };
+            if seen.insert(file_path.to_string()) {
+                files.push(directory.join(file_path));
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// Find the input files under `input_path`
+///
+/// A file path is returned as-is when it has one of the accepted formats
+/// and is an error otherwise; the name pattern only filters files found in
+/// directories. A symlink found alongside the file it points to is left
+/// out.
+///
+/// # Examples
+///
+/// ```
+/// use parquet_consolidator::{discover_input_files, DiscoveryOptions};
+/// use tempfile::TempDir;
+///
+/// let temp_dir = TempDir::new().unwrap();
+/// std::fs::create_dir_all(temp_dir.path().join("_temporary")).unwrap();
+/// std::fs::write(temp_dir.path().join("a.parquet"), "").unwrap();
+/// std::fs::write(temp_dir.path().join("_temporary/b.parquet"), "").unwrap();
+///
+/// let options = DiscoveryOptions {
+///     recursive: true,
+///     exclude_dirs: vec!["_temporary".to_string()],
+///     ..Default::default()
+/// };
+/// let files = discover_input_files(temp_dir.path(), &options).unwrap();
+/// assert_eq!(files, vec![temp_dir.path().join("a.parquet")]);
+/// ```
+pub fn discover_input_files(input_path: &Path, options: &DiscoveryOptions) -> Result<Vec<PathBuf>> {
+    Ok(discover_file_infos(input_path, options)?.into_iter().map(|file| file.path).collect())
+}
+
+/// Find the input files under `input_path` like [`discover_input_files`],
+/// along with their size and modification time
+///
+/// Each file is stat'ed once, and ordering uses that metadata. Footers
+/// aren't read; see [`FileInfo::read_footer`].
+pub fn discover_file_infos(input_path: &Path, options: &DiscoveryOptions) -> Result<Vec<FileInfo>> {
+    let input_files = walk_input_files(input_path, options)
+        .map(|path| path.map(FileInfo::stat))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(select_input_files(dedupe_symlinks(input_files), options))
+}
+
+/// Resolve an explicit list of input paths
+///
+/// Each path is handled like an input path given to
+/// [`discover_input_files`], so directories are searched and files must
+/// have an accepted format. Ordering, the file cap and the stability check
+/// apply to the combined list.
+pub fn listed_input_files(paths: &[PathBuf], options: &DiscoveryOptions) -> Result<Vec<PathBuf>> {
+    let mut input_files = Vec::new();
+    for path in paths {
+        if !path.exists() {
+            anyhow::bail!("Listed input does not exist: {:?}", path);
+        }
+        for input_file in walk_input_files(path, options) {
+            input_files.push(FileInfo::stat(input_file?));
+        }
+    }
+    Ok(select_input_files(dedupe_symlinks(input_files), options).into_iter().map(|file| file.path).collect())
+}
+
+/// Read a list of paths, one per line or, with `null_delimited`, separated
+/// by NUL bytes as written by `find -print0`
+///
+/// Empty entries are ignored. NUL-delimited entries are taken verbatim, so
+/// paths may contain newlines and spaces.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::PathBuf;
+/// use parquet_consolidator::read_file_list;
+///
+/// let paths = read_file_list(&b"a.parquet\0dir/with\nnewline.parquet\0"[..], true).unwrap();
+/// assert_eq!(paths, vec![PathBuf::from("a.parquet"), PathBuf::from("dir/with\nnewline.parquet")]);
+/// ```
+pub fn read_file_list(mut reader: impl BufRead, null_delimited: bool) -> Result<Vec<PathBuf>> {
+    let delimiter = if null_delimited { b'\0' } else { b'\n' };
+    let mut paths = Vec::new();
+    let mut entry = Vec::new();
+    loop {
+        entry.clear();
+        if reader.read_until(delimiter, &mut entry).context("Failed to read the file list")? == 0 {
+            break;
+        }
+        if entry.last() == Some(&delimiter) {
+            entry.pop();
+        }
+        if !null_delimited && entry.last() == Some(&b'\r') {
+            entry.pop();
+        }
+        if !entry.is_empty() {
+            paths.push(PathBuf::from(os_string_from_bytes(std::mem::take(&mut entry))?));
+        }
+    }
+    Ok(paths)
+}
+
+#[cfg(unix)]
+fn os_string_from_bytes(bytes: Vec<u8>) -> Result<OsString> {
+    use std::os::unix::ffi::OsStringExt;
+    Ok(OsString::from_vec(bytes))
+}
+
+#[cfg(not(unix))]
+fn os_string_from_bytes(bytes: Vec<u8>) -> Result<OsString> {
+    Ok(OsString::from(String::from_utf8(bytes).context("Listed path is not valid UTF-8")?))
+}
+
+/// Lazily find the input files under `input_path`, yielding each one as
+/// soon as the directory walk reaches it
+///
+/// Unlike [`discover_input_files`] nothing is buffered, so work can start on
+/// the first files of a huge tree while the rest are still being listed.
+/// Consolidation doesn't use it that way: its schema checks and batching
+/// look at every input before the first is read, so the CLI and
+/// [`consolidate_with_options`](crate::consolidate_with_options) still take
+/// the whole listing.
+/// Files are yielded in walk order; `order` and `max_files` need the whole
+/// listing and are ignored, while the name, exclusion and stability filters
+/// apply. Unreadable directory entries are skipped; a file path without an
+/// accepted format yields an error. A symlink and the file it points to
+/// are both yielded, as telling them apart needs the whole listing.
+///
+/// With `read_archives`, the member files of an archive are yielded in its
+/// place, subject to the format and name filters, while the stability check
+/// applies to the archive itself.
+///
+/// With `use_summary_metadata`, a directory holding a `_metadata` file is
+/// not walked: its files are taken from the summary in row group order, and
+/// a listed file that no longer exists yields an error, as the summary is
+/// then stale.
+///
+/// # Examples
+///
+/// ```
+/// use parquet_consolidator::{walk_input_files, DiscoveryOptions};
+/// use tempfile::TempDir;
+///
+/// let temp_dir = TempDir::new().unwrap();
+/// std::fs::write(temp_dir.path().join("a.parquet"), "").unwrap();
+/// std::fs::write(temp_dir.path().join("b.parquet"), "").unwrap();
+///
+/// let options = DiscoveryOptions::default();
+/// let first = walk_input_files(temp_dir.path(), &options).next().unwrap().unwrap();
+/// assert_eq!(first, temp_dir.path().join("a.parquet"));
+/// ```
+pub fn walk_input_files<'a>(
+    input_path: &'a Path,
+    options: &'a DiscoveryOptions,
+) -> Box<dyn Iterator<Item = Result<PathBuf>> + 'a> {
+    let now = SystemTime::now();
+    let is_settled = move |path: &Path| options.min_file_age.is_none_or(|age| is_stable(path, age, now));
+
+    if input_path.is_file() && options.is_archive(input_path) {
+        if !is_settled(input_path) {
+            return Box::new(std::iter::empty());
+        }
+        options.archive_input_files(input_path)
+    } else if input_path.is_file() {
+        let file = if !options.matches_format(input_path) {
+            let names: Vec<String> = options.formats.iter().map(|f| f.to_string()).collect();
+            Some(Err(anyhow::anyhow!("Input file is not a {} file: {:?}", names.join("/"), input_path)))
+        } else if is_settled(input_path) {
+            Some(Ok(input_path.to_path_buf()))
+        } else {
+            None
+        };
+        Box::new(file.into_iter())
+    } else if input_path.is_dir() && options.use_summary_metadata && input_path.join(SUMMARY_METADATA_FILE).is_file() {
+        let files = match summary_data_files(&input_path.join(SUMMARY_METADATA_FILE)) {
+            Ok(files) => files,
+            Err(err) => return Box::new(std::iter::once(Err(err))),
+        };
+        let excluded_paths = options.excluded_paths_in(input_path);
+        Box::new(
+            files
+                .into_iter()
+                .filter(move |path| {
+                    let relative = path.strip_prefix(input_path).unwrap_or(path);
+                    !options.is_excluded_relative(relative)
+                        && !excluded_paths.iter().any(|excluded| path.starts_with(excluded))
+                        && options.matches_format(path)
+                        && options.matches_name(path)
+                })
+                .filter_map(move |path| {
+                    if !path.is_file() {
+                        Some(Err(anyhow::anyhow!("File listed in the _metadata summary is missing: {:?}", path)))
+                    } else {
+                        is_settled(&path).then_some(Ok(path))
+                    }
+                }),
+        )
+    } else if input_path.is_dir() {
+        Box::new(
+            options
+                .walk(input_path, options.recursive)
+                .filter(|entry| entry.as_ref().map_or(true, |entry| options.is_walked_file(entry)))
+                .flat_map(move |entry| -> Box<dyn Iterator<Item = Result<PathBuf>> + 'a> {
+                    let entry = match entry {
+                        Ok(entry) => entry,
+                        Err(err) => return Box::new(std::iter::once(Err(err))),
+                    };
+                    let path = entry.path();
+                    if options.is_archive(path) {
+                        if is_settled(path) {
+                            return options.archive_input_files(path);
+                        }
+                    } else if options.matches_format(path) && options.matches_name(path) && is_settled(path) {
+                        return Box::new(std::iter::once(Ok(entry.into_path())));
+                    }
+                    Box::new(std::iter::empty())
+                }),
+        )
+    } else {
+        Box::new(std::iter::empty())
+    }
+}
+
+/// Leave out the files that are symlinks to a file listed before or after
+/// them, so no file is read twice
+///
+/// Each file keeps the position of its first listing, under its own path
+/// rather than a link's. Files listed twice under their own path are kept.
+fn dedupe_symlinks(input_files: Vec<FileInfo>) -> Vec<FileInfo> {
+    let links: Vec<bool> = input_files.iter().map(|file| file.path.is_symlink()).collect();
+    if !links.contains(&true) {
+        return input_files;
+    }
+    let mut kept: Vec<FileInfo> = Vec::with_capacity(input_files.len());
+    let mut positions: HashMap<PathBuf, usize> = HashMap::new();
+    for (file, is_link) in input_files.into_iter().zip(links) {
+        let target = fs::canonicalize(&file.path).unwrap_or_else(|_| file.path.clone());
+        match positions.get(&target) {
+            Some(_) if is_link => {}
+            Some(&position) if kept[position].path.is_symlink() => kept[position] = file,
+            Some(_) => kept.push(file),
+            None => {
+                positions.insert(target, kept.len());
+                kept.push(file);
+            }
+        }
+    }
+    kept
+}
+
+/// Apply the ordering and file cap
+fn select_input_files(mut input_files: Vec<FileInfo>, options: &DiscoveryOptions) -> Vec<FileInfo> {
+    let order = options.order.or(options.max_files.map(|_| InputOrder::OldestFirst));
+    if let Some(order) = order {
+        order.sort_file_infos(&mut input_files);
+    }
+    if let Some(max_files) = options.max_files {
+        input_files.truncate(max_files);
+    }
+
+    input_files
+}
+
+/// Whether a file was last modified at least `min_age` before `now` and no
+/// other process holds a lock on it
+///
+/// Files with a modification time in the future count as recently modified.
+fn is_stable(path: &Path, min_age: Duration, now: SystemTime) -> bool {
+    let old_enough = now.duration_since(modified_time(path)).is_ok_and(|age| age >= min_age);
+    old_enough && is_unlocked(path)
+}
+
+fn is_unlocked(path: &Path) -> bool {
+    let Ok(file) = File::open(path) else {
+        return false;
+    };
+    match file.try_lock() {
+        Ok(()) => true,
+        Err(TryLockError::WouldBlock) => false,
+        // Locking is unsupported on some filesystems; fall back to the age check
+        Err(TryLockError::Error(_)) => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use crate::file_info::file_size;
+    use crate::test_utils::{create_summary_metadata_files, create_test_parquet_file};
+
+    #[test]
+    fn test_exclude_dirs() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let root = temp_dir.path();
+        for dir in ["day=1/_temporary/0", "day=1/.spark-staging", "archive", "day=2"] {
+            fs::create_dir_all(root.join(dir))?;
+        }
+        for file in [
+            "day=1/a.parquet",
+            "day=1/_temporary/0/b.parquet",
+            "day=1/.spark-staging/c.parquet",
+            "archive/d.parquet",
+            "day=2/e.parquet",
+        ] {
+            fs::write(root.join(file), "")?;
+        }
+
+        let options = DiscoveryOptions { recursive: true, ..Default::default() };
+        assert_eq!(discover_input_files(root, &options)?.len(), 5);
+
+        let options = DiscoveryOptions {
+            recursive: true,
+            exclude_dirs: vec!["_temporary".to_string(), ".spark-staging".to_string(), "archive".to_string()],
+            ..Default::default()
+        };
+        let files = discover_input_files(root, &options)?;
+        assert_eq!(files, vec![root.join("day=1/a.parquet"), root.join("day=2/e.parquet")]);
+
+        // The input directory itself is never excluded
+        let files = discover_input_files(&root.join("archive"), &DiscoveryOptions {
+            exclude_dirs: vec!["archive".to_string()],
+            ..Default::default()
+        })?;
+        assert_eq!(files.len(), 1);
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_file_symlinks() -> Result<()> {
+        use std::os::unix::fs::symlink;
+        let temp_dir = tempfile::TempDir::new()?;
+        let (input, elsewhere) = (temp_dir.path().join("input"), temp_dir.path().join("elsewhere"));
+        fs::create_dir_all(&input)?;
+        fs::create_dir_all(&elsewhere)?;
+        create_test_parquet_file(&input.join("b.parquet"), 0, 10)?;
+        create_test_parquet_file(&elsewhere.join("shared.parquet"), 10, 20)?;
+        // Sorts before its target
+        symlink(input.join("b.parquet"), input.join("a.parquet"))?;
+        symlink(elsewhere.join("shared.parquet"), input.join("c.parquet"))?;
+        symlink(elsewhere.join("missing.parquet"), input.join("d.parquet"))?;
+
+        let options = DiscoveryOptions::default();
+        assert_eq!(discover_input_files(&input, &options)?, [input.join("b.parquet"), input.join("c.parquet")]);
+        let listed = listed_input_files(&[input.join("c.parquet"), elsewhere.join("shared.parquet")], &options)?;
+        assert_eq!(listed, [elsewhere.join("shared.parquet")]);
+
+        let options = DiscoveryOptions { follow_file_symlinks: false, ..Default::default() };
+        assert_eq!(discover_input_files(&input, &options)?, [input.join("b.parquet")]);
+        assert_eq!(discover_input_files(&input.join("c.parquet"), &options)?, [input.join("c.parquet")]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unreadable_paths() -> Result<()> {
+        // A directory removed between being listed and being read fails
+        // like one without read permission, which root could still read
+        let log = WarningLog::new(false);
+        let walk_with = |policy| -> Result<Vec<Result<DirEntry>>> {
+            let temp_dir = tempfile::TempDir::new()?;
+            fs::create_dir_all(temp_dir.path().join("sub"))?;
+            let options = DiscoveryOptions { unreadable_paths: policy, warnings: log.clone(), ..Default::default() };
+            let mut walk = options.walk(temp_dir.path(), true);
+            let mut entries = vec![walk.next().unwrap()];
+            fs::remove_dir(temp_dir.path().join("sub"))?;
+            entries.extend(walk);
+            Ok(entries)
+        };
+
+        let entries = walk_with(UnreadablePathPolicy::Fail)?;
+        assert_eq!(entries.len(), 3);
+        let err = entries[2].as_ref().unwrap_err().to_string();
+        assert!(err.starts_with("Failed to read") && err.contains("sub\""), "{}", err);
+
+        assert!(log.take().is_empty());
+
+        assert_eq!(walk_with(UnreadablePathPolicy::Warn)?.len(), 2);
+        let warnings = log.take();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].kind, WarningKind::UnreadablePath);
+        assert!(warnings[0].message.starts_with("Skipping") && warnings[0].message.contains("sub\""), "{}", warnings[0]);
+        assert!("ignore".parse::<UnreadablePathPolicy>().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_output_in_input() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let input_dir = temp_dir.path().join("input");
+        fs::create_dir_all(input_dir.join("merged"))?;
+        crate::test_utils::create_test_parquet_file(&input_dir.join("a.parquet"), 0, 10)?;
+        // Left by an earlier run writing into the input directory
+        let output = input_dir.join("merged/out.parquet");
+        crate::test_utils::create_test_parquet_file(&output, 0, 10)?;
+
+        let mut options = DiscoveryOptions { recursive: true, ..Default::default() };
+        let err = options.check_output_location(&input_dir, &output, OutputInInputPolicy::Error).unwrap_err();
+        assert!(err.to_string().contains("is inside the input directory"));
+
+        // Spelled differently from the input, the output is still found
+        let spelled = temp_dir.path().join("input/../input/merged/out.parquet");
+        options.check_output_location(&input_dir, &spelled, OutputInInputPolicy::Exclude)?;
+        assert_eq!(discover_input_files(&input_dir, &options)?, vec![input_dir.join("a.parquet")]);
+
+        // Elsewhere, the output is neither an error nor excluded
+        let mut options = DiscoveryOptions::default();
+        options.check_output_location(&input_dir, &temp_dir.path().join("out.parquet"), OutputInInputPolicy::Error)?;
+        assert!(options.exclude_paths.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_name_regex() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let root = temp_dir.path();
+        for file in ["part-00000-a.parquet", "part-00001-b.parquet", "summary.parquet", "xpart-1-c.parquet"] {
+            fs::write(root.join(file), "")?;
+        }
+
+        let options = DiscoveryOptions {
+            name_regex: Some(file_name_regex(r"part-\d+-.*\.parquet")?),
+            ..Default::default()
+        };
+        let files = discover_input_files(root, &options)?;
+        assert_eq!(files, vec![root.join("part-00000-a.parquet"), root.join("part-00001-b.parquet")]);
+
+        // Explicitly named files are not filtered by name
+        assert_eq!(discover_input_files(&root.join("summary.parquet"), &options)?.len(), 1);
+
+        assert!(file_name_regex("part-(").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_max_files_takes_oldest() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let root = temp_dir.path();
+        let now = SystemTime::now();
+        for (name, age) in [("a.parquet", 10), ("b.parquet", 30), ("c.parquet", 20)] {
+            let file = File::create(root.join(name))?;
+            file.set_modified(now - Duration::from_secs(age))?;
+        }
+
+        let options = DiscoveryOptions { max_files: Some(2), ..Default::default() };
+        let files = discover_input_files(root, &options)?;
+        assert_eq!(files, vec![root.join("b.parquet"), root.join("c.parquet")]);
+
+        let options = DiscoveryOptions { max_files: Some(5), ..Default::default() };
+        assert_eq!(discover_input_files(root, &options)?.len(), 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_input_order() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let root = temp_dir.path();
+        let now = SystemTime::now();
+        for (name, age, size) in [("a.parquet", 10, 300), ("b.parquet", 30, 100), ("c.parquet", 20, 200)] {
+            let file = File::create(root.join(name))?;
+            file.set_len(size)?;
+            file.set_modified(now - Duration::from_secs(age))?;
+        }
+
+        let names = |order: InputOrder, max_files: Option<usize>| -> Result<Vec<String>> {
+            let options = DiscoveryOptions { order: Some(order), max_files, ..Default::default() };
+            Ok(discover_input_files(root, &options)?
+                .iter()
+                .map(|path| path.file_name().unwrap().to_string_lossy().into_owned())
+                .collect())
+        };
+        assert_eq!(names(InputOrder::OldestFirst, None)?, vec!["b.parquet", "c.parquet", "a.parquet"]);
+        assert_eq!(names(InputOrder::NewestFirst, None)?, vec!["a.parquet", "c.parquet", "b.parquet"]);
+        assert_eq!(names(InputOrder::LargestFirst, None)?, vec!["a.parquet", "c.parquet", "b.parquet"]);
+        assert_eq!(names(InputOrder::SmallestFirst, Some(2))?, vec!["b.parquet", "c.parquet"]);
+        assert_eq!(names(InputOrder::NewestFirst, Some(1))?, vec!["a.parquet"]);
+
+        assert_eq!("Newest-First".parse::<InputOrder>()?, InputOrder::NewestFirst);
+        assert!("random".parse::<InputOrder>().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_file_infos() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let root = temp_dir.path();
+        create_test_parquet_file(&root.join("a.parquet"), 0, 10)?;
+        create_test_parquet_file(&root.join("b.parquet"), 10, 200)?;
+        create_test_parquet_file(&root.join("c.parquet"), 200, 205)?;
+
+        let options = DiscoveryOptions { order: Some(InputOrder::LargestFirst), ..Default::default() };
+        let mut files = discover_file_infos(root, &options)?;
+        let paths: Vec<PathBuf> = files.iter().map(|file| file.path.clone()).collect();
+        assert_eq!(paths, discover_input_files(root, &options)?);
+        assert_eq!(paths[0], root.join("b.parquet"));
+        assert!(files.iter().all(|file| file.size == file_size(&file.path) && file.modified == modified_time(&file.path)));
+
+        for file in &mut files {
+            file.read_footer()?;
+        }
+        let rows: Vec<Option<u64>> = files.iter().map(|file| file.row_count).collect();
+        assert_eq!(rows, [Some(190), Some(10), Some(5)]);
+        // Same schema, same fingerprint
+        assert!(files[0].schema_fingerprint.is_some());
+        assert!(files.iter().all(|file| file.schema_fingerprint == files[0].schema_fingerprint));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_min_file_age() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let root = temp_dir.path();
+        let now = SystemTime::now();
+        for (name, age) in [("settled.parquet", 600), ("fresh.parquet", 5), ("locked.parquet", 600)] {
+            let file = File::create(root.join(name))?;
+            file.set_modified(now - Duration::from_secs(age))?;
+        }
+        let writer = File::options().write(true).open(root.join("locked.parquet"))?;
+        writer.lock()?;
+
+        let options = DiscoveryOptions { min_file_age: Some(Duration::from_secs(60)), ..Default::default() };
+        assert_eq!(discover_input_files(root, &options)?, vec![root.join("settled.parquet")]);
+
+        writer.unlock()?;
+        assert_eq!(discover_input_files(root, &options)?.len(), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_file_list() -> Result<()> {
+        let paths = read_file_list(&b"a b.parquet\r\n\nc.parquet"[..], false)?;
+        assert_eq!(paths, vec![PathBuf::from("a b.parquet"), PathBuf::from("c.parquet")]);
+
+        let paths = read_file_list(&b"line\nbreak.parquet\0\0"[..], true)?;
+        assert_eq!(paths, vec![PathBuf::from("line\nbreak.parquet")]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_listed_input_files() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("dir"))?;
+        for file in ["a.parquet", "dir/b.parquet", "c.csv"] {
+            fs::write(root.join(file), "")?;
+        }
+
+        let files = listed_input_files(&[root.join("a.parquet"), root.join("dir")], &DiscoveryOptions::default())?;
+        assert_eq!(files, vec![root.join("a.parquet"), root.join("dir/b.parquet")]);
+
+        assert!(listed_input_files(&[root.join("c.csv")], &DiscoveryOptions::default()).is_err());
+        assert!(listed_input_files(&[root.join("missing.parquet")], &DiscoveryOptions::default()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_walk_input_files_is_lazy() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("b"))?;
+        for file in ["a.parquet", "b/c.parquet", "b/d.csv"] {
+            fs::write(root.join(file), "")?;
+        }
+
+        let options = DiscoveryOptions { recursive: true, ..Default::default() };
+        let mut files = walk_input_files(root, &options);
+        assert_eq!(files.next().transpose()?, Some(root.join("a.parquet")));
+        // Files created after the walk started but before it reaches them
+        // are still found
+        fs::write(root.join("b/e.parquet"), "")?;
+        let rest = files.collect::<Result<Vec<_>>>()?;
+        assert_eq!(rest, vec![root.join("b/c.parquet"), root.join("b/e.parquet")]);
+
+        assert!(walk_input_files(&root.join("b/d.csv"), &options).next().unwrap().is_err());
+        assert!(walk_input_files(&root.join("missing"), &options).next().is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_archives() -> Result<()> {
+        use std::io::Write;
+
+        let temp_dir = tempfile::TempDir::new()?;
+        let root = temp_dir.path();
+        fs::write(root.join("a.parquet"), "")?;
+        let mut zip = zip::ZipWriter::new(File::create(root.join("daily.zip"))?);
+        for name in ["part-1.parquet", "README.txt", "nested/part-0.parquet"] {
+            zip.start_file(name, zip::write::SimpleFileOptions::default())?;
+            zip.write_all(b"")?;
+        }
+        zip.finish()?;
+
+        let options = DiscoveryOptions { read_archives: true, ..Default::default() };
+        let files = discover_input_files(root, &options)?;
+        assert_eq!(
+            files,
+            vec![root.join("a.parquet"), root.join("daily.zip/part-1.parquet"), root.join("daily.zip/nested/part-0.parquet")]
+        );
+        let files = discover_input_files(&root.join("daily.zip"), &options)?;
+        assert_eq!(files.len(), 2);
+
+        let options = DiscoveryOptions { name_regex: Some(file_name_regex(r"part-0\..*")?), ..options };
+        assert_eq!(discover_input_files(root, &options)?, vec![root.join("daily.zip/nested/part-0.parquet")]);
+
+        // Archives are ordinary files unless asked for
+        assert_eq!(discover_input_files(root, &DiscoveryOptions::default())?, vec![root.join("a.parquet")]);
+        assert!(discover_input_files(&root.join("daily.zip"), &DiscoveryOptions::default()).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_summary_metadata() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("day=1"))?;
+        fs::create_dir_all(root.join("_temporary"))?;
+        create_test_parquet_file(&root.join("b.parquet"), 0, 5)?;
+        create_test_parquet_file(&root.join("day=1/a.parquet"), 5, 10)?;
+        create_test_parquet_file(&root.join("_temporary/c.parquet"), 10, 15)?;
+        create_test_parquet_file(&root.join("unlisted.parquet"), 15, 20)?;
+        create_summary_metadata_files(root, &["b.parquet", "day=1/a.parquet", "_temporary/c.parquet"])?;
+
+        assert_eq!(summary_schema_file(root), Some(root.join(COMMON_METADATA_FILE)));
+        assert_eq!(summary_data_files(&root.join(SUMMARY_METADATA_FILE))?.len(), 3);
+
+        let options = DiscoveryOptions {
+            recursive: true,
+            exclude_dirs: vec!["_temporary".to_string()],
+            use_summary_metadata: true,
+            ..Default::default()
+        };
+        let files = discover_input_files(root, &options)?;
+        assert_eq!(files, vec![root.join("b.parquet"), root.join("day=1/a.parquet")]);
+
+        let options = DiscoveryOptions { recursive: false, ..options };
+        assert_eq!(discover_input_files(root, &options)?, vec![root.join("b.parquet")]);
+
+        fs::remove_file(root.join("b.parquet"))?;
+        assert!(discover_input_files(root, &options).unwrap_err().to_string().contains("missing"));
+        let options = DiscoveryOptions { use_summary_metadata: false, ..options };
+        assert_eq!(discover_input_files(root, &options)?, vec![root.join("unlisted.parquet")]);
+
+        Ok(())
+    }
+
+}
@@ -0,0 +1,214 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+use anyhow::{Result, Context};
+use chrono::{DateTime, Utc};
+use polars::prelude::*;
+use crate::file_info::modified_time;
+use crate::formats::{InputFormat, ReadOptions};
+
+/// A difference between the schemas of two consecutive input files
+#[derive(Debug, Clone, PartialEq)]
+pub enum SchemaChange {
+    Added { column: String, data_type: DataType },
+    Removed { column: String, data_type: DataType },
+    TypeChanged { column: String, from: DataType, to: DataType },
+}
+
+impl fmt::Display for SchemaChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SchemaChange::Added { column, data_type } => write!(f, "+ {} ({})", column, data_type),
+            SchemaChange::Removed { column, data_type } => write!(f, "- {} ({})", column, data_type),
+            SchemaChange::TypeChanged { column, from, to } => write!(f, "~ {} ({} -> {})", column, from, to),
+        }
+    }
+}
+
+/// A file that introduced schema changes, or the first file of the dataset
+#[derive(Debug, Clone, PartialEq)]
+pub struct DriftEntry {
+    pub file: PathBuf,
+    /// Modification time or partition value the file was ordered by
+    pub position: String,
+    /// Number of columns in the file
+    pub columns: usize,
+    /// Changes relative to the previous file; empty for the first file
+    pub changes: Vec<SchemaChange>,
+}
+
+/// When columns appeared, disappeared or changed type across a dataset
+#[derive(Debug, Clone, PartialEq)]
+pub struct DriftReport {
+    pub files: usize,
+    pub entries: Vec<DriftEntry>,
+}
+
+impl DriftReport {
+    /// Total number of changes between consecutive files
+    pub fn change_count(&self) -> usize {
+        self.entries.iter().map(|entry| entry.changes.len()).sum()
+    }
+}
+
+impl fmt::Display for DriftReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, entry) in self.entries.iter().enumerate() {
+            if index == 0 {
+                writeln!(
+                    f,
+                    "{}  {}  initial schema ({} columns)",
+                    entry.position,
+                    entry.file.display(),
+                    entry.columns
+                )?;
+                continue;
+            }
+            writeln!(f, "{}  {}", entry.position, entry.file.display())?;
+            for change in &entry.changes {
+                writeln!(f, "    {}", change)?;
+            }
+        }
+        writeln!(f, "{} schema changes across {} files", self.change_count(), self.files)
+    }
+}
+
+/// Report how the schema of the input files evolved over time
+///
+/// Files are ordered by modification time, or by the value of the hive
+/// partition directory `<partition_key>=<value>` in their path, with ties
+/// broken by path. Each file's schema is compared with the previous file's,
+/// so a producer that briefly wrote a different schema shows up as the
+/// change and its reversal.
+pub fn drift_report(input_files: &[PathBuf], read: &ReadOptions, partition_key: Option<&str>) -> Result<DriftReport> {
+    if input_files.is_empty() {
+        anyhow::bail!("No input files provided");
+    }
+
+    let mut ordered = input_files
+        .iter()
+        .map(|path| {
+            let position = match partition_key {
+                Some(key) => partition_value(path, key)
+                    .ok_or_else(|| anyhow::anyhow!("No {}=<value> directory in the path of {:?}", key, path))?,
+                None => DateTime::<Utc>::from(modified_time(path)).format("%Y-%m-%d %H:%M:%S").to_string(),
+            };
+            Ok((position, path))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    ordered.sort();
+
+    let mut entries: Vec<DriftEntry> = Vec::new();
+    let mut previous: Option<Schema> = None;
+    for (position, path) in ordered {
+        let format = InputFormat::from_path(path).unwrap_or(InputFormat::Parquet);
+        let schema = format
+            .scan_with_options(path, read)
+            .and_then(|scan| Ok(scan.schema()?))
+            .context(format!("Failed to read the schema of {:?}", path))?;
+
+        let changes = previous.as_ref().map(|previous| schema_changes(previous, &schema));
+        if changes.as_ref().is_none_or(|changes| !changes.is_empty()) {
+            entries.push(DriftEntry {
+                file: path.clone(),
+                position,
+                columns: schema.len(),
+                changes: changes.unwrap_or_default(),
+            });
+        }
+        previous = Some(schema.as_ref().clone());
+    }
+
+    Ok(DriftReport { files: input_files.len(), entries })
+}
+
+/// Value of the `key=value` directory in a path
+fn partition_value(path: &Path, key: &str) -> Option<String> {
+    path.parent()?.iter().rev().find_map(|component| {
+        let component = component.to_string_lossy();
+        let (name, value) = component.split_once('=')?;
+        (name == key).then(|| value.to_string())
+    })
+}
+
+/// Columns added, removed or retyped from `before` to `after`
+fn schema_changes(before: &Schema, after: &Schema) -> Vec<SchemaChange> {
+    let mut changes = Vec::new();
+    for (column, data_type) in after.iter() {
+        match before.get(column) {
+            None => changes.push(SchemaChange::Added { column: column.to_string(), data_type: data_type.clone() }),
+            Some(from) if from != data_type => changes.push(SchemaChange::TypeChanged {
+                column: column.to_string(),
+                from: from.clone(),
+                to: data_type.clone(),
+            }),
+            Some(_) => {}
+        }
+    }
+    for (column, data_type) in before.iter() {
+        if after.get(column).is_none() {
+            changes.push(SchemaChange::Removed { column: column.to_string(), data_type: data_type.clone() });
+        }
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{self, File};
+    use std::time::{Duration, SystemTime};
+    use crate::test_utils::{create_test_parquet_file, create_test_parquet_file_with_extra_column};
+
+    #[test]
+    fn test_drift_report() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let root = temp_dir.path();
+        for day in ["2024-01-01", "2024-01-02", "2024-01-03", "2024-01-04"] {
+            fs::create_dir_all(root.join(format!("date={}", day)))?;
+        }
+        let files = vec![
+            root.join("date=2024-01-03/c.parquet"),
+            root.join("date=2024-01-01/a.parquet"),
+            root.join("date=2024-01-02/b.parquet"),
+            root.join("date=2024-01-04/d.csv"),
+        ];
+        create_test_parquet_file(&files[1], 0, 5)?;
+        create_test_parquet_file(&files[2], 5, 10)?;
+        create_test_parquet_file_with_extra_column(&files[0], 10, 15)?;
+        fs::write(&files[3], "id,name,value\n1,a,x\n")?;
+
+        let report = drift_report(&files, &ReadOptions::default(), Some("date"))?;
+        let positions: Vec<&str> = report.entries.iter().map(|entry| entry.position.as_str()).collect();
+        assert_eq!(positions, vec!["2024-01-01", "2024-01-03", "2024-01-04"]);
+        assert_eq!(
+            report.entries[1].changes,
+            vec![SchemaChange::Added { column: "extra".to_string(), data_type: DataType::Utf8 }]
+        );
+        assert_eq!(
+            report.entries[2].changes,
+            vec![
+                SchemaChange::TypeChanged { column: "id".to_string(), from: DataType::Int32, to: DataType::Int64 },
+                SchemaChange::TypeChanged { column: "value".to_string(), from: DataType::Float64, to: DataType::Utf8 },
+                SchemaChange::Removed { column: "extra".to_string(), data_type: DataType::Utf8 },
+            ]
+        );
+        assert_eq!(report.change_count(), 4);
+        let text = report.to_string();
+        assert!(text.contains("initial schema (3 columns)"));
+        assert!(text.ends_with("4 schema changes across 4 files\n"));
+
+        // By modification time the extra column appears in the oldest file
+        for (index, file) in files.iter().enumerate() {
+            let modified = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000 + index as u64 * 60);
+            File::options().write(true).open(file)?.set_modified(modified)?;
+        }
+        let report = drift_report(&files[..3], &ReadOptions::default(), None)?;
+        assert_eq!(report.entries[0].file, files[0]);
+        assert_eq!(report.entries[0].position, "2023-11-14 22:13:20");
+        assert_eq!(report.change_count(), 1);
+
+        assert!(drift_report(&files, &ReadOptions::default(), Some("hour")).is_err());
+
+        Ok(())
+    }
+}
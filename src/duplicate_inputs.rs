@@ -0,0 +1,133 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
+use anyhow::Result;
+use crate::audit::FileRecord;
+use crate::file_info::file_size;
+use crate::formats::is_buffered_input;
+
+/// Inputs with byte-identical contents, such as the copies a retried
+/// upload leaves behind
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateInputs {
+    /// The copy that is read: the first in input order
+    pub kept: PathBuf,
+    /// The other copies, in input order
+    pub duplicates: Vec<PathBuf>,
+    /// Size of each copy
+    pub bytes: u64,
+}
+
+impl fmt::Display for DuplicateInputs {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let duplicates: Vec<String> = self.duplicates.iter().map(|path| format!("{:?}", path)).collect();
+        write!(f, "Collapsed {} into {:?}, with the same {} bytes", duplicates.join(", "), self.kept, self.bytes)
+    }
+}
+
+/// Find inputs that are byte-identical to an earlier input
+///
+/// Only inputs sharing their size with another are read, to compare their
+/// XXH3-128 checksums, so inputs of distinct sizes cost a stat each.
+/// Archive members and standard input are never compared. Groups are in
+/// the input order of the copy kept.
+pub fn find_duplicate_inputs(input_files: &[PathBuf]) -> Result<Vec<DuplicateInputs>> {
+    let sizes: Vec<Option<u64>> = input_files
+        .iter()
+        .map(|path| (!is_buffered_input(path) && path.is_file()).then(|| file_size(path)))
+        .collect();
+    let mut size_counts: HashMap<u64, usize> = HashMap::new();
+    for size in sizes.iter().flatten() {
+        *size_counts.entry(*size).or_default() += 1;
+    }
+
+    // Index of the group each checksum starts
+    let mut groups: Vec<DuplicateInputs> = Vec::new();
+    let mut by_checksum: HashMap<(u64, String), usize> = HashMap::new();
+    for (path, size) in input_files.iter().zip(sizes) {
+        let Some(size) = size.filter(|size| size_counts[size] > 1) else {
+            continue;
+        };
+        let checksum = FileRecord::of(path)?.xxh3;
+        match by_checksum.get(&(size, checksum.clone())) {
+            Some(&group) => groups[group].duplicates.push(path.clone()),
+            None => {
+                by_checksum.insert((size, checksum), groups.len());
+                groups.push(DuplicateInputs { kept: path.clone(), duplicates: Vec::new(), bytes: size });
+            }
+        }
+    }
+    groups.retain(|group| !group.duplicates.is_empty());
+    Ok(groups)
+}
+
+/// The inputs with every byte-identical copy but the first left out, and
+/// the groups of copies collapsed
+///
+/// # Examples
+///
+/// ```
+/// use std::fs;
+/// use parquet_consolidator::collapse_duplicate_inputs;
+/// use parquet_consolidator::test_utils::create_test_parquet_file;
+/// use tempfile::TempDir;
+///
+/// let temp_dir = TempDir::new().unwrap();
+/// let inputs = ["a.parquet", "a (1).parquet", "b.parquet"].map(|name| temp_dir.path().join(name));
+/// create_test_parquet_file(&inputs[0], 0, 10).unwrap();
+/// fs::copy(&inputs[0], &inputs[1]).unwrap();
+/// create_test_parquet_file(&inputs[2], 10, 20).unwrap();
+///
+/// let (kept, collapsed) = collapse_duplicate_inputs(&inputs).unwrap();
+/// assert_eq!(kept, [inputs[0].clone(), inputs[2].clone()]);
+/// assert_eq!(collapsed[0].duplicates, [inputs[1].clone()]);
+/// ```
+pub fn collapse_duplicate_inputs(input_files: &[PathBuf]) -> Result<(Vec<PathBuf>, Vec<DuplicateInputs>)> {
+    let collapsed = find_duplicate_inputs(input_files)?;
+    let mut kept: Vec<PathBuf> = Vec::new();
+    for path in input_files {
+        let is_copy = collapsed.iter().any(|group| group.duplicates.contains(path));
+        // A path listed twice is its own copy, and still kept once
+        let is_kept = collapsed.iter().any(|group| &group.kept == path) && !kept.contains(path);
+        if !is_copy || is_kept {
+            kept.push(path.clone());
+        }
+    }
+    Ok((kept, collapsed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use crate::test_utils::create_test_parquet_file;
+
+    #[test]
+    fn test_find_duplicate_inputs() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let path = |name: &str| temp_dir.path().join(name);
+        create_test_parquet_file(&path("a.parquet"), 0, 10)?;
+        create_test_parquet_file(&path("c.parquet"), 0, 500)?;
+        fs::copy(path("a.parquet"), path("a-retry-1.parquet"))?;
+        fs::copy(path("a.parquet"), path("a-retry-2.parquet"))?;
+        // The same size, but not the same bytes
+        let mut bytes = fs::read(path("a.parquet"))?;
+        bytes[100] ^= 0xff;
+        fs::write(path("b.parquet"), bytes)?;
+
+        let inputs: Vec<PathBuf> = ["a-retry-1", "b", "a", "c", "a-retry-2", "c"]
+            .iter()
+            .map(|name| path(&format!("{}.parquet", name)))
+            .collect();
+        let groups = find_duplicate_inputs(&inputs)?;
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].kept, path("a-retry-1.parquet"));
+        assert_eq!(groups[0].duplicates, vec![path("a.parquet"), path("a-retry-2.parquet")]);
+        assert!(groups[0].to_string().starts_with("Collapsed "));
+
+        let (kept, _) = collapse_duplicate_inputs(&inputs)?;
+        assert_eq!(kept, vec![path("a-retry-1.parquet"), path("b.parquet"), path("c.parquet")]);
+
+        Ok(())
+    }
+}
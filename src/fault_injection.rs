@@ -0,0 +1,178 @@
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
+use anyhow::Result;
+
+/// Places in a run where faults can be injected
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FaultPoint {
+    /// Opening an input file
+    Read,
+    /// Creating an output file, before any of it is written
+    Write,
+    /// Publishing finished outputs: moving a compacted file into place,
+    /// pointing `LATEST` at a run, or deleting replaced partitions
+    Commit,
+}
+
+impl fmt::Display for FaultPoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            FaultPoint::Read => "read",
+            FaultPoint::Write => "write",
+            FaultPoint::Commit => "commit",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// What happens when a fault fires
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    /// The operation fails with an error
+    Error,
+    /// The operation is delayed by this long, then goes ahead
+    Slow(Duration),
+    /// The process aborts on the spot, without unwinding or cleaning up, as
+    /// if it had been killed
+    Crash,
+}
+
+/// A fault to inject, parsed from `POINT:FAULT[@N]`
+///
+/// `POINT` is `read`, `write` or `commit` and `FAULT` is `error`,
+/// `slow=<MILLIS>` or `crash`. With `@N` the fault fires only the `N`th time
+/// the point is reached, counting from 1; otherwise it fires every time.
+///
+/// # Examples
+///
+/// ```
+/// use parquet_consolidator::{Fault, FaultPoint, FaultSpec};
+/// use std::time::Duration;
+///
+/// let spec: FaultSpec = "read:slow=250@2".parse().unwrap();
+/// assert_eq!(spec.point, FaultPoint::Read);
+/// assert_eq!(spec.fault, Fault::Slow(Duration::from_millis(250)));
+/// assert_eq!(spec.nth, Some(2));
+/// assert!("fsync:crash".parse::<FaultSpec>().is_err());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FaultSpec {
+    pub point: FaultPoint,
+    pub fault: Fault,
+    pub nth: Option<usize>,
+}
+
+impl FromStr for FaultSpec {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || anyhow::anyhow!("Invalid fault {:?} (expected POINT:FAULT[@N], e.g. read:error or commit:crash@2)", s);
+        let (point, rest) = s.split_once(':').ok_or_else(invalid)?;
+        let (fault, nth) = match rest.split_once('@') {
+            Some((fault, nth)) => (fault, Some(nth.parse::<usize>().ok().filter(|&nth| nth > 0).ok_or_else(invalid)?)),
+            None => (rest, None),
+        };
+        let point = match point {
+            "read" => FaultPoint::Read,
+            "write" => FaultPoint::Write,
+            "commit" => FaultPoint::Commit,
+            _ => return Err(invalid()),
+        };
+        let fault = match fault.split_once('=') {
+            None if fault == "error" => Fault::Error,
+            None if fault == "crash" => Fault::Crash,
+            Some(("slow", millis)) => Fault::Slow(Duration::from_millis(millis.parse().map_err(|_| invalid())?)),
+            _ => return Err(invalid()),
+        };
+        Ok(FaultSpec { point, fault, nth })
+    }
+}
+
+/// A set of faults and how many times each has been reached
+#[derive(Debug, Default)]
+pub struct FaultInjector {
+    faults: Vec<(FaultSpec, AtomicUsize)>,
+}
+
+impl FaultInjector {
+    pub fn new(specs: &[FaultSpec]) -> Self {
+        FaultInjector { faults: specs.iter().map(|spec| (*spec, AtomicUsize::new(0))).collect() }
+    }
+
+    /// Reach `point` while working on `path`, firing the faults due there
+    pub fn hit(&self, point: FaultPoint, path: &Path) -> Result<()> {
+        for (spec, hits) in self.faults.iter().filter(|(spec, _)| spec.point == point) {
+            let hit = hits.fetch_add(1, Ordering::SeqCst) + 1;
+            if spec.nth.is_some_and(|nth| nth != hit) {
+                continue;
+            }
+            match spec.fault {
+                Fault::Error => anyhow::bail!("Injected {} error for {:?}", point, path),
+                Fault::Slow(delay) => thread::sleep(delay),
+                Fault::Crash => {
+                    eprintln!("Injected crash at {} of {:?}", point, path);
+                    std::process::abort();
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+static INSTALLED: OnceLock<FaultInjector> = OnceLock::new();
+
+/// Inject the given faults into every later run of this process; can only
+/// be done once
+pub fn install_faults(specs: &[FaultSpec]) -> Result<()> {
+    INSTALLED
+        .set(FaultInjector::new(specs))
+        .map_err(|_| anyhow::anyhow!("Faults are already installed"))
+}
+
+/// Reach `point` while working on `path`, firing the installed faults due
+/// there; does nothing when none are installed
+pub fn inject_fault(point: FaultPoint, path: &Path) -> Result<()> {
+    match INSTALLED.get() {
+        Some(injector) => injector.hit(point, path),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    #[test]
+    fn test_fault_injector() -> Result<()> {
+        let specs: Vec<FaultSpec> = ["read:error@2", "write:slow=50", "commit:error"]
+            .iter()
+            .map(|spec| spec.parse())
+            .collect::<Result<_>>()?;
+        let injector = FaultInjector::new(&specs);
+        let path = Path::new("a.parquet");
+
+        injector.hit(FaultPoint::Read, path)?;
+        let err = injector.hit(FaultPoint::Read, path).unwrap_err();
+        assert_eq!(err.to_string(), "Injected read error for \"a.parquet\"");
+        injector.hit(FaultPoint::Read, path)?;
+
+        let started = Instant::now();
+        injector.hit(FaultPoint::Write, path)?;
+        assert!(started.elapsed() >= Duration::from_millis(50));
+        assert!(injector.hit(FaultPoint::Commit, path).is_err());
+        assert!(injector.hit(FaultPoint::Commit, path).is_err());
+
+        assert_eq!("commit:crash".parse::<FaultSpec>()?.fault, Fault::Crash);
+        for invalid in ["read", "read:error@0", "write:slow", "read:explode"] {
+            assert!(invalid.parse::<FaultSpec>().is_err(), "{}", invalid);
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,207 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use anyhow::{Result, Context};
+use arrow::datatypes::{Field, Schema, SchemaRef};
+use parquet::arrow::arrow_reader::ArrowReaderMetadata;
+use crate::formats::{is_buffered_input, InputFormat};
+
+/// Arrow field metadata, such as descriptions and units, of top-level
+/// columns by name
+pub type FieldMetadata = HashMap<String, HashMap<String, String>>;
+
+/// What to do when inputs give a column's metadata key different values
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FieldMetadataPolicy {
+    /// Fail the run before anything is written
+    Error,
+    /// Keep the value of the first input holding the key, with a warning
+    #[default]
+    First,
+    /// Keep the value of the last input holding the key, with a warning
+    Last,
+    /// Leave the key out, with a warning
+    Drop,
+}
+
+impl fmt::Display for FieldMetadataPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            FieldMetadataPolicy::Error => "error",
+            FieldMetadataPolicy::First => "first",
+            FieldMetadataPolicy::Last => "last",
+            FieldMetadataPolicy::Drop => "drop",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for FieldMetadataPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "error" => Ok(FieldMetadataPolicy::Error),
+            "first" => Ok(FieldMetadataPolicy::First),
+            "last" => Ok(FieldMetadataPolicy::Last),
+            "drop" => Ok(FieldMetadataPolicy::Drop),
+            other => anyhow::bail!("Unknown field metadata policy: {} (expected error, first, last or drop)", other),
+        }
+    }
+}
+
+/// A metadata key of a column that inputs give different values
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldMetadataConflict {
+    pub column: String,
+    pub key: String,
+    /// The distinct values, in input order
+    pub values: Vec<String>,
+}
+
+impl fmt::Display for FieldMetadataConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let values: Vec<String> = self.values.iter().map(|value| format!("{:?}", value)).collect();
+        write!(f, "Inputs give metadata {:?} of column {} different values: {}", self.key, self.column, values.join(", "))
+    }
+}
+
+/// Read the field metadata of a parquet file's top-level columns, leaving
+/// out columns without any
+///
+/// It comes from the arrow schema stored in the footer, so only files
+/// written by arrow-based writers carry it.
+pub fn read_field_metadata(path: &Path) -> Result<FieldMetadata> {
+    let file = File::open(path).context(format!("Failed to open {:?}", path))?;
+    let metadata = ArrowReaderMetadata::load(&file, Default::default())
+        .context(format!("Failed to read parquet footer of {:?}", path))?;
+    Ok(metadata
+        .schema()
+        .fields()
+        .iter()
+        .filter(|field| !field.metadata().is_empty())
+        .map(|field| (field.name().clone(), field.metadata().clone()))
+        .collect())
+}
+
+/// Merge the field metadata of the parquet inputs, resolving keys that
+/// inputs give different values by `policy`
+///
+/// A key only some inputs carry is kept. Other formats carry no field
+/// metadata and are left out. Returns the merged metadata and the
+/// conflicts, which fail nothing here, even under
+/// [`FieldMetadataPolicy::Error`].
+pub fn merge_field_metadata(
+    input_files: &[PathBuf],
+    policy: FieldMetadataPolicy,
+) -> Result<(FieldMetadata, Vec<FieldMetadataConflict>)> {
+    let mut values: BTreeMap<(String, String), Vec<String>> = BTreeMap::new();
+    for path in input_files {
+        let parquet = InputFormat::from_path(path).unwrap_or(InputFormat::Parquet) == InputFormat::Parquet;
+        if !parquet || is_buffered_input(path) {
+            continue;
+        }
+        for (column, metadata) in read_field_metadata(path)? {
+            for (key, value) in metadata {
+                let seen = values.entry((column.clone(), key)).or_default();
+                if !seen.contains(&value) {
+                    seen.push(value);
+                }
+            }
+        }
+    }
+
+    let mut merged = FieldMetadata::new();
+    let mut conflicts = Vec::new();
+    for ((column, key), seen) in values {
+        let value = match (seen.len(), policy) {
+            (1, _) | (_, FieldMetadataPolicy::First) => seen.first().cloned(),
+            (_, FieldMetadataPolicy::Last) => seen.last().cloned(),
+            (_, FieldMetadataPolicy::Drop | FieldMetadataPolicy::Error) => None,
+        };
+        if let Some(value) = value {
+            merged.entry(column.clone()).or_default().insert(key.clone(), value);
+        }
+        if seen.len() > 1 {
+            conflicts.push(FieldMetadataConflict { column, key, values: seen });
+        }
+    }
+    Ok((merged, conflicts))
+}
+
+/// Attach metadata to the top-level fields of a schema it names, over any
+/// metadata they already have
+pub(crate) fn attach_field_metadata(schema: SchemaRef, metadata: &FieldMetadata) -> SchemaRef {
+    if metadata.is_empty() {
+        return schema;
+    }
+    let fields: Vec<Field> = schema
+        .fields()
+        .iter()
+        .map(|field| match metadata.get(field.name()) {
+            Some(extra) => {
+                let mut merged = field.metadata().clone();
+                merged.extend(extra.clone());
+                field.as_ref().clone().with_metadata(merged)
+            }
+            None => field.as_ref().clone(),
+        })
+        .collect();
+    SchemaRef::new(Schema::new_with_metadata(fields, schema.metadata().clone()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use arrow::array::Int32Array;
+    use arrow::datatypes::DataType;
+    use arrow::record_batch::RecordBatch;
+    use parquet::arrow::ArrowWriter;
+    use crate::consolidator::{consolidate_to_writer, ConsolidateOptions};
+
+    fn write_described(path: &Path, description: &str) -> Result<()> {
+        let metadata = HashMap::from([
+            ("description".to_string(), description.to_string()),
+            ("unit".to_string(), "ms".to_string()),
+        ]);
+        let schema = Arc::new(Schema::new(vec![Field::new("latency", DataType::Int32, false).with_metadata(metadata)]));
+        let batch = RecordBatch::try_new(schema.clone(), vec![Arc::new(Int32Array::from(vec![1, 2]))])?;
+        let mut writer = ArrowWriter::try_new(File::create(path)?, schema, None)?;
+        writer.write(&batch)?;
+        writer.close()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_field_metadata() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let inputs = [temp_dir.path().join("a.parquet"), temp_dir.path().join("b.parquet")];
+        write_described(&inputs[0], "Request latency")?;
+        write_described(&inputs[1], "Latency of the request")?;
+
+        let (merged, conflicts) = merge_field_metadata(&inputs, FieldMetadataPolicy::Last)?;
+        assert_eq!(merged["latency"]["description"], "Latency of the request");
+        assert_eq!(merged["latency"]["unit"], "ms");
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(
+            conflicts[0].to_string(),
+            "Inputs give metadata \"description\" of column latency different values: \"Request latency\", \"Latency of the request\""
+        );
+        let (merged, _) = merge_field_metadata(&inputs, FieldMetadataPolicy::Drop)?;
+        assert_eq!(merged["latency"].keys().collect::<Vec<_>>(), ["unit"]);
+
+        // The merged metadata survives consolidation
+        let (_, buffer) = consolidate_to_writer(&inputs, Vec::new(), &ConsolidateOptions::default())?;
+        let output = ArrowReaderMetadata::load(&bytes::Bytes::from(buffer), Default::default())?;
+        let field = output.schema().field_with_name("latency")?.clone();
+        assert_eq!(field.metadata()["description"], "Request latency");
+        assert_eq!(field.metadata()["unit"], "ms");
+
+        assert!("keep".parse::<FieldMetadataPolicy>().is_err());
+
+        Ok(())
+    }
+}
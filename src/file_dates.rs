@@ -0,0 +1,201 @@
+use std::fmt;
+use std::str::FromStr;
+use anyhow::{Result, Context};
+use chrono::NaiveDate;
+use regex::Regex;
+
+/// Where a date sits in file names, as a strftime pattern such as
+/// `%Y%m%d` or `events_%Y-%m-%d`
+///
+/// The pattern is looked for anywhere in the name. It must give the year,
+/// month and day (`%Y`, `%m`, `%d`, or `%F` for all three); `%y`, `%j`,
+/// `%H`, `%M` and `%S` may appear too, and other characters match
+/// themselves.
+#[derive(Debug, Clone)]
+pub struct FileNameDate {
+    pattern: String,
+    regex: Regex,
+}
+
+impl FileNameDate {
+    /// The date in the file name `name`: the first place the pattern is
+    /// found in it that reads as a valid date
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use chrono::NaiveDate;
+    /// use parquet_consolidator::FileNameDate;
+    ///
+    /// let pattern: FileNameDate = "%Y%m%d".parse().unwrap();
+    /// assert_eq!(pattern.date_of("events_20240601.parquet"), NaiveDate::from_ymd_opt(2024, 6, 1));
+    /// assert_eq!(pattern.date_of("events_20241301.parquet"), None);
+    /// assert_eq!(pattern.date_of("1717200000_20240601.parquet"), NaiveDate::from_ymd_opt(2024, 6, 1));
+    /// assert_eq!(pattern.date_of("events_latest.parquet"), None);
+    /// ```
+    pub fn date_of(&self, name: &str) -> Option<NaiveDate> {
+        self.regex
+            .find_iter(name)
+            .find_map(|found| NaiveDate::parse_from_str(found.as_str(), &self.pattern).ok())
+    }
+}
+
+impl fmt::Display for FileNameDate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.pattern)
+    }
+}
+
+impl FromStr for FileNameDate {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut regex = String::new();
+        let (mut year, mut month, mut day) = (false, false, false);
+        let mut chars = s.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                regex.push_str(&regex::escape(&c.to_string()));
+                continue;
+            }
+            let digits = match chars.next() {
+                Some('Y') => {
+                    year = true;
+                    r"\d{4}"
+                }
+                Some('m') => {
+                    month = true;
+                    r"\d{2}"
+                }
+                Some('d') => {
+                    day = true;
+                    r"\d{2}"
+                }
+                Some('F') => {
+                    (year, month, day) = (true, true, true);
+                    r"\d{4}-\d{2}-\d{2}"
+                }
+                Some('y') => {
+                    year = true;
+                    r"\d{2}"
+                }
+                Some('j') => {
+                    (month, day) = (true, true);
+                    r"\d{3}"
+                }
+                Some('H' | 'M' | 'S') => r"\d{2}",
+                Some('%') => "%",
+                Some(other) => anyhow::bail!("Unsupported specifier %{} in file name date pattern {}", other, s),
+                None => anyhow::bail!("File name date pattern {} ends in a lone %", s),
+            };
+            regex.push_str(digits);
+        }
+        if !(year && month && day) {
+            anyhow::bail!("File name date pattern {} must give the year, month and day", s);
+        }
+        let regex = Regex::new(&regex).context(format!("Invalid file name date pattern: {}", s))?;
+        Ok(FileNameDate { pattern: s.to_string(), regex })
+    }
+}
+
+/// Dates from `start` to `end`, both included; an unset end is open
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DateRange {
+    pub start: Option<NaiveDate>,
+    pub end: Option<NaiveDate>,
+}
+
+impl DateRange {
+    pub fn contains(&self, date: NaiveDate) -> bool {
+        self.start.is_none_or(|start| date >= start) && self.end.is_none_or(|end| date <= end)
+    }
+}
+
+impl fmt::Display for DateRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(start) = self.start {
+            write!(f, "{}", start)?;
+        }
+        write!(f, "..")?;
+        if let Some(end) = self.end {
+            write!(f, "{}", end)?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses `START..END` with ISO dates, either of which may be left out,
+/// or a single date
+impl FromStr for DateRange {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let parse = |date: &str| -> Result<Option<NaiveDate>> {
+            match date.trim() {
+                "" => Ok(None),
+                date => NaiveDate::parse_from_str(date, "%Y-%m-%d")
+                    .map(Some)
+                    .map_err(|_| anyhow::anyhow!("Invalid date range: {} (expected START..END with dates as YYYY-MM-DD)", s)),
+            }
+        };
+        let range = match s.split_once("..") {
+            Some((start, end)) => DateRange { start: parse(start)?, end: parse(end)? },
+            None => {
+                let date = parse(s)?;
+                DateRange { start: date, end: date }
+            }
+        };
+        if let (Some(start), Some(end)) = (range.start, range.end) {
+            if end < start {
+                anyhow::bail!("Date range {} ends before it starts", s);
+            }
+        }
+        Ok(range)
+    }
+}
+
+/// Select files by the date in their names rather than by their
+/// modification times, which a copy or sync may have reset
+///
+/// Files without the date in their name are left out.
+#[derive(Debug, Clone)]
+pub struct FileDateFilter {
+    pub pattern: FileNameDate,
+    pub range: DateRange,
+}
+
+impl FileDateFilter {
+    /// Whether the file name `name` carries a date in the range
+    pub fn matches(&self, name: &str) -> bool {
+        self.pattern.date_of(name).is_some_and(|date| self.range.contains(date))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_date_filter() -> Result<()> {
+        let filter = FileDateFilter { pattern: "dt=%F".parse()?, range: "2024-06-01..2024-06-30".parse()? };
+        assert!(filter.matches("part-0_dt=2024-06-01.parquet"));
+        assert!(filter.matches("dt=2024-06-30_part-1.parquet"));
+        assert!(!filter.matches("dt=2024-07-01.parquet"));
+        assert!(!filter.matches("2024-06-15.parquet"));
+
+        // Open ends, a single day, and the day of the year
+        let range: DateRange = "..2024-01-31".parse()?;
+        assert_eq!(range.to_string(), "..2024-01-31");
+        assert!(range.contains(NaiveDate::from_ymd_opt(1999, 1, 1).unwrap()));
+        let filter = FileDateFilter { pattern: "%Y_%j".parse()?, range: "2024-02-01".parse()? };
+        assert!(filter.matches("log_2024_032.parquet"));
+        assert!(!filter.matches("log_2024_033.parquet"));
+
+        assert!("%Y%m".parse::<FileNameDate>().is_err());
+        assert!("%Y%m%d%Q".parse::<FileNameDate>().is_err());
+        assert!("2024-06-30..2024-06-01".parse::<DateRange>().is_err());
+        assert!("June..".parse::<DateRange>().is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,101 @@
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use anyhow::{Result, Context};
+use parquet::file::reader::{FileReader, SerializedFileReader};
+use xxhash_rust::xxh3::xxh3_64;
+use crate::formats::{is_buffered_input, InputFormat};
+
+/// An input file with the metadata filtering, planning, ordering and
+/// reporting need, so each of them doesn't stat the file again
+///
+/// Size and modification time come from a single stat when the file is
+/// discovered or listed. Row count and schema fingerprint need its footer, which is
+/// only read by [`FileInfo::read_footer`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileInfo {
+    pub path: PathBuf,
+    /// Size in bytes, or zero when it cannot be read
+    pub size: u64,
+    /// Modification time, or the epoch when it cannot be read
+    pub modified: SystemTime,
+    pub row_count: Option<u64>,
+    /// Hash of the parquet schema; files with equal fingerprints have the
+    /// same columns, types and nullability
+    pub schema_fingerprint: Option<u64>,
+}
+
+impl FileInfo {
+    /// Stat `path`, without reading any of its contents
+    pub fn stat(path: PathBuf) -> Self {
+        let metadata = fs::metadata(&path).ok();
+        FileInfo {
+            size: metadata.as_ref().map_or(0, |m| m.len()),
+            modified: metadata.and_then(|m| m.modified().ok()).unwrap_or(SystemTime::UNIX_EPOCH),
+            path,
+            row_count: None,
+            schema_fingerprint: None,
+        }
+    }
+
+    /// Fill in the row count and schema fingerprint from the file's parquet
+    /// footer
+    ///
+    /// Files in other formats and archive members are left as they are.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use parquet_consolidator::FileInfo;
+    /// use parquet_consolidator::test_utils::create_test_parquet_file;
+    /// use tempfile::TempDir;
+    ///
+    /// let temp_dir = TempDir::new().unwrap();
+    /// let path = temp_dir.path().join("a.parquet");
+    /// create_test_parquet_file(&path, 0, 10).unwrap();
+    ///
+    /// let mut file = FileInfo::stat(path);
+    /// assert_eq!(file.row_count, None);
+    /// file.read_footer().unwrap();
+    /// assert_eq!(file.row_count, Some(10));
+    /// ```
+    pub fn read_footer(&mut self) -> Result<()> {
+        let parquet = InputFormat::from_path(&self.path).unwrap_or(InputFormat::Parquet) == InputFormat::Parquet;
+        if !parquet || is_buffered_input(&self.path) {
+            return Ok(());
+        }
+        let reader = SerializedFileReader::new(File::open(&self.path).context(format!("Failed to open {:?}", self.path))?)
+            .context(format!("Failed to read parquet footer of {:?}", self.path))?;
+        let file_metadata = reader.metadata().file_metadata();
+        let mut schema = Vec::new();
+        parquet::schema::printer::print_schema(&mut schema, file_metadata.schema());
+        self.row_count = Some(file_metadata.num_rows().max(0) as u64);
+        self.schema_fingerprint = Some(xxh3_64(&schema));
+        Ok(())
+    }
+}
+
+/// Modification time of a file, or the epoch when it cannot be read
+pub(crate) fn modified_time(path: &Path) -> SystemTime {
+    fs::metadata(path).and_then(|m| m.modified()).unwrap_or(SystemTime::UNIX_EPOCH)
+}
+
+/// Size of a file in bytes, or zero when it cannot be read
+pub(crate) fn file_size(path: &Path) -> u64 {
+    fs::metadata(path).map(|m| m.len()).unwrap_or(0)
+}
+
+/// Whether `path` is a named pipe (FIFO), which is written as a stream
+/// another process reads, and can't be seeked, read back or replaced
+pub fn is_named_pipe(path: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::FileTypeExt;
+        fs::metadata(path).map(|m| m.file_type().is_fifo()).unwrap_or(false)
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = path;
+        false
+    }
+}
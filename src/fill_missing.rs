@@ -0,0 +1,99 @@
+use std::str::FromStr;
+use anyhow::{Result, Context};
+use polars::prelude::*;
+
+/// The value rows from inputs lacking a column get in it when inputs with
+/// different columns are stacked, instead of null
+///
+/// Parsed from `column=value`, e.g. `status=unknown` or `score=0`. The
+/// value is text, cast to the column's type in the merged schema.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColumnFill {
+    pub column: String,
+    pub value: String,
+}
+
+impl FromStr for ColumnFill {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.split_once('=') {
+            Some((column, value)) if !column.is_empty() => {
+                Ok(ColumnFill { column: column.to_string(), value: value.to_string() })
+            }
+            _ => anyhow::bail!("Invalid fill {:?}: expected column=value", s),
+        }
+    }
+}
+
+/// The literal filling `column`, of type `data_type`, in the rows of an
+/// input lacking it: its fill value when one is given, otherwise null
+///
+/// # Examples
+///
+/// ```
+/// use polars::prelude::DataType;
+/// use parquet_consolidator::{missing_column_value, ColumnFill};
+///
+/// let fills: Vec<ColumnFill> = vec!["score=0".parse().unwrap()];
+/// assert!(missing_column_value("score", &DataType::Int64, &fills).is_ok());
+/// assert!(missing_column_value("status", &DataType::Utf8, &fills).is_ok());
+/// assert!(missing_column_value("score", &DataType::Boolean, &fills).is_err());
+/// ```
+pub fn missing_column_value(column: &str, data_type: &DataType, fills: &[ColumnFill]) -> Result<Expr> {
+    let Some(fill) = fills.iter().find(|fill| fill.column == column) else {
+        return Ok(lit(NULL).cast(data_type.clone()).alias(column));
+    };
+    // Cast up front, so a value of the wrong type fails before any input
+    // is read; the single value is broadcast like a scalar literal
+    let value = Series::new(column, [fill.value.as_str()])
+        .strict_cast(data_type)
+        .ok()
+        .filter(|value| value.null_count() == 0)
+        .context(format!("Fill value {:?} of column {} is not a valid {}", fill.value, column, data_type))?;
+    Ok(lit(value).first().alias(column))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use crate::consolidator::{consolidate_with_options, ConsolidateOptions};
+    use crate::formats::ReadOptions;
+
+    #[test]
+    fn test_fill_missing_columns() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let old = temp_dir.path().join("old.parquet");
+        let new = temp_dir.path().join("new.parquet");
+        let empty = temp_dir.path().join("empty.parquet");
+        let mut df = df!("id" => [1i64, 2])?;
+        ParquetWriter::new(File::create(&old)?).finish(&mut df)?;
+        ParquetWriter::new(File::create(&empty)?).finish(&mut df.slice(0, 0))?;
+        let mut df = df!("id" => [3i64], "status" => [Some("shipped")], "score" => [Some(7i32)])?;
+        ParquetWriter::new(File::create(&new)?).finish(&mut df)?;
+
+        let output = temp_dir.path().join("out.parquet");
+        let read = ReadOptions {
+            union_columns: true,
+            fill_missing: vec!["status=unknown".parse()?, "score=0".parse()?],
+            ..Default::default()
+        };
+        let options = ConsolidateOptions { read, ..Default::default() };
+        consolidate_with_options(&[old.clone(), empty, new.clone()], &output, &options)?;
+        let df = ParquetReader::new(File::open(&output)?).finish()?;
+        let status: Vec<Option<&str>> = df.column("status")?.utf8()?.into_iter().collect();
+        assert_eq!(status, [Some("unknown"), Some("unknown"), Some("shipped")]);
+        let score: Vec<Option<i32>> = df.column("score")?.i32()?.into_iter().collect();
+        assert_eq!(score, [Some(0), Some(0), Some(7)]);
+
+        let read = ReadOptions { union_columns: true, fill_missing: vec!["score=high".parse()?], ..Default::default() };
+        let options = ConsolidateOptions { read, ..Default::default() };
+        let err = consolidate_with_options(&[old, new], &output, &options).unwrap_err();
+        assert!(format!("{:#}", err).contains("Fill value \"high\" of column score is not a valid i32"));
+
+        assert!("=0".parse::<ColumnFill>().is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,207 @@
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::str::FromStr;
+use anyhow::{Result, Context};
+use parquet::format::{FileMetaData, KeyValue};
+use parquet::thrift::{TCompactOutputProtocol, TSerializable};
+use thrift::protocol::TCompactInputProtocol;
+
+pub(crate) const MAGIC: &[u8] = b"PAR1";
+
+/// Length of the trailer: the footer length followed by the magic bytes
+const TRAILER_LEN: usize = 8;
+
+/// A change to the key-value metadata in a parquet footer
+#[derive(Debug, Clone, PartialEq)]
+pub enum MetadataEdit {
+    /// Add the key, or replace its value
+    Set { key: String, value: String },
+    Remove { key: String },
+}
+
+impl MetadataEdit {
+    fn apply(&self, entries: &mut Vec<KeyValue>) {
+        match self {
+            MetadataEdit::Set { key, value } => match entries.iter_mut().find(|entry| &entry.key == key) {
+                Some(entry) => entry.value = Some(value.clone()),
+                None => entries.push(KeyValue::new(key.clone(), value.clone())),
+            },
+            MetadataEdit::Remove { key } => entries.retain(|entry| &entry.key != key),
+        }
+    }
+}
+
+/// Parses `key=value` into a [`MetadataEdit::Set`]
+impl FromStr for MetadataEdit {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (key, value) = s
+            .split_once('=')
+            .ok_or_else(|| anyhow::anyhow!("Expected key=value, got: {}", s))?;
+        if key.is_empty() {
+            anyhow::bail!("Metadata key must not be empty: {}", s);
+        }
+        Ok(MetadataEdit::Set { key: key.to_string(), value: value.to_string() })
+    }
+}
+
+/// Read the key-value metadata of a parquet file's footer, in stored order
+pub fn read_key_value_metadata(path: &Path) -> Result<Vec<(String, Option<String>)>> {
+    let (_, metadata) = read_footer(&mut File::open(path).context(format!("Failed to open {:?}", path))?)
+        .context(format!("Failed to read the footer of {:?}", path))?;
+    Ok(key_values(&metadata))
+}
+
+/// Apply `edits` in order to the key-value metadata of a parquet file
+///
+/// Only the footer changes: the data pages are copied as they are, with
+/// the edited footer after them, to a temporary file next to `path` that is
+/// then renamed over it. The original stays intact until the rename, so a
+/// run killed or out of disk space part way never leaves it without a
+/// footer. Returns the resulting metadata.
+pub fn edit_key_value_metadata(path: &Path, edits: &[MetadataEdit]) -> Result<Vec<(String, Option<String>)>> {
+    let mut file = File::open(path).context(format!("Failed to open {:?}", path))?;
+    let (footer_start, mut metadata) = read_footer(&mut file).context(format!("Failed to read the footer of {:?}", path))?;
+
+    let mut entries = metadata.key_value_metadata.take().unwrap_or_default();
+    for edit in edits {
+        edit.apply(&mut entries);
+    }
+    metadata.key_value_metadata = (!entries.is_empty()).then_some(entries);
+
+    let mut footer = Vec::new();
+    write_footer(&mut footer, &metadata)?;
+
+    let file_name = path.file_name().ok_or_else(|| anyhow::anyhow!("Not a file: {:?}", path))?;
+    let staging = path.with_file_name(format!(".{}.tmp", file_name.to_string_lossy()));
+    let replaced = write_edited_copy(&mut file, footer_start, &footer, &staging).and_then(|_| {
+        fs::set_permissions(&staging, file.metadata()?.permissions())?;
+        #[cfg(feature = "fault-injection")]
+        crate::fault_injection::inject_fault(crate::fault_injection::FaultPoint::Commit, path)?;
+        fs::rename(&staging, path).context(format!("Failed to move the edited copy of {:?} into place", path))
+    });
+    if replaced.is_err() {
+        let _ = fs::remove_file(&staging);
+    }
+    replaced?;
+
+    Ok(key_values(&metadata))
+}
+
+/// Write the first `body_len` bytes of `file` followed by `footer` to
+/// `staging`, synced to disk
+fn write_edited_copy(file: &mut File, body_len: u64, footer: &[u8], staging: &Path) -> Result<()> {
+    #[cfg(feature = "fault-injection")]
+    crate::fault_injection::inject_fault(crate::fault_injection::FaultPoint::Write, staging)?;
+    let mut copy = File::create(staging).context(format!("Failed to create {:?}", staging))?;
+    file.seek(SeekFrom::Start(0))?;
+    let copied = io::copy(&mut Read::by_ref(file).take(body_len), &mut copy)?;
+    if copied != body_len {
+        anyhow::bail!("Expected {} bytes before the footer, read {}", body_len, copied);
+    }
+    copy.write_all(footer)?;
+    copy.sync_all().context(format!("Failed to write {:?}", staging))
+}
+
+/// Serialize `metadata` followed by the trailer, completing a parquet file
+/// whose body is already in `sink`
+pub(crate) fn write_footer(sink: &mut Vec<u8>, metadata: &FileMetaData) -> Result<()> {
+    let footer_start = sink.len();
+    metadata.write_to_out_protocol(&mut TCompactOutputProtocol::new(&mut *sink))?;
+    let footer_len = (sink.len() - footer_start) as u32;
+    sink.extend_from_slice(&footer_len.to_le_bytes());
+    sink.extend_from_slice(MAGIC);
+    Ok(())
+}
+
+/// Decode the footer, returning it with the offset where it starts
+pub(crate) fn read_footer(file: &mut File) -> Result<(u64, FileMetaData)> {
+    let file_len = file.seek(SeekFrom::End(0))?;
+    if file_len < (MAGIC.len() + TRAILER_LEN) as u64 {
+        anyhow::bail!("File is too short to be parquet");
+    }
+
+    let mut trailer = [0u8; TRAILER_LEN];
+    file.seek(SeekFrom::End(-(TRAILER_LEN as i64)))?;
+    file.read_exact(&mut trailer)?;
+    if &trailer[4..] != MAGIC {
+        anyhow::bail!("Missing parquet magic bytes; the footer may be encrypted or damaged");
+    }
+    let footer_len = u32::from_le_bytes(trailer[..4].try_into()?) as u64;
+    let footer_start = file_len
+        .checked_sub(TRAILER_LEN as u64 + footer_len)
+        .filter(|start| *start >= MAGIC.len() as u64)
+        .ok_or_else(|| anyhow::anyhow!("Footer length {} exceeds the file size", footer_len))?;
+
+    let mut footer = vec![0u8; footer_len as usize];
+    file.seek(SeekFrom::Start(footer_start))?;
+    file.read_exact(&mut footer)?;
+    let metadata = FileMetaData::read_from_in_protocol(&mut TCompactInputProtocol::new(footer.as_slice()))?;
+    Ok((footer_start, metadata))
+}
+
+fn key_values(metadata: &FileMetaData) -> Vec<(String, Option<String>)> {
+    metadata
+        .key_value_metadata
+        .iter()
+        .flatten()
+        .map(|entry| (entry.key.clone(), entry.value.clone()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+    use crate::test_utils::create_test_parquet_file;
+
+    #[test]
+    fn test_edit_key_value_metadata() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let path = temp_dir.path().join("data.parquet");
+        create_test_parquet_file(&path, 0, 10)?;
+        let original = read_key_value_metadata(&path)?;
+        let body_len = fs::metadata(&path)?.len();
+
+        let edits = vec![
+            "owner=etl".parse()?,
+            "source=s3://landing".parse()?,
+            "owner=analytics".parse()?,
+        ];
+        let entries = edit_key_value_metadata(&path, &edits)?;
+        assert_eq!(&entries[..original.len()], &original[..]);
+        assert_eq!(
+            &entries[original.len()..],
+            &[
+                ("owner".to_string(), Some("analytics".to_string())),
+                ("source".to_string(), Some("s3://landing".to_string())),
+            ]
+        );
+        assert_eq!(read_key_value_metadata(&path)?, entries);
+        assert!(fs::metadata(&path)?.len() > body_len);
+        assert!(!temp_dir.path().join(".data.parquet.tmp").exists());
+
+        edit_key_value_metadata(&path, &[MetadataEdit::Remove { key: "owner".to_string() }])?;
+        let reader = SerializedFileReader::new(File::open(&path)?)?;
+        assert_eq!(reader.metadata().file_metadata().num_rows(), 10);
+        let keys: Vec<&str> = reader
+            .metadata()
+            .file_metadata()
+            .key_value_metadata()
+            .unwrap()
+            .iter()
+            .map(|entry| entry.key.as_str())
+            .collect();
+        assert!(keys.contains(&"source"));
+        assert!(!keys.contains(&"owner"));
+
+        assert!("novalue".parse::<MetadataEdit>().is_err());
+        assert!("=x".parse::<MetadataEdit>().is_err());
+        fs::write(&path, b"PAR1 not parquet")?;
+        assert!(edit_key_value_metadata(&path, &edits).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,973 @@
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{BufReader, Cursor, Read, Seek};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+use anyhow::{Result, Context};
+use bytes::Bytes;
+use polars::prelude::*;
+use crate::archive::{read_archive_member, split_archive_path};
+use crate::columns::ColumnSelection;
+use crate::fill_missing::ColumnFill;
+use crate::row_group_selection::{selected_row_groups, RowGroupSelection};
+use crate::interop::record_batches_to_dataframe;
+use arrow::array::{
+    ArrayRef, RecordBatch, RecordBatchReader, TimestampMicrosecondArray, TimestampMillisecondArray,
+    TimestampNanosecondArray, TimestampSecondArray,
+};
+use arrow::datatypes::{DataType as ArrowDataType, Field as ArrowField, Schema as ArrowSchema, TimeUnit as ArrowTimeUnit};
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use parquet::basic::{ConvertedType, LogicalType, Type as PhysicalType};
+use parquet::column::reader::get_typed_column_reader;
+use parquet::data_type::{Int96, Int96Type};
+use parquet::file::metadata::ParquetMetaData;
+use parquet::file::properties::ReaderProperties;
+use parquet::file::reader::{ChunkReader, FileReader, RowGroupReader, SerializedFileReader};
+use parquet::file::serialized_reader::SerializedRowGroupReader;
+use parquet::schema::types::{ColumnDescriptor, SchemaDescriptor};
+
+/// Input file formats that can be folded into the consolidated output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum InputFormat {
+    Parquet,
+    Csv,
+    Ndjson,
+    #[cfg(feature = "xlsx")]
+    Xlsx,
+}
+
+impl InputFormat {
+    /// All formats supported by this build
+    pub fn all() -> Vec<InputFormat> {
+        vec![
+            InputFormat::Parquet,
+            InputFormat::Csv,
+            InputFormat::Ndjson,
+            #[cfg(feature = "xlsx")]
+            InputFormat::Xlsx,
+        ]
+    }
+
+    /// File extensions (lowercase, without the dot) recognised for this format
+    pub fn extensions(&self) -> &'static [&'static str] {
+        match self {
+            InputFormat::Parquet => &["parquet"],
+            InputFormat::Csv => &["csv"],
+            InputFormat::Ndjson => &["ndjson", "jsonl"],
+            #[cfg(feature = "xlsx")]
+            InputFormat::Xlsx => &["xlsx"],
+        }
+    }
+
+    /// Detect the format of a file from its extension
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use parquet_consolidator::InputFormat;
+    /// use std::path::Path;
+    ///
+    /// assert_eq!(InputFormat::from_path(Path::new("a.parquet")), Some(InputFormat::Parquet));
+    /// assert_eq!(InputFormat::from_path(Path::new("a.JSONL")), Some(InputFormat::Ndjson));
+    /// assert_eq!(InputFormat::from_path(Path::new("a.parquet.gz")), Some(InputFormat::Parquet));
+    /// assert_eq!(InputFormat::from_path(Path::new("a.txt")), None);
+    /// ```
+    pub fn from_path(path: &Path) -> Option<InputFormat> {
+        let stem;
+        let path = match FileCompression::from_path(path) {
+            Some(_) => {
+                stem = path.with_extension("");
+                &stem
+            }
+            None => path,
+        };
+        let ext = path.extension()?.to_str()?.to_lowercase();
+        InputFormat::all()
+            .into_iter()
+            .find(|format| format.extensions().contains(&ext.as_str()))
+    }
+
+    /// Lazily scan a file of this format
+    pub fn scan(&self, path: &Path) -> Result<LazyFrame> {
+        self.scan_with_options(path, &ReadOptions::default())
+    }
+
+    /// Lazily scan a file of this format using the given read options
+    ///
+    /// Archive members named as by [`crate::archive_members`] and
+    /// compressed files are read into memory in full; see
+    /// [`is_buffered_input`].
+    pub fn scan_with_options(&self, path: &Path, options: &ReadOptions) -> Result<LazyFrame> {
+        let scan = self.scan_all_columns(path, options)?;
+        options.columns.apply(scan).context(format!("Failed to select columns of {:?}", path))
+    }
+
+    fn scan_all_columns(&self, path: &Path, options: &ReadOptions) -> Result<LazyFrame> {
+        #[cfg(feature = "fault-injection")]
+        crate::fault_injection::inject_fault(crate::fault_injection::FaultPoint::Read, path)?;
+        if is_buffered_input(path) {
+            if options.row_groups.iter().any(|selection| selection.selects(path)) {
+                anyhow::bail!("Row groups can only be selected from uncompressed parquet files, not {:?}", path);
+            }
+            let data = read_buffered_input(path)?;
+            return Ok(self.read_bytes(data, options).context(format!("Failed to read {:?}", path))?.lazy());
+        }
+        match self {
+            InputFormat::Parquet => {
+                let selected = match options.row_groups.iter().any(|selection| selection.selects(path)) {
+                    true => selected_row_groups(&options.row_groups, path, row_group_count(path)?)?,
+                    false => None,
+                };
+                let coerce_int96 = options.coerce_int96.is_some() && !int96_columns(path)?.is_empty();
+                if selected.is_some() || coerce_int96 || (options.normalize_legacy_types && !legacy_type_columns(path)?.is_empty()) {
+                    let df = read_normalized_parquet(|| Ok(File::open(path)?), selected, options.coerce_int96)
+                        .context(format!("Failed to read {:?}", path))?;
+                    Ok(df.lazy())
+                } else {
+                    let args = ScanArgsParquet { parallel: options.parallel_strategy(), ..Default::default() };
+                    Ok(LazyFrame::scan_parquet(path, args)?)
+                }
+            }
+            InputFormat::Csv => {
+                Ok(LazyCsvReader::new(path).has_header(true).finish()?)
+            }
+            InputFormat::Ndjson => scan_ndjson(path),
+            #[cfg(feature = "xlsx")]
+            InputFormat::Xlsx => scan_xlsx(path),
+        }
+    }
+
+    /// Read a complete file of this format held in memory
+    pub fn read_bytes(&self, data: Vec<u8>, options: &ReadOptions) -> Result<DataFrame> {
+        match self {
+            InputFormat::Parquet => {
+                let data = Bytes::from(data);
+                let coerce_int96 = options.coerce_int96.is_some() && !int96_columns_of(data.clone())?.is_empty();
+                if coerce_int96 || (options.normalize_legacy_types && !legacy_type_columns_of(data.clone())?.is_empty()) {
+                    read_normalized_parquet(|| Ok(data.clone()), None, options.coerce_int96)
+                } else {
+                    Ok(ParquetReader::new(Cursor::new(data)).read_parallel(options.parallel_strategy()).finish()?)
+                }
+            }
+            InputFormat::Csv => Ok(CsvReader::new(Cursor::new(data)).has_header(true).finish()?),
+            InputFormat::Ndjson => read_ndjson(Cursor::new(data)),
+            #[cfg(feature = "xlsx")]
+            InputFormat::Xlsx => {
+                let workbook = calamine::open_workbook_auto_from_rs(Cursor::new(data))
+                    .context("Failed to open workbook")?;
+                read_xlsx(workbook)
+            }
+        }
+    }
+}
+
+/// Compression applied to a whole input file by the pipeline that produced
+/// it, as in `part-0.parquet.gz`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileCompression {
+    Gzip,
+    Zstd,
+}
+
+impl FileCompression {
+    /// Detect the compression of a file from its last extension
+    pub fn from_path(path: &Path) -> Option<FileCompression> {
+        match path.extension()?.to_str()?.to_lowercase().as_str() {
+            "gz" => Some(FileCompression::Gzip),
+            "zst" | "zstd" => Some(FileCompression::Zstd),
+            _ => None,
+        }
+    }
+
+    /// Decompress the whole of `reader`
+    pub fn decompress(&self, reader: impl Read) -> Result<Vec<u8>> {
+        let mut data = Vec::new();
+        match self {
+            FileCompression::Gzip => {
+                flate2::read::MultiGzDecoder::new(reader).read_to_end(&mut data).context("Invalid gzip data")?;
+            }
+            FileCompression::Zstd => {
+                zstd::stream::read::Decoder::new(reader)?.read_to_end(&mut data).context("Invalid zstd data")?;
+            }
+        }
+        Ok(data)
+    }
+}
+
+/// Whether `path` can't be read in place, being an archive member or a
+/// compressed file, and is buffered in memory instead
+///
+/// Parquet footers of such inputs can't be read on their own.
+pub fn is_buffered_input(path: &Path) -> bool {
+    FileCompression::from_path(path).is_some() || split_archive_path(path).is_some()
+}
+
+/// Read the contents of an input, decompressing it and extracting it from
+/// its archive as needed
+pub fn read_buffered_input(path: &Path) -> Result<Vec<u8>> {
+    let data = match split_archive_path(path) {
+        Some((archive, member)) => read_archive_member(archive, &member)?,
+        None => fs::read(path).context(format!("Failed to read {:?}", path))?,
+    };
+    match FileCompression::from_path(path) {
+        Some(compression) => compression.decompress(&data[..]).context(format!("Failed to decompress {:?}", path)),
+        None => Ok(data),
+    }
+}
+
+/// An input read from any byte source rather than a path, such as an
+/// in-memory buffer, an archive member or a database blob
+pub struct ReaderInput {
+    /// Label used in progress output and error messages
+    pub name: String,
+    pub format: InputFormat,
+    pub reader: Box<dyn Read + Send>,
+}
+
+impl ReaderInput {
+    pub fn new(name: impl Into<String>, format: InputFormat, reader: impl Read + Send + 'static) -> Self {
+        ReaderInput { name: name.into(), format, reader: Box::new(reader) }
+    }
+
+    /// Read the whole input into a frame
+    ///
+    /// The source is buffered in memory first, as parquet footers sit at
+    /// the end of the data.
+    pub fn read(mut self, options: &ReadOptions) -> Result<DataFrame> {
+        let mut data = Vec::new();
+        self.reader.read_to_end(&mut data).context(format!("Failed to read {}", self.name))?;
+        self.format.read_bytes(data, options).context(format!("Failed to read {}", self.name))
+    }
+}
+
+impl fmt::Debug for ReaderInput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReaderInput").field("name", &self.name).field("format", &self.format).finish()
+    }
+}
+
+impl fmt::Display for InputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            InputFormat::Parquet => "parquet",
+            InputFormat::Csv => "csv",
+            InputFormat::Ndjson => "ndjson",
+            #[cfg(feature = "xlsx")]
+            InputFormat::Xlsx => "xlsx",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for InputFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "parquet" => Ok(InputFormat::Parquet),
+            "csv" => Ok(InputFormat::Csv),
+            "ndjson" | "jsonl" => Ok(InputFormat::Ndjson),
+            #[cfg(feature = "xlsx")]
+            "xlsx" => Ok(InputFormat::Xlsx),
+            #[cfg(not(feature = "xlsx"))]
+            "xlsx" => anyhow::bail!("xlsx input requires building with the `xlsx` feature"),
+            other => {
+                let names: Vec<String> = InputFormat::all().iter().map(|f| f.to_string()).collect();
+                anyhow::bail!("Unknown input format: {} (expected one of {})", other, names.join(", "))
+            }
+        }
+    }
+}
+
+/// Time unit for timestamp columns
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimestampUnit {
+    Milliseconds,
+    Microseconds,
+    Nanoseconds,
+}
+
+impl From<TimestampUnit> for TimeUnit {
+    fn from(unit: TimestampUnit) -> Self {
+        match unit {
+            TimestampUnit::Milliseconds => TimeUnit::Milliseconds,
+            TimestampUnit::Microseconds => TimeUnit::Microseconds,
+            TimestampUnit::Nanoseconds => TimeUnit::Nanoseconds,
+        }
+    }
+}
+
+impl From<TimestampUnit> for arrow::datatypes::TimeUnit {
+    fn from(unit: TimestampUnit) -> Self {
+        match unit {
+            TimestampUnit::Milliseconds => arrow::datatypes::TimeUnit::Millisecond,
+            TimestampUnit::Microseconds => arrow::datatypes::TimeUnit::Microsecond,
+            TimestampUnit::Nanoseconds => arrow::datatypes::TimeUnit::Nanosecond,
+        }
+    }
+}
+
+impl FromStr for TimestampUnit {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "ms" => Ok(TimestampUnit::Milliseconds),
+            "us" => Ok(TimestampUnit::Microseconds),
+            "ns" => Ok(TimestampUnit::Nanoseconds),
+            other => anyhow::bail!("Unknown time unit: {} (expected ms, us or ns)", other),
+        }
+    }
+}
+
+/// Options controlling how input files are read
+#[derive(Debug, Clone, Default)]
+pub struct ReadOptions {
+    /// Unit that legacy INT96 timestamp columns are decoded to. When unset
+    /// they keep the reader's default nanosecond representation, which
+    /// wraps around for timestamps before 1677 or after 2262
+    pub coerce_int96: Option<TimestampUnit>,
+    /// Read parquet inputs with columns in [`legacy_type_columns`] through
+    /// the arrow reader, so they keep their decimal, string and integer
+    /// types instead of coming out as binary or floats, or failing to load.
+    /// Decimals only stay decimals while [`DECIMAL_ACTIVATION_VAR`] is set
+    /// to `1`; otherwise polars converts them, like every decimal it reads,
+    /// to floats
+    pub normalize_legacy_types: bool,
+    /// Top-level columns to read; all of them by default
+    pub columns: ColumnSelection,
+    /// Stack inputs with different columns into the union of their
+    /// columns, as null where an input lacks them; otherwise every input
+    /// must have the same columns in the same order
+    pub union_columns: bool,
+    /// With `union_columns`, values of columns filled in for inputs
+    /// lacking them, instead of null
+    pub fill_missing: Vec<ColumnFill>,
+    /// Decode the row groups of each parquet input in parallel, rather
+    /// than letting polars parallelize over columns, as it does for an
+    /// input with no more row groups than columns and threads; see
+    /// [`prefers_row_group_parallelism`]
+    pub row_group_parallel: bool,
+    /// Read only these row groups of the parquet inputs they name, through
+    /// the arrow reader as with `normalize_legacy_types`; other inputs are
+    /// read in full
+    pub row_groups: Vec<RowGroupSelection>,
+}
+
+impl ReadOptions {
+    fn parallel_strategy(&self) -> ParallelStrategy {
+        match self.row_group_parallel {
+            true => ParallelStrategy::RowGroups,
+            false => ParallelStrategy::Auto,
+        }
+    }
+}
+
+/// Whether the inputs are a few large files whose row groups are better
+/// decoded in parallel within each file
+///
+/// That is when there are fewer inputs than `threads`, so decoding one
+/// input per thread leaves threads idle, and the parquet inputs hold at
+/// least as many row groups as there are threads. Only the footers of the
+/// parquet inputs are read; other inputs have no row groups.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs::File;
+/// use polars::prelude::*;
+/// use parquet_consolidator::prefers_row_group_parallelism;
+/// use tempfile::TempDir;
+///
+/// let temp_dir = TempDir::new().unwrap();
+/// let input = temp_dir.path().join("big.parquet");
+/// let mut df = df!("id" => (0..1000).collect::<Vec<i32>>()).unwrap();
+/// ParquetWriter::new(File::create(&input).unwrap()).with_row_group_size(Some(100)).finish(&mut df).unwrap();
+///
+/// assert!(prefers_row_group_parallelism(&[input.clone()], 8).unwrap());
+/// assert!(!prefers_row_group_parallelism(&[input], 16).unwrap());
+/// ```
+pub fn prefers_row_group_parallelism(input_files: &[std::path::PathBuf], threads: usize) -> Result<bool> {
+    if input_files.len() >= threads {
+        return Ok(false);
+    }
+    let mut row_groups = 0;
+    for path in input_files {
+        if InputFormat::from_path(path).unwrap_or(InputFormat::Parquet) != InputFormat::Parquet || is_buffered_input(path) {
+            continue;
+        }
+        row_groups += row_group_count(path)?;
+    }
+    Ok(row_groups >= threads)
+}
+
+/// Number of row groups in the footer of the parquet file at `path`
+fn row_group_count(path: &Path) -> Result<usize> {
+    let reader = SerializedFileReader::new(File::open(path)?)
+        .context(format!("Failed to read parquet footer of {:?}", path))?;
+    Ok(reader.metadata().num_row_groups())
+}
+
+/// Environment variable under which polars keeps decimal columns as
+/// decimals instead of converting them to 64-bit floats
+pub const DECIMAL_ACTIVATION_VAR: &str = "POLARS_ACTIVATE_DECIMAL";
+
+/// Names of the top-level columns stored as legacy INT96 timestamps
+pub fn int96_columns(path: &Path) -> Result<Vec<String>> {
+    int96_columns_of(File::open(path)?).context(format!("Failed to read parquet footer of {:?}", path))
+}
+
+fn int96_columns_of<R: ChunkReader + 'static>(data: R) -> Result<Vec<String>> {
+    let reader = SerializedFileReader::new(data)?;
+    let schema = reader.metadata().file_metadata().schema_descr_ptr();
+
+    Ok(schema
+        .columns()
+        .iter()
+        .filter(|column| column.physical_type() == PhysicalType::INT96 && column.path().parts().len() == 1)
+        .map(|column| column.name().to_string())
+        .collect())
+}
+
+/// Names of the top-level columns whose type annotation the default reader
+/// can't map: decimals stored as variable-length byte arrays (read as
+/// binary) or as fixed-length arrays above 18 digits (read as floats),
+/// `ENUM` and `JSON` strings (read as binary) and 8- and 16-bit integers
+/// (not loadable at all)
+///
+/// Such files are mostly written by old Hive and Impala versions, which
+/// annotate columns with the legacy converted types only. Dates stored as
+/// `INT32` days and narrower fixed-length decimals are read correctly
+/// either way.
+pub fn legacy_type_columns(path: &Path) -> Result<Vec<String>> {
+    legacy_type_columns_of(File::open(path)?).context(format!("Failed to read parquet footer of {:?}", path))
+}
+
+fn legacy_type_columns_of<R: ChunkReader + 'static>(data: R) -> Result<Vec<String>> {
+    let reader = SerializedFileReader::new(data)?;
+    let schema = reader.metadata().file_metadata().schema_descr_ptr();
+
+    Ok(schema
+        .columns()
+        .iter()
+        .filter(|column| column.path().parts().len() == 1)
+        .filter(|column| {
+            let decimal = column.converted_type() == ConvertedType::DECIMAL
+                || matches!(column.logical_type(), Some(LogicalType::Decimal { .. }));
+            let small_integer = matches!(
+                column.converted_type(),
+                ConvertedType::INT_8 | ConvertedType::INT_16 | ConvertedType::UINT_8 | ConvertedType::UINT_16
+            ) || matches!(column.logical_type(), Some(LogicalType::Integer { bit_width: 8 | 16, .. }));
+            match column.physical_type() {
+                PhysicalType::BYTE_ARRAY => decimal || is_text_annotated(column),
+                PhysicalType::FIXED_LEN_BYTE_ARRAY => decimal && column.type_precision() > 18,
+                _ => small_integer,
+            }
+        })
+        .map(|column| column.name().to_string())
+        .collect())
+}
+
+fn is_text_annotated(column: &ColumnDescriptor) -> bool {
+    matches!(column.converted_type(), ConvertedType::ENUM | ConvertedType::JSON)
+        || matches!(column.logical_type(), Some(LogicalType::Enum | LogicalType::Json))
+}
+
+/// Read a parquet file, or only `row_groups` of it, through the arrow
+/// reader, which maps legacy decimal annotations, widening 8- and 16-bit
+/// integers polars has no type for and reading `ENUM` and `JSON` columns
+/// as strings. INT96 columns are decoded straight to `int96_unit` when it
+/// is set. `open` is called once per reader that needs the file
+fn read_normalized_parquet<R: ChunkReader + 'static>(
+    open: impl Fn() -> Result<R>,
+    row_groups: Option<Vec<usize>>,
+    int96_unit: Option<TimestampUnit>,
+) -> Result<DataFrame> {
+    let mut builder = ParquetRecordBatchReaderBuilder::try_new(open()?)?;
+    let int96 = match int96_unit {
+        Some(unit) => {
+            let row_groups = row_groups.clone().unwrap_or_else(|| (0..builder.metadata().num_row_groups()).collect());
+            let data = Arc::new(open()?);
+            builder
+                .schema()
+                .fields()
+                .iter()
+                .enumerate()
+                .filter(|(_, field)| field.data_type() == &ArrowDataType::Timestamp(ArrowTimeUnit::Nanosecond, None))
+                .filter(|(_, field)| is_int96_column(builder.parquet_schema(), field.name()))
+                .map(|(index, field)| {
+                    let decoded = decode_int96_column(&data, builder.metadata(), field.name(), &row_groups, unit.into())?;
+                    Ok((index, decoded))
+                })
+                .collect::<Result<Vec<_>>>()?
+        }
+        None => Vec::new(),
+    };
+    if let Some(row_groups) = row_groups {
+        builder = builder.with_row_groups(row_groups);
+    }
+    let text_columns: Vec<String> = builder
+        .parquet_schema()
+        .columns()
+        .iter()
+        .filter(|column| column.path().parts().len() == 1 && is_text_annotated(column))
+        .map(|column| column.name().to_string())
+        .collect();
+    let reader = builder.build()?;
+    let fields: Vec<ArrowField> = reader
+        .schema()
+        .fields()
+        .iter()
+        .enumerate()
+        .map(|(index, field)| {
+            let widened = match int96.iter().find(|(int96_index, _)| *int96_index == index) {
+                Some((_, decoded)) => decoded.data_type().clone(),
+                None => match field.data_type() {
+                    ArrowDataType::Int8 | ArrowDataType::Int16 => ArrowDataType::Int32,
+                    ArrowDataType::UInt8 | ArrowDataType::UInt16 => ArrowDataType::UInt32,
+                    ArrowDataType::Binary if text_columns.contains(field.name()) => ArrowDataType::Utf8,
+                    other => other.clone(),
+                },
+            };
+            field.as_ref().clone().with_data_type(widened)
+        })
+        .collect();
+    let schema = Arc::new(ArrowSchema::new(fields));
+
+    let mut offset = 0;
+    let batches = reader
+        .map(|batch| {
+            let batch = splice_columns(&batch?, &int96, offset)?;
+            offset += batch.num_rows();
+            let columns = batch
+                .columns()
+                .iter()
+                .zip(schema.fields())
+                .map(|(column, field)| arrow::compute::cast(column, field.data_type()))
+                .collect::<std::result::Result<Vec<_>, _>>()?;
+            Ok(RecordBatch::try_new(schema.clone(), columns)?)
+        })
+        .collect::<Result<Vec<_>>>()
+        .context("Failed to read records")?;
+
+    record_batches_to_dataframe(&schema, &batches)
+}
+
+pub(crate) fn is_int96_column(schema: &SchemaDescriptor, name: &str) -> bool {
+    schema
+        .columns()
+        .iter()
+        .any(|column| column.path().parts() == [name] && column.physical_type() == PhysicalType::INT96)
+}
+
+/// Decode the top-level INT96 column `name` of the given row groups
+/// straight to `unit`
+///
+/// The arrow reader only decodes INT96 to nanoseconds, which wraps around
+/// outside 1677-2262, so a cast to a coarser unit afterwards can't recover
+/// older or later timestamps. Decoding from the stored julian day and
+/// nanoseconds of the day keeps them.
+pub(crate) fn decode_int96_column<R: ChunkReader + 'static>(
+    data: &Arc<R>,
+    metadata: &ParquetMetaData,
+    name: &str,
+    row_groups: &[usize],
+    unit: ArrowTimeUnit,
+) -> Result<ArrayRef> {
+    let schema = metadata.file_metadata().schema_descr();
+    let index = (0..schema.num_columns())
+        .find(|index| schema.column(*index).path().parts() == [name])
+        .ok_or_else(|| anyhow::anyhow!("No INT96 column named {}", name))?;
+    let max_def_level = schema.column(index).max_def_level();
+    let props = Arc::new(ReaderProperties::builder().build());
+
+    let mut timestamps: Vec<Option<i64>> = Vec::new();
+    for &row_group in row_groups {
+        let row_group_metadata = metadata.row_group(row_group);
+        let rows = row_group_metadata.num_rows() as usize;
+        let row_group_reader = SerializedRowGroupReader::new(data.clone(), row_group_metadata, None, props.clone())?;
+        let mut column = get_typed_column_reader::<Int96Type>(row_group_reader.get_column_reader(index)?);
+        let mut values = Vec::with_capacity(rows);
+        let mut levels = Vec::with_capacity(rows);
+        column.read_records(rows, (max_def_level > 0).then_some(&mut levels), None, &mut values)?;
+
+        let values = values
+            .iter()
+            .map(|value| {
+                int96_in_unit(value, unit).ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "INT96 value {:?} in column {} of row group {} is out of range for {:?} timestamps",
+                        value.data(),
+                        name,
+                        row_group,
+                        unit
+                    )
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let mut values = values.into_iter();
+        if max_def_level > 0 {
+            timestamps.extend(levels.iter().map(|level| if *level == max_def_level { values.next() } else { None }));
+        } else {
+            timestamps.extend(values.map(Some));
+        }
+    }
+
+    Ok(match unit {
+        ArrowTimeUnit::Second => Arc::new(TimestampSecondArray::from(timestamps)),
+        ArrowTimeUnit::Millisecond => Arc::new(TimestampMillisecondArray::from(timestamps)),
+        ArrowTimeUnit::Microsecond => Arc::new(TimestampMicrosecondArray::from(timestamps)),
+        ArrowTimeUnit::Nanosecond => Arc::new(TimestampNanosecondArray::from(timestamps)),
+    })
+}
+
+/// The timestamp an INT96 value holds, in `unit`, or `None` when it is
+/// damaged: the nanoseconds exceed a day, or the julian day is too far
+/// from the epoch for the unit
+fn int96_in_unit(value: &Int96, unit: ArrowTimeUnit) -> Option<i64> {
+    const NANOSECONDS_PER_DAY: i64 = 86_400_000_000_000;
+
+    let (seconds, nanoseconds) = value.to_seconds_and_nanos();
+    if !(0..NANOSECONDS_PER_DAY).contains(&nanoseconds) {
+        return None;
+    }
+    let per_second = match unit {
+        ArrowTimeUnit::Second => 1,
+        ArrowTimeUnit::Millisecond => 1_000,
+        ArrowTimeUnit::Microsecond => 1_000_000,
+        ArrowTimeUnit::Nanosecond => 1_000_000_000,
+    };
+    seconds.checked_mul(per_second)?.checked_add(nanoseconds / (1_000_000_000 / per_second))
+}
+
+/// Replace the columns at the given indices of `batch` with the matching
+/// rows of whole-file arrays, starting at row `offset`
+pub(crate) fn splice_columns(batch: &RecordBatch, replacements: &[(usize, ArrayRef)], offset: usize) -> Result<RecordBatch> {
+    if replacements.is_empty() {
+        return Ok(batch.clone());
+    }
+    let mut fields: Vec<ArrowField> = batch.schema().fields().iter().map(|field| field.as_ref().clone()).collect();
+    let mut columns = batch.columns().to_vec();
+    for (index, array) in replacements {
+        columns[*index] = array.slice(offset, batch.num_rows());
+        fields[*index] = fields[*index].clone().with_data_type(array.data_type().clone());
+    }
+    let schema = ArrowSchema::new_with_metadata(fields, batch.schema().metadata().clone());
+    Ok(RecordBatch::try_new(Arc::new(schema), columns)?)
+}
+
+/// Scan a newline-delimited JSON file
+///
+/// The schema is inferred from the file contents before reading.
+fn scan_ndjson(path: &Path) -> Result<LazyFrame> {
+    Ok(read_ndjson(File::open(path)?)?.lazy())
+}
+
+fn read_ndjson<R: Read + Seek>(source: R) -> Result<DataFrame> {
+    let mut reader = BufReader::new(source);
+    let (schema, _) = arrow::json::reader::infer_json_schema_from_seekable(&mut reader, None)
+        .context("Failed to infer schema")?;
+    let schema = Arc::new(schema);
+
+    let batches = arrow::json::ReaderBuilder::new(schema.clone())
+        .build(reader)?
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .context("Failed to read records")?;
+
+    record_batches_to_dataframe(&schema, &batches)
+}
+
+/// Read the first worksheet of an Excel workbook
+///
+/// The first row is used as the header. Each column is typed from its
+/// non-empty cells: all booleans, all integers, all numbers or all dates
+/// keep that type, anything mixed falls back to strings.
+#[cfg(feature = "xlsx")]
+fn scan_xlsx(path: &Path) -> Result<LazyFrame> {
+    let workbook = calamine::open_workbook_auto(path)
+        .context(format!("Failed to open workbook {:?}", path))?;
+    Ok(read_xlsx(workbook)?.lazy())
+}
+
+#[cfg(feature = "xlsx")]
+fn read_xlsx<RS: Read + Seek>(mut workbook: calamine::Sheets<RS>) -> Result<DataFrame> {
+    use calamine::{Data, Reader};
+
+    let range = workbook
+        .worksheet_range_at(0)
+        .ok_or_else(|| anyhow::anyhow!("Workbook has no worksheets"))??;
+
+    let mut rows = range.rows();
+    let header: Vec<String> = match rows.next() {
+        Some(cells) => cells
+            .iter()
+            .enumerate()
+            .map(|(i, cell)| match cell {
+                Data::Empty => format!("column_{}", i),
+                other => other.to_string(),
+            })
+            .collect(),
+        None => return Ok(DataFrame::empty()),
+    };
+    let rows: Vec<&[Data]> = rows.collect();
+
+    let columns = header
+        .iter()
+        .enumerate()
+        .map(|(i, name)| {
+            let cells: Vec<&Data> = rows.iter().map(|row| row.get(i).unwrap_or(&Data::Empty)).collect();
+            xlsx_column_to_series(name, &cells)
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(DataFrame::new(columns)?)
+}
+
+#[cfg(feature = "xlsx")]
+fn xlsx_column_to_series(name: &str, cells: &[&calamine::Data]) -> Result<Series> {
+    use calamine::Data;
+
+    // Excel serial dates count days from 1899-12-30; the unix epoch is day 25569
+    const EXCEL_EPOCH_OFFSET_DAYS: f64 = 25569.0;
+    const MILLIS_PER_DAY: f64 = 86_400_000.0;
+
+    let values: Vec<&Data> = cells.iter().copied().filter(|c| !matches!(c, Data::Empty)).collect();
+    let all = |f: fn(&Data) -> bool| !values.is_empty() && values.iter().all(|c| f(c));
+
+    let series = if all(|c| matches!(c, Data::Bool(_))) {
+        let data: Vec<Option<bool>> = cells.iter().map(|c| match c {
+            Data::Bool(b) => Some(*b),
+            _ => None,
+        }).collect();
+        Series::new(name, data)
+    } else if all(|c| matches!(c, Data::Int(_)) || matches!(c, Data::Float(f) if f.fract() == 0.0)) {
+        let data: Vec<Option<i64>> = cells.iter().map(|c| match c {
+            Data::Int(i) => Some(*i),
+            Data::Float(f) => Some(*f as i64),
+            _ => None,
+        }).collect();
+        Series::new(name, data)
+    } else if all(|c| matches!(c, Data::Int(_) | Data::Float(_))) {
+        let data: Vec<Option<f64>> = cells.iter().map(|c| match c {
+            Data::Int(i) => Some(*i as f64),
+            Data::Float(f) => Some(*f),
+            _ => None,
+        }).collect();
+        Series::new(name, data)
+    } else if all(|c| matches!(c, Data::DateTime(_))) {
+        let data: Vec<Option<i64>> = cells.iter().map(|c| match c {
+            Data::DateTime(dt) => Some(((dt.as_f64() - EXCEL_EPOCH_OFFSET_DAYS) * MILLIS_PER_DAY).round() as i64),
+            _ => None,
+        }).collect();
+        Series::new(name, data).cast(&DataType::Datetime(TimeUnit::Milliseconds, None))?
+    } else {
+        let data: Vec<Option<String>> = cells.iter().map(|c| match c {
+            Data::Empty => None,
+            other => Some(other.to_string()),
+        }).collect();
+        Series::new(name, data)
+    };
+
+    Ok(series)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_input_format_from_str() {
+        assert_eq!("parquet".parse::<InputFormat>().unwrap(), InputFormat::Parquet);
+        assert_eq!("CSV".parse::<InputFormat>().unwrap(), InputFormat::Csv);
+        assert_eq!("jsonl".parse::<InputFormat>().unwrap(), InputFormat::Ndjson);
+        assert!("xml".parse::<InputFormat>().is_err());
+    }
+
+    #[test]
+    fn test_scan_csv_and_ndjson() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let csv_file = temp_dir.path().join("data.csv");
+        let ndjson_file = temp_dir.path().join("data.ndjson");
+
+        std::fs::write(&csv_file, "id,name\n1,a\n2,b\n")?;
+        std::fs::write(&ndjson_file, "{\"id\": 3, \"name\": \"c\"}\n{\"id\": 4, \"name\": \"d\"}\n")?;
+
+        let csv_df = InputFormat::Csv.scan(&csv_file)?.collect()?;
+        assert_eq!(csv_df.height(), 2);
+
+        let ndjson_df = InputFormat::Ndjson.scan(&ndjson_file)?.collect()?;
+        assert_eq!(ndjson_df.height(), 2);
+        assert_eq!(ndjson_df.get_column_names(), vec!["id", "name"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_scan_coerces_int96() -> Result<()> {
+        use crate::test_utils::create_test_int96_parquet_file;
+
+        let temp_dir = TempDir::new()?;
+        let int96_file = temp_dir.path().join("legacy.parquet");
+        create_test_int96_parquet_file(&int96_file, 0, 3)?;
+
+        assert_eq!(int96_columns(&int96_file)?, vec!["ts"]);
+
+        let options = ReadOptions { coerce_int96: Some(TimestampUnit::Microseconds), ..Default::default() };
+        let df = InputFormat::Parquet.scan_with_options(&int96_file, &options)?.collect()?;
+        assert_eq!(df.column("ts")?.dtype(), &DataType::Datetime(TimeUnit::Microseconds, None));
+
+        // One day after the epoch, in microseconds
+        let ts = df.column("ts")?.cast(&DataType::Int64)?;
+        assert_eq!(ts.i64()?.get(1), Some(86_400_000_000));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_scan_coerces_int96_outside_nanosecond_range() -> Result<()> {
+        use crate::test_utils::create_test_int96_parquet_file;
+
+        // 200,000 days before the epoch falls in 1422, which nanoseconds
+        // since the epoch can't hold
+        let temp_dir = TempDir::new()?;
+        let int96_file = temp_dir.path().join("legacy.parquet");
+        create_test_int96_parquet_file(&int96_file, -200_000, -199_998)?;
+        let expected = Some(-200_000 * 86_400_000_000);
+
+        let options = ReadOptions { coerce_int96: Some(TimestampUnit::Microseconds), ..Default::default() };
+        let df = InputFormat::Parquet.scan_with_options(&int96_file, &options)?.collect()?;
+        assert_eq!(df.column("ts")?.cast(&DataType::Int64)?.i64()?.get(0), expected);
+
+        let df = InputFormat::Parquet.read_bytes(fs::read(&int96_file)?, &options)?;
+        assert_eq!(df.column("ts")?.dtype(), &DataType::Datetime(TimeUnit::Microseconds, None));
+        assert_eq!(df.column("ts")?.cast(&DataType::Int64)?.i64()?.get(0), expected);
+
+        // A julian day too far out for microseconds fails instead of wrapping
+        create_test_int96_parquet_file(&int96_file, 200_000_000, 200_000_001)?;
+        let err = InputFormat::Parquet.read_bytes(fs::read(&int96_file)?, &options).unwrap_err();
+        assert!(format!("{:#}", err).contains("in column ts of row group 0 is out of range"), "{:#}", err);
+        assert!(InputFormat::Parquet.scan_with_options(&int96_file, &options).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_scan_normalizes_legacy_types() -> Result<()> {
+        use crate::test_utils::create_test_legacy_parquet_file;
+
+        let temp_dir = TempDir::new()?;
+        let legacy_file = temp_dir.path().join("legacy.parquet");
+        create_test_legacy_parquet_file(&legacy_file, 0, 3)?;
+        assert_eq!(legacy_type_columns(&legacy_file)?, vec!["amount", "total", "status", "flags"]);
+
+        let options = ReadOptions { normalize_legacy_types: true, ..Default::default() };
+        let df = InputFormat::Parquet.scan_with_options(&legacy_file, &options)?.collect()?;
+        assert_eq!(df.column("day")?.dtype(), &DataType::Date);
+        // Decimals are floats unless polars' decimal support is switched on
+        let amount = df.column("amount")?.cast(&DataType::Float64)?;
+        assert_eq!(amount.f64()?.get(2), Some(2.05));
+        let total = df.column("total")?.cast(&DataType::Float64)?;
+        assert_eq!(total.f64()?.get(2), Some(2.0));
+        assert_eq!(df.column("status")?.dtype(), &DataType::Utf8);
+        assert_eq!(df.column("flags")?.dtype(), &DataType::Int32);
+
+        let buffered = InputFormat::Parquet.read_bytes(std::fs::read(&legacy_file)?, &options)?;
+        assert_eq!(buffered.schema(), df.schema());
+        assert_eq!(buffered.column("total")?.cast(&DataType::Float64)?.f64()?.get(2), Some(2.0));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_scan_compressed_files() -> Result<()> {
+        use std::io::Write;
+        use crate::test_utils::create_test_parquet_file;
+
+        let temp_dir = TempDir::new()?;
+        let plain = temp_dir.path().join("plain.parquet");
+        create_test_parquet_file(&plain, 0, 10)?;
+        let parquet = fs::read(&plain)?;
+
+        let gzip_file = temp_dir.path().join("data.parquet.gz");
+        let mut gzip = flate2::write::GzEncoder::new(File::create(&gzip_file)?, flate2::Compression::default());
+        gzip.write_all(&parquet)?;
+        gzip.finish()?;
+        let zstd_file = temp_dir.path().join("data.parquet.zst");
+        fs::write(&zstd_file, zstd::encode_all(&parquet[..], 3)?)?;
+        let csv_file = temp_dir.path().join("data.csv.gz");
+        let mut gzip = flate2::write::GzEncoder::new(File::create(&csv_file)?, flate2::Compression::default());
+        gzip.write_all(b"id,name\n1,a\n")?;
+        gzip.finish()?;
+
+        let expected = InputFormat::Parquet.scan(&plain)?.collect()?;
+        for path in [&gzip_file, &zstd_file] {
+            assert!(is_buffered_input(path));
+            let format = InputFormat::from_path(path).unwrap();
+            assert_eq!(format.scan(path)?.collect()?, expected);
+        }
+        assert!(!is_buffered_input(&plain));
+        assert_eq!(InputFormat::from_path(&csv_file), Some(InputFormat::Csv));
+        assert_eq!(InputFormat::Csv.scan(&csv_file)?.collect()?.height(), 1);
+
+        // Data that isn't compressed is reported as such
+        let fake = temp_dir.path().join("fake.parquet.gz");
+        fs::copy(&plain, &fake)?;
+        let err = InputFormat::Parquet.scan(&fake).err().unwrap();
+        assert!(format!("{:#}", err).contains("Invalid gzip data"), "{:#}", err);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_row_group_parallel_scan() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let input = temp_dir.path().join("big.parquet");
+        let mut df = df!("id" => (0..1000).collect::<Vec<i32>>(), "name" => vec!["a"; 1000])?;
+        ParquetWriter::new(File::create(&input)?).with_row_group_size(Some(100)).finish(&mut df)?;
+        let small = temp_dir.path().join("small.parquet");
+        crate::test_utils::create_test_parquet_file(&small, 0, 10)?;
+
+        assert!(prefers_row_group_parallelism(std::slice::from_ref(&input), 4)?);
+        // As many inputs as threads already keep them busy
+        assert!(!prefers_row_group_parallelism(&[input.clone(), small.clone(), small.clone(), small.clone()], 4)?);
+        assert!(!prefers_row_group_parallelism(&[small], 4)?);
+
+        let options = ReadOptions { row_group_parallel: true, ..Default::default() };
+        assert_eq!(InputFormat::Parquet.scan_with_options(&input, &options)?.collect()?, df);
+
+        Ok(())
+    }
+
+    #[cfg(feature = "xlsx")]
+    #[test]
+    fn test_scan_xlsx_infers_types() -> Result<()> {
+        use rust_xlsxwriter::Workbook;
+
+        let temp_dir = TempDir::new()?;
+        let xlsx_file = temp_dir.path().join("data.xlsx");
+
+        let mut workbook = Workbook::new();
+        let sheet = workbook.add_worksheet();
+        sheet.write_row(0, 0, ["id", "name", "value", "active"])?;
+        sheet.write(1, 0, 1)?;
+        sheet.write(1, 1, "a")?;
+        sheet.write(1, 2, 1.5)?;
+        sheet.write(1, 3, true)?;
+        sheet.write(2, 0, 2)?;
+        sheet.write(2, 1, "b")?;
+        sheet.write(2, 2, 3)?;
+        workbook.save(&xlsx_file)?;
+
+        let df = InputFormat::Xlsx.scan(&xlsx_file)?.collect()?;
+        assert_eq!(df.height(), 2);
+        assert_eq!(df.column("id")?.dtype(), &DataType::Int64);
+        assert_eq!(df.column("name")?.dtype(), &DataType::Utf8);
+        assert_eq!(df.column("value")?.dtype(), &DataType::Float64);
+        assert_eq!(df.column("active")?.dtype(), &DataType::Boolean);
+        assert_eq!(df.column("active")?.null_count(), 1);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,393 @@
+use std::fmt;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+use anyhow::{Result, Context};
+use arrow::array::{
+    ArrayRef, BinaryArray, BooleanArray, Date32Array, Date64Array, Decimal128Array, Float32Array, Float64Array,
+    Int16Array, Int32Array, Int64Array, Int8Array, LargeStringArray, ListArray, RecordBatch, StringArray,
+    StructArray, TimestampMicrosecondArray, TimestampMillisecondArray, TimestampNanosecondArray,
+    TimestampSecondArray, UInt16Array, UInt32Array, UInt64Array, UInt8Array,
+};
+use arrow::buffer::{NullBuffer, OffsetBuffer};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use parquet::arrow::ArrowWriter;
+
+/// A small deterministic random number generator (SplitMix64)
+///
+/// The same seed produces the same sequence on every platform and build,
+/// which is what makes generated datasets reproducible.
+#[derive(Debug, Clone)]
+pub struct SeededRng {
+    state: u64,
+}
+
+impl SeededRng {
+    pub fn new(seed: u64) -> Self {
+        SeededRng { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A uniform value in `[0, 1)`
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// A uniform value in `[0, bound)`
+    pub fn below(&mut self, bound: u64) -> u64 {
+        ((self.next_u64() as u128 * bound as u128) >> 64) as u64
+    }
+
+    /// True with probability `ratio`
+    pub fn chance(&mut self, ratio: f64) -> bool {
+        ratio > 0.0 && self.next_f64() < ratio
+    }
+}
+
+/// Types a generated column can have
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnType {
+    Int32,
+    Int64,
+    Float64,
+    Boolean,
+    String,
+    Date,
+    Timestamp,
+}
+
+impl ColumnType {
+    pub fn data_type(&self) -> DataType {
+        match self {
+            ColumnType::Int32 => DataType::Int32,
+            ColumnType::Int64 => DataType::Int64,
+            ColumnType::Float64 => DataType::Float64,
+            ColumnType::Boolean => DataType::Boolean,
+            ColumnType::String => DataType::Utf8,
+            ColumnType::Date => DataType::Date32,
+            ColumnType::Timestamp => DataType::Timestamp(TimeUnit::Microsecond, None),
+        }
+    }
+}
+
+impl fmt::Display for ColumnType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ColumnType::Int32 => "int32",
+            ColumnType::Int64 => "int64",
+            ColumnType::Float64 => "float64",
+            ColumnType::Boolean => "bool",
+            ColumnType::String => "string",
+            ColumnType::Date => "date",
+            ColumnType::Timestamp => "timestamp",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for ColumnType {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "int32" | "int" => Ok(ColumnType::Int32),
+            "int64" | "long" => Ok(ColumnType::Int64),
+            "float64" | "double" => Ok(ColumnType::Float64),
+            "bool" | "boolean" => Ok(ColumnType::Boolean),
+            "string" | "utf8" => Ok(ColumnType::String),
+            "date" => Ok(ColumnType::Date),
+            "timestamp" => Ok(ColumnType::Timestamp),
+            other => anyhow::bail!(
+                "Unknown column type: {} (expected int32, int64, float64, bool, string, date or timestamp)",
+                other
+            ),
+        }
+    }
+}
+
+/// A generated column, given on the command line as `name:type`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColumnSpec {
+    pub name: String,
+    pub column_type: ColumnType,
+}
+
+impl FromStr for ColumnSpec {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (name, column_type) = s
+            .split_once(':')
+            .ok_or_else(|| anyhow::anyhow!("Invalid column {:?}, expected name:type", s))?;
+        if name.trim().is_empty() {
+            anyhow::bail!("Invalid column {:?}: the name is empty", s);
+        }
+        Ok(ColumnSpec { name: name.trim().to_string(), column_type: column_type.trim().parse()? })
+    }
+}
+
+/// What [`generate_dataset`] writes
+#[derive(Debug, Clone)]
+pub struct GenerateOptions {
+    pub files: usize,
+    /// Rows in each file
+    pub rows: usize,
+    pub columns: Vec<ColumnSpec>,
+    /// Files are spread over directories nested up to this deep
+    pub nesting_depth: usize,
+    /// Fraction of values in each column that are null
+    pub null_ratio: f64,
+    pub seed: u64,
+}
+
+impl Default for GenerateOptions {
+    fn default() -> Self {
+        GenerateOptions {
+            files: 5,
+            rows: 100,
+            columns: ["id:int32", "name:string", "value:float64"]
+                .iter()
+                .map(|spec| spec.parse().expect("valid default column"))
+                .collect(),
+            nesting_depth: 1,
+            null_ratio: 0.0,
+            seed: 0,
+        }
+    }
+}
+
+impl GenerateOptions {
+    /// Schema of every generated file; columns are nullable when nulls
+    /// are generated
+    pub fn schema(&self) -> SchemaRef {
+        let fields: Vec<Field> = self
+            .columns
+            .iter()
+            .map(|column| Field::new(&column.name, column.column_type.data_type(), self.null_ratio > 0.0))
+            .collect();
+        Arc::new(Schema::new(fields))
+    }
+}
+
+/// Random values of `data_type`, each null with probability `null_ratio`
+///
+/// Integers are uniform below one million (or the type's maximum), floats
+/// below one thousand, and dates and timestamps fall in 2020 to 2024.
+/// Decimals use every digit of their precision. Lists hold up to four
+/// elements and, like structs, are filled recursively; a nested field only
+/// gets nulls when it is nullable.
+pub fn random_array(data_type: &DataType, len: usize, null_ratio: f64, rng: &mut SeededRng) -> Result<ArrayRef> {
+    // 2020-01-01 and the five years after it, in days since the epoch
+    const FIRST_DAY: i64 = 18262;
+    const DAYS: u64 = 1827;
+    const MICROS_PER_DAY: i64 = 86_400_000_000;
+    const MAX_LIST_LENGTH: u64 = 4;
+
+    macro_rules! integers {
+        ($array:ty, $native:ty) => {
+            Arc::new(<$array>::from(random_values(len, null_ratio, rng, |rng| {
+                rng.below((<$native>::MAX as u64).min(1_000_000)) as $native
+            })))
+        };
+    }
+
+    let array: ArrayRef = match data_type {
+        DataType::Int8 => integers!(Int8Array, i8),
+        DataType::Int16 => integers!(Int16Array, i16),
+        DataType::Int32 => integers!(Int32Array, i32),
+        DataType::Int64 => integers!(Int64Array, i64),
+        DataType::UInt8 => integers!(UInt8Array, u8),
+        DataType::UInt16 => integers!(UInt16Array, u16),
+        DataType::UInt32 => integers!(UInt32Array, u32),
+        DataType::UInt64 => integers!(UInt64Array, u64),
+        DataType::Float32 => Arc::new(Float32Array::from(random_values(len, null_ratio, rng, |rng| {
+            rng.next_f64() as f32 * 1000.0
+        }))),
+        DataType::Float64 => Arc::new(Float64Array::from(random_values(len, null_ratio, rng, |rng| {
+            rng.next_f64() * 1000.0
+        }))),
+        DataType::Boolean => Arc::new(BooleanArray::from(random_values(len, null_ratio, rng, |rng| rng.below(2) == 1))),
+        DataType::Utf8 => Arc::new(StringArray::from(random_values(len, null_ratio, rng, |rng| {
+            format!("value_{}", rng.below(1_000_000))
+        }))),
+        DataType::LargeUtf8 => Arc::new(LargeStringArray::from(random_values(len, null_ratio, rng, |rng| {
+            format!("value_{}", rng.below(1_000_000))
+        }))),
+        DataType::Binary => Arc::new(BinaryArray::from_iter(random_values(len, null_ratio, rng, |rng| {
+            rng.next_u64().to_le_bytes()
+        }))),
+        DataType::Date32 => Arc::new(Date32Array::from(random_values(len, null_ratio, rng, |rng| {
+            (FIRST_DAY + rng.below(DAYS) as i64) as i32
+        }))),
+        DataType::Date64 => Arc::new(Date64Array::from(random_values(len, null_ratio, rng, |rng| {
+            (FIRST_DAY + rng.below(DAYS) as i64) * 86_400_000
+        }))),
+        DataType::Timestamp(unit, timezone) => {
+            let micros = random_values(len, null_ratio, rng, |rng| {
+                FIRST_DAY * MICROS_PER_DAY + rng.below(DAYS * MICROS_PER_DAY as u64) as i64
+            });
+            let values = micros.into_iter().map(|value| {
+                value.map(|micros| match unit {
+                    TimeUnit::Second => micros / 1_000_000,
+                    TimeUnit::Millisecond => micros / 1_000,
+                    TimeUnit::Microsecond => micros,
+                    TimeUnit::Nanosecond => micros * 1_000,
+                })
+            });
+            let values: Vec<Option<i64>> = values.collect();
+            let array: ArrayRef = match unit {
+                TimeUnit::Second => Arc::new(TimestampSecondArray::from(values).with_timezone_opt(timezone.clone())),
+                TimeUnit::Millisecond => {
+                    Arc::new(TimestampMillisecondArray::from(values).with_timezone_opt(timezone.clone()))
+                }
+                TimeUnit::Microsecond => {
+                    Arc::new(TimestampMicrosecondArray::from(values).with_timezone_opt(timezone.clone()))
+                }
+                TimeUnit::Nanosecond => {
+                    Arc::new(TimestampNanosecondArray::from(values).with_timezone_opt(timezone.clone()))
+                }
+            };
+            array
+        }
+        DataType::Decimal128(precision, scale) => {
+            let bound = 10u64.pow((*precision).min(18) as u32);
+            let values = random_values(len, null_ratio, rng, |rng| rng.below(bound) as i128);
+            Arc::new(Decimal128Array::from(values).with_precision_and_scale(*precision, *scale)?)
+        }
+        DataType::List(field) => {
+            let lengths = random_values(len, null_ratio, rng, |rng| rng.below(MAX_LIST_LENGTH + 1) as usize);
+            let nulls = NullBuffer::from_iter(lengths.iter().map(Option::is_some));
+            let offsets = OffsetBuffer::from_lengths(lengths.iter().map(|length| length.unwrap_or(0)));
+            let values = random_field_array(field, *offsets.last().unwrap_or(&0) as usize, null_ratio, rng)?;
+            Arc::new(ListArray::try_new(field.clone(), offsets, values, Some(nulls))?)
+        }
+        DataType::Struct(fields) => {
+            let nulls = NullBuffer::from_iter(random_values(len, null_ratio, rng, |_| ()).iter().map(Option::is_some));
+            let columns = fields
+                .iter()
+                .map(|field| random_field_array(field, len, null_ratio, rng))
+                .collect::<Result<Vec<_>>>()?;
+            Arc::new(StructArray::try_new(fields.clone(), columns, Some(nulls))?)
+        }
+        other => anyhow::bail!("Generating {} columns is not supported", other),
+    };
+    Ok(array)
+}
+
+/// Random values for `field`, with nulls only when it is nullable
+pub fn random_field_array(field: &Field, len: usize, null_ratio: f64, rng: &mut SeededRng) -> Result<ArrayRef> {
+    let null_ratio = if field.is_nullable() { null_ratio } else { 0.0 };
+    random_array(field.data_type(), len, null_ratio, rng)
+}
+
+/// A batch of `rows` random rows of `schema`; see [`random_array`]
+pub fn random_record_batch(schema: &SchemaRef, rows: usize, null_ratio: f64, rng: &mut SeededRng) -> Result<RecordBatch> {
+    let columns = schema
+        .fields()
+        .iter()
+        .map(|field| random_field_array(field, rows, null_ratio, rng))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(RecordBatch::try_new(schema.clone(), columns)?)
+}
+
+fn random_values<T>(
+    len: usize,
+    null_ratio: f64,
+    rng: &mut SeededRng,
+    mut generate: impl FnMut(&mut SeededRng) -> T,
+) -> Vec<Option<T>> {
+    (0..len).map(|_| if rng.chance(null_ratio) { None } else { Some(generate(rng)) }).collect()
+}
+
+/// Write a random dataset into `output_dir`, returning the files written
+///
+/// Files are named `file1.parquet`, `file2.parquet` and so on. File `n`
+/// goes `(n - 1) % (nesting_depth + 1)` directories deep, into
+/// `nested/`, `nested/nested/` and so on, so with a depth of one every
+/// other file lands in `nested/`. The same options and seed always produce
+/// the same data.
+pub fn generate_dataset(output_dir: &Path, options: &GenerateOptions) -> Result<Vec<PathBuf>> {
+    if options.columns.is_empty() {
+        anyhow::bail!("At least one column is required");
+    }
+    if !(0.0..=1.0).contains(&options.null_ratio) {
+        anyhow::bail!("The null ratio must be between 0 and 1, got {}", options.null_ratio);
+    }
+
+    let schema = options.schema();
+    let mut rng = SeededRng::new(options.seed);
+    let mut paths = Vec::new();
+    for index in 0..options.files {
+        let mut directory = output_dir.to_path_buf();
+        for _ in 0..index % (options.nesting_depth + 1) {
+            directory.push("nested");
+        }
+        fs::create_dir_all(&directory).context(format!("Failed to create {:?}", directory))?;
+        let path = directory.join(format!("file{}.parquet", index + 1));
+
+        let batch = random_record_batch(&schema, options.rows, options.null_ratio, &mut rng)?;
+        let mut writer = ArrowWriter::try_new(File::create(&path)?, schema.clone(), None)?;
+        writer.write(&batch)?;
+        writer.close()?;
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::Array;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    #[test]
+    fn test_generate_dataset() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let options = GenerateOptions {
+            files: 3,
+            rows: 200,
+            columns: "id:int64,name:string,day:date,at:timestamp,ok:bool"
+                .split(',')
+                .map(str::parse)
+                .collect::<Result<_>>()?,
+            nesting_depth: 2,
+            null_ratio: 0.25,
+            seed: 42,
+        };
+
+        let first = temp_dir.path().join("first");
+        let paths = generate_dataset(&first, &options)?;
+        assert_eq!(
+            paths,
+            vec![first.join("file1.parquet"), first.join("nested/file2.parquet"), first.join("nested/nested/file3.parquet")]
+        );
+
+        let batch = ParquetRecordBatchReaderBuilder::try_new(File::open(&paths[0])?)?.build()?.next().unwrap()?;
+        assert_eq!(batch.num_rows(), 200);
+        assert_eq!(batch.schema(), options.schema());
+        let nulls = batch.column(0).null_count();
+        assert!((20..80).contains(&nulls), "{} nulls", nulls);
+
+        // The same seed reproduces the same files
+        let second = temp_dir.path().join("second");
+        generate_dataset(&second, &options)?;
+        assert_eq!(fs::read(&paths[2])?, fs::read(second.join("nested/nested/file3.parquet"))?);
+        let third = temp_dir.path().join("third");
+        generate_dataset(&third, &GenerateOptions { seed: 7, ..options.clone() })?;
+        assert_ne!(fs::read(&paths[2])?, fs::read(third.join("nested/nested/file3.parquet"))?);
+
+        assert!("id".parse::<ColumnSpec>().is_err());
+        assert!("id:uuid".parse::<ColumnSpec>().is_err());
+        assert!(generate_dataset(&first, &GenerateOptions { null_ratio: 1.5, ..options }).is_err());
+
+        Ok(())
+    }
+}
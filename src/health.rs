@@ -0,0 +1,193 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use anyhow::{Result, Context};
+
+/// Path answering whether the service is alive
+pub const LIVENESS_PATH: &str = "/healthz";
+
+/// Path answering whether the service is ready
+pub const READINESS_PATH: &str = "/readyz";
+
+#[derive(Debug)]
+struct HealthInner {
+    ready: bool,
+    /// When the last pass without failures ended; the start of the
+    /// service until there is one
+    last_success: Instant,
+    passes: u64,
+}
+
+/// What a long-running service reports on its health endpoints
+///
+/// It is ready once [marked](HealthState::mark_ready) so, after its
+/// first pass over the datasets. It is alive unless a pass without
+/// failures is older than the maximum age, so a service whose every run
+/// fails, or whose loop is stuck, is restarted by its orchestrator.
+#[derive(Debug)]
+pub struct HealthState {
+    max_success_age: Option<Duration>,
+    inner: Mutex<HealthInner>,
+}
+
+impl HealthState {
+    pub fn new(max_success_age: Option<Duration>) -> Self {
+        HealthState {
+            max_success_age,
+            inner: Mutex::new(HealthInner { ready: false, last_success: Instant::now(), passes: 0 }),
+        }
+    }
+
+    pub fn mark_ready(&self) {
+        self.inner.lock().unwrap().ready = true;
+    }
+
+    /// Record the end of a pass over the datasets, which succeeded when
+    /// none of them failed
+    pub fn record_pass(&self, succeeded: bool) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.passes += 1;
+        if succeeded {
+            inner.last_success = Instant::now();
+        }
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.inner.lock().unwrap().ready
+    }
+
+    /// `Ok` while alive, or why not
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::{Duration, Instant};
+    /// use parquet_consolidator::HealthState;
+    ///
+    /// let health = HealthState::new(Some(Duration::from_secs(3600)));
+    /// assert!(health.liveness(Instant::now()).is_ok());
+    /// let err = health.liveness(Instant::now() + Duration::from_secs(7200)).unwrap_err();
+    /// assert!(err.starts_with("No successful run for 7200s"));
+    /// ```
+    pub fn liveness(&self, now: Instant) -> std::result::Result<(), String> {
+        let inner = self.inner.lock().unwrap();
+        let age = now.saturating_duration_since(inner.last_success);
+        match self.max_success_age {
+            Some(max_age) if age > max_age => Err(format!(
+                "No successful run for {}s (at most {}s allowed, {} passes so far)",
+                age.as_secs(),
+                max_age.as_secs(),
+                inner.passes
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// The status code and body answering a request for `path`
+    fn respond(&self, path: &str) -> (u16, String) {
+        match path {
+            LIVENESS_PATH => match self.liveness(Instant::now()) {
+                Ok(()) => (200, "ok".to_string()),
+                Err(reason) => (503, reason),
+            },
+            READINESS_PATH if self.is_ready() => (200, "ready".to_string()),
+            READINESS_PATH => (503, "The first pass over the datasets hasn't finished".to_string()),
+            _ => (404, format!("Unknown path {} (expected {} or {})", path, LIVENESS_PATH, READINESS_PATH)),
+        }
+    }
+}
+
+/// Serve the health endpoints on `addr`, e.g. `0.0.0.0:8080`, from a
+/// background thread for as long as the process runs
+///
+/// Only `GET` requests of the two paths are answered, with a plain text
+/// body; anything else is a 404. Returns the address bound, which tells
+/// the port picked for port 0.
+pub fn serve_health(addr: &str, state: Arc<HealthState>) -> Result<SocketAddr> {
+    let listener = TcpListener::bind(addr).context(format!("Failed to listen for health checks on {}", addr))?;
+    let local_addr = listener.local_addr()?;
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            if let Err(err) = answer(stream, &state) {
+                eprintln!("Warning: Failed to answer a health check: {:#}", err);
+            }
+        }
+    });
+    Ok(local_addr)
+}
+
+fn answer(stream: TcpStream, state: &HealthState) -> Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    let mut reader = BufReader::new(&stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // The headers are read and ignored, so the client sees its whole
+    // request consumed
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let (status, body) = match (parts.next(), parts.next()) {
+        (Some("GET"), Some(path)) => state.respond(path),
+        _ => (405, "Only GET is supported".to_string()),
+    };
+    let reason = match status {
+        200 => "OK",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        _ => "Service Unavailable",
+    };
+    let mut stream = &stream;
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}\n",
+        status,
+        reason,
+        body.len() + 1,
+        body
+    )?;
+    stream.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    fn get(addr: SocketAddr, path: &str) -> Result<String> {
+        let mut stream = TcpStream::connect(addr)?;
+        write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path)?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response)?;
+        Ok(response)
+    }
+
+    #[test]
+    fn test_serve_health() -> Result<()> {
+        let state = Arc::new(HealthState::new(Some(Duration::ZERO)));
+        let addr = serve_health("127.0.0.1:0", state.clone())?;
+
+        assert!(get(addr, READINESS_PATH)?.starts_with("HTTP/1.1 503"));
+        state.mark_ready();
+        assert!(get(addr, READINESS_PATH)?.starts_with("HTTP/1.1 200"));
+
+        // No run has succeeded within a zero age once any time passes
+        thread::sleep(Duration::from_millis(10));
+        let response = get(addr, LIVENESS_PATH)?;
+        assert!(response.starts_with("HTTP/1.1 503"));
+        assert!(response.contains("No successful run for 0s"));
+
+        let state = Arc::new(HealthState::new(None));
+        let addr = serve_health("127.0.0.1:0", state.clone())?;
+        state.record_pass(false);
+        assert!(get(addr, LIVENESS_PATH)?.ends_with("\r\n\r\nok\n"));
+        assert!(get(addr, "/metrics")?.starts_with("HTTP/1.1 404"));
+
+        Ok(())
+    }
+}
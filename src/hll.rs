@@ -0,0 +1,140 @@
+use anyhow::Result;
+use polars::prelude::*;
+use polars_core::export::ahash::RandomState;
+
+/// Register index bits of the sketches used for column statistics: 2^14
+/// one-byte registers, for a standard error of about 0.8%
+pub const HLL_PRECISION: u8 = 14;
+
+/// A HyperLogLog sketch estimating the number of distinct values seen
+///
+/// Memory use is fixed by the precision however many values are added,
+/// and sketches of the same precision can be merged, e.g. to combine the
+/// sketches of several files or row groups.
+///
+/// # Examples
+///
+/// ```
+/// use parquet_consolidator::HyperLogLog;
+///
+/// let mut sketch = HyperLogLog::new(12);
+/// for value in 0..10_000u64 {
+///     sketch.insert_hash(value);
+///     sketch.insert_hash(value);
+/// }
+/// let estimate = sketch.estimate();
+/// assert!((9_500..10_500).contains(&estimate));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HyperLogLog {
+    precision: u8,
+    registers: Vec<u8>,
+}
+
+impl HyperLogLog {
+    /// An empty sketch with `2^precision` registers, for precisions from 4 to 18
+    pub fn new(precision: u8) -> Self {
+        let precision = precision.clamp(4, 18);
+        HyperLogLog { precision, registers: vec![0; 1 << precision] }
+    }
+
+    /// Add a value by its 64-bit hash
+    ///
+    /// The hash is mixed again first, so hashes whose high bits are poorly
+    /// distributed, such as small integers, still spread over the registers.
+    pub fn insert_hash(&mut self, hash: u64) {
+        let hash = mix(hash);
+        let index = (hash >> (64 - self.precision)) as usize;
+        // A sentinel bit keeps the count of leading zeros within the hash
+        let rest = (hash << self.precision) | (1 << (self.precision - 1));
+        let rank = rest.leading_zeros() as u8 + 1;
+        self.registers[index] = self.registers[index].max(rank);
+    }
+
+    /// Add every value of a series, counting null as one value
+    ///
+    /// Fails for types polars can't hash, such as nested lists.
+    pub fn insert_series(&mut self, series: &Series) -> Result<()> {
+        let mut hashes = Vec::with_capacity(series.len());
+        // Fixed seeds keep estimates of the same data identical between runs
+        series.vec_hash(RandomState::with_seeds(0, 1, 2, 3), &mut hashes)?;
+        for hash in hashes {
+            self.insert_hash(hash);
+        }
+        Ok(())
+    }
+
+    /// Fold another sketch of the same precision into this one
+    pub fn merge(&mut self, other: &HyperLogLog) -> Result<()> {
+        if other.precision != self.precision {
+            anyhow::bail!("Cannot merge sketches of precision {} and {}", self.precision, other.precision);
+        }
+        for (register, other) in self.registers.iter_mut().zip(&other.registers) {
+            *register = (*register).max(*other);
+        }
+        Ok(())
+    }
+
+    /// Estimated number of distinct values added so far
+    ///
+    /// Small cardinalities, where registers are still empty, use linear
+    /// counting, which is close to exact.
+    pub fn estimate(&self) -> u64 {
+        let m = self.registers.len() as f64;
+        let alpha = 0.7213 / (1.0 + 1.079 / m);
+        let sum: f64 = self.registers.iter().map(|&rank| 2f64.powi(-(rank as i32))).sum();
+        let raw = alpha * m * m / sum;
+
+        let empty = self.registers.iter().filter(|&&rank| rank == 0).count();
+        let estimate = if raw <= 2.5 * m && empty > 0 {
+            m * (m / empty as f64).ln()
+        } else {
+            raw
+        };
+        estimate.round() as u64
+    }
+}
+
+/// The SplitMix64 finalizer, spreading every input bit over the whole hash
+fn mix(mut hash: u64) -> u64 {
+    hash = (hash ^ (hash >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    hash = (hash ^ (hash >> 27)).wrapping_mul(0x94d049bb133111eb);
+    hash ^ (hash >> 31)
+}
+
+/// Approximate distinct count of a series, including null, with a sketch
+/// of [`HLL_PRECISION`]; `None` for types that can't be hashed
+pub fn approx_distinct_count(series: &Series) -> Option<u64> {
+    let mut sketch = HyperLogLog::new(HLL_PRECISION);
+    sketch.insert_series(series).ok()?;
+    Some(sketch.estimate())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hyperloglog() -> Result<()> {
+        assert_eq!(HyperLogLog::new(HLL_PRECISION).estimate(), 0);
+
+        let ids = Series::new("id", (0..200_000i64).map(|i| i % 50_000).collect::<Vec<_>>());
+        let estimate = approx_distinct_count(&ids).unwrap() as f64;
+        assert!((estimate - 50_000.0).abs() / 50_000.0 < 0.03, "estimate {}", estimate);
+
+        let names = Series::new("name", &[Some("a"), None, Some("b"), Some("a"), None]);
+        assert_eq!(approx_distinct_count(&names), Some(3));
+
+        // Merging sketches of halves estimates the whole
+        let mut first = HyperLogLog::new(HLL_PRECISION);
+        first.insert_series(&ids.slice(0, 30_000))?;
+        let mut second = HyperLogLog::new(HLL_PRECISION);
+        second.insert_series(&ids.slice(20_000, 30_000))?;
+        first.merge(&second)?;
+        let merged = first.estimate() as f64;
+        assert!((merged - 50_000.0).abs() / 50_000.0 < 0.03, "merged {}", merged);
+        assert!(first.merge(&HyperLogLog::new(10)).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,40 @@
+use std::io::Cursor;
+use anyhow::{Result, Context};
+use arrow::array::RecordBatch;
+use arrow::datatypes::SchemaRef;
+use arrow::ipc::reader::FileReader;
+use arrow::ipc::writer::FileWriter;
+use polars::prelude::*;
+
+// polars and the arrow/parquet crates each ship their own arrow
+// implementation, so data crosses between them as an in-memory Arrow IPC
+// file, which both sides read and write natively.
+
+/// Convert arrow-rs record batches into a polars DataFrame
+pub(crate) fn record_batches_to_dataframe(schema: &SchemaRef, batches: &[RecordBatch]) -> Result<DataFrame> {
+    let mut buffer = Vec::new();
+    let mut writer = FileWriter::try_new(&mut buffer, schema)?;
+    for batch in batches {
+        writer.write(batch)?;
+    }
+    writer.finish()?;
+    drop(writer);
+
+    IpcReader::new(Cursor::new(buffer))
+        .finish()
+        .context("Failed to convert record batches to a DataFrame")
+}
+
+/// Convert a polars DataFrame into arrow-rs record batches
+pub(crate) fn dataframe_to_record_batches(df: &mut DataFrame) -> Result<(SchemaRef, Vec<RecordBatch>)> {
+    let mut buffer = Vec::new();
+    IpcWriter::new(&mut buffer)
+        .finish(df)
+        .context("Failed to convert DataFrame to record batches")?;
+
+    let reader = FileReader::try_new(Cursor::new(buffer), None)?;
+    let schema = reader.schema();
+    let batches = reader.collect::<std::result::Result<Vec<_>, _>>()?;
+
+    Ok((schema, batches))
+}
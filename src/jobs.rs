@@ -0,0 +1,499 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+#[cfg(feature = "discovery")]
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Instant;
+use anyhow::{Result, Context};
+#[cfg(feature = "discovery")]
+use walkdir::WalkDir;
+use crate::consolidator::{consolidate_with_options, ConsolidateOptions, RunSummary};
+#[cfg(feature = "discovery")]
+use crate::discovery::{discover_input_files, DiscoveryOptions};
+#[cfg(feature = "discovery")]
+use crate::naming::{output_file_name, partition_values, OutputNameContext, OutputNamer};
+
+/// File name of the consolidated file written into each mirrored directory
+pub const MIRRORED_FILE_NAME: &str = "part-00000.parquet";
+
+/// One independent consolidation: a set of inputs and where they go
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConsolidationJob {
+    /// Short label used in progress output
+    pub name: String,
+    pub input_files: Vec<PathBuf>,
+    pub output_path: PathBuf,
+}
+
+/// Plan one job per immediate subdirectory of `input_dir`
+///
+/// Each job consolidates the discovered files of one subdirectory into
+/// `<output_dir>/<subdirectory>.parquet`, or the name `namer` gives it.
+/// Files directly inside `input_dir`, excluded subdirectories and
+/// subdirectories without matching files are skipped. Jobs are ordered by
+/// subdirectory name.
+#[cfg(feature = "discovery")]
+pub fn subdirectory_jobs(
+    input_dir: &Path,
+    output_dir: &Path,
+    discovery: &DiscoveryOptions,
+    namer: &dyn OutputNamer,
+) -> Result<Vec<ConsolidationJob>> {
+    if !input_dir.is_dir() {
+        anyhow::bail!("Per-directory mode requires an input directory: {:?}", input_dir);
+    }
+
+    let mut subdirectories = Vec::new();
+    for entry in discovery.walk(input_dir, false) {
+        let entry = entry?;
+        if entry.depth() == 1 && entry.file_type().is_dir() {
+            subdirectories.push(entry.into_path());
+        }
+    }
+
+    let mut jobs = Vec::new();
+    for subdirectory in subdirectories {
+        let input_files = discover_input_files(&subdirectory, discovery)?;
+        if input_files.is_empty() {
+            continue;
+        }
+
+        let name = subdirectory
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let output = OutputNameContext {
+            default_name: format!("{}.parquet", name),
+            partition_values: partition_values(Path::new(&name)),
+            sequence: jobs.len(),
+            range: None,
+        };
+        jobs.push(ConsolidationJob {
+            output_path: output_dir.join(output_file_name(namer, &output)?),
+            name,
+            input_files,
+        });
+    }
+
+    Ok(jobs)
+}
+
+/// Plan one job per directory of the `input_dir` tree that directly holds
+/// matching files
+///
+/// Each directory's own files are consolidated into
+/// `<output_dir>/<relative path>/part-00000.parquet`, or the name `namer`
+/// gives it, so partition layouts
+/// such as `year=2024/month=01/` are kept while file counts shrink. Jobs are
+/// named after the relative path and ordered by it. Excluded directories
+/// are skipped along with everything below them.
+#[cfg(feature = "discovery")]
+pub fn mirrored_jobs(
+    input_dir: &Path,
+    output_dir: &Path,
+    discovery: &DiscoveryOptions,
+    namer: &dyn OutputNamer,
+) -> Result<Vec<ConsolidationJob>> {
+    if !input_dir.is_dir() {
+        anyhow::bail!("Mirroring the directory structure requires an input directory: {:?}", input_dir);
+    }
+
+    let leaf_discovery = DiscoveryOptions { recursive: false, ..discovery.clone() };
+    let mut jobs = Vec::new();
+    for entry in discovery.walk(input_dir, true) {
+        let entry = entry.context(format!("Failed to walk {:?}", input_dir))?;
+        if !entry.file_type().is_dir() {
+            continue;
+        }
+
+        let input_files = discover_input_files(entry.path(), &leaf_discovery)?;
+        if input_files.is_empty() {
+            continue;
+        }
+
+        let relative = entry.path().strip_prefix(input_dir)?;
+        let name = if relative.as_os_str().is_empty() {
+            ".".to_string()
+        } else {
+            relative.to_string_lossy().into_owned()
+        };
+        let output = OutputNameContext {
+            default_name: MIRRORED_FILE_NAME.to_string(),
+            partition_values: partition_values(relative),
+            sequence: 0,
+            range: None,
+        };
+        jobs.push(ConsolidationJob {
+            name,
+            input_files,
+            output_path: output_dir.join(relative).join(output_file_name(namer, &output)?),
+        });
+    }
+
+    Ok(jobs)
+}
+
+/// How mirrored output treats the partition directories already in the
+/// output directory, following Spark's partition overwrite modes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PartitionOverwrite {
+    /// Replace the partitions present in the new data and keep the others
+    Dynamic,
+    /// Replace the whole dataset, removing partitions absent from the new data
+    Static,
+    /// Add a new file next to the existing files of each partition
+    Append,
+}
+
+impl fmt::Display for PartitionOverwrite {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            PartitionOverwrite::Dynamic => "dynamic",
+            PartitionOverwrite::Static => "static",
+            PartitionOverwrite::Append => "append",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for PartitionOverwrite {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "dynamic" => Ok(PartitionOverwrite::Dynamic),
+            "static" => Ok(PartitionOverwrite::Static),
+            "append" => Ok(PartitionOverwrite::Append),
+            other => anyhow::bail!("Unknown partition overwrite mode: {} (expected dynamic, static or append)", other),
+        }
+    }
+}
+
+/// Existing data files a mirrored run supersedes, by partition directory
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PartitionReplacement {
+    pub stale: BTreeMap<PathBuf, Vec<PathBuf>>,
+}
+
+impl PartitionReplacement {
+    /// Every superseded file, in path order
+    pub fn files(&self) -> Vec<PathBuf> {
+        self.stale.values().flatten().cloned().collect()
+    }
+
+    /// Delete the superseded files once the run is over, returning them
+    ///
+    /// Files in the partitions of `written` outputs are always removed, as
+    /// those partitions now hold the new data. The rest, in partitions the
+    /// new data lacks, are only removed when every job succeeded, so a
+    /// failed static overwrite never drops partitions it didn't replace.
+    pub fn remove(&self, written: &[PathBuf], all_succeeded: bool) -> Result<Vec<PathBuf>> {
+        let mut removed = Vec::new();
+        for (partition, files) in &self.stale {
+            if all_succeeded || written.iter().any(|output| output.parent() == Some(partition.as_path())) {
+                for file in files {
+                    #[cfg(feature = "fault-injection")]
+                    crate::fault_injection::inject_fault(crate::fault_injection::FaultPoint::Commit, file)?;
+                    fs::remove_file(file).context(format!("Failed to delete replaced file {:?}", file))?;
+                    removed.push(file.clone());
+                }
+            }
+        }
+        Ok(removed)
+    }
+}
+
+/// Point mirrored jobs writing into `output_dir` at their outputs under
+/// `mode`, listing the existing data files they supersede
+///
+/// Data files are the `.parquet` files in the output tree, ignoring hidden
+/// and `_`-prefixed names such as `_SUCCESS`. With `Append` each output
+/// takes the first free name `namer` gives it in its partition, counting
+/// the sequence number up from 0, `part-NNNNN.parquet` by default, and
+/// nothing is superseded. `Dynamic` supersedes the other data files in the
+/// partitions being written, and `Static` every data file in the tree.
+#[cfg(feature = "discovery")]
+pub fn plan_partition_overwrite(
+    jobs: &mut [ConsolidationJob],
+    output_dir: &Path,
+    mode: PartitionOverwrite,
+    namer: &dyn OutputNamer,
+) -> Result<PartitionReplacement> {
+    let mut replacement = PartitionReplacement::default();
+    if mode == PartitionOverwrite::Append {
+        for job in jobs.iter_mut() {
+            let partition = job.output_path.parent().and_then(|parent| parent.strip_prefix(output_dir).ok());
+            let mut output = OutputNameContext {
+                default_name: String::new(),
+                partition_values: partition.map(partition_values).unwrap_or_default(),
+                sequence: 0,
+                range: None,
+            };
+            job.output_path = loop {
+                output.default_name = format!("part-{:05}.parquet", output.sequence);
+                let candidate = job.output_path.with_file_name(output_file_name(namer, &output)?);
+                if !candidate.exists() {
+                    break candidate;
+                }
+                output.sequence += 1;
+            };
+        }
+        return Ok(replacement);
+    }
+    let outputs: Vec<&Path> = jobs.iter().map(|job| job.output_path.as_path()).collect();
+    let (partitions, depth) = match mode {
+        PartitionOverwrite::Static => (vec![output_dir], usize::MAX),
+        _ => (outputs.iter().filter_map(|output| output.parent()).collect(), 1),
+    };
+    for partition in partitions.into_iter().filter(|partition| partition.is_dir()) {
+        for entry in WalkDir::new(partition).max_depth(depth).sort_by_file_name() {
+            let entry = entry.context(format!("Failed to walk {:?}", partition))?;
+            let path = entry.path();
+            let name = entry.file_name().to_string_lossy();
+            let is_data = entry.file_type().is_file()
+                && !name.starts_with('.')
+                && !name.starts_with('_')
+                && path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("parquet"));
+            if is_data && !outputs.contains(&path) {
+                let parent = path.parent().unwrap_or(output_dir).to_path_buf();
+                replacement.stale.entry(parent).or_default().push(path.to_path_buf());
+            }
+        }
+    }
+    Ok(replacement)
+}
+
+/// Number of jobs to run at once for a `--jobs` value of `jobs`
+///
+/// `0` runs one job per available CPU. The result is capped by the number
+/// of jobs and, with `max_open_files`, so every running job can keep at
+/// least one input and its output open.
+///
+/// # Examples
+///
+/// ```
+/// use parquet_consolidator::job_parallelism;
+///
+/// assert_eq!(job_parallelism(4, 10, None), 4);
+/// assert_eq!(job_parallelism(4, 2, None), 2);
+/// assert_eq!(job_parallelism(8, 100, Some(6)), 3);
+/// assert!(job_parallelism(0, 100, None) >= 1);
+/// ```
+pub fn job_parallelism(jobs: usize, job_count: usize, max_open_files: Option<usize>) -> usize {
+    let requested = match jobs {
+        0 => thread::available_parallelism().map_or(1, |cpus| cpus.get()),
+        jobs => jobs,
+    };
+    let by_open_files = max_open_files.map_or(usize::MAX, |max| max / 2);
+    requested.min(job_count).min(by_open_files).max(1)
+}
+
+/// Run independent jobs with at most `parallelism` running at once
+///
+/// Every job runs even if others fail; results are returned in job order.
+/// Jobs must not share outputs or state files, as nothing serializes their
+/// writes. Jobs but the first that haven't started by the options' deadline
+/// aren't run; their summaries are [deferred](RunSummary::is_deferred),
+/// listing every input as remaining.
+pub fn run_jobs(jobs: &[ConsolidationJob], options: &ConsolidateOptions, parallelism: usize) -> Vec<Result<RunSummary>> {
+    let next_job = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<Result<RunSummary>>>> = Mutex::new(jobs.iter().map(|_| None).collect());
+
+    thread::scope(|scope| {
+        for _ in 0..parallelism.clamp(1, jobs.len().max(1)) {
+            scope.spawn(|| loop {
+                let index = next_job.fetch_add(1, Ordering::SeqCst);
+                let Some(job) = jobs.get(index) else { break };
+                if index > 0 && options.deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                    let deferred = RunSummary { remaining_files: job.input_files.clone(), ..Default::default() };
+                    results.lock().unwrap()[index] = Some(Ok(deferred));
+                    continue;
+                }
+                if options.verbose && !job.name.is_empty() {
+                    println!("Consolidating {} ({} files)", job.name, job.input_files.len());
+                }
+                let mut result = consolidate_with_options(&job.input_files, &job.output_path, options);
+                if !job.name.is_empty() {
+                    result = result.context(format!("Failed to consolidate {}", job.name));
+                }
+                results.lock().unwrap()[index] = Some(result);
+            });
+        }
+    });
+
+    results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .map(|result| result.expect("every job is run"))
+        .collect()
+}
+
+#[cfg(all(test, feature = "discovery"))]
+mod tests {
+    use super::*;
+    use std::fs;
+    use crate::naming::DefaultNamer;
+    use crate::test_utils::create_test_parquet_file;
+
+    /// `<partition values>-<sequence>.parquet`
+    struct FlatNamer;
+
+    impl OutputNamer for FlatNamer {
+        fn file_name(&self, output: &OutputNameContext) -> String {
+            let values: Vec<&str> = output.partition_values.iter().map(|(_, value)| value.as_str()).collect();
+            format!("{}-{}.parquet", values.join("_"), output.sequence)
+        }
+    }
+
+    #[test]
+    fn test_subdirectory_jobs() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let input_dir = temp_dir.path().join("landing");
+        for table in ["orders", "customers", "empty"] {
+            fs::create_dir_all(input_dir.join(table))?;
+        }
+        fs::create_dir_all(input_dir.join("orders/2024"))?;
+        create_test_parquet_file(&input_dir.join("orders/a.parquet"), 0, 5)?;
+        create_test_parquet_file(&input_dir.join("orders/2024/b.parquet"), 5, 10)?;
+        create_test_parquet_file(&input_dir.join("customers/c.parquet"), 0, 5)?;
+        create_test_parquet_file(&input_dir.join("root.parquet"), 0, 5)?;
+
+        let output_dir = temp_dir.path().join("out");
+        let jobs = subdirectory_jobs(&input_dir, &output_dir, &DiscoveryOptions::default(), &DefaultNamer)?;
+        let names: Vec<&str> = jobs.iter().map(|job| job.name.as_str()).collect();
+        assert_eq!(names, vec!["customers", "orders"]);
+        assert_eq!(jobs[1].input_files.len(), 1);
+        assert_eq!(jobs[1].output_path, output_dir.join("orders.parquet"));
+
+        let discovery = DiscoveryOptions { recursive: true, ..Default::default() };
+        let jobs = subdirectory_jobs(&input_dir, &output_dir, &discovery, &DefaultNamer)?;
+        assert_eq!(jobs[1].input_files.len(), 2);
+
+        let discovery = DiscoveryOptions { exclude_dirs: vec!["orders".to_string()], ..Default::default() };
+        assert_eq!(subdirectory_jobs(&input_dir, &output_dir, &discovery, &DefaultNamer)?.len(), 1);
+
+        assert!(subdirectory_jobs(&input_dir.join("root.parquet"), &output_dir, &discovery, &DefaultNamer).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_mirrored_jobs() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let input_dir = temp_dir.path().join("table");
+        fs::create_dir_all(input_dir.join("year=2024/month=01"))?;
+        fs::create_dir_all(input_dir.join("year=2024/month=02"))?;
+        fs::create_dir_all(input_dir.join("year=2025"))?;
+        create_test_parquet_file(&input_dir.join("root.parquet"), 0, 5)?;
+        create_test_parquet_file(&input_dir.join("year=2024/month=01/a.parquet"), 0, 5)?;
+        create_test_parquet_file(&input_dir.join("year=2024/month=01/b.parquet"), 5, 10)?;
+        create_test_parquet_file(&input_dir.join("year=2024/month=02/c.parquet"), 0, 5)?;
+
+        let output_dir = temp_dir.path().join("out");
+        let jobs = mirrored_jobs(&input_dir, &output_dir, &DiscoveryOptions::default(), &DefaultNamer)?;
+        let names: Vec<&str> = jobs.iter().map(|job| job.name.as_str()).collect();
+        assert_eq!(names, vec![".", "year=2024/month=01", "year=2024/month=02"]);
+        assert_eq!(jobs[0].input_files.len(), 1);
+        assert_eq!(jobs[1].input_files.len(), 2);
+        assert_eq!(jobs[1].output_path, output_dir.join("year=2024/month=01").join(MIRRORED_FILE_NAME));
+
+        let discovery = DiscoveryOptions { exclude_dirs: vec!["year=2024".to_string()], ..Default::default() };
+        assert_eq!(mirrored_jobs(&input_dir, &output_dir, &discovery, &DefaultNamer)?.len(), 1);
+
+        let jobs = mirrored_jobs(&input_dir, &output_dir, &DiscoveryOptions::default(), &FlatNamer)?;
+        assert_eq!(jobs[1].output_path, output_dir.join("year=2024/month=01/2024_01-0.parquet"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_plan_partition_overwrite() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let output_dir = temp_dir.path().join("out");
+        for file in ["day=1/part-00000.parquet", "day=1/part-00001.parquet", "day=1/_SUCCESS", "day=2/part-00000.parquet"] {
+            fs::create_dir_all(output_dir.join(file).parent().unwrap())?;
+            fs::write(output_dir.join(file), "")?;
+        }
+        let new_jobs = || {
+            ["day=1", "day=3"]
+                .map(|day| ConsolidationJob {
+                    name: day.to_string(),
+                    input_files: Vec::new(),
+                    output_path: output_dir.join(day).join(MIRRORED_FILE_NAME),
+                })
+                .to_vec()
+        };
+
+        let mut jobs = new_jobs();
+        let replacement = plan_partition_overwrite(&mut jobs, &output_dir, PartitionOverwrite::Dynamic, &DefaultNamer)?;
+        assert_eq!(jobs, new_jobs());
+        assert_eq!(replacement.files(), vec![output_dir.join("day=1/part-00001.parquet")]);
+
+        let replacement = plan_partition_overwrite(&mut jobs, &output_dir, PartitionOverwrite::Static, &DefaultNamer)?;
+        assert_eq!(
+            replacement.files(),
+            vec![output_dir.join("day=1/part-00001.parquet"), output_dir.join("day=2/part-00000.parquet")]
+        );
+        // A failed run keeps the partitions it didn't write
+        let written = vec![jobs[0].output_path.clone()];
+        assert_eq!(replacement.remove(&written, false)?, vec![output_dir.join("day=1/part-00001.parquet")]);
+        assert!(output_dir.join("day=2/part-00000.parquet").exists());
+
+        let mut jobs = new_jobs();
+        let replacement = plan_partition_overwrite(&mut jobs, &output_dir, PartitionOverwrite::Append, &DefaultNamer)?;
+        assert_eq!(replacement, PartitionReplacement::default());
+        assert_eq!(jobs[0].output_path, output_dir.join("day=1/part-00001.parquet"));
+        assert_eq!(jobs[1].output_path, output_dir.join("day=3/part-00000.parquet"));
+
+        let mut jobs = new_jobs();
+        fs::write(output_dir.join("day=1/1-0.parquet"), "")?;
+        plan_partition_overwrite(&mut jobs, &output_dir, PartitionOverwrite::Append, &FlatNamer)?;
+        assert_eq!(jobs[0].output_path, output_dir.join("day=1/1-1.parquet"));
+
+        assert_eq!("Dynamic".parse::<PartitionOverwrite>()?, PartitionOverwrite::Dynamic);
+        assert!("replace".parse::<PartitionOverwrite>().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_run_jobs() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let jobs: Vec<ConsolidationJob> = (0..4)
+            .map(|i| {
+                let input = temp_dir.path().join(format!("in{}.parquet", i));
+                create_test_parquet_file(&input, 0, 10 * (i + 1)).unwrap();
+                ConsolidationJob {
+                    name: format!("job{}", i),
+                    input_files: vec![input],
+                    output_path: temp_dir.path().join(format!("out{}.parquet", i)),
+                }
+            })
+            .chain(std::iter::once(ConsolidationJob {
+                name: "broken".to_string(),
+                input_files: vec![temp_dir.path().join("missing.parquet")],
+                output_path: temp_dir.path().join("broken.parquet"),
+            }))
+            .collect();
+
+        let results = run_jobs(&jobs, &ConsolidateOptions::default(), 3);
+        let rows: Vec<usize> = results[..4].iter().map(|r| r.as_ref().unwrap().rows_written).collect();
+        assert_eq!(rows, vec![10, 20, 30, 40]);
+        assert!(results[4].as_ref().unwrap_err().to_string().contains("Failed to consolidate broken"));
+
+        // Past the deadline only the first job runs
+        let options = ConsolidateOptions { deadline: Some(Instant::now()), ..Default::default() };
+        let results = run_jobs(&jobs[..3], &options, 1);
+        assert_eq!(results[0].as_ref().unwrap().rows_written, 10);
+        assert!(results[1..].iter().all(|r| r.as_ref().unwrap().is_deferred()));
+        assert_eq!(results[2].as_ref().unwrap().remaining_files, jobs[2].input_files);
+
+        Ok(())
+    }
+}
@@ -1,4 +1,143 @@
+pub mod anomaly;
+pub mod archive;
+pub mod audit;
+pub mod auto_batch;
+pub mod auto_codec;
+pub mod budget;
+pub mod build_info;
+pub mod cancel;
+pub mod cdc;
+pub mod checks;
+pub mod columns;
+#[cfg(feature = "discovery")]
+pub mod command;
+#[cfg(feature = "discovery")]
+pub mod compaction;
+pub mod compat;
+pub mod confirm;
 pub mod consolidator;
+pub mod content_digest;
+#[cfg(feature = "discovery")]
+pub mod datasets;
+pub mod dedupe;
+#[cfg(feature = "discovery")]
+pub mod discovery;
+pub mod drift;
+pub mod duplicate_inputs;
+#[cfg(feature = "fault-injection")]
+pub mod fault_injection;
+pub mod field_metadata;
+pub mod file_dates;
+pub mod file_info;
+pub mod fill_missing;
+pub mod footer;
+pub mod formats;
+pub mod generate;
+pub mod health;
+pub mod hll;
+mod interop;
+pub mod jobs;
+pub mod manifest;
+pub mod memory;
+pub mod naming;
+pub mod nullability;
+pub mod open_files;
+pub mod pipeline;
+pub mod plan;
+pub mod profile;
+pub mod redact;
+pub mod repair;
+pub mod report;
+pub mod row_group_selection;
+pub mod row_groups;
+pub mod runs;
+pub mod schema_doc;
+pub mod schema_merge;
+#[cfg(feature = "schema-registry")]
+pub mod schema_registry;
+pub mod snapshot;
+pub mod sortedness;
+pub mod split;
+pub mod statsd;
+pub mod stats_validation;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod strategies;
 pub mod test_utils;
+pub mod timings;
+pub mod trace;
+pub mod transform;
+pub mod versions;
+pub mod warnings;
+pub mod workspace;
+pub mod writer;
 
+pub use anomaly::*;
+pub use archive::*;
+pub use audit::*;
+pub use auto_batch::*;
+pub use auto_codec::*;
+pub use budget::*;
+pub use build_info::*;
+pub use cancel::*;
+pub use cdc::*;
+pub use checks::*;
+pub use columns::*;
+#[cfg(feature = "discovery")]
+pub use command::*;
+#[cfg(feature = "discovery")]
+pub use compaction::*;
+pub use compat::*;
+pub use confirm::*;
 pub use consolidator::*;
+pub use content_digest::*;
+#[cfg(feature = "discovery")]
+pub use datasets::*;
+pub use dedupe::*;
+#[cfg(feature = "discovery")]
+pub use discovery::*;
+pub use drift::*;
+pub use duplicate_inputs::*;
+#[cfg(feature = "fault-injection")]
+pub use fault_injection::*;
+pub use field_metadata::*;
+pub use file_dates::*;
+pub use file_info::*;
+pub use fill_missing::*;
+pub use footer::*;
+pub use formats::*;
+pub use generate::*;
+pub use health::*;
+pub use hll::*;
+pub use jobs::*;
+pub use manifest::*;
+pub use memory::*;
+pub use naming::*;
+pub use nullability::*;
+pub use open_files::*;
+pub use pipeline::*;
+pub use plan::*;
+pub use profile::*;
+pub use redact::*;
+pub use repair::*;
+pub use report::*;
+pub use row_group_selection::*;
+pub use row_groups::*;
+pub use runs::*;
+pub use schema_doc::*;
+pub use schema_merge::*;
+#[cfg(feature = "schema-registry")]
+pub use schema_registry::*;
+pub use snapshot::*;
+pub use sortedness::*;
+pub use split::*;
+pub use statsd::*;
+pub use stats_validation::*;
+#[cfg(any(test, feature = "test-utils"))]
+pub use strategies::*;
+pub use timings::*;
+pub use trace::*;
+pub use transform::*;
+pub use versions::*;
+pub use warnings::*;
+pub use workspace::*;
+pub use writer::*;
@@ -1,32 +1,1142 @@
-use clap::Parser;
-use std::path::PathBuf;
-use anyhow::Result;
-use parquet_consolidator::{find_parquet_files, consolidate_parquet_files};
+use clap::{Parser, Subcommand};
+use std::fs::File;
+use std::io::{BufReader, IsTerminal};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+use anyhow::{Result, Context};
+use parquet::basic::Compression;
+use regex::Regex;
+use parquet_consolidator::{
+    append_audit_record, check_input_schemas, clean_up_workspaces_on_interrupt, confirm, default_created_by,
+    discover_input_files, drift_report, edit_key_value_metadata, explain_consolidation, file_name_regex,
+    generate_dataset, is_named_pipe, job_parallelism, merged_schema, null_report, open_file_budget, open_file_limit,
+    output_stats, overwrite_summary, parse_codec, profile_files, read_file_list, read_key_value_metadata,
+    read_versions, render_schema, repair_file, run_metrics, serve_health, summary_schema_file, verify_audit_log,
+    verify_output, version_log_path, AbortThreshold, AuditRecord, BuildInfo, CdcOptions, ChangedInputPolicy,
+    ColumnCompression, ColumnEncoding, ColumnFill, ColumnRedaction, ColumnSelection, ColumnSpec, CompactCommand,
+    CompactionPolicy, CompatMode, ConsolidateCommand, ConsolidateOptions, DatasetConfig, DatasetOutcome, DateRange,
+    DefaultNamer, DiscoveryOptions, FieldMetadataPolicy, FileDateFilter, FileNameDate, GenerateOptions, HealthState,
+    IfExists, InputFormat, InputOrder, InputSnapshot, JobLayout, MaxRuntime, MetadataEdit, NullabilityPolicy,
+    OutputInInputPolicy, OutputManifest, PartitionOverwrite, PipelineOptions, QualityChecks, ReadOptions,
+    RowGroupSelection, RunCommand, RunSummary, SchemaDocFormat, Span, StatsdSink, TargetReader, TimestampUnit,
+    Tracer, UnreadablePathPolicy, WriterOptions, ALL_DATASETS, DECIMAL_ACTIVATION_VAR, DEFAULT_OP_COLUMN,
+    LATEST_FILE_NAME, LIVENESS_PATH, PARTIAL_SUCCESS_EXIT_CODE, READINESS_PATH,
+};
+use parquet_consolidator::warnings::WarningLog;
 
 #[derive(Parser)]
-#[command(author, version, about)]
+#[command(author, version, about, subcommand_negates_reqs = true, disable_version_flag = true)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// Print version; with --verbose, also the features, library versions, codecs and storage schemes of this build
+    #[arg(short = 'V', long, global = true, default_value_t = false)]
+    version: bool,
+    /// Print the --version --verbose information as JSON
+    #[arg(long, default_value_t = false, requires = "version")]
+    json: bool,
+    /// Print progress; repeat (-vv) to also print polars' own diagnostics, such as the operators it runs and its streaming decisions, to stderr
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    verbose: u8,
+    /// Inject faults to test recovery: read, write or commit, then error, slow=<MILLIS> or crash, optionally @N for only the Nth hit (repeatable)
+    #[cfg(feature = "fault-injection")]
+    #[arg(long, global = true, hide = true, value_name = "POINT:FAULT[@N]")]
+    fault_inject: Vec<parquet_consolidator::FaultSpec>,
+    /// Without a subcommand, the options of `consolidate`
+    #[command(flatten)]
+    consolidate: ConsolidateArgs,
+}
+
+/// Options of a consolidation run, given to the `consolidate` subcommand or
+/// without any
+#[derive(clap::Args)]
+struct ConsolidateArgs {
+    #[arg(short, long, required_unless_present_any = ["files_from", "version"], conflicts_with = "files_from")]
+    input: Option<PathBuf>,
+    #[arg(short, long, required_unless_present = "version")]
+    output: Option<PathBuf>,
+    /// Read the input files and directories from this list, one per line ("-" for stdin)
+    #[arg(long)]
+    files_from: Option<PathBuf>,
+    /// Entries of --files-from are NUL-delimited, as written by `find -print0`
+    #[arg(long, default_value_t = false, requires = "files_from")]
+    null: bool,
+    #[command(flatten)]
+    discovery: DiscoveryArgs,
+    /// Codec of columns without an override, e.g. "zstd:3", or "auto" to pick each column's codec and encoding from a sample of it
+    #[arg(long, value_name = "CODEC", default_value = "snappy")]
+    compression: String,
+    /// Per-column compression overrides, e.g. "blob=zstd:19,id=snappy"
+    #[arg(long, value_delimiter = ',')]
+    compression_per_column: Vec<ColumnCompression>,
+    /// Truncate min/max statistics values to at most this many bytes
+    #[arg(long)]
+    stats_truncate_length: Option<usize>,
+    /// Comma-separated columns to write without min/max statistics
+    #[arg(long, value_delimiter = ',')]
+    no_stats_columns: Vec<String>,
+    /// Per-column encodings, e.g. "ts=delta_binary_packed,price=byte_stream_split"
+    #[arg(long, value_delimiter = ',')]
+    encoding_per_column: Vec<ColumnEncoding>,
+    /// Copy input row groups 1:1 into the output (inputs must share an identical schema)
+    #[arg(long, default_value_t = false)]
+    preserve_row_groups: bool,
+    /// Decode, transform and encode parquet inputs sharing one schema in overlapping stages, holding only a few row groups in memory
+    #[arg(long, default_value_t = false)]
+    pipeline: bool,
+    /// With --pipeline, decode row groups on this many threads (default: one per CPU)
+    #[arg(long, value_name = "N", requires = "pipeline")]
+    pipeline_readers: Option<usize>,
+    /// Pick in-memory, streaming (preserved row groups) or batched reading from the inputs' footers and the memory available
+    #[arg(long, default_value_t = false, conflicts_with_all = ["preserve_row_groups", "pipeline"])]
+    auto_batch: bool,
+    /// Skip input files whose schema can't be read, with a warning, instead of failing the run
+    #[arg(long, default_value_t = false, conflicts_with = "preserve_row_groups")]
+    continue_on_error: bool,
+    /// With --continue-on-error, fail anyway once more than this many inputs, or this percentage of them, are skipped, e.g. 10%
+    #[arg(long, value_name = "N|PERCENT%", requires = "continue_on_error")]
+    abort_threshold: Option<AbortThreshold>,
+    /// Give up on an input that takes longer than this many seconds to read, failing it (or skipping it with --continue-on-error)
+    #[arg(long, value_name = "SECS", conflicts_with = "preserve_row_groups")]
+    file_timeout: Option<u64>,
+    /// Stop reading new inputs once the run has taken this long (e.g. 30m), writing what was read and listing the inputs left for the next run
+    #[arg(long, value_name = "DURATION", conflicts_with_all = ["preserve_row_groups", "pipeline"])]
+    max_runtime: Option<MaxRuntime>,
+    /// Where --max-runtime lists the inputs left (default: <output>.remaining, or _remaining_inputs.txt in an output directory)
+    #[arg(long, value_name = "FILE", requires = "max_runtime")]
+    remaining_inputs: Option<PathBuf>,
+    /// Record the inputs' sizes and modification times once they are listed, and fail (or skip, then re-consolidate without) inputs that change while the run reads them
+    #[arg(long, value_name = "POLICY", num_args = 0..=1, default_missing_value = "fail")]
+    snapshot_inputs: Option<ChangedInputPolicy>,
+    /// Read only one copy of inputs that are byte-identical (same size and checksum), reporting the copies left out
+    #[arg(long, default_value_t = false)]
+    skip_duplicate_inputs: bool,
+    /// What to do when --output lies inside the --input directory, where a later run would read it back (error or exclude)
+    #[arg(long, value_name = "POLICY", default_value = "error")]
+    output_in_input: OutputInInputPolicy,
+    /// Coalesce row groups smaller than this many rows where possible
+    #[arg(long)]
+    min_row_group_rows: Option<usize>,
+    /// Split row groups larger than this many rows
+    #[arg(long)]
+    max_row_group_rows: Option<usize>,
+    /// Convert legacy INT96 timestamps to this unit (us, ms, ns)
+    #[arg(long)]
+    coerce_int96: Option<TimestampUnit>,
+    /// Read decimals, enums and small integers of files annotated with legacy Hive-era types as proper types instead of binary
+    #[arg(long, default_value_t = false)]
+    normalize_legacy_types: bool,
+    /// Only read columns whose name matches this regex, e.g. '^metric_'
+    #[arg(long, value_name = "REGEX", conflicts_with = "preserve_row_groups")]
+    columns_regex: Option<Regex>,
+    /// Leave out columns whose name matches this regex, e.g. '_debug$'
+    #[arg(long, value_name = "REGEX", conflicts_with = "preserve_row_groups")]
+    drop_columns_regex: Option<Regex>,
+    /// Stack inputs with different columns, filling the columns an input lacks with nulls
+    #[arg(long, default_value_t = false, conflicts_with = "preserve_row_groups")]
+    union_schemas: bool,
+    /// Comma-separated column=value pairs; with --union-schemas, rows from inputs lacking the column get this value instead of null
+    #[arg(long, value_delimiter = ',', value_name = "COLUMN=VALUE", requires = "union_schemas")]
+    fill_missing: Vec<ColumnFill>,
+    /// Read only these row groups of an input, counted from 0 with both ends included, e.g. big.parquet:0-9 (repeatable)
+    #[arg(long, value_name = "FILE:RANGE", conflicts_with = "preserve_row_groups")]
+    row_groups: Vec<RowGroupSelection>,
+    /// What to do with a column required in some inputs and holding nulls in others (error, coerce-nullable, drop-null-rows)
+    #[arg(long, value_name = "POLICY", default_value = "coerce-nullable")]
+    nullability_conflicts: NullabilityPolicy,
+    /// What to do when inputs give a column's field metadata (descriptions, units) different values (first, last, drop, error)
+    #[arg(long, value_name = "POLICY", default_value = "first")]
+    field_metadata_conflicts: FieldMetadataPolicy,
+    /// Restrict writer settings to what a downstream engine reads (spark, for Spark 3.x and Athena)
+    #[arg(long)]
+    compat: Option<CompatMode>,
+    /// Warn before writing about columns and writer options this reader can't handle (spark-3.3, athena, duckdb)
+    #[arg(long, value_name = "READER")]
+    target_reader: Option<TargetReader>,
+    /// Value written to the footer's created_by field (default: "parquet_consolidator <version>")
+    #[arg(long)]
+    created_by: Option<String>,
+    /// Print per-column null counts of the consolidated output
+    #[arg(long, default_value_t = false)]
+    null_report: bool,
+    /// Write per-column statistics of the output to this file (.json or .parquet)
+    #[arg(long)]
+    stats_output: Option<PathBuf>,
+    /// Append the min and max of this column to the output file name, e.g. events_2024-06-01_2024-06-07.parquet
+    #[arg(long)]
+    name_by_range: Option<String>,
+    /// Split outputs with more rows than this into several files, e.g. events-00000.parquet, events-00001.parquet
+    #[arg(long, value_name = "ROWS")]
+    max_rows_per_file: Option<usize>,
+    /// Fail the run unless each output is sorted ascending by this column, nulls first
+    #[arg(long, value_name = "COLUMN")]
+    assert_sorted_by: Option<String>,
+    /// Print the optimized query plan of each output instead of consolidating
+    #[arg(long, default_value_t = false)]
+    explain: bool,
+    /// Read only the schemas of every job's inputs and check they merge before any data is read
+    #[arg(long, default_value_t = false)]
+    check_schemas_first: bool,
+    /// What to do when the output file already exists (overwrite, skip, unique)
+    #[arg(long, default_value = "overwrite")]
+    if_exists: IfExists,
+    /// Overwrite existing outputs without asking for confirmation on a terminal
+    #[arg(short, long, default_value_t = false)]
+    yes: bool,
+    /// TOML file of data quality checks to run on the consolidated data
+    #[arg(long)]
+    checks: Option<PathBuf>,
+    /// Write rows rejected by quarantine checks to this parquet file, with a _quarantine_reason column
+    #[arg(long, requires = "checks")]
+    quarantine: Option<PathBuf>,
+    /// Scrub regex matches from string columns, e.g. 'comment=/\b\d{16}\b/' (repeatable)
+    #[arg(long)]
+    redact: Vec<ColumnRedaction>,
+    /// Comma-separated key columns; rows repeating an earlier key are dropped
+    #[arg(long, value_delimiter = ',')]
+    dedupe_key: Vec<String>,
+    /// File of keys emitted by previous runs; their rows are dropped and new keys are added
+    #[arg(long, requires = "dedupe_key")]
+    dedupe_state: Option<PathBuf>,
+    /// Comma-separated key columns; merges CDC records (insert/update/delete) into a snapshot
+    #[arg(long, value_delimiter = ',')]
+    cdc_key: Vec<String>,
+    /// Column holding the CDC operation
+    #[arg(long, default_value = DEFAULT_OP_COLUMN)]
+    cdc_op_column: String,
+    /// Column ordering CDC records, e.g. a commit timestamp (default: input order)
+    #[arg(long, requires = "cdc_key")]
+    cdc_order_by: Option<String>,
+    /// Comma-separated key columns; count and sample rows sharing a key without removing them
+    #[arg(long, value_delimiter = ',')]
+    report_duplicates: Vec<String>,
+    /// Don't warn about input files whose column statistics are outliers
+    #[arg(long, default_value_t = false)]
+    no_anomaly_warnings: bool,
+    /// Check the footer min/max and null count statistics of this many row groups per parquet input against their data, warning where they are wrong
+    #[arg(long, value_name = "ROW_GROUPS", num_args = 0..=1, default_missing_value = "3")]
+    validate_stats: Option<usize>,
+    /// Report wall time, CPU time and thread utilization for each phase of the run
+    #[arg(long, default_value_t = false)]
+    timings: bool,
+    /// Report an order-insensitive digest of the rows written, to compare outputs across re-runs and environments
+    #[arg(long, default_value_t = false)]
+    content_digest: bool,
+    /// Append a record of the run (inputs and outputs with checksums, replaced files, arguments, result) to this JSON-lines file
+    #[arg(long, value_name = "FILE")]
+    audit_log: Option<PathBuf>,
+    /// Record the size, checksum, row count, schema and content digest of every output in this JSON file, for later checks with `audit --output`
+    #[arg(long, value_name = "FILE")]
+    manifest: Option<PathBuf>,
+    /// Send run metrics (jobs, files, rows, bytes, duration, peak memory) to a statsd or Datadog agent over UDP
+    #[arg(long, value_name = "HOST:PORT")]
+    statsd: Option<String>,
+    /// Export a trace of the run to this OpenTelemetry collector (OTLP/HTTP), e.g. http://localhost:4318
+    #[cfg(feature = "otel")]
+    #[arg(long, value_name = "URL")]
+    otlp_endpoint: Option<String>,
+    /// Conform the output to a schema from this Confluent-style schema registry, e.g. http://localhost:8081
+    #[cfg(feature = "schema-registry")]
+    #[arg(long, value_name = "URL", requires = "schema_subject", conflicts_with = "preserve_row_groups")]
+    schema_registry: Option<String>,
+    /// Subject whose schema the output conforms to, with --schema-registry
+    #[cfg(feature = "schema-registry")]
+    #[arg(long, value_name = "SUBJECT", requires = "schema_registry")]
+    schema_subject: Option<String>,
+    /// Version of the subject's schema (default: latest)
+    #[cfg(feature = "schema-registry")]
+    #[arg(long, value_name = "VERSION", requires = "schema_registry")]
+    schema_version: Option<u64>,
+    /// Consolidate each immediate subdirectory of the input into its own file in the output directory
+    #[arg(long, default_value_t = false, conflicts_with_all = ["stats_output", "quarantine", "files_from"])]
+    per_directory: bool,
+    /// Maximum number of per-directory outputs consolidated concurrently (0: one per CPU)
+    #[arg(long, default_value_t = 1, conflicts_with = "dedupe_state")]
+    jobs: usize,
+    /// Keep at most this many input files open at once, reading larger jobs in batches (default: the process's open file limit, less a reserve)
+    #[arg(long, value_name = "N")]
+    max_open_files: Option<usize>,
+    /// Mirror the input directory tree into the output directory, compacting each directory's files into one
+    #[arg(long, default_value_t = false, conflicts_with_all = ["per_directory", "stats_output", "quarantine", "files_from"])]
+    mirror_structure: bool,
+    /// With --mirror-structure, how existing partitions in the output are treated (dynamic, static or append)
+    #[arg(long, requires = "mirror_structure", conflicts_with = "name_by_range")]
+    partition_overwrite: Option<PartitionOverwrite>,
+    /// Stamp each successful run with the next dataset version, in the outputs' footers and a version log next to the output
+    #[arg(long, default_value_t = false)]
+    versioned: bool,
+    /// Treat --output as a directory, write this run into run=<uuid>/ inside it and point its LATEST file at the run once every output is written
+    #[arg(long, default_value_t = false, conflicts_with = "partition_overwrite")]
+    run_id_dirs: bool,
+}
+
+/// Options selecting the input files, shared by every command
+#[derive(clap::Args)]
+struct DiscoveryArgs {
+    #[arg(short, long, default_value_t = false)]
+    recursive: bool,
+    /// Comma-separated input formats to include (parquet, csv, ndjson)
+    #[arg(long, value_delimiter = ',', default_value = "parquet")]
+    formats: Vec<InputFormat>,
+    /// Directory names never descended into, e.g. "_temporary,.spark-staging" (repeatable)
+    #[arg(long, value_delimiter = ',')]
+    exclude_dir: Vec<String>,
+    /// Only consolidate files whose whole name matches this regex, e.g. 'part-\d+-.*\.parquet'
+    #[arg(long)]
+    name_regex: Option<String>,
+    /// Consolidate at most this many files per output, the first in --order (oldest first by default)
+    #[arg(long)]
+    max_files: Option<usize>,
+    /// Order files are consolidated in (oldest-first, newest-first, largest-first, smallest-first)
+    #[arg(long)]
+    order: Option<InputOrder>,
+    /// Skip files modified within this many seconds, or locked by a writer, as they may be incomplete
+    #[arg(long)]
+    min_file_age: Option<u64>,
+    /// List files and the schema from the input directory's _metadata/_common_metadata summary files when present
+    #[arg(long, default_value_t = false)]
+    use_metadata_file: bool,
+    /// What to do with directories and files that can't be read, e.g. for lack of permission (warn or fail)
+    #[arg(long, value_name = "POLICY", default_value = "warn")]
+    unreadable_paths: UnreadablePathPolicy,
+    /// Skip symlinks to files found in input directories instead of reading the files they point to
+    #[arg(long, default_value_t = false)]
+    no_follow_symlinks: bool,
+    /// Where file names carry their date, as a strftime pattern, e.g. 'events_%Y%m%d'; used with --date-range
+    #[arg(long, value_name = "PATTERN", requires = "date_range")]
+    filename_date_pattern: Option<FileNameDate>,
+    /// Only consolidate files whose name carries a date in this range, e.g. 2024-06-01..2024-06-30; either end may be left open
+    #[arg(long, value_name = "START..END", requires = "filename_date_pattern")]
+    date_range: Option<DateRange>,
+}
+
+impl DiscoveryArgs {
+    fn options(&self) -> Result<DiscoveryOptions> {
+        Ok(DiscoveryOptions {
+            recursive: self.recursive,
+            formats: self.formats.clone(),
+            exclude_dirs: self.exclude_dir.clone(),
+            name_regex: self.name_regex.as_deref().map(file_name_regex).transpose()?,
+            max_files: self.max_files,
+            order: self.order,
+            min_file_age: self.min_file_age.map(Duration::from_secs),
+            use_summary_metadata: self.use_metadata_file,
+            read_archives: true,
+            unreadable_paths: self.unreadable_paths,
+            exclude_paths: Vec::new(),
+            follow_file_symlinks: !self.no_follow_symlinks,
+            file_dates: self.filename_date_pattern.clone().zip(self.date_range).map(|(pattern, range)| FileDateFilter { pattern, range }),
+            warnings: WarningLog::new(true),
+        })
+    }
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Consolidate the input files into one output, as when no subcommand is given
+    Consolidate(Box<ConsolidateArgs>),
+    /// Sample the input files and print a per-column profile
+    Profile(ProfileArgs),
+    /// Render the merged schema of the input files as a document
+    Schema(SchemaArgs),
+    /// Show when columns appeared, disappeared or changed type across the input files
+    Drift(DriftArgs),
+    /// Salvage the readable row groups of a damaged parquet file into a new file
+    Repair(RepairArgs),
+    /// Show or edit the key-value metadata in a parquet footer, without rewriting data
+    Meta(MetaArgs),
+    /// Compact small files and expire old ones as a policy file requires
+    Compact(CompactArgs),
+    /// List the versions recorded for an output written with --versioned
+    Versions(VersionsArgs),
+    /// Consolidate the datasets defined in a dataset config file, when their schedules are due
+    Run(RunArgs),
+    /// Verify the hash chain of an audit log written with --audit-log
+    Audit(AuditArgs),
+    /// Write a reproducible random parquet dataset, for benchmarks and bug reports
+    Generate(GenerateArgs),
+}
+
+#[derive(clap::Args)]
+struct ProfileArgs {
+    #[arg(short, long)]
+    input: PathBuf,
+    #[command(flatten)]
+    discovery: DiscoveryArgs,
+    /// Number of rows to sample from the start of the dataset
+    #[arg(long, default_value_t = 100_000)]
+    sample_rows: usize,
+    /// Convert legacy INT96 timestamps to this unit (us, ms, ns)
+    #[arg(long)]
+    coerce_int96: Option<TimestampUnit>,
+}
+
+#[derive(clap::Args)]
+struct SchemaArgs {
+    #[arg(short, long)]
+    input: PathBuf,
+    #[command(flatten)]
+    discovery: DiscoveryArgs,
+    /// Document format (markdown, html)
+    #[arg(long, default_value = "markdown")]
+    format: SchemaDocFormat,
+    /// Convert legacy INT96 timestamps to this unit (us, ms, ns)
+    #[arg(long)]
+    coerce_int96: Option<TimestampUnit>,
+}
+
+#[derive(clap::Args)]
+struct DriftArgs {
+    #[arg(short, long)]
+    input: PathBuf,
+    #[command(flatten)]
+    discovery: DiscoveryArgs,
+    /// Order files by the value of this hive partition directory, e.g. "date", instead of modification time
+    #[arg(long)]
+    partition_key: Option<String>,
+    /// Convert legacy INT96 timestamps to this unit (us, ms, ns)
+    #[arg(long)]
+    coerce_int96: Option<TimestampUnit>,
+}
+
+#[derive(clap::Args)]
+struct RepairArgs {
     #[arg(short, long)]
     input: PathBuf,
     #[arg(short, long)]
     output: PathBuf,
+    /// Healthy parquet file with the same schema, used to rebuild an unreadable footer
+    #[arg(long)]
+    schema_from: Option<PathBuf>,
+}
+
+#[derive(clap::Args)]
+struct MetaArgs {
+    #[arg(short, long)]
+    input: PathBuf,
+    /// Add or replace a metadata entry, e.g. "owner=analytics" (repeatable)
+    #[arg(long)]
+    set: Vec<MetadataEdit>,
+    /// Remove the metadata entry with this key (repeatable)
+    #[arg(long)]
+    remove: Vec<String>,
+}
+
+#[derive(clap::Args)]
+struct CompactArgs {
+    #[arg(short, long)]
+    input: PathBuf,
+    #[command(flatten)]
+    discovery: DiscoveryArgs,
+    /// TOML file with the small-file threshold, target size, partitioning and retention rules
+    #[arg(long)]
+    policy: PathBuf,
+    /// Merge the outputs of earlier compactions that are small against the target size into
+    /// larger generations, instead of compacting small files
+    #[arg(long, default_value_t = false)]
+    recompact: bool,
+    /// With --recompact, also merge the outputs listed in this manifest, written by consolidate --manifest, as
+    /// generation 1; repeatable
+    #[arg(long, value_name = "PATH", requires = "recompact")]
+    manifest: Vec<PathBuf>,
+    /// Print the planned compactions and deletions without executing them
+    #[arg(long, default_value_t = false)]
+    dry_run: bool,
+    /// Maximum number of compactions run concurrently (0: one per CPU)
+    #[arg(long, default_value_t = 1)]
+    jobs: usize,
+    /// Delete compacted and expired files without asking for confirmation on a terminal
     #[arg(short, long, default_value_t = false)]
-    recursive: bool,
-    #[arg(short, long, default_value_t = false)]
-    verbose: bool,
+    yes: bool,
+    /// Append a record of the compaction, including every deleted file, to this JSON-lines file
+    #[arg(long, value_name = "FILE")]
+    audit_log: Option<PathBuf>,
+}
+
+#[derive(clap::Args)]
+struct AuditArgs {
+    /// The JSON-lines audit log
+    #[arg(required_unless_present = "output", conflicts_with = "output")]
+    log: Option<PathBuf>,
+    /// Re-check this output against the manifest written with --manifest instead
+    #[arg(long, requires = "manifest")]
+    output: Option<PathBuf>,
+    /// The manifest recorded when the output was written
+    #[arg(long, requires = "output")]
+    manifest: Option<PathBuf>,
+}
+
+#[derive(clap::Args)]
+struct VersionsArgs {
+    /// The output file or directory, as given to --output
+    output: PathBuf,
+    /// Print the versions as JSON
+    #[arg(long, default_value_t = false)]
+    json: bool,
+}
+
+#[derive(clap::Args)]
+struct RunArgs {
+    /// TOML file defining the datasets, each with its input, output, partitioning and schedule
+    #[arg(long, value_name = "FILE")]
+    config: PathBuf,
+    /// Dataset to run, or "all" for every dataset in the config
+    #[arg(long, default_value = ALL_DATASETS)]
+    dataset: String,
+    /// Run the selected datasets even when their schedule isn't due yet
+    #[arg(long, default_value_t = false)]
+    force: bool,
+    /// Maximum number of outputs of a dataset written concurrently (0: one per CPU)
+    #[arg(long, default_value_t = 1)]
+    jobs: usize,
+    /// Keep running as a service, running each dataset again whenever it is due
+    #[arg(long, default_value_t = false, conflicts_with = "force")]
+    watch: bool,
+    /// With --watch, check the schedules at least this often; datasets without a schedule run this often
+    #[arg(long, value_name = "SECS", default_value_t = 60, requires = "watch")]
+    poll_interval: u64,
+    /// With --watch, serve /healthz and /readyz on this address, e.g. 0.0.0.0:8080
+    #[arg(long, value_name = "HOST:PORT", requires = "watch")]
+    health_addr: Option<String>,
+    /// Fail /healthz once no pass over the datasets has succeeded for this many seconds
+    #[arg(long, value_name = "SECS", requires = "health_addr")]
+    max_success_age: Option<u64>,
+}
+
+#[derive(clap::Args)]
+struct GenerateArgs {
+    /// Directory the dataset is written into
+    #[arg(short, long)]
+    output: PathBuf,
+    /// Number of files
+    #[arg(long, default_value_t = 5)]
+    files: usize,
+    /// Rows in each file
+    #[arg(long, default_value_t = 100)]
+    rows: usize,
+    /// Comma-separated name:type columns (int32, int64, float64, bool, string, date, timestamp)
+    #[arg(long, value_delimiter = ',', default_value = "id:int32,name:string,value:float64")]
+    schema: Vec<ColumnSpec>,
+    /// Spread the files over directories nested up to this deep
+    #[arg(long, default_value_t = 1)]
+    nesting_depth: usize,
+    /// Fraction of values in each column that are null, e.g. 0.1
+    #[arg(long, default_value_t = 0.0)]
+    null_ratio: f64,
+    /// Random seed; the same seed and options always produce the same files
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
 }
 
+/// Environment variable turning on polars' diagnostic output
+const POLARS_VERBOSE_VAR: &str = "POLARS_VERBOSE";
+
 fn main() -> Result<()> {
     let args = Args::parse();
+    // Set before any other thread starts, as polars reads it from all of them
+    if args.verbose >= 2 {
+        std::env::set_var(POLARS_VERBOSE_VAR, "1");
+    }
+    #[cfg(feature = "fault-injection")]
+    parquet_consolidator::install_faults(&args.fault_inject)?;
+    clean_up_workspaces_on_interrupt()?;
+
+    match args.command {
+        _ if args.version => version(args.verbose > 0, args.json),
+        Some(Command::Consolidate(consolidate_args)) => consolidate(*consolidate_args, args.verbose),
+        Some(Command::Profile(profile_args)) => profile(profile_args, args.verbose > 0),
+        Some(Command::Schema(schema_args)) => schema(schema_args, args.verbose > 0),
+        Some(Command::Drift(drift_args)) => drift(drift_args, args.verbose > 0),
+        Some(Command::Repair(repair_args)) => repair(repair_args, args.verbose > 0),
+        Some(Command::Meta(meta_args)) => meta(meta_args, args.verbose > 0),
+        Some(Command::Compact(compact_args)) => compact(compact_args, args.verbose > 0),
+        Some(Command::Versions(versions_args)) => versions(versions_args, args.verbose > 0),
+        Some(Command::Run(run_args)) => run(run_args, args.verbose > 0),
+        Some(Command::Audit(audit_args)) => audit(audit_args, args.verbose > 0),
+        Some(Command::Generate(generate_args)) => generate(generate_args, args.verbose > 0),
+        None => consolidate(args.consolidate, args.verbose),
+    }
+}
+
+fn version(verbose: bool, json: bool) -> Result<()> {
+    let info = BuildInfo::current();
+    if json {
+        println!("{}", serde_json::to_string_pretty(&info)?);
+    } else if verbose {
+        print!("{}", info);
+    } else {
+        println!("parquet_consolidator {}", info.version);
+    }
+    Ok(())
+}
+
+#[cfg(feature = "otel")]
+fn otlp_endpoint(args: &ConsolidateArgs) -> Option<String> {
+    args.otlp_endpoint.clone()
+}
+
+#[cfg(not(feature = "otel"))]
+fn otlp_endpoint(_args: &ConsolidateArgs) -> Option<String> {
+    None
+}
+
+/// The registry schema the output is conformed to, fetched from the registry
+#[cfg(feature = "schema-registry")]
+fn registry_schema(
+    url: Option<&str>,
+    subject: Option<&str>,
+    version: Option<u64>,
+) -> Result<Option<std::sync::Arc<parquet_consolidator::RegistrySchema>>> {
+    let (Some(url), Some(subject)) = (url, subject) else {
+        return Ok(None);
+    };
+    let schema = parquet_consolidator::RegistrySchema::fetch(url, subject, version)?;
+    if schema.fields.iter().any(|field| matches!(field.data_type, arrow::datatypes::DataType::Decimal128(_, _))) {
+        // Keeps the conformed decimals from being turned back into floats
+        std::env::set_var(DECIMAL_ACTIVATION_VAR, "1");
+    }
+    println!("Conforming to schema {} version {} (id {})", schema.subject, schema.version, schema.id);
+    Ok(Some(std::sync::Arc::new(schema)))
+}
+
+/// Send the trace of the run to the collector; a collector being down
+/// doesn't fail the run
+#[cfg(feature = "otel")]
+fn export_trace(tracer: &Tracer, endpoint: &str) {
+    let service_name = std::env::var("OTEL_SERVICE_NAME").unwrap_or_else(|_| "parquet_consolidator".to_string());
+    if let Err(err) = tracer.export_otlp(endpoint, &service_name) {
+        eprintln!("Warning: Failed to export the trace: {:#}", err);
+    }
+}
+
+#[cfg(not(feature = "otel"))]
+fn export_trace(_tracer: &Tracer, _endpoint: &str) {}
+
+/// The input files of a subcommand reading them, saying how many were
+/// found with `verbose`
+fn discover_inputs(input: &Path, discovery: &DiscoveryArgs, verbose: bool) -> Result<Vec<PathBuf>> {
+    let discovery = discovery.options()?;
+    let input_files = discover_input_files(input, &discovery)?;
+    if input_files.is_empty() {
+        anyhow::bail!("No {} files found in the specified directory", discovery.format_names());
+    }
+    if verbose {
+        println!("Found {} input files in {:?}", input_files.len(), input);
+    }
+    Ok(input_files)
+}
+
+fn profile(args: ProfileArgs, verbose: bool) -> Result<()> {
+    let input_files = discover_inputs(&args.input, &args.discovery, verbose)?;
+    let read = ReadOptions { coerce_int96: args.coerce_int96, ..Default::default() };
+    print!("{}", profile_files(&input_files, &read, args.sample_rows)?);
+    Ok(())
+}
+
+fn schema(args: SchemaArgs, verbose: bool) -> Result<()> {
+    let read = ReadOptions { coerce_int96: args.coerce_int96, ..Default::default() };
+    if args.discovery.use_metadata_file {
+        if let Some(summary) = summary_schema_file(&args.input) {
+            if verbose {
+                println!("Reading the schema from {:?}", summary);
+            }
+            print!("{}", render_schema(&merged_schema(&[summary], &read)?, args.format));
+            return Ok(());
+        }
+    }
+
+    let input_files = discover_inputs(&args.input, &args.discovery, verbose)?;
+    print!("{}", render_schema(&merged_schema(&input_files, &read)?, args.format));
+    Ok(())
+}
+
+fn drift(args: DriftArgs, verbose: bool) -> Result<()> {
+    let input_files = discover_inputs(&args.input, &args.discovery, verbose)?;
+    let read = ReadOptions { coerce_int96: args.coerce_int96, ..Default::default() };
+    print!("{}", drift_report(&input_files, &read, args.partition_key.as_deref())?);
+    Ok(())
+}
+
+fn repair(args: RepairArgs, verbose: bool) -> Result<()> {
+    if let Some(schema_from) = args.schema_from.as_ref().filter(|_| verbose) {
+        println!("Reading the schema of {:?} from {:?}", args.input, schema_from);
+    }
+    let summary = repair_file(&args.input, &args.output, args.schema_from.as_deref(), &WriterOptions::default())?;
+    println!("Recovered {:?} into {:?}", args.input, args.output);
+    print!("{}", summary);
+    Ok(())
+}
+
+fn meta(args: MetaArgs, verbose: bool) -> Result<()> {
+    let removals = args.remove.into_iter().map(|key| MetadataEdit::Remove { key });
+    let edits: Vec<MetadataEdit> = args.set.into_iter().chain(removals).collect();
+    let entries = if edits.is_empty() {
+        read_key_value_metadata(&args.input)?
+    } else {
+        let entries = edit_key_value_metadata(&args.input, &edits)?;
+        if verbose {
+            println!("Applied {} edits to the footer of {:?}", edits.len(), args.input);
+        }
+        entries
+    };
+
+    for (key, value) in entries {
+        println!("{}={}", key, value.unwrap_or_default());
+    }
+    Ok(())
+}
+
+fn compact(args: CompactArgs, verbose: bool) -> Result<()> {
+    let command = CompactCommand {
+        input: args.input,
+        policy: CompactionPolicy::from_file(&args.policy)?,
+        discovery: args.discovery.options()?,
+        recompact: args.recompact,
+        manifests: args.manifest.iter().map(|path| OutputManifest::load(path)).collect::<Result<_>>()?,
+        audit_log: args.audit_log,
+    };
+    let plan = command.plan(SystemTime::now())?;
+    print!("{}", plan);
+    if args.dry_run || plan.is_empty() {
+        return Ok(());
+    }
+
+    if !args.yes && std::io::stdin().is_terminal() {
+        let summary = "The files listed above will be deleted once their data is compacted.\n";
+        if !confirm(summary, std::io::stdin().lock(), std::io::stderr())? {
+            anyhow::bail!("Aborted: no files were changed");
+        }
+    }
+
+    let parallelism = job_parallelism(args.jobs, plan.tasks.len(), None);
+    let options = ConsolidateOptions { verbose, ..Default::default() };
+    print!("{}", command.execute(&plan, &options, parallelism)?);
+    Ok(())
+}
+
+fn versions(args: VersionsArgs, verbose: bool) -> Result<()> {
+    let log = version_log_path(&args.output, args.output.is_dir());
+    if verbose {
+        println!("Reading versions from {:?}", log);
+    }
+    let versions = read_versions(&log)?;
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&versions)?);
+    } else if versions.is_empty() {
+        println!("No versions recorded in {:?}", log);
+    } else {
+        for version in &versions {
+            print!("{}", version);
+        }
+    }
+    Ok(())
+}
+
+fn run(args: RunArgs, verbose: bool) -> Result<()> {
+    let command = RunCommand {
+        config: DatasetConfig::from_file(&args.config)?,
+        selection: args.dataset,
+        force: args.force,
+        poll_interval: Duration::from_secs(args.poll_interval),
+    };
+    let parallelism = job_parallelism(args.jobs, usize::MAX, None);
+    let options = ConsolidateOptions { verbose, ..Default::default() };
+    if !args.watch {
+        let outcomes = command.run_once(&options, parallelism)?;
+        let failed = report_outcomes(&outcomes);
+        if failed > 0 {
+            anyhow::bail!("{} of {} datasets failed", failed, outcomes.len());
+        }
+        return Ok(());
+    }
+
+    let health = Arc::new(HealthState::new(args.max_success_age.map(Duration::from_secs)));
+    if let Some(addr) = &args.health_addr {
+        let bound = serve_health(addr, health.clone())?;
+        println!("Serving {} and {} on {}", LIVENESS_PATH, READINESS_PATH, bound);
+    }
+    command.watch(&options, parallelism, &health, &mut |outcomes| match outcomes {
+        Ok(outcomes) => {
+            report_outcomes(outcomes);
+        }
+        Err(err) => eprintln!("Error: {:#}", err),
+    })
+}
+
+/// Print what each dataset did, returning how many failed
+fn report_outcomes(outcomes: &[(String, DatasetOutcome)]) -> usize {
+    let mut failed = 0;
+    for (name, outcome) in outcomes {
+        println!("{}: {}", name, outcome);
+        if let DatasetOutcome::Failed(errors) = outcome {
+            for err in errors {
+                eprintln!("Error: {}: {:#}", name, err);
+            }
+            failed += 1;
+        }
+    }
+    failed
+}
+
+fn audit(args: AuditArgs, verbose: bool) -> Result<()> {
+    if let (Some(output), Some(manifest)) = (&args.output, &args.manifest) {
+        if verbose {
+            println!("Verifying {:?} against the manifest {:?}", output, manifest);
+        }
+        let verification = verify_output(output, &OutputManifest::load(manifest)?)?;
+        print!("{}", verification);
+        if !verification.is_intact() {
+            anyhow::bail!("Output {:?} failed verification against {:?}", output, manifest);
+        }
+        return Ok(());
+    }
+    let log = args.log.expect("the audit log is required without --output");
+    if verbose {
+        println!("Verifying the hash chain of {:?}", log);
+    }
+    let verification = verify_audit_log(&log)?;
+    print!("{}", verification);
+    if verification.broken.is_some() {
+        anyhow::bail!("Audit log {:?} failed verification", log);
+    }
+    Ok(())
+}
+
+fn generate(args: GenerateArgs, verbose: bool) -> Result<()> {
+    let options = GenerateOptions {
+        files: args.files,
+        rows: args.rows,
+        columns: args.schema,
+        nesting_depth: args.nesting_depth,
+        null_ratio: args.null_ratio,
+        seed: args.seed,
+    };
+    let paths = generate_dataset(&args.output, &options)?;
+    if verbose {
+        for path in &paths {
+            println!("Wrote {:?}", path);
+        }
+    }
+    println!("Generated {} files of {} rows in {:?}", paths.len(), options.rows, args.output);
+    Ok(())
+}
+
+fn consolidate(args: ConsolidateArgs, verbose: u8) -> Result<()> {
+    let started = Instant::now();
+    let otlp_endpoint = otlp_endpoint(&args);
+    let statsd = args.statsd.as_deref().map(StatsdSink::connect).transpose()?;
+    if args.normalize_legacy_types {
+        // polars only keeps decimals as decimals, rather than converting
+        // them to floats, while this is set
+        std::env::set_var(DECIMAL_ACTIVATION_VAR, "1");
+    }
+    // Required by clap whenever no subcommand is given; --input may be
+    // replaced by --files-from
+    let output = args.output.expect("--output is required");
+
+    if args.max_rows_per_file == Some(0) {
+        anyhow::bail!("--max-rows-per-file must be at least 1");
+    }
+    if is_named_pipe(&output) {
+        // Whatever reads the pipe consumes the output, so nothing can read
+        // it back, rewrite or rename it afterwards
+        let read_back = [
+            (args.max_rows_per_file.is_some(), "--max-rows-per-file"),
+            (args.name_by_range.is_some(), "--name-by-range"),
+            (args.assert_sorted_by.is_some(), "--assert-sorted-by"),
+            (args.null_report, "--null-report"),
+            (args.stats_output.is_some(), "--stats-output"),
+            (args.versioned, "--versioned"),
+            (args.audit_log.is_some(), "--audit-log"),
+            (args.manifest.is_some(), "--manifest"),
+        ];
+        #[cfg(feature = "schema-registry")]
+        let read_back = [read_back.as_slice(), &[(args.schema_registry.is_some(), "--schema-registry")]].concat();
+        if let Some((_, option)) = read_back.iter().find(|(set, _)| *set) {
+            anyhow::bail!("{} cannot be combined with writing to the named pipe {:?}", option, output);
+        }
+    }
+    #[cfg(feature = "schema-registry")]
+    let registry_schema = registry_schema(args.schema_registry.as_deref(), args.schema_subject.as_deref(), args.schema_version)?;
+
+    let input_list = match &args.files_from {
+        Some(list) if list.as_os_str() == "-" => Some(read_file_list(std::io::stdin().lock(), args.null)?),
+        Some(list) => {
+            let file = File::open(list).context(format!("Failed to open file list {:?}", list))?;
+            Some(read_file_list(BufReader::new(file), args.null)?)
+        }
+        None => None,
+    };
+    let command = ConsolidateCommand {
+        input: args.input,
+        input_list,
+        output,
+        layout: match (args.per_directory, args.mirror_structure) {
+            (true, _) => JobLayout::PerDirectory,
+            (_, true) => JobLayout::Mirror,
+            _ => JobLayout::Single,
+        },
+        discovery: args.discovery.options()?,
+        output_in_input: args.output_in_input,
+        run_id_dirs: args.run_id_dirs,
+        skip_duplicate_inputs: args.skip_duplicate_inputs,
+        if_exists: args.if_exists,
+        partition_overwrite: args.partition_overwrite,
+        max_rows_per_file: args.max_rows_per_file,
+        name_by_range: args.name_by_range,
+        assert_sorted_by: args.assert_sorted_by,
+        versioned: args.versioned,
+        manifest: args.manifest,
+        remaining_inputs: args.remaining_inputs,
+    };
+    let plan = command.plan(&DefaultNamer)?;
+    for duplicates in &plan.duplicate_inputs {
+        println!("{}", duplicates);
+    }
+    let jobs = &plan.jobs;
+    let parallelism = job_parallelism(args.jobs, jobs.len(), args.max_open_files);
+
+    if let (Some(max), Some(limit)) = (args.max_open_files, open_file_limit()) {
+        if max > limit {
+            eprintln!("Warning: --max-open-files {} is above the process's open file limit of {}", max, limit);
+        }
+    }
+
+    let run_span = otlp_endpoint.as_ref().map(|_| Tracer::default().start_span("parquet_consolidator"));
+    #[cfg(feature = "schema-registry")]
+    let transforms: Vec<std::sync::Arc<dyn parquet_consolidator::RowTransform>> = registry_schema
+        .iter()
+        .map(|schema| schema.clone() as std::sync::Arc<dyn parquet_consolidator::RowTransform>)
+        .collect();
+    #[cfg(not(feature = "schema-registry"))]
+    let transforms = Vec::new();
+
+    let auto_compression = args.compression.eq_ignore_ascii_case("auto");
+    let options = ConsolidateOptions {
+        verbose: verbose > 0,
+        read: ReadOptions {
+            coerce_int96: args.coerce_int96,
+            normalize_legacy_types: args.normalize_legacy_types,
+            columns: ColumnSelection { include: args.columns_regex, exclude: args.drop_columns_regex },
+            union_columns: args.union_schemas,
+            fill_missing: args.fill_missing,
+            // Picked for each run from the number of inputs and row groups
+            row_group_parallel: false,
+            row_groups: args.row_groups,
+        },
+        writer: WriterOptions {
+            compression: if auto_compression { Compression::SNAPPY } else { parse_codec(&args.compression)? },
+            auto_compression,
+            column_compression: args.compression_per_column,
+            statistics_truncate_length: args.stats_truncate_length,
+            disabled_statistics: args.no_stats_columns,
+            column_encodings: args.encoding_per_column,
+            min_row_group_rows: args.min_row_group_rows,
+            max_row_group_rows: args.max_row_group_rows,
+            compat: args.compat,
+            target_reader: args.target_reader,
+            created_by: args.created_by.unwrap_or_else(default_created_by),
+            ..Default::default()
+        },
+        preserve_row_groups: args.preserve_row_groups,
+        checks: args.checks.as_deref().map(QualityChecks::from_file).transpose()?,
+        quarantine_path: args.quarantine,
+        redactions: args.redact,
+        transforms,
+        dedupe_keys: args.dedupe_key,
+        dedupe_state: args.dedupe_state,
+        cdc: (!args.cdc_key.is_empty()).then_some(CdcOptions {
+            keys: args.cdc_key,
+            op_column: args.cdc_op_column,
+            order_by: args.cdc_order_by,
+        }),
+        report_duplicates: args.report_duplicates,
+        anomaly_warnings: !args.no_anomaly_warnings,
+        validate_statistics: args.validate_stats,
+        timings: args.timings,
+        trace: run_span.as_ref().map(Span::as_parent),
+        continue_on_error: args.continue_on_error,
+        abort_threshold: args.abort_threshold,
+        max_open_files: open_file_budget(args.max_open_files, parallelism),
+        auto_batch: args.auto_batch,
+        nullability: args.nullability_conflicts,
+        field_metadata: args.field_metadata_conflicts,
+        file_timeout: args.file_timeout.map(Duration::from_secs),
+        pipeline: args.pipeline.then(|| PipelineOptions {
+            readers: args.pipeline_readers.unwrap_or(0),
+            ..Default::default()
+        }),
+        deadline: args.max_runtime.map(|budget| started + budget.0),
+        snapshot: args.snapshot_inputs.map(|policy| {
+            Arc::new(InputSnapshot::capture(jobs.iter().flat_map(|job| job.input_files.clone()), policy))
+        }),
+        // Signals stop the command line, cleaning up its workspaces
+        cancel: None,
+        print_warnings: true,
+        content_digest: args.content_digest,
+    };
+
+    if args.explain {
+        for job in jobs {
+            if !job.name.is_empty() {
+                println!("{}:", job.name);
+            }
+            print!("{}", explain_consolidation(&job.input_files, &options)?);
+        }
+        return Ok(());
+    }
+
+    if args.check_schemas_first {
+        let clock = Instant::now();
+        for job in jobs {
+            let checked = check_input_schemas(&job.input_files, &options);
+            match job.name.is_empty() {
+                true => checked,
+                false => checked.context(format!("Schemas of {} don't merge", job.name)),
+            }
+            .context("Schema check failed before any data was read")?;
+        }
+        if verbose > 0 {
+            let inputs: usize = jobs.iter().map(|job| job.input_files.len()).sum();
+            println!("Checked the schemas of {} inputs in {:.3}s", inputs, clock.elapsed().as_secs_f64());
+        }
+    }
+
+    for existing in &plan.existing_outputs {
+        println!("Skipping {:?}: output already exists", existing);
+    }
+    let replaced = plan.replaced_files();
+    if !replaced.is_empty() && !args.yes && std::io::stdin().is_terminal() {
+        let summary = overwrite_summary(&replaced);
+        if !confirm(&summary, std::io::stdin().lock(), std::io::stderr())? {
+            anyhow::bail!("Aborted: existing outputs were left unchanged");
+        }
+    }
+
+    let audit_record = match &args.audit_log {
+        Some(_) => {
+            let mut record = AuditRecord::new("consolidate");
+            record.inputs = AuditRecord::file_records(jobs.iter().flat_map(|job| &job.input_files))?;
+            record.replaced = AuditRecord::file_records(&replaced)?;
+            Some(record)
+        }
+        None => None,
+    };
 
-    let parquet_files = find_parquet_files(&args.input, args.recursive)?;
+    let job_count = jobs.len();
+    if args.timings && job_count > 1 {
+        println!("Discovery of {} jobs took {:.3}s", job_count, plan.discovery_timing.wall.as_secs_f64());
+    }
+    let run_id = plan.run_id.clone();
+    #[cfg(feature = "schema-registry")]
+    let mut finish_output = |output: &Path| match &registry_schema {
+        Some(schema) => schema.stamp(output),
+        None => Ok(()),
+    };
+    #[cfg(not(feature = "schema-registry"))]
+    let mut finish_output = |_: &Path| Ok(());
+    let mut outcome = command.execute(plan, &options, parallelism, &DefaultNamer, &mut finish_output)?;
+
+    for deferred in &outcome.deferred {
+        println!("Deferred {:?}: the runtime budget ran out before it started", deferred);
+    }
+    if let Some(max_rows) = command.max_rows_per_file {
+        for (output, parts) in &outcome.split {
+            println!("Split {:?} into {} files of at most {} rows", output, parts, max_rows);
+        }
+    }
+    for part in &outcome.existing_range_names {
+        println!("Skipping {:?}: range-named output already exists", part);
+    }
+    for job in &outcome.completed {
+        for output in &job.outputs {
+            println!("Successfully consolidated files into {:?}", output);
+        }
+        print!("{}", job.summary);
+
+        for output in &job.outputs {
+            if args.null_report {
+                print!("{}", null_report(output)?);
+            }
+
+            if let Some(stats_path) = &args.stats_output {
+                output_stats(output)?.write(stats_path)?;
+            }
+        }
+    }
+
+    if let (Some(budget), Some(list)) = (args.max_runtime, &outcome.remaining_list) {
+        if !outcome.remaining.is_empty() {
+            eprintln!(
+                "Warning: the runtime budget of {} ran out; {} inputs were left unread and are listed in {:?} for --files-from",
+                budget,
+                outcome.remaining.len(),
+                list
+            );
+        }
+    }
+    if let Some((version, log)) = &outcome.version {
+        println!("Recorded version {} in {:?}", version.version, log);
+    }
+    if let Some(run_id) = &run_id {
+        let latest = command.output.join(LATEST_FILE_NAME);
+        if !outcome.failures.is_empty() {
+            eprintln!("Warning: {:?} still points at the previous run, as this run failed", latest);
+        } else if !outcome.remaining.is_empty() {
+            eprintln!("Warning: {:?} still points at the previous run, as this run left inputs unread", latest);
+        } else if outcome.latest_updated {
+            println!("Run {} is now the latest in {:?}", run_id, command.output);
+        }
+    }
+    if verbose > 0 && !outcome.deleted.is_empty() {
+        println!("Deleted {} replaced files", outcome.deleted.len());
+    }
+
+    if let (Some(mut span), Some(endpoint)) = (run_span, &otlp_endpoint) {
+        span.set_attribute("jobs", job_count);
+        let tracer = span.tracer().clone();
+        span.end(&match outcome.failures.len() {
+            0 => Ok(()),
+            failed => Err(format!("{} of {} jobs failed", failed, job_count)),
+        });
+        export_trace(&tracer, endpoint);
+    }
 
-    if parquet_files.is_empty() {
-        anyhow::bail!("No parquet files found in the specified directory");
+    let written = outcome.written();
+    if let Some(manifest) = command.manifest.as_ref().filter(|_| verbose > 0 && !written.is_empty()) {
+        println!("Recorded {} outputs in the manifest {:?}", written.len(), manifest);
     }
 
-    consolidate_parquet_files(&parquet_files, &args.output, args.verbose)?;
+    if let (Some(mut record), Some(log)) = (audit_record, &args.audit_log) {
+        record.outputs = AuditRecord::file_records(&written)?;
+        record.rows_written = outcome.rows_written();
+        record.deleted = std::mem::take(&mut outcome.deleted);
+        record.errors = outcome.failures.iter().map(|err| format!("{:#}", err)).collect();
+        append_audit_record(log, record)?;
+    }
+
+    let completed: Vec<RunSummary> = outcome.completed.into_iter().map(|job| job.summary).collect();
+    let mut failures = outcome.failures;
+    let remaining = outcome.remaining;
+    if let Some(statsd) = &statsd {
+        if let Err(err) = statsd.send(&run_metrics(&completed, failures.len(), started.elapsed())) {
+            eprintln!("Warning: {:#}", err);
+        }
+    }
 
-    println!("Successfully consolidated files into {:?}", args.output);
+    if job_count == 1 {
+        if let Some(err) = failures.pop() {
+            return Err(err);
+        }
+    } else if !failures.is_empty() {
+        for err in &failures {
+            eprintln!("Error: {:#}", err);
+        }
+        anyhow::bail!("{} of {} jobs failed", failures.len(), job_count);
+    }
+    if !remaining.is_empty() {
+        std::process::exit(PARTIAL_SUCCESS_EXIT_CODE);
+    }
     Ok(())
 }
@@ -0,0 +1,213 @@
+use std::fmt;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use anyhow::{Result, Context};
+use chrono::{SecondsFormat, Utc};
+use parquet::file::reader::{FileReader, SerializedFileReader};
+use serde::{Deserialize, Serialize};
+use crate::audit::FileRecord;
+use crate::content_digest::content_digest;
+use crate::formats::InputFormat;
+
+/// An output as the run that wrote it left it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ManifestOutput {
+    pub path: PathBuf,
+    pub bytes: u64,
+    /// XXH3-128 of the contents, as 32 hex digits
+    pub xxh3: String,
+    /// Row count from the parquet footer; unset for other formats
+    pub rows: Option<u64>,
+    /// The parquet schema as printed from the footer; unset for other
+    /// formats
+    pub schema: Option<String>,
+    /// Order-insensitive digest of the rows, to compare with other runs'
+    /// outputs; unset for other formats. See [`content_digest`]
+    pub content_digest: Option<String>,
+}
+
+/// Row count and printed schema of a parquet file's footer
+fn parquet_footer(path: &Path) -> Result<(u64, String)> {
+    let reader = SerializedFileReader::new(File::open(path).context(format!("Failed to open {:?}", path))?)
+        .context(format!("Failed to read parquet footer of {:?}", path))?;
+    let file_metadata = reader.metadata().file_metadata();
+    let mut schema = Vec::new();
+    parquet::schema::printer::print_schema(&mut schema, file_metadata.schema());
+    Ok((file_metadata.num_rows().max(0) as u64, String::from_utf8_lossy(&schema).into_owned()))
+}
+
+impl ManifestOutput {
+    /// Size, checksum and, for parquet files, row count, schema and content
+    /// digest of the file at `path`, reading it in full
+    pub fn of(path: &Path) -> Result<Self> {
+        let record = FileRecord::of(path)?;
+        let (rows, schema, content_digest) = match InputFormat::from_path(path) {
+            Some(InputFormat::Parquet) => {
+                let (rows, schema) = parquet_footer(path)?;
+                (Some(rows), Some(schema), Some(content_digest(&[path.to_path_buf()])?))
+            }
+            _ => (None, None, None),
+        };
+        Ok(ManifestOutput { path: record.path, bytes: record.bytes, xxh3: record.xxh3, rows, schema, content_digest })
+    }
+}
+
+/// What a run wrote, recorded so the outputs can be re-checked long after
+/// the run, such as in periodic audits of archived consolidations
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OutputManifest {
+    /// When the manifest was written, in UTC
+    pub created: String,
+    pub version: String,
+    pub outputs: Vec<ManifestOutput>,
+}
+
+impl OutputManifest {
+    /// A manifest of `outputs`, each read in full to checksum it
+    pub fn of(outputs: &[PathBuf]) -> Result<Self> {
+        Ok(OutputManifest {
+            created: Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            outputs: outputs.iter().map(|path| ManifestOutput::of(path)).collect::<Result<_>>()?,
+        })
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let json = fs::read_to_string(path).context(format!("Failed to read manifest {:?}", path))?;
+        serde_json::from_str(&json).context(format!("Failed to parse manifest {:?}", path))
+    }
+
+    /// Write the manifest as JSON under a temporary name and rename it into
+    /// place, so a reader never sees half a manifest
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let mut staging = path.as_os_str().to_owned();
+        staging.push(".tmp");
+        fs::write(&staging, serde_json::to_string_pretty(self)?).context(format!("Failed to write {:?}", staging))?;
+        fs::rename(&staging, path).context(format!("Failed to move manifest {:?} into place", path))
+    }
+
+    /// The entry of `output`: the one recorded under the same path, or
+    /// else the only one with the same file name, so an output moved to an
+    /// archive is still found
+    pub fn entry(&self, output: &Path) -> Option<&ManifestOutput> {
+        self.outputs.iter().find(|entry| entry.path == output).or_else(|| {
+            let mut named = self.outputs.iter().filter(|entry| entry.path.file_name() == output.file_name());
+            match (named.next(), named.next()) {
+                (Some(entry), None) => Some(entry),
+                _ => None,
+            }
+        })
+    }
+}
+
+/// The outcome of re-checking an output against its manifest
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutputVerification {
+    pub path: PathBuf,
+    /// What differs from the manifest; empty when the output matches
+    pub mismatches: Vec<String>,
+}
+
+impl OutputVerification {
+    pub fn is_intact(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+}
+
+impl fmt::Display for OutputVerification {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_intact() {
+            return writeln!(f, "{:?} matches its manifest", self.path);
+        }
+        writeln!(f, "{:?} does not match its manifest:", self.path)?;
+        for mismatch in &self.mismatches {
+            writeln!(f, "  {}", mismatch)?;
+        }
+        Ok(())
+    }
+}
+
+/// Re-check an output's size, checksum, row count and schema against the
+/// manifest the run that wrote it recorded
+///
+/// Fails when the manifest has no entry for the output; a mismatch is
+/// reported in the verification instead.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs::OpenOptions;
+/// use parquet_consolidator::{verify_output, OutputManifest};
+/// use parquet_consolidator::test_utils::create_test_parquet_file;
+/// use tempfile::TempDir;
+///
+/// let temp_dir = TempDir::new().unwrap();
+/// let output = temp_dir.path().join("out.parquet");
+/// create_test_parquet_file(&output, 0, 10).unwrap();
+/// let manifest = OutputManifest::of(&[output.clone()]).unwrap();
+/// assert!(verify_output(&output, &manifest).unwrap().is_intact());
+///
+/// OpenOptions::new().append(true).open(&output).unwrap().set_len(100).unwrap();
+/// assert!(!verify_output(&output, &manifest).unwrap().is_intact());
+/// ```
+pub fn verify_output(output: &Path, manifest: &OutputManifest) -> Result<OutputVerification> {
+    let expected = manifest
+        .entry(output)
+        .with_context(|| format!("The manifest has no entry for {:?}", output))?;
+    let actual = FileRecord::of(output)?;
+    let mut mismatches = Vec::new();
+    if actual.bytes != expected.bytes {
+        mismatches.push(format!("{} bytes, where the manifest records {}", actual.bytes, expected.bytes));
+    }
+    if actual.xxh3 != expected.xxh3 {
+        mismatches.push(format!("checksum {}, where the manifest records {}", actual.xxh3, expected.xxh3));
+    }
+    if expected.rows.is_some() || expected.schema.is_some() {
+        match parquet_footer(output) {
+            Ok((rows, schema)) => {
+                if let Some(expected_rows) = expected.rows.filter(|&expected_rows| expected_rows != rows) {
+                    mismatches.push(format!("{} rows, where the manifest records {}", rows, expected_rows));
+                }
+                if expected.schema.as_ref().is_some_and(|expected_schema| *expected_schema != schema) {
+                    mismatches.push(format!("its schema differs from the manifest's:\n{}", schema.trim_end()));
+                }
+            }
+            Err(err) => mismatches.push(format!("{:#}", err)),
+        }
+    }
+    Ok(OutputVerification { path: output.to_path_buf(), mismatches })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::create_test_parquet_file;
+
+    #[test]
+    fn test_verify_output() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let output = temp_dir.path().join("out.parquet");
+        create_test_parquet_file(&output, 0, 10)?;
+        let manifest_path = temp_dir.path().join("manifest.json");
+        OutputManifest::of(std::slice::from_ref(&output))?.save(&manifest_path)?;
+        let manifest = OutputManifest::load(&manifest_path)?;
+        assert_eq!(manifest.outputs[0].rows, Some(10));
+        assert_eq!(manifest.outputs[0].content_digest, Some(content_digest(std::slice::from_ref(&output))?));
+
+        // Archived elsewhere, the output is found by its name
+        let archived = temp_dir.path().join("archive");
+        fs::create_dir_all(&archived)?;
+        fs::copy(&output, archived.join("out.parquet"))?;
+        assert!(verify_output(&archived.join("out.parquet"), &manifest)?.is_intact());
+
+        // Replaced by a file with more rows
+        create_test_parquet_file(&output, 0, 20)?;
+        let verification = verify_output(&output, &manifest)?;
+        assert!(verification.mismatches.iter().any(|mismatch| mismatch == "20 rows, where the manifest records 10"));
+        assert!(verification.to_string().contains("does not match its manifest"));
+
+        assert!(verify_output(&temp_dir.path().join("other.parquet"), &manifest).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,87 @@
+use std::fs;
+
+/// Peak resident set size of this process so far, in bytes
+///
+/// Read from the `VmHWM` line of `/proc/self/status`, so only available on
+/// Linux. Polars uses the system allocator here and keeps no allocation
+/// statistics of its own, which leaves RSS as the measure of what a run
+/// needed.
+pub fn peak_rss_bytes() -> Option<u64> {
+    parse_peak_rss(&fs::read_to_string("/proc/self/status").ok()?)
+}
+
+fn parse_peak_rss(status: &str) -> Option<u64> {
+    let line = status.lines().find_map(|line| line.strip_prefix("VmHWM:"))?;
+    let kib: u64 = line.trim().strip_suffix("kB")?.trim().parse().ok()?;
+    Some(kib * 1024)
+}
+
+/// Memory this process can still allocate without swapping, in bytes
+///
+/// The kernel's `MemAvailable` estimate from `/proc/meminfo`, lowered to
+/// what is left under a cgroup v2 memory limit when the process runs in a
+/// container, so only available on Linux.
+pub fn available_memory_bytes() -> Option<u64> {
+    let available = parse_mem_available(&fs::read_to_string("/proc/meminfo").ok()?)?;
+    let cgroup = fs::read_to_string("/sys/fs/cgroup/memory.max").ok()
+        .zip(fs::read_to_string("/sys/fs/cgroup/memory.current").ok())
+        .and_then(|(max, current)| cgroup_headroom(&max, &current));
+    Some(cgroup.map_or(available, |headroom| headroom.min(available)))
+}
+
+fn parse_mem_available(meminfo: &str) -> Option<u64> {
+    let line = meminfo.lines().find_map(|line| line.strip_prefix("MemAvailable:"))?;
+    let kib: u64 = line.trim().strip_suffix("kB")?.trim().parse().ok()?;
+    Some(kib * 1024)
+}
+
+/// Bytes left under a cgroup's `memory.max`, which reads `max` when unlimited
+fn cgroup_headroom(max: &str, current: &str) -> Option<u64> {
+    let max: u64 = max.trim().parse().ok()?;
+    let current: u64 = current.trim().parse().ok()?;
+    Some(max.saturating_sub(current))
+}
+
+/// Render a byte count with a binary unit, e.g. `12.5 MiB`
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_peak_rss() {
+        let status = "Name:\tparquet_consolidator\nVmPeak:\t  300000 kB\nVmHWM:\t   12800 kB\nVmRSS:\t    9000 kB\n";
+        assert_eq!(parse_peak_rss(status), Some(12800 * 1024));
+        assert_eq!(parse_peak_rss("Name:\tx\n"), None);
+        if cfg!(target_os = "linux") {
+            assert!(peak_rss_bytes().unwrap() > 0);
+        }
+
+        let meminfo = "MemTotal:       16318412 kB\nMemFree:         1023400 kB\nMemAvailable:    8159204 kB\n";
+        assert_eq!(parse_mem_available(meminfo), Some(8159204 * 1024));
+        assert_eq!(parse_mem_available("MemTotal: 1 kB\n"), None);
+        assert_eq!(cgroup_headroom("4294967296\n", "1073741824\n"), Some(3 * 1024 * 1024 * 1024));
+        assert_eq!(cgroup_headroom("max\n", "1073741824\n"), None);
+        if cfg!(target_os = "linux") {
+            assert!(available_memory_bytes().unwrap() > 0);
+        }
+
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(12800 * 1024), "12.5 MiB");
+        assert_eq!(format_bytes(3 * 1024 * 1024 * 1024), "3.0 GiB");
+    }
+}
@@ -0,0 +1,297 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use anyhow::{Result, Context};
+use polars::prelude::*;
+use crate::file_info::is_named_pipe;
+
+/// What to do when an output path is already taken
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IfExists {
+    /// Replace the existing file
+    #[default]
+    Overwrite,
+    /// Leave the existing file alone and produce no output
+    Skip,
+    /// Write to the first free suffixed name instead; see [`unique_path`]
+    Unique,
+}
+
+impl IfExists {
+    /// The path to write to, or `None` when the output should be skipped
+    pub fn resolve(&self, path: &Path) -> Option<PathBuf> {
+        // A named pipe is written into, never replaced
+        if !path.exists() || is_named_pipe(path) {
+            return Some(path.to_path_buf());
+        }
+        match self {
+            IfExists::Overwrite => Some(path.to_path_buf()),
+            IfExists::Skip => None,
+            IfExists::Unique => Some(unique_path(path)),
+        }
+    }
+}
+
+impl fmt::Display for IfExists {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            IfExists::Overwrite => "overwrite",
+            IfExists::Skip => "skip",
+            IfExists::Unique => "unique",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for IfExists {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "overwrite" => Ok(IfExists::Overwrite),
+            "skip" => Ok(IfExists::Skip),
+            "unique" => Ok(IfExists::Unique),
+            other => anyhow::bail!("Unknown if-exists policy: {} (expected overwrite, skip or unique)", other),
+        }
+    }
+}
+
+/// First path of the form `stem-N.ext` that does not exist yet, counting
+/// from 1
+///
+/// # Examples
+///
+/// ```
+/// use parquet_consolidator::unique_path;
+/// use tempfile::TempDir;
+///
+/// let temp_dir = TempDir::new().unwrap();
+/// let path = temp_dir.path().join("events.parquet");
+/// std::fs::write(temp_dir.path().join("events-1.parquet"), "").unwrap();
+/// assert_eq!(unique_path(&path), temp_dir.path().join("events-2.parquet"));
+/// ```
+pub fn unique_path(path: &Path) -> PathBuf {
+    let stem = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    let extension = path.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+    (1..)
+        .map(|n| path.with_file_name(format!("{}-{}{}", stem, n, extension)))
+        .find(|candidate| !candidate.exists())
+        .expect("some suffix is free")
+}
+
+/// Smallest and largest value of a column in a parquet file, rendered for
+/// use in a file name
+///
+/// Timestamps are reduced to their date so names stay short and readable.
+/// Returns `None` when the column holds only nulls.
+pub fn column_range(path: &Path, column: &str) -> Result<Option<(String, String)>> {
+    let scan = LazyFrame::scan_parquet(path, Default::default())
+        .context(format!("Failed to read {:?}", path))?;
+    let schema = scan.schema()?;
+    let data_type = schema
+        .get(column)
+        .ok_or_else(|| anyhow::anyhow!("Column not found in consolidated schema: {}", column))?;
+
+    let value = match data_type {
+        DataType::Datetime(_, _) => col(column).cast(DataType::Date),
+        _ => col(column),
+    };
+    let range = scan
+        .select([
+            value.clone().min().cast(DataType::Utf8).alias("min"),
+            value.max().cast(DataType::Utf8).alias("max"),
+        ])
+        .collect()?;
+
+    let min = range.column("min")?.utf8()?.get(0).map(sanitize);
+    let max = range.column("max")?.utf8()?.get(0).map(sanitize);
+    Ok(min.zip(max))
+}
+
+/// Replace characters that are awkward in file names
+fn sanitize(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || matches!(c, '-' | '.') { c } else { '-' })
+        .collect()
+}
+
+/// Path with a column's value range appended to the file stem
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// use parquet_consolidator::range_named_path;
+///
+/// let path = range_named_path(Path::new("out/events.parquet"), "2024-06-01", "2024-06-07");
+/// assert_eq!(path, Path::new("out/events_2024-06-01_2024-06-07.parquet"));
+/// ```
+pub fn range_named_path(path: &Path, min: &str, max: &str) -> PathBuf {
+    let stem = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    let mut name = format!("{}_{}_{}", stem, min, max);
+    if let Some(extension) = path.extension() {
+        name.push('.');
+        name.push_str(&extension.to_string_lossy());
+    }
+    path.with_file_name(name)
+}
+
+/// What an output file of a multi-file run is named from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputNameContext {
+    /// The name the built-in convention gives the output, e.g.
+    /// `part-00000.parquet`
+    pub default_name: String,
+    /// Values of the `key=value` directories the output is written in, in
+    /// path order
+    pub partition_values: Vec<(String, String)>,
+    /// Number of the output among those written to the same directory, from 0
+    pub sequence: usize,
+    /// Smallest and largest value of the column outputs are named by, when
+    /// naming by range
+    pub range: Option<(String, String)>,
+}
+
+/// Names the files of runs that write several outputs: per-directory and
+/// mirrored jobs, appended partitions and range-named outputs
+///
+/// Implement it to enforce a file naming convention; [`DefaultNamer`]
+/// keeps the built-in names. Names must be plain file names, without a
+/// directory.
+///
+/// # Examples
+///
+/// ```
+/// use parquet_consolidator::{OutputNameContext, OutputNamer};
+///
+/// /// `<team>_<partition values>_<sequence>.parquet`
+/// struct TeamNamer;
+///
+/// impl OutputNamer for TeamNamer {
+///     fn file_name(&self, output: &OutputNameContext) -> String {
+///         let partitions: Vec<&str> = output.partition_values.iter().map(|(_, value)| value.as_str()).collect();
+///         format!("analytics_{}_{:03}.parquet", partitions.join("-"), output.sequence)
+///     }
+/// }
+///
+/// let output = OutputNameContext {
+///     default_name: "part-00000.parquet".to_string(),
+///     partition_values: vec![("year".to_string(), "2024".to_string()), ("month".to_string(), "06".to_string())],
+///     sequence: 0,
+///     range: None,
+/// };
+/// assert_eq!(TeamNamer.file_name(&output), "analytics_2024-06_000.parquet");
+/// ```
+pub trait OutputNamer: Send + Sync {
+    fn file_name(&self, output: &OutputNameContext) -> String;
+}
+
+/// The built-in names: the default name, with the value range appended to
+/// its stem when naming by range; see [`range_named_path`]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DefaultNamer;
+
+impl OutputNamer for DefaultNamer {
+    fn file_name(&self, output: &OutputNameContext) -> String {
+        match &output.range {
+            Some((min, max)) => range_named_path(Path::new(&output.default_name), min, max)
+                .to_string_lossy()
+                .into_owned(),
+            None => output.default_name.clone(),
+        }
+    }
+}
+
+/// Values of the `key=value` components of a path, in path order
+pub fn partition_values(path: &Path) -> Vec<(String, String)> {
+    path.iter()
+        .filter_map(|component| {
+            let (key, value) = component.to_str()?.split_once('=')?;
+            Some((key.to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+/// The file name `namer` gives an output, checked to be a plain file name
+pub(crate) fn output_file_name(namer: &dyn OutputNamer, output: &OutputNameContext) -> Result<String> {
+    let name = namer.file_name(output);
+    if name.is_empty() || name == "." || name == ".." || name.contains(['/', '\\']) {
+        anyhow::bail!("Output namer returned {:?}, which is not a plain file name", name);
+    }
+    Ok(name)
+}
+
+/// Rename a written parquet file after the value range of a column
+///
+/// The new name is chosen by `namer` from the range. Returns the new path.
+/// Files whose column is entirely null keep their name. When the new path
+/// is taken and `if_exists` is [`IfExists::Skip`], the written file is
+/// removed and `None` is returned.
+pub fn rename_by_range(path: &Path, column: &str, if_exists: IfExists, namer: &dyn OutputNamer) -> Result<Option<PathBuf>> {
+    let Some((min, max)) = column_range(path, column)? else {
+        return Ok(Some(path.to_path_buf()));
+    };
+    let output = OutputNameContext {
+        default_name: path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default(),
+        partition_values: path.parent().map(partition_values).unwrap_or_default(),
+        sequence: 0,
+        range: Some((min, max)),
+    };
+    let Some(named) = if_exists.resolve(&path.with_file_name(output_file_name(namer, &output)?)) else {
+        std::fs::remove_file(path).context(format!("Failed to remove {:?}", path))?;
+        return Ok(None);
+    };
+    std::fs::rename(path, &named).context(format!("Failed to rename {:?} to {:?}", path, named))?;
+    Ok(Some(named))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::writer::{write_dataframe, WriterOptions};
+
+    #[test]
+    fn test_rename_by_range() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let output_file = temp_dir.path().join("events.parquet");
+        let ts = Series::new("ts", &[1_717_286_400_000i64, 1_717_200_000_000, 1_717_718_400_000])
+            .cast(&DataType::Datetime(TimeUnit::Milliseconds, None))?;
+        let mut df = DataFrame::new(vec![ts, Series::new("kind", &["a/b", "c", "d"])])?;
+        write_dataframe(&mut df, &output_file, &WriterOptions::default())?;
+
+        assert_eq!(column_range(&output_file, "kind")?, Some(("a-b".to_string(), "d".to_string())));
+        assert!(column_range(&output_file, "missing").is_err());
+
+        let named = rename_by_range(&output_file, "ts", IfExists::Overwrite, &DefaultNamer)?.unwrap();
+        assert_eq!(named, temp_dir.path().join("events_2024-06-01_2024-06-07.parquet"));
+        assert!(named.exists());
+        assert!(!output_file.exists());
+
+        write_dataframe(&mut df, &output_file, &WriterOptions::default())?;
+        let renamed = rename_by_range(&output_file, "ts", IfExists::Unique, &DefaultNamer)?.unwrap();
+        assert_eq!(renamed, temp_dir.path().join("events_2024-06-01_2024-06-07-1.parquet"));
+
+        write_dataframe(&mut df, &output_file, &WriterOptions::default())?;
+        assert_eq!(rename_by_range(&output_file, "ts", IfExists::Skip, &DefaultNamer)?, None);
+        assert!(!output_file.exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_if_exists_resolve() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let free = temp_dir.path().join("free.parquet");
+        let taken = temp_dir.path().join("taken.parquet");
+        std::fs::write(&taken, "")?;
+
+        assert_eq!(IfExists::Skip.resolve(&free), Some(free.clone()));
+        assert_eq!(IfExists::Overwrite.resolve(&taken), Some(taken.clone()));
+        assert_eq!(IfExists::Skip.resolve(&taken), None);
+        assert_eq!(IfExists::Unique.resolve(&taken), Some(temp_dir.path().join("taken-1.parquet")));
+        assert!("fail".parse::<IfExists>().is_err());
+
+        Ok(())
+    }
+}
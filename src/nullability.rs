@@ -0,0 +1,189 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::File;
+use std::path::PathBuf;
+use std::str::FromStr;
+use anyhow::{Result, Context};
+use parquet::basic::Repetition;
+use parquet::file::reader::{FileReader, SerializedFileReader};
+use crate::formats::{is_buffered_input, InputFormat};
+
+/// What to do with a column that is required in some inputs and holds
+/// nulls in others
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NullabilityPolicy {
+    /// Fail the run before anything is written
+    Error,
+    /// Write the column as nullable, keeping the nulls, with a warning
+    #[default]
+    CoerceNullable,
+    /// Drop the rows that are null in the column, so it holds no nulls
+    DropNullRows,
+}
+
+impl fmt::Display for NullabilityPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            NullabilityPolicy::Error => "error",
+            NullabilityPolicy::CoerceNullable => "coerce-nullable",
+            NullabilityPolicy::DropNullRows => "drop-null-rows",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for NullabilityPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "error" => Ok(NullabilityPolicy::Error),
+            "coerce-nullable" => Ok(NullabilityPolicy::CoerceNullable),
+            "drop-null-rows" => Ok(NullabilityPolicy::DropNullRows),
+            other => anyhow::bail!(
+                "Unknown nullability policy: {} (expected error, coerce-nullable or drop-null-rows)",
+                other
+            ),
+        }
+    }
+}
+
+/// A top-level column that is required in some inputs and nullable, with
+/// nulls, in others
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NullabilityConflict {
+    pub column: String,
+    /// Inputs declaring the column required
+    pub required_files: usize,
+    /// Inputs declaring the column nullable and holding nulls in it, or
+    /// whose statistics don't say
+    pub nullable_files: usize,
+    /// Nulls in those inputs according to their statistics; `None` when
+    /// some of them don't record null counts
+    pub null_count: Option<u64>,
+}
+
+impl fmt::Display for NullabilityConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Column {} is required in {} inputs but nullable in {}",
+            self.column, self.required_files, self.nullable_files
+        )?;
+        match self.null_count {
+            Some(nulls) => write!(f, " with {} nulls", nulls),
+            None => write!(f, " with nulls"),
+        }
+    }
+}
+
+#[derive(Default)]
+struct ColumnNullability {
+    required_files: usize,
+    nullable_files: usize,
+    null_count: Option<u64>,
+}
+
+/// Find the top-level columns that are required in some parquet inputs
+/// and nullable in others that hold nulls in them, from the inputs' footers
+///
+/// A nullable column whose statistics record no nulls is no conflict, as
+/// its rows fit the required declaration. Other formats carry no
+/// nullability and are left out.
+pub fn nullability_conflicts(input_files: &[PathBuf]) -> Result<Vec<NullabilityConflict>> {
+    let mut columns: BTreeMap<String, ColumnNullability> = BTreeMap::new();
+    for path in input_files {
+        let parquet = InputFormat::from_path(path).unwrap_or(InputFormat::Parquet) == InputFormat::Parquet;
+        if !parquet || is_buffered_input(path) {
+            continue;
+        }
+        let reader = SerializedFileReader::new(File::open(path)?)
+            .context(format!("Failed to read parquet footer of {:?}", path))?;
+        let metadata = reader.metadata();
+        let schema = metadata.file_metadata().schema_descr();
+        for field in schema.root_schema().get_fields() {
+            let column = columns.entry(field.name().to_string()).or_default();
+            if field.get_basic_info().repetition() == Repetition::REQUIRED {
+                column.required_files += 1;
+                continue;
+            }
+            // Nested columns have no single null count of their own
+            let leaf = (0..schema.num_columns())
+                .find(|&i| schema.column(i).path().parts() == [field.name().to_string()]);
+            let nulls = leaf.and_then(|i| {
+                metadata
+                    .row_groups()
+                    .iter()
+                    .map(|row_group| row_group.column(i).statistics().and_then(|stats| stats.null_count_opt()))
+                    .sum::<Option<u64>>()
+            });
+            if nulls != Some(0) {
+                column.null_count = match (column.nullable_files, column.null_count, nulls) {
+                    (0, _, nulls) => nulls,
+                    (_, Some(total), Some(nulls)) => Some(total + nulls),
+                    _ => None,
+                };
+                column.nullable_files += 1;
+            }
+        }
+    }
+    Ok(columns
+        .into_iter()
+        .filter(|(_, column)| column.required_files > 0 && column.nullable_files > 0)
+        .map(|(name, column)| NullabilityConflict {
+            column: name,
+            required_files: column.required_files,
+            nullable_files: column.nullable_files,
+            null_count: column.null_count,
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use arrow::array::{Float64Array, Int32Array, StringArray};
+    use arrow::datatypes::{DataType, Field, Schema};
+    use arrow::record_batch::RecordBatch;
+    use parquet::arrow::ArrowWriter;
+    use crate::test_utils::create_test_parquet_file;
+
+    #[test]
+    fn test_nullability_conflicts() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let required = temp_dir.path().join("required.parquet");
+        create_test_parquet_file(&required, 0, 10)?;
+
+        // The same columns, all nullable, with nulls only in value
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, true),
+            Field::new("name", DataType::Utf8, true),
+            Field::new("value", DataType::Float64, true),
+        ]));
+        let batch = RecordBatch::try_new(schema.clone(), vec![
+            Arc::new(Int32Array::from(vec![10, 11, 12])),
+            Arc::new(StringArray::from(vec!["a", "b", "c"])),
+            Arc::new(Float64Array::from(vec![Some(1.0), None, None])),
+        ])?;
+        let nullable = temp_dir.path().join("nullable.parquet");
+        let mut writer = ArrowWriter::try_new(File::create(&nullable)?, schema, None)?;
+        writer.write(&batch)?;
+        writer.close()?;
+
+        let conflicts = nullability_conflicts(&[required.clone(), nullable])?;
+        assert_eq!(conflicts, [NullabilityConflict {
+            column: "value".to_string(),
+            required_files: 1,
+            nullable_files: 1,
+            null_count: Some(2),
+        }]);
+        assert_eq!(conflicts[0].to_string(), "Column value is required in 1 inputs but nullable in 1 with 2 nulls");
+        assert!(nullability_conflicts(&[required])?.is_empty());
+
+        assert_eq!("drop-null-rows".parse::<NullabilityPolicy>()?, NullabilityPolicy::DropNullRows);
+        assert!("ignore".parse::<NullabilityPolicy>().is_err());
+
+        Ok(())
+    }
+}
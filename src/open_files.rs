@@ -0,0 +1,64 @@
+use std::fs;
+
+/// File descriptors left out of the budget for everything besides input
+/// scans: standard streams, the output, audit logs, sockets and the ones
+/// the allocator and thread pool hold
+pub const RESERVED_FILE_DESCRIPTORS: usize = 64;
+
+/// Soft limit on the number of files this process may have open
+///
+/// Read from the `Max open files` line of `/proc/self/limits`, so only
+/// available on Linux. `None` when the limit is unknown or unlimited.
+pub fn open_file_limit() -> Option<usize> {
+    parse_open_file_limit(&fs::read_to_string("/proc/self/limits").ok()?)
+}
+
+fn parse_open_file_limit(limits: &str) -> Option<usize> {
+    let line = limits.lines().find_map(|line| line.strip_prefix("Max open files"))?;
+    line.split_whitespace().next()?.parse().ok()
+}
+
+/// How many inputs each of `jobs` concurrent jobs may keep open at once
+///
+/// An explicit `max_open_files` is shared between the jobs; without one
+/// the budget is the process's open file limit less
+/// [`RESERVED_FILE_DESCRIPTORS`]. Returns `None` when there is nothing to
+/// budget against.
+///
+/// # Examples
+///
+/// ```
+/// use parquet_consolidator::open_file_budget;
+///
+/// assert_eq!(open_file_budget(Some(100), 4), Some(25));
+/// assert_eq!(open_file_budget(Some(2), 4), Some(1));
+/// ```
+pub fn open_file_budget(max_open_files: Option<usize>, jobs: usize) -> Option<usize> {
+    let total = match max_open_files {
+        Some(max) => max,
+        None => open_file_limit()?.saturating_sub(RESERVED_FILE_DESCRIPTORS),
+    };
+    Some((total / jobs.max(1)).max(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_open_file_limit() {
+        let limits = "Limit                     Soft Limit           Hard Limit           Units     \n\
+                      Max processes             63432                63432                processes \n\
+                      Max open files            1024                 524288               files     \n";
+        assert_eq!(parse_open_file_limit(limits), Some(1024));
+        assert_eq!(parse_open_file_limit("Max open files            unlimited            unlimited            files\n"), None);
+        assert_eq!(parse_open_file_limit("Max processes 10 10 processes\n"), None);
+        if open_file_limit().is_some() {
+            assert!(open_file_budget(None, 1).unwrap() >= 1);
+        }
+
+        assert_eq!(open_file_budget(Some(10), 1), Some(10));
+        assert_eq!(open_file_budget(Some(10), 0), Some(10));
+        assert_eq!(open_file_budget(Some(10), 3), Some(3));
+    }
+}
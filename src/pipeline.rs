@@ -0,0 +1,308 @@
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use anyhow::{Result, Context};
+use arrow::array::RecordBatch;
+use arrow::datatypes::SchemaRef;
+use parquet::arrow::arrow_reader::ArrowReaderMetadata;
+use crate::consolidator::ConsolidateOptions;
+use crate::row_groups::{identical_input_schema, read_row_group, RowGroupWriter};
+use crate::transform::RowTransform;
+use crate::warnings::WarningLog;
+
+/// Decoded row groups each stage may hold ahead of the next, by default
+pub const DEFAULT_QUEUE_DEPTH: usize = 4;
+
+/// How a pipelined run spreads its work
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PipelineOptions {
+    /// Threads decoding row groups; 0 uses one per CPU, and the open file
+    /// budget caps it either way
+    pub readers: usize,
+    /// Decoded row groups each stage may hold ahead of the next, which
+    /// bounds the rows in memory
+    pub queue_depth: usize,
+}
+
+impl Default for PipelineOptions {
+    fn default() -> Self {
+        PipelineOptions { readers: 0, queue_depth: DEFAULT_QUEUE_DEPTH }
+    }
+}
+
+/// One row group of one input, in the order it is written
+#[derive(Debug, Clone, Copy)]
+struct Unit {
+    file: usize,
+    row_group: usize,
+}
+
+/// Consolidate parquet inputs sharing one schema through a staged pipeline
+///
+/// A pool of readers decodes row groups in parallel; a single stage runs
+/// the [`RowTransform`]s over each decoded row group in input order; and
+/// the calling thread encodes them into the sink. The stages hand row
+/// groups over through bounded channels, so decoding and encoding overlap
+/// with each other and with the reads while only a few row groups are in
+/// memory at once. Output rows keep the input order.
+///
+/// Transforms must keep every row of a batch, and are first run on an
+/// empty batch to learn the output schema. With `preserve_row_groups`,
+/// each input row group is closed as its own output row group, within the
+/// configured limits. The row count written is checked against the
+/// inputs' footers.
+///
+/// Returns the rows written and the sink; warnings about the output
+/// schema are raised on `warnings`.
+///
+/// # Examples
+///
+/// ```
+/// use parquet_consolidator::{consolidate_pipelined, ConsolidateOptions, PipelineOptions};
+/// use parquet_consolidator::warnings::WarningLog;
+/// use parquet_consolidator::test_utils::create_test_parquet_file;
+/// use tempfile::TempDir;
+///
+/// let temp_dir = TempDir::new().unwrap();
+/// let inputs = [temp_dir.path().join("a.parquet"), temp_dir.path().join("b.parquet")];
+/// create_test_parquet_file(&inputs[0], 0, 10).unwrap();
+/// create_test_parquet_file(&inputs[1], 10, 15).unwrap();
+///
+/// let options = ConsolidateOptions { pipeline: Some(PipelineOptions::default()), ..Default::default() };
+/// let (rows, buffer) = consolidate_pipelined(&inputs, Vec::new(), &options, &WarningLog::default()).unwrap();
+/// assert_eq!(rows, 15);
+/// assert_eq!(&buffer[..4], b"PAR1");
+/// ```
+pub fn consolidate_pipelined<W: Write + Send>(
+    input_files: &[PathBuf],
+    sink: W,
+    options: &ConsolidateOptions,
+    warnings: &WarningLog,
+) -> Result<(usize, W)> {
+    options.writer.validate_row_group_limits()?;
+    let pipeline = options.pipeline.unwrap_or_default();
+    let schema = identical_input_schema(input_files, &options.read)
+        .context("A pipelined run needs parquet inputs sharing one schema")?;
+    let output_schema = transform_batch(RecordBatch::new_empty(schema.clone()), &options.transforms)?.schema();
+
+    let mut footers = Vec::with_capacity(input_files.len());
+    let mut units = Vec::new();
+    let mut expected_rows = 0;
+    for (file, input_file) in input_files.iter().enumerate() {
+        let footer = ArrowReaderMetadata::load(&File::open(input_file)?, Default::default())
+            .context(format!("Failed to read parquet footer of {:?}", input_file))?;
+        for (row_group, metadata) in footer.metadata().row_groups().iter().enumerate() {
+            units.push(Unit { file, row_group });
+            expected_rows += metadata.num_rows().max(0) as usize;
+        }
+        footers.push(footer);
+    }
+
+    let readers = match pipeline.readers {
+        0 => thread::available_parallelism().map_or(1, |cpus| cpus.get()),
+        readers => readers,
+    };
+    let readers = readers.min(options.max_open_files.unwrap_or(usize::MAX)).min(units.len()).max(1);
+    if options.verbose {
+        println!(
+            "Pipelining {} row groups of {} inputs through {} readers",
+            units.len(),
+            input_files.len(),
+            readers
+        );
+    }
+
+    let max_rows = options.writer.max_row_group_rows.unwrap_or(usize::MAX);
+    let mut writer = RowGroupWriter::try_new(sink, output_schema, &options.writer, max_rows, warnings)?;
+    let queue_depth = pipeline.queue_depth.max(1);
+    // Readers take jobs in input order and answer each on its own channel,
+    // whose receivers queue up in that order for the transform stage; the
+    // bound on that queue is what holds the readers back
+    let (jobs, job_queue) = mpsc::channel::<(Unit, mpsc::SyncSender<Result<Vec<RecordBatch>>>)>();
+    let job_queue = Mutex::new(job_queue);
+    let rows = thread::scope(|scope| -> Result<usize> {
+        for _ in 0..readers {
+            let (job_queue, footers, schema) = (&job_queue, &footers, &schema);
+            scope.spawn(move || loop {
+                let job = job_queue.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).recv();
+                let Ok((unit, reply)) = job else {
+                    break;
+                };
+                let _ = reply.send(decode_row_group(&input_files[unit.file], &footers[unit.file], unit.row_group, schema));
+            });
+        }
+
+        let (pending, pending_queue) = mpsc::sync_channel(queue_depth);
+        let units = &units;
+        scope.spawn(move || {
+            for &unit in units {
+                let (reply, decoded) = mpsc::sync_channel(1);
+                if jobs.send((unit, reply)).is_err() || pending.send(decoded).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let (transformed, transformed_queue) = mpsc::sync_channel(queue_depth);
+        let transforms = &options.transforms;
+        scope.spawn(move || {
+            for decoded in pending_queue {
+                let batches = decoded
+                    .recv()
+                    .unwrap_or_else(|_| Err(anyhow::anyhow!("Decoding a row group panicked")))
+                    .and_then(|batches| {
+                        batches.into_iter().map(|batch| transform_batch(batch, transforms)).collect::<Result<Vec<_>>>()
+                    });
+                let failed = batches.is_err();
+                if transformed.send(batches).is_err() || failed {
+                    break;
+                }
+            }
+        });
+
+        // Dropping the queue on an error stops the stages feeding it
+        let mut rows = 0;
+        for batches in transformed_queue {
+            options.check_cancelled()?;
+            for batch in batches? {
+                writer.write(&batch)?;
+                rows += batch.num_rows();
+            }
+            if options.preserve_row_groups {
+                writer.boundary()?;
+            }
+        }
+        Ok(rows)
+    })?;
+
+    if rows != expected_rows {
+        anyhow::bail!("The pipeline wrote {} rows, but the inputs hold {}", rows, expected_rows);
+    }
+    Ok((rows, writer.into_inner()?))
+}
+
+/// Decode one row group of an input into batches of the shared schema
+fn decode_row_group(
+    input_file: &Path,
+    footer: &ArrowReaderMetadata,
+    row_group: usize,
+    schema: &SchemaRef,
+) -> Result<Vec<RecordBatch>> {
+    #[cfg(feature = "fault-injection")]
+    if row_group == 0 {
+        crate::fault_injection::inject_fault(crate::fault_injection::FaultPoint::Read, input_file)?;
+    }
+    read_row_group(&File::open(input_file)?, footer, row_group, schema)?
+        .collect::<Result<Vec<_>>>()
+        .context(format!("Failed to decode row group {} of {:?}", row_group, input_file))
+}
+
+/// Run one batch through each transform in order, failing if any of them
+/// changes its row count
+fn transform_batch(batch: RecordBatch, transforms: &[Arc<dyn RowTransform>]) -> Result<RecordBatch> {
+    transforms.iter().try_fold(batch, |batch, transform| {
+        let rows = batch.num_rows();
+        let transformed = transform.transform(batch).context(format!("Transform {} failed", transform.name()))?;
+        if transformed.num_rows() != rows {
+            anyhow::bail!(
+                "Transform {} turned {} rows into {}; a pipelined run only takes transforms that keep every row",
+                transform.name(),
+                rows,
+                transformed.num_rows()
+            );
+        }
+        Ok(transformed)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::create_test_parquet_file;
+    use arrow::array::{Array, Int32Array, RecordBatchReader};
+    use arrow::compute::filter_record_batch;
+    use parquet::arrow::ArrowWriter;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+    use parquet::file::properties::WriterProperties;
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+
+    /// Negates `id`, or with `keep_even` drops the rows with an odd one
+    #[derive(Debug)]
+    struct Ids {
+        keep_even: bool,
+    }
+
+    impl RowTransform for Ids {
+        fn name(&self) -> &str {
+            "ids"
+        }
+
+        fn transform(&self, batch: RecordBatch) -> Result<RecordBatch> {
+            let index = batch.schema().index_of("id")?;
+            let ids = batch.column(index).as_any().downcast_ref::<Int32Array>().expect("int32 ids").clone();
+            if self.keep_even {
+                let even = arrow::compute::kernels::cmp::eq(
+                    &arrow::compute::kernels::numeric::rem(&ids, &Int32Array::new_scalar(2))?,
+                    &Int32Array::new_scalar(0),
+                )?;
+                return Ok(filter_record_batch(&batch, &even)?);
+            }
+            let mut columns = batch.columns().to_vec();
+            columns[index] = Arc::new(arrow::compute::kernels::numeric::neg(&ids)?);
+            Ok(RecordBatch::try_new(batch.schema(), columns)?)
+        }
+    }
+
+    #[test]
+    fn test_consolidate_pipelined() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let mut inputs = Vec::new();
+        for (index, (start, end)) in [(0, 10), (10, 13), (13, 30)].into_iter().enumerate() {
+            let staging = temp_dir.path().join(format!("{}.staging", index));
+            create_test_parquet_file(&staging, start, end)?;
+            let reader = ParquetRecordBatchReaderBuilder::try_new(File::open(&staging)?)?.build()?;
+            let input = temp_dir.path().join(format!("{}.parquet", index));
+            let props = WriterProperties::builder().set_max_row_group_size(4).build();
+            let mut writer = ArrowWriter::try_new(File::create(&input)?, reader.schema(), Some(props))?;
+            for batch in reader {
+                writer.write(&batch?)?;
+            }
+            writer.close()?;
+            inputs.push(input);
+        }
+
+        let transforms: Vec<Arc<dyn RowTransform>> = vec![Arc::new(Ids { keep_even: false })];
+        let options = ConsolidateOptions {
+            pipeline: Some(PipelineOptions { readers: 3, queue_depth: 1 }),
+            preserve_row_groups: true,
+            transforms,
+            ..Default::default()
+        };
+        let output = temp_dir.path().join("output.parquet");
+        let (rows, _) = consolidate_pipelined(&inputs, File::create(&output)?, &options, &WarningLog::default())?;
+        assert_eq!(rows, 30);
+
+        // Rows keep the input order and each input row group closes one
+        let ids: Vec<i32> = ParquetRecordBatchReaderBuilder::try_new(File::open(&output)?)?
+            .build()?
+            .map(|batch| Ok(batch?.column(0).as_any().downcast_ref::<Int32Array>().unwrap().values().to_vec()))
+            .collect::<Result<Vec<_>>>()?
+            .concat();
+        assert_eq!(ids, (0..30).map(|id| -id).collect::<Vec<_>>());
+        let reader = SerializedFileReader::new(File::open(&output)?)?;
+        let row_counts: Vec<i64> = reader.metadata().row_groups().iter().map(|rg| rg.num_rows()).collect();
+        assert_eq!(row_counts, [4, 4, 2, 3, 4, 4, 4, 4, 1]);
+
+        let dropping = ConsolidateOptions {
+            pipeline: Some(PipelineOptions::default()),
+            transforms: vec![Arc::new(Ids { keep_even: true })],
+            ..Default::default()
+        };
+        let err = consolidate_pipelined(&inputs, Vec::new(), &dropping, &WarningLog::default()).unwrap_err();
+        assert!(err.to_string().starts_with("Transform ids turned 4 rows into 2"), "{}", err);
+
+        Ok(())
+    }
+}
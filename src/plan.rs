@@ -0,0 +1,254 @@
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use anyhow::{Result, Context};
+use polars::prelude::*;
+use serde::{Deserialize, Serialize};
+use crate::auto_batch::decoded_size;
+use crate::consolidator::{ConsolidateOptions, RunSummary};
+use crate::file_info::file_size;
+use crate::formats::InputFormat;
+use crate::jobs::{run_jobs, ConsolidationJob};
+use crate::memory::format_bytes;
+use crate::schema_merge::{merge_schemas, MergeOptions};
+
+/// A column of a planned output
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlannedColumn {
+    pub name: String,
+    /// Type the column is written as
+    pub data_type: String,
+    /// Differing types of the column across the inputs, in input order, when
+    /// they are widened to `data_type`; empty when every input agrees
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub widened_from: Vec<String>,
+}
+
+/// One output of a plan: a group of inputs and where they are written
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlannedOutput {
+    /// Short label used in progress output; empty for a single output
+    #[serde(default)]
+    pub name: String,
+    pub input_files: Vec<PathBuf>,
+    pub output_path: PathBuf,
+    /// Size of the inputs on disk
+    pub input_bytes: u64,
+    /// Estimated size of the inputs once decoded
+    pub decoded_bytes: u64,
+    /// The output schema, after merging the input schemas
+    pub columns: Vec<PlannedColumn>,
+}
+
+impl PlannedOutput {
+    /// Columns whose type differs between the inputs
+    pub fn widened_columns(&self) -> impl Iterator<Item = &PlannedColumn> {
+        self.columns.iter().filter(|column| !column.widened_from.is_empty())
+    }
+}
+
+/// What a consolidation run will read and write, decided before any rows
+/// are read
+///
+/// Plans can be inspected, edited, for instance to drop inputs or move
+/// outputs, and saved as JSON to be executed later. The schema is only
+/// recorded for review: it is merged again from the inputs when the plan
+/// is executed.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ConsolidationPlan {
+    pub outputs: Vec<PlannedOutput>,
+}
+
+impl ConsolidationPlan {
+    /// The jobs that execute this plan
+    pub fn jobs(&self) -> Vec<ConsolidationJob> {
+        self.outputs
+            .iter()
+            .map(|output| ConsolidationJob {
+                name: output.name.clone(),
+                input_files: output.input_files.clone(),
+                output_path: output.output_path.clone(),
+            })
+            .collect()
+    }
+
+    /// Write the plan to a JSON file
+    pub fn save(&self, path: &Path) -> Result<()> {
+        fs::write(path, serde_json::to_string_pretty(self)?).context(format!("Failed to write plan {:?}", path))
+    }
+
+    /// Read a plan written by [`ConsolidationPlan::save`]
+    pub fn load(path: &Path) -> Result<Self> {
+        let json = fs::read_to_string(path).context(format!("Failed to read plan {:?}", path))?;
+        serde_json::from_str(&json).context(format!("Failed to parse plan {:?}", path))
+    }
+}
+
+impl fmt::Display for ConsolidationPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for output in &self.outputs {
+            writeln!(
+                f,
+                "Consolidate {} files ({} on disk, about {} decoded) into {}",
+                output.input_files.len(),
+                format_bytes(output.input_bytes),
+                format_bytes(output.decoded_bytes),
+                output.output_path.display()
+            )?;
+            for column in output.widened_columns() {
+                writeln!(f, "  Widen {} from {} to {}", column.name, column.widened_from.join(", "), column.data_type)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Plan the given jobs without reading any rows
+///
+/// Reads the schema of every input and the footer of parquet inputs, and
+/// fails when the schemas of a job can't be merged, as executing it would.
+///
+/// # Examples
+///
+/// ```
+/// use parquet_consolidator::{execute_consolidation, plan_consolidation, ConsolidateOptions, ConsolidationJob};
+/// use parquet_consolidator::test_utils::create_test_parquet_file;
+/// use tempfile::TempDir;
+///
+/// let temp_dir = TempDir::new().unwrap();
+/// let inputs = vec![temp_dir.path().join("a.parquet"), temp_dir.path().join("b.parquet")];
+/// create_test_parquet_file(&inputs[0], 0, 10).unwrap();
+/// create_test_parquet_file(&inputs[1], 10, 20).unwrap();
+///
+/// let job = ConsolidationJob { name: String::new(), input_files: inputs, output_path: temp_dir.path().join("out.parquet") };
+/// let options = ConsolidateOptions::default();
+/// let mut plan = plan_consolidation(&[job], &options).unwrap();
+/// assert_eq!(plan.outputs[0].columns[0].name, "id");
+///
+/// // Leave the second input out before running
+/// plan.outputs[0].input_files.pop();
+/// let results = execute_consolidation(&plan, &options, 1);
+/// assert_eq!(results[0].as_ref().unwrap().rows_written, 10);
+/// ```
+pub fn plan_consolidation(jobs: &[ConsolidationJob], options: &ConsolidateOptions) -> Result<ConsolidationPlan> {
+    let outputs = jobs
+        .iter()
+        .map(|job| plan_output(job, options).context(format!("Failed to plan {:?}", job.output_path)))
+        .collect::<Result<_>>()?;
+    Ok(ConsolidationPlan { outputs })
+}
+
+fn plan_output(job: &ConsolidationJob, options: &ConsolidateOptions) -> Result<PlannedOutput> {
+    if job.input_files.is_empty() {
+        anyhow::bail!("No input files provided");
+    }
+
+    let schemas = job
+        .input_files
+        .iter()
+        .map(|input_file| {
+            let format = InputFormat::from_path(input_file).unwrap_or(InputFormat::Parquet);
+            let scan = format.scan_with_options(input_file, &options.read).context(format!("Failed to read {:?}", input_file))?;
+            let schema = scan.schema().context(format!("Failed to resolve the schema of {:?}", input_file))?;
+            Ok(schema.as_ref().clone())
+        })
+        .collect::<Result<Vec<Schema>>>()?;
+    let merge = MergeOptions { union_columns: options.read.union_columns, ..Default::default() };
+    let merged = merge_schemas(&schemas, &merge).context("Failed to merge input schemas")?;
+
+    let columns = merged
+        .iter()
+        .map(|(name, data_type)| {
+            let input_types: Vec<&DataType> = schemas.iter().filter_map(|schema| schema.get(name)).collect();
+            let widened_from = if input_types.iter().all(|input_type| *input_type == data_type) {
+                Vec::new()
+            } else {
+                input_types.iter().map(|input_type| input_type.to_string()).collect()
+            };
+            PlannedColumn { name: name.to_string(), data_type: data_type.to_string(), widened_from }
+        })
+        .collect();
+
+    Ok(PlannedOutput {
+        name: job.name.clone(),
+        input_files: job.input_files.clone(),
+        output_path: job.output_path.clone(),
+        input_bytes: job.input_files.iter().map(|path| file_size(path)).sum(),
+        decoded_bytes: job.input_files.iter().map(|path| decoded_size(path)).sum::<Result<u64>>()?,
+        columns,
+    })
+}
+
+/// Execute a plan, running up to `parallelism` outputs at once
+///
+/// Every output is written even if others fail; results are returned in
+/// the order of the plan's outputs.
+pub fn execute_consolidation(
+    plan: &ConsolidationPlan,
+    options: &ConsolidateOptions,
+    parallelism: usize,
+) -> Vec<Result<RunSummary>> {
+    run_jobs(&plan.jobs(), options, parallelism)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use polars::df;
+    use crate::test_utils::create_test_parquet_file;
+
+    #[test]
+    fn test_plan_consolidation() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let narrow = temp_dir.path().join("narrow.csv");
+        fs::write(&narrow, "id,value\n1,2\n")?;
+        let wide = temp_dir.path().join("wide.parquet");
+        let mut df = df!("id" => [2i64], "value" => [2.5f64])?;
+        ParquetWriter::new(fs::File::create(&wide)?).finish(&mut df)?;
+        let other = temp_dir.path().join("other.parquet");
+        create_test_parquet_file(&other, 0, 5)?;
+
+        let jobs = vec![
+            ConsolidationJob {
+                name: "values".to_string(),
+                input_files: vec![narrow.clone(), wide.clone()],
+                output_path: temp_dir.path().join("values.parquet"),
+            },
+            ConsolidationJob {
+                name: "other".to_string(),
+                input_files: vec![other.clone()],
+                output_path: temp_dir.path().join("other-out.parquet"),
+            },
+        ];
+        let options = ConsolidateOptions::default();
+        let plan = plan_consolidation(&jobs, &options)?;
+        assert_eq!(plan.jobs(), jobs);
+
+        let values = &plan.outputs[0];
+        assert_eq!(values.input_bytes, file_size(&narrow) + file_size(&wide));
+        assert!(values.decoded_bytes > 0);
+        let widened: Vec<&str> = values.widened_columns().map(|column| column.name.as_str()).collect();
+        assert_eq!(widened, ["value"]);
+        assert_eq!(values.columns[1].widened_from, ["i64", "f64"]);
+        assert!(plan.to_string().contains("Widen value from i64, f64 to f64"));
+        assert!(plan.outputs[1].widened_columns().next().is_none());
+
+        let saved = temp_dir.path().join("plan.json");
+        plan.save(&saved)?;
+        let mut loaded = ConsolidationPlan::load(&saved)?;
+        assert_eq!(loaded, plan);
+
+        loaded.outputs.remove(0);
+        let results = execute_consolidation(&loaded, &options, 2);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].as_ref().unwrap().rows_written, 5);
+        assert!(!temp_dir.path().join("values.parquet").exists());
+
+        let conflicting = temp_dir.path().join("conflicting.csv");
+        fs::write(&conflicting, "name\nx\n")?;
+        let job = ConsolidationJob { name: String::new(), input_files: vec![other, conflicting], output_path: saved };
+        assert!(plan_consolidation(&[job], &options).is_err());
+
+        Ok(())
+    }
+}
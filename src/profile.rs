@@ -0,0 +1,203 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
+use anyhow::{Result, Context};
+use polars::prelude::*;
+use crate::formats::{InputFormat, ReadOptions};
+
+/// Number of most frequent values listed per column
+const TOP_VALUES: usize = 5;
+
+/// Distribution of a numeric column's non-null values
+#[derive(Debug, Clone, PartialEq)]
+pub struct NumericSummary {
+    pub min: f64,
+    pub p25: f64,
+    pub median: f64,
+    pub p75: f64,
+    pub max: f64,
+    pub mean: f64,
+}
+
+impl NumericSummary {
+    fn from_series(series: &Series) -> Result<Option<Self>> {
+        let values = series.cast(&DataType::Float64)?;
+        let mut values: Vec<f64> = values.f64()?.into_no_null_iter().collect();
+        values.retain(|value| !value.is_nan());
+        if values.is_empty() {
+            return Ok(None);
+        }
+        values.sort_by(|a, b| a.total_cmp(b));
+
+        // Nearest-rank quantiles are exact values from the sample, which
+        // reads better than interpolations for integer columns
+        let quantile = |q: f64| values[((values.len() - 1) as f64 * q).round() as usize];
+        Ok(Some(NumericSummary {
+            min: values[0],
+            p25: quantile(0.25),
+            median: quantile(0.5),
+            p75: quantile(0.75),
+            max: values[values.len() - 1],
+            mean: values.iter().sum::<f64>() / values.len() as f64,
+        }))
+    }
+}
+
+/// Profile of one column of the sampled rows
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnProfile {
+    pub column: String,
+    pub data_type: String,
+    pub null_rate: f64,
+    /// Distinct non-null values in the sample; a lower bound on the
+    /// column's cardinality
+    pub distinct: usize,
+    /// Most frequent non-null values with their counts, most frequent first
+    pub top_values: Vec<(String, usize)>,
+    pub numeric: Option<NumericSummary>,
+}
+
+impl ColumnProfile {
+    fn from_series(series: &Series) -> Result<Self> {
+        let rows = series.len();
+        let null_rate = if rows == 0 { 0.0 } else { series.null_count() as f64 / rows as f64 };
+
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        if let Ok(values) = series.cast(&DataType::Utf8) {
+            for value in values.utf8()?.into_iter().flatten() {
+                *counts.entry(value.to_string()).or_default() += 1;
+            }
+        }
+        let distinct = counts.len();
+        let mut top_values: Vec<(String, usize)> = counts.into_iter().collect();
+        top_values.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        top_values.truncate(TOP_VALUES);
+
+        let numeric = if series.dtype().is_numeric() {
+            NumericSummary::from_series(series)?
+        } else {
+            None
+        };
+
+        Ok(ColumnProfile {
+            column: series.name().to_string(),
+            data_type: series.dtype().to_string(),
+            null_rate,
+            distinct,
+            top_values,
+            numeric,
+        })
+    }
+}
+
+/// Profile of a sample of the discovered dataset
+#[derive(Debug, Clone, PartialEq)]
+pub struct DatasetProfile {
+    pub files: usize,
+    pub sampled_rows: usize,
+    pub columns: Vec<ColumnProfile>,
+}
+
+/// Profile the first `sample_rows` rows of the input files
+///
+/// Inputs are merged the same way consolidation merges them, so the
+/// profile describes the schema the output would have.
+pub fn profile_files(input_files: &[PathBuf], read: &ReadOptions, sample_rows: usize) -> Result<DatasetProfile> {
+    if input_files.is_empty() {
+        anyhow::bail!("No input files provided");
+    }
+
+    let scans = input_files
+        .iter()
+        .map(|input_file| {
+            let format = InputFormat::from_path(input_file).unwrap_or(InputFormat::Parquet);
+            format.scan_with_options(input_file, read).context(format!("Failed to read {:?}", input_file))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let union_args = UnionArgs { parallel: true, rechunk: true, to_supertypes: true };
+    let sample = concat(scans, union_args)
+        .context("Failed to concatenate DataFrames")?
+        .limit(sample_rows as IdxSize)
+        .collect()
+        .context("Failed to sample input files")?;
+
+    let columns = sample
+        .get_columns()
+        .iter()
+        .map(ColumnProfile::from_series)
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(DatasetProfile { files: input_files.len(), sampled_rows: sample.height(), columns })
+}
+
+impl fmt::Display for DatasetProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Profiled {} rows from {} files", self.sampled_rows, self.files)?;
+        for column in &self.columns {
+            writeln!(f)?;
+            writeln!(f, "{} ({})", column.column, column.data_type)?;
+            writeln!(f, "  null rate: {:.2}%", column.null_rate * 100.0)?;
+            writeln!(f, "  distinct:  {}", column.distinct)?;
+            if let Some(numeric) = &column.numeric {
+                writeln!(
+                    f,
+                    "  range:     min {} / p25 {} / median {} / p75 {} / max {} (mean {:.4})",
+                    numeric.min, numeric.p25, numeric.median, numeric.p75, numeric.max, numeric.mean
+                )?;
+            }
+            if !column.top_values.is_empty() {
+                let top: Vec<String> = column
+                    .top_values
+                    .iter()
+                    .map(|(value, count)| format!("{} ({})", value, count))
+                    .collect();
+                writeln!(f, "  top:       {}", top.join(", "))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::create_test_parquet_file;
+
+    #[test]
+    fn test_profile_files() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let first = temp_dir.path().join("a.parquet");
+        let second = temp_dir.path().join("b.csv");
+        create_test_parquet_file(&first, 0, 10)?;
+        std::fs::write(&second, "id,name,value\n100,x,\n100,x,1.5\n")?;
+
+        let profile = profile_files(&[first, second], &ReadOptions::default(), 11)?;
+        assert_eq!(profile.sampled_rows, 11);
+
+        let id = &profile.columns[0];
+        assert_eq!(id.distinct, 11);
+        let numeric = id.numeric.as_ref().unwrap();
+        assert_eq!(numeric.min, 0.0);
+        assert_eq!(numeric.median, 5.0);
+        assert_eq!(numeric.max, 100.0);
+
+        let value = profile.columns.iter().find(|c| c.column == "value").unwrap();
+        assert!((value.null_rate - 1.0 / 11.0).abs() < 1e-9);
+
+        let rendered = profile.to_string();
+        assert!(rendered.contains("Profiled 11 rows from 2 files"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_top_values() -> Result<()> {
+        let series = Series::new("status", &[Some("a"), Some("b"), Some("a"), None]);
+        let profile = ColumnProfile::from_series(&series)?;
+        assert_eq!(profile.top_values, vec![("a".to_string(), 2), ("b".to_string(), 1)]);
+        assert_eq!(profile.null_rate, 0.25);
+        assert!(profile.numeric.is_none());
+        Ok(())
+    }
+}
@@ -0,0 +1,97 @@
+use std::str::FromStr;
+use anyhow::{Result, Context};
+use polars::prelude::*;
+use regex::Regex;
+
+/// Text substituted for every redacted match
+pub const REDACTED: &str = "[REDACTED]";
+
+/// A pattern scrubbed from the values of one string column
+///
+/// Parsed from `column=/regex/`, e.g. `comment=/\b\d{16}\b/`. The slashes
+/// are optional.
+#[derive(Debug, Clone)]
+pub struct ColumnRedaction {
+    pub column: String,
+    pub pattern: Regex,
+}
+
+impl PartialEq for ColumnRedaction {
+    fn eq(&self, other: &Self) -> bool {
+        self.column == other.column && self.pattern.as_str() == other.pattern.as_str()
+    }
+}
+
+impl FromStr for ColumnRedaction {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (column, pattern) = s
+            .split_once('=')
+            .ok_or_else(|| anyhow::anyhow!("Invalid redaction {:?}: expected column=/regex/", s))?;
+        let pattern = pattern
+            .strip_prefix('/')
+            .and_then(|p| p.strip_suffix('/'))
+            .unwrap_or(pattern);
+        if column.is_empty() || pattern.is_empty() {
+            anyhow::bail!("Invalid redaction {:?}: expected column=/regex/", s);
+        }
+
+        Ok(ColumnRedaction {
+            column: column.to_string(),
+            pattern: Regex::new(pattern).context(format!("Invalid redaction pattern for column {}", column))?,
+        })
+    }
+}
+
+/// Replace every match of each redaction pattern in its column
+pub fn redact(df: &mut DataFrame, redactions: &[ColumnRedaction]) -> Result<()> {
+    for redaction in redactions {
+        let series = df
+            .column(&redaction.column)
+            .context(format!("Column not found in consolidated schema: {}", redaction.column))?;
+        let values = series
+            .utf8()
+            .context(format!("Redaction requires a string column: {}", redaction.column))?;
+
+        let pattern = &redaction.pattern;
+        let redacted = values.apply(|value| pattern.replace_all(value, REDACTED));
+        df.with_column(redacted.into_series())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_column_redaction_from_str() {
+        let redaction: ColumnRedaction = r"comment=/\b\d{16}\b/".parse().unwrap();
+        assert_eq!(redaction.column, "comment");
+        assert_eq!(redaction.pattern.as_str(), r"\b\d{16}\b");
+
+        let redaction: ColumnRedaction = "email=@.*$".parse().unwrap();
+        assert_eq!(redaction.pattern.as_str(), "@.*$");
+
+        assert!("comment".parse::<ColumnRedaction>().is_err());
+        assert!("comment=/(/".parse::<ColumnRedaction>().is_err());
+    }
+
+    #[test]
+    fn test_redact() -> Result<()> {
+        let mut df = df!(
+            "id" => &[1i32, 2, 3],
+            "comment" => &[Some("card 4111111111111111 declined"), Some("no card"), None]
+        )?;
+
+        redact(&mut df, &[r"comment=/\b\d{16}\b/".parse()?])?;
+        let comments: Vec<Option<&str>> = df.column("comment")?.utf8()?.into_iter().collect();
+        assert_eq!(comments, vec![Some("card [REDACTED] declined"), Some("no card"), None]);
+
+        assert!(redact(&mut df, &["id=/1/".parse()?]).is_err());
+        assert!(redact(&mut df, &["missing=/1/".parse()?]).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,446 @@
+use std::fmt;
+use std::fs::{self, File};
+use std::io::Cursor;
+use std::ops::Range;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use anyhow::{Result, Context};
+use arrow::array::RecordBatch;
+use arrow::datatypes::SchemaRef;
+use bytes::Bytes;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use parquet::basic::Compression;
+use parquet::file::reader::{FileReader, SerializedFileReader};
+use parquet::format::{self, PageHeader, PageType};
+use parquet::schema::types::{to_thrift, SchemaDescriptor};
+use parquet::thrift::TSerializable;
+use thrift::protocol::TCompactInputProtocol;
+use crate::footer::{write_footer, MAGIC};
+use crate::interop::record_batches_to_dataframe;
+use crate::writer::{write_dataframe, WriterOptions};
+
+/// The outcome of salvaging a damaged parquet file
+#[derive(Debug, Clone, PartialEq)]
+pub struct RepairSummary {
+    /// Whether the original footer could be read, rather than being rebuilt
+    /// from the page headers
+    pub footer_intact: bool,
+    pub row_groups_found: usize,
+    pub row_groups_recovered: usize,
+    pub rows_recovered: usize,
+}
+
+impl fmt::Display for RepairSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let footer = if self.footer_intact { "intact" } else { "rebuilt from page headers" };
+        writeln!(f, "Footer: {}", footer)?;
+        writeln!(f, "Row groups recovered: {} of {}", self.row_groups_recovered, self.row_groups_found)?;
+        writeln!(f, "Rows recovered: {}", self.rows_recovered)
+    }
+}
+
+/// Salvage the readable row groups of a damaged parquet file into a new file
+///
+/// When the footer is readable, each row group it lists is decoded on its
+/// own and the ones that fail are dropped. When the footer or the tail of
+/// the file is damaged, column chunks are located by scanning page headers
+/// from the start of the file. That needs `schema_from`, a healthy file
+/// written with the same schema and compression, and only works for flat
+/// schemas; row groups cut short by truncation are dropped.
+pub fn repair_file(
+    input: &Path,
+    output: &Path,
+    schema_from: Option<&Path>,
+    options: &WriterOptions,
+) -> Result<RepairSummary> {
+    let data = Bytes::from(fs::read(input).context(format!("Failed to read {:?}", input))?);
+    let (data, footer_intact) = match SerializedFileReader::new(data.clone()) {
+        Ok(_) => (data, true),
+        Err(err) => {
+            let Some(schema_from) = schema_from else {
+                anyhow::bail!(
+                    "The footer of {:?} is unreadable ({}); pass a healthy file with the same schema to rebuild it",
+                    input,
+                    err
+                );
+            };
+            (rebuild_footer(&data, schema_from)?, false)
+        }
+    };
+
+    let row_groups_found = SerializedFileReader::new(data.clone())?.num_row_groups();
+    let mut schema = None;
+    let mut batches = Vec::new();
+    let mut row_groups_recovered = 0;
+    for index in 0..row_groups_found {
+        if let Some((row_group_schema, row_group_batches)) = read_row_group(&data, index) {
+            schema.get_or_insert(row_group_schema);
+            batches.extend(row_group_batches);
+            row_groups_recovered += 1;
+        }
+    }
+    let Some(schema) = schema else {
+        anyhow::bail!("No readable row groups found in {:?}", input);
+    };
+
+    let mut df = record_batches_to_dataframe(&schema, &batches)?;
+    write_dataframe(&mut df, output, options)?;
+
+    Ok(RepairSummary {
+        footer_intact,
+        row_groups_found,
+        row_groups_recovered,
+        rows_recovered: df.height(),
+    })
+}
+
+/// Decode a single row group, or None if any of its pages is unreadable
+fn read_row_group(data: &Bytes, index: usize) -> Option<(SchemaRef, Vec<RecordBatch>)> {
+    // Decoders may panic rather than fail on corrupt pages
+    let result = panic::catch_unwind(AssertUnwindSafe(|| -> Result<(SchemaRef, Vec<RecordBatch>)> {
+        let builder = ParquetRecordBatchReaderBuilder::try_new(data.clone())?.with_row_groups(vec![index]);
+        let schema = builder.schema().clone();
+        let batches = builder.build()?.collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok((schema, batches))
+    }));
+    result.ok()?.ok()
+}
+
+/// A page found by scanning the body of a file
+#[derive(Debug, Clone)]
+struct ScannedPage {
+    /// Offset of the page header
+    start: usize,
+    /// Offset just past the page data
+    end: usize,
+    dictionary: bool,
+    encoding: format::Encoding,
+    num_values: i64,
+    /// Header plus uncompressed data size
+    uncompressed_size: i64,
+}
+
+impl ScannedPage {
+    fn from_header(header: &PageHeader, start: usize, header_len: usize) -> Option<Self> {
+        let (dictionary, encoding, num_values) = match header.type_ {
+            PageType::DICTIONARY_PAGE => {
+                let page = header.dictionary_page_header.as_ref()?;
+                (true, page.encoding, page.num_values)
+            }
+            PageType::DATA_PAGE => {
+                let page = header.data_page_header.as_ref()?;
+                (false, page.encoding, page.num_values)
+            }
+            PageType::DATA_PAGE_V2 => {
+                let page = header.data_page_header_v2.as_ref()?;
+                (false, page.encoding, page.num_rows)
+            }
+            _ => return None,
+        };
+        if header.compressed_page_size < 0 || header.uncompressed_page_size < 0 || num_values < 0 {
+            return None;
+        }
+
+        Some(ScannedPage {
+            start,
+            end: start + header_len + header.compressed_page_size as usize,
+            dictionary,
+            encoding,
+            num_values: num_values as i64,
+            uncompressed_size: (header_len as i64) + header.uncompressed_page_size as i64,
+        })
+    }
+
+    fn is_dictionary_encoded(&self) -> bool {
+        !self.dictionary && matches!(self.encoding, format::Encoding::PLAIN_DICTIONARY | format::Encoding::RLE_DICTIONARY)
+    }
+
+    /// Whether a column chunk may begin with this page: dictionary encoded
+    /// data pages need the chunk's dictionary page before them
+    fn can_start_chunk(&self) -> bool {
+        !self.is_dictionary_encoded()
+    }
+}
+
+/// Read page headers from the start of the file until one is unreadable or
+/// its data runs past the end
+fn scan_pages(data: &[u8]) -> Vec<ScannedPage> {
+    let mut pages = Vec::new();
+    let mut offset = MAGIC.len();
+    while offset < data.len() {
+        let mut cursor = Cursor::new(&data[offset..]);
+        let Ok(header) = PageHeader::read_from_in_protocol(&mut TCompactInputProtocol::new(&mut cursor)) else {
+            break;
+        };
+        let Some(page) = ScannedPage::from_header(&header, offset, cursor.position() as usize) else {
+            break;
+        };
+        if page.end > data.len() {
+            break;
+        }
+        offset = page.end;
+        pages.push(page);
+    }
+    pages
+}
+
+/// Number of values in a column chunk made of `pages`, or None if they
+/// cannot form one
+fn chunk_values(pages: &[ScannedPage]) -> Option<i64> {
+    let (first, rest) = pages.split_first()?;
+    let data_pages = if first.dictionary { rest } else { pages };
+    if !first.can_start_chunk() || data_pages.is_empty() || data_pages.iter().any(|page| page.dictionary) {
+        return None;
+    }
+    Some(data_pages.iter().map(|page| page.num_values).sum())
+}
+
+/// Length of the column chunk at the start of `pages` holding exactly
+/// `values` values
+fn chunk_len(pages: &[ScannedPage], values: i64) -> Option<usize> {
+    for len in 1..=pages.len() {
+        let at_boundary = pages.get(len).is_none_or(ScannedPage::can_start_chunk);
+        match chunk_values(&pages[..len]) {
+            Some(found) if found == values && at_boundary => return Some(len),
+            Some(found) if found > values => return None,
+            None if len > 1 => return None,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Split the pages at the start of `pages` into the column chunks of one
+/// row group, as page index ranges
+///
+/// Every column of a flat schema holds one value per row, so the first
+/// column's chunk is grown until the following pages divide into chunks of
+/// the same size for the remaining columns.
+fn next_row_group(pages: &[ScannedPage], columns: usize) -> Option<Vec<Range<usize>>> {
+    for first_len in 1..=pages.len() {
+        if pages.get(first_len).is_some_and(|page| !page.can_start_chunk()) {
+            continue;
+        }
+        let Some(values) = chunk_values(&pages[..first_len]) else {
+            continue;
+        };
+
+        let mut chunks = Vec::with_capacity(columns);
+        chunks.push(0..first_len);
+        while chunks.len() < columns {
+            let start = chunks.last().map_or(0, |chunk| chunk.end);
+            let Some(len) = chunk_len(&pages[start..], values) else {
+                break;
+            };
+            chunks.push(start..start + len);
+        }
+        if chunks.len() == columns {
+            return Some(chunks);
+        }
+    }
+    None
+}
+
+/// Rebuild a footer for the complete row groups at the start of `data`,
+/// using the schema and compression of the healthy file `schema_from`
+fn rebuild_footer(data: &[u8], schema_from: &Path) -> Result<Bytes> {
+    let reference = SerializedFileReader::new(File::open(schema_from)?)
+        .context(format!("Failed to read the footer of {:?}", schema_from))?;
+    let metadata = reference.metadata();
+    let schema = metadata.file_metadata().schema_descr();
+    if schema.columns().iter().any(|column| column.max_rep_level() > 0) {
+        anyhow::bail!("Only files with flat schemas can be recovered without a footer");
+    }
+    let Some(reference_row_group) = metadata.row_groups().first() else {
+        anyhow::bail!("{:?} has no row groups to take compression settings from", schema_from);
+    };
+    let codecs: Vec<Compression> = reference_row_group.columns().iter().map(|column| column.compression()).collect();
+    if !data.starts_with(MAGIC) {
+        anyhow::bail!("Not a parquet file: the leading magic bytes are missing");
+    }
+
+    let pages = scan_pages(data);
+    let mut row_groups = Vec::new();
+    let mut start = 0;
+    while let Some(chunks) = next_row_group(&pages[start..], schema.num_columns()) {
+        let chunks: Vec<Range<usize>> = chunks.into_iter().map(|chunk| chunk.start + start..chunk.end + start).collect();
+        start = chunks.last().map_or(start, |chunk| chunk.end);
+        row_groups.push(row_group_metadata(&pages, &chunks, schema, &codecs, row_groups.len()));
+    }
+
+    let body_end = pages[..start].last().map_or(MAGIC.len(), |page| page.end);
+    let file_metadata = format::FileMetaData::new(
+        1,
+        to_thrift(schema.root_schema())?,
+        row_groups.iter().map(|row_group| row_group.num_rows).sum(),
+        row_groups,
+        metadata.file_metadata().key_value_metadata().cloned(),
+        metadata.file_metadata().created_by().map(str::to_string),
+        None,
+        None,
+        None,
+    );
+
+    let mut repaired = data[..body_end].to_vec();
+    write_footer(&mut repaired, &file_metadata)?;
+    Ok(Bytes::from(repaired))
+}
+
+fn row_group_metadata(
+    pages: &[ScannedPage],
+    chunks: &[Range<usize>],
+    schema: &SchemaDescriptor,
+    codecs: &[Compression],
+    ordinal: usize,
+) -> format::RowGroup {
+    let mut num_rows = 0;
+    let mut total_byte_size = 0;
+    let columns = chunks
+        .iter()
+        .enumerate()
+        .map(|(index, chunk)| {
+            let chunk_pages = &pages[chunk.clone()];
+            let first = &chunk_pages[0];
+            let data_page = chunk_pages.iter().find(|page| !page.dictionary).unwrap_or(first);
+            let mut encodings = vec![format::Encoding::RLE];
+            for page in chunk_pages {
+                if !encodings.contains(&page.encoding) {
+                    encodings.push(page.encoding);
+                }
+            }
+            let uncompressed_size: i64 = chunk_pages.iter().map(|page| page.uncompressed_size).sum();
+            num_rows = chunk_values(chunk_pages).unwrap_or(0);
+            total_byte_size += uncompressed_size;
+
+            let column = schema.column(index);
+            let metadata = format::ColumnMetaData::new(
+                column.physical_type().into(),
+                encodings,
+                column.path().parts().to_vec(),
+                codecs.get(index).copied().unwrap_or(Compression::UNCOMPRESSED).into(),
+                num_rows,
+                uncompressed_size,
+                (chunk_pages[chunk_pages.len() - 1].end - first.start) as i64,
+                None,
+                data_page.start as i64,
+                None,
+                first.dictionary.then_some(first.start as i64),
+                None,
+                None,
+                None,
+                None,
+                None,
+            );
+            format::ColumnChunk::new(None, first.start as i64, metadata, None, None, None, None, None, None)
+        })
+        .collect::<Vec<_>>();
+
+    let file_offset = pages[chunks[0].start].start as i64;
+    let compressed_size = pages[chunks[chunks.len() - 1].end - 1].end as i64 - file_offset;
+    format::RowGroup::new(
+        columns,
+        total_byte_size,
+        num_rows,
+        None,
+        file_offset,
+        compressed_size,
+        ordinal as i16,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use arrow::array::{Int64Array, StringArray};
+    use arrow::datatypes::{DataType, Field, Schema};
+    use parquet::arrow::ArrowWriter;
+    use parquet::file::properties::WriterProperties;
+
+    /// Write 40 rows in row groups of 10, with a dictionary encoded column
+    fn write_row_groups(path: &Path) -> Result<()> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("category", DataType::Utf8, true),
+        ]));
+        let properties = WriterProperties::builder()
+            .set_max_row_group_size(10)
+            .set_compression(Compression::SNAPPY)
+            .build();
+        let mut writer = ArrowWriter::try_new(File::create(path)?, schema.clone(), Some(properties))?;
+        let categories: Vec<Option<&str>> = (0..40).map(|i| [Some("a"), Some("b"), None][i % 3]).collect();
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![Arc::new(Int64Array::from_iter_values(0..40)), Arc::new(StringArray::from(categories))],
+        )?;
+        writer.write(&batch)?;
+        writer.close()?;
+        Ok(())
+    }
+
+    fn read_ids(path: &Path) -> Result<Vec<i64>> {
+        let batches = ParquetRecordBatchReaderBuilder::try_new(File::open(path)?)?
+            .build()?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(batches
+            .iter()
+            .flat_map(|batch| batch.column(0).as_any().downcast_ref::<Int64Array>().unwrap().values().to_vec())
+            .collect())
+    }
+
+    #[test]
+    fn test_repair_truncated_file() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let healthy = temp_dir.path().join("healthy.parquet");
+        let truncated = temp_dir.path().join("truncated.parquet");
+        let output = temp_dir.path().join("repaired.parquet");
+        write_row_groups(&healthy)?;
+
+        // Cut the file inside its third row group
+        let data = fs::read(&healthy)?;
+        let third_row_group = SerializedFileReader::new(File::open(&healthy)?)?.metadata().row_group(2).columns()[1]
+            .data_page_offset() as usize;
+        fs::write(&truncated, &data[..third_row_group + 3])?;
+
+        let err = repair_file(&truncated, &output, None, &WriterOptions::default()).unwrap_err();
+        assert!(err.to_string().contains("footer"));
+
+        let summary = repair_file(&truncated, &output, Some(&healthy), &WriterOptions::default())?;
+        assert_eq!(
+            summary,
+            RepairSummary { footer_intact: false, row_groups_found: 2, row_groups_recovered: 2, rows_recovered: 20 }
+        );
+        assert_eq!(read_ids(&output)?, (0..20).collect::<Vec<_>>());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_repair_corrupt_row_group() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let input = temp_dir.path().join("input.parquet");
+        let output = temp_dir.path().join("repaired.parquet");
+        write_row_groups(&input)?;
+
+        let summary = repair_file(&input, &output, None, &WriterOptions::default())?;
+        assert!(summary.footer_intact);
+        assert_eq!(summary.rows_recovered, 40);
+
+        // Overwrite the first page header of the second row group
+        let mut data = fs::read(&input)?;
+        let offset = SerializedFileReader::new(File::open(&input)?)?.metadata().row_group(1).columns()[0]
+            .data_page_offset() as usize;
+        data[offset..offset + 8].fill(0xff);
+        fs::write(&input, &data)?;
+
+        let summary = repair_file(&input, &output, None, &WriterOptions::default())?;
+        assert_eq!(
+            summary,
+            RepairSummary { footer_intact: true, row_groups_found: 4, row_groups_recovered: 3, rows_recovered: 30 }
+        );
+        assert_eq!(read_ids(&output)?, (0..10).chain(20..40).collect::<Vec<_>>());
+        assert!(summary.to_string().contains("Row groups recovered: 3 of 4"));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,238 @@
+use std::fmt;
+use std::fs::File;
+use std::path::Path;
+use anyhow::{Result, Context};
+use parquet::file::reader::{FileReader, SerializedFileReader};
+use polars::prelude::*;
+use serde::Serialize;
+use crate::hll::approx_distinct_count;
+use crate::writer::{write_dataframe, WriterOptions};
+
+/// Null count of a single top-level column in the consolidated output
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnNulls {
+    pub column: String,
+    pub null_count: usize,
+    pub total_rows: usize,
+}
+
+impl ColumnNulls {
+    /// Share of rows that are null, from 0 to 100
+    pub fn null_percentage(&self) -> f64 {
+        if self.total_rows == 0 {
+            return 0.0;
+        }
+        self.null_count as f64 * 100.0 / self.total_rows as f64
+    }
+
+    /// Whether every row of a non-empty output is null, as happens when a
+    /// column only exists in inputs that contributed no rows to it
+    pub fn is_all_null(&self) -> bool {
+        self.total_rows > 0 && self.null_count == self.total_rows
+    }
+}
+
+/// Per-column completeness of a consolidated parquet file
+#[derive(Debug, Clone, PartialEq)]
+pub struct NullReport {
+    pub total_rows: usize,
+    pub columns: Vec<ColumnNulls>,
+}
+
+/// Count nulls in every top-level column of a parquet file
+pub fn null_report(path: &Path) -> Result<NullReport> {
+    let scan = LazyFrame::scan_parquet(path, Default::default())
+        .context(format!("Failed to read {:?}", path))?;
+    let total_rows = scan.clone().select([count()]).collect()?;
+    let total_rows = total_rows.get_columns()[0].u32()?.get(0).unwrap_or(0) as usize;
+    let null_counts = scan.null_count().collect()?;
+
+    let columns = null_counts
+        .get_columns()
+        .iter()
+        .map(|series| {
+            Ok(ColumnNulls {
+                column: series.name().to_string(),
+                null_count: series.cast(&DataType::UInt64)?.u64()?.get(0).unwrap_or(0) as usize,
+                total_rows,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(NullReport { total_rows, columns })
+}
+
+impl fmt::Display for NullReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let width = self.columns.iter().map(|c| c.column.len()).max().unwrap_or(0).max("column".len());
+        writeln!(f, "Null report ({} rows)", self.total_rows)?;
+        writeln!(f, "{:<width$}  {:>12}  {:>8}", "column", "nulls", "null %")?;
+        for column in &self.columns {
+            write!(
+                f,
+                "{:<width$}  {:>12}  {:>7.2}%",
+                column.column,
+                column.null_count,
+                column.null_percentage()
+            )?;
+            if column.is_all_null() {
+                write!(f, "  (all null)")?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// Summary statistics of one top-level column of the consolidated output
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ColumnStats {
+    pub column: String,
+    pub data_type: String,
+    pub null_count: usize,
+    /// Number of distinct values, including null; absent for types that
+    /// cannot be hashed, such as nested lists
+    pub distinct_count: Option<usize>,
+    /// HyperLogLog estimate of the number of distinct values, including
+    /// null, within about 1%; cheap enough to recompute on any output
+    pub approx_distinct_count: Option<u64>,
+    /// Smallest and largest non-null values, rendered as text; absent for
+    /// nested and all-null columns
+    pub min: Option<String>,
+    pub max: Option<String>,
+}
+
+/// Statistics describing a consolidated output file, for catalogs and
+/// monitoring
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct OutputStats {
+    pub row_count: usize,
+    pub row_group_count: usize,
+    pub columns: Vec<ColumnStats>,
+}
+
+/// Compute per-column statistics of a parquet file
+pub fn output_stats(path: &Path) -> Result<OutputStats> {
+    let reader = SerializedFileReader::new(File::open(path)?)
+        .context(format!("Failed to read {:?}", path))?;
+    let row_group_count = reader.metadata().num_row_groups();
+    let df = LazyFrame::scan_parquet(path, Default::default())?.collect()?;
+
+    let columns = df
+        .get_columns()
+        .iter()
+        .map(|series| ColumnStats {
+            column: series.name().to_string(),
+            data_type: series.dtype().to_string(),
+            null_count: series.null_count(),
+            distinct_count: series.n_unique().ok(),
+            approx_distinct_count: approx_distinct_count(series),
+            min: value_to_string(series.min_as_series()),
+            max: value_to_string(series.max_as_series()),
+        })
+        .collect();
+
+    Ok(OutputStats { row_count: df.height(), row_group_count, columns })
+}
+
+fn value_to_string(series: Series) -> Option<String> {
+    match series.get(0).ok()? {
+        AnyValue::Null => None,
+        AnyValue::Utf8(value) => Some(value.to_string()),
+        value => Some(value.to_string()),
+    }
+}
+
+impl OutputStats {
+    /// Write the statistics as JSON, or as a parquet table with one row
+    /// per column when the path has a `.parquet` extension
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let is_parquet = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("parquet"));
+
+        if is_parquet {
+            let columns = &self.columns;
+            let mut df = df!(
+                "column" => columns.iter().map(|c| c.column.as_str()).collect::<Vec<_>>(),
+                "data_type" => columns.iter().map(|c| c.data_type.as_str()).collect::<Vec<_>>(),
+                "null_count" => columns.iter().map(|c| c.null_count as u64).collect::<Vec<_>>(),
+                "distinct_count" => columns.iter().map(|c| c.distinct_count.map(|n| n as u64)).collect::<Vec<_>>(),
+                "approx_distinct_count" => columns.iter().map(|c| c.approx_distinct_count).collect::<Vec<_>>(),
+                "min" => columns.iter().map(|c| c.min.as_deref()).collect::<Vec<_>>(),
+                "max" => columns.iter().map(|c| c.max.as_deref()).collect::<Vec<_>>(),
+                "row_count" => vec![self.row_count as u64; columns.len()]
+            )?;
+            write_dataframe(&mut df, path, &WriterOptions::default())
+        } else {
+            let file = File::create(path).context(format!("Failed to create {:?}", path))?;
+            serde_json::to_writer_pretty(file, self)?;
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_null_report() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let output_file = temp_dir.path().join("output.parquet");
+        let mut df = df!(
+            "id" => &[1i32, 2, 3, 4],
+            "name" => &[Some("a"), None, Some("c"), None],
+            "legacy" => &[None::<i64>, None, None, None]
+        )?;
+        write_dataframe(&mut df, &output_file, &WriterOptions::default())?;
+
+        let report = null_report(&output_file)?;
+        assert_eq!(report.total_rows, 4);
+        assert_eq!(report.columns[0].null_count, 0);
+        assert_eq!(report.columns[1].null_count, 2);
+        assert_eq!(report.columns[1].null_percentage(), 50.0);
+        assert!(report.columns[2].is_all_null());
+
+        let rendered = report.to_string();
+        assert!(rendered.contains("Null report (4 rows)"));
+        assert!(rendered.contains("50.00%"));
+        assert!(rendered.contains("(all null)"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_output_stats() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let output_file = temp_dir.path().join("output.parquet");
+        let mut df = df!(
+            "id" => &[3i32, 1, 2, 2],
+            "name" => &[Some("b"), None, Some("a"), Some("c")]
+        )?;
+        write_dataframe(&mut df, &output_file, &WriterOptions::default())?;
+
+        let stats = output_stats(&output_file)?;
+        assert_eq!(stats.row_count, 4);
+        assert_eq!(stats.row_group_count, 1);
+        assert_eq!(stats.columns[0].distinct_count, Some(3));
+        assert_eq!(stats.columns[0].approx_distinct_count, Some(3));
+        assert_eq!(stats.columns[1].approx_distinct_count, Some(4));
+        assert_eq!(stats.columns[0].min.as_deref(), Some("1"));
+        assert_eq!(stats.columns[0].max.as_deref(), Some("3"));
+        assert_eq!(stats.columns[1].null_count, 1);
+        assert_eq!(stats.columns[1].min.as_deref(), Some("a"));
+
+        let json_file = temp_dir.path().join("stats.json");
+        stats.write(&json_file)?;
+        let json: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&json_file)?)?;
+        assert_eq!(json["columns"][1]["max"], "c");
+        assert_eq!(json["columns"][1]["approx_distinct_count"], 4);
+
+        let parquet_file = temp_dir.path().join("stats.parquet");
+        stats.write(&parquet_file)?;
+        assert_eq!(null_report(&parquet_file)?.total_rows, 2);
+
+        Ok(())
+    }
+}
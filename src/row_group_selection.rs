@@ -0,0 +1,125 @@
+use std::fmt;
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use anyhow::Result;
+
+/// A range of row groups of one parquet input, the only ones read from it
+///
+/// Written `FILE:START-END` with both ends included, or `FILE:INDEX` for
+/// a single row group, counting from 0, e.g. `big.parquet:0-9`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RowGroupSelection {
+    pub path: PathBuf,
+    pub row_groups: Range<usize>,
+}
+
+impl RowGroupSelection {
+    /// Whether the selection names the file at `path`, under that path or
+    /// another one resolving to the same file
+    pub(crate) fn selects(&self, path: &Path) -> bool {
+        self.path == path
+            || matches!((fs::canonicalize(&self.path), fs::canonicalize(path)), (Ok(ours), Ok(theirs)) if ours == theirs)
+    }
+}
+
+impl fmt::Display for RowGroupSelection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}-{}", self.path.display(), self.row_groups.start, self.row_groups.end - 1)
+    }
+}
+
+impl FromStr for RowGroupSelection {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || anyhow::anyhow!("Invalid row group selection: {} (expected FILE:START-END or FILE:INDEX)", s);
+        let (path, range) = s.rsplit_once(':').ok_or_else(invalid)?;
+        let (start, end) = range.split_once('-').unwrap_or((range, range));
+        let start: usize = start.trim().parse().map_err(|_| invalid())?;
+        let end: usize = end.trim().parse().map_err(|_| invalid())?;
+        if path.is_empty() || end < start {
+            return Err(invalid());
+        }
+        Ok(RowGroupSelection { path: PathBuf::from(path), row_groups: start..end + 1 })
+    }
+}
+
+/// The row groups `selections` pick from the parquet file at `path`, in
+/// file order, when any of them names it; failing when one lies past the
+/// file's `row_group_count`
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// use parquet_consolidator::{selected_row_groups, RowGroupSelection};
+///
+/// let selections: Vec<RowGroupSelection> = vec!["big.parquet:4-5".parse().unwrap(), "big.parquet:0-1".parse().unwrap()];
+/// assert_eq!(selected_row_groups(&selections, Path::new("big.parquet"), 8).unwrap(), Some(vec![0, 1, 4, 5]));
+/// assert_eq!(selected_row_groups(&selections, Path::new("other.parquet"), 8).unwrap(), None);
+/// assert!(selected_row_groups(&selections, Path::new("big.parquet"), 5).is_err());
+/// ```
+pub fn selected_row_groups(selections: &[RowGroupSelection], path: &Path, row_group_count: usize) -> Result<Option<Vec<usize>>> {
+    let mut row_groups = Vec::new();
+    let mut selected = false;
+    for selection in selections.iter().filter(|selection| selection.selects(path)) {
+        if selection.row_groups.end > row_group_count {
+            anyhow::bail!(
+                "Cannot select row groups {}-{} of {:?}, which has {}",
+                selection.row_groups.start,
+                selection.row_groups.end - 1,
+                path,
+                row_group_count
+            );
+        }
+        row_groups.extend(selection.row_groups.clone());
+        selected = true;
+    }
+    row_groups.sort_unstable();
+    row_groups.dedup();
+    Ok(selected.then_some(row_groups))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use polars::prelude::*;
+    use crate::consolidator::{consolidate_with_options, ConsolidateOptions};
+    use crate::formats::ReadOptions;
+
+    #[test]
+    fn test_row_group_selection() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let big = temp_dir.path().join("big.parquet");
+        let mut df = df!("id" => (0..1000).collect::<Vec<i32>>())?;
+        ParquetWriter::new(File::create(&big)?).with_row_group_size(Some(100)).finish(&mut df)?;
+        let small = temp_dir.path().join("small.parquet");
+        ParquetWriter::new(File::create(&small)?).finish(&mut df.head(Some(10)))?;
+
+        let selection: RowGroupSelection = format!("{}:2-4", big.display()).parse()?;
+        assert_eq!(selection.row_groups, 2..5);
+        assert_eq!(selection.to_string(), format!("{}:2-4", big.display()));
+        assert!("big.parquet:4-2".parse::<RowGroupSelection>().is_err());
+        assert!("big.parquet".parse::<RowGroupSelection>().is_err());
+
+        // The last row group too, given as a single index
+        let read = ReadOptions { row_groups: vec![selection, format!("{}:9", big.display()).parse()?], ..Default::default() };
+        let output = temp_dir.path().join("out.parquet");
+        let options = ConsolidateOptions { read, ..Default::default() };
+        assert_eq!(consolidate_with_options(&[big.clone(), small.clone()], &output, &options)?.rows_written, 410);
+        let ids = LazyFrame::scan_parquet(&output, Default::default())?.collect()?;
+        let ids: Vec<i32> = ids.column("id")?.i32()?.into_no_null_iter().collect();
+        assert_eq!(&ids[..2], [200, 201]);
+        assert_eq!(ids[300], 900);
+
+        let read = ReadOptions { row_groups: vec![format!("{}:8-10", big.display()).parse()?], ..Default::default() };
+        let options = ConsolidateOptions { read, ..Default::default() };
+        let err = consolidate_with_options(&[big], &output, &options).unwrap_err();
+        assert!(format!("{:#}", err).contains("Cannot select row groups 8-10"), "{:#}", err);
+
+        Ok(())
+    }
+}
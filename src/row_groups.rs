@@ -0,0 +1,429 @@
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use anyhow::{Result, Context};
+use arrow::array::RecordBatch;
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use parquet::arrow::ArrowWriter;
+use parquet::arrow::arrow_reader::{ArrowReaderMetadata, ParquetRecordBatchReaderBuilder};
+use parquet::file::properties::DEFAULT_MAX_ROW_GROUP_SIZE;
+use crate::consolidator::ConsolidateOptions;
+use crate::field_metadata::attach_field_metadata;
+use crate::formats::{
+    decode_int96_column, int96_columns, is_buffered_input, is_int96_column, splice_columns, InputFormat, ReadOptions,
+};
+use crate::warnings::{WarningKind, WarningLog};
+use crate::writer::WriterOptions;
+
+/// Writes record batches into row groups within configured size limits
+///
+/// Row groups are closed as soon as they reach `max_rows`. Callers that know
+/// where natural boundaries lie (such as input row groups) call `boundary`,
+/// which closes the current row group only once it holds `min_rows`, so
+/// runs of tiny inputs are coalesced.
+pub(crate) struct RowGroupWriter<W: Write + Send> {
+    writer: ArrowWriter<W>,
+    schema: SchemaRef,
+    min_rows: usize,
+    max_rows: usize,
+    buffered_rows: usize,
+}
+
+impl<W: Write + Send> RowGroupWriter<W> {
+    pub(crate) fn try_new(
+        sink: W,
+        schema: SchemaRef,
+        options: &WriterOptions,
+        max_rows: usize,
+        warnings: &WarningLog,
+    ) -> Result<Self> {
+        let schema = match options.compat {
+            Some(mode) => {
+                for warning in mode.lint(&schema) {
+                    warnings.warn(WarningKind::CompatLint, warning);
+                }
+                SchemaRef::new(mode.normalize_schema(&schema))
+            }
+            None => schema,
+        };
+        let schema = attach_field_metadata(schema, &options.field_metadata);
+        if let Some(reader) = options.target_reader {
+            for warning in reader.check(&schema, options) {
+                warnings.warn(WarningKind::TargetReader, warning);
+            }
+        }
+        let props = options
+            .properties_builder(&schema)?
+            .set_max_row_group_size(usize::MAX)
+            .build();
+
+        Ok(RowGroupWriter {
+            writer: ArrowWriter::try_new(sink, schema.clone(), Some(props))?,
+            schema,
+            min_rows: options.min_row_group_rows.unwrap_or(1),
+            max_rows,
+            buffered_rows: 0,
+        })
+    }
+
+    pub(crate) fn write(&mut self, batch: &RecordBatch) -> Result<()> {
+        let batch = cast_to_schema(batch, &self.schema)?;
+        let mut offset = 0;
+
+        while offset < batch.num_rows() {
+            let take = (self.max_rows - self.buffered_rows).min(batch.num_rows() - offset);
+            self.writer.write(&batch.slice(offset, take))?;
+            self.buffered_rows += take;
+            offset += take;
+
+            if self.buffered_rows >= self.max_rows {
+                self.flush()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn boundary(&mut self) -> Result<()> {
+        if self.buffered_rows >= self.min_rows {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        self.buffered_rows = 0;
+        Ok(())
+    }
+
+    pub(crate) fn close(self) -> Result<()> {
+        self.into_inner()?;
+        Ok(())
+    }
+
+    /// Finish the file and hand back the sink
+    pub(crate) fn into_inner(self) -> Result<W> {
+        self.writer.into_inner().context("Failed to write consolidated parquet file")
+    }
+}
+
+/// Cast a batch's columns to the types of a schema with the same fields
+pub(crate) fn cast_to_schema(batch: &RecordBatch, schema: &SchemaRef) -> Result<RecordBatch> {
+    let columns = batch
+        .columns()
+        .iter()
+        .zip(schema.fields())
+        .map(|(column, field)| {
+            if column.data_type() == field.data_type() {
+                Ok(column.clone())
+            } else {
+                Ok(arrow::compute::cast(column, field.data_type())?)
+            }
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(RecordBatch::try_new(schema.clone(), columns)?)
+}
+
+/// Read one row group of an input as batches of `schema`
+///
+/// INT96 columns that the schema gives a coarser unit than nanoseconds are
+/// decoded straight to that unit, as casting the reader's nanoseconds would
+/// wrap around for timestamps outside 1677-2262.
+pub(crate) fn read_row_group(
+    input: &File,
+    footer: &ArrowReaderMetadata,
+    row_group: usize,
+    schema: &SchemaRef,
+) -> Result<impl Iterator<Item = Result<RecordBatch>>> {
+    let parquet_schema = footer.metadata().file_metadata().schema_descr();
+    let data = Arc::new(input.try_clone()?);
+    let int96 = schema
+        .fields()
+        .iter()
+        .enumerate()
+        .filter_map(|(index, field)| match field.data_type() {
+            DataType::Timestamp(unit, _) if *unit != TimeUnit::Nanosecond && is_int96_column(parquet_schema, field.name()) => {
+                Some((index, field.name(), *unit))
+            }
+            _ => None,
+        })
+        .map(|(index, name, unit)| Ok((index, decode_int96_column(&data, footer.metadata(), name, &[row_group], unit)?)))
+        .collect::<Result<Vec<_>>>()?;
+
+    let reader = ParquetRecordBatchReaderBuilder::new_with_metadata(input.try_clone()?, footer.clone())
+        .with_row_groups(vec![row_group])
+        .build()?;
+    let schema = schema.clone();
+    let mut offset = 0;
+    Ok(reader.map(move |batch| {
+        let batch = splice_columns(&batch?, &int96, offset)?;
+        offset += batch.num_rows();
+        cast_to_schema(&batch, &schema)
+    }))
+}
+
+/// Row group size for writing `total_rows` rows in one pass
+///
+/// Uses the fewest row groups that stay within the maximum and spreads rows
+/// evenly across them, so the trailing row group is never a tiny remainder.
+///
+/// # Examples
+///
+/// ```
+/// use parquet_consolidator::balanced_row_group_size;
+///
+/// assert_eq!(balanced_row_group_size(1001, Some(1000)), 501);
+/// assert_eq!(balanced_row_group_size(10, Some(100)), 10);
+/// ```
+pub fn balanced_row_group_size(total_rows: usize, max_rows: Option<usize>) -> usize {
+    let max_rows = max_rows.unwrap_or(DEFAULT_MAX_ROW_GROUP_SIZE);
+    if total_rows == 0 {
+        return max_rows;
+    }
+    let groups = total_rows.div_ceil(max_rows);
+    total_rows.div_ceil(groups)
+}
+
+/// Read the arrow schema every input shares, failing if any input differs
+///
+/// Only footers are read. INT96 timestamp columns take the unit requested
+/// in the read options.
+pub fn identical_input_schema(input_files: &[PathBuf], read: &ReadOptions) -> Result<SchemaRef> {
+    let mut schema: Option<SchemaRef> = None;
+
+    for input_file in input_files {
+        if InputFormat::from_path(input_file).unwrap_or(InputFormat::Parquet) != InputFormat::Parquet {
+            anyhow::bail!("Row groups can only be preserved for parquet inputs: {:?}", input_file);
+        }
+        if is_buffered_input(input_file) {
+            anyhow::bail!("Row groups can't be preserved for compressed or archived inputs: {:?}", input_file);
+        }
+
+        let file = File::open(input_file)?;
+        let metadata = ArrowReaderMetadata::load(&file, Default::default())
+            .context(format!("Failed to read parquet footer of {:?}", input_file))?;
+        let file_schema = coerce_int96_fields(metadata.schema(), &int96_columns(input_file)?, read);
+
+        match &schema {
+            None => schema = Some(file_schema),
+            Some(expected) if expected.fields() != file_schema.fields() => {
+                anyhow::bail!(
+                    "Row groups can only be preserved when all inputs share an identical schema: {:?} differs",
+                    input_file
+                );
+            }
+            Some(_) => {}
+        }
+    }
+
+    schema.ok_or_else(|| anyhow::anyhow!("No input files provided"))
+}
+
+fn coerce_int96_fields(schema: &SchemaRef, int96_columns: &[String], read: &ReadOptions) -> SchemaRef {
+    let Some(unit) = read.coerce_int96 else {
+        return schema.clone();
+    };
+
+    let fields: Vec<Field> = schema
+        .fields()
+        .iter()
+        .map(|field| {
+            if int96_columns.contains(field.name()) {
+                field.as_ref().clone().with_data_type(DataType::Timestamp(unit.into(), None))
+            } else {
+                field.as_ref().clone()
+            }
+        })
+        .collect();
+
+    SchemaRef::new(Schema::new_with_metadata(fields, schema.metadata().clone()))
+}
+
+/// Consolidate inputs with identical schemas without re-chunking
+///
+/// Each input row group becomes exactly one output row group, so row-group
+/// statistics used for pruning keep their original granularity. When row
+/// group limits are configured, input row groups smaller than the minimum
+/// are coalesced with their neighbours and ones above the maximum are split.
+/// Warnings about the output schema are raised on `warnings`.
+pub fn consolidate_preserving_row_groups(
+    input_files: &[PathBuf],
+    output_path: &Path,
+    options: &ConsolidateOptions,
+    warnings: &WarningLog,
+) -> Result<()> {
+    options.writer.validate_row_group_limits()?;
+
+    let schema = identical_input_schema(input_files, &options.read)?;
+    let max_rows = options.writer.max_row_group_rows.unwrap_or(usize::MAX);
+    let output = File::create(output_path)?;
+    #[cfg(feature = "fault-injection")]
+    crate::fault_injection::inject_fault(crate::fault_injection::FaultPoint::Write, output_path)?;
+    let mut writer = RowGroupWriter::try_new(output, schema, &options.writer, max_rows, warnings)?;
+
+    for input_file in input_files {
+        #[cfg(feature = "fault-injection")]
+        crate::fault_injection::inject_fault(crate::fault_injection::FaultPoint::Read, input_file)?;
+        let input = File::open(input_file)?;
+        copy_row_groups(&input, input_file, &mut writer, options)?;
+    }
+
+    writer.close()
+}
+
+/// Write each row group of one input, marking its end as a boundary
+fn copy_row_groups<W: Write + Send>(
+    input: &File,
+    input_file: &Path,
+    writer: &mut RowGroupWriter<W>,
+    options: &ConsolidateOptions,
+) -> Result<()> {
+    let metadata = ArrowReaderMetadata::load(input, Default::default())?;
+    let num_row_groups = metadata.metadata().num_row_groups();
+
+    if options.verbose {
+        println!("Copying {} row groups from {:?}", num_row_groups, input_file);
+    }
+
+    for row_group in 0..num_row_groups {
+        options.check_cancelled()?;
+        let schema = writer.schema.clone();
+        for batch in read_row_group(input, &metadata, row_group, &schema)? {
+            writer.write(&batch?)?;
+        }
+        writer.boundary()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use arrow::array::{RecordBatchReader, TimestampMillisecondArray};
+    use parquet::file::properties::WriterProperties;
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+    use tempfile::TempDir;
+
+    fn create_file_with_row_groups(path: &Path, start_id: i32, end_id: i32, row_group_size: usize) -> Result<()> {
+        // Rewrite a standard test file with small row groups
+        let staging = path.with_extension("staging");
+        create_test_parquet_file(&staging, start_id, end_id)?;
+        let reader = ParquetRecordBatchReaderBuilder::try_new(File::open(&staging)?)?.build()?;
+        let props = WriterProperties::builder().set_max_row_group_size(row_group_size).build();
+        let mut writer = ArrowWriter::try_new(File::create(path)?, reader.schema(), Some(props))?;
+        for batch in reader {
+            writer.write(&batch?)?;
+        }
+        writer.close()?;
+        std::fs::remove_file(staging)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_preserve_row_groups() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let file1 = temp_dir.path().join("file1.parquet");
+        let file2 = temp_dir.path().join("file2.parquet");
+        let output_file = temp_dir.path().join("output.parquet");
+
+        create_file_with_row_groups(&file1, 0, 10, 4)?;
+        create_file_with_row_groups(&file2, 10, 13, 4)?;
+
+        consolidate_preserving_row_groups(&[file1, file2], &output_file, &ConsolidateOptions::default(), &WarningLog::default())?;
+
+        let reader = SerializedFileReader::new(File::open(&output_file)?)?;
+        let row_counts: Vec<i64> = reader.metadata().row_groups().iter().map(|rg| rg.num_rows()).collect();
+        assert_eq!(row_counts, vec![4, 4, 2, 3]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_preserve_row_groups_with_limits() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let file1 = temp_dir.path().join("file1.parquet");
+        let file2 = temp_dir.path().join("file2.parquet");
+        let file3 = temp_dir.path().join("file3.parquet");
+        let output_file = temp_dir.path().join("output.parquet");
+
+        create_file_with_row_groups(&file1, 0, 2, 2)?;
+        create_file_with_row_groups(&file2, 2, 5, 3)?;
+        create_file_with_row_groups(&file3, 5, 25, 20)?;
+
+        let options = ConsolidateOptions {
+            writer: WriterOptions {
+                min_row_group_rows: Some(4),
+                max_row_group_rows: Some(8),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        consolidate_preserving_row_groups(&[file1, file2, file3], &output_file, &options, &WarningLog::default())?;
+
+        // The two tiny inputs are coalesced, the large one is split at the maximum
+        let reader = SerializedFileReader::new(File::open(&output_file)?)?;
+        let row_counts: Vec<i64> = reader.metadata().row_groups().iter().map(|rg| rg.num_rows()).collect();
+        assert_eq!(row_counts, vec![5, 8, 8, 4]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_preserve_row_groups_coerces_int96() -> Result<()> {
+        use crate::formats::TimestampUnit;
+
+        let temp_dir = TempDir::new()?;
+        let file1 = temp_dir.path().join("file1.parquet");
+        let output_file = temp_dir.path().join("output.parquet");
+
+        create_test_int96_parquet_file(&file1, -200_000, -199_995)?;
+
+        let options = ConsolidateOptions {
+            read: ReadOptions { coerce_int96: Some(TimestampUnit::Milliseconds), ..Default::default() },
+            ..Default::default()
+        };
+        consolidate_preserving_row_groups(&[file1], &output_file, &options, &WarningLog::default())?;
+
+        let builder = ParquetRecordBatchReaderBuilder::try_new(File::open(&output_file)?)?;
+        assert_eq!(
+            builder.schema().field_with_name("ts")?.data_type(),
+            &DataType::Timestamp(arrow::datatypes::TimeUnit::Millisecond, None)
+        );
+
+        // Days before 1677 keep their value instead of wrapping around
+        let batch = builder.build()?.next().unwrap()?;
+        let ts = batch.column_by_name("ts").unwrap().as_any().downcast_ref::<TimestampMillisecondArray>().unwrap();
+        assert_eq!(ts.value(0), -200_000 * 86_400_000);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_balanced_row_group_size() {
+        assert_eq!(balanced_row_group_size(0, Some(10)), 10);
+        assert_eq!(balanced_row_group_size(10, Some(4)), 4);
+        assert_eq!(balanced_row_group_size(9, Some(4)), 3);
+        assert_eq!(balanced_row_group_size(100, None), 100);
+    }
+
+    #[test]
+    fn test_preserve_row_groups_rejects_schema_mismatch() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let file1 = temp_dir.path().join("file1.parquet");
+        let file2 = temp_dir.path().join("file2.parquet");
+        let output_file = temp_dir.path().join("output.parquet");
+
+        create_test_parquet_file(&file1, 0, 10)?;
+        create_test_parquet_file_with_extra_column(&file2, 10, 20)?;
+
+        let result = consolidate_preserving_row_groups(&[file1, file2], &output_file, &ConsolidateOptions::default(), &WarningLog::default());
+        assert!(result.unwrap_err().to_string().contains("identical schema"));
+        assert!(!output_file.exists());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,80 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use anyhow::{Result, Context};
+
+/// Name of the file in the output directory naming the latest complete run
+pub const LATEST_FILE_NAME: &str = "LATEST";
+
+/// A random (version 4) UUID identifying one run
+pub fn new_run_id() -> Result<String> {
+    let mut bytes = [0u8; 16];
+    getrandom::getrandom(&mut bytes).context("Failed to generate a run id")?;
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+    Ok(format!("{}-{}-{}-{}-{}", &hex[..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..]))
+}
+
+/// Directory a run writes its outputs into, `<output_dir>/run=<run_id>`
+pub fn run_directory(output_dir: &Path, run_id: &str) -> PathBuf {
+    output_dir.join(format!("run={}", run_id))
+}
+
+/// Point the `LATEST` file of `output_dir` at the run `run_id`
+///
+/// The pointer holds the run's directory name, e.g. `run=<uuid>`. It is
+/// written under a temporary name and renamed over the old pointer, so
+/// readers see either the previous run or this one, never a partial write.
+pub fn update_latest(output_dir: &Path, run_id: &str) -> Result<()> {
+    let latest = output_dir.join(LATEST_FILE_NAME);
+    #[cfg(feature = "fault-injection")]
+    crate::fault_injection::inject_fault(crate::fault_injection::FaultPoint::Commit, &latest)?;
+    let staging = output_dir.join(format!(".{}.tmp", LATEST_FILE_NAME));
+    fs::write(&staging, format!("run={}\n", run_id)).context(format!("Failed to write {:?}", staging))?;
+    fs::File::open(&staging)?.sync_all()?;
+    fs::rename(&staging, &latest).context(format!("Failed to update {:?}", latest))
+}
+
+/// Directory of the latest complete run in `output_dir`, if any run has
+/// completed
+pub fn latest_run(output_dir: &Path) -> Result<Option<PathBuf>> {
+    let latest = output_dir.join(LATEST_FILE_NAME);
+    if !latest.exists() {
+        return Ok(None);
+    }
+    let name = fs::read_to_string(&latest).context(format!("Failed to read {:?}", latest))?;
+    let name = name.trim();
+    if !name.starts_with("run=") || name.contains(['/', '\\']) {
+        anyhow::bail!("{:?} does not name a run directory: {:?}", latest, name);
+    }
+    Ok(Some(output_dir.join(name)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_latest_run_pointer() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let output_dir = temp_dir.path();
+        assert_eq!(latest_run(output_dir)?, None);
+
+        let first = new_run_id()?;
+        let second = new_run_id()?;
+        assert_ne!(first, second);
+        assert_eq!(first.len(), 36);
+        assert_eq!(&first[14..15], "4");
+
+        update_latest(output_dir, &first)?;
+        assert_eq!(latest_run(output_dir)?, Some(run_directory(output_dir, &first)));
+        update_latest(output_dir, &second)?;
+        assert_eq!(latest_run(output_dir)?, Some(output_dir.join(format!("run={}", second))));
+        assert_eq!(fs::read_dir(output_dir)?.count(), 1);
+
+        fs::write(output_dir.join(LATEST_FILE_NAME), "../elsewhere")?;
+        assert!(latest_run(output_dir).is_err());
+
+        Ok(())
+    }
+}
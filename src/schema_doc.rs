@@ -0,0 +1,219 @@
+use std::fmt;
+use std::fs::File;
+use std::path::PathBuf;
+use std::str::FromStr;
+use anyhow::{Result, Context};
+use parquet::arrow::arrow_reader::ArrowReaderMetadata;
+use crate::formats::{is_buffered_input, InputFormat, ReadOptions};
+use crate::schema_merge::{merge_schemas, MergeOptions};
+
+/// Field metadata keys read as a column's description, in order of preference
+const DESCRIPTION_KEYS: &[&str] = &["description", "comment", "doc"];
+
+/// Document formats the merged schema can be rendered as
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SchemaDocFormat {
+    #[default]
+    Markdown,
+    Html,
+}
+
+impl fmt::Display for SchemaDocFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            SchemaDocFormat::Markdown => "markdown",
+            SchemaDocFormat::Html => "html",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for SchemaDocFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "markdown" | "md" => Ok(SchemaDocFormat::Markdown),
+            "html" => Ok(SchemaDocFormat::Html),
+            other => anyhow::bail!("Unknown schema format: {} (expected markdown or html)", other),
+        }
+    }
+}
+
+/// A top-level column of the merged schema
+#[derive(Debug, Clone, PartialEq)]
+pub struct SchemaColumn {
+    pub name: String,
+    pub data_type: String,
+    pub nullable: bool,
+    pub description: Option<String>,
+}
+
+/// Merge the schemas of the input files the way consolidation would
+///
+/// Types are those of the consolidated output. A column is nullable when
+/// any input declares it nullable; only parquet inputs carry nullability
+/// and description metadata, so columns from other formats are nullable.
+pub fn merged_schema(input_files: &[PathBuf], read: &ReadOptions) -> Result<Vec<SchemaColumn>> {
+    if input_files.is_empty() {
+        anyhow::bail!("No input files provided");
+    }
+
+    let mut scans = Vec::new();
+    let mut arrow_schemas = Vec::new();
+    for input_file in input_files {
+        let format = InputFormat::from_path(input_file).unwrap_or(InputFormat::Parquet);
+        scans.push(format.scan_with_options(input_file, read).context(format!("Failed to read {:?}", input_file))?);
+
+        if format == InputFormat::Parquet && !is_buffered_input(input_file) {
+            let metadata = ArrowReaderMetadata::load(&File::open(input_file)?, Default::default())
+                .context(format!("Failed to read parquet footer of {:?}", input_file))?;
+            arrow_schemas.push(Some(metadata.schema().clone()));
+        } else {
+            arrow_schemas.push(None);
+        }
+    }
+
+    let schemas = scans
+        .iter()
+        .zip(input_files)
+        .map(|(scan, input_file)| {
+            let schema = scan.schema().context(format!("Failed to resolve the schema of {:?}", input_file))?;
+            Ok(schema.as_ref().clone())
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let schema = merge_schemas(&schemas, &MergeOptions::default()).context("Failed to merge input schemas")?;
+
+    let columns = schema
+        .iter()
+        .map(|(name, data_type)| {
+            let fields: Vec<_> = arrow_schemas
+                .iter()
+                .map(|arrow_schema| arrow_schema.as_ref().and_then(|s| s.field_with_name(name).ok()))
+                .collect();
+            let nullable = fields.iter().any(|field| field.is_none_or(|f| f.is_nullable()));
+            let description = fields.iter().flatten().find_map(|field| {
+                DESCRIPTION_KEYS.iter().find_map(|key| field.metadata().get(*key).cloned())
+            });
+
+            SchemaColumn {
+                name: name.to_string(),
+                data_type: data_type.to_string(),
+                nullable,
+                description,
+            }
+        })
+        .collect();
+
+    Ok(columns)
+}
+
+/// Render schema columns as a table in the given document format
+///
+/// # Examples
+///
+/// ```
+/// use parquet_consolidator::{render_schema, SchemaColumn, SchemaDocFormat};
+///
+/// let columns = vec![SchemaColumn {
+///     name: "id".to_string(),
+///     data_type: "i64".to_string(),
+///     nullable: false,
+///     description: Some("Primary key".to_string()),
+/// }];
+/// let markdown = render_schema(&columns, SchemaDocFormat::Markdown);
+/// assert!(markdown.contains("| id | i64 | no | Primary key |"));
+/// ```
+pub fn render_schema(columns: &[SchemaColumn], format: SchemaDocFormat) -> String {
+    let yes_no = |nullable: bool| if nullable { "yes" } else { "no" };
+    let mut out = String::new();
+
+    match format {
+        SchemaDocFormat::Markdown => {
+            out.push_str("| Column | Type | Nullable | Description |\n");
+            out.push_str("| --- | --- | --- | --- |\n");
+            for column in columns {
+                out.push_str(&format!(
+                    "| {} | {} | {} | {} |\n",
+                    escape_markdown(&column.name),
+                    escape_markdown(&column.data_type),
+                    yes_no(column.nullable),
+                    escape_markdown(column.description.as_deref().unwrap_or(""))
+                ));
+            }
+        }
+        SchemaDocFormat::Html => {
+            out.push_str("<table>\n");
+            out.push_str("  <tr><th>Column</th><th>Type</th><th>Nullable</th><th>Description</th></tr>\n");
+            for column in columns {
+                out.push_str(&format!(
+                    "  <tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                    escape_html(&column.name),
+                    escape_html(&column.data_type),
+                    yes_no(column.nullable),
+                    escape_html(column.description.as_deref().unwrap_or(""))
+                ));
+            }
+            out.push_str("</table>\n");
+        }
+    }
+
+    out
+}
+
+fn escape_markdown(text: &str) -> String {
+    text.replace('|', "\\|").replace('\n', " ")
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use arrow::array::{Int32Array, RecordBatch, StringArray};
+    use arrow::datatypes::{DataType as ArrowDataType, Field, Schema};
+    use parquet::arrow::ArrowWriter;
+
+    #[test]
+    fn test_merged_schema() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let parquet_file = temp_dir.path().join("a.parquet");
+        let csv_file = temp_dir.path().join("b.csv");
+
+        let description = HashMap::from([("description".to_string(), "Primary | key".to_string())]);
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", ArrowDataType::Int32, false).with_metadata(description),
+            Field::new("name", ArrowDataType::Utf8, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(Int32Array::from(vec![1])), Arc::new(StringArray::from(vec!["a"]))],
+        )?;
+        let mut writer = ArrowWriter::try_new(File::create(&parquet_file)?, schema, None)?;
+        writer.write(&batch)?;
+        writer.close()?;
+
+        let columns = merged_schema(std::slice::from_ref(&parquet_file), &ReadOptions::default())?;
+        assert!(!columns[0].nullable);
+        assert_eq!(columns[0].description.as_deref(), Some("Primary | key"));
+
+        std::fs::write(&csv_file, "id,name\n2,b\n")?;
+        let columns = merged_schema(&[parquet_file, csv_file], &ReadOptions::default())?;
+        assert!(columns[0].nullable);
+        assert_eq!(columns[1].data_type, "str");
+
+        let markdown = render_schema(&columns, SchemaDocFormat::Markdown);
+        assert!(markdown.contains("Primary \\| key"));
+        let html = render_schema(&columns, SchemaDocFormat::Html);
+        assert!(html.contains("<td>id</td>"));
+
+        Ok(())
+    }
+}
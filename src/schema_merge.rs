@@ -0,0 +1,207 @@
+use std::error::Error;
+use std::fmt;
+use polars::prelude::*;
+use polars_core::utils::try_get_supertype;
+
+/// Rules for unifying the schemas of several inputs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MergeOptions {
+    /// Widen differing column types to a common supertype, e.g. `i32` and
+    /// `i64` to `i64`; otherwise column types must match exactly
+    pub to_supertypes: bool,
+    /// Allow schemas with different columns, merging them into the union
+    /// of their columns in order of first appearance; otherwise every
+    /// schema must have the same columns in the same order
+    pub union_columns: bool,
+}
+
+impl Default for MergeOptions {
+    /// The rules consolidation applies
+    fn default() -> Self {
+        MergeOptions { to_supertypes: true, union_columns: false }
+    }
+}
+
+/// Why a set of schemas cannot be merged
+#[derive(Debug, Clone, PartialEq)]
+pub enum SchemaMergeError {
+    /// No schemas were given
+    Empty,
+    /// A schema has a different number of columns than the first one
+    ColumnCount { schema: usize, expected: usize, found: usize },
+    /// A schema names the column at some position differently than the first one
+    ColumnName { schema: usize, position: usize, expected: String, found: String },
+    /// Two types of a column have no common supertype, or differ while
+    /// supertypes are disabled
+    IncompatibleTypes { column: String, left: DataType, right: DataType },
+}
+
+impl fmt::Display for SchemaMergeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SchemaMergeError::Empty => write!(f, "No schemas to merge"),
+            SchemaMergeError::ColumnCount { schema, expected, found } => write!(
+                f,
+                "Schema {} has {} columns, expected {}",
+                schema, found, expected
+            ),
+            SchemaMergeError::ColumnName { schema, position, expected, found } => write!(
+                f,
+                "Schema {} has column {} at position {}, expected {}",
+                schema, found, position, expected
+            ),
+            SchemaMergeError::IncompatibleTypes { column, left, right } => write!(
+                f,
+                "Column {} has incompatible types {} and {}",
+                column, left, right
+            ),
+        }
+    }
+}
+
+impl Error for SchemaMergeError {}
+
+/// Unify input schemas the way consolidation does
+///
+/// All schemas must have the same columns in the same order, unless
+/// `options` asks for the union of their columns. Types are widened to their
+/// common supertype unless `options` disables it.
+///
+/// # Examples
+///
+/// ```
+/// use polars::prelude::*;
+/// use parquet_consolidator::{merge_schemas, MergeOptions};
+///
+/// let a = Schema::from_iter([Field::new("id", DataType::Int32)]);
+/// let b = Schema::from_iter([Field::new("id", DataType::Int64)]);
+///
+/// let merged = merge_schemas(&[a.clone(), b.clone()], &MergeOptions::default()).unwrap();
+/// assert_eq!(merged.get("id"), Some(&DataType::Int64));
+///
+/// let strict = MergeOptions { to_supertypes: false, ..Default::default() };
+/// assert!(merge_schemas(&[a.clone(), b], &strict).is_err());
+///
+/// let c = Schema::from_iter([Field::new("name", DataType::Utf8)]);
+/// let union = MergeOptions { union_columns: true, ..Default::default() };
+/// assert_eq!(merge_schemas(&[a, c], &union).unwrap().len(), 2);
+/// ```
+pub fn merge_schemas(schemas: &[Schema], options: &MergeOptions) -> Result<Schema, SchemaMergeError> {
+    let (first, rest) = schemas.split_first().ok_or(SchemaMergeError::Empty)?;
+    let mut merged = first.clone();
+    if options.union_columns {
+        for schema in rest {
+            for (name, data_type) in schema.iter() {
+                let merged_type = match merged.get(name) {
+                    Some(existing) => merge_types(name, existing, data_type, options)?,
+                    None => data_type.clone(),
+                };
+                merged.with_column(name.clone(), merged_type);
+            }
+        }
+        return Ok(merged);
+    }
+
+    for (index, schema) in rest.iter().enumerate() {
+        let schema_index = index + 1;
+        if schema.len() != merged.len() {
+            return Err(SchemaMergeError::ColumnCount {
+                schema: schema_index,
+                expected: merged.len(),
+                found: schema.len(),
+            });
+        }
+
+        let mut columns = Vec::with_capacity(merged.len());
+        for (position, ((name, left), (other_name, right))) in merged.iter().zip(schema.iter()).enumerate() {
+            if name != other_name {
+                return Err(SchemaMergeError::ColumnName {
+                    schema: schema_index,
+                    position,
+                    expected: name.to_string(),
+                    found: other_name.to_string(),
+                });
+            }
+
+            columns.push(Field::new(name, merge_types(name, left, right, options)?));
+        }
+        merged = Schema::from_iter(columns);
+    }
+
+    Ok(merged)
+}
+
+/// The type of a column that is `left` in one schema and `right` in another
+fn merge_types(name: &str, left: &DataType, right: &DataType, options: &MergeOptions) -> Result<DataType, SchemaMergeError> {
+    let incompatible = || SchemaMergeError::IncompatibleTypes {
+        column: name.to_string(),
+        left: left.clone(),
+        right: right.clone(),
+    };
+    if left == right {
+        Ok(left.clone())
+    } else if options.to_supertypes {
+        try_get_supertype(left, right).map_err(|_| incompatible())
+    } else {
+        Err(incompatible())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schema(fields: &[(&str, DataType)]) -> Schema {
+        Schema::from_iter(fields.iter().map(|(name, data_type)| Field::new(name, data_type.clone())))
+    }
+
+    #[test]
+    fn test_merge_schemas() {
+        let options = MergeOptions::default();
+        let a = schema(&[("id", DataType::Int32), ("value", DataType::Float32)]);
+        let b = schema(&[("id", DataType::Int64), ("value", DataType::Float32)]);
+        let c = schema(&[("id", DataType::Int32), ("value", DataType::Float64)]);
+
+        let merged = merge_schemas(&[a.clone(), b, c], &options).unwrap();
+        assert_eq!(merged, schema(&[("id", DataType::Int64), ("value", DataType::Float64)]));
+
+        assert_eq!(merge_schemas(&[], &options), Err(SchemaMergeError::Empty));
+
+        let short = schema(&[("id", DataType::Int32)]);
+        assert_eq!(
+            merge_schemas(&[a.clone(), short], &options),
+            Err(SchemaMergeError::ColumnCount { schema: 1, expected: 2, found: 1 })
+        );
+
+        let renamed = schema(&[("id", DataType::Int32), ("amount", DataType::Float32)]);
+        let err = merge_schemas(&[a.clone(), renamed], &options).unwrap_err();
+        assert_eq!(err.to_string(), "Schema 1 has column amount at position 1, expected value");
+
+        let text = schema(&[("id", DataType::Int32), ("value", DataType::Boolean)]);
+        let strict = MergeOptions { to_supertypes: false, ..Default::default() };
+        assert!(matches!(
+            merge_schemas(&[a.clone(), text], &strict),
+            Err(SchemaMergeError::IncompatibleTypes { column, .. }) if column == "value"
+        ));
+
+        let union = MergeOptions { union_columns: true, ..Default::default() };
+        let sparse = schema(&[("tag", DataType::Utf8), ("id", DataType::Int64)]);
+        assert_eq!(
+            merge_schemas(&[a, sparse], &union).unwrap(),
+            schema(&[("id", DataType::Int64), ("value", DataType::Float32), ("tag", DataType::Utf8)])
+        );
+    }
+
+    #[test]
+    fn test_merge_schemas_matches_concat() -> anyhow::Result<()> {
+        let a = df!("id" => &[1i32], "name" => &["a"])?;
+        let b = df!("id" => &[2i64], "name" => &["b"])?;
+
+        let union_args = UnionArgs { parallel: true, rechunk: true, to_supertypes: true };
+        let concatenated = concat([a.clone().lazy(), b.clone().lazy()], union_args)?.schema()?;
+        let merged = merge_schemas(&[a.schema(), b.schema()], &MergeOptions::default())?;
+        assert_eq!(&merged, concatenated.as_ref());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,333 @@
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use anyhow::{Result, Context};
+use arrow::array::{new_null_array, RecordBatch};
+use arrow::compute::{cast_with_options, CastOptions};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use serde::Deserialize;
+use serde_json::Value;
+use crate::footer::{edit_key_value_metadata, MetadataEdit};
+use crate::transform::RowTransform;
+
+/// Footer key holding the registry id of the schema an output conforms to
+pub const SCHEMA_ID_METADATA_KEY: &str = "parquet_consolidator.schema_registry.id";
+
+/// Footer key holding the subject and version of that schema, as
+/// `<subject>:<version>`
+pub const SCHEMA_SUBJECT_METADATA_KEY: &str = "parquet_consolidator.schema_registry.subject";
+
+/// How long connecting to and hearing back from the registry may take
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A column of a registry schema
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegistryField {
+    pub name: String,
+    pub data_type: DataType,
+    pub nullable: bool,
+}
+
+/// A version of a subject's schema, as registered in a Confluent-style
+/// schema registry
+///
+/// Only Avro record schemas of primitive fields are supported: `boolean`,
+/// `int`, `long`, `float`, `double`, `string` and `bytes`, the `date`,
+/// `timestamp-millis`, `timestamp-micros`, `decimal` and `uuid` logical
+/// types, and unions of one of those with `null`, which make the field
+/// nullable. Timestamps are read without a time zone.
+///
+/// As a [`RowTransform`], it makes the consolidated rows conform to the
+/// schema: columns are put in the schema's order and cast to its types, and
+/// missing nullable columns are filled with nulls. Extra or missing required
+/// columns, values that don't cast and nulls in required columns fail the
+/// run.
+///
+/// # Examples
+///
+/// ```
+/// use parquet_consolidator::RegistrySchema;
+///
+/// let response = r#"{"subject": "orders-value", "version": 3, "id": 42,
+///     "schema": "{\"type\": \"record\", \"name\": \"Order\", \"fields\": [{\"name\": \"id\", \"type\": \"long\"}, {\"name\": \"note\", \"type\": [\"null\", \"string\"]}]}"}"#;
+/// let schema = RegistrySchema::from_response(response).unwrap();
+/// assert_eq!(schema.id, 42);
+/// assert_eq!(schema.fields.len(), 2);
+/// assert!(schema.fields[1].nullable);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegistrySchema {
+    pub subject: String,
+    pub version: u64,
+    /// Registry-wide id of the schema
+    pub id: u64,
+    pub fields: Vec<RegistryField>,
+}
+
+/// Body of `GET /subjects/<subject>/versions/<version>`
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct VersionResponse {
+    subject: String,
+    version: u64,
+    id: u64,
+    schema: String,
+    schema_type: Option<String>,
+}
+
+impl RegistrySchema {
+    /// Fetch a version of a subject's schema, or its latest version, from
+    /// the registry at `url`, e.g. `http://localhost:8081`
+    ///
+    /// Only plain `http` is supported.
+    pub fn fetch(url: &str, subject: &str, version: Option<u64>) -> Result<Self> {
+        let (host, base) = parse_url(url)?;
+        let version = version.map_or_else(|| "latest".to_string(), |version| version.to_string());
+        let path = format!("{}/subjects/{}/versions/{}", base, percent_encode(subject), version);
+
+        let address = host
+            .to_socket_addrs()
+            .context(format!("Failed to resolve {}", host))?
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("No address found for {}", host))?;
+        let mut stream = TcpStream::connect_timeout(&address, TIMEOUT)
+            .context(format!("Failed to connect to the schema registry at {}", host))?;
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        // HTTP/1.0 keeps the response body unchunked
+        write!(
+            stream,
+            "GET {} HTTP/1.0\r\nHost: {}\r\nAccept: application/vnd.schemaregistry.v1+json, application/json\r\n\r\n",
+            path, host
+        )?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response)?;
+
+        let (head, body) = response.split_once("\r\n\r\n").unwrap_or((&response, ""));
+        let status_line = head.lines().next().unwrap_or_default();
+        match status_line.split_whitespace().nth(1) {
+            Some(status) if status.starts_with('2') => Self::from_response(body)
+                .context(format!("Invalid schema for subject {} version {} from {}", subject, version, url)),
+            _ => {
+                let message = serde_json::from_str::<Value>(body)
+                    .ok()
+                    .and_then(|error| error["message"].as_str().map(str::to_string))
+                    .unwrap_or_else(|| status_line.to_string());
+                anyhow::bail!("Schema registry has no subject {} version {}: {}", subject, version, message)
+            }
+        }
+    }
+
+    /// Read the body of a registry response for one version of a subject
+    pub fn from_response(body: &str) -> Result<Self> {
+        let response: VersionResponse = serde_json::from_str(body).context("Failed to parse the registry response")?;
+        match response.schema_type.as_deref() {
+            None | Some("AVRO") => {}
+            Some(other) => anyhow::bail!("Only Avro schemas are supported, got a {} schema", other),
+        }
+        let schema: Value = serde_json::from_str(&response.schema).context("Failed to parse the Avro schema")?;
+        if schema["type"] != "record" {
+            anyhow::bail!("Expected an Avro record schema, got: {}", schema["type"]);
+        }
+        let fields = schema["fields"]
+            .as_array()
+            .ok_or_else(|| anyhow::anyhow!("Avro record schema has no fields"))?
+            .iter()
+            .map(|field| {
+                let name = field["name"].as_str().ok_or_else(|| anyhow::anyhow!("Avro field without a name: {}", field))?;
+                let (data_type, nullable) = avro_type(&field["type"]).context(format!("Unsupported type of field {}", name))?;
+                Ok(RegistryField { name: name.to_string(), data_type, nullable })
+            })
+            .collect::<Result<_>>()?;
+        Ok(RegistrySchema { subject: response.subject, version: response.version, id: response.id, fields })
+    }
+
+    pub fn arrow_schema(&self) -> SchemaRef {
+        Arc::new(Schema::new(
+            self.fields
+                .iter()
+                .map(|field| Field::new(&field.name, field.data_type.clone(), field.nullable))
+                .collect::<Vec<_>>(),
+        ))
+    }
+
+    /// Record the schema's id, subject and version in the footer of a
+    /// written output
+    pub fn stamp(&self, output: &Path) -> Result<()> {
+        let edits = [
+            MetadataEdit::Set { key: SCHEMA_ID_METADATA_KEY.to_string(), value: self.id.to_string() },
+            MetadataEdit::Set {
+                key: SCHEMA_SUBJECT_METADATA_KEY.to_string(),
+                value: format!("{}:{}", self.subject, self.version),
+            },
+        ];
+        edit_key_value_metadata(output, &edits).context(format!("Failed to record the schema id in {:?}", output))?;
+        Ok(())
+    }
+}
+
+impl RowTransform for RegistrySchema {
+    fn name(&self) -> &str {
+        "schema-registry"
+    }
+
+    fn transform(&self, batch: RecordBatch) -> Result<RecordBatch> {
+        let batch_schema = batch.schema();
+        if let Some(extra) = batch_schema.fields().iter().find(|field| !self.fields.iter().any(|f| &f.name == field.name())) {
+            anyhow::bail!("Column {} is not in schema {} version {}", extra.name(), self.subject, self.version);
+        }
+        let cast_options = CastOptions { safe: false, ..Default::default() };
+        let columns = self
+            .fields
+            .iter()
+            .map(|field| {
+                let Ok(index) = batch_schema.index_of(&field.name) else {
+                    if field.nullable {
+                        return Ok(new_null_array(&field.data_type, batch.num_rows()));
+                    }
+                    anyhow::bail!("Required column {} of schema {} version {} is missing", field.name, self.subject, self.version);
+                };
+                let column = cast_with_options(batch.column(index), &field.data_type, &cast_options)
+                    .context(format!("Column {} doesn't fit type {} of the registry schema", field.name, field.data_type))?;
+                if !field.nullable && column.null_count() > 0 {
+                    anyhow::bail!(
+                        "Column {} holds {} nulls but is required by schema {} version {}",
+                        field.name,
+                        column.null_count(),
+                        self.subject,
+                        self.version
+                    );
+                }
+                Ok(column)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(RecordBatch::try_new(self.arrow_schema(), columns)?)
+    }
+}
+
+/// The arrow type of an Avro field type, and whether it is nullable
+fn avro_type(avro: &Value) -> Result<(DataType, bool)> {
+    if let Some(branches) = avro.as_array() {
+        let non_null: Vec<&Value> = branches.iter().filter(|branch| *branch != "null").collect();
+        return match non_null[..] {
+            [branch] if non_null.len() < branches.len() => Ok((avro_type(branch)?.0, true)),
+            _ => anyhow::bail!("Only unions of null and one other type are supported, got: {}", avro),
+        };
+    }
+    let logical = avro["logicalType"].as_str();
+    let primitive = match avro {
+        Value::String(name) => name.as_str(),
+        _ => avro["type"].as_str().unwrap_or_default(),
+    };
+    let data_type = match (primitive, logical) {
+        ("int", Some("date")) => DataType::Date32,
+        ("long", Some("timestamp-millis" | "local-timestamp-millis")) => DataType::Timestamp(TimeUnit::Millisecond, None),
+        ("long", Some("timestamp-micros" | "local-timestamp-micros")) => DataType::Timestamp(TimeUnit::Microsecond, None),
+        ("bytes" | "fixed", Some("decimal")) => {
+            let precision = avro["precision"].as_u64().ok_or_else(|| anyhow::anyhow!("Decimal without a precision: {}", avro))?;
+            DataType::Decimal128(precision as u8, avro["scale"].as_u64().unwrap_or(0) as i8)
+        }
+        ("boolean", _) => DataType::Boolean,
+        ("int", _) => DataType::Int32,
+        ("long", _) => DataType::Int64,
+        ("float", _) => DataType::Float32,
+        ("double", _) => DataType::Float64,
+        ("string", _) => DataType::Utf8,
+        ("bytes", _) => DataType::Binary,
+        _ => anyhow::bail!("{}", avro),
+    };
+    Ok((data_type, false))
+}
+
+/// Split `http://host:port/path` into the address and base path
+fn parse_url(url: &str) -> Result<(String, String)> {
+    let rest = url
+        .strip_prefix("http://")
+        .ok_or_else(|| anyhow::anyhow!("Schema registry URL must be an http:// URL, got: {}", url))?;
+    let (authority, path) = match rest.find('/') {
+        Some(index) => rest.split_at(index),
+        None => (rest, ""),
+    };
+    if authority.is_empty() {
+        anyhow::bail!("Schema registry URL has no host: {}", url);
+    }
+    let host = if authority.contains(':') { authority.to_string() } else { format!("{}:80", authority) };
+    Ok((host, path.trim_end_matches('/').to_string()))
+}
+
+/// Escape a subject for use as a path segment
+fn percent_encode(segment: &str) -> String {
+    segment
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (byte as char).to_string(),
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::thread;
+    use arrow::array::{Array, Float64Array, Int32Array, StringArray};
+
+    #[test]
+    fn test_registry_schema() -> Result<()> {
+        let avro = r#"{"type": "record", "name": "Row", "fields": [
+            {"name": "id", "type": "long"},
+            {"name": "value", "type": ["null", {"type": "bytes", "logicalType": "decimal", "precision": 10, "scale": 2}]},
+            {"name": "note", "type": ["null", "string"]}
+        ]}"#;
+        let body = serde_json::json!({ "subject": "rows value", "version": 2, "id": 7, "schema": avro }).to_string();
+
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let url = format!("http://{}/registry/", listener.local_addr()?);
+        let registry = thread::spawn(move || -> Result<String> {
+            let (mut stream, _) = listener.accept()?;
+            let mut request = Vec::new();
+            let mut buffer = [0u8; 4096];
+            while !request.ends_with(b"\r\n\r\n") {
+                let read = stream.read(&mut buffer)?;
+                if read == 0 {
+                    break;
+                }
+                request.extend_from_slice(&buffer[..read]);
+            }
+            write!(stream, "HTTP/1.0 200 OK\r\nContent-Length: {}\r\n\r\n{}", body.len(), body)?;
+            Ok(String::from_utf8(request)?)
+        });
+        let schema = RegistrySchema::fetch(&url, "rows value", None)?;
+        let request = registry.join().unwrap()?;
+        assert!(request.starts_with("GET /registry/subjects/rows%20value/versions/latest HTTP/1.0\r\n"));
+        assert_eq!((schema.id, schema.version), (7, 2));
+        assert_eq!(schema.fields[1].data_type, DataType::Decimal128(10, 2));
+
+        let batch_schema = Arc::new(Schema::new(vec![
+            Field::new("value", DataType::Float64, false),
+            Field::new("id", DataType::Int32, false),
+        ]));
+        let batch = RecordBatch::try_new(batch_schema.clone(), vec![
+            Arc::new(Float64Array::from(vec![1.25, 2.5])),
+            Arc::new(Int32Array::from(vec![1, 2])),
+        ])?;
+        let conformed = schema.transform(batch)?;
+        assert_eq!(conformed.schema(), schema.arrow_schema());
+        assert_eq!(conformed.column(2).null_count(), 2);
+
+        let extra = RecordBatch::try_new(
+            Arc::new(Schema::new(vec![Field::new("id", DataType::Int32, false), Field::new("extra", DataType::Utf8, false)])),
+            vec![Arc::new(Int32Array::from(vec![1])), Arc::new(StringArray::from(vec!["x"]))],
+        )?;
+        assert!(schema.transform(extra).unwrap_err().to_string().contains("Column extra is not in schema"));
+        let missing = RecordBatch::try_new(batch_schema.project(&[0])?.into(), vec![Arc::new(Float64Array::from(vec![1.0]))])?;
+        assert!(schema.transform(missing).unwrap_err().to_string().contains("Required column id"));
+
+        let protobuf = r#"{"subject": "s", "version": 1, "id": 1, "schemaType": "PROTOBUF", "schema": "syntax = \"proto3\";"}"#;
+        assert!(RegistrySchema::from_response(protobuf).is_err());
+
+        Ok(())
+    }
+}
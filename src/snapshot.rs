@@ -0,0 +1,159 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::SystemTime;
+use anyhow::Result;
+
+/// What to do with an input that changed after its run's snapshot
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChangedInputPolicy {
+    /// Fail the output, removing what was written from it
+    #[default]
+    Fail,
+    /// Consolidate the output again without the changed inputs, with a
+    /// warning, reporting them as skipped
+    Skip,
+}
+
+impl fmt::Display for ChangedInputPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ChangedInputPolicy::Fail => "fail",
+            ChangedInputPolicy::Skip => "skip",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for ChangedInputPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "fail" => Ok(ChangedInputPolicy::Fail),
+            "skip" => Ok(ChangedInputPolicy::Skip),
+            other => anyhow::bail!("Unknown changed input policy: {} (expected fail or skip)", other),
+        }
+    }
+}
+
+/// An input whose size or modification time differs from the snapshot
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChangedInput {
+    pub path: PathBuf,
+    /// Size when the snapshot was taken and now; `None` now when the file
+    /// is gone
+    pub size: (u64, Option<u64>),
+}
+
+impl fmt::Display for ChangedInput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.size {
+            (_, None) => write!(f, "{:?} was removed", self.path),
+            (before, Some(now)) if before != now => write!(f, "{:?} changed from {} to {} bytes", self.path, before, now),
+            _ => write!(f, "{:?} was modified", self.path),
+        }
+    }
+}
+
+/// The size and modification time of every input when a run started
+///
+/// Files that arrive later aren't part of the run, as its inputs were
+/// listed when the snapshot was taken; inputs that are rewritten,
+/// appended to or removed while the run reads them are found by
+/// [`InputSnapshot::changed`].
+///
+/// # Examples
+///
+/// ```
+/// use std::fs;
+/// use parquet_consolidator::{ChangedInputPolicy, InputSnapshot};
+/// use parquet_consolidator::test_utils::create_test_parquet_file;
+/// use tempfile::TempDir;
+///
+/// let temp_dir = TempDir::new().unwrap();
+/// let path = temp_dir.path().join("a.parquet");
+/// create_test_parquet_file(&path, 0, 10).unwrap();
+///
+/// let snapshot = InputSnapshot::capture([path.clone()], ChangedInputPolicy::Fail);
+/// assert!(snapshot.changed(&[path.clone()]).is_empty());
+/// fs::remove_file(&path).unwrap();
+/// assert_eq!(snapshot.changed(&[path])[0].size.1, None);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct InputSnapshot {
+    pub policy: ChangedInputPolicy,
+    files: HashMap<PathBuf, (u64, SystemTime)>,
+}
+
+impl InputSnapshot {
+    /// Stat every input, without reading any of its contents; inputs that
+    /// can't be stat'ed are left out, as reading them fails anyway
+    pub fn capture(input_files: impl IntoIterator<Item = PathBuf>, policy: ChangedInputPolicy) -> Self {
+        let files = input_files
+            .into_iter()
+            .filter_map(|path| {
+                let metadata = fs::metadata(&path).ok()?;
+                Some((path, (metadata.len(), metadata.modified().ok()?)))
+            })
+            .collect();
+        InputSnapshot { policy, files }
+    }
+
+    /// The inputs whose size or modification time differ from the
+    /// snapshot, in input order; inputs not in the snapshot are left out
+    pub fn changed(&self, input_files: &[PathBuf]) -> Vec<ChangedInput> {
+        input_files
+            .iter()
+            .filter_map(|path| {
+                let &(size, modified) = self.files.get(path)?;
+                let now = path.metadata().ok();
+                let unchanged = now
+                    .as_ref()
+                    .is_some_and(|now| now.len() == size && now.modified().ok() == Some(modified));
+                (!unchanged).then(|| ChangedInput { path: path.clone(), size: (size, now.map(|now| now.len())) })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use crate::consolidator::{consolidate_with_options, ConsolidateOptions};
+    use crate::test_utils::create_test_parquet_file;
+
+    #[test]
+    fn test_changed_inputs() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let inputs = vec![temp_dir.path().join("a.parquet"), temp_dir.path().join("b.parquet")];
+        create_test_parquet_file(&inputs[0], 0, 10)?;
+        create_test_parquet_file(&inputs[1], 10, 20)?;
+        let snapshot = InputSnapshot::capture(inputs.clone(), ChangedInputPolicy::Fail);
+
+        // A producer rewrites b after the run listed it
+        create_test_parquet_file(&inputs[1], 10, 50)?;
+        let changed = snapshot.changed(&inputs);
+        assert_eq!(changed.len(), 1);
+        assert!(changed[0].to_string().contains("b.parquet\" changed from"));
+
+        let output = temp_dir.path().join("out.parquet");
+        let options = ConsolidateOptions { snapshot: Some(Arc::new(snapshot.clone())), ..Default::default() };
+        let err = consolidate_with_options(&inputs, &output, &options).unwrap_err();
+        assert!(err.to_string().contains("changed after the run's snapshot was taken"));
+        assert!(!output.exists());
+
+        let skipping = InputSnapshot { policy: ChangedInputPolicy::Skip, ..snapshot };
+        let options = ConsolidateOptions { snapshot: Some(Arc::new(skipping)), ..Default::default() };
+        let summary = consolidate_with_options(&inputs, &output, &options)?;
+        assert_eq!(summary.rows_written, 10);
+        assert_eq!(summary.skipped_files, vec![inputs[1].clone()]);
+
+        assert!("ignore".parse::<ChangedInputPolicy>().is_err());
+
+        Ok(())
+    }
+}
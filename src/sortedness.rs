@@ -0,0 +1,244 @@
+use std::fmt;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use anyhow::{Result, Context};
+use arrow::array::{Array, ArrayRef};
+use arrow::compute::SortOptions;
+use arrow::datatypes::DataType;
+use arrow::row::{OwnedRow, RowConverter, SortField};
+use arrow::util::display::array_value_to_string;
+use parquet::arrow::arrow_reader::{ArrowReaderMetadata, ParquetRecordBatchReaderBuilder};
+use parquet::arrow::ProjectionMask;
+use crate::stats_validation::{render_bounds, Bounds};
+
+/// Where a file stops being sorted
+#[derive(Debug, Clone, PartialEq)]
+pub enum SortViolationKind {
+    /// The footer statistics show a row group holding values below every
+    /// value of the row group before it, so no data was decoded
+    RowGroups { max: String, previous_min: String },
+    /// A value is smaller than the one before it, or a null comes after
+    /// non-null values
+    Row { row: u64, value: String, previous: String },
+}
+
+/// The first place a parquet file breaks the sort order it was claimed
+/// to have
+#[derive(Debug, Clone, PartialEq)]
+pub struct SortViolation {
+    pub file: PathBuf,
+    pub column: String,
+    pub row_group: usize,
+    pub kind: SortViolationKind,
+}
+
+impl fmt::Display for SortViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} is not sorted by {}: ", self.file, self.column)?;
+        match &self.kind {
+            SortViolationKind::RowGroups { max, previous_min } => write!(
+                f,
+                "row group {} holds values of at most {} after row group {} with values of at least {}",
+                self.row_group,
+                max,
+                self.row_group - 1,
+                previous_min
+            ),
+            SortViolationKind::Row { row, value, previous } => {
+                write!(f, "row {} (row group {}) holds {} after {}", row, self.row_group, value, previous)
+            }
+        }
+    }
+}
+
+/// Types whose footer statistics order the same way as their values
+fn has_ordered_statistics(data_type: &DataType) -> bool {
+    matches!(
+        data_type,
+        DataType::Int8
+            | DataType::Int16
+            | DataType::Int32
+            | DataType::Int64
+            | DataType::Date32
+            | DataType::Date64
+            | DataType::Time32(_)
+            | DataType::Time64(_)
+            | DataType::Timestamp(_, _)
+            | DataType::Float32
+            | DataType::Float64
+            | DataType::Utf8
+            | DataType::LargeUtf8
+            | DataType::Binary
+    )
+}
+
+/// Check that a parquet file is sorted ascending by a top-level column,
+/// with any nulls first
+///
+/// The footer is checked first: a row group whose stated maximum is below
+/// the stated minimum of the row group before it can't follow it in a
+/// sorted file, so most unsorted files fail without decoding anything.
+/// Statistics may be wider than the data, so passing that check proves
+/// nothing; the column alone is then decoded, one batch at a time, and
+/// each value compared with the one before it, across row group
+/// boundaries too. Returns the first violation found.
+///
+/// # Examples
+///
+/// ```
+/// use parquet_consolidator::check_sorted;
+/// use parquet_consolidator::test_utils::create_test_parquet_file;
+/// use tempfile::TempDir;
+///
+/// let temp_dir = TempDir::new().unwrap();
+/// let path = temp_dir.path().join("sorted.parquet");
+/// create_test_parquet_file(&path, 0, 100).unwrap();
+///
+/// assert_eq!(check_sorted(&path, "id").unwrap(), None);
+/// assert!(check_sorted(&path, "missing").is_err());
+/// ```
+pub fn check_sorted(path: &Path, column: &str) -> Result<Option<SortViolation>> {
+    let file = File::open(path).context(format!("Failed to open {:?}", path))?;
+    let metadata = ArrowReaderMetadata::load(&file, Default::default())
+        .context(format!("Failed to read parquet footer of {:?}", path))?;
+    let index = metadata
+        .schema()
+        .index_of(column)
+        .map_err(|_| anyhow::anyhow!("Column {} to check the sort order of is not in {:?}", column, path))?;
+    let data_type = metadata.schema().field(index).data_type().clone();
+    let parquet_metadata = metadata.metadata().clone();
+    let schema_descr = parquet_metadata.file_metadata().schema_descr();
+    let violation = |row_group, kind| SortViolation { file: path.to_path_buf(), column: column.to_string(), row_group, kind };
+
+    let leaf = (0..schema_descr.num_columns()).find(|&i| schema_descr.column(i).path().parts() == [column.to_string()]);
+    if let (Some(leaf), true) = (leaf, has_ordered_statistics(&data_type)) {
+        let bounds: Vec<Option<Bounds>> = parquet_metadata
+            .row_groups()
+            .iter()
+            .map(|row_group| row_group.column(leaf).statistics().and_then(Bounds::from_statistics))
+            .collect();
+        for (row_group, pair) in bounds.windows(2).enumerate() {
+            let (Some(previous), Some(current)) = (&pair[0], &pair[1]) else {
+                continue;
+            };
+            let out_of_order = match (previous, current) {
+                (Bounds::Int(min, _), Bounds::Int(_, max)) => max < min,
+                (Bounds::Float(min, _), Bounds::Float(_, max)) => max < min,
+                (Bounds::Bytes(min, _), Bounds::Bytes(_, max)) => max < min,
+                _ => false,
+            };
+            if out_of_order {
+                let (previous_min, _) = render_bounds(previous);
+                let (_, max) = render_bounds(current);
+                return Ok(Some(violation(row_group + 1, SortViolationKind::RowGroups { max, previous_min })));
+            }
+        }
+    }
+
+    let converter = RowConverter::new(vec![SortField::new_with_options(
+        data_type,
+        SortOptions { descending: false, nulls_first: true },
+    )])?;
+    // The last value of the previous batch, kept as its array and index so
+    // it is only rendered when it is reported
+    let mut previous: Option<(OwnedRow, ArrayRef, usize)> = None;
+    let mut row = 0u64;
+    for row_group in 0..parquet_metadata.num_row_groups() {
+        let reader = ParquetRecordBatchReaderBuilder::new_with_metadata(file.try_clone()?, metadata.clone())
+            .with_row_groups(vec![row_group])
+            .with_projection(ProjectionMask::roots(schema_descr, [index]))
+            .build()?;
+        for batch in reader {
+            let values = batch?.column(0).clone();
+            if values.is_empty() {
+                continue;
+            }
+            let rows = converter.convert_columns(std::slice::from_ref(&values))?;
+            for i in 0..rows.num_rows() {
+                let out_of_order = match (i, &previous) {
+                    (0, Some((last, last_values, last_index))) => {
+                        (rows.row(0) < last.row()).then(|| (last_values.clone(), *last_index))
+                    }
+                    (0, None) => None,
+                    _ => (rows.row(i) < rows.row(i - 1)).then(|| (values.clone(), i - 1)),
+                };
+                if let Some((previous_values, previous_index)) = out_of_order {
+                    let kind = SortViolationKind::Row {
+                        row: row + i as u64,
+                        value: array_value_to_string(&values, i)?,
+                        previous: array_value_to_string(&previous_values, previous_index)?,
+                    };
+                    return Ok(Some(violation(row_group, kind)));
+                }
+            }
+            row += rows.num_rows() as u64;
+            previous = Some((rows.row(rows.num_rows() - 1).owned(), values.clone(), values.len() - 1));
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use arrow::array::{Int32Array, StringArray};
+    use arrow::datatypes::{Field, Schema};
+    use arrow::record_batch::RecordBatch;
+    use parquet::arrow::ArrowWriter;
+    use parquet::file::properties::WriterProperties;
+
+    fn write_row_groups(path: &Path, row_groups: &[Vec<Option<i32>>]) -> Result<()> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, true),
+            Field::new("name", DataType::Utf8, false),
+        ]));
+        let mut writer = ArrowWriter::try_new(File::create(path)?, schema.clone(), Some(WriterProperties::default()))?;
+        for ids in row_groups {
+            let names: Vec<String> = ids.iter().map(|id| format!("name-{}", 9 - id.unwrap_or(0))).collect();
+            writer.write(&RecordBatch::try_new(schema.clone(), vec![
+                Arc::new(Int32Array::from(ids.clone())),
+                Arc::new(StringArray::from(names)),
+            ])?)?;
+            writer.flush()?;
+        }
+        writer.close()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_sorted() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+
+        let sorted = temp_dir.path().join("sorted.parquet");
+        write_row_groups(&sorted, &[vec![None, Some(1), Some(3)], vec![Some(3), Some(7)]])?;
+        assert_eq!(check_sorted(&sorted, "id")?, None);
+
+        // Caught from the footer alone
+        let swapped = temp_dir.path().join("swapped.parquet");
+        write_row_groups(&swapped, &[vec![Some(5), Some(6)], vec![Some(1), Some(2)]])?;
+        let violation = check_sorted(&swapped, "id")?.expect("row groups are out of order");
+        assert_eq!(violation.kind, SortViolationKind::RowGroups { max: "2".to_string(), previous_min: "5".to_string() });
+        assert!(violation.to_string().ends_with(
+            "is not sorted by id: row group 1 holds values of at most 2 after row group 0 with values of at least 5"
+        ));
+
+        // Overlapping row groups need the data: the boundary, then a trailing null
+        let boundary = temp_dir.path().join("boundary.parquet");
+        write_row_groups(&boundary, &[vec![Some(1), Some(4)], vec![Some(2), Some(9)]])?;
+        let violation = check_sorted(&boundary, "id")?.expect("4 comes before 2");
+        assert_eq!(violation.row_group, 1);
+        assert_eq!(violation.kind, SortViolationKind::Row { row: 2, value: "2".to_string(), previous: "4".to_string() });
+
+        let trailing_null = temp_dir.path().join("trailing_null.parquet");
+        write_row_groups(&trailing_null, &[vec![Some(1), None]])?;
+        let violation = check_sorted(&trailing_null, "id")?.expect("nulls come first");
+        assert_eq!(violation.kind, SortViolationKind::Row { row: 1, value: String::new(), previous: "1".to_string() });
+
+        // Names run the other way
+        assert!(check_sorted(&sorted, "name")?.is_some());
+        assert!(check_sorted(&sorted, "missing").is_err());
+
+        Ok(())
+    }
+}
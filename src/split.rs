@@ -0,0 +1,102 @@
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use anyhow::{Result, Context};
+use parquet::file::reader::{FileReader, SerializedFileReader};
+use polars::prelude::*;
+use crate::field_metadata::read_field_metadata;
+use crate::naming::{output_file_name, partition_values, OutputNameContext, OutputNamer};
+use crate::workspace::Workspace;
+use crate::writer::{write_dataframe, WriterOptions};
+
+/// Split a written parquet file into files of at most `max_rows` rows each
+///
+/// Files within the limit are left alone. Larger ones are rewritten, in row
+/// order, to parts named by `namer`, which by default are the file's name
+/// with `-00000`, `-00001` and so on appended to its stem. The parts are
+/// staged in a [`Workspace`] and only moved into place, replacing existing
+/// files of the same name, once every one of them is written; the original
+/// is removed after that. Only one part's rows are held in memory at a time.
+///
+/// Returns the files the rows ended up in.
+pub fn split_by_rows(
+    path: &Path,
+    max_rows: usize,
+    writer: &WriterOptions,
+    namer: &dyn OutputNamer,
+) -> Result<Vec<PathBuf>> {
+    if max_rows == 0 {
+        anyhow::bail!("The maximum number of rows per file must be at least 1");
+    }
+    let reader = SerializedFileReader::new(File::open(path)?)
+        .context(format!("Failed to read back {:?}", path))?;
+    let rows = reader.metadata().file_metadata().num_rows().max(0) as usize;
+    if rows <= max_rows {
+        return Ok(vec![path.to_path_buf()]);
+    }
+
+    // Parts are written from polars frames, which carry no field metadata
+    let writer = &WriterOptions { field_metadata: read_field_metadata(path)?, ..writer.clone() };
+    let stem = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    let extension = path.extension().map(|ext| format!(".{}", ext.to_string_lossy())).unwrap_or_default();
+    let scan = LazyFrame::scan_parquet(path, Default::default()).context(format!("Failed to read {:?}", path))?;
+    let parent = path.parent().filter(|parent| !parent.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let workspace = Workspace::create(parent)?;
+    let mut parts = Vec::new();
+    let mut staged = Vec::new();
+    for (sequence, offset) in (0..rows).step_by(max_rows).enumerate() {
+        let output = OutputNameContext {
+            default_name: format!("{}-{:05}{}", stem, sequence, extension),
+            partition_values: path.parent().map(partition_values).unwrap_or_default(),
+            sequence,
+            range: None,
+        };
+        let part = path.with_file_name(output_file_name(namer, &output)?);
+        if part == path || parts.contains(&part) {
+            anyhow::bail!("Output namer gave part {} of {:?} the name of another file: {:?}", sequence, path, part);
+        }
+        let mut df = scan.clone().slice(offset as i64, max_rows as IdxSize).collect()?;
+        let staging = workspace.stage(&part);
+        write_dataframe(&mut df, &staging, writer).context(format!("Failed to write {:?}", part))?;
+        staged.push(staging);
+        parts.push(part);
+    }
+    for (staging, part) in staged.iter().zip(&parts) {
+        workspace.commit(staging, part)?;
+    }
+    fs::remove_file(path).context(format!("Failed to remove {:?}", path))?;
+    Ok(parts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::naming::DefaultNamer;
+    use crate::test_utils::create_test_parquet_file;
+
+    #[test]
+    fn test_split_by_rows() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let output = temp_dir.path().join("events.parquet");
+        create_test_parquet_file(&output, 0, 25)?;
+
+        let parts = split_by_rows(&output, 10, &WriterOptions::default(), &DefaultNamer)?;
+        let names: Vec<String> = parts.iter().map(|part| part.file_name().unwrap().to_string_lossy().into_owned()).collect();
+        assert_eq!(names, ["events-00000.parquet", "events-00001.parquet", "events-00002.parquet"]);
+        assert!(!output.exists());
+
+        let ids: Vec<Vec<i32>> = parts
+            .iter()
+            .map(|part| {
+                let df = LazyFrame::scan_parquet(part, Default::default())?.collect()?;
+                Ok(df.column("id")?.i32()?.into_no_null_iter().collect())
+            })
+            .collect::<Result<_>>()?;
+        assert_eq!(ids, [(0..10).collect::<Vec<_>>(), (10..20).collect(), (20..25).collect()]);
+
+        // Files within the limit keep their name
+        assert_eq!(split_by_rows(&parts[2], 10, &WriterOptions::default(), &DefaultNamer)?, [parts[2].clone()]);
+        assert!(split_by_rows(&parts[2], 0, &WriterOptions::default(), &DefaultNamer).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,306 @@
+use std::fmt;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use anyhow::Result;
+use arrow::array::{Array, ArrayRef, AsArray};
+use arrow::compute::cast;
+use arrow::datatypes::{DataType, Float64Type, Int64Type};
+use parquet::arrow::arrow_reader::{ArrowReaderMetadata, ParquetRecordBatchReaderBuilder};
+use parquet::arrow::ProjectionMask;
+use parquet::file::statistics::Statistics;
+use crate::formats::{is_buffered_input, InputFormat};
+
+/// The smallest and largest value of a column chunk, in the physical
+/// domain its statistics are compared in
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Bounds {
+    Int(i64, i64),
+    Float(f64, f64),
+    Bytes(Vec<u8>, Vec<u8>),
+}
+
+impl Bounds {
+    pub(crate) fn from_statistics(statistics: &Statistics) -> Option<Bounds> {
+        match statistics {
+            Statistics::Int32(s) => Some(Bounds::Int(*s.min_opt()? as i64, *s.max_opt()? as i64)),
+            Statistics::Int64(s) => Some(Bounds::Int(*s.min_opt()?, *s.max_opt()?)),
+            Statistics::Float(s) => Some(Bounds::Float(*s.min_opt()? as f64, *s.max_opt()? as f64)),
+            Statistics::Double(s) => Some(Bounds::Float(*s.min_opt()?, *s.max_opt()?)),
+            Statistics::ByteArray(s) => {
+                Some(Bounds::Bytes(s.min_bytes_opt()?.to_vec(), s.max_bytes_opt()?.to_vec()))
+            }
+            _ => None,
+        }
+    }
+
+    /// Bounds of the non-null values of `array`, for the types whose
+    /// statistics compare the same way as their arrow values
+    ///
+    /// Unsigned integers and decimals are left out, as their statistics
+    /// order or scale differs from the signed physical values.
+    fn of_array(array: &ArrayRef) -> Option<Bounds> {
+        match array.data_type() {
+            DataType::Int8
+            | DataType::Int16
+            | DataType::Int32
+            | DataType::Int64
+            | DataType::Date32
+            | DataType::Date64
+            | DataType::Time32(_)
+            | DataType::Time64(_)
+            | DataType::Timestamp(_, _) => {
+                let values = cast(array, &DataType::Int64).ok()?;
+                let values = values.as_primitive::<Int64Type>().iter().flatten();
+                values.fold(None, |bounds, value| match bounds {
+                    None => Some(Bounds::Int(value, value)),
+                    Some(Bounds::Int(min, max)) => Some(Bounds::Int(min.min(value), max.max(value))),
+                    other => other,
+                })
+            }
+            DataType::Float32 | DataType::Float64 => {
+                let values = cast(array, &DataType::Float64).ok()?;
+                // Writers leave NaN out of statistics
+                let values = values.as_primitive::<Float64Type>().iter().flatten().filter(|value| !value.is_nan());
+                values.fold(None, |bounds, value| match bounds {
+                    None => Some(Bounds::Float(value, value)),
+                    Some(Bounds::Float(min, max)) => Some(Bounds::Float(min.min(value), max.max(value))),
+                    other => other,
+                })
+            }
+            DataType::Utf8 => byte_bounds(array.as_string::<i32>().iter().flatten().map(str::as_bytes)),
+            DataType::LargeUtf8 => byte_bounds(array.as_string::<i64>().iter().flatten().map(str::as_bytes)),
+            DataType::Binary => byte_bounds(array.as_binary::<i32>().iter().flatten()),
+            _ => None,
+        }
+    }
+
+    fn merge(self, other: Bounds) -> Bounds {
+        match (self, other) {
+            (Bounds::Int(a, b), Bounds::Int(c, d)) => Bounds::Int(a.min(c), b.max(d)),
+            (Bounds::Float(a, b), Bounds::Float(c, d)) => Bounds::Float(a.min(c), b.max(d)),
+            (Bounds::Bytes(a, b), Bounds::Bytes(c, d)) => Bounds::Bytes(a.min(c), b.max(d)),
+            (bounds, _) => bounds,
+        }
+    }
+}
+
+fn byte_bounds<'a>(values: impl Iterator<Item = &'a [u8]>) -> Option<Bounds> {
+    values.fold(None, |bounds, value| match bounds {
+        None => Some(Bounds::Bytes(value.to_vec(), value.to_vec())),
+        Some(Bounds::Bytes(min, max)) => Some(Bounds::Bytes(
+            if value < min.as_slice() { value.to_vec() } else { min },
+            if value > max.as_slice() { value.to_vec() } else { max },
+        )),
+        other => other,
+    })
+}
+
+/// A minimum and a maximum of the same column, rendered for a message
+pub(crate) fn render_bounds(bounds: &Bounds) -> (String, String) {
+    match bounds {
+        Bounds::Int(min, max) => (min.to_string(), max.to_string()),
+        Bounds::Float(min, max) => (min.to_string(), max.to_string()),
+        Bounds::Bytes(min, max) => (
+            format!("{:?}", String::from_utf8_lossy(min)),
+            format!("{:?}", String::from_utf8_lossy(max)),
+        ),
+    }
+}
+
+/// How a row group's footer statistics for a column disagree with its data
+#[derive(Debug, Clone, PartialEq)]
+pub enum StatisticsMismatchKind {
+    /// The stated minimum is above the smallest value
+    Min { stated: String, actual: String },
+    /// The stated maximum is below the largest value
+    Max { stated: String, actual: String },
+    NullCount { stated: u64, actual: u64 },
+}
+
+/// A row group whose footer statistics can't be trusted for pruning
+#[derive(Debug, Clone, PartialEq)]
+pub struct StatisticsMismatch {
+    pub file: PathBuf,
+    pub row_group: usize,
+    pub column: String,
+    pub kind: StatisticsMismatchKind,
+}
+
+impl fmt::Display for StatisticsMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} row group {}: column {} ", self.file, self.row_group, self.column)?;
+        match &self.kind {
+            StatisticsMismatchKind::Min { stated, actual } => {
+                write!(f, "has a stated minimum of {} but holds {}", stated, actual)?
+            }
+            StatisticsMismatchKind::Max { stated, actual } => {
+                write!(f, "has a stated maximum of {} but holds {}", stated, actual)?
+            }
+            StatisticsMismatchKind::NullCount { stated, actual } => {
+                write!(f, "has a stated null count of {} but holds {} nulls", stated, actual)?
+            }
+        }
+        write!(f, "; filters relying on its statistics may skip matching rows")
+    }
+}
+
+/// Indexes of up to `sample` row groups out of `count`, spread evenly from
+/// the first to the last
+fn sampled_row_groups(count: usize, sample: usize) -> Vec<usize> {
+    match sample {
+        0 => Vec::new(),
+        _ if count <= sample => (0..count).collect(),
+        1 => vec![0],
+        _ => {
+            let mut indexes: Vec<usize> = (0..sample).map(|i| i * (count - 1) / (sample - 1)).collect();
+            indexes.dedup();
+            indexes
+        }
+    }
+}
+
+/// Cross-check the footer statistics of a sample of row groups in each
+/// parquet input against their data
+///
+/// Up to `sample` row groups per file, spread from its first to its last,
+/// are decoded. A top-level column's statistics are reported when its
+/// values fall outside the stated minimum and maximum, or when the stated
+/// null count is off: filter pushdown and file skipping would then drop
+/// rows that match. Bounds wider than the data, such as truncated string
+/// statistics, are fine. Integer, float, string and binary columns are
+/// checked; other formats, buffered inputs and unreadable files are
+/// skipped.
+pub fn validate_statistics(input_files: &[PathBuf], sample: usize) -> Vec<StatisticsMismatch> {
+    input_files
+        .iter()
+        .filter(|path| InputFormat::from_path(path).unwrap_or(InputFormat::Parquet) == InputFormat::Parquet)
+        .filter(|path| !is_buffered_input(path))
+        .filter_map(|path| file_mismatches(path, sample).ok())
+        .flatten()
+        .collect()
+}
+
+fn file_mismatches(path: &Path, sample: usize) -> Result<Vec<StatisticsMismatch>> {
+    let file = File::open(path)?;
+    let metadata = ArrowReaderMetadata::load(&file, Default::default())?;
+    let parquet_metadata = metadata.metadata().clone();
+    let schema_descr = parquet_metadata.file_metadata().schema_descr();
+    let leaves: Vec<usize> = (0..schema_descr.num_columns())
+        .filter(|&index| schema_descr.column(index).path().parts().len() == 1)
+        .collect();
+    let projection = ProjectionMask::leaves(schema_descr, leaves.iter().copied());
+
+    let mut mismatches = Vec::new();
+    for row_group in sampled_row_groups(parquet_metadata.num_row_groups(), sample) {
+        let reader = ParquetRecordBatchReaderBuilder::new_with_metadata(file.try_clone()?, metadata.clone())
+            .with_row_groups(vec![row_group])
+            .with_projection(projection.clone())
+            .build()?;
+        let batches = reader.collect::<std::result::Result<Vec<_>, _>>()?;
+
+        for &leaf in &leaves {
+            let name = schema_descr.column(leaf).name().to_string();
+            let Some(statistics) = parquet_metadata.row_group(row_group).column(leaf).statistics() else {
+                continue;
+            };
+            let columns: Vec<&ArrayRef> = batches.iter().filter_map(|batch| batch.column_by_name(&name)).collect();
+            let mismatch = |kind| StatisticsMismatch { file: path.to_path_buf(), row_group, column: name.clone(), kind };
+
+            if let Some(stated) = statistics.null_count_opt() {
+                let actual = columns.iter().map(|column| column.null_count() as u64).sum();
+                if stated != actual {
+                    mismatches.push(mismatch(StatisticsMismatchKind::NullCount { stated, actual }));
+                }
+            }
+
+            let (Some(stated), Some(actual)) = (
+                Bounds::from_statistics(statistics),
+                columns.iter().filter_map(|column| Bounds::of_array(column)).reduce(Bounds::merge),
+            ) else {
+                continue;
+            };
+            let (below, above) = match (&stated, &actual) {
+                (Bounds::Int(min, max), Bounds::Int(lo, hi)) => (lo < min, hi > max),
+                (Bounds::Float(min, max), Bounds::Float(lo, hi)) => (lo < min, hi > max),
+                (Bounds::Bytes(min, max), Bounds::Bytes(lo, hi)) => (lo < min, hi > max),
+                _ => continue,
+            };
+            let (stated_min, stated_max) = render_bounds(&stated);
+            let (actual_min, actual_max) = render_bounds(&actual);
+            if below {
+                mismatches.push(mismatch(StatisticsMismatchKind::Min { stated: stated_min, actual: actual_min }));
+            }
+            if above {
+                mismatches.push(mismatch(StatisticsMismatchKind::Max { stated: stated_max, actual: actual_max }));
+            }
+        }
+    }
+    Ok(mismatches)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::OpenOptions;
+    use std::io::{Seek, SeekFrom, Write};
+    use crate::footer::{read_footer, write_footer};
+    use crate::test_utils::create_test_parquet_file;
+
+    /// Overwrite the statistics of column `column` in the first row group
+    fn set_statistics(path: &Path, column: usize, min: i32, max: i32, null_count: i64) -> Result<()> {
+        let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+        let (footer_start, mut metadata) = read_footer(&mut file)?;
+        let statistics = metadata.row_groups[0].columns[column]
+            .meta_data
+            .as_mut()
+            .and_then(|meta| meta.statistics.as_mut())
+            .expect("statistics are written");
+        statistics.min_value = Some(min.to_le_bytes().to_vec());
+        statistics.max_value = Some(max.to_le_bytes().to_vec());
+        statistics.null_count = Some(null_count);
+
+        let mut footer = Vec::new();
+        write_footer(&mut footer, &metadata)?;
+        file.set_len(footer_start)?;
+        file.seek(SeekFrom::End(0))?;
+        file.write_all(&footer)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate_statistics() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let good = temp_dir.path().join("good.parquet");
+        let bad = temp_dir.path().join("bad.parquet");
+        let loose = temp_dir.path().join("loose.parquet");
+        create_test_parquet_file(&good, 0, 10)?;
+        create_test_parquet_file(&bad, 0, 10)?;
+        create_test_parquet_file(&loose, 0, 10)?;
+        // ids 0..10 stated as 2..=5 with a null, and as the wider -5..=50
+        set_statistics(&bad, 0, 2, 5, 1)?;
+        set_statistics(&loose, 0, -5, 50, 0)?;
+
+        let files = [good, bad.clone(), loose, temp_dir.path().join("other.csv")];
+        let mismatches = validate_statistics(&files, 3);
+        let kinds: Vec<&StatisticsMismatchKind> = mismatches.iter().map(|mismatch| &mismatch.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                &StatisticsMismatchKind::NullCount { stated: 1, actual: 0 },
+                &StatisticsMismatchKind::Min { stated: "2".to_string(), actual: "0".to_string() },
+                &StatisticsMismatchKind::Max { stated: "5".to_string(), actual: "9".to_string() },
+            ]
+        );
+        assert!(mismatches.iter().all(|mismatch| mismatch.file == bad && mismatch.column == "id"));
+        assert!(mismatches[1].to_string().contains("row group 0: column id has a stated minimum of 2 but holds 0"));
+
+        assert!(validate_statistics(&[bad], 0).is_empty());
+
+        assert_eq!(sampled_row_groups(2, 3), vec![0, 1]);
+        assert_eq!(sampled_row_groups(10, 3), vec![0, 4, 9]);
+        assert_eq!(sampled_row_groups(10, 1), vec![0]);
+        assert_eq!(sampled_row_groups(10, 0), Vec::<usize>::new());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,133 @@
+use std::fmt;
+use std::net::{ToSocketAddrs, UdpSocket};
+use std::time::Duration;
+use anyhow::{Result, Context};
+use crate::consolidator::RunSummary;
+use crate::file_info::file_size;
+
+/// Prefix of every metric name
+pub const METRIC_PREFIX: &str = "parquet_consolidator";
+
+/// How a statsd server aggregates a metric
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetricKind {
+    Counter,
+    Gauge,
+    Timer,
+}
+
+/// A measurement of a consolidation run
+#[derive(Debug, Clone, PartialEq)]
+pub struct Metric {
+    pub name: &'static str,
+    pub value: u64,
+    pub kind: MetricKind,
+}
+
+/// Renders the metric as a statsd line, e.g. `parquet_consolidator.rows_written:10|c`
+impl fmt::Display for Metric {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self.kind {
+            MetricKind::Counter => "c",
+            MetricKind::Gauge => "g",
+            MetricKind::Timer => "ms",
+        };
+        write!(f, "{}.{}:{}|{}", METRIC_PREFIX, self.name, self.value, kind)
+    }
+}
+
+/// The counters of an invocation: jobs run and failed, input files, rows
+/// and bytes written, along with its duration and peak memory
+pub fn run_metrics(summaries: &[RunSummary], failed_jobs: usize, elapsed: Duration) -> Vec<Metric> {
+    let counter = |name, value| Metric { name, value, kind: MetricKind::Counter };
+    let mut metrics = vec![
+        counter("jobs", (summaries.len() + failed_jobs) as u64),
+        counter("jobs_failed", failed_jobs as u64),
+        counter("input_files", summaries.iter().map(|summary| summary.input_files as u64).sum()),
+        counter("rows_written", summaries.iter().map(|summary| summary.rows_written as u64).sum()),
+        counter(
+            "bytes_written",
+            summaries.iter().filter_map(|summary| summary.output_path.as_deref()).map(file_size).sum(),
+        ),
+        Metric { name: "duration_ms", value: elapsed.as_millis() as u64, kind: MetricKind::Timer },
+    ];
+    if let Some(peak) = summaries.iter().filter_map(|summary| summary.peak_memory_bytes).max() {
+        metrics.push(Metric { name: "peak_memory_bytes", value: peak, kind: MetricKind::Gauge });
+    }
+    metrics
+}
+
+/// Sends metrics over UDP to a statsd or Datadog agent
+#[derive(Debug)]
+pub struct StatsdSink {
+    socket: UdpSocket,
+}
+
+impl StatsdSink {
+    /// Address the agent listening on `address`, such as `localhost:8125`
+    pub fn connect(address: &str) -> Result<Self> {
+        let agent = address
+            .to_socket_addrs()
+            .ok()
+            .and_then(|mut addresses| addresses.next())
+            .ok_or_else(|| anyhow::anyhow!("Failed to resolve the statsd address {}", address))?;
+        let local = if agent.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" };
+        let socket = UdpSocket::bind(local)?;
+        socket.connect(agent)?;
+        Ok(StatsdSink { socket })
+    }
+
+    /// Send each metric in its own datagram; delivery is not confirmed
+    pub fn send(&self, metrics: &[Metric]) -> Result<()> {
+        for metric in metrics {
+            self.socket.send(metric.to_string().as_bytes()).context("Failed to send metrics to statsd")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use crate::test_utils::create_test_parquet_file;
+
+    #[test]
+    fn test_run_metrics_sent_to_statsd() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let output = temp_dir.path().join("out.parquet");
+        create_test_parquet_file(&output, 0, 10)?;
+        let summaries = vec![
+            RunSummary { output_path: Some(output.clone()), input_files: 3, rows_written: 10, peak_memory_bytes: Some(2048), ..Default::default() },
+            RunSummary { output_path: None::<PathBuf>, input_files: 2, rows_written: 5, peak_memory_bytes: Some(4096), ..Default::default() },
+        ];
+
+        let metrics = run_metrics(&summaries, 1, Duration::from_millis(1500));
+        let lines: Vec<String> = metrics.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            lines,
+            vec![
+                "parquet_consolidator.jobs:3|c".to_string(),
+                "parquet_consolidator.jobs_failed:1|c".to_string(),
+                "parquet_consolidator.input_files:5|c".to_string(),
+                "parquet_consolidator.rows_written:15|c".to_string(),
+                format!("parquet_consolidator.bytes_written:{}|c", file_size(&output)),
+                "parquet_consolidator.duration_ms:1500|ms".to_string(),
+                "parquet_consolidator.peak_memory_bytes:4096|g".to_string(),
+            ]
+        );
+
+        let agent = UdpSocket::bind("127.0.0.1:0")?;
+        agent.set_read_timeout(Some(Duration::from_secs(5)))?;
+        StatsdSink::connect(&agent.local_addr()?.to_string())?.send(&metrics[..2])?;
+        let mut buffer = [0u8; 512];
+        let received = agent.recv(&mut buffer)?;
+        assert_eq!(&buffer[..received], b"parquet_consolidator.jobs:3|c");
+        let received = agent.recv(&mut buffer)?;
+        assert_eq!(&buffer[..received], b"parquet_consolidator.jobs_failed:1|c");
+
+        assert!(StatsdSink::connect("not an address").is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,143 @@
+//! Proptest strategies for random schemas and parquet datasets
+//!
+//! Enabled by the `test-utils` feature, so crates embedding the library
+//! can property-test their integration against arbitrary inputs.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use anyhow::Result;
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use proptest::collection::{btree_set, vec};
+use proptest::prelude::*;
+use crate::test_utils::create_random_parquet_file;
+
+/// A random parquet dataset: a schema shared by files at random places in
+/// a directory tree
+///
+/// Only the shape is held; [`DatasetSpec::write`] generates the rows from
+/// `seed`, so a failing case shrinks and replays cheaply.
+#[derive(Debug, Clone)]
+pub struct DatasetSpec {
+    pub schema: SchemaRef,
+    /// Path of each file relative to the dataset root, and its row count
+    pub files: Vec<(PathBuf, usize)>,
+    pub null_ratio: f64,
+    pub seed: u64,
+}
+
+impl DatasetSpec {
+    /// Total rows across the files
+    pub fn rows(&self) -> usize {
+        self.files.iter().map(|(_, rows)| rows).sum()
+    }
+
+    /// Write the files under `root`, returning their paths
+    pub fn write(&self, root: &Path) -> Result<Vec<PathBuf>> {
+        let mut paths = Vec::new();
+        for (index, (relative, rows)) in self.files.iter().enumerate() {
+            let path = root.join(relative);
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            create_random_parquet_file(&path, &self.schema, *rows, self.null_ratio, self.seed.wrapping_add(index as u64))?;
+            paths.push(path);
+        }
+        Ok(paths)
+    }
+}
+
+/// Element types of list columns
+fn arb_list_item_type() -> impl Strategy<Value = DataType> {
+    prop_oneof![
+        Just(DataType::Int32),
+        Just(DataType::Int64),
+        Just(DataType::Float64),
+        Just(DataType::Boolean),
+        Just(DataType::Utf8),
+    ]
+}
+
+/// Column types consolidation supports: integers, floats, booleans,
+/// strings, dates, timestamps, decimals and lists of primitives
+///
+/// Struct columns and lists of temporal values are left out, as the polars
+/// build the consolidator reads with can't load them.
+pub fn arb_data_type() -> impl Strategy<Value = DataType> {
+    prop_oneof![
+        arb_list_item_type(),
+        Just(DataType::Date32),
+        Just(DataType::Timestamp(TimeUnit::Microsecond, None)),
+        (2u8..=18, 0i8..=6).prop_map(|(precision, scale)| DataType::Decimal128(precision, scale.min(precision as i8 - 1))),
+        arb_list_item_type().prop_map(|item| DataType::List(Arc::new(Field::new("item", item, true)))),
+    ]
+}
+
+/// Schemas of 1 to `max_columns` uniquely named columns; the first one is
+/// never nullable, like an id
+pub fn arb_schema(max_columns: usize) -> impl Strategy<Value = SchemaRef> {
+    btree_set("[a-z][a-z0-9_]{0,7}", 1..=max_columns.max(1))
+        .prop_flat_map(|names| {
+            let names: Vec<String> = names.into_iter().collect();
+            let columns = names.len();
+            (Just(names), vec(arb_data_type(), columns), vec(any::<bool>(), columns))
+        })
+        .prop_map(|(names, types, nullable)| {
+            let fields: Vec<Field> = names
+                .into_iter()
+                .zip(types)
+                .zip(nullable)
+                .enumerate()
+                .map(|(i, ((name, data_type), nullable))| Field::new(name, data_type, nullable && i > 0))
+                .collect();
+            Arc::new(Schema::new(fields))
+        })
+}
+
+/// Datasets of 1 to `max_files` files of up to `max_rows` rows each,
+/// nested up to `max_depth` directories deep in `level_N` directories
+pub fn arb_dataset(max_files: usize, max_rows: usize, max_depth: usize) -> impl Strategy<Value = DatasetSpec> {
+    let file = (0..=max_depth, 0..=max_rows);
+    (arb_schema(6), vec(file, 1..=max_files.max(1)), 0.0f64..=0.5, any::<u64>()).prop_map(
+        |(schema, files, null_ratio, seed)| {
+            let files = files
+                .into_iter()
+                .enumerate()
+                .map(|(index, (depth, rows))| {
+                    let mut path: PathBuf = (1..=depth).map(|level| format!("level_{}", level)).collect();
+                    path.push(format!("part-{:05}.parquet", index));
+                    (path, rows)
+                })
+                .collect();
+            DatasetSpec { schema, files, null_ratio, seed }
+        },
+    )
+}
+
+#[cfg(all(test, feature = "discovery"))]
+mod tests {
+    use super::*;
+    use crate::consolidator::consolidate_with_options;
+    use crate::discovery::{discover_input_files, DiscoveryOptions};
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(16))]
+
+        #[test]
+        fn test_arb_dataset_consolidates(spec in arb_dataset(4, 100, 2)) {
+            let temp_dir = tempfile::TempDir::new().unwrap();
+            let root = temp_dir.path().join("dataset");
+            let mut written = spec.write(&root).unwrap();
+            written.sort();
+
+            let options = DiscoveryOptions { recursive: true, ..Default::default() };
+            let mut input_files = discover_input_files(&root, &options).unwrap();
+            input_files.sort();
+            prop_assert_eq!(&input_files, &written);
+
+            let output = temp_dir.path().join("out.parquet");
+            let summary = consolidate_with_options(&input_files, &output, &Default::default()).unwrap();
+            prop_assert_eq!(summary.rows_written, spec.rows());
+        }
+    }
+}
@@ -29,10 +29,17 @@ fn main() -> Result<()> {
     println!("Running property-based tests...");
     run_property_tests()?;
 
-    // Run benchmark tests (optional)
-    if std::env::args().any(|arg| arg == "--bench") {
-        println!("Running benchmark tests...");
-        run_benchmark_tests()?;
+    // Run benchmarks (optional)
+    let args: Vec<String> = std::env::args().collect();
+    let bench_profile = args.iter().position(|arg| arg == "--bench-profile").map(|index| {
+        args.get(index + 1).cloned().context("--bench-profile requires a baseline name")
+    });
+    if let Some(baseline) = bench_profile {
+        println!("Running benchmarks...");
+        run_benchmarks(Some(&baseline?))?;
+    } else if args.iter().any(|arg| arg == "--bench") {
+        println!("Running benchmarks...");
+        run_benchmarks(None)?;
     }
 
     println!();
@@ -69,7 +76,7 @@ fn cleanup_test_files() -> Result<()> {
 
 fn build_project() -> Result<()> {
     let output = Command::new("cargo")
-        .args(&["build", "--release"])
+        .args(["build", "--release"])
         .output()
         .context("Failed to execute cargo build")?;
     
@@ -86,7 +93,7 @@ fn build_project() -> Result<()> {
 
 fn generate_test_data() -> Result<()> {
     let output = Command::new("cargo")
-        .args(&["run", "--bin", "test_data_generator"])
+        .args(["run", "--bin", "parquet_consolidator", "--", "generate", "-o", "test_data"])
         .output()
         .context("Failed to generate test data")?;
     
@@ -103,7 +110,7 @@ fn generate_test_data() -> Result<()> {
 
 fn run_unit_tests() -> Result<()> {
     let output = Command::new("cargo")
-        .args(&["test", "--lib"])
+        .args(["test", "--lib"])
         .output()
         .context("Failed to run unit tests")?;
     
@@ -120,7 +127,7 @@ fn run_unit_tests() -> Result<()> {
 
 fn run_integration_tests() -> Result<()> {
     let output = Command::new("cargo")
-        .args(&["test", "--test", "integration_tests"])
+        .args(["test", "--test", "integration_tests"])
         .output()
         .context("Failed to run integration tests")?;
     
@@ -137,7 +144,7 @@ fn run_integration_tests() -> Result<()> {
 
 fn run_property_tests() -> Result<()> {
     let output = Command::new("cargo")
-        .args(&["test", "--test", "property_tests"])
+        .args(["test", "--test", "property_tests"])
         .output()
         .context("Failed to run property tests")?;
     
@@ -152,21 +159,50 @@ fn run_property_tests() -> Result<()> {
     Ok(())
 }
 
-fn run_benchmark_tests() -> Result<()> {
+/// Run the criterion benchmarks, comparing them against the saved
+/// `baseline` when there is one and saving it otherwise
+fn run_benchmarks(baseline: Option<&str>) -> Result<()> {
+    let mut args = vec!["bench".to_string(), "--bench".to_string(), "consolidation".to_string(), "--".to_string()];
+    match baseline {
+        Some(name) if baseline_exists(name) => {
+            println!("Comparing against baseline {:?}", name);
+            args.extend(["--baseline".to_string(), name.to_string()]);
+        }
+        Some(name) => {
+            println!("Saving baseline {:?}", name);
+            args.extend(["--save-baseline".to_string(), name.to_string()]);
+        }
+        None => {}
+    }
+
     let output = Command::new("cargo")
-        .args(&["test", "--test", "benchmark_tests", "--", "--nocapture"])
+        .args(&args)
         .output()
-        .context("Failed to run benchmark tests")?;
+        .context("Failed to run benchmarks")?;
     
     if !output.status.success() {
         anyhow::bail!(
-            "Benchmark tests failed:\n{}",
+            "Benchmarks failed:\n{}",
             String::from_utf8_lossy(&output.stderr)
         );
     }
     
-    // Print benchmark output
+    // Print benchmark output, including changes against the baseline
     println!("{}", String::from_utf8_lossy(&output.stdout));
-    println!("✓ Benchmark tests completed!");
+    println!("✓ Benchmarks completed!");
     Ok(())
 }
+
+/// Whether criterion has saved a baseline of this name for any benchmark
+fn baseline_exists(name: &str) -> bool {
+    walk_for_baseline(Path::new("target/criterion"), name)
+}
+
+fn walk_for_baseline(dir: &Path, name: &str) -> bool {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return false;
+    };
+    entries.flatten().filter(|entry| entry.path().is_dir()).any(|entry| {
+        entry.file_name() == name || walk_for_baseline(&entry.path(), name)
+    })
+}
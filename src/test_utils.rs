@@ -1,11 +1,28 @@
 use arrow::array::{Int32Array, StringArray, Float64Array};
-use arrow::datatypes::{DataType, Field, Schema};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
 use arrow::array::RecordBatch;
 use parquet::arrow::ArrowWriter;
+use parquet::basic::{ConvertedType, Repetition, Type as PhysicalType};
+use parquet::data_type::{ByteArray, ByteArrayType, FixedLenByteArray, FixedLenByteArrayType, Int32Type, Int96, Int96Type};
+use parquet::file::properties::WriterProperties;
+#[cfg(feature = "discovery")]
+use parquet::file::reader::{FileReader, SerializedFileReader};
+use parquet::file::writer::SerializedFileWriter;
+#[cfg(feature = "discovery")]
+use parquet::format::FileMetaData;
+use parquet::schema::parser::parse_message_type;
+use parquet::schema::types::{PrimitiveTypeBuilder, Type};
+#[cfg(feature = "discovery")]
+use parquet::schema::types::to_thrift;
 use std::fs::File;
 use std::path::Path;
 use std::sync::Arc;
 use anyhow::Result;
+#[cfg(feature = "discovery")]
+use crate::discovery::{COMMON_METADATA_FILE, SUMMARY_METADATA_FILE};
+#[cfg(feature = "discovery")]
+use crate::footer::{write_footer, MAGIC};
+use crate::generate::{random_record_batch, SeededRng};
 
 /// Create a test parquet file with a standard schema
 pub fn create_test_parquet_file(path: &Path, start_id: i32, end_id: i32) -> Result<()> {
@@ -101,6 +118,129 @@ pub fn create_test_parquet_file_with_schema(
     Ok(())
 }
 
+/// Create a test parquet file of `rows` random rows of any schema
+///
+/// Every type [`crate::random_array`] supports can be used, including
+/// dates, timestamps, decimals, lists and structs. Nullable fields, at any
+/// depth, are null with probability `null_ratio`. The same seed always
+/// produces the same file, so a failing property test can be replayed.
+pub fn create_random_parquet_file(
+    path: &Path,
+    schema: &SchemaRef,
+    rows: usize,
+    null_ratio: f64,
+    seed: u64,
+) -> Result<()> {
+    let batch = random_record_batch(schema, rows, null_ratio, &mut SeededRng::new(seed))?;
+    let mut writer = ArrowWriter::try_new(File::create(path)?, schema.clone(), None)?;
+    writer.write(&batch)?;
+    writer.close()?;
+    Ok(())
+}
+
+/// Create a test parquet file with a legacy INT96 timestamp column
+/// 
+/// Row `id` holds the timestamp `id` days after the unix epoch (before it
+/// for negative ids), as written by old Hive/Impala versions.
+pub fn create_test_int96_parquet_file(path: &Path, start_id: i32, end_id: i32) -> Result<()> {
+    // Julian day number of 1970-01-01
+    const JULIAN_DAY_OF_EPOCH: u32 = 2_440_588;
+
+    let schema = Arc::new(parse_message_type(
+        "message schema { REQUIRED INT32 id; REQUIRED INT96 ts; }"
+    )?);
+    let props = Arc::new(WriterProperties::builder().build());
+    let mut writer = SerializedFileWriter::new(File::create(path)?, schema, props)?;
+
+    let ids: Vec<i32> = (start_id..end_id).collect();
+    let timestamps: Vec<Int96> = ids
+        .iter()
+        .map(|id| {
+            let mut ts = Int96::new();
+            ts.set_data(0, 0, (JULIAN_DAY_OF_EPOCH as i64 + *id as i64) as u32);
+            ts
+        })
+        .collect();
+
+    let mut row_group = writer.next_row_group()?;
+    if let Some(mut column) = row_group.next_column()? {
+        column.typed::<Int32Type>().write_batch(&ids, None, None)?;
+        column.close()?;
+    }
+    if let Some(mut column) = row_group.next_column()? {
+        column.typed::<Int96Type>().write_batch(&timestamps, None, None)?;
+        column.close()?;
+    }
+    row_group.close()?;
+    writer.close()?;
+
+    Ok(())
+}
+
+/// Create a test parquet file annotated only with legacy converted types,
+/// as written by old Hive versions
+///
+/// Row `id` has `day` = 2022-01-08 plus `id` days (`DATE`), `amount` =
+/// `id` + 0.05 as a `DECIMAL(10,2)` byte array, `total` = `id` as a
+/// `DECIMAL(30,4)` 16-byte fixed-length array, `status` = "active"
+/// (`ENUM`) and `flags` = `id % 3` (`INT_8`).
+pub fn create_test_legacy_parquet_file(path: &Path, start_id: i32, end_id: i32) -> Result<()> {
+    fn column(name: &str, physical: PhysicalType, converted: ConvertedType) -> PrimitiveTypeBuilder<'_> {
+        Type::primitive_type_builder(name, physical)
+            .with_repetition(Repetition::REQUIRED)
+            .with_converted_type(converted)
+    }
+    let fields = vec![
+        column("id", PhysicalType::INT32, ConvertedType::NONE).build()?,
+        column("day", PhysicalType::INT32, ConvertedType::DATE).build()?,
+        column("amount", PhysicalType::BYTE_ARRAY, ConvertedType::DECIMAL).with_precision(10).with_scale(2).build()?,
+        column("total", PhysicalType::FIXED_LEN_BYTE_ARRAY, ConvertedType::DECIMAL)
+            .with_length(16)
+            .with_precision(30)
+            .with_scale(4)
+            .build()?,
+        column("status", PhysicalType::BYTE_ARRAY, ConvertedType::ENUM).build()?,
+        column("flags", PhysicalType::INT32, ConvertedType::INT_8).build()?,
+    ];
+    let schema = Arc::new(Type::group_type_builder("schema").with_fields(fields.into_iter().map(Arc::new).collect()).build()?);
+    let props = Arc::new(WriterProperties::builder().build());
+    let mut writer = SerializedFileWriter::new(File::create(path)?, schema, props)?;
+
+    let ids: Vec<i32> = (start_id..end_id).collect();
+    let days: Vec<i32> = ids.iter().map(|id| 19_000 + id).collect();
+    // Decimals are big-endian two's complement unscaled values
+    let amounts: Vec<ByteArray> = ids.iter().map(|id| ByteArray::from((*id as i64 * 100 + 5).to_be_bytes().to_vec())).collect();
+    let totals: Vec<FixedLenByteArray> = ids
+        .iter()
+        .map(|id| FixedLenByteArray::from((*id as i128 * 10_000).to_be_bytes().to_vec()))
+        .collect();
+    let statuses: Vec<ByteArray> = ids.iter().map(|_| ByteArray::from("active")).collect();
+    let flags: Vec<i32> = ids.iter().map(|id| id % 3).collect();
+
+    let mut row_group = writer.next_row_group()?;
+    for values in [&ids, &days] {
+        let mut column = row_group.next_column()?.expect("column to write");
+        column.typed::<Int32Type>().write_batch(values, None, None)?;
+        column.close()?;
+    }
+    let mut column = row_group.next_column()?.expect("column to write");
+    column.typed::<ByteArrayType>().write_batch(&amounts, None, None)?;
+    column.close()?;
+    let mut column = row_group.next_column()?.expect("column to write");
+    column.typed::<FixedLenByteArrayType>().write_batch(&totals, None, None)?;
+    column.close()?;
+    let mut column = row_group.next_column()?.expect("column to write");
+    column.typed::<ByteArrayType>().write_batch(&statuses, None, None)?;
+    column.close()?;
+    let mut column = row_group.next_column()?.expect("column to write");
+    column.typed::<Int32Type>().write_batch(&flags, None, None)?;
+    column.close()?;
+    row_group.close()?;
+    writer.close()?;
+
+    Ok(())
+}
+
 /// Create a directory structure with test parquet files
 pub fn create_test_directory_structure(base_path: &Path) -> Result<()> {
     std::fs::create_dir_all(base_path)?;
@@ -123,6 +263,40 @@ pub fn create_test_directory_structure(base_path: &Path) -> Result<()> {
     Ok(())
 }
 
+/// Write Spark-style `_metadata` and `_common_metadata` summary files into
+/// `dir` for the given data files, named relative to `dir`
+#[cfg(feature = "discovery")]
+pub fn create_summary_metadata_files(dir: &Path, files: &[&str]) -> Result<()> {
+    let mut schema = None;
+    let mut row_groups = Vec::new();
+    for file in files {
+        let reader = SerializedFileReader::new(File::open(dir.join(file))?)?;
+        let metadata = reader.metadata();
+        schema.get_or_insert_with(|| metadata.file_metadata().schema_descr_ptr());
+        for row_group in metadata.row_groups() {
+            let mut row_group = row_group.to_thrift();
+            for column in &mut row_group.columns {
+                column.file_path = Some(file.to_string());
+            }
+            row_groups.push(row_group);
+        }
+    }
+    let schema = schema.ok_or_else(|| anyhow::anyhow!("No data files given"))?;
+
+    let schema_elements = to_thrift(schema.root_schema())?;
+    let num_rows = row_groups.iter().map(|row_group| row_group.num_rows).sum();
+    let summaries = [
+        (SUMMARY_METADATA_FILE, FileMetaData::new(1, schema_elements.clone(), num_rows, row_groups, None, None, None, None, None)),
+        (COMMON_METADATA_FILE, FileMetaData::new(1, schema_elements, 0, Vec::new(), None, None, None, None, None)),
+    ];
+    for (name, metadata) in summaries {
+        let mut contents = MAGIC.to_vec();
+        write_footer(&mut contents, &metadata)?;
+        std::fs::write(dir.join(name), contents)?;
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -152,6 +326,47 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_create_random_parquet_file() -> Result<()> {
+        use arrow::array::Array;
+        use arrow::datatypes::{DataType, Field, Fields, Schema, TimeUnit};
+        use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+        let item = Arc::new(Field::new("item", DataType::Int64, true));
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new("day", DataType::Date32, true),
+            Field::new("at", DataType::Timestamp(TimeUnit::Millisecond, Some("UTC".into())), true),
+            Field::new("amount", DataType::Decimal128(12, 2), true),
+            Field::new("tags", DataType::List(item), true),
+            Field::new(
+                "address",
+                DataType::Struct(Fields::from(vec![
+                    Field::new("city", DataType::Utf8, false),
+                    Field::new("zip", DataType::UInt16, true),
+                ])),
+                true,
+            ),
+        ]));
+
+        let temp_dir = TempDir::new()?;
+        let path = temp_dir.path().join("random.parquet");
+        create_random_parquet_file(&path, &schema, 500, 0.2, 3)?;
+        let batch = ParquetRecordBatchReaderBuilder::try_new(File::open(&path)?)?.build()?.next().unwrap()?;
+        assert_eq!(batch.schema(), schema);
+        assert_eq!(batch.num_rows(), 500);
+        assert_eq!(batch.column(0).null_count(), 0);
+        for column in &batch.columns()[1..] {
+            assert!((50..150).contains(&column.null_count()), "{} nulls", column.null_count());
+        }
+
+        let again = temp_dir.path().join("again.parquet");
+        create_random_parquet_file(&again, &schema, 500, 0.2, 3)?;
+        assert_eq!(std::fs::read(&path)?, std::fs::read(&again)?);
+
+        Ok(())
+    }
+
     #[test]
     fn test_create_test_directory_structure() -> Result<()> {
         let temp_dir = TempDir::new()?;
@@ -0,0 +1,195 @@
+use std::fmt;
+use std::fs;
+use std::time::{Duration, Instant};
+use polars_core::POOL;
+
+/// Clock ticks per second of the CPU times in `/proc`, fixed by the Linux ABI
+const CLOCK_TICKS_PER_SECOND: u64 = 100;
+
+/// A step of a consolidation run, in the order the steps happen
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Phase {
+    /// Walking the input directory and applying the discovery filters
+    Discovery,
+    /// Reading the footers and schemas of the inputs
+    SchemaCheck,
+    /// Reading and decoding the rows of every input
+    Decode,
+    /// Stacking the decoded inputs into one frame
+    Concat,
+    /// CDC merge, redaction, deduplication and quality checks
+    RowOptions,
+    /// Encoding and writing the output
+    Write,
+}
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Phase::Discovery => "discovery",
+            Phase::SchemaCheck => "schema check",
+            Phase::Decode => "scan/decode",
+            Phase::Concat => "concat",
+            Phase::RowOptions => "row options",
+            Phase::Write => "write",
+        };
+        f.pad(name)
+    }
+}
+
+/// Wall and CPU time spent in a phase
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PhaseTiming {
+    pub wall: Duration,
+    /// CPU time of the whole process, where the platform reports it
+    pub cpu: Option<Duration>,
+}
+
+impl PhaseTiming {
+    /// Run `f`, returning its result with the time it took
+    pub fn measure<T>(f: impl FnOnce() -> T) -> (T, PhaseTiming) {
+        let clock = PhaseClock::start();
+        let result = f();
+        (result, clock.stop())
+    }
+
+    /// Share of the available thread time the phase kept busy
+    pub fn utilization(&self, threads: usize) -> Option<f64> {
+        let capacity = self.wall.as_secs_f64() * threads as f64;
+        (capacity > 0.0).then(|| self.cpu.map(|cpu| cpu.as_secs_f64() / capacity)).flatten()
+    }
+
+    fn add(&mut self, other: PhaseTiming) {
+        self.wall += other.wall;
+        self.cpu = self.cpu.zip(other.cpu).map(|(a, b)| a + b);
+    }
+}
+
+/// Measures a phase spanning code that cannot be wrapped in a closure
+#[derive(Debug, Clone, Copy)]
+pub struct PhaseClock {
+    started: Instant,
+    cpu: Option<Duration>,
+}
+
+impl PhaseClock {
+    pub fn start() -> Self {
+        PhaseClock { started: Instant::now(), cpu: process_cpu_time() }
+    }
+
+    pub fn stop(&self) -> PhaseTiming {
+        let wall = self.started.elapsed();
+        let cpu = self.cpu.zip(process_cpu_time()).map(|(before, after)| after.saturating_sub(before));
+        PhaseTiming { wall, cpu }
+    }
+}
+
+/// Where the wall time of a run went, phase by phase
+#[derive(Debug, Clone, PartialEq)]
+pub struct PhaseTimings {
+    /// Size of the thread pool decoding and encoding runs on
+    pub threads: usize,
+    /// Phases that took place, in run order
+    pub phases: Vec<(Phase, PhaseTiming)>,
+}
+
+impl Default for PhaseTimings {
+    fn default() -> Self {
+        PhaseTimings { threads: POOL.current_num_threads(), phases: Vec::new() }
+    }
+}
+
+impl PhaseTimings {
+    /// Run `f` and add the time it took to `phase`
+    pub fn time<T>(&mut self, phase: Phase, f: impl FnOnce() -> T) -> T {
+        let (result, timing) = PhaseTiming::measure(f);
+        self.record(phase, timing);
+        result
+    }
+
+    /// Add `timing` to `phase`, such as discovery measured by the caller
+    pub fn record(&mut self, phase: Phase, timing: PhaseTiming) {
+        match self.phases.iter_mut().find(|(existing, _)| *existing == phase) {
+            Some((_, total)) => total.add(timing),
+            None => {
+                self.phases.push((phase, timing));
+                self.phases.sort_by_key(|(phase, _)| *phase);
+            }
+        }
+    }
+
+    pub fn total(&self) -> PhaseTiming {
+        let mut total = PhaseTiming { wall: Duration::ZERO, cpu: Some(Duration::ZERO) };
+        for (_, timing) in &self.phases {
+            total.add(*timing);
+        }
+        total
+    }
+}
+
+impl fmt::Display for PhaseTimings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Timings ({} threads):", self.threads)?;
+        let total = self.total();
+        let rows = self.phases.iter().map(|(phase, timing)| (phase.to_string(), *timing));
+        for (name, timing) in rows.chain(std::iter::once(("total".to_string(), total))) {
+            write!(f, "  {:<14}{:>9.3}s wall", name, timing.wall.as_secs_f64())?;
+            if let Some(cpu) = timing.cpu {
+                write!(f, "{:>9.3}s CPU", cpu.as_secs_f64())?;
+            }
+            if let Some(utilization) = timing.utilization(self.threads) {
+                write!(f, "{:>7.1}% utilization", utilization * 100.0)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// User and system CPU time consumed by this process so far
+///
+/// Read from `/proc/self/stat`, so only available on Linux.
+fn process_cpu_time() -> Option<Duration> {
+    parse_cpu_ticks(&fs::read_to_string("/proc/self/stat").ok()?)
+        .map(|ticks| Duration::from_millis(ticks * 1000 / CLOCK_TICKS_PER_SECOND))
+}
+
+fn parse_cpu_ticks(stat: &str) -> Option<u64> {
+    // The command name may contain spaces, so fields are counted after it;
+    // utime and stime are the 14th and 15th fields
+    let fields: Vec<&str> = stat.get(stat.rfind(')')? + 1..)?.split_whitespace().collect();
+    let utime: u64 = fields.get(11)?.parse().ok()?;
+    let stime: u64 = fields.get(12)?.parse().ok()?;
+    Some(utime + stime)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_phase_timings() {
+        let stat = "4242 (parquet consol) R 1 4242 4242 0 -1 4194304 900 0 0 0 250 30 0 0 20 0 9 0 100 0 0";
+        assert_eq!(parse_cpu_ticks(stat), Some(280));
+        assert_eq!(parse_cpu_ticks("4242 (x) R 1"), None);
+
+        let second = |cpu_ms| PhaseTiming { wall: Duration::from_secs(1), cpu: Some(Duration::from_millis(cpu_ms)) };
+        let mut timings = PhaseTimings { threads: 4, phases: Vec::new() };
+        timings.record(Phase::Write, second(1000));
+        timings.record(Phase::Discovery, second(500));
+        timings.record(Phase::Write, second(3000));
+        let phases: Vec<Phase> = timings.phases.iter().map(|(phase, _)| *phase).collect();
+        assert_eq!(phases, vec![Phase::Discovery, Phase::Write]);
+        assert_eq!(timings.phases[1].1.utilization(4), Some(0.5));
+        assert_eq!(timings.total().wall, Duration::from_secs(3));
+
+        let text = timings.to_string();
+        assert!(text.starts_with("Timings (4 threads):\n"));
+        assert!(text.contains("  write             2.000s wall    4.000s CPU   50.0% utilization\n"));
+        assert!(text.contains("  total             3.000s wall    4.500s CPU   37.5% utilization\n"));
+
+        let (value, timing) = PhaseTiming::measure(|| 7);
+        assert_eq!(value, 7);
+        assert_eq!(timing.cpu.is_some(), cfg!(target_os = "linux"));
+    }
+}
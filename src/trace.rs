@@ -0,0 +1,370 @@
+use std::fmt;
+use std::process;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use xxhash_rust::xxh3::{xxh3_128, xxh3_64};
+
+/// A value attached to a span
+#[derive(Debug, Clone, PartialEq)]
+pub enum AttributeValue {
+    String(String),
+    Int(i64),
+}
+
+impl From<&str> for AttributeValue {
+    fn from(value: &str) -> Self {
+        AttributeValue::String(value.to_string())
+    }
+}
+
+impl From<String> for AttributeValue {
+    fn from(value: String) -> Self {
+        AttributeValue::String(value)
+    }
+}
+
+impl From<usize> for AttributeValue {
+    fn from(value: usize) -> Self {
+        AttributeValue::Int(value as i64)
+    }
+}
+
+impl From<u64> for AttributeValue {
+    fn from(value: u64) -> Self {
+        AttributeValue::Int(value as i64)
+    }
+}
+
+/// A span that has ended
+#[derive(Debug, Clone, PartialEq)]
+pub struct FinishedSpan {
+    pub name: String,
+    pub span_id: [u8; 8],
+    pub parent_span_id: Option<[u8; 8]>,
+    pub start: SystemTime,
+    pub end: SystemTime,
+    pub attributes: Vec<(String, AttributeValue)>,
+    /// Set when the traced operation failed
+    pub error: Option<String>,
+}
+
+#[derive(Debug)]
+struct TracerState {
+    trace_id: [u8; 16],
+    spans: Mutex<Vec<FinishedSpan>>,
+}
+
+/// Collects the spans of one trace in memory until they are exported
+///
+/// Clones share the same trace, so spans can end on any thread.
+#[derive(Debug, Clone)]
+pub struct Tracer {
+    state: Arc<TracerState>,
+}
+
+impl Default for Tracer {
+    fn default() -> Self {
+        Tracer {
+            state: Arc::new(TracerState {
+                trace_id: xxh3_128(&id_seed()).to_be_bytes(),
+                spans: Mutex::new(Vec::new()),
+            }),
+        }
+    }
+}
+
+impl Tracer {
+    pub fn trace_id(&self) -> [u8; 16] {
+        self.state.trace_id
+    }
+
+    /// Start a span at the root of the trace
+    pub fn start_span(&self, name: &str) -> Span {
+        Span::start(self.clone(), name, None)
+    }
+
+    /// Spans ended so far, in the order they ended
+    pub fn finished_spans(&self) -> Vec<FinishedSpan> {
+        self.state.spans.lock().unwrap().clone()
+    }
+}
+
+/// Where new spans are nested, handed to code running inside a span
+#[derive(Debug, Clone)]
+pub struct TraceParent {
+    tracer: Tracer,
+    span_id: [u8; 8],
+}
+
+impl TraceParent {
+    pub fn start_span(&self, name: &str) -> Span {
+        Span::start(self.tracer.clone(), name, Some(self.span_id))
+    }
+}
+
+/// An operation being timed; it is recorded when ended
+#[derive(Debug)]
+pub struct Span {
+    tracer: Tracer,
+    name: String,
+    span_id: [u8; 8],
+    parent_span_id: Option<[u8; 8]>,
+    start: SystemTime,
+    attributes: Vec<(String, AttributeValue)>,
+}
+
+impl Span {
+    fn start(tracer: Tracer, name: &str, parent_span_id: Option<[u8; 8]>) -> Self {
+        Span {
+            tracer,
+            name: name.to_string(),
+            span_id: xxh3_64(&id_seed()).max(1).to_be_bytes(),
+            parent_span_id,
+            start: SystemTime::now(),
+            attributes: Vec::new(),
+        }
+    }
+
+    pub fn set_attribute(&mut self, key: &str, value: impl Into<AttributeValue>) {
+        self.attributes.push((key.to_string(), value.into()));
+    }
+
+    /// Parent for spans of the steps inside this one
+    pub fn as_parent(&self) -> TraceParent {
+        TraceParent { tracer: self.tracer.clone(), span_id: self.span_id }
+    }
+
+    pub fn tracer(&self) -> &Tracer {
+        &self.tracer
+    }
+
+    /// End the span, marking it failed when `result` is an error
+    pub fn end<T, E: fmt::Display>(self, result: &Result<T, E>) {
+        let span = FinishedSpan {
+            name: self.name,
+            span_id: self.span_id,
+            parent_span_id: self.parent_span_id,
+            start: self.start,
+            end: SystemTime::now(),
+            attributes: self.attributes,
+            error: result.as_ref().err().map(|err| format!("{:#}", err)),
+        };
+        self.tracer.state.spans.lock().unwrap().push(span);
+    }
+}
+
+/// Bytes that differ for every id generated, across processes and threads
+fn id_seed() -> Vec<u8> {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos();
+    let mut seed = nanos.to_le_bytes().to_vec();
+    seed.extend_from_slice(&process::id().to_le_bytes());
+    seed.extend_from_slice(&COUNTER.fetch_add(1, Ordering::Relaxed).to_le_bytes());
+    seed
+}
+
+#[cfg(feature = "otel")]
+mod otlp {
+    use std::io::{Read, Write};
+    use std::net::{TcpStream, ToSocketAddrs};
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+    use anyhow::{Result, Context};
+    use serde_json::{json, Value};
+    use super::{AttributeValue, FinishedSpan, Tracer};
+
+    /// How long connecting to and hearing back from the collector may take
+    const TIMEOUT: Duration = Duration::from_secs(10);
+
+    /// Path of the trace service under an OTLP/HTTP endpoint
+    const TRACES_PATH: &str = "/v1/traces";
+
+    impl Tracer {
+        /// Send the finished spans to an OpenTelemetry collector
+        ///
+        /// Spans are posted as OTLP/HTTP JSON to `endpoint`, such as
+        /// `http://localhost:4318`; `/v1/traces` is appended unless the
+        /// endpoint already has a path. Only plain `http` is supported.
+        pub fn export_otlp(&self, endpoint: &str, service_name: &str) -> Result<()> {
+            let (host, path) = parse_endpoint(endpoint)?;
+            let body = self.otlp_json(service_name).to_string();
+
+            let address = host
+                .to_socket_addrs()
+                .context(format!("Failed to resolve {}", host))?
+                .next()
+                .ok_or_else(|| anyhow::anyhow!("No address found for {}", host))?;
+            let mut stream = TcpStream::connect_timeout(&address, TIMEOUT)
+                .context(format!("Failed to connect to the OTLP collector at {}", host))?;
+            stream.set_read_timeout(Some(TIMEOUT))?;
+            write!(
+                stream,
+                "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                path,
+                host,
+                body.len(),
+                body
+            )?;
+
+            let mut response = String::new();
+            stream.read_to_string(&mut response)?;
+            let status_line = response.lines().next().unwrap_or_default();
+            match status_line.split_whitespace().nth(1) {
+                Some(status) if status.starts_with('2') => Ok(()),
+                _ => anyhow::bail!("OTLP collector rejected the trace: {}", status_line),
+            }
+        }
+
+        /// The finished spans as an OTLP `ExportTraceServiceRequest`
+        pub fn otlp_json(&self, service_name: &str) -> Value {
+            let trace_id = hex(&self.trace_id());
+            let spans: Vec<Value> = self.finished_spans().iter().map(|span| span_json(span, &trace_id)).collect();
+            json!({
+                "resourceSpans": [{
+                    "resource": { "attributes": [attribute_json("service.name", &service_name.into())] },
+                    "scopeSpans": [{
+                        "scope": { "name": env!("CARGO_PKG_NAME"), "version": env!("CARGO_PKG_VERSION") },
+                        "spans": spans,
+                    }],
+                }],
+            })
+        }
+    }
+
+    fn span_json(span: &FinishedSpan, trace_id: &str) -> Value {
+        let mut value = json!({
+            "traceId": trace_id,
+            "spanId": hex(&span.span_id),
+            "name": span.name,
+            // SPAN_KIND_INTERNAL
+            "kind": 1,
+            "startTimeUnixNano": unix_nanos(span.start),
+            "endTimeUnixNano": unix_nanos(span.end),
+            "attributes": span.attributes.iter().map(|(key, value)| attribute_json(key, value)).collect::<Vec<_>>(),
+        });
+        if let Some(parent) = &span.parent_span_id {
+            value["parentSpanId"] = json!(hex(parent));
+        }
+        if let Some(message) = &span.error {
+            // STATUS_CODE_ERROR
+            value["status"] = json!({ "code": 2, "message": message });
+        }
+        value
+    }
+
+    fn attribute_json(key: &str, value: &AttributeValue) -> Value {
+        let value = match value {
+            AttributeValue::String(s) => json!({ "stringValue": s }),
+            // 64-bit integers are strings in the JSON encoding
+            AttributeValue::Int(i) => json!({ "intValue": i.to_string() }),
+        };
+        json!({ "key": key, "value": value })
+    }
+
+    fn unix_nanos(time: SystemTime) -> String {
+        time.duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos().to_string()
+    }
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    /// Split `http://host:port/path` into the address and request path
+    fn parse_endpoint(endpoint: &str) -> Result<(String, String)> {
+        let rest = endpoint
+            .strip_prefix("http://")
+            .ok_or_else(|| anyhow::anyhow!("OTLP endpoint must be an http:// URL, got: {}", endpoint))?;
+        let (authority, path) = match rest.find('/') {
+            Some(index) => rest.split_at(index),
+            None => (rest, ""),
+        };
+        if authority.is_empty() {
+            anyhow::bail!("OTLP endpoint has no host: {}", endpoint);
+        }
+        let host = if authority.contains(':') { authority.to_string() } else { format!("{}:80", authority) };
+        let path = match path.trim_end_matches('/') {
+            "" => TRACES_PATH.to_string(),
+            path => path.to_string(),
+        };
+        Ok((host, path))
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use std::net::TcpListener;
+        use std::thread;
+
+        #[test]
+        fn test_export_otlp() -> Result<()> {
+            assert_eq!(parse_endpoint("http://collector:4318")?, ("collector:4318".to_string(), TRACES_PATH.to_string()));
+            assert_eq!(parse_endpoint("http://collector/custom/")?, ("collector:80".to_string(), "/custom".to_string()));
+            assert!(parse_endpoint("https://collector:4318").is_err());
+
+            let tracer = Tracer::default();
+            let mut run = tracer.start_span("run");
+            run.set_attribute("jobs", 1usize);
+            run.as_parent().start_span("file").end(&Err::<(), _>(anyhow::anyhow!("unreadable")));
+            run.end(&Ok::<(), String>(()));
+
+            let json = tracer.otlp_json("etl");
+            let spans = &json["resourceSpans"][0]["scopeSpans"][0]["spans"];
+            assert_eq!(json["resourceSpans"][0]["resource"]["attributes"][0]["value"]["stringValue"], "etl");
+            assert_eq!(spans[0]["name"], "file");
+            assert_eq!(spans[0]["parentSpanId"], spans[1]["spanId"]);
+            assert_eq!(spans[0]["status"]["message"], "unreadable");
+            assert_eq!(spans[1]["traceId"].as_str().unwrap().len(), 32);
+            assert_eq!(spans[1]["attributes"][0]["value"]["intValue"], "1");
+            assert!(spans[1].get("parentSpanId").is_none());
+
+            let listener = TcpListener::bind("127.0.0.1:0")?;
+            let endpoint = format!("http://{}", listener.local_addr()?);
+            let collector = thread::spawn(move || -> Result<String> {
+                let (mut stream, _) = listener.accept()?;
+                let mut request = Vec::new();
+                let mut buffer = [0u8; 4096];
+                while !String::from_utf8_lossy(&request).contains("\"resourceSpans\"") || !request.ends_with(b"}") {
+                    let read = stream.read(&mut buffer)?;
+                    if read == 0 {
+                        break;
+                    }
+                    request.extend_from_slice(&buffer[..read]);
+                }
+                stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\n{}")?;
+                Ok(String::from_utf8(request)?)
+            });
+            tracer.export_otlp(&endpoint, "etl")?;
+            let request = collector.join().unwrap()?;
+            assert!(request.starts_with("POST /v1/traces HTTP/1.1\r\n"));
+            assert!(request.contains("Content-Type: application/json"));
+
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tracer_records_nested_spans() {
+        let tracer = Tracer::default();
+        let mut job = tracer.start_span("consolidate");
+        job.set_attribute("output.path", "out.parquet");
+        let file = job.as_parent().start_span("read file");
+        file.end(&Ok::<(), String>(()));
+        job.end(&Err::<(), _>(anyhow::anyhow!("disk full")));
+
+        let spans = tracer.finished_spans();
+        assert_eq!(spans.len(), 2);
+        assert_eq!(spans[0].name, "read file");
+        assert_eq!(spans[0].parent_span_id, Some(spans[1].span_id));
+        assert_ne!(spans[0].span_id, spans[1].span_id);
+        assert_eq!(spans[1].parent_span_id, None);
+        assert_eq!(spans[1].attributes, vec![("output.path".to_string(), AttributeValue::from("out.parquet"))]);
+        assert_eq!(spans[1].error.as_deref(), Some("disk full"));
+        assert!(spans[1].start <= spans[0].start && spans[0].end <= spans[1].end);
+        assert_ne!(tracer.trace_id(), Tracer::default().trace_id());
+    }
+}
@@ -0,0 +1,162 @@
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use anyhow::{Result, Context};
+use chrono::{SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use crate::file_info::file_size;
+use crate::footer::{edit_key_value_metadata, MetadataEdit};
+
+/// Footer key holding the dataset version an output was written as
+pub const VERSION_METADATA_KEY: &str = "parquet_consolidator.dataset_version";
+
+/// Name of the version log kept in an output directory
+pub const VERSION_LOG_NAME: &str = "_versions.jsonl";
+
+/// An input file of a version, as it was when the version was written
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    pub path: PathBuf,
+    pub bytes: u64,
+}
+
+/// One successful run of a dataset, as recorded in its version log
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DatasetVersion {
+    /// One more than the version before it, starting at 1
+    pub version: u64,
+    /// When the run finished, in UTC
+    pub timestamp: String,
+    pub outputs: Vec<PathBuf>,
+    /// The input manifest: every file the run consolidated
+    pub inputs: Vec<ManifestEntry>,
+    pub rows_written: u64,
+}
+
+impl fmt::Display for DatasetVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Version {} at {}: {} rows from {} inputs into {} outputs",
+            self.version,
+            self.timestamp,
+            self.rows_written,
+            self.inputs.len(),
+            self.outputs.len()
+        )?;
+        for output in &self.outputs {
+            writeln!(f, "  output {}", output.display())?;
+        }
+        for input in &self.inputs {
+            writeln!(f, "  input {} ({} bytes)", input.path.display(), input.bytes)?;
+        }
+        Ok(())
+    }
+}
+
+/// Where the version log of an output lives: `_versions.jsonl` inside an
+/// output directory, or `<file>.versions.jsonl` next to an output file
+///
+/// ```
+/// use parquet_consolidator::version_log_path;
+/// use std::path::Path;
+///
+/// assert_eq!(version_log_path(Path::new("out/events.parquet"), false), Path::new("out/events.parquet.versions.jsonl"));
+/// assert_eq!(version_log_path(Path::new("warehouse/events"), true), Path::new("warehouse/events/_versions.jsonl"));
+/// ```
+pub fn version_log_path(output: &Path, is_directory: bool) -> PathBuf {
+    if is_directory {
+        output.join(VERSION_LOG_NAME)
+    } else {
+        let mut name = output.as_os_str().to_owned();
+        name.push(".versions.jsonl");
+        PathBuf::from(name)
+    }
+}
+
+/// Every version recorded in a version log, oldest first; none when the log
+/// doesn't exist yet
+pub fn read_versions(log: &Path) -> Result<Vec<DatasetVersion>> {
+    if !log.exists() {
+        return Ok(Vec::new());
+    }
+    let contents = fs::read_to_string(log).context(format!("Failed to read version log {:?}", log))?;
+    contents
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| serde_json::from_str(line).context(format!("Invalid record on line {} of {:?}", i + 1, log)))
+        .collect()
+}
+
+/// Record a successful run as the next version of the dataset whose
+/// version log is `log`
+///
+/// Each output gets the version in its footer metadata under
+/// [`VERSION_METADATA_KEY`] before the version is appended to the log, so
+/// a version in the log always has its outputs stamped. Runs writing to the
+/// same log must not overlap, as they would take the same version.
+pub fn record_version(
+    log: &Path,
+    input_files: &[PathBuf],
+    outputs: &[PathBuf],
+    rows_written: u64,
+) -> Result<DatasetVersion> {
+    let version = read_versions(log)?.last().map_or(1, |last| last.version + 1);
+    let stamp = MetadataEdit::Set { key: VERSION_METADATA_KEY.to_string(), value: version.to_string() };
+    for output in outputs {
+        edit_key_value_metadata(output, std::slice::from_ref(&stamp))
+            .context(format!("Failed to stamp version {} on {:?}", version, output))?;
+    }
+
+    let record = DatasetVersion {
+        version,
+        timestamp: Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+        outputs: outputs.to_vec(),
+        inputs: input_files
+            .iter()
+            .map(|path| ManifestEntry { path: path.clone(), bytes: file_size(path) })
+            .collect(),
+        rows_written,
+    };
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log)
+        .context(format!("Failed to open version log {:?}", log))?;
+    writeln!(file, "{}", serde_json::to_string(&record)?)?;
+    file.sync_all()?;
+    Ok(record)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::footer::read_key_value_metadata;
+    use crate::test_utils::create_test_parquet_file;
+
+    #[test]
+    fn test_record_version() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let input = temp_dir.path().join("input.parquet");
+        create_test_parquet_file(&input, 0, 10)?;
+        let output = temp_dir.path().join("output.parquet");
+        create_test_parquet_file(&output, 0, 10)?;
+        let log = version_log_path(&output, false);
+        assert!(read_versions(&log)?.is_empty());
+
+        let (inputs, outputs) = (vec![input.clone()], vec![output.clone()]);
+        let first = record_version(&log, &inputs, &outputs, 10)?;
+        assert_eq!(first.version, 1);
+        assert_eq!(first.inputs, [ManifestEntry { path: input.clone(), bytes: file_size(&input) }]);
+        let second = record_version(&log, &inputs, &outputs, 10)?;
+        assert_eq!(second.version, 2);
+
+        assert_eq!(read_versions(&log)?, [first, second]);
+        let metadata = read_key_value_metadata(&output)?;
+        assert!(metadata.contains(&(VERSION_METADATA_KEY.to_string(), Some("2".to_string()))));
+
+        Ok(())
+    }
+}
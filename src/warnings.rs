@@ -0,0 +1,132 @@
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+/// What a warning raised by a consolidation run is about
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WarningKind {
+    /// An input was skipped because it couldn't be read
+    SkippedInput,
+    /// Inputs changed after the run's snapshot was taken and were left out
+    ChangedInput,
+    /// The execution picked for the inputs may still not fit in memory
+    MemoryPlan,
+    /// A column required in some inputs was written as nullable
+    NullabilityCoerced,
+    /// Inputs gave a column's field metadata key different values
+    FieldMetadataConflict,
+    /// An input's column statistics are strong outliers against the others'
+    InputAnomaly,
+    /// An input's footer statistics disagree with its data
+    StatisticsMismatch,
+    /// Rows failed a data quality check
+    QualityCheck,
+    /// A directory or file under the input couldn't be read and was left out
+    UnreadablePath,
+    /// The output schema has something the compatibility mode's engine
+    /// handles poorly
+    CompatLint,
+    /// The target reader can't read part of the output faithfully
+    TargetReader,
+}
+
+/// A warning raised by a consolidation run, collected on its
+/// [`RunSummary`](crate::RunSummary) so an embedding service can route it
+/// to its own alerting
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Warning {
+    pub kind: WarningKind,
+    /// The warning as the command line prints it, without the `Warning:`
+    /// prefix
+    pub message: String,
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+/// The warnings of one run, printed as they are raised when asked to
+///
+/// Clones share the warnings, so a log handed to discovery or a writer
+/// collects into the run's own.
+#[derive(Debug, Clone, Default)]
+pub struct WarningLog {
+    print: bool,
+    warnings: Arc<Mutex<Vec<Warning>>>,
+}
+
+impl WarningLog {
+    /// A log that also prints each warning to stderr when `print` is set
+    pub fn new(print: bool) -> Self {
+        WarningLog { print, warnings: Arc::default() }
+    }
+
+    pub fn warn(&self, kind: WarningKind, message: String) {
+        if self.print {
+            eprintln!("Warning: {}", message);
+        }
+        self.warnings.lock().unwrap_or_else(|err| err.into_inner()).push(Warning { kind, message });
+    }
+
+    /// Remove and return the warnings raised so far
+    pub fn take(&self) -> Vec<Warning> {
+        std::mem::take(&mut *self.warnings.lock().unwrap_or_else(|err| err.into_inner()))
+    }
+
+    pub(crate) fn into_warnings(self) -> Vec<Warning> {
+        self.take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use anyhow::Result;
+    use parquet::basic::Compression;
+    use polars::prelude::*;
+    use crate::compat::{CompatMode, TargetReader};
+    use crate::consolidator::{consolidate_with_options, ConsolidateOptions};
+    use crate::test_utils::create_test_parquet_file;
+    use crate::writer::{write_dataframe_logged, WriterOptions};
+
+    #[test]
+    fn test_collected_warnings() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let inputs = [temp_dir.path().join("a.parquet"), temp_dir.path().join("b.parquet")];
+        create_test_parquet_file(&inputs[0], 0, 10)?;
+        std::fs::write(&inputs[1], "not parquet")?;
+        let output = temp_dir.path().join("out.parquet");
+
+        let options = ConsolidateOptions { continue_on_error: true, ..Default::default() };
+        let summary = consolidate_with_options(&inputs, &output, &options)?;
+        assert_eq!(summary.rows_written, 10);
+        assert_eq!(summary.warnings.len(), 1);
+        assert_eq!(summary.warnings[0].kind, WarningKind::SkippedInput);
+        assert!(summary.warnings[0].message.starts_with("Skipping input: "), "{}", summary.warnings[0]);
+
+        // A clean run has none
+        let summary = consolidate_with_options(&inputs[..1], &output, &options)?;
+        assert!(summary.warnings.is_empty());
+
+        // Nor does writing print them, which the writer once did directly
+        let linted = temp_dir.path().join("linted.parquet");
+        let mut df = df!("bad column" => &[1i32, 2])?;
+        write_dataframe_logged(&mut df, File::create(&linted)?, &WriterOptions::default(), &WarningLog::default())?;
+        let writer = WriterOptions {
+            compat: Some(CompatMode::Spark),
+            target_reader: Some(TargetReader::Athena),
+            compression: Compression::BROTLI(Default::default()),
+            ..Default::default()
+        };
+        let options = ConsolidateOptions { writer, ..Default::default() };
+        let summary = consolidate_with_options(&[linted], &output, &options)?;
+        let kinds: Vec<WarningKind> = summary.warnings.iter().map(|warning| warning.kind).collect();
+        assert_eq!(kinds, [WarningKind::CompatLint, WarningKind::TargetReader]);
+        assert!(summary.warnings[0].message.contains("\"bad column\""), "{}", summary.warnings[0]);
+        assert!(summary.warnings[1].message.contains("brotli"), "{}", summary.warnings[1]);
+
+        Ok(())
+    }
+}
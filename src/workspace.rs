@@ -0,0 +1,160 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use anyhow::{Result, Context};
+
+/// Name prefix of workspace directories
+pub const WORKSPACE_PREFIX: &str = ".parquet_consolidator-";
+
+/// Workspaces of this process that haven't been removed yet
+static LIVE_WORKSPACES: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+/// A uniquely named scratch directory holding the temporary and staged
+/// files of one run
+///
+/// It is created next to the run's outputs, so staged files are moved into
+/// place with a rename on the same filesystem, and it is removed with
+/// everything in it when dropped, whether the run succeeded or failed.
+/// Interrupted processes remove their workspaces too, once
+/// [`clean_up_workspaces_on_interrupt`] is installed; only a process killed
+/// outright leaves its workspace behind.
+///
+/// # Examples
+///
+/// ```
+/// use parquet_consolidator::Workspace;
+/// use tempfile::TempDir;
+///
+/// let temp_dir = TempDir::new().unwrap();
+/// let output = temp_dir.path().join("events.parquet");
+/// let workspace = Workspace::create(temp_dir.path()).unwrap();
+///
+/// let staged = workspace.stage(&output);
+/// std::fs::write(&staged, b"PAR1").unwrap();
+/// workspace.commit(&staged, &output).unwrap();
+///
+/// let scratch = workspace.path().to_path_buf();
+/// drop(workspace);
+/// assert!(output.exists() && !scratch.exists());
+/// ```
+#[derive(Debug)]
+pub struct Workspace {
+    path: PathBuf,
+    staged: AtomicUsize,
+}
+
+impl Workspace {
+    /// Create a workspace in `parent`, named after the process and a random
+    /// suffix so concurrent runs never share one
+    pub fn create(parent: &Path) -> Result<Self> {
+        fs::create_dir_all(parent).context(format!("Failed to create {:?}", parent))?;
+        let mut bytes = [0u8; 8];
+        getrandom::getrandom(&mut bytes).context("Failed to name a workspace")?;
+        let suffix: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+        let path = parent.join(format!("{}{}-{}", WORKSPACE_PREFIX, std::process::id(), suffix));
+        fs::create_dir(&path).context(format!("Failed to create workspace {:?}", path))?;
+        LIVE_WORKSPACES.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).push(path.clone());
+        Ok(Workspace { path, staged: AtomicUsize::new(0) })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Where to write a file that is moved to `target` once complete
+    ///
+    /// Each call gives a new name, ending in `.tmp` so input discovery never
+    /// picks up a staged file.
+    pub fn stage(&self, target: &Path) -> PathBuf {
+        let name = target.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+        let index = self.staged.fetch_add(1, Ordering::SeqCst);
+        self.path.join(format!("{:05}-{}.tmp", index, name))
+    }
+
+    /// Move a complete staged file into place, replacing `target`
+    pub fn commit(&self, staged: &Path, target: &Path) -> Result<()> {
+        fs::rename(staged, target).context(format!("Failed to move {:?} into place", target))
+    }
+}
+
+impl Drop for Workspace {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+        LIVE_WORKSPACES.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).retain(|path| path != &self.path);
+    }
+}
+
+/// Remove every workspace of this process that still exists
+pub fn remove_live_workspaces() {
+    for path in LIVE_WORKSPACES.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).drain(..) {
+        let _ = fs::remove_dir_all(path);
+    }
+}
+
+/// Remove the live workspaces when the process is interrupted (Ctrl-C, or
+/// SIGTERM on Unix), then exit with the conventional status of the signal
+///
+/// Installing this replaces the default handling of those signals for the
+/// rest of the process.
+pub fn clean_up_workspaces_on_interrupt() -> Result<()> {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .context("Failed to start the signal handler")?;
+    #[cfg(unix)]
+    let mut terminate = runtime
+        .block_on(async { tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) })
+        .context("Failed to handle SIGTERM")?;
+    std::thread::Builder::new()
+        .name("workspace-cleanup".to_string())
+        .spawn(move || {
+            let status = runtime.block_on(async {
+                #[cfg(unix)]
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => 130,
+                    _ = terminate.recv() => 143,
+                }
+                #[cfg(not(unix))]
+                {
+                    let _ = tokio::signal::ctrl_c().await;
+                    130
+                }
+            });
+            remove_live_workspaces();
+            std::process::exit(status);
+        })
+        .context("Failed to start the signal handler")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_workspace_cleanup() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let first = Workspace::create(temp_dir.path())?;
+        let second = Workspace::create(temp_dir.path())?;
+        assert_ne!(first.path(), second.path());
+        assert!(first.path().file_name().unwrap().to_string_lossy().starts_with(WORKSPACE_PREFIX));
+
+        let target = temp_dir.path().join("out.parquet");
+        let staged = [first.stage(&target), first.stage(&target)];
+        assert_ne!(staged[0], staged[1]);
+        fs::write(&staged[0], "done")?;
+        fs::write(&staged[1], "abandoned")?;
+        first.commit(&staged[0], &target)?;
+        assert_eq!(fs::read_to_string(&target)?, "done");
+
+        // Dropped workspaces are removed with whatever was left in them
+        let paths = [first.path().to_path_buf(), second.path().to_path_buf()];
+        drop(first);
+        drop(second);
+        assert!(paths.iter().all(|path| !path.exists()));
+        assert!(target.exists());
+
+        Ok(())
+    }
+}
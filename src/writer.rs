@@ -0,0 +1,552 @@
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::str::FromStr;
+use anyhow::Result;
+use arrow::datatypes::{DataType as ArrowDataType, Field, Schema};
+use parquet::basic::{BrotliLevel, Compression, Encoding, GzipLevel, ZstdLevel};
+use parquet::file::properties::{EnabledStatistics, WriterProperties, WriterPropertiesBuilder};
+use parquet::schema::types::ColumnPath;
+use polars::prelude::*;
+use crate::auto_codec::{apply_codec_choices, choose_column_codecs};
+use crate::compat::{CompatMode, TargetReader};
+use crate::field_metadata::FieldMetadata;
+use crate::interop::dataframe_to_record_batches;
+use crate::row_groups::{balanced_row_group_size, RowGroupWriter};
+use crate::warnings::WarningLog;
+
+/// A compression codec override for a single output column
+/// 
+/// Parsed from `column=codec[:level]`, e.g. `blob=zstd:19` or `id=snappy`.
+/// Nested columns are addressed with dotted paths.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnCompression {
+    pub column: String,
+    pub codec: Compression,
+}
+
+impl FromStr for ColumnCompression {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (column, codec) = s
+            .split_once('=')
+            .ok_or_else(|| anyhow::anyhow!("Expected column=codec, got: {}", s))?;
+        if column.is_empty() {
+            anyhow::bail!("Missing column name in: {}", s);
+        }
+        Ok(ColumnCompression {
+            column: column.to_string(),
+            codec: parse_codec(codec)?,
+        })
+    }
+}
+
+/// Codec names accepted by [`parse_codec`]
+pub const SUPPORTED_CODECS: &[&str] = &["uncompressed", "snappy", "gzip", "lz4", "lz4_raw", "brotli", "zstd"];
+
+/// Parse a compression codec name with an optional level
+/// 
+/// # Examples
+/// 
+/// ```
+/// use parquet_consolidator::parse_codec;
+/// use parquet::basic::{Compression, ZstdLevel};
+/// 
+/// assert_eq!(parse_codec("snappy").unwrap(), Compression::SNAPPY);
+/// assert_eq!(parse_codec("zstd:19").unwrap(), Compression::ZSTD(ZstdLevel::try_new(19).unwrap()));
+/// assert!(parse_codec("snappy:3").is_err());
+/// ```
+pub fn parse_codec(s: &str) -> Result<Compression> {
+    let (name, level) = match s.split_once(':') {
+        Some((name, level)) => {
+            let level: i32 = level.parse()
+                .map_err(|_| anyhow::anyhow!("Invalid compression level: {}", level))?;
+            (name, Some(level))
+        }
+        None => (s, None),
+    };
+
+    let codec = match (name.to_lowercase().as_str(), level) {
+        ("uncompressed" | "none", None) => Compression::UNCOMPRESSED,
+        ("snappy", None) => Compression::SNAPPY,
+        ("lz4", None) => Compression::LZ4,
+        ("lz4_raw", None) => Compression::LZ4_RAW,
+        ("gzip", level) => Compression::GZIP(match level {
+            Some(level) => GzipLevel::try_new(level as u32)?,
+            None => GzipLevel::default(),
+        }),
+        ("brotli", level) => Compression::BROTLI(match level {
+            Some(level) => BrotliLevel::try_new(level as u32)?,
+            None => BrotliLevel::default(),
+        }),
+        ("zstd", level) => Compression::ZSTD(match level {
+            Some(level) => ZstdLevel::try_new(level)?,
+            None => ZstdLevel::default(),
+        }),
+        (name, Some(_)) if ["uncompressed", "none", "snappy", "lz4", "lz4_raw"].contains(&name) => {
+            anyhow::bail!("Compression codec {} does not take a level", name)
+        }
+        (name, _) => anyhow::bail!("Unknown compression codec: {}", name),
+    };
+
+    Ok(codec)
+}
+
+/// An encoding override for a single output column
+/// 
+/// Parsed from `column=encoding`, e.g. `ts=delta_binary_packed`. Columns
+/// with an override are written without dictionary encoding so the chosen
+/// encoding is used for every page.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnEncoding {
+    pub column: String,
+    pub encoding: Encoding,
+}
+
+impl FromStr for ColumnEncoding {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (column, encoding) = s
+            .split_once('=')
+            .ok_or_else(|| anyhow::anyhow!("Expected column=encoding, got: {}", s))?;
+        if column.is_empty() {
+            anyhow::bail!("Missing column name in: {}", s);
+        }
+        Ok(ColumnEncoding {
+            column: column.to_string(),
+            encoding: parse_encoding(encoding)?,
+        })
+    }
+}
+
+/// Parse a parquet column encoding name
+/// 
+/// Dictionary encodings are not accepted; they are chosen by the writer.
+/// 
+/// # Examples
+/// 
+/// ```
+/// use parquet_consolidator::parse_encoding;
+/// use parquet::basic::Encoding;
+/// 
+/// assert_eq!(parse_encoding("byte_stream_split").unwrap(), Encoding::BYTE_STREAM_SPLIT);
+/// assert!(parse_encoding("rle_dictionary").is_err());
+/// ```
+pub fn parse_encoding(s: &str) -> Result<Encoding> {
+    let encoding = match s.to_lowercase().as_str() {
+        "plain" => Encoding::PLAIN,
+        "rle" => Encoding::RLE,
+        "delta_binary_packed" => Encoding::DELTA_BINARY_PACKED,
+        "delta_length_byte_array" => Encoding::DELTA_LENGTH_BYTE_ARRAY,
+        "delta_byte_array" => Encoding::DELTA_BYTE_ARRAY,
+        "byte_stream_split" => Encoding::BYTE_STREAM_SPLIT,
+        "plain_dictionary" | "rle_dictionary" => {
+            anyhow::bail!("Dictionary encodings cannot be forced per column: {}", s)
+        }
+        other => anyhow::bail!("Unknown encoding: {}", other),
+    };
+    Ok(encoding)
+}
+
+/// Check that an encoding can be applied to a column of the given type
+fn check_encoding(column: &str, encoding: Encoding, data_type: &ArrowDataType) -> Result<()> {
+    use ArrowDataType::*;
+
+    let supported = match encoding {
+        Encoding::PLAIN => true,
+        Encoding::RLE => matches!(data_type, Boolean),
+        Encoding::DELTA_BINARY_PACKED => data_type.is_integer()
+            || matches!(data_type, Date32 | Date64 | Time32(_) | Time64(_) | Timestamp(_, _) | Duration(_)),
+        Encoding::DELTA_LENGTH_BYTE_ARRAY => matches!(data_type, Utf8 | LargeUtf8 | Binary | LargeBinary),
+        Encoding::DELTA_BYTE_ARRAY => {
+            matches!(data_type, Utf8 | LargeUtf8 | Binary | LargeBinary | FixedSizeBinary(_))
+        }
+        Encoding::BYTE_STREAM_SPLIT => data_type.is_floating()
+            || data_type.is_integer()
+            || matches!(data_type, FixedSizeBinary(_)),
+        _ => false,
+    };
+
+    if !supported {
+        anyhow::bail!("Encoding {} cannot be used for column {} of type {}", encoding, column, data_type);
+    }
+    Ok(())
+}
+
+/// Options controlling how the consolidated output is written
+#[derive(Debug, Clone)]
+pub struct WriterOptions {
+    /// Codec used for every column without an override
+    pub compression: Compression,
+    /// Pick the codec and encoding of every column without an override
+    /// from a sample of it; see [`choose_column_codecs`]
+    pub auto_compression: bool,
+    pub column_compression: Vec<ColumnCompression>,
+    /// Maximum length of min/max values in column statistics and the
+    /// column index; longer values are truncated. `None` keeps the defaults
+    pub statistics_truncate_length: Option<usize>,
+    /// Columns written without any min/max statistics
+    pub disabled_statistics: Vec<String>,
+    pub column_encodings: Vec<ColumnEncoding>,
+    /// Row groups below this size are coalesced where a boundary allows
+    pub min_row_group_rows: Option<usize>,
+    /// Row groups above this size are split
+    pub max_row_group_rows: Option<usize>,
+    /// Restrict the output to what a downstream engine reads reliably
+    pub compat: Option<CompatMode>,
+    /// Warn about what this reader can't read faithfully before writing
+    pub target_reader: Option<TargetReader>,
+    /// Value of the footer's `created_by` field
+    pub created_by: String,
+    /// Field metadata, such as descriptions and units, attached to the
+    /// output columns it names
+    pub field_metadata: FieldMetadata,
+}
+
+impl Default for WriterOptions {
+    fn default() -> Self {
+        WriterOptions {
+            compression: Compression::SNAPPY,
+            auto_compression: false,
+            column_compression: Vec::new(),
+            statistics_truncate_length: None,
+            disabled_statistics: Vec::new(),
+            column_encodings: Vec::new(),
+            min_row_group_rows: None,
+            max_row_group_rows: None,
+            compat: None,
+            target_reader: None,
+            created_by: default_created_by(),
+            field_metadata: FieldMetadata::new(),
+        }
+    }
+}
+
+/// The `created_by` string written when none is configured
+///
+/// ```
+/// assert!(parquet_consolidator::default_created_by().starts_with("parquet_consolidator "));
+/// ```
+pub fn default_created_by() -> String {
+    format!("parquet_consolidator {}", env!("CARGO_PKG_VERSION"))
+}
+
+impl WriterOptions {
+    /// Check that the row group limits are usable together
+    pub fn validate_row_group_limits(&self) -> Result<()> {
+        if self.min_row_group_rows == Some(0) || self.max_row_group_rows == Some(0) {
+            anyhow::bail!("Row group limits must be greater than zero");
+        }
+        if let (Some(min), Some(max)) = (self.min_row_group_rows, self.max_row_group_rows) {
+            if min > max {
+                anyhow::bail!("Minimum row group rows ({}) exceeds the maximum ({})", min, max);
+            }
+        }
+        Ok(())
+    }
+
+    /// Build parquet writer properties for an output with the given schema
+    pub fn writer_properties(&self, schema: &Schema) -> Result<WriterProperties> {
+        Ok(self.properties_builder(schema)?.build())
+    }
+
+    /// Start a properties builder with every configured option applied
+    pub(crate) fn properties_builder(&self, schema: &Schema) -> Result<WriterPropertiesBuilder> {
+        let mut builder = WriterProperties::builder()
+            .set_compression(self.compression)
+            .set_created_by(self.created_by.clone());
+        if let Some(mode) = self.compat {
+            builder = mode.apply(builder);
+        }
+
+        for override_ in &self.column_compression {
+            builder = builder.set_column_compression(column_path(schema, &override_.column)?, override_.codec);
+        }
+
+        if let Some(length) = self.statistics_truncate_length {
+            builder = builder
+                .set_statistics_truncate_length(Some(length))
+                .set_column_index_truncate_length(Some(length));
+        }
+
+        for column in &self.disabled_statistics {
+            builder = builder.set_column_statistics_enabled(column_path(schema, column)?, EnabledStatistics::None);
+        }
+
+        for override_ in &self.column_encodings {
+            let path = column_path(schema, &override_.column)?;
+            if let Some(field) = leaf_field(schema, &override_.column) {
+                check_encoding(&override_.column, override_.encoding, field.data_type())?;
+            }
+            builder = builder
+                .set_column_dictionary_enabled(path.clone(), false)
+                .set_column_encoding(path, override_.encoding);
+        }
+
+        Ok(builder)
+    }
+}
+
+/// Resolve a dotted column name against the output schema
+fn column_path(schema: &Schema, column: &str) -> Result<ColumnPath> {
+    let parts: Vec<String> = column.split('.').map(String::from).collect();
+    if schema.field_with_name(&parts[0]).is_err() {
+        anyhow::bail!("Column not found in consolidated schema: {}", column);
+    }
+    Ok(ColumnPath::new(parts))
+}
+
+/// Find the field a dotted column name refers to, descending through structs
+/// 
+/// Returns `None` when the path passes through a list or map, whose leaf
+/// types are left to the writer to check.
+fn leaf_field<'a>(schema: &'a Schema, column: &str) -> Option<&'a Field> {
+    let mut parts = column.split('.');
+    let mut field = schema.field_with_name(parts.next()?).ok()?;
+    for part in parts {
+        match field.data_type() {
+            ArrowDataType::Struct(children) => {
+                field = children.iter().find(|child| child.name() == part)?;
+            }
+            _ => return None,
+        }
+    }
+    Some(field)
+}
+/// Write a DataFrame as parquet to the file at `output_path`, replacing it
+/// Write a DataFrame to the output path using the configured sink
+pub fn write_dataframe(df: &mut DataFrame, output_path: &Path, options: &WriterOptions) -> Result<()> {
+    options.validate_row_group_limits()?;
+
+    write_dataframe_to(df, File::create(output_path)?, options)?;
+    Ok(())
+}
+
+/// Write a DataFrame as parquet into any sink, such as an upload stream or
+/// an encryption wrapper, returning the sink once the file is complete
+///
+/// # Examples
+///
+/// ```
+/// use polars::prelude::*;
+/// use parquet_consolidator::{write_dataframe_to, WriterOptions};
+///
+/// let mut df = df!("id" => &[1i32, 2, 3]).unwrap();
+/// let buffer = write_dataframe_to(&mut df, Vec::new(), &WriterOptions::default()).unwrap();
+/// assert_eq!(&buffer[..4], b"PAR1");
+/// ```
+pub fn write_dataframe_to<W: Write + Send>(df: &mut DataFrame, sink: W, options: &WriterOptions) -> Result<W> {
+    write_dataframe_logged(df, sink, options, &WarningLog::new(true))
+}
+
+/// [`write_dataframe_to`], raising warnings about the output schema on a
+/// run's log rather than printing them
+pub(crate) fn write_dataframe_logged<W: Write + Send>(
+    df: &mut DataFrame,
+    sink: W,
+    options: &WriterOptions,
+    warnings: &WarningLog,
+) -> Result<W> {
+    options.validate_row_group_limits()?;
+    if options.auto_compression {
+        let choices = choose_column_codecs(df, options)?;
+        return write_dataframe_logged(df, sink, &apply_codec_choices(options, &choices), warnings);
+    }
+
+    let (schema, batches) = dataframe_to_record_batches(df)?;
+    let max_rows = balanced_row_group_size(df.height(), options.max_row_group_rows);
+
+    let mut writer = RowGroupWriter::try_new(sink, schema, options, max_rows, warnings)?;
+    for batch in &batches {
+        writer.write(batch)?;
+    }
+    writer.into_inner()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_column_compression_from_str() {
+        let parsed: ColumnCompression = "blob=zstd:19".parse().unwrap();
+        assert_eq!(parsed.column, "blob");
+        assert_eq!(parsed.codec, Compression::ZSTD(ZstdLevel::try_new(19).unwrap()));
+
+        let parsed: ColumnCompression = "id=snappy".parse().unwrap();
+        assert_eq!(parsed.codec, Compression::SNAPPY);
+
+        assert!("id".parse::<ColumnCompression>().is_err());
+        assert!("id=zstd:99".parse::<ColumnCompression>().is_err());
+        assert!("id=lzma".parse::<ColumnCompression>().is_err());
+    }
+
+    #[test]
+    fn test_write_dataframe_with_column_compression() -> Result<()> {
+        use parquet::file::reader::{FileReader, SerializedFileReader};
+
+        let temp_dir = tempfile::TempDir::new()?;
+        let output_file = temp_dir.path().join("output.parquet");
+        let mut df = df!("id" => [1i32, 2, 3], "blob" => ["a", "b", "c"])?;
+
+        let options = WriterOptions {
+            column_compression: vec!["blob=zstd:19".parse()?],
+            ..Default::default()
+        };
+        write_dataframe(&mut df, &output_file, &options)?;
+
+        let reader = SerializedFileReader::new(File::open(&output_file)?)?;
+        let row_group = reader.metadata().row_group(0);
+        assert_eq!(row_group.column(0).compression(), Compression::SNAPPY);
+        // Levels are not recorded in the footer, only the codec
+        assert!(matches!(row_group.column(1).compression(), Compression::ZSTD(_)));
+
+        let options = WriterOptions {
+            column_compression: vec!["missing=snappy".parse()?],
+            ..Default::default()
+        };
+        assert!(write_dataframe(&mut df, &output_file, &options).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_dataframe_with_statistics_options() -> Result<()> {
+        use parquet::file::reader::{FileReader, SerializedFileReader};
+
+        let temp_dir = tempfile::TempDir::new()?;
+        let output_file = temp_dir.path().join("output.parquet");
+        let long_value = "x".repeat(100);
+        let mut df = df!(
+            "id" => [1i32, 2],
+            "comment" => [long_value.as_str(), long_value.as_str()],
+            "payload" => ["a", "b"]
+        )?;
+
+        let options = WriterOptions {
+            statistics_truncate_length: Some(8),
+            disabled_statistics: vec!["payload".to_string()],
+            ..Default::default()
+        };
+        write_dataframe(&mut df, &output_file, &options)?;
+
+        let reader = SerializedFileReader::new(File::open(&output_file)?)?;
+        let row_group = reader.metadata().row_group(0);
+
+        let comment_stats = row_group.column(1).statistics().unwrap();
+        assert!(comment_stats.max_bytes_opt().unwrap().len() <= 8);
+        assert!(row_group.column(2).statistics().is_none_or(|s| s.min_bytes_opt().is_none()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_dataframe_with_column_encodings() -> Result<()> {
+        use parquet::file::reader::{FileReader, SerializedFileReader};
+
+        let temp_dir = tempfile::TempDir::new()?;
+        let output_file = temp_dir.path().join("output.parquet");
+        let mut df = df!(
+            "ts" => [1i64, 2, 3],
+            "price" => [1.5f64, 2.5, 3.5],
+            "name" => ["a", "b", "c"]
+        )?;
+
+        let options = WriterOptions {
+            column_encodings: vec!["ts=delta_binary_packed".parse()?, "price=byte_stream_split".parse()?],
+            ..Default::default()
+        };
+        write_dataframe(&mut df, &output_file, &options)?;
+
+        let reader = SerializedFileReader::new(File::open(&output_file)?)?;
+        let row_group = reader.metadata().row_group(0);
+        assert!(row_group.column(0).encodings().contains(&Encoding::DELTA_BINARY_PACKED));
+        assert!(row_group.column(1).encodings().contains(&Encoding::BYTE_STREAM_SPLIT));
+
+        let options = WriterOptions {
+            column_encodings: vec!["name=delta_binary_packed".parse()?],
+            ..Default::default()
+        };
+        let result = write_dataframe(&mut df, &output_file, &options);
+        assert!(result.unwrap_err().to_string().contains("cannot be used for column name"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_dataframe_with_row_group_limits() -> Result<()> {
+        use parquet::file::reader::{FileReader, SerializedFileReader};
+
+        let temp_dir = tempfile::TempDir::new()?;
+        let output_file = temp_dir.path().join("output.parquet");
+        let ids: Vec<i32> = (0..1001).collect();
+        let mut df = df!("id" => ids)?;
+
+        let options = WriterOptions {
+            max_row_group_rows: Some(500),
+            ..Default::default()
+        };
+        write_dataframe(&mut df, &output_file, &options)?;
+
+        let reader = SerializedFileReader::new(File::open(&output_file)?)?;
+        let row_counts: Vec<i64> = reader.metadata().row_groups().iter().map(|rg| rg.num_rows()).collect();
+        assert_eq!(row_counts, vec![334, 334, 333]);
+
+        let options = WriterOptions {
+            min_row_group_rows: Some(600),
+            max_row_group_rows: Some(500),
+            ..Default::default()
+        };
+        assert!(write_dataframe(&mut df, &output_file, &options).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_dataframe_with_spark_compat() -> Result<()> {
+        use parquet::file::reader::{FileReader, SerializedFileReader};
+
+        let temp_dir = tempfile::TempDir::new()?;
+        let output_file = temp_dir.path().join("output.parquet");
+        let tags = Series::new("tags", &[Series::new("", &[1i64, 2]), Series::new("", &[3i64])]);
+        let mut df = DataFrame::new(vec![Series::new("id", &[1i32, 2]), tags])?;
+
+        let options = WriterOptions {
+            compat: Some(CompatMode::Spark),
+            ..Default::default()
+        };
+        write_dataframe(&mut df, &output_file, &options)?;
+
+        let reader = SerializedFileReader::new(File::open(&output_file)?)?;
+        let schema = reader.metadata().file_metadata().schema_descr_ptr();
+        assert_eq!(schema.column(1).path().string(), "tags.list.element");
+        assert_eq!(reader.metadata().file_metadata().version(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_dataframe_with_created_by() -> Result<()> {
+        use parquet::file::reader::{FileReader, SerializedFileReader};
+
+        let temp_dir = tempfile::TempDir::new()?;
+        let output_file = temp_dir.path().join("output.parquet");
+        let mut df = df!("id" => &[1i32, 2])?;
+
+        write_dataframe(&mut df, &output_file, &WriterOptions::default())?;
+        let reader = SerializedFileReader::new(File::open(&output_file)?)?;
+        assert_eq!(reader.metadata().file_metadata().created_by(), Some(default_created_by().as_str()));
+
+        let options = WriterOptions {
+            created_by: "lineage-job 42".to_string(),
+            ..Default::default()
+        };
+        write_dataframe(&mut df, &output_file, &options)?;
+        let reader = SerializedFileReader::new(File::open(&output_file)?)?;
+        assert_eq!(reader.metadata().file_metadata().created_by(), Some("lineage-job 42"));
+
+        Ok(())
+    }
+}
@@ -2,7 +2,10 @@ use assert_cmd::Command;
 use predicates::prelude::*;
 use std::fs;
 use tempfile::TempDir;
+use arrow::datatypes::{DataType, Field, Schema};
 use parquet_consolidator::test_utils::*;
+use parquet::file::reader::{FileReader, SerializedFileReader};
+use parquet::record::RowAccessor;
 
 #[test]
 fn test_cli_help() {
@@ -13,6 +16,51 @@ fn test_cli_help() {
         .stdout(predicate::str::contains("Usage:"));
 }
 
+#[test]
+fn test_cli_consolidate_subcommand() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_data_dir = temp_dir.path().join("test_data");
+    let output_file = temp_dir.path().join("output.parquet");
+    create_test_directory_structure(&test_data_dir).unwrap();
+    
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("consolidate")
+        .arg("-i")
+        .arg(&test_data_dir)
+        .arg("-o")
+        .arg(&output_file)
+        .arg("-v")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Successfully consolidated"));
+    assert!(output_file.exists());
+    
+    // Global flags are accepted by every subcommand
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("consolidate")
+        .arg("-V")
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with("parquet_consolidator "));
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("consolidate")
+        .arg("-i")
+        .arg(&test_data_dir)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("required arguments"));
+
+    // and --verbose reaches every subcommand
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("schema")
+        .arg("-i")
+        .arg(&test_data_dir)
+        .arg("-v")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Found 3 input files"));
+}
+
 #[test]
 fn test_cli_basic_consolidation() {
     let temp_dir = TempDir::new().unwrap();
@@ -36,6 +84,24 @@ fn test_cli_basic_consolidation() {
     assert!(output_file.exists());
 }
 
+#[test]
+#[cfg(target_os = "linux")]
+fn test_cli_peak_memory() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_data_dir = temp_dir.path().join("test_data");
+    let output_file = temp_dir.path().join("output.parquet");
+    create_test_directory_structure(&test_data_dir).unwrap();
+
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("-i")
+        .arg(&test_data_dir)
+        .arg("-o")
+        .arg(&output_file)
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match(r"Peak memory \(RSS\): [0-9.]+ [KMG]iB\n").unwrap());
+}
+
 #[test]
 fn test_cli_recursive_consolidation() {
     let temp_dir = TempDir::new().unwrap();
@@ -134,6 +200,18 @@ fn test_cli_empty_directory() {
         .assert()
         .failure()
         .stderr(predicate::str::contains("No parquet files found"));
+
+    // The message names the formats that were looked for
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("-i")
+        .arg(&empty_dir)
+        .arg("-o")
+        .arg(&output_file)
+        .arg("--formats")
+        .arg("csv,ndjson")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("No csv or ndjson files found"));
 }
 
 #[test]
@@ -143,3 +221,2604 @@ fn test_cli_missing_arguments() {
         .failure()
         .stderr(predicate::str::contains("required arguments"));
 }
+
+#[test]
+fn test_cli_mixed_formats() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_data_dir = temp_dir.path().join("test_data");
+    let output_file = temp_dir.path().join("output.parquet");
+    
+    // Create test data; the structure includes a nested csv file
+    create_test_directory_structure(&test_data_dir).unwrap();
+    
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("-i")
+        .arg(&test_data_dir)
+        .arg("-o")
+        .arg(&output_file)
+        .arg("--recursive")
+        .arg("--formats")
+        .arg("parquet,csv")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Successfully consolidated"));
+    
+    assert!(output_file.exists());
+}
+
+#[test]
+fn test_cli_unknown_format() {
+    let temp_dir = TempDir::new().unwrap();
+    let output_file = temp_dir.path().join("output.parquet");
+    
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("-i")
+        .arg(temp_dir.path())
+        .arg("-o")
+        .arg(&output_file)
+        .arg("--formats")
+        .arg("parquet,xml")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Unknown input format"));
+}
+
+#[test]
+fn test_cli_compression_per_column() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_data_dir = temp_dir.path().join("test_data");
+    let output_file = temp_dir.path().join("output.parquet");
+    
+    create_test_directory_structure(&test_data_dir).unwrap();
+    
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("-i")
+        .arg(&test_data_dir)
+        .arg("-o")
+        .arg(&output_file)
+        .arg("--compression-per-column")
+        .arg("name=zstd:19,id=gzip")
+        .assert()
+        .success();
+    
+    assert!(output_file.exists());
+    
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("-i")
+        .arg(&test_data_dir)
+        .arg("-o")
+        .arg(&output_file)
+        .arg("--compression-per-column")
+        .arg("name=lzma")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Unknown compression codec"));
+}
+
+#[test]
+fn test_cli_statistics_options() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_data_dir = temp_dir.path().join("test_data");
+    let output_file = temp_dir.path().join("output.parquet");
+    
+    create_test_directory_structure(&test_data_dir).unwrap();
+    
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("-i")
+        .arg(&test_data_dir)
+        .arg("-o")
+        .arg(&output_file)
+        .arg("--stats-truncate-length")
+        .arg("16")
+        .arg("--no-stats-columns")
+        .arg("name,value")
+        .assert()
+        .success();
+    
+    assert!(output_file.exists());
+}
+
+#[test]
+fn test_cli_encoding_per_column() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_data_dir = temp_dir.path().join("test_data");
+    let output_file = temp_dir.path().join("output.parquet");
+    
+    create_test_directory_structure(&test_data_dir).unwrap();
+    
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("-i")
+        .arg(&test_data_dir)
+        .arg("-o")
+        .arg(&output_file)
+        .arg("--encoding-per-column")
+        .arg("id=delta_binary_packed,value=byte_stream_split,name=delta_byte_array")
+        .assert()
+        .success();
+    
+    assert!(output_file.exists());
+}
+
+#[test]
+fn test_cli_preserve_row_groups() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_data_dir = temp_dir.path().join("test_data");
+    let output_file = temp_dir.path().join("output.parquet");
+    
+    create_test_directory_structure(&test_data_dir).unwrap();
+    
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("-i")
+        .arg(&test_data_dir)
+        .arg("-o")
+        .arg(&output_file)
+        .arg("--recursive")
+        .arg("--preserve-row-groups")
+        .assert()
+        .success();
+    
+    // One row group per input file
+    let reader = SerializedFileReader::new(fs::File::open(&output_file).unwrap()).unwrap();
+    assert_eq!(reader.metadata().num_row_groups(), 5);
+}
+
+#[test]
+fn test_cli_row_group_limits() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_data_dir = temp_dir.path().join("test_data");
+    let output_file = temp_dir.path().join("output.parquet");
+    
+    create_test_directory_structure(&test_data_dir).unwrap();
+    
+    // Five 100-row inputs coalesced into row groups of at least 250 rows
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("-i")
+        .arg(&test_data_dir)
+        .arg("-o")
+        .arg(&output_file)
+        .arg("--recursive")
+        .arg("--preserve-row-groups")
+        .arg("--min-row-group-rows")
+        .arg("250")
+        .assert()
+        .success();
+    
+    let reader = SerializedFileReader::new(fs::File::open(&output_file).unwrap()).unwrap();
+    assert_eq!(reader.metadata().num_row_groups(), 2);
+    
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("-i")
+        .arg(&test_data_dir)
+        .arg("-o")
+        .arg(&output_file)
+        .arg("--min-row-group-rows")
+        .arg("500")
+        .arg("--max-row-group-rows")
+        .arg("100")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("exceeds the maximum"));
+}
+
+#[test]
+fn test_cli_coerce_int96() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_file = temp_dir.path().join("legacy.parquet");
+    let output_file = temp_dir.path().join("output.parquet");
+    
+    create_test_int96_parquet_file(&input_file, 0, 10).unwrap();
+    
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("-i")
+        .arg(&input_file)
+        .arg("-o")
+        .arg(&output_file)
+        .arg("--coerce-int96")
+        .arg("us")
+        .assert()
+        .success();
+    
+    // The output stores the timestamps as INT64 rather than INT96
+    let reader = SerializedFileReader::new(fs::File::open(&output_file).unwrap()).unwrap();
+    let schema = reader.metadata().file_metadata().schema_descr_ptr();
+    assert_eq!(schema.column(1).physical_type(), parquet::basic::Type::INT64);
+    
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("-i")
+        .arg(&input_file)
+        .arg("-o")
+        .arg(&output_file)
+        .arg("--coerce-int96")
+        .arg("seconds")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Unknown time unit"));
+}
+
+#[test]
+fn test_cli_compat_spark() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_file = temp_dir.path().join("legacy.parquet");
+    let output_file = temp_dir.path().join("output.parquet");
+    
+    create_test_int96_parquet_file(&input_file, 0, 10).unwrap();
+    
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("-i")
+        .arg(&input_file)
+        .arg("-o")
+        .arg(&output_file)
+        .arg("--coerce-int96")
+        .arg("ns")
+        .arg("--compat")
+        .arg("spark")
+        .assert()
+        .success();
+    
+    // Nanosecond timestamps are stored as microseconds in a v1 file
+    let reader = SerializedFileReader::new(fs::File::open(&output_file).unwrap()).unwrap();
+    let file_metadata = reader.metadata().file_metadata();
+    assert_eq!(file_metadata.version(), 1);
+    let ts = file_metadata.schema_descr().column(1);
+    assert_eq!(
+        ts.logical_type(),
+        Some(parquet::basic::LogicalType::Timestamp {
+            is_adjusted_to_u_t_c: false,
+            unit: parquet::basic::TimeUnit::MICROS(Default::default()),
+        })
+    );
+}
+
+#[test]
+fn test_cli_compat_spark_warns_about_schema() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_file = temp_dir.path().join("data.csv");
+    let output_file = temp_dir.path().join("output.parquet");
+    
+    fs::write(&input_file, "id,Name,name\n1,a,b\n").unwrap();
+    
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("-i")
+        .arg(&input_file)
+        .arg("-o")
+        .arg(&output_file)
+        .arg("--formats")
+        .arg("csv")
+        .arg("--compat")
+        .arg("spark")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("differ only by case"));
+}
+
+#[test]
+fn test_cli_created_by() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_file = temp_dir.path().join("input.parquet");
+    let output_file = temp_dir.path().join("output.parquet");
+    
+    create_test_parquet_file(&input_file, 0, 10).unwrap();
+    
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("-i")
+        .arg(&input_file)
+        .arg("-o")
+        .arg(&output_file)
+        .arg("--created-by")
+        .arg("nightly-compaction v3")
+        .assert()
+        .success();
+    
+    let reader = SerializedFileReader::new(fs::File::open(&output_file).unwrap()).unwrap();
+    assert_eq!(reader.metadata().file_metadata().created_by(), Some("nightly-compaction v3"));
+}
+
+#[test]
+fn test_cli_null_report() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    let output_file = temp_dir.path().join("output.parquet");
+    fs::create_dir(&input_dir).unwrap();
+    
+    fs::write(input_dir.join("a.csv"), "id,name\n1,a\n2,b\n").unwrap();
+    fs::write(input_dir.join("b.csv"), "id,name\n3,\n4,\n").unwrap();
+    
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("-i")
+        .arg(&input_dir)
+        .arg("-o")
+        .arg(&output_file)
+        .arg("--formats")
+        .arg("csv")
+        .arg("--null-report")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Null report (4 rows)"))
+        .stdout(predicate::str::contains("50.00%"));
+}
+
+#[test]
+fn test_cli_checks() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_file = temp_dir.path().join("input.parquet");
+    let output_file = temp_dir.path().join("output.parquet");
+    let checks_file = temp_dir.path().join("checks.toml");
+    
+    create_test_parquet_file(&input_file, 0, 10).unwrap();
+    
+    fs::write(&checks_file, "[[check]]\ntype = \"range\"\ncolumn = \"id\"\nmax = 4\npolicy = \"quarantine\"\n").unwrap();
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("-i")
+        .arg(&input_file)
+        .arg("-o")
+        .arg(&output_file)
+        .arg("--checks")
+        .arg(&checks_file)
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("range(id) found 5 violating rows"));
+    
+    let reader = SerializedFileReader::new(fs::File::open(&output_file).unwrap()).unwrap();
+    assert_eq!(reader.metadata().file_metadata().num_rows(), 5);
+    
+    fs::write(&checks_file, "[[check]]\ntype = \"unique\"\ncolumns = [\"id\"]\npolicy = \"fail\"\n[[check]]\ntype = \"range\"\ncolumn = \"id\"\nmin = 5\npolicy = \"fail\"\n").unwrap();
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("-i")
+        .arg(&input_file)
+        .arg("-o")
+        .arg(&output_file)
+        .arg("--checks")
+        .arg(&checks_file)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Data quality checks failed: check range(id) found 5 violating rows"));
+}
+
+#[test]
+fn test_cli_quarantine() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_file = temp_dir.path().join("input.parquet");
+    let output_file = temp_dir.path().join("output.parquet");
+    let quarantine_file = temp_dir.path().join("bad_rows.parquet");
+    let checks_file = temp_dir.path().join("checks.toml");
+    
+    create_test_parquet_file(&input_file, 0, 10).unwrap();
+    fs::write(&checks_file, "[[check]]\nname = \"small ids\"\ntype = \"range\"\ncolumn = \"id\"\nmax = 6\npolicy = \"quarantine\"\n").unwrap();
+    
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("-i")
+        .arg(&input_file)
+        .arg("-o")
+        .arg(&output_file)
+        .arg("--checks")
+        .arg(&checks_file)
+        .arg("--quarantine")
+        .arg(&quarantine_file)
+        .assert()
+        .success();
+    
+    let reader = SerializedFileReader::new(fs::File::open(&output_file).unwrap()).unwrap();
+    assert_eq!(reader.metadata().file_metadata().num_rows(), 7);
+    
+    let reader = SerializedFileReader::new(fs::File::open(&quarantine_file).unwrap()).unwrap();
+    let file_metadata = reader.metadata().file_metadata();
+    assert_eq!(file_metadata.num_rows(), 3);
+    assert_eq!(file_metadata.schema_descr().column(3).name(), "_quarantine_reason");
+}
+
+#[test]
+fn test_cli_redact() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_file = temp_dir.path().join("input.csv");
+    let output_file = temp_dir.path().join("output.parquet");
+    
+    fs::write(&input_file, "id,comment\n1,paid with 4111111111111111\n2,cash\n").unwrap();
+    
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("-i")
+        .arg(&input_file)
+        .arg("-o")
+        .arg(&output_file)
+        .arg("--formats")
+        .arg("csv")
+        .arg("--redact")
+        .arg(r"comment=/\b\d{16}\b/")
+        .assert()
+        .success();
+    
+    let contents = fs::read(&output_file).unwrap();
+    let contents = String::from_utf8_lossy(&contents);
+    assert!(contents.contains("paid with [REDACTED]"));
+    assert!(!contents.contains("4111111111111111"));
+}
+
+#[test]
+fn test_cli_dedupe_state_across_runs() {
+    let temp_dir = TempDir::new().unwrap();
+    let state_file = temp_dir.path().join("seen.keys");
+    let output_file = temp_dir.path().join("output.parquet");
+    
+    for (run, start_id) in [(1, 0), (2, 5)] {
+        let input_file = temp_dir.path().join(format!("run{}.parquet", run));
+        create_test_parquet_file(&input_file, start_id, start_id + 10).unwrap();
+        
+        let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+        cmd.arg("-i")
+            .arg(&input_file)
+            .arg("-o")
+            .arg(&output_file)
+            .arg("--dedupe-key")
+            .arg("id")
+            .arg("--dedupe-state")
+            .arg(&state_file)
+            .assert()
+            .success();
+    }
+    
+    // Ids 5..10 were emitted by the first run
+    let reader = SerializedFileReader::new(fs::File::open(&output_file).unwrap()).unwrap();
+    assert_eq!(reader.metadata().file_metadata().num_rows(), 5);
+}
+
+#[test]
+fn test_cli_cdc_merge() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("changes");
+    let output_file = temp_dir.path().join("snapshot.parquet");
+    fs::create_dir(&input_dir).unwrap();
+    
+    fs::write(input_dir.join("a.csv"), "id,name,lsn,_op\n1,a,1,i\n2,b,2,i\n3,c,3,i\n").unwrap();
+    fs::write(input_dir.join("b.csv"), "id,name,lsn,_op\n1,a2,4,u\n3,c,5,d\n").unwrap();
+    
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("-i")
+        .arg(&input_dir)
+        .arg("-o")
+        .arg(&output_file)
+        .arg("--formats")
+        .arg("csv")
+        .arg("--cdc-key")
+        .arg("id")
+        .arg("--cdc-order-by")
+        .arg("lsn")
+        .assert()
+        .success();
+    
+    let reader = SerializedFileReader::new(fs::File::open(&output_file).unwrap()).unwrap();
+    let file_metadata = reader.metadata().file_metadata();
+    assert_eq!(file_metadata.num_rows(), 2);
+    assert_eq!(file_metadata.schema_descr().num_columns(), 3);
+}
+
+#[test]
+fn test_cli_stats_output() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_file = temp_dir.path().join("input.parquet");
+    let output_file = temp_dir.path().join("output.parquet");
+    let stats_file = temp_dir.path().join("stats.json");
+    
+    create_test_parquet_file(&input_file, 0, 10).unwrap();
+    
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("-i")
+        .arg(&input_file)
+        .arg("-o")
+        .arg(&output_file)
+        .arg("--stats-output")
+        .arg(&stats_file)
+        .assert()
+        .success();
+    
+    let stats = fs::read_to_string(&stats_file).unwrap();
+    assert!(stats.contains("\"row_count\": 10"));
+    assert!(stats.contains("\"distinct_count\": 10"));
+}
+
+#[test]
+fn test_cli_profile() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_file = temp_dir.path().join("input.parquet");
+    
+    create_test_parquet_file(&input_file, 0, 10).unwrap();
+    
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("profile")
+        .arg("-i")
+        .arg(&input_file)
+        .arg("--sample-rows")
+        .arg("4")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Profiled 4 rows from 1 files"))
+        .stdout(predicate::str::contains("id (i32)"));
+    
+    // Consolidation still requires an output without a subcommand
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("-i")
+        .arg(&input_file)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--output"));
+}
+
+#[test]
+fn test_cli_schema_markdown() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_file = temp_dir.path().join("input.parquet");
+    
+    create_test_parquet_file(&input_file, 0, 10).unwrap();
+    
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("schema")
+        .arg("-i")
+        .arg(&input_file)
+        .arg("--format")
+        .arg("markdown")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("| Column | Type | Nullable | Description |"))
+        .stdout(predicate::str::contains("| id | i32 | no |  |"));
+}
+
+#[test]
+fn test_cli_name_by_range() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_file = temp_dir.path().join("input.parquet");
+    let output_file = temp_dir.path().join("events.parquet");
+    
+    create_test_parquet_file(&input_file, 3, 42).unwrap();
+    
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("-i")
+        .arg(&input_file)
+        .arg("-o")
+        .arg(&output_file)
+        .arg("--name-by-range")
+        .arg("id")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("events_3_41.parquet"));
+    
+    assert!(temp_dir.path().join("events_3_41.parquet").exists());
+    assert!(!output_file.exists());
+}
+
+#[test]
+fn test_cli_report_duplicates() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    let output_file = temp_dir.path().join("output.parquet");
+    fs::create_dir(&input_dir).unwrap();
+    
+    create_test_parquet_file(&input_dir.join("a.parquet"), 0, 10).unwrap();
+    create_test_parquet_file(&input_dir.join("b.parquet"), 8, 12).unwrap();
+    
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("-i")
+        .arg(&input_dir)
+        .arg("-o")
+        .arg(&output_file)
+        .arg("--report-duplicates")
+        .arg("id")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Consolidated 2 files into 14 rows"))
+        .stdout(predicate::str::contains("Duplicate keys (id): 2 keys shared by 4 rows"));
+}
+
+#[test]
+fn test_cli_per_directory() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("landing");
+    let output_dir = temp_dir.path().join("compacted");
+    fs::create_dir_all(input_dir.join("orders")).unwrap();
+    fs::create_dir_all(input_dir.join("customers")).unwrap();
+    
+    create_test_parquet_file(&input_dir.join("orders/a.parquet"), 0, 10).unwrap();
+    create_test_parquet_file(&input_dir.join("orders/b.parquet"), 10, 20).unwrap();
+    create_test_parquet_file(&input_dir.join("customers/a.parquet"), 0, 5).unwrap();
+    
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("-i")
+        .arg(&input_dir)
+        .arg("-o")
+        .arg(&output_dir)
+        .arg("--per-directory")
+        .assert()
+        .success();
+    
+    let rows = |name: &str| {
+        let reader = SerializedFileReader::new(fs::File::open(output_dir.join(name)).unwrap()).unwrap();
+        reader.metadata().file_metadata().num_rows()
+    };
+    assert_eq!(rows("orders.parquet"), 20);
+    assert_eq!(rows("customers.parquet"), 5);
+}
+
+#[test]
+fn test_cli_per_directory_jobs() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("landing");
+    let output_dir = temp_dir.path().join("compacted");
+    for (i, table) in ["a", "b", "c"].iter().enumerate() {
+        fs::create_dir_all(input_dir.join(table)).unwrap();
+        create_test_parquet_file(&input_dir.join(table).join("part.parquet"), 0, 5 * (i as i32 + 1)).unwrap();
+    }
+    fs::create_dir_all(input_dir.join("broken")).unwrap();
+    fs::write(input_dir.join("broken/part.parquet"), b"not parquet").unwrap();
+    
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("-i")
+        .arg(&input_dir)
+        .arg("-o")
+        .arg(&output_dir)
+        .arg("--per-directory")
+        .arg("--jobs")
+        .arg("2")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Failed to consolidate broken"))
+        .stderr(predicate::str::contains("1 of 4 jobs failed"));
+    
+    for table in ["a", "b", "c"] {
+        assert!(output_dir.join(format!("{}.parquet", table)).exists());
+    }
+}
+
+#[test]
+fn test_cli_mirror_structure() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("events");
+    let output_dir = temp_dir.path().join("compacted");
+    fs::create_dir_all(input_dir.join("day=1")).unwrap();
+    fs::create_dir_all(input_dir.join("day=2")).unwrap();
+    
+    create_test_parquet_file(&input_dir.join("day=1/a.parquet"), 0, 10).unwrap();
+    create_test_parquet_file(&input_dir.join("day=1/b.parquet"), 10, 20).unwrap();
+    create_test_parquet_file(&input_dir.join("day=2/a.parquet"), 0, 5).unwrap();
+    
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("-i")
+        .arg(&input_dir)
+        .arg("-o")
+        .arg(&output_dir)
+        .arg("--mirror-structure")
+        .assert()
+        .success();
+    
+    let rows = |name: &str| {
+        let reader = SerializedFileReader::new(fs::File::open(output_dir.join(name)).unwrap()).unwrap();
+        reader.metadata().file_metadata().num_rows()
+    };
+    assert_eq!(rows("day=1/part-00000.parquet"), 20);
+    assert_eq!(rows("day=2/part-00000.parquet"), 5);
+}
+
+#[test]
+fn test_cli_partition_overwrite() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("events");
+    let output_dir = temp_dir.path().join("dataset");
+    fs::create_dir_all(input_dir.join("day=2")).unwrap();
+    fs::create_dir_all(input_dir.join("day=3")).unwrap();
+    create_test_parquet_file(&input_dir.join("day=2/a.parquet"), 0, 10).unwrap();
+    create_test_parquet_file(&input_dir.join("day=3/a.parquet"), 0, 5).unwrap();
+
+    let seed_dataset = || {
+        let _ = fs::remove_dir_all(&output_dir);
+        for (file, rows) in [("day=1/part-00000.parquet", 7), ("day=2/part-00000.parquet", 3), ("day=2/part-00001.parquet", 4)] {
+            fs::create_dir_all(output_dir.join(file).parent().unwrap()).unwrap();
+            create_test_parquet_file(&output_dir.join(file), 0, rows).unwrap();
+        }
+    };
+    let run = |mode: &str| {
+        Command::cargo_bin("parquet_consolidator").unwrap()
+            .arg("-i").arg(&input_dir)
+            .arg("-o").arg(&output_dir)
+            .arg("--mirror-structure")
+            .arg("--partition-overwrite").arg(mode)
+            .arg("-y")
+            .assert()
+            .success();
+    };
+    let rows = |name: &str| {
+        let path = output_dir.join(name);
+        path.exists().then(|| {
+            SerializedFileReader::new(fs::File::open(path).unwrap()).unwrap().metadata().file_metadata().num_rows()
+        })
+    };
+
+    // Only the partitions in the new data are replaced
+    seed_dataset();
+    run("dynamic");
+    assert_eq!(rows("day=1/part-00000.parquet"), Some(7));
+    assert_eq!(rows("day=2/part-00000.parquet"), Some(10));
+    assert_eq!(rows("day=2/part-00001.parquet"), None);
+    assert_eq!(rows("day=3/part-00000.parquet"), Some(5));
+
+    // The whole dataset is replaced
+    seed_dataset();
+    run("static");
+    assert_eq!(rows("day=1/part-00000.parquet"), None);
+    assert_eq!(rows("day=2/part-00000.parquet"), Some(10));
+    assert_eq!(rows("day=2/part-00001.parquet"), None);
+
+    // New files are added next to the existing ones
+    seed_dataset();
+    run("append");
+    assert_eq!(rows("day=1/part-00000.parquet"), Some(7));
+    assert_eq!(rows("day=2/part-00000.parquet"), Some(3));
+    assert_eq!(rows("day=2/part-00001.parquet"), Some(4));
+    assert_eq!(rows("day=2/part-00002.parquet"), Some(10));
+    assert_eq!(rows("day=3/part-00000.parquet"), Some(5));
+
+    Command::cargo_bin("parquet_consolidator").unwrap()
+        .arg("-i").arg(&input_dir)
+        .arg("-o").arg(&output_dir)
+        .arg("--partition-overwrite").arg("dynamic")
+        .assert()
+        .failure();
+}
+
+#[test]
+fn test_cli_exclude_dir() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    let output_file = temp_dir.path().join("output.parquet");
+    fs::create_dir_all(input_dir.join("_temporary/0")).unwrap();
+    fs::create_dir_all(input_dir.join("archive")).unwrap();
+    
+    create_test_parquet_file(&input_dir.join("a.parquet"), 0, 10).unwrap();
+    create_test_parquet_file(&input_dir.join("_temporary/0/b.parquet"), 10, 20).unwrap();
+    create_test_parquet_file(&input_dir.join("archive/c.parquet"), 20, 30).unwrap();
+    
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("-i")
+        .arg(&input_dir)
+        .arg("-o")
+        .arg(&output_file)
+        .arg("-r")
+        .arg("--exclude-dir")
+        .arg("_temporary")
+        .arg("--exclude-dir")
+        .arg("archive")
+        .assert()
+        .success();
+    
+    let reader = SerializedFileReader::new(fs::File::open(&output_file).unwrap()).unwrap();
+    assert_eq!(reader.metadata().file_metadata().num_rows(), 10);
+}
+
+#[test]
+fn test_cli_name_regex() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    let output_file = temp_dir.path().join("output.parquet");
+    fs::create_dir_all(&input_dir).unwrap();
+    
+    create_test_parquet_file(&input_dir.join("part-00000-a.parquet"), 0, 10).unwrap();
+    create_test_parquet_file(&input_dir.join("part-00001-b.parquet"), 10, 20).unwrap();
+    create_test_parquet_file(&input_dir.join("snapshot.parquet"), 0, 20).unwrap();
+    
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("-i")
+        .arg(&input_dir)
+        .arg("-o")
+        .arg(&output_file)
+        .arg("--name-regex")
+        .arg(r"part-\d+-.*\.parquet")
+        .assert()
+        .success();
+    
+    let reader = SerializedFileReader::new(fs::File::open(&output_file).unwrap()).unwrap();
+    assert_eq!(reader.metadata().file_metadata().num_rows(), 20);
+    
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("-i")
+        .arg(&input_dir)
+        .arg("-o")
+        .arg(&output_file)
+        .arg("--name-regex")
+        .arg("part-(")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid file name pattern"));
+}
+
+#[test]
+fn test_cli_max_files() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    let output_file = temp_dir.path().join("output.parquet");
+    fs::create_dir_all(&input_dir).unwrap();
+    
+    let now = std::time::SystemTime::now();
+    for (name, age, start) in [("a.parquet", 10, 0), ("b.parquet", 30, 10), ("c.parquet", 20, 20)] {
+        let path = input_dir.join(name);
+        create_test_parquet_file(&path, start, start + 10).unwrap();
+        let file = fs::File::options().write(true).open(&path).unwrap();
+        file.set_modified(now - std::time::Duration::from_secs(age)).unwrap();
+    }
+    
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("-i")
+        .arg(&input_dir)
+        .arg("-o")
+        .arg(&output_file)
+        .arg("--max-files")
+        .arg("2")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Consolidated 2 files into 20 rows"));
+    
+    // The newest file (ids 0..10) is left for the next run
+    let reader = SerializedFileReader::new(fs::File::open(&output_file).unwrap()).unwrap();
+    let ids: Vec<i32> = reader
+        .get_row_iter(None)
+        .unwrap()
+        .map(|row| row.unwrap().get_int(0).unwrap())
+        .collect();
+    assert_eq!(ids, (10..30).collect::<Vec<_>>());
+}
+
+#[test]
+fn test_cli_order() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    let output_file = temp_dir.path().join("output.parquet");
+    fs::create_dir_all(&input_dir).unwrap();
+    
+    create_test_parquet_file(&input_dir.join("a.parquet"), 0, 5).unwrap();
+    create_test_parquet_file(&input_dir.join("b.parquet"), 5, 100).unwrap();
+    
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("-i")
+        .arg(&input_dir)
+        .arg("-o")
+        .arg(&output_file)
+        .arg("--order")
+        .arg("largest-first")
+        .assert()
+        .success();
+    
+    let reader = SerializedFileReader::new(fs::File::open(&output_file).unwrap()).unwrap();
+    let first_id = reader.get_row_iter(None).unwrap().next().unwrap().unwrap().get_int(0).unwrap();
+    assert_eq!(first_id, 5);
+    
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("-i")
+        .arg(&input_dir)
+        .arg("-o")
+        .arg(&output_file)
+        .arg("--order")
+        .arg("random")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Unknown input order"));
+}
+
+#[test]
+fn test_cli_min_file_age() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    let output_file = temp_dir.path().join("output.parquet");
+    fs::create_dir_all(&input_dir).unwrap();
+    
+    let settled = input_dir.join("settled.parquet");
+    create_test_parquet_file(&settled, 0, 10).unwrap();
+    let file = fs::File::options().write(true).open(&settled).unwrap();
+    file.set_modified(std::time::SystemTime::now() - std::time::Duration::from_secs(3600)).unwrap();
+    create_test_parquet_file(&input_dir.join("in_flight.parquet"), 10, 20).unwrap();
+    
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("-i")
+        .arg(&input_dir)
+        .arg("-o")
+        .arg(&output_file)
+        .arg("--min-file-age")
+        .arg("300")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Consolidated 1 files into 10 rows"));
+}
+
+#[test]
+fn test_cli_files_from_stdin_null_delimited() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("in put");
+    let output_file = temp_dir.path().join("output.parquet");
+    fs::create_dir_all(&input_dir).unwrap();
+    
+    let first = input_dir.join("with\nnewline.parquet");
+    let second = input_dir.join("with space.parquet");
+    create_test_parquet_file(&first, 0, 10).unwrap();
+    create_test_parquet_file(&second, 10, 15).unwrap();
+    create_test_parquet_file(&input_dir.join("unlisted.parquet"), 0, 100).unwrap();
+    
+    let mut list = Vec::new();
+    for path in [&first, &second] {
+        list.extend_from_slice(path.to_str().unwrap().as_bytes());
+        list.push(0);
+    }
+    
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("--files-from")
+        .arg("-")
+        .arg("--null")
+        .arg("-o")
+        .arg(&output_file)
+        .write_stdin(list)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Consolidated 2 files into 15 rows"));
+    
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("-o")
+        .arg(&output_file)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--input"));
+}
+
+#[test]
+fn test_cli_if_exists() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_file = temp_dir.path().join("input.parquet");
+    let output_file = temp_dir.path().join("output.parquet");
+    create_test_parquet_file(&input_file, 0, 10).unwrap();
+    fs::write(&output_file, "existing").unwrap();
+    
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("-i")
+        .arg(&input_file)
+        .arg("-o")
+        .arg(&output_file)
+        .arg("--if-exists")
+        .arg("skip")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("output already exists"));
+    assert_eq!(fs::read_to_string(&output_file).unwrap(), "existing");
+    
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("-i")
+        .arg(&input_file)
+        .arg("-o")
+        .arg(&output_file)
+        .arg("--if-exists")
+        .arg("unique")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("output-1.parquet"));
+    assert_eq!(fs::read_to_string(&output_file).unwrap(), "existing");
+    assert!(temp_dir.path().join("output-1.parquet").exists());
+}
+
+#[test]
+fn test_cli_explain() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    let output_file = temp_dir.path().join("output.parquet");
+    fs::create_dir_all(&input_dir).unwrap();
+    
+    create_test_parquet_file(&input_dir.join("a.parquet"), 0, 10).unwrap();
+    create_test_parquet_file(&input_dir.join("b.parquet"), 10, 20).unwrap();
+    
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("-i")
+        .arg(&input_dir)
+        .arg("-o")
+        .arg(&output_file)
+        .arg("--explain")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("UNION"))
+        .stdout(predicate::str::contains("a.parquet"));
+    
+    assert!(!output_file.exists());
+}
+
+#[test]
+fn test_cli_overwrite_without_terminal_or_with_yes() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    let output_file = temp_dir.path().join("output.parquet");
+    fs::create_dir_all(&input_dir).unwrap();
+    
+    create_test_parquet_file(&input_dir.join("a.parquet"), 0, 10).unwrap();
+    create_test_parquet_file(&output_file, 0, 3).unwrap();
+    
+    // Piped stdin is not a terminal, so no confirmation is asked for
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("-i")
+        .arg(&input_dir)
+        .arg("-o")
+        .arg(&output_file)
+        .write_stdin("")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Continue?").not());
+    
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("-i")
+        .arg(&input_dir)
+        .arg("-o")
+        .arg(&output_file)
+        .arg("--yes")
+        .assert()
+        .success();
+    
+    let reader = SerializedFileReader::new(fs::File::open(&output_file).unwrap()).unwrap();
+    assert_eq!(reader.metadata().file_metadata().num_rows(), 10);
+}
+
+#[test]
+fn test_cli_version_verbose() {
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("--version")
+        .assert()
+        .success()
+        .stdout(format!("parquet_consolidator {}\n", env!("CARGO_PKG_VERSION")));
+    
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("--version")
+        .arg("--verbose")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("polars: "))
+        .stdout(predicate::str::contains("Compression codecs: "))
+        .stdout(predicate::str::contains("Storage schemes: file"));
+    
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    let output = cmd.arg("-V").arg("--json").output().unwrap();
+    assert!(output.status.success());
+    let info: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(info["version"], env!("CARGO_PKG_VERSION"));
+    assert!(info["input_formats"].as_array().unwrap().contains(&"parquet".into()));
+}
+
+#[test]
+fn test_cli_repair_truncated_file() {
+    let temp_dir = TempDir::new().unwrap();
+    let healthy = temp_dir.path().join("healthy.parquet");
+    let truncated = temp_dir.path().join("truncated.parquet");
+    let output_file = temp_dir.path().join("recovered.parquet");
+    
+    create_test_parquet_file(&healthy, 0, 25).unwrap();
+    let data = fs::read(&healthy).unwrap();
+    fs::write(&truncated, &data[..data.len() - 20]).unwrap();
+    
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("repair")
+        .arg("-i")
+        .arg(&truncated)
+        .arg("-o")
+        .arg(&output_file)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--schema-from").or(predicate::str::contains("footer")));
+    
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("repair")
+        .arg("-i")
+        .arg(&truncated)
+        .arg("-o")
+        .arg(&output_file)
+        .arg("--schema-from")
+        .arg(&healthy)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Footer: rebuilt from page headers"))
+        .stdout(predicate::str::contains("Rows recovered: 25"));
+    
+    let reader = SerializedFileReader::new(fs::File::open(&output_file).unwrap()).unwrap();
+    assert_eq!(reader.metadata().file_metadata().num_rows(), 25);
+}
+
+#[test]
+fn test_cli_meta() {
+    let temp_dir = TempDir::new().unwrap();
+    let file = temp_dir.path().join("data.parquet");
+    create_test_parquet_file(&file, 0, 10).unwrap();
+    
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("meta")
+        .arg("-i")
+        .arg(&file)
+        .arg("--set")
+        .arg("owner=etl")
+        .arg("--set")
+        .arg("pipeline=nightly")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("owner=etl\npipeline=nightly\n"));
+    
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("meta")
+        .arg("-i")
+        .arg(&file)
+        .arg("--remove")
+        .arg("owner")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("owner=").not())
+        .stdout(predicate::str::contains("pipeline=nightly"));
+    
+    let reader = SerializedFileReader::new(fs::File::open(&file).unwrap()).unwrap();
+    assert_eq!(reader.metadata().file_metadata().num_rows(), 10);
+}
+
+#[cfg(feature = "fault-injection")]
+#[test]
+fn test_cli_fault_injection() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("table");
+    let policy = temp_dir.path().join("policy.toml");
+    fs::create_dir_all(&input_dir).unwrap();
+    for i in 0..2 {
+        create_test_parquet_file(&input_dir.join(format!("part-{}.parquet", i)), i * 10, i * 10 + 10).unwrap();
+    }
+    fs::write(&policy, "small_file_bytes = 1000000\ntarget_file_bytes = 100000000\n").unwrap();
+
+    // A crash before the compacted file is moved into place leaves the inputs untouched
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("compact")
+        .arg("-i")
+        .arg(&input_dir)
+        .arg("--policy")
+        .arg(&policy)
+        .arg("-y")
+        .arg("--fault-inject")
+        .arg("commit:crash")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Injected crash at commit"));
+    assert!(input_dir.join("part-0.parquet").exists());
+    assert!(input_dir.join("part-1.parquet").exists());
+    assert!(!input_dir.join("compacted-00000.parquet").exists());
+
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("-i")
+        .arg(&input_dir)
+        .arg("-o")
+        .arg(temp_dir.path().join("output.parquet"))
+        .arg("--fault-inject")
+        .arg("read:error@2")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Injected read error"));
+
+    // A footer edit that fails or crashes before its rename leaves the file as it was
+    let file = input_dir.join("part-0.parquet");
+    let original = fs::read(&file).unwrap();
+    for fault in ["write:error", "commit:error", "commit:crash"] {
+        let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+        cmd.arg("meta")
+            .arg("-i")
+            .arg(&file)
+            .arg("--set")
+            .arg("owner=etl")
+            .arg("--fault-inject")
+            .arg(fault)
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("Injected"));
+        assert_eq!(fs::read(&file).unwrap(), original, "{}", fault);
+    }
+}
+
+#[test]
+fn test_cli_versioned_outputs() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_data_dir = temp_dir.path().join("test_data");
+    let output_file = temp_dir.path().join("output.parquet");
+    create_test_directory_structure(&test_data_dir).unwrap();
+
+    for _ in 0..2 {
+        let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+        cmd.arg("-i")
+            .arg(&test_data_dir)
+            .arg("-o")
+            .arg(&output_file)
+            .arg("--versioned")
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("Recorded version"));
+    }
+
+    let reader = SerializedFileReader::new(fs::File::open(&output_file).unwrap()).unwrap();
+    let metadata = reader.metadata().file_metadata().key_value_metadata().unwrap();
+    let version = metadata.iter().find(|entry| entry.key == "parquet_consolidator.dataset_version").unwrap();
+    assert_eq!(version.value.as_deref(), Some("2"));
+
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("versions")
+        .arg(&output_file)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Version 1 at "))
+        .stdout(predicate::str::contains("Version 2 at "))
+        .stdout(predicate::str::contains("file1.parquet"));
+}
+
+#[test]
+fn test_cli_union_schemas() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    let output_file = temp_dir.path().join("output.parquet");
+    fs::create_dir_all(&input_dir).unwrap();
+    create_test_parquet_file(&input_dir.join("a.parquet"), 0, 10).unwrap();
+    create_test_parquet_file_with_extra_column(&input_dir.join("b.parquet"), 10, 15).unwrap();
+
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("-i").arg(&input_dir).arg("-o").arg(&output_file).assert().failure();
+
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("-i")
+        .arg(&input_dir)
+        .arg("-o")
+        .arg(&output_file)
+        .arg("--union-schemas")
+        .assert()
+        .success();
+
+    let reader = SerializedFileReader::new(fs::File::open(&output_file).unwrap()).unwrap();
+    let metadata = reader.metadata().file_metadata();
+    assert_eq!(metadata.num_rows(), 15);
+    let columns: Vec<&str> = metadata.schema_descr().columns().iter().map(|column| column.name()).collect();
+    assert_eq!(columns, ["id", "name", "value", "extra"]);
+}
+
+#[cfg(feature = "schema-registry")]
+#[test]
+fn test_cli_schema_registry() {
+    use std::io::{Read, Write};
+
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    let output_file = temp_dir.path().join("output.parquet");
+    fs::create_dir_all(&input_dir).unwrap();
+    create_test_parquet_file(&input_dir.join("a.parquet"), 0, 10).unwrap();
+
+    let avro = r#"{"type": "record", "name": "Row", "fields": [{"name": "id", "type": "long"},
+        {"name": "value", "type": "double"}, {"name": "name", "type": ["null", "string"]}]}"#;
+    let body = serde_json::json!({ "subject": "rows-value", "version": 4, "id": 19, "schema": avro }).to_string();
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut request = Vec::new();
+        let mut buffer = [0u8; 1024];
+        while !request.ends_with(b"\r\n\r\n") {
+            let read = stream.read(&mut buffer).unwrap();
+            request.extend_from_slice(&buffer[..read]);
+        }
+        write!(stream, "HTTP/1.0 200 OK\r\nContent-Length: {}\r\n\r\n{}", body.len(), body).unwrap();
+    });
+
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("-i")
+        .arg(&input_dir)
+        .arg("-o")
+        .arg(&output_file)
+        .arg("--schema-registry")
+        .arg(&url)
+        .arg("--schema-subject")
+        .arg("rows-value")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Conforming to schema rows-value version 4 (id 19)"));
+
+    let reader = SerializedFileReader::new(fs::File::open(&output_file).unwrap()).unwrap();
+    let metadata = reader.metadata().file_metadata();
+    let columns: Vec<(&str, parquet::basic::Type)> = metadata
+        .schema_descr()
+        .columns()
+        .iter()
+        .map(|column| (column.name(), column.physical_type()))
+        .collect();
+    assert_eq!(columns, [
+        ("id", parquet::basic::Type::INT64),
+        ("value", parquet::basic::Type::DOUBLE),
+        ("name", parquet::basic::Type::BYTE_ARRAY),
+    ]);
+    let key_value: Vec<(String, Option<String>)> = metadata
+        .key_value_metadata()
+        .unwrap()
+        .iter()
+        .map(|kv| (kv.key.clone(), kv.value.clone()))
+        .collect();
+    assert!(key_value.contains(&(parquet_consolidator::SCHEMA_ID_METADATA_KEY.to_string(), Some("19".to_string()))));
+    assert!(key_value.contains(&(parquet_consolidator::SCHEMA_SUBJECT_METADATA_KEY.to_string(), Some("rows-value:4".to_string()))));
+}
+
+#[test]
+fn test_cli_max_rows_per_file() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    let output_file = temp_dir.path().join("events.parquet");
+    fs::create_dir_all(&input_dir).unwrap();
+    create_test_parquet_file(&input_dir.join("a.parquet"), 0, 10).unwrap();
+    create_test_parquet_file(&input_dir.join("b.parquet"), 10, 25).unwrap();
+
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("-i")
+        .arg(&input_dir)
+        .arg("-o")
+        .arg(&output_file)
+        .arg("--max-rows-per-file")
+        .arg("10")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("into 3 files of at most 10 rows"));
+
+    assert!(!output_file.exists());
+    let rows: Vec<i64> = ["events-00000.parquet", "events-00001.parquet", "events-00002.parquet"]
+        .iter()
+        .map(|name| {
+            let reader = SerializedFileReader::new(fs::File::open(temp_dir.path().join(name)).unwrap()).unwrap();
+            reader.metadata().file_metadata().num_rows()
+        })
+        .collect();
+    assert_eq!(rows, [10, 10, 5]);
+}
+
+#[test]
+fn test_cli_target_reader() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    let output_file = temp_dir.path().join("output.parquet");
+    fs::create_dir_all(&input_dir).unwrap();
+    let schema = std::sync::Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int64, false),
+        Field::new("count", DataType::UInt32, true),
+    ]));
+    create_random_parquet_file(&input_dir.join("a.parquet"), &schema, 10, 0.0, 1).unwrap();
+
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("-i")
+        .arg(&input_dir)
+        .arg("-o")
+        .arg(&output_file)
+        .arg("--target-reader")
+        .arg("athena")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Warning: athena can't read column \"count\" (UInt32) faithfully"));
+
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("-i")
+        .arg(&input_dir)
+        .arg("-o")
+        .arg(&output_file)
+        .arg("--target-reader")
+        .arg("duckdb")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Warning").not());
+}
+
+#[test]
+fn test_cli_polars_verbosity() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    let output_file = temp_dir.path().join("output.parquet");
+    fs::create_dir_all(&input_dir).unwrap();
+    create_test_parquet_file(&input_dir.join("a.parquet"), 0, 10).unwrap();
+    create_test_parquet_file(&input_dir.join("b.parquet"), 10, 20).unwrap();
+
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("-i")
+        .arg(&input_dir)
+        .arg("-o")
+        .arg(&output_file)
+        .arg("-v")
+        .env_remove("POLARS_VERBOSE")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Reading file"))
+        .stdout(predicate::str::contains("UNION:").not());
+
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("-i")
+        .arg(&input_dir)
+        .arg("-o")
+        .arg(&output_file)
+        .arg("-vv")
+        .env_remove("POLARS_VERBOSE")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("UNION: union is run in parallel"));
+}
+
+#[test]
+fn test_cli_assert_sorted_by() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    let output_file = temp_dir.path().join("output.parquet");
+    fs::create_dir_all(&input_dir).unwrap();
+    create_test_parquet_file(&input_dir.join("a.parquet"), 0, 10).unwrap();
+    create_test_parquet_file(&input_dir.join("b.parquet"), 10, 20).unwrap();
+
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("-i").arg(&input_dir).arg("-o").arg(&output_file).arg("--assert-sorted-by").arg("id").assert().success();
+
+    // Names sort as strings, so name_10 comes before name_9
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("-i")
+        .arg(&input_dir)
+        .arg("-o")
+        .arg(&output_file)
+        .arg("--assert-sorted-by")
+        .arg("name")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("is not sorted by name: row 10 (row group 0) holds name_10 after name_9"));
+}
+
+#[test]
+fn test_cli_nullability_conflicts() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    let output_file = temp_dir.path().join("output.parquet");
+    fs::create_dir_all(&input_dir).unwrap();
+    create_test_parquet_file(&input_dir.join("a.parquet"), 0, 10).unwrap();
+    let schema = std::sync::Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("name", DataType::Utf8, false),
+        Field::new("value", DataType::Float64, true),
+    ]));
+    create_random_parquet_file(&input_dir.join("b.parquet"), &schema, 20, 0.5, 7).unwrap();
+
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("-i")
+        .arg(&input_dir)
+        .arg("-o")
+        .arg(&output_file)
+        .arg("--nullability-conflicts")
+        .arg("error")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Column value is required in 1 inputs but nullable in 1"));
+    assert!(!output_file.exists());
+
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("-i")
+        .arg(&input_dir)
+        .arg("-o")
+        .arg(&output_file)
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("it is written as nullable"));
+    let reader = SerializedFileReader::new(fs::File::open(&output_file).unwrap()).unwrap();
+    assert_eq!(reader.metadata().file_metadata().num_rows(), 30);
+
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("-i")
+        .arg(&input_dir)
+        .arg("-o")
+        .arg(&output_file)
+        .arg("--nullability-conflicts")
+        .arg("drop-null-rows")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("1 columns were required in some inputs and nullable in others; dropped"));
+    let reader = SerializedFileReader::new(fs::File::open(&output_file).unwrap()).unwrap();
+    let rows = reader.metadata().file_metadata().num_rows();
+    assert!((10..30).contains(&rows), "{}", rows);
+}
+
+#[test]
+fn test_cli_run_datasets() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    fs::create_dir_all(root.join("landing/events/day=1")).unwrap();
+    fs::create_dir_all(root.join("landing/users")).unwrap();
+    create_test_parquet_file(&root.join("landing/events/day=1/a.parquet"), 0, 10).unwrap();
+    create_test_parquet_file(&root.join("landing/events/day=1/b.parquet"), 10, 20).unwrap();
+    create_test_parquet_file(&root.join("landing/users/u.parquet"), 0, 5).unwrap();
+    let config = root.join("datasets.toml");
+    fs::write(
+        &config,
+        "[datasets.events]\ninput = \"landing/events\"\noutput = \"warehouse/events\"\n\
+         partitioning = \"mirror\"\nrecursive = true\nschedule = \"1h\"\n\n\
+         [datasets.users]\ninput = \"landing/users\"\noutput = \"warehouse/users.parquet\"\n",
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("run")
+        .arg("--config")
+        .arg(&config)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("events: wrote 1 outputs with 20 rows"))
+        .stdout(predicate::str::contains("users: wrote 1 outputs with 5 rows"));
+    assert!(root.join("warehouse/events/day=1/part-00000.parquet").exists());
+    assert!(root.join("datasets.state.json").exists());
+
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("run")
+        .arg("--config")
+        .arg(&config)
+        .arg("--dataset")
+        .arg("events")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("events: not due for another"));
+
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("run")
+        .arg("--config")
+        .arg(&config)
+        .arg("--dataset")
+        .arg("orders")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Unknown dataset \"orders\" (defined: events, users)"));
+}
+
+#[test]
+fn test_cli_compact() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("table");
+    let policy = temp_dir.path().join("policy.toml");
+    fs::create_dir_all(input_dir.join("day=1")).unwrap();
+    
+    for i in 0..3 {
+        create_test_parquet_file(&input_dir.join(format!("day=1/part-{}.parquet", i)), i * 10, i * 10 + 10).unwrap();
+    }
+    fs::write(&policy, "small_file_bytes = 1000000\ntarget_file_bytes = 100000000\n").unwrap();
+    
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("compact")
+        .arg("-i")
+        .arg(&input_dir)
+        .arg("-r")
+        .arg("--policy")
+        .arg(&policy)
+        .arg("--dry-run")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Compact 3 files"));
+    assert!(input_dir.join("day=1/part-0.parquet").exists());
+    
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("compact")
+        .arg("-i")
+        .arg(&input_dir)
+        .arg("-r")
+        .arg("--policy")
+        .arg(&policy)
+        .arg("--audit-log")
+        .arg(temp_dir.path().join("runs.jsonl"))
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Files compacted: 3"));
+    
+    assert!(!input_dir.join("day=1/part-0.parquet").exists());
+    let output_file = input_dir.join("day=1/compacted-00000.parquet");
+    let reader = SerializedFileReader::new(fs::File::open(&output_file).unwrap()).unwrap();
+    assert_eq!(reader.metadata().file_metadata().num_rows(), 30);
+    
+    let record: serde_json::Value = serde_json::from_str(&fs::read_to_string(temp_dir.path().join("runs.jsonl")).unwrap()).unwrap();
+    assert_eq!(record["command"], "compact");
+    assert_eq!(record["deleted"].as_array().unwrap().len(), 3);
+    assert_eq!(record["outputs"][0]["path"], output_file.to_str().unwrap());
+    
+    assert_eq!(parquet_consolidator::compaction_generation(&output_file), 1);
+    
+    // A single generation 1 file has nothing to be merged with
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("compact")
+        .arg("-i")
+        .arg(&input_dir)
+        .arg("-r")
+        .arg("--policy")
+        .arg(&policy)
+        .arg("--recompact")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Nothing to compact"));
+}
+
+#[test]
+fn test_cli_use_metadata_file() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    let output_file = temp_dir.path().join("output.parquet");
+    fs::create_dir_all(input_dir.join("day=1")).unwrap();
+    
+    create_test_parquet_file(&input_dir.join("day=1/a.parquet"), 0, 10).unwrap();
+    create_test_parquet_file(&input_dir.join("day=1/b.parquet"), 10, 20).unwrap();
+    create_summary_metadata_files(&input_dir, &["day=1/a.parquet"]).unwrap();
+    
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("-i")
+        .arg(&input_dir)
+        .arg("-o")
+        .arg(&output_file)
+        .arg("-r")
+        .arg("--use-metadata-file")
+        .assert()
+        .success();
+    
+    let reader = SerializedFileReader::new(fs::File::open(&output_file).unwrap()).unwrap();
+    assert_eq!(reader.metadata().file_metadata().num_rows(), 10);
+    
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("schema")
+        .arg("-i")
+        .arg(&input_dir)
+        .arg("--use-metadata-file")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("value"));
+}
+
+#[test]
+fn test_cli_drift() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    fs::create_dir_all(input_dir.join("day=1")).unwrap();
+    fs::create_dir_all(input_dir.join("day=2")).unwrap();
+    
+    create_test_parquet_file(&input_dir.join("day=1/a.parquet"), 0, 10).unwrap();
+    create_test_parquet_file_with_extra_column(&input_dir.join("day=2/b.parquet"), 10, 20).unwrap();
+    
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("drift")
+        .arg("-i")
+        .arg(&input_dir)
+        .arg("-r")
+        .arg("--partition-key")
+        .arg("day")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("initial schema (3 columns)"))
+        .stdout(predicate::str::contains("+ extra (str)"))
+        .stdout(predicate::str::contains("1 schema changes across 2 files"));
+}
+
+#[test]
+fn test_cli_anomaly_warnings() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    fs::create_dir(&input_dir).unwrap();
+    
+    create_test_parquet_file(&input_dir.join("a.parquet"), 0, 10).unwrap();
+    create_test_parquet_file(&input_dir.join("b.parquet"), 10, 20).unwrap();
+    create_test_parquet_file(&input_dir.join("c.parquet"), 20, 30).unwrap();
+    create_test_parquet_file(&input_dir.join("d.parquet"), 1_000_000, 1_000_010).unwrap();
+    
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("-i")
+        .arg(&input_dir)
+        .arg("-o")
+        .arg(temp_dir.path().join("output.parquet"))
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("d.parquet\": column id ranges from 1000000 to 1000009, far outside the typical"))
+        .stderr(predicate::str::contains("a.parquet").not());
+    
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("-i")
+        .arg(&input_dir)
+        .arg("-o")
+        .arg(temp_dir.path().join("quiet.parquet"))
+        .arg("--no-anomaly-warnings")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("far outside").not());
+}
+
+#[test]
+fn test_cli_timings() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    fs::create_dir(&input_dir).unwrap();
+    
+    create_test_parquet_file(&input_dir.join("a.parquet"), 0, 10).unwrap();
+    create_test_parquet_file(&input_dir.join("b.parquet"), 10, 20).unwrap();
+    
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    let assert = cmd.arg("-i")
+        .arg(&input_dir)
+        .arg("-o")
+        .arg(temp_dir.path().join("output.parquet"))
+        .arg("--timings")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Consolidated 2 files into 20 rows"))
+        .stdout(predicate::str::contains("Timings ("));
+    
+    let stdout = String::from_utf8(assert.get_output().stdout.clone()).unwrap();
+    let phases: Vec<&str> = stdout
+        .lines()
+        .skip_while(|line| !line.starts_with("Timings"))
+        .skip(1)
+        .filter_map(|line| line.split("  ").map(str::trim).find(|field| !field.is_empty()))
+        .collect();
+    assert_eq!(phases, vec!["discovery", "schema check", "scan/decode", "concat", "row options", "write", "total"]);
+}
+
+#[test]
+fn test_cli_statsd() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    fs::create_dir(&input_dir).unwrap();
+    create_test_parquet_file(&input_dir.join("a.parquet"), 0, 10).unwrap();
+    create_test_parquet_file(&input_dir.join("b.parquet"), 10, 20).unwrap();
+    
+    let agent = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    agent.set_read_timeout(Some(std::time::Duration::from_secs(5))).unwrap();
+    
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("-i")
+        .arg(&input_dir)
+        .arg("-o")
+        .arg(temp_dir.path().join("output.parquet"))
+        .arg("--statsd")
+        .arg(agent.local_addr().unwrap().to_string())
+        .assert()
+        .success();
+    
+    let mut lines = Vec::new();
+    let mut buffer = [0u8; 512];
+    while let Ok(received) = agent.recv(&mut buffer) {
+        lines.push(String::from_utf8_lossy(&buffer[..received]).into_owned());
+        if lines.last().unwrap().contains("duration_ms") {
+            break;
+        }
+    }
+    assert!(lines.contains(&"parquet_consolidator.jobs:1|c".to_string()));
+    assert!(lines.contains(&"parquet_consolidator.input_files:2|c".to_string()));
+    assert!(lines.contains(&"parquet_consolidator.rows_written:20|c".to_string()));
+    
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("-i")
+        .arg(&input_dir)
+        .arg("-o")
+        .arg(temp_dir.path().join("unused.parquet"))
+        .arg("--statsd")
+        .arg("no-port")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Failed to resolve the statsd address no-port"));
+}
+
+#[test]
+fn test_cli_audit_log() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    let output_file = temp_dir.path().join("output.parquet");
+    let audit_log = temp_dir.path().join("runs.jsonl");
+    fs::create_dir(&input_dir).unwrap();
+    create_test_parquet_file(&input_dir.join("a.parquet"), 0, 10).unwrap();
+    create_test_parquet_file(&input_dir.join("b.parquet"), 10, 20).unwrap();
+    
+    for _ in 0..2 {
+        let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+        cmd.arg("-i")
+            .arg(&input_dir)
+            .arg("-o")
+            .arg(&output_file)
+            .arg("--audit-log")
+            .arg(&audit_log)
+            .assert()
+            .success();
+    }
+    
+    let contents = fs::read_to_string(&audit_log).unwrap();
+    let records: Vec<serde_json::Value> = contents.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0]["command"], "consolidate");
+    assert_eq!(records[0]["inputs"].as_array().unwrap().len(), 2);
+    assert_eq!(records[0]["outputs"][0]["path"], output_file.to_str().unwrap());
+    assert_eq!(records[0]["rows_written"], 20);
+    assert!(records[0]["replaced"].as_array().unwrap().is_empty());
+    // The second run overwrote the first run's output
+    assert_eq!(records[1]["replaced"][0]["xxh3"], records[0]["outputs"][0]["xxh3"]);
+    assert_eq!(records[1]["previous_hash"], records[0]["hash"]);
+    
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("audit")
+        .arg(&audit_log)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("2 records, hash chain intact"));
+    
+    fs::write(&audit_log, contents.replacen("\"rows_written\":20", "\"rows_written\":2", 1)).unwrap();
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("audit")
+        .arg(&audit_log)
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("Hash chain broken at line 1"));
+}
+
+#[test]
+fn test_cli_archive_inputs() {
+    use std::io::Write;
+
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    let staging = temp_dir.path().join("staging");
+    fs::create_dir_all(&input_dir).unwrap();
+    fs::create_dir_all(&staging).unwrap();
+    create_test_parquet_file(&input_dir.join("a.parquet"), 0, 10).unwrap();
+
+    let archive = input_dir.join("daily.zip");
+    let mut zip = zip::ZipWriter::new(fs::File::create(&archive).unwrap());
+    for (i, name) in ["part-0.parquet", "part-1.parquet"].iter().enumerate() {
+        let member = staging.join(name);
+        create_test_parquet_file(&member, 10 + i as i32 * 10, 20 + i as i32 * 10).unwrap();
+        zip.start_file(*name, zip::write::SimpleFileOptions::default()).unwrap();
+        zip.write_all(&fs::read(&member).unwrap()).unwrap();
+    }
+    zip.finish().unwrap();
+
+    // An archive given as the input
+    let output_file = temp_dir.path().join("from_archive.parquet");
+    Command::cargo_bin("parquet_consolidator").unwrap()
+        .arg("-i").arg(&archive)
+        .arg("-o").arg(&output_file)
+        .assert()
+        .success();
+    let reader = SerializedFileReader::new(fs::File::open(&output_file).unwrap()).unwrap();
+    assert_eq!(reader.metadata().file_metadata().num_rows(), 20);
+
+    // An archive found in the input directory
+    let output_file = temp_dir.path().join("from_dir.parquet");
+    Command::cargo_bin("parquet_consolidator").unwrap()
+        .arg("-i").arg(&input_dir)
+        .arg("-o").arg(&output_file)
+        .arg("-v")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("daily.zip/part-1.parquet"));
+    let reader = SerializedFileReader::new(fs::File::open(&output_file).unwrap()).unwrap();
+    assert_eq!(reader.metadata().file_metadata().num_rows(), 30);
+}
+
+#[test]
+fn test_cli_compressed_inputs() {
+    use std::io::Write;
+
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    fs::create_dir_all(&input_dir).unwrap();
+    create_test_parquet_file(&input_dir.join("a.parquet"), 0, 10).unwrap();
+
+    let staging = temp_dir.path().join("b.parquet");
+    create_test_parquet_file(&staging, 10, 25).unwrap();
+    let mut gzip = flate2::write::GzEncoder::new(fs::File::create(input_dir.join("b.parquet.gz")).unwrap(), flate2::Compression::default());
+    gzip.write_all(&fs::read(&staging).unwrap()).unwrap();
+    gzip.finish().unwrap();
+    fs::write(input_dir.join("c.parquet.zst"), zstd::encode_all(&fs::read(&staging).unwrap()[..], 3).unwrap()).unwrap();
+
+    let output_file = temp_dir.path().join("output.parquet");
+    Command::cargo_bin("parquet_consolidator").unwrap()
+        .arg("-i").arg(&input_dir)
+        .arg("-o").arg(&output_file)
+        .assert()
+        .success();
+    let reader = SerializedFileReader::new(fs::File::open(&output_file).unwrap()).unwrap();
+    assert_eq!(reader.metadata().file_metadata().num_rows(), 40);
+}
+
+#[test]
+fn test_cli_run_id_dirs() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    let output_dir = temp_dir.path().join("output");
+    create_test_directory_structure(&input_dir).unwrap();
+
+    let run = || {
+        let assert = Command::cargo_bin("parquet_consolidator").unwrap()
+            .arg("-i").arg(&input_dir)
+            .arg("-o").arg(&output_dir)
+            .arg("--run-id-dirs")
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("is now the latest"));
+        let latest = fs::read_to_string(output_dir.join("LATEST")).unwrap();
+        let run_dir = output_dir.join(latest.trim());
+        assert!(run_dir.join("part-00000.parquet").is_file());
+        assert!(String::from_utf8_lossy(&assert.get_output().stdout).contains(&latest.trim()[4..]));
+        run_dir
+    };
+
+    let first = run();
+    let second = run();
+    assert_ne!(first, second);
+    // Earlier runs stay readable
+    assert!(first.join("part-00000.parquet").is_file());
+    assert_eq!(parquet_consolidator::latest_run(&output_dir).unwrap(), Some(second));
+}
+
+#[test]
+fn test_cli_generate() {
+    let temp_dir = TempDir::new().unwrap();
+    let dataset = temp_dir.path().join("dataset");
+    let generate = |dir: &std::path::Path| {
+        Command::cargo_bin("parquet_consolidator").unwrap()
+            .arg("generate")
+            .arg("-o").arg(dir)
+            .arg("--files").arg("4")
+            .arg("--rows").arg("50")
+            .arg("--schema").arg("id:int64,label:string,at:timestamp")
+            .arg("--null-ratio").arg("0.1")
+            .arg("--seed").arg("7")
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("Generated 4 files of 50 rows"));
+    };
+    generate(&dataset);
+    assert!(dataset.join("nested/file4.parquet").is_file());
+
+    let copy = temp_dir.path().join("copy");
+    generate(&copy);
+    assert_eq!(fs::read(dataset.join("file1.parquet")).unwrap(), fs::read(copy.join("file1.parquet")).unwrap());
+
+    let output_file = temp_dir.path().join("output.parquet");
+    Command::cargo_bin("parquet_consolidator").unwrap()
+        .arg("-i").arg(&dataset)
+        .arg("-o").arg(&output_file)
+        .arg("-r")
+        .assert()
+        .success();
+    let reader = SerializedFileReader::new(fs::File::open(&output_file).unwrap()).unwrap();
+    assert_eq!(reader.metadata().file_metadata().num_rows(), 200);
+
+    Command::cargo_bin("parquet_consolidator").unwrap()
+        .arg("generate")
+        .arg("-o").arg(&dataset)
+        .arg("--schema").arg("id:uuid")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Unknown column type"));
+}
+
+#[test]
+fn test_cli_continue_on_error_abort_threshold() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    fs::create_dir_all(&input_dir).unwrap();
+    for i in 0..9 {
+        create_test_parquet_file(&input_dir.join(format!("part-{}.parquet", i)), i * 10, i * 10 + 10).unwrap();
+    }
+    fs::write(input_dir.join("truncated.parquet"), b"PAR1").unwrap();
+    let output_file = temp_dir.path().join("output.parquet");
+    let consolidate = |extra: &[&str]| {
+        Command::cargo_bin("parquet_consolidator").unwrap()
+            .arg("-i").arg(&input_dir)
+            .arg("-o").arg(&output_file)
+            .args(extra)
+            .assert()
+    };
+
+    consolidate(&[]).failure();
+    consolidate(&["--continue-on-error", "--abort-threshold", "10%"])
+        .success()
+        .stderr(predicate::str::contains("truncated.parquet"))
+        .stdout(predicate::str::contains("Skipped 1 unreadable files"));
+    let reader = SerializedFileReader::new(fs::File::open(&output_file).unwrap()).unwrap();
+    assert_eq!(reader.metadata().file_metadata().num_rows(), 90);
+
+    consolidate(&["--continue-on-error", "--abort-threshold", "5%"])
+        .failure()
+        .stderr(predicate::str::contains("Skipped 1 of 10 inputs, more than the abort threshold of 5%"));
+    consolidate(&["--abort-threshold", "1"]).failure();
+}
+
+#[test]
+fn test_cli_max_open_files() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    fs::create_dir_all(&input_dir).unwrap();
+    for i in 0..5 {
+        create_test_parquet_file(&input_dir.join(format!("part-{}.parquet", i)), i * 10, i * 10 + 10).unwrap();
+    }
+
+    let output_file = temp_dir.path().join("output.parquet");
+    Command::cargo_bin("parquet_consolidator").unwrap()
+        .arg("-i").arg(&input_dir)
+        .arg("-o").arg(&output_file)
+        .arg("--max-open-files").arg("2")
+        .arg("-v")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Reading 5 inputs in batches of 2"));
+    let reader = SerializedFileReader::new(fs::File::open(&output_file).unwrap()).unwrap();
+    assert_eq!(reader.metadata().file_metadata().num_rows(), 50);
+}
+
+#[test]
+fn test_cli_validate_stats() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    create_test_directory_structure(&input_dir).unwrap();
+
+    let output_file = temp_dir.path().join("output.parquet");
+    Command::cargo_bin("parquet_consolidator").unwrap()
+        .arg("-i").arg(&input_dir)
+        .arg("-o").arg(&output_file)
+        .arg("--validate-stats")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("stated").not());
+    Command::cargo_bin("parquet_consolidator").unwrap()
+        .arg("-i").arg(&input_dir)
+        .arg("-o").arg(&output_file)
+        .arg("--validate-stats").arg("1")
+        .assert()
+        .success();
+}
+
+#[test]
+fn test_cli_mirror_structure_jobs_per_cpu() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("events");
+    let output_dir = temp_dir.path().join("compacted");
+    for day in 1..=6 {
+        let partition = input_dir.join(format!("day={}", day));
+        fs::create_dir_all(&partition).unwrap();
+        create_test_parquet_file(&partition.join("a.parquet"), 0, day).unwrap();
+        create_test_parquet_file(&partition.join("b.parquet"), day, 2 * day).unwrap();
+    }
+
+    Command::cargo_bin("parquet_consolidator").unwrap()
+        .arg("-i").arg(&input_dir)
+        .arg("-o").arg(&output_dir)
+        .arg("-r")
+        .arg("--mirror-structure")
+        .arg("--jobs").arg("0")
+        .arg("--max-open-files").arg("4")
+        .assert()
+        .success();
+
+    for day in 1..=6 {
+        assert!(output_dir.join(format!("day={}/part-00000.parquet", day)).exists());
+    }
+}
+
+#[test]
+fn test_cli_auto_batch() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    create_test_directory_structure(&input_dir).unwrap();
+
+    let output_file = temp_dir.path().join("output.parquet");
+    let assert = Command::cargo_bin("parquet_consolidator").unwrap()
+        .arg("-i").arg(&input_dir)
+        .arg("-o").arg(&output_file)
+        .arg("--auto-batch")
+        .arg("--verbose")
+        .assert()
+        .success();
+    if cfg!(target_os = "linux") {
+        assert.stdout(predicate::str::contains("reading them in memory in one pass"));
+    }
+    assert!(output_file.exists());
+}
+
+#[test]
+fn test_cli_normalize_legacy_types() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("hive");
+    fs::create_dir_all(&input_dir).unwrap();
+    create_test_legacy_parquet_file(&input_dir.join("part-0.parquet"), 0, 5).unwrap();
+    create_test_legacy_parquet_file(&input_dir.join("part-1.parquet"), 5, 10).unwrap();
+    let output_file = temp_dir.path().join("output.parquet");
+
+    Command::cargo_bin("parquet_consolidator").unwrap()
+        .arg("-i").arg(&input_dir)
+        .arg("-o").arg(&output_file)
+        .arg("--normalize-legacy-types")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("into 10 rows"));
+
+    let reader = SerializedFileReader::new(fs::File::open(&output_file).unwrap()).unwrap();
+    let schema = reader.metadata().file_metadata().schema_descr_ptr();
+    let column = |name: &str| schema.columns().iter().find(|column| column.name() == name).unwrap().clone();
+    assert_eq!(column("day").logical_type(), Some(parquet::basic::LogicalType::Date));
+    assert_eq!(column("status").logical_type(), Some(parquet::basic::LogicalType::String));
+    assert_eq!(column("flags").physical_type(), parquet::basic::Type::INT32);
+    for (name, precision, scale) in [("amount", 10, 2), ("total", 30, 4)] {
+        assert_eq!(column(name).converted_type(), parquet::basic::ConvertedType::DECIMAL);
+        assert_eq!((column(name).type_precision(), column(name).type_scale()), (precision, scale));
+    }
+}
+
+#[test]
+fn test_cli_column_regexes() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    create_test_directory_structure(&input_dir).unwrap();
+    let output_file = temp_dir.path().join("output.parquet");
+
+    Command::cargo_bin("parquet_consolidator").unwrap()
+        .arg("-i").arg(&input_dir)
+        .arg("-o").arg(&output_file)
+        .arg("--columns-regex").arg("^(id|name|value)$")
+        .arg("--drop-columns-regex").arg("^val")
+        .assert()
+        .success();
+
+    let reader = SerializedFileReader::new(fs::File::open(&output_file).unwrap()).unwrap();
+    let schema = reader.metadata().file_metadata().schema_descr_ptr();
+    let names: Vec<&str> = schema.columns().iter().map(|column| column.name()).collect();
+    assert_eq!(names, ["id", "name"]);
+
+    Command::cargo_bin("parquet_consolidator").unwrap()
+        .arg("-i").arg(&input_dir)
+        .arg("-o").arg(&output_file)
+        .arg("--columns-regex").arg("^metric_")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("match the column selection"));
+
+    Command::cargo_bin("parquet_consolidator").unwrap()
+        .arg("-i").arg(&input_dir)
+        .arg("-o").arg(&output_file)
+        .arg("--drop-columns-regex").arg("(")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("regex"));
+}
+
+#[test]
+fn test_cli_pipeline() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_data_dir = temp_dir.path().join("test_data");
+    let output_file = temp_dir.path().join("output.parquet");
+
+    create_test_directory_structure(&test_data_dir).unwrap();
+
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("-i")
+        .arg(&test_data_dir)
+        .arg("-o")
+        .arg(&output_file)
+        .arg("--recursive")
+        .arg("--pipeline")
+        .arg("--pipeline-readers")
+        .arg("2")
+        .arg("-v")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Pipelining 5 row groups of 5 inputs through 2 readers"));
+
+    let reader = SerializedFileReader::new(fs::File::open(&output_file).unwrap()).unwrap();
+    assert_eq!(reader.metadata().file_metadata().num_rows(), 500);
+
+    // Deduplication needs every row at once
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("-i")
+        .arg(&test_data_dir)
+        .arg("-o")
+        .arg(&output_file)
+        .arg("--recursive")
+        .arg("--pipeline")
+        .arg("--dedupe-key")
+        .arg("id")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Deduplication cannot be combined with a pipelined run"));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_cli_named_pipe_output() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_data_dir = temp_dir.path().join("test_data");
+    let pipe = temp_dir.path().join("output.fifo");
+
+    create_test_directory_structure(&test_data_dir).unwrap();
+    assert!(std::process::Command::new("mkfifo").arg(&pipe).status().unwrap().success());
+
+    // Read the stream live, as another process on the host would
+    let reader = {
+        let pipe = pipe.clone();
+        std::thread::spawn(move || fs::read(pipe).unwrap())
+    };
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("-i")
+        .arg(&test_data_dir)
+        .arg("-o")
+        .arg(&pipe)
+        .arg("--recursive")
+        .arg("--if-exists")
+        .arg("skip")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Consolidated 5 files into 500 rows"));
+
+    let streamed = temp_dir.path().join("streamed.parquet");
+    fs::write(&streamed, reader.join().unwrap()).unwrap();
+    let reader = SerializedFileReader::new(fs::File::open(&streamed).unwrap()).unwrap();
+    assert_eq!(reader.metadata().file_metadata().num_rows(), 500);
+
+    // Nothing can read the output back from the pipe
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("-i")
+        .arg(&test_data_dir)
+        .arg("-o")
+        .arg(&pipe)
+        .arg("--recursive")
+        .arg("--max-rows-per-file")
+        .arg("100")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--max-rows-per-file cannot be combined with writing to the named pipe"));
+}
+
+#[test]
+fn test_cli_field_metadata_conflicts() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    let output_file = temp_dir.path().join("output.parquet");
+    fs::create_dir_all(&input_dir).unwrap();
+    for (name, description) in [("a.parquet", "Row id"), ("b.parquet", "Identifier")] {
+        let metadata = std::collections::HashMap::from([("description".to_string(), description.to_string())]);
+        let schema = std::sync::Arc::new(Schema::new(vec![Field::new("id", DataType::Int64, false).with_metadata(metadata)]));
+        create_random_parquet_file(&input_dir.join(name), &schema, 10, 0.0, 1).unwrap();
+    }
+
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("-i")
+        .arg(&input_dir)
+        .arg("-o")
+        .arg(&output_file)
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "Warning: Inputs give metadata \"description\" of column id different values: \"Row id\", \"Identifier\"; the first is written",
+        ));
+
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("-i")
+        .arg(&input_dir)
+        .arg("-o")
+        .arg(&output_file)
+        .arg("--field-metadata-conflicts")
+        .arg("error")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("use another field metadata policy"));
+}
+
+#[test]
+fn test_cli_check_schemas_first() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    let output_file = temp_dir.path().join("output.parquet");
+    fs::create_dir_all(&input_dir).unwrap();
+    create_test_parquet_file(&input_dir.join("a.parquet"), 0, 10).unwrap();
+    create_test_parquet_file(&input_dir.join("b.parquet"), 10, 20).unwrap();
+
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("-i")
+        .arg(&input_dir)
+        .arg("-o")
+        .arg(&output_file)
+        .arg("--check-schemas-first")
+        .arg("-v")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Checked the schemas of 2 inputs"));
+
+    // A file with other columns fails the run before anything is read or written
+    fs::remove_file(&output_file).unwrap();
+    let schema = std::sync::Arc::new(Schema::new(vec![Field::new("id", DataType::Int64, false)]));
+    create_random_parquet_file(&input_dir.join("c.parquet"), &schema, 10, 0.0, 1).unwrap();
+    let mut cmd = Command::cargo_bin("parquet_consolidator").unwrap();
+    cmd.arg("-i")
+        .arg(&input_dir)
+        .arg("-o")
+        .arg(&output_file)
+        .arg("--check-schemas-first")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Schema check failed before any data was read"))
+        .stderr(predicate::str::contains("c.parquet\" has 1 columns, where the inputs before it have 3"));
+    assert!(!output_file.exists());
+}
+
+#[test]
+fn test_cli_max_runtime() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    fs::create_dir_all(&input_dir).unwrap();
+    for i in 0..5 {
+        create_test_parquet_file(&input_dir.join(format!("part-{}.parquet", i)), i * 10, i * 10 + 10).unwrap();
+    }
+
+    // The first batch is always read; the budget is spent before the second
+    let output_file = temp_dir.path().join("output.parquet");
+    let remaining = temp_dir.path().join("output.parquet.remaining");
+    Command::cargo_bin("parquet_consolidator").unwrap()
+        .arg("-i").arg(&input_dir)
+        .arg("-o").arg(&output_file)
+        .arg("--max-open-files").arg("2")
+        .arg("--max-runtime").arg("0s")
+        .assert()
+        .code(3)
+        .stdout(predicate::str::contains("Left 3 files unread when the deadline passed"))
+        .stderr(predicate::str::contains("the runtime budget of 0s ran out; 3 inputs were left unread"));
+    let reader = SerializedFileReader::new(fs::File::open(&output_file).unwrap()).unwrap();
+    assert_eq!(reader.metadata().file_metadata().num_rows(), 20);
+    let listed = fs::read_to_string(&remaining).unwrap();
+    assert_eq!(listed.lines().count(), 3);
+    assert!(listed.lines().next().unwrap().ends_with("part-2.parquet"));
+
+    // The next run picks up the rest and clears the list
+    let rest_file = temp_dir.path().join("rest.parquet");
+    Command::cargo_bin("parquet_consolidator").unwrap()
+        .arg("--files-from").arg(&remaining)
+        .arg("-o").arg(&rest_file)
+        .arg("--max-runtime").arg("1h")
+        .arg("--remaining-inputs").arg(&remaining)
+        .assert()
+        .success();
+    let reader = SerializedFileReader::new(fs::File::open(&rest_file).unwrap()).unwrap();
+    assert_eq!(reader.metadata().file_metadata().num_rows(), 30);
+    assert!(!remaining.exists());
+}
+
+#[test]
+fn test_cli_skip_duplicate_inputs() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    fs::create_dir_all(&input_dir).unwrap();
+    create_test_parquet_file(&input_dir.join("a.parquet"), 0, 10).unwrap();
+    create_test_parquet_file(&input_dir.join("b.parquet"), 10, 30).unwrap();
+    // A retried upload left a second copy of a
+    fs::copy(input_dir.join("a.parquet"), input_dir.join("a_retry.parquet")).unwrap();
+
+    let output_file = temp_dir.path().join("output.parquet");
+    Command::cargo_bin("parquet_consolidator").unwrap()
+        .arg("-i").arg(&input_dir)
+        .arg("-o").arg(&output_file)
+        .arg("--skip-duplicate-inputs")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("a_retry.parquet\" into"))
+        .stdout(predicate::str::contains("Consolidated 2 files into 30 rows"));
+    let reader = SerializedFileReader::new(fs::File::open(&output_file).unwrap()).unwrap();
+    assert_eq!(reader.metadata().file_metadata().num_rows(), 30);
+}
+
+#[test]
+fn test_cli_fill_missing() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    fs::create_dir_all(&input_dir).unwrap();
+    create_test_parquet_file(&input_dir.join("a.parquet"), 0, 10).unwrap();
+    let schema = std::sync::Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("status", DataType::Utf8, false),
+    ]));
+    create_random_parquet_file(&input_dir.join("b.parquet"), &schema, 5, 0.0, 1).unwrap();
+
+    let output_file = temp_dir.path().join("output.parquet");
+    Command::cargo_bin("parquet_consolidator").unwrap()
+        .arg("-i").arg(&input_dir)
+        .arg("-o").arg(&output_file)
+        .arg("--union-schemas")
+        .arg("--fill-missing").arg("status=unknown,value=0")
+        .assert()
+        .success();
+
+    // Columns id, name, value, status; only the rows of a lack a status
+    let reader = SerializedFileReader::new(fs::File::open(&output_file).unwrap()).unwrap();
+    let statuses: Vec<String> = reader
+        .get_row_iter(None)
+        .unwrap()
+        .map(|row| row.unwrap().get_string(3).unwrap().clone())
+        .collect();
+    assert_eq!(statuses.iter().filter(|status| *status == "unknown").count(), 10);
+    assert_eq!(statuses.len(), 15);
+
+    Command::cargo_bin("parquet_consolidator").unwrap()
+        .arg("-i").arg(&input_dir)
+        .arg("-o").arg(&output_file)
+        .arg("--fill-missing").arg("status=unknown")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--union-schemas"));
+}
+
+#[test]
+fn test_cli_audit_output_manifest() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    create_test_directory_structure(&input_dir).unwrap();
+    let output_file = temp_dir.path().join("output.parquet");
+    let manifest = temp_dir.path().join("manifest.json");
+
+    Command::cargo_bin("parquet_consolidator").unwrap()
+        .arg("-i").arg(&input_dir)
+        .arg("-o").arg(&output_file)
+        .arg("-r")
+        .arg("--manifest").arg(&manifest)
+        .assert()
+        .success();
+    Command::cargo_bin("parquet_consolidator").unwrap()
+        .arg("audit")
+        .arg("--output").arg(&output_file)
+        .arg("--manifest").arg(&manifest)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("matches its manifest"));
+
+    // A truncated output no longer matches
+    let bytes = fs::read(&output_file).unwrap();
+    fs::write(&output_file, &bytes[..bytes.len() / 2]).unwrap();
+    Command::cargo_bin("parquet_consolidator").unwrap()
+        .arg("audit")
+        .arg("--output").arg(&output_file)
+        .arg("--manifest").arg(&manifest)
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("does not match its manifest"))
+        .stderr(predicate::str::contains("failed verification"));
+}
+
+#[test]
+fn test_cli_output_in_input() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    fs::create_dir_all(&input_dir).unwrap();
+    create_test_parquet_file(&input_dir.join("a.parquet"), 0, 10).unwrap();
+    create_test_parquet_file(&input_dir.join("b.parquet"), 10, 30).unwrap();
+    let output_file = input_dir.join("merged").join("output.parquet");
+    fs::create_dir_all(output_file.parent().unwrap()).unwrap();
+
+    Command::cargo_bin("parquet_consolidator").unwrap()
+        .arg("-i").arg(&input_dir)
+        .arg("-o").arg(&output_file)
+        .arg("--recursive")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("is inside the input directory"));
+    assert!(!output_file.exists());
+
+    // Run twice, as a schedule would; the second run doesn't read the first's output
+    for _ in 0..2 {
+        Command::cargo_bin("parquet_consolidator").unwrap()
+            .arg("-i").arg(&input_dir)
+            .arg("-o").arg(&output_file)
+            .arg("--recursive")
+            .arg("--output-in-input").arg("exclude")
+            .arg("--if-exists").arg("overwrite")
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("Consolidated 2 files into 30 rows"));
+    }
+    let reader = SerializedFileReader::new(fs::File::open(&output_file).unwrap()).unwrap();
+    assert_eq!(reader.metadata().file_metadata().num_rows(), 30);
+}
+
+#[test]
+fn test_cli_compression_auto() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    fs::create_dir_all(&input_dir).unwrap();
+    create_test_parquet_file(&input_dir.join("a.parquet"), 0, 2000).unwrap();
+
+    let output_file = temp_dir.path().join("output.parquet");
+    Command::cargo_bin("parquet_consolidator").unwrap()
+        .arg("-i").arg(&input_dir)
+        .arg("-o").arg(&output_file)
+        .arg("--compression").arg("auto")
+        .arg("--compression-per-column").arg("name=gzip")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Compression chosen per column:\n  id: "))
+        .stdout(predicate::str::contains("  name: ").not());
+    let reader = SerializedFileReader::new(fs::File::open(&output_file).unwrap()).unwrap();
+    assert_eq!(reader.metadata().row_group(0).column(1).compression().to_string(), "GZIP(GzipLevel(6))");
+
+    Command::cargo_bin("parquet_consolidator").unwrap()
+        .arg("-i").arg(&input_dir)
+        .arg("-o").arg(&output_file)
+        .arg("--if-exists").arg("overwrite")
+        .arg("--compression").arg("zstd:3")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Compression chosen").not());
+    let reader = SerializedFileReader::new(fs::File::open(&output_file).unwrap()).unwrap();
+    assert!(reader.metadata().row_group(0).column(0).compression().to_string().starts_with("ZSTD"));
+}
+
+#[test]
+fn test_cli_content_digest() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    fs::create_dir_all(&input_dir).unwrap();
+    create_test_parquet_file(&input_dir.join("a.parquet"), 0, 10).unwrap();
+    create_test_parquet_file(&input_dir.join("b.parquet"), 10, 30).unwrap();
+
+    let digest_of = |output: &str, extra: &[&str]| {
+        let assert = Command::cargo_bin("parquet_consolidator").unwrap()
+            .arg("-i").arg(&input_dir)
+            .arg("-o").arg(temp_dir.path().join(output))
+            .arg("--content-digest")
+            .args(extra)
+            .assert()
+            .success();
+        let stdout = String::from_utf8(assert.get_output().stdout.clone()).unwrap();
+        stdout.lines().find_map(|line| line.strip_prefix("Content digest: ")).unwrap().to_string()
+    };
+    let digest = digest_of("one.parquet", &[]);
+    assert_eq!(digest.len(), 32);
+    assert_eq!(digest_of("split.parquet", &["--max-row-group-rows", "7", "--max-rows-per-file", "12"]), digest);
+}
+
+#[test]
+fn test_cli_row_groups() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    fs::create_dir_all(&input_dir).unwrap();
+    let big = input_dir.join("big.parquet");
+    create_test_parquet_file(&big, 0, 100).unwrap();
+    create_test_parquet_file(&input_dir.join("small.parquet"), 100, 110).unwrap();
+    let output_file = temp_dir.path().join("output.parquet");
+
+    // Split the big input into row groups of 10 rows first
+    Command::cargo_bin("parquet_consolidator").unwrap()
+        .arg("-i").arg(&big)
+        .arg("-o").arg(temp_dir.path().join("chunked.parquet"))
+        .arg("--max-row-group-rows").arg("10")
+        .assert()
+        .success();
+    fs::rename(temp_dir.path().join("chunked.parquet"), &big).unwrap();
+
+    Command::cargo_bin("parquet_consolidator").unwrap()
+        .arg("-i").arg(&input_dir)
+        .arg("-o").arg(&output_file)
+        .arg("--row-groups").arg(format!("{}:0-1", big.display()))
+        .arg("--row-groups").arg(format!("{}:5", big.display()))
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Consolidated 2 files into 40 rows"));
+
+    Command::cargo_bin("parquet_consolidator").unwrap()
+        .arg("-i").arg(&input_dir)
+        .arg("-o").arg(&output_file)
+        .arg("--if-exists").arg("overwrite")
+        .arg("--row-groups").arg(format!("{}:5-12", big.display()))
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("which has 10"));
+}
+
+#[test]
+fn test_cli_filename_date_range() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("input");
+    fs::create_dir_all(&input_dir).unwrap();
+    for (i, name) in ["events_20240531", "events_20240601", "events_20240630", "events_20240701", "events_latest"].iter().enumerate() {
+        create_test_parquet_file(&input_dir.join(format!("{}.parquet", name)), i as i32 * 10, i as i32 * 10 + 10).unwrap();
+    }
+    let output_file = temp_dir.path().join("june.parquet");
+
+    Command::cargo_bin("parquet_consolidator").unwrap()
+        .arg("-i").arg(&input_dir)
+        .arg("-o").arg(&output_file)
+        .arg("--filename-date-pattern").arg("events_%Y%m%d")
+        .arg("--date-range").arg("2024-06-01..2024-06-30")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Consolidated 2 files into 20 rows"));
+
+    Command::cargo_bin("parquet_consolidator").unwrap()
+        .arg("-i").arg(&input_dir)
+        .arg("-o").arg(&output_file)
+        .arg("--date-range").arg("2024-06-01..")
+        .assert()
+        .failure();
+}
@@ -1,7 +1,7 @@
 use proptest::prelude::*;
 use tempfile::TempDir;
 use parquet_consolidator::test_utils::*;
-use parquet_consolidator::{find_parquet_files, consolidate_parquet_files, is_parquet_file};
+use parquet_consolidator::{find_parquet_files, consolidate_parquet_files, is_parquet_file, DECIMAL_ACTIVATION_VAR};
 use polars::prelude::*;
 use std::path::PathBuf;
 
@@ -54,6 +54,69 @@ proptest! {
         prop_assert_eq!(df.height(), total_expected_records as usize);
     }
 
+    #[test]
+    fn test_consolidation_preserves_random_typed_rows(
+        seed in any::<u64>(),
+        num_files in 1usize..=4,
+        rows in 0usize..=200,
+        null_ratio in 0.0f64..=0.5
+    ) {
+        use arrow::array::{Array, RecordBatch};
+        use arrow::compute::concat_batches;
+        use arrow::datatypes::{DataType, Field, Schema, TimeUnit};
+        use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+        use std::sync::Arc;
+
+        // Struct columns need a polars feature this build leaves out
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("day", DataType::Date32, true),
+            Field::new("at", DataType::Timestamp(TimeUnit::Microsecond, None), true),
+            Field::new("amount", DataType::Decimal128(10, 3), true),
+            Field::new("scores", DataType::List(Arc::new(Field::new("item", DataType::Float64, true))), true),
+        ]));
+        let temp_dir = TempDir::new().unwrap();
+        let input_files: Vec<PathBuf> = (0..num_files)
+            .map(|i| {
+                let path = temp_dir.path().join(format!("file_{}.parquet", i));
+                create_random_parquet_file(&path, &schema, rows, null_ratio, seed.wrapping_add(i as u64)).unwrap();
+                path
+            })
+            .collect();
+        let output_file = temp_dir.path().join("output.parquet");
+
+        // Keep decimals as decimals rather than floats, as
+        // --normalize-legacy-types does
+        std::env::set_var(DECIMAL_ACTIVATION_VAR, "1");
+        consolidate_parquet_files(&input_files, &output_file, false).unwrap();
+
+        let df = LazyFrame::scan_parquet(&output_file, Default::default())?.collect()?;
+        prop_assert_eq!(df.height(), rows * num_files);
+        prop_assert_eq!(df.width(), schema.fields().len());
+
+        // Every input row comes out in order, with its type and value
+        let read_all = |path: &PathBuf| -> RecordBatch {
+            let builder = ParquetRecordBatchReaderBuilder::try_new(std::fs::File::open(path).unwrap()).unwrap();
+            let schema = builder.schema().clone();
+            let batches = builder.build().unwrap().collect::<Result<Vec<_>, _>>().unwrap();
+            concat_batches(&schema, &batches).unwrap()
+        };
+        let inputs: Vec<RecordBatch> = input_files.iter().map(read_all).collect();
+        let expected = concat_batches(&inputs[0].schema(), &inputs).unwrap();
+        let output = read_all(&output_file);
+        for (field, column) in schema.fields().iter().zip(output.columns()) {
+            // polars writes lists with 64-bit offsets
+            let expected = expected.column_by_name(field.name()).unwrap();
+            let expected_type = match expected.data_type() {
+                DataType::List(item) => DataType::LargeList(item.clone()),
+                other => other.clone(),
+            };
+            prop_assert_eq!(column.data_type(), &expected_type, "column {} changed type", field.name());
+            let expected = arrow::compute::cast(expected, &expected_type).unwrap();
+            prop_assert_eq!(column.to_data(), expected.to_data(), "column {} changed values", field.name());
+        }
+    }
+
     #[test]
     fn test_find_files_respects_recursive_flag(
         depth in 1usize..=3,